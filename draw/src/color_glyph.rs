@@ -0,0 +1,447 @@
+//! Color glyphs, either built from COLRv0 layers colored by a CPAL palette, or
+//! taken from embedded bitmap strikes (`sbix`, `CBDT`). These are rasterized to
+//! premultiplied RGBA, and end up in the color atlas of `CxFontsAtlas` instead
+//! of the single channel SDF atlas.
+
+use {
+    makepad_rustybuzz::ttf_parser::{Face, GlyphId, RasterGlyphImage, RasterImageFormat, Tag},
+    makepad_vector::{
+        font::Glyph,
+        geometry::{AffineTransformation, Point, Rectangle, Transform, Vector},
+        path::PathCommand,
+    },
+    makepad_zune_png::PngDecoder,
+};
+
+// The palette index COLR uses for "the current text color".
+const FOREGROUND_PALETTE_INDEX: u16 = 0xFFFF;
+
+pub struct ColrTable {
+    // (base glyph id, first layer index, number of layers), sorted by glyph id
+    base_glyphs: Vec<(u16, u16, u16)>,
+    // (layer glyph id, palette index)
+    layers: Vec<(u16, u16)>,
+    // the first CPAL palette, as straight RGBA
+    palette: Vec<[u8; 4]>,
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+impl ColrTable {
+    pub fn parse(face: &Face) -> Option<Self> {
+        let colr = face.raw_face().table(Tag::from_bytes(b"COLR"))?;
+        let num_base_glyphs = read_u16(colr, 2)? as usize;
+        let base_glyphs_offset = read_u32(colr, 4)? as usize;
+        let layers_offset = read_u32(colr, 8)? as usize;
+        let num_layers = read_u16(colr, 12)? as usize;
+
+        let mut base_glyphs = Vec::with_capacity(num_base_glyphs);
+        for i in 0..num_base_glyphs {
+            let record = base_glyphs_offset + i * 6;
+            base_glyphs.push((read_u16(colr, record)?, read_u16(colr, record + 2)?, read_u16(colr, record + 4)?));
+        }
+        base_glyphs.sort_by_key( | record | record.0);
+
+        let mut layers = Vec::with_capacity(num_layers);
+        for i in 0..num_layers {
+            let record = layers_offset + i * 4;
+            layers.push((read_u16(colr, record)?, read_u16(colr, record + 2)?));
+        }
+
+        let mut palette = Vec::new();
+        if let Some(cpal) = face.raw_face().table(Tag::from_bytes(b"CPAL")) {
+            let num_entries = read_u16(cpal, 2)? as usize;
+            let records_offset = read_u32(cpal, 8)? as usize;
+            let first_record = read_u16(cpal, 12)? as usize;
+            for i in 0..num_entries {
+                let bgra = cpal.get(records_offset + (first_record + i) * 4..)?.get(..4)?;
+                palette.push([bgra[2], bgra[1], bgra[0], bgra[3]]);
+            }
+        }
+
+        if base_glyphs.is_empty() {
+            return None
+        }
+        Some(Self {base_glyphs, layers, palette})
+    }
+
+    // Returns the layers of a color glyph, bottom first, with their straight RGBA color.
+    // None as color means the layer should be drawn in the text color.
+    pub fn layers(&self, glyph_id: usize) -> Option<Vec<(usize, Option<[u8; 4]>)>> {
+        let index = self.base_glyphs.binary_search_by_key(&glyph_id, | record | record.0 as usize).ok()?;
+        let (_, first_layer, num_layers) = self.base_glyphs[index];
+        let layers = self.layers.get(first_layer as usize..first_layer as usize + num_layers as usize)?;
+        Some(layers.iter().map( | &(layer_glyph_id, palette_index) | {
+            let color = if palette_index == FOREGROUND_PALETTE_INDEX {
+                None
+            }
+            else {
+                self.palette.get(palette_index as usize).copied()
+            };
+            (layer_glyph_id as usize, color)
+        }).collect())
+    }
+
+    // Whether a color glyph has layers in the text color, these can't be baked into the atlas.
+    pub fn has_foreground_layers(&self, glyph_id: usize) -> bool {
+        self.layers(glyph_id).is_some_and( | layers | layers.iter().any( | (_, color) | color.is_none()))
+    }
+}
+
+// Returns the bitmap strike image of a glyph, if it has one in a format we can draw in color.
+pub fn raster_image<'a>(face: &'a Face, glyph_id: usize, pixels_per_em: f64) -> Option<RasterGlyphImage<'a>> {
+    let pixels_per_em = pixels_per_em.round().clamp(1.0, u16::MAX as f64) as u16;
+    let image = face.glyph_raster_image(GlyphId(glyph_id as u16), pixels_per_em)?;
+    match image.format {
+        RasterImageFormat::PNG | RasterImageFormat::BitmapPremulBgra32 if image.width > 0 && image.height > 0 => Some(image),
+        _ => None
+    }
+}
+
+// The bounds of a bitmap strike image, in font units.
+pub fn raster_image_bounds(image: &RasterGlyphImage, units_per_em: f64) -> Rectangle {
+    let scale = units_per_em / image.pixels_per_em as f64;
+    Rectangle::new(
+        Point::new(image.x as f64 * scale, image.y as f64 * scale),
+        Point::new((image.x as f64 + image.width as f64) * scale, (image.y as f64 + image.height as f64) * scale),
+    )
+}
+
+// A premultiplied RGBA raster, with row 0 at the bottom of the glyph. Layers in the text color
+// leave their color out of `pixels` and add their visible coverage to `tint` instead, so the
+// final color is `pixels + tint * text color`.
+pub struct ColorRaster {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<[f32; 4]>,
+    pub tint: Vec<f32>,
+}
+
+impl ColorRaster {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {width, height, pixels: vec![[0.0; 4]; width * height], tint: vec![0.0; width * height]}
+    }
+
+    // Packs a pixel for a VecBGRAu8_32 texture.
+    pub fn packed(&self, x: usize, y: usize) -> u32 {
+        let [r, g, b, a] = self.pixels[y * self.width + x].map( | c | (c.clamp(0.0, 1.0) * 255.0).round() as u32);
+        (a << 24) | (r << 16) | (g << 8) | b
+    }
+
+    // Packs the tint of a pixel as gray for a VecBGRAu8_32 texture.
+    pub fn packed_tint(&self, x: usize, y: usize) -> u32 {
+        (self.tint[y * self.width + x].clamp(0.0, 1.0) * 255.0).round() as u32 * 0x01010101
+    }
+
+    // Composites one COLR layer over the raster, using the same transform as the outline
+    // rasterizer of the SDF atlas (font units in, raster pixels out). A layer without a color
+    // is in the text color, which is taken as opaque as its alpha applies to the whole glyph.
+    pub fn draw_layer(&mut self, glyph: &Glyph, transform: &AffineTransformation, color: Option<[u8; 4]>) {
+        let is_foreground = color.is_none();
+        let [r, g, b, a] = color.unwrap_or([0, 0, 0, 255]).map( | c | c as f32 / 255.0);
+        let mut cur = ab_glyph_rasterizer::point(0.0, 0.0);
+        let to_ab = | p: Point | ab_glyph_rasterizer::point(p.x as f32, p.y as f32);
+        let mut rasterizer = ab_glyph_rasterizer::Rasterizer::new(self.width, self.height);
+        for command in glyph.outline.iter().map( | command | command.transform(transform)) {
+            match command {
+                PathCommand::MoveTo(p) => {
+                    cur = to_ab(p);
+                }
+                PathCommand::LineTo(p1) => {
+                    let p1 = to_ab(p1);
+                    rasterizer.draw_line(cur, p1);
+                    cur = p1;
+                }
+                PathCommand::QuadraticTo(p1, p2) => {
+                    let (p1, p2) = (to_ab(p1), to_ab(p2));
+                    rasterizer.draw_quad(cur, p1, p2);
+                    cur = p2;
+                }
+                PathCommand::CubicTo(p1, p2, p3) => {
+                    let (p1, p2, p3) = (to_ab(p1), to_ab(p2), to_ab(p3));
+                    rasterizer.draw_cubic(cur, p1, p2, p3);
+                    cur = p3;
+                }
+                PathCommand::ArcTo(..) => {
+                    unreachable!("font glyphs should not use arcs");
+                }
+                PathCommand::Close => ()
+            }
+        }
+        let width = self.width;
+        let pixels = &mut self.pixels;
+        let tint = &mut self.tint;
+        rasterizer.for_each_pixel_2d( | x, y, coverage | {
+            let alpha = coverage.min(1.0) * a;
+            let index = y as usize * width + x as usize;
+            let dst = &mut pixels[index];
+            let keep = 1.0 - alpha;
+            *dst = [r * alpha + dst[0] * keep, g * alpha + dst[1] * keep, b * alpha + dst[2] * keep, alpha + dst[3] * keep];
+            tint[index] = if is_foreground {alpha} else {0.0} + tint[index] * keep;
+        });
+    }
+
+    // Scales a bitmap strike image into the raster, leaving `pad` pixels of border.
+    pub fn draw_image(&mut self, image: &RasterGlyphImage, pad: usize) -> Option<()> {
+        let (src_width, src_height, src) = decode_raster_image(image)?;
+        let inner_width = self.width.checked_sub(pad * 2).filter( | w | *w > 0)?;
+        let inner_height = self.height.checked_sub(pad * 2).filter( | h | *h > 0)?;
+        for y in 0..inner_height {
+            // the raster is bottom up, the image is top down
+            let sy0 = (inner_height - 1 - y) * src_height / inner_height;
+            let sy1 = ((inner_height - y) * src_height / inner_height).max(sy0 + 1);
+            for x in 0..inner_width {
+                let sx0 = x * src_width / inner_width;
+                let sx1 = ((x + 1) * src_width / inner_width).max(sx0 + 1);
+                // box filter over the source pixels covered by this one
+                let mut sum = [0.0; 4];
+                for sy in sy0..sy1 {
+                    for sx in sx0..sx1 {
+                        let pixel = src[sy * src_width + sx];
+                        for i in 0..4 {
+                            sum[i] += pixel[i];
+                        }
+                    }
+                }
+                let count = ((sy1 - sy0) * (sx1 - sx0)) as f32;
+                self.pixels[(y + pad) * self.width + x + pad] = sum.map( | c | c / count);
+            }
+        }
+        Some(())
+    }
+}
+
+// Decodes a bitmap strike image into premultiplied RGBA, top row first.
+fn decode_raster_image(image: &RasterGlyphImage) -> Option<(usize, usize, Vec<[f32; 4]>)> {
+    match image.format {
+        RasterImageFormat::PNG => {
+            let mut decoder = PngDecoder::new(image.data);
+            let data = decoder.decode().ok()?.u8()?;
+            let (width, height) = decoder.get_dimensions()?;
+            let pixels = width * height;
+            if pixels == 0 {
+                return None
+            }
+            let channels = data.len() / pixels;
+            let mut out = Vec::with_capacity(pixels);
+            for i in 0..pixels {
+                let p = &data[i * channels..(i + 1) * channels];
+                let [r, g, b, a] = match channels {
+                    4 => [p[0], p[1], p[2], p[3]],
+                    3 => [p[0], p[1], p[2], 255],
+                    2 => [p[0], p[0], p[0], p[1]],
+                    1 => [p[0], p[0], p[0], 255],
+                    _ => return None
+                }.map( | c | c as f32 / 255.0);
+                out.push([r * a, g * a, b * a, a]);
+            }
+            Some((width, height, out))
+        }
+        RasterImageFormat::BitmapPremulBgra32 => {
+            let (width, height) = (image.width as usize, image.height as usize);
+            let data = image.data.get(..width * height * 4)?;
+            let out = data.chunks_exact(4).map( | p | [p[2], p[1], p[0], p[3]].map( | c | c as f32 / 255.0)).collect();
+            Some((width, height, out))
+        }
+        _ => None
+    }
+}
+
+// The pixels per font unit to rasterize a color glyph at. That is the size it's drawn at, unless
+// its raster with `pad` pixels of border wouldn't fit in max_width by max_height, then it is
+// rasterized smaller and stretched when drawn. Glyphs with a tint mask need twice the width.
+pub fn fit_glyph_scale(bounds: &Rectangle, pixels_per_unit: f64, pad: usize, is_tinted: bool, max_width: usize, max_height: usize) -> f64 {
+    let max_width = if is_tinted {max_width / 2} else {max_width};
+    // the raster size is rounded up, so leave a pixel for that
+    let fit = | size: f64, max: usize | if size > 0.0 {(max as f64 - (pad * 2 + 1) as f64).max(1.0) / size} else {f64::INFINITY};
+    pixels_per_unit
+        .min(fit(bounds.p_max.x - bounds.p_min.x, max_width))
+        .min(fit(bounds.p_max.y - bounds.p_min.y, max_height))
+}
+
+// The transform from font units to raster pixels, with the glyph bounds starting at `pad`.
+pub fn glyph_transform(bounds: &Rectangle, font_scale_pixels: f64, pad: f64) -> AffineTransformation {
+    AffineTransformation::identity()
+        .translate(Vector::new(-bounds.p_min.x, -bounds.p_min.y))
+        .uniform_scale(font_scale_pixels)
+        .translate(Vector::new(pad, pad))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use makepad_vector::font::HorizontalMetrics;
+
+    // a 2x2 RGBA png, red and green on top, blue and transparent white below
+    const PNG: [u8; 76] = [
+        0x89, 0x50, 0x4e, 0x47, 0x0d, 0x0a, 0x1a, 0x0a, 0x00, 0x00, 0x00, 0x0d, 0x49, 0x48, 0x44, 0x52,
+        0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x08, 0x06, 0x00, 0x00, 0x00, 0x72, 0xb6, 0x0d,
+        0x24, 0x00, 0x00, 0x00, 0x13, 0x49, 0x44, 0x41, 0x54, 0x78, 0x9c, 0x63, 0xf8, 0xcf, 0xc0, 0xf0,
+        0x1f, 0x0c, 0x81, 0x34, 0x08, 0x30, 0x00, 0x00, 0x48, 0xc9, 0x08, 0xf8, 0x71, 0xc5, 0x31, 0xe0,
+        0x00, 0x00, 0x00, 0x00, 0x49, 0x45, 0x4e, 0x44, 0xae, 0x42, 0x60, 0x82,
+    ];
+
+    fn be(values: &[u32], sizes: &[usize]) -> Vec<u8> {
+        values.iter().zip(sizes).flat_map( | (v, size) | v.to_be_bytes()[4 - size..].to_vec()).collect()
+    }
+
+    // an sfnt with the given tables, plus the head, hhea and maxp every face needs, for 4 glyphs
+    // at 1000 units per em
+    fn font(mut tables: Vec<([u8; 4], Vec<u8>)>) -> Vec<u8> {
+        let mut head = vec![0; 54];
+        head[18..20].copy_from_slice(&1000u16.to_be_bytes());
+        tables.push((*b"head", head));
+        tables.push((*b"hhea", vec![0; 36]));
+        tables.push((*b"maxp", be(&[0x5000, 4], &[4, 2])));
+        tables.sort_by_key( | (tag, _) | *tag);
+        let mut out = be(&[0x10000, tables.len() as u32, 0, 0, 0], &[4, 2, 2, 2, 2]);
+        let mut offset = 12 + tables.len() * 16;
+        for (tag, data) in &tables {
+            out.extend_from_slice(tag);
+            out.extend(be(&[0, offset as u32, data.len() as u32], &[4, 4, 4]));
+            offset += data.len().next_multiple_of(4);
+        }
+        for (_, data) in &tables {
+            out.extend_from_slice(data);
+            out.resize(out.len().next_multiple_of(4), 0);
+        }
+        out
+    }
+
+    // glyph 3 has a red layer under one in the text color, glyph 2 a half transparent green one
+    fn colr_font() -> Vec<u8> {
+        let mut colr = be(&[0, 2, 14, 26, 3], &[2, 2, 4, 4, 2]);
+        // base glyphs out of order
+        colr.extend(be(&[3, 1, 2, 2, 0, 1], &[2; 6]));
+        colr.extend(be(&[1, 1, 1, 0, 2, 0xFFFF], &[2; 6]));
+        let mut cpal = be(&[0, 2, 1, 2, 14, 0], &[2, 2, 2, 2, 4, 2]);
+        cpal.extend([0, 0, 255, 255, 0, 128, 0, 128]);
+        font(vec![(*b"COLR", colr), (*b"CPAL", cpal)])
+    }
+
+    // glyph 3 has the png in a 20 pixels per em strike
+    fn sbix_font() -> Vec<u8> {
+        let mut sbix = be(&[1, 0, 1, 12], &[2, 2, 4, 4]);
+        sbix.extend(be(&[20, 72, 24, 24, 24, 24, 24 + 8 + PNG.len() as u32], &[2, 2, 4, 4, 4, 4, 4]));
+        sbix.extend(be(&[0, 0], &[2, 2]));
+        sbix.extend(b"png ");
+        sbix.extend(PNG);
+        font(vec![(*b"sbix", sbix)])
+    }
+
+    #[test]
+    fn parses_colr_layers() {
+        let data = colr_font();
+        let face = Face::parse(&data, 0).unwrap();
+        let colr = ColrTable::parse(&face).unwrap();
+        assert_eq!(colr.layers(3), Some(vec![(1, Some([255, 0, 0, 255])), (2, None)]));
+        assert_eq!(colr.layers(2), Some(vec![(1, Some([0, 128, 0, 128]))]));
+        assert_eq!(colr.layers(1), None);
+        assert!(colr.has_foreground_layers(3));
+        assert!(!colr.has_foreground_layers(2));
+        assert!(!colr.has_foreground_layers(0));
+
+        // no COLR, or one that points past its end, is no color glyphs at all
+        let data = font(Vec::new());
+        assert!(ColrTable::parse(&Face::parse(&data, 0).unwrap()).is_none());
+        let data = font(vec![(*b"COLR", be(&[0, 1, 14, 20, 1, 3, 0, 1], &[2, 2, 4, 4, 2, 2, 2, 2]))]);
+        assert!(ColrTable::parse(&Face::parse(&data, 0).unwrap()).is_none());
+    }
+
+    fn square(x0: f64, y0: f64, x1: f64, y1: f64) -> Glyph {
+        let p = Point::new;
+        Glyph {
+            horizontal_metrics: HorizontalMetrics {advance_width: x1, left_side_bearing: x0},
+            bounds: Rectangle::new(p(x0, y0), p(x1, y1)),
+            outline: vec![
+                PathCommand::MoveTo(p(x0, y0)),
+                PathCommand::LineTo(p(x1, y0)),
+                PathCommand::LineTo(p(x1, y1)),
+                PathCommand::LineTo(p(x0, y1)),
+                PathCommand::LineTo(p(x0, y0)),
+                PathCommand::Close,
+            ],
+        }
+    }
+
+    #[test]
+    fn draws_colr_layers() {
+        // 100 font units at 0.04 pixels per unit with a pixel of border is a 6x6 raster
+        let bounds = Rectangle::new(Point::new(0.0, 0.0), Point::new(100.0, 100.0));
+        let transform = glyph_transform(&bounds, 0.04, 1.0);
+        let mut raster = ColorRaster::new(6, 6);
+        raster.draw_layer(&square(0.0, 0.0, 100.0, 100.0), &transform, Some([255, 0, 0, 255]));
+        // the text color layer covers the left half, blue at half alpha the bottom half
+        raster.draw_layer(&square(0.0, 0.0, 50.0, 100.0), &transform, None);
+        raster.draw_layer(&square(0.0, 0.0, 100.0, 50.0), &transform, Some([0, 0, 255, 128]));
+
+        assert_eq!(raster.packed(0, 0), 0);
+        assert_eq!(raster.packed(5, 5), 0);
+        assert_eq!(raster.packed(4, 4), 0xffff0000);
+        assert_eq!(raster.packed_tint(4, 4), 0);
+        // the text color leaves its color out and goes into the tint
+        assert_eq!(raster.packed(1, 4), 0xff000000);
+        assert_eq!(raster.packed_tint(1, 4), 0xffffffff);
+        // and the layers over it cover the tint up like they cover colors
+        assert_eq!(raster.packed(4, 1), 0xff7f0080);
+        assert_eq!(raster.packed(1, 1), 0xff000080);
+        assert_eq!(raster.packed_tint(1, 1), 0x7f7f7f7f);
+    }
+
+    #[test]
+    fn draws_png_strikes() {
+        let data = sbix_font();
+        let face = Face::parse(&data, 0).unwrap();
+        assert!(raster_image(&face, 1, 20.0).is_none());
+        let image = raster_image(&face, 3, 64.0).unwrap();
+        assert_eq!((image.width, image.height, image.pixels_per_em), (2, 2, 20));
+        let bounds = raster_image_bounds(&image, 1000.0);
+        assert_eq!((bounds.p_min.x, bounds.p_min.y, bounds.p_max.x, bounds.p_max.y), (0.0, 0.0, 100.0, 100.0));
+
+        // at its own size, upside down as row 0 is the bottom, with the border left clear
+        let mut raster = ColorRaster::new(4, 4);
+        raster.draw_image(&image, 1).unwrap();
+        let row = | raster: &ColorRaster, y: usize | (0..raster.width).map( | x | raster.packed(x, y)).collect::<Vec<_>>();
+        assert_eq!(row(&raster, 0), [0; 4]);
+        assert_eq!(row(&raster, 1), [0, 0xff0000ff, 0, 0]);
+        assert_eq!(row(&raster, 2), [0, 0xffff0000, 0xff00ff00, 0]);
+        assert_eq!(row(&raster, 3), [0; 4]);
+
+        // scaled up every source pixel covers 2x2, scaled down they are averaged
+        let mut raster = ColorRaster::new(4, 4);
+        raster.draw_image(&image, 0).unwrap();
+        assert_eq!(row(&raster, 0), [0xff0000ff, 0xff0000ff, 0, 0]);
+        assert_eq!(row(&raster, 3), [0xffff0000, 0xffff0000, 0xff00ff00, 0xff00ff00]);
+        let mut raster = ColorRaster::new(3, 3);
+        raster.draw_image(&image, 1).unwrap();
+        assert_eq!(raster.packed(1, 1), 0xbf404040);
+        // no room inside the border
+        assert!(ColorRaster::new(2, 2).draw_image(&image, 1).is_none());
+    }
+
+    #[test]
+    fn fits_large_glyphs_in_the_atlas() {
+        let bounds = Rectangle::new(Point::new(0.0, -200.0), Point::new(1000.0, 800.0));
+        // small enough to draw as is
+        assert_eq!(fit_glyph_scale(&bounds, 0.5, 1, false, 2048, 2048), 0.5);
+        assert_eq!(fit_glyph_scale(&bounds, 0.5, 1, true, 2048, 2048), 0.5);
+        // too wide for the atlas, or for half of it with a tint mask
+        for (pixels_per_unit, is_tinted) in [(4.0, false), (1.5, true), (100.0, true)] {
+            let scale = fit_glyph_scale(&bounds, pixels_per_unit, 1, is_tinted, 2048, 2048);
+            assert!(scale < pixels_per_unit);
+            // the bounds are square, so this is the height too
+            let width = (1000.0 * scale).ceil() as usize + 2;
+            let atlas_width = if is_tinted {width * 2} else {width};
+            assert!(atlas_width < 2048 && atlas_width > 2000);
+        }
+        // a tall glyph is limited by its height
+        let tall = Rectangle::new(Point::new(0.0, 0.0), Point::new(10.0, 5000.0));
+        assert!(((5000.0 * fit_glyph_scale(&tall, 1.0, 1, false, 2048, 2048)).ceil() as usize) + 2 < 2048);
+    }
+}
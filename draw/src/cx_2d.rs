@@ -19,6 +19,7 @@ use {
         icon_atlas::CxIconAtlasRc,
         font_atlas::CxFontsAtlasRc,
         draw_list_2d::DrawList2d,
+        shader::draw_text::TextRun,
         turtle::{Turtle, TurtleWalk, Walk, AlignEntry},
     }
 };
@@ -45,6 +46,7 @@ pub struct Cx2d<'a> {
     pub fonts_atlas_rc: CxFontsAtlasRc,
     pub icon_atlas_rc: CxIconAtlasRc,
     pub nav_tree_rc: CxNavTreeRc,
+    pub (crate) text_runs: Vec<Vec<TextRun>>,
 }
 
 impl<'a> Deref for Cx2d<'a> {type Target = Cx; fn deref(&self) -> &Self::Target {self.cx}}
@@ -91,7 +93,27 @@ impl<'a> Cx2d<'a> {
             turtles: Vec::new(),
             align_list: Vec::new(),
             nav_tree_rc,
-            icon_atlas_rc
+            icon_atlas_rc,
+            text_runs: Vec::new(),
+        }
+    }
+    
+    // Starts collecting the text runs DrawText places, until the matching end_text_runs.
+    pub fn begin_text_runs(&mut self) {
+        self.text_runs.push(Vec::new());
+    }
+    
+    pub fn end_text_runs(&mut self) -> Vec<TextRun> {
+        self.text_runs.pop().unwrap_or_default()
+    }
+    
+    pub (crate) fn is_recording_text_runs(&self) -> bool {
+        !self.text_runs.is_empty()
+    }
+    
+    pub (crate) fn push_text_run(&mut self, run: TextRun) {
+        if let Some(runs) = self.text_runs.last_mut() {
+            runs.push(run);
         }
     }
     
@@ -19,22 +19,31 @@ pub use {
         geometry::GeometryQuad2D,
         makepad_vector::font::Glyph,
         makepad_vector::trapezoidator::Trapezoidator,
-        makepad_vector::geometry::{AffineTransformation, Transform, Vector},
+        makepad_vector::geometry::{AffineTransformation, Point, Rectangle, Transform, Vector},
         makepad_vector::internal_iter::ExtendFromInternalIterator,
         makepad_vector::path::PathIterator,
     },
     makepad_rustybuzz::{Direction, GlyphInfo, UnicodeBuffer},
 };
+use crate::color_glyph::{self, ColrTable, ColorRaster};
 
 pub(crate) const ATLAS_WIDTH: usize = 4096;
 pub(crate) const ATLAS_HEIGHT: usize = 4096;
+pub(crate) const COLOR_ATLAS_WIDTH: usize = 2048;
+pub(crate) const COLOR_ATLAS_HEIGHT: usize = 2048;
+
+// Color glyphs get a transparent border, so bilinear sampling doesn't pick up their neighbours.
+pub(crate) const COLOR_GLYPH_PAD: usize = 1;
 
 pub struct CxFontsAtlas {
     pub fonts: Vec<Option<CxFont >>,
     pub path_to_font_id: HashMap<String, usize>,
+    pub fallback_shape_cache: FallbackShapeCache,
     pub texture: Texture,
+    pub color_texture: Texture,
     pub clear_buffer: bool,
-    pub alloc: CxFontsAtlasAlloc
+    pub alloc: CxFontsAtlasAlloc,
+    pub color_alloc: CxFontsAtlasAlloc,
 }
 
 #[derive(Default)]
@@ -53,11 +62,13 @@ pub struct CxFontsAtlasSdfConfig {
 }
 
 impl CxFontsAtlas {
-    pub fn new(texture: Texture) -> Self {
+    pub fn new(texture: Texture, color_texture: Texture) -> Self {
         Self {
             fonts: Vec::new(),
             path_to_font_id: HashMap::new(),
+            fallback_shape_cache: FallbackShapeCache::new(),
             texture,
+            color_texture,
             clear_buffer: false,
             alloc: CxFontsAtlasAlloc {
                 full: false,
@@ -79,6 +90,14 @@ impl CxFontsAtlas {
                     },
                 })
             },
+            // Color glyphs are rasterized at their final size, straight into RGBA.
+            color_alloc: CxFontsAtlasAlloc {
+                texture_size: DVec2 {
+                    x: COLOR_ATLAS_WIDTH as f64,
+                    y: COLOR_ATLAS_HEIGHT as f64
+                },
+                ..Default::default()
+            },
         }
     }
 }
@@ -104,7 +123,12 @@ impl CxFontsAtlasAlloc {
             (w * scale).ceil() as usize + pad * 2,
             (h * scale).ceil() as usize + pad * 2,
         );
-
+        self.alloc_atlas_rect(w, h, pad, todo)
+    }
+    
+    // Allocates a `w` by `h` texel rect, of which `pad` texels on each side are left out of the
+    // returned texture coordinates.
+    pub fn alloc_atlas_rect(&mut self, w: usize, h: usize, pad: usize, todo: CxFontsAtlasTodo) -> CxFontAtlasGlyph {
         if w + self.xpos >= self.texture_size.x as usize {
             self.xpos = 0;
             self.ypos += self.hmax;
@@ -196,17 +220,112 @@ impl CxFontsAtlas {
                 cxfont.atlas_pages.clear();
             }
         }
-        self.alloc.todo.clear();
-        self.alloc.full = false;
-        self.alloc.xpos = 0;
-        self.alloc.ypos = 0;
-        self.alloc.hmax = 0;
+        for alloc in [&mut self.alloc, &mut self.color_alloc] {
+            alloc.todo.clear();
+            alloc.full = false;
+            alloc.xpos = 0;
+            alloc.ypos = 0;
+            alloc.hmax = 0;
+        }
         self.clear_buffer = true;
     }
     
     pub fn get_internal_font_atlas_texture_id(&self) -> Texture {
         self.texture.clone()
     }
+    
+    // Returns the first font in the chain that has a glyph for the given char.
+    pub fn get_fallback_font_id(&self, font_ids: &[usize], c: char) -> Option<usize> {
+        font_ids.iter().copied().find( | &font_id | {
+            self.fonts[font_id].as_ref().is_some_and( | cxfont | cxfont.has_glyph(c))
+        })
+    }
+    
+    // Returns the advance width of the given char, taken from the first font in the chain that
+    // has a glyph for it. The advance is expressed in the units of the first font, so that
+    // callers can keep scaling it by the first font's units_per_em.
+    pub fn get_glyph_advance(&mut self, font_ids: &[usize], c: char) -> Option<f64> {
+        let units_per_em = self.fonts[*font_ids.first()?].as_ref()?.ttf_font.units_per_em;
+        let font_id = self.get_fallback_font_id(font_ids, c)?;
+        let cxfont = self.fonts[font_id].as_mut().unwrap();
+        let glyph_id = cxfont.owned_font_face.with_ref( | face | face.glyph_index(c))?.0 as usize;
+        let scale = units_per_em / cxfont.ttf_font.units_per_em;
+        let glyph = cxfont.get_glyph_by_id(glyph_id).ok()?;
+        Some(glyph.horizontal_metrics.advance_width * scale)
+    }
+    
+    // Shapes a single directional run of text with a chain of fonts. The text is split into
+    // clusters, each cluster is assigned the first font in the chain that covers it, and
+    // consecutive clusters with the same font are shaped together. The resulting glyphs are
+    // returned in visual order, and cached per font chain.
+    pub fn shape_with_fallbacks(&mut self, font_ids: &[usize], direction: Direction, text: &str) -> Rc<[ShapedGlyph]> {
+        let Some(&first_font_id) = font_ids.first() else {
+            return Vec::new().into()
+        };
+        if let Some(glyphs) = self.fallback_shape_cache.get(font_ids, direction, text) {
+            return glyphs
+        }
+        let mut runs = fallback_runs(text, | c | self.get_fallback_font_id(font_ids, c).unwrap_or(first_font_id));
+        if direction == Direction::RightToLeft {
+            runs.reverse();
+        }
+        
+        let mut glyphs = Vec::new();
+        let mut rustybuzz_buffer = UnicodeBuffer::new();
+        for (font_id, range) in runs {
+            let cxfont = if let Some(cxfont) = self.fonts[font_id].as_mut() {cxfont} else {continue};
+            let (run_glyphs, new_rustybuzz_buffer) = cxfont.shape_cache.get_or_compute_glyphs(
+                (direction, &text[range.clone()]),
+                rustybuzz_buffer,
+                &cxfont.owned_font_face,
+                font_id
+            );
+            rustybuzz_buffer = new_rustybuzz_buffer;
+            glyphs.extend(run_glyphs.iter().map( | glyph | ShapedGlyph {cluster: glyph.cluster + range.start, ..*glyph}));
+        }
+        let glyphs: Rc<[ShapedGlyph]> = glyphs.into();
+        self.fallback_shape_cache.insert(font_ids, direction, text, glyphs.clone());
+        glyphs
+    }
+}
+
+// Splits text into runs of the same font. Every cluster gets the font font_for picks for its
+// first char, the chars that extend a cluster stay with the font of the char before them.
+fn fallback_runs(text: &str, mut font_for: impl FnMut(char) -> usize) -> Vec<(usize, std::ops::Range<usize>)> {
+    let mut runs: Vec<(usize, std::ops::Range<usize>)> = Vec::new();
+    let mut after_zwj = false;
+    for (index, c) in text.char_indices() {
+        let end = index + c.len_utf8();
+        let extends_cluster = after_zwj || is_cluster_extender(c);
+        after_zwj = c == '\u{200D}';
+        if let Some((_, range)) = runs.last_mut().filter( | _ | extends_cluster) {
+            range.end = end;
+            continue;
+        }
+        let font_id = font_for(c);
+        match runs.last_mut() {
+            Some((run_font_id, range)) if *run_font_id == font_id => range.end = end,
+            _ => runs.push((font_id, index..end))
+        }
+    }
+    runs
+}
+
+// Chars that never start a cluster of their own, so they stay with the font of the char before
+// them: combining marks, joiners, variation selectors, emoji modifiers and tag characters.
+fn is_cluster_extender(c: char) -> bool {
+    matches!(c,
+        '\u{0300}'..='\u{036F}'
+        | '\u{1AB0}'..='\u{1AFF}'
+        | '\u{1DC0}'..='\u{1DFF}'
+        | '\u{20D0}'..='\u{20FF}'
+        | '\u{FE20}'..='\u{FE2F}'
+        | '\u{200C}' | '\u{200D}'
+        | '\u{FE00}'..='\u{FE0F}'
+        | '\u{1F3FB}'..='\u{1F3FF}'
+        | '\u{E0020}'..='\u{E007F}'
+        | '\u{E0100}'..='\u{E01EF}'
+    )
 }
 
 impl<'a> Cx2d<'a> {
@@ -221,7 +340,13 @@ impl<'a> Cx2d<'a> {
                 unpack_row_length: None
             });
             
-            let fonts_atlas = CxFontsAtlas::new(texture);
+            let color_texture = Texture::new_with_format(cx, TextureFormat::VecBGRAu8_32 {
+                width: COLOR_ATLAS_WIDTH,
+                height: COLOR_ATLAS_HEIGHT,
+                data: vec![],
+            });
+            
+            let fonts_atlas = CxFontsAtlas::new(texture, color_texture);
             cx.set_global(CxFontsAtlasRc(Rc::new(RefCell::new(fonts_atlas))));
         }
     }
@@ -238,7 +363,7 @@ impl<'a> Cx2d<'a> {
         let mut fonts_atlas = fonts_atlas_rc.0.borrow_mut();
         let fonts_atlas = &mut*fonts_atlas;
 
-        if fonts_atlas.alloc.full {
+        if fonts_atlas.alloc.full || fonts_atlas.color_alloc.full {
             fonts_atlas.reset_fonts_atlas();
         }
 
@@ -248,6 +373,68 @@ impl<'a> Cx2d<'a> {
         for todo in std::mem::take(&mut fonts_atlas.alloc.todo) {
             self.swrast_atlas_todo(fonts_atlas, todo, &mut reuse_sdfer_bufs);
         }
+        for todo in std::mem::take(&mut fonts_atlas.color_alloc.todo) {
+            self.swrast_color_atlas_todo(fonts_atlas, todo);
+        }
+    }
+    
+    fn swrast_color_atlas_todo(&mut self, fonts_atlas: &mut CxFontsAtlas, todo: CxFontsAtlasTodo) {
+        let cxfont = fonts_atlas.fonts[todo.font_id].as_mut().unwrap();
+        let units_per_em = cxfont.ttf_font.units_per_em;
+        let atlas_page = &cxfont.atlas_pages[todo.atlas_page_id];
+        let color_glyph = atlas_page.color_glyphs.get(&todo.glyph_id).copied().flatten().unwrap();
+        let glyphtc = color_glyph.tc;
+        
+        let font_scale_pixels = color_glyph.pixels_per_unit;
+        let texture_size = fonts_atlas.color_alloc.texture_size;
+        
+        // NOTE(eddyb) `+ 1.0` is because the texture coordinate rectangle
+        // formed by `t1` and `t2` is *inclusive*.
+        let mut raster = ColorRaster::new(
+            ((glyphtc.t2.x - glyphtc.t1.x) as f64 * texture_size.x + 1.0).round() as usize,
+            ((glyphtc.t2.y - glyphtc.t1.y) as f64 * texture_size.y + 1.0).round() as usize,
+        );
+        
+        if let Some(layers) = cxfont.colr.as_ref().and_then( | colr | colr.layers(todo.glyph_id)) {
+            let transform = color_glyph::glyph_transform(&color_glyph.bounds, font_scale_pixels, COLOR_GLYPH_PAD as f64);
+            for (layer_glyph_id, color) in layers {
+                if let Ok(glyph) = cxfont.get_glyph_by_id(layer_glyph_id) {
+                    raster.draw_layer(glyph, &transform, color);
+                }
+            }
+        }
+        else {
+            cxfont.owned_font_face.with_ref( | face | {
+                if let Some(image) = color_glyph::raster_image(face, todo.glyph_id, font_scale_pixels * units_per_em) {
+                    raster.draw_image(&image, COLOR_GLYPH_PAD);
+                }
+            });
+        }
+        
+        let mut atlas_data = vec![];
+        fonts_atlas.color_texture.swap_vec_u32(self.cx, &mut atlas_data);
+        let (atlas_w, atlas_h) = fonts_atlas.color_texture.get_format(self.cx).vec_width_height().unwrap();
+        if atlas_data.is_empty() {
+            atlas_data = vec![0; atlas_w*atlas_h];
+        } else {
+            assert_eq!(atlas_data.len(), atlas_w*atlas_h);
+        }
+        
+        let atlas_x0 = (glyphtc.t1.x as f64 * texture_size.x).round() as usize;
+        let atlas_y0 = (glyphtc.t1.y as f64 * texture_size.y).round() as usize;
+        
+        for y in 0..raster.height {
+            let row = &mut atlas_data[(atlas_h - atlas_y0 - 1 - y) * atlas_w..][..atlas_w][atlas_x0..];
+            for (x, dst) in row[..raster.width].iter_mut().enumerate() {
+                *dst = raster.packed(x, y);
+            }
+            if color_glyph.tint_offset > 0.0 {
+                for (x, dst) in row[raster.width..][..raster.width].iter_mut().enumerate() {
+                    *dst = raster.packed_tint(x, y);
+                }
+            }
+        }
+        fonts_atlas.color_texture.swap_vec_u32(self.cx, &mut atlas_data);
     }
 
     fn swrast_atlas_todo(
@@ -392,11 +579,13 @@ pub struct CxFont {
     pub owned_font_face: crate::owned_font_face::OwnedFace,
     pub atlas_pages: Vec<CxFontAtlasPage>,
     pub shape_cache: ShapeCache,
+    pub colr: Option<ColrTable>,
+    pub has_raster_images: bool,
 }
 
 pub struct ShapeCache {
     pub keys: VecDeque<(Direction, Rc<str>)>,
-    pub glyph_ids: HashMap<(Direction, Rc<str>), Vec<usize>>,
+    pub glyphs: HashMap<(Direction, Rc<str>), Vec<ShapedGlyph>>,
 }
 
 impl ShapeCache {
@@ -406,13 +595,13 @@ impl ShapeCache {
     pub fn new() -> Self {
         Self {
             keys: VecDeque::new(),
-            glyph_ids: HashMap::new(),
+            glyphs: HashMap::new(),
         }
     }
 
     // If there is an entry for the given key in the cache, returns the corresponding list of
-    // glyph indices for that key. Otherwise, uses the given UnicodeBuffer and OwnedFace to
-    // compute the list of glyph indices for the key, inserts that in the cache and then returns
+    // shaped glyphs for that key. Otherwise, uses the given UnicodeBuffer and OwnedFace to
+    // compute the list of shaped glyphs for the key, inserts that in the cache and then returns
     // the corresponding list.
     //
     // This method takes a UnicodeBuffer by value, and then returns the same buffer by value. This
@@ -421,18 +610,19 @@ impl ShapeCache {
     // it and then return yet another UnicodeBuffer that reuses the same storage. This allows us to
     // avoid unnecessary heap allocations.
     //
-    // Note that owned_font_face should be the same as the CxFont to which this cache belongs,
-    // otherwise you will not get correct results.
-    pub fn get_or_compute_glyph_ids(
+    // Note that owned_font_face and font_id should be those of the CxFont to which this cache
+    // belongs, otherwise you will not get correct results.
+    pub fn get_or_compute_glyphs(
         &mut self, 
         key: (Direction, &str),
         mut rustybuzz_buffer: UnicodeBuffer,
-        owned_font_face: &crate::owned_font_face::OwnedFace
-    ) -> (&[usize], UnicodeBuffer) {
-        if !self.glyph_ids.contains_key(&key as &dyn ShapeCacheKey) {
+        owned_font_face: &crate::owned_font_face::OwnedFace,
+        font_id: usize,
+    ) -> (&[ShapedGlyph], UnicodeBuffer) {
+        if !self.glyphs.contains_key(&key as &dyn ShapeCacheKey) {
             if self.keys.len() == Self::MAX_SIZE {
                 for run in self.keys.drain(..Self::MAX_SIZE / 2) {
-                    self.glyph_ids.remove(&run);
+                    self.glyphs.remove(&run);
                 }
             }
 
@@ -440,14 +630,21 @@ impl ShapeCache {
             rustybuzz_buffer.set_direction(direction);
             rustybuzz_buffer.push_str(string);
             let glyph_buffer = owned_font_face.with_ref( | face | makepad_rustybuzz::shape(face, &[], rustybuzz_buffer));
-            let glyph_ids: Vec<_> = glyph_buffer.glyph_infos().iter().map( | glyph | glyph.glyph_id as usize).collect();
+            let glyphs: Vec<_> = glyph_buffer.glyph_infos().iter().zip(glyph_buffer.glyph_positions()).map( | (glyph, position) | ShapedGlyph {
+                font_id,
+                glyph_id: glyph.glyph_id as usize,
+                cluster: glyph.cluster as usize,
+                x_advance: position.x_advance as f64,
+                x_offset: position.x_offset as f64,
+                y_offset: position.y_offset as f64,
+            }).collect();
             rustybuzz_buffer = glyph_buffer.clear();
 
             let owned_string: Rc<str> = string.into();
             self.keys.push_back((direction, owned_string.clone()));
-            self.glyph_ids.insert((direction, owned_string), glyph_ids);
+            self.glyphs.insert((direction, owned_string), glyphs);
         }
-        (&self.glyph_ids[&key as &dyn ShapeCacheKey], rustybuzz_buffer)
+        (&self.glyphs[&key as &dyn ShapeCacheKey], rustybuzz_buffer)
     }
 }
 
@@ -519,11 +716,70 @@ impl ShapeCacheKey for (Direction, Rc<str>) {
     }
 }
 
+// A glyph as placed by rustybuzz, with its advance and offsets in the units of its font.
+#[derive(Clone, Copy, Debug)]
+pub struct ShapedGlyph {
+    pub font_id: usize,
+    pub glyph_id: usize,
+    // the byte offset in the shaped text of the cluster this glyph belongs to
+    pub cluster: usize,
+    pub x_advance: f64,
+    pub x_offset: f64,
+    pub y_offset: f64,
+}
+
+// Caches the glyphs produced by CxFontsAtlas::shape_with_fallbacks, first by font chain and then
+// by run, reusing the ShapeCacheKey trick so lookups don't allocate.
+pub struct FallbackShapeCache {
+    pub chains: HashMap<Vec<usize>, FallbackShapeRuns>,
+}
+
+pub struct FallbackShapeRuns {
+    pub keys: VecDeque<(Direction, Rc<str>)>,
+    pub glyphs: HashMap<(Direction, Rc<str>), Rc<[ShapedGlyph]>>,
+}
+
+impl FallbackShapeCache {
+    // The maximum number of runs that can be stored per font chain.
+    const MAX_SIZE: usize = 4096;
+    
+    pub fn new() -> Self {
+        Self {
+            chains: HashMap::new(),
+        }
+    }
+    
+    pub fn get(&self, font_ids: &[usize], direction: Direction, string: &str) -> Option<Rc<[ShapedGlyph]>> {
+        let runs = self.chains.get(font_ids)?;
+        runs.glyphs.get(&(direction, string) as &dyn ShapeCacheKey).cloned()
+    }
+    
+    pub fn insert(&mut self, font_ids: &[usize], direction: Direction, string: &str, glyphs: Rc<[ShapedGlyph]>) {
+        if !self.chains.contains_key(font_ids) {
+            self.chains.insert(font_ids.to_vec(), FallbackShapeRuns {
+                keys: VecDeque::new(),
+                glyphs: HashMap::new(),
+            });
+        }
+        let runs = self.chains.get_mut(font_ids).unwrap();
+        if runs.keys.len() == Self::MAX_SIZE {
+            for run in runs.keys.drain(..Self::MAX_SIZE / 2) {
+                runs.glyphs.remove(&run);
+            }
+        }
+        let owned_string: Rc<str> = string.into();
+        runs.keys.push_back((direction, owned_string.clone()));
+        runs.glyphs.insert((direction, owned_string), glyphs);
+    }
+}
+
 #[derive(Clone)]
 pub struct CxFontAtlasPage {
     pub dpi_factor: f64,
     pub font_size: f64,
-    pub atlas_glyphs: HashMap<usize, CxFontAtlasGlyph>
+    pub atlas_glyphs: HashMap<usize, CxFontAtlasGlyph>,
+    // None for glyphs that have no color version at this size
+    pub color_glyphs: HashMap<usize, Option<CxColorAtlasGlyph>>,
 }
 
 #[derive(Clone, Copy)]
@@ -532,6 +788,19 @@ pub struct CxFontAtlasGlyph {
     pub t2: Vec2,
 }
 
+#[derive(Clone, Copy)]
+pub struct CxColorAtlasGlyph {
+    // in font units, like `Glyph::bounds`
+    pub bounds: Rectangle,
+    pub tc: CxFontAtlasGlyph,
+    // the scale of the raster in pixels per font unit, below the font size for glyphs that
+    // wouldn't fit the atlas at it
+    pub pixels_per_unit: f64,
+    // glyphs with layers in the text color keep them as a mask right next to `tc`, this far
+    // away in texture coordinates, and 0 for glyphs that don't
+    pub tint_offset: f32,
+}
+
 #[derive(Default, Debug)]
 pub struct CxFontsAtlasTodo {
     pub font_id: usize,
@@ -543,11 +812,17 @@ impl CxFont {
     pub fn load_from_ttf_bytes(bytes: Rc<Vec<u8>>) -> Result<Self, crate::owned_font_face::FaceParsingError> {
         let owned_font_face = crate::owned_font_face::OwnedFace::parse(bytes, 0)?;
         let ttf_font = owned_font_face.with_ref(|face| makepad_vector::ttf_parser::from_ttf_parser_face(face));
+        let colr = owned_font_face.with_ref(|face| ColrTable::parse(face));
+        let has_raster_images = owned_font_face.with_ref(|face| {
+            [b"sbix", b"CBDT"].iter().any(|tag| face.raw_face().table(makepad_rustybuzz::ttf_parser::Tag::from_bytes(tag)).is_some())
+        });
         Ok(Self {
             ttf_font,
             owned_font_face,
             atlas_pages: Vec::new(),
             shape_cache: ShapeCache::new(),
+            colr,
+            has_raster_images,
         })
     }
     
@@ -562,10 +837,15 @@ impl CxFont {
             dpi_factor: dpi_factor,
             font_size: font_size,
             atlas_glyphs: HashMap::new(),
+            color_glyphs: HashMap::new(),
         });
         self.atlas_pages.len() - 1
     }
 
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.owned_font_face.with_ref( | face | face.glyph_index(c).map_or(false, | id | id.0 != 0))
+    }
+
     pub fn get_glyph(&mut self, c:char)->Option<&Glyph>{
         if c < '\u{10000}' {
             Some(self.get_glyph_by_id(self.owned_font_face.with_ref(|face| face.glyph_index(c))?.0 as usize).unwrap())
@@ -577,4 +857,126 @@ impl CxFont {
     pub fn get_glyph_by_id(&mut self, id: usize) -> makepad_vector::ttf_parser::Result<&Glyph> {
         self.owned_font_face.with_ref(|face| self.ttf_font.get_glyph_by_id(face, id))
     }
+    
+    pub fn has_color_glyphs(&self) -> bool {
+        self.colr.is_some() || self.has_raster_images
+    }
+    
+    // The bounds in font units of the color version of a glyph, either the union of its COLR
+    // layers or its bitmap strike image.
+    pub fn get_color_glyph_bounds(&mut self, glyph_id: usize, pixels_per_em: f64) -> Option<Rectangle> {
+        if let Some(layers) = self.colr.as_ref().and_then(|colr| colr.layers(glyph_id)) {
+            let mut bounds: Option<Rectangle> = None;
+            for (layer_glyph_id, _) in layers {
+                let layer = self.get_glyph_by_id(layer_glyph_id).ok()?.bounds;
+                bounds = Some(match bounds {
+                    None => layer,
+                    Some(b) => Rectangle::new(
+                        Point::new(b.p_min.x.min(layer.p_min.x), b.p_min.y.min(layer.p_min.y)),
+                        Point::new(b.p_max.x.max(layer.p_max.x), b.p_max.y.max(layer.p_max.y)),
+                    )
+                });
+            }
+            return bounds.filter(|b| b.p_max.x > b.p_min.x && b.p_max.y > b.p_min.y)
+        }
+        if !self.has_raster_images {
+            return None
+        }
+        let units_per_em = self.ttf_font.units_per_em;
+        self.owned_font_face.with_ref(|face| {
+            color_glyph::raster_image(face, glyph_id, pixels_per_em).map(|image| color_glyph::raster_image_bounds(&image, units_per_em))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // IBM Plex Sans has U+0301 but no ☺, Liberation Mono has ☺ but no U+0301
+    fn atlas() -> CxFontsAtlas {
+        let mut cx = Cx::new(Box::new( | _, _ | {}));
+        let mut atlas = CxFontsAtlas::new(Texture::new(&mut cx), Texture::new(&mut cx));
+        for bytes in [
+            &include_bytes!("../../widgets/resources/IBMPlexSans-Text.ttf")[..],
+            &include_bytes!("../../widgets/resources/LiberationMono-Regular.ttf")[..],
+        ] {
+            atlas.fonts.push(Some(CxFont::load_from_ttf_bytes(Rc::new(bytes.to_vec())).unwrap()));
+        }
+        atlas
+    }
+
+    #[test]
+    fn runs_follow_clusters() {
+        // a is font 0, x is font 1 and everything else font 2
+        let runs = | text: &str | fallback_runs(text, | c | match c {'a' => 0, 'x' => 1, _ => 2});
+        assert_eq!(runs("aax"), [(0, 0..2), (1, 2..3)]);
+        // combining marks, variation selectors and emoji modifiers stay with the char before them
+        assert_eq!(runs("x\u{301}a\u{301}"), [(1, 0..3), (0, 3..6)]);
+        assert_eq!(runs("x\u{FE0F}a"), [(1, 0..4), (0, 4..5)]);
+        assert_eq!(runs("x\u{1F3FB}a"), [(1, 0..5), (0, 5..6)]);
+        // so does the char after a zero width joiner
+        assert_eq!(runs("x\u{200D}aa"), [(1, 0..5), (0, 5..6)]);
+        // whitespace starts a cluster like any other char
+        assert_eq!(runs("x a"), [(1, 0..1), (2, 1..2), (0, 2..3)]);
+        assert_eq!(runs("x\ta\n"), [(1, 0..1), (2, 1..2), (0, 2..3), (2, 3..4)]);
+        // a mark at the start has nothing to extend
+        assert_eq!(runs("\u{301}a"), [(2, 0..2), (0, 2..3)]);
+        assert_eq!(runs(""), []);
+    }
+
+    #[test]
+    fn shapes_with_fallback_fonts() {
+        let mut atlas = atlas();
+        let fonts = | glyphs: &[ShapedGlyph] | glyphs.iter().map( | g | g.font_id).collect::<Vec<_>>();
+        // ☺ is missing from the first font, the mark after it stays in the second one even though
+        // the first font has it
+        let glyphs = atlas.shape_with_fallbacks(&[0, 1], Direction::LeftToRight, "a\u{263A}\u{301}b");
+        assert_eq!(fonts(&glyphs), [0, 1, 1, 0]);
+        assert_eq!(glyphs[0].cluster, 0);
+        assert_eq!(glyphs[1].cluster, 1);
+        assert_eq!(glyphs[3].cluster, 6);
+        // a space between fallback chars is shaped in the first font that has one
+        let glyphs = atlas.shape_with_fallbacks(&[0, 1], Direction::LeftToRight, "\u{263A} \u{263A}");
+        assert_eq!(fonts(&glyphs), [1, 0, 1]);
+        // the char after a joiner stays in the font of the char before it
+        let glyphs = atlas.shape_with_fallbacks(&[0, 1], Direction::LeftToRight, "\u{263A}\u{200D}a");
+        assert!(glyphs.iter().all( | g | g.font_id == 1));
+        // right to left runs come in visual order
+        let glyphs = atlas.shape_with_fallbacks(&[0, 1], Direction::RightToLeft, "a\u{263A}");
+        assert_eq!(fonts(&glyphs), [1, 0]);
+        // a char no font has gets the first font's notdef glyph
+        let glyphs = atlas.shape_with_fallbacks(&[0, 1], Direction::LeftToRight, "\u{5D0}");
+        assert_eq!(fonts(&glyphs), [0]);
+        assert_eq!(glyphs[0].glyph_id, 0);
+        // an empty chain shapes nothing
+        assert!(atlas.shape_with_fallbacks(&[], Direction::LeftToRight, "a").is_empty());
+        assert_eq!(atlas.get_glyph_advance(&[], 'a'), None);
+    }
+
+    #[test]
+    fn caches_runs_per_chain() {
+        let mut atlas = atlas();
+        let first = atlas.shape_with_fallbacks(&[0, 1], Direction::LeftToRight, "ab");
+        let again = atlas.shape_with_fallbacks(&[0, 1], Direction::LeftToRight, "ab");
+        assert!(Rc::ptr_eq(&first, &again));
+        // another chain or direction shapes again
+        let other = atlas.shape_with_fallbacks(&[1, 0], Direction::LeftToRight, "ab");
+        assert!(!Rc::ptr_eq(&first, &other));
+        assert!(other.iter().all( | g | g.font_id == 1));
+        assert!(atlas.fallback_shape_cache.get(&[0, 1], Direction::RightToLeft, "ab").is_none());
+
+        // a full chain drops its oldest half
+        let mut cache = FallbackShapeCache::new();
+        let glyphs: Rc<[ShapedGlyph]> = Vec::new().into();
+        for i in 0..FallbackShapeCache::MAX_SIZE {
+            cache.insert(&[0], Direction::LeftToRight, &i.to_string(), glyphs.clone());
+        }
+        assert!(cache.get(&[0], Direction::LeftToRight, "0").is_some());
+        cache.insert(&[0], Direction::LeftToRight, "new", glyphs.clone());
+        assert!(cache.get(&[0], Direction::LeftToRight, "0").is_none());
+        assert!(cache.get(&[0], Direction::LeftToRight, &(FallbackShapeCache::MAX_SIZE / 2).to_string()).is_some());
+        assert!(cache.get(&[0], Direction::LeftToRight, "new").is_some());
+        assert_eq!(cache.chains[&vec![0]].keys.len(), FallbackShapeCache::MAX_SIZE / 2 + 1);
+    }
 }
@@ -9,10 +9,12 @@ pub mod draw_list_2d;
 pub mod shader;
 pub mod turtle;
 pub mod font_atlas;
+pub mod rich_text;
 pub mod geometry;
 pub mod nav;
 pub mod icon_atlas;
 mod owned_font_face;
+mod color_glyph;
  
 pub use crate::{
     match_event::MatchEvent, 
@@ -51,9 +53,16 @@ pub use crate::{
         draw_icon::DrawIcon,
         draw_quad::DrawQuad,
         draw_line::DrawLine,
-        draw_text::DrawText,
+        draw_text::{DrawText, TextRun},
         draw_color::DrawColor,
     },
+    rich_text::{
+        AttributedText,
+        TextAttrs,
+        TextSpan,
+        RichTextLayout,
+        RichTextDecoration,
+    },
     geometry::{
         GeometryGen,
         GeometryQuad2D,
@@ -24,7 +24,12 @@ pub trait MatchEvent{
 
     fn handle_http_response(&mut self, _cx:&mut Cx, _request_id:LiveId, _response:&HttpResponse){}
     fn handle_http_request_error(&mut self, _cx:&mut Cx, _request_id:LiveId, _err:&str){}
+    fn handle_http_request_cancelled(&mut self, _cx:&mut Cx, _request_id:LiveId){}
+    fn handle_http_request_timed_out(&mut self, _cx:&mut Cx, _request_id:LiveId){}
     fn handle_http_progress(&mut self, _cx:&mut Cx, _request_id:LiveId, _loaded:u64, _total:u64){}
+    fn handle_http_stream(&mut self, _cx:&mut Cx, _request_id:LiveId, _response:&HttpResponse){}
+    fn handle_http_server_sent_event(&mut self, _cx:&mut Cx, _request_id:LiveId, _event:&ServerSentEvent){}
+    fn handle_http_stream_complete(&mut self, _cx:&mut Cx, _request_id:LiveId, _response:&HttpResponse){}
 
     fn handle_network_responses(&mut self, cx: &mut Cx, e:&NetworkResponsesEvent ){
         for e in e{
@@ -32,9 +37,24 @@ pub trait MatchEvent{
                 NetworkResponse::HttpRequestError(err)=>{
                     self.handle_http_request_error(cx, e.request_id, err);
                 }
+                NetworkResponse::HttpRequestCancelled=>{
+                    self.handle_http_request_cancelled(cx, e.request_id);
+                }
+                NetworkResponse::HttpRequestTimedOut=>{
+                    self.handle_http_request_timed_out(cx, e.request_id);
+                }
                 NetworkResponse::HttpResponse(res)=>{
                     self.handle_http_response(cx, e.request_id, res);
                 }
+                NetworkResponse::HttpStreamResponse(res)=>{
+                    self.handle_http_stream(cx, e.request_id, res);
+                }
+                NetworkResponse::HttpServerSentEvent(event)=>{
+                    self.handle_http_server_sent_event(cx, e.request_id, event);
+                }
+                NetworkResponse::HttpStreamComplete(res)=>{
+                    self.handle_http_stream_complete(cx, e.request_id, res);
+                }
                 NetworkResponse::HttpProgress{loaded, total}=>{
                     self.handle_http_progress(cx, e.request_id, *loaded, *total);
                 }
@@ -0,0 +1,687 @@
+use {
+    std::ops::Range,
+    crate::{
+        makepad_platform::*,
+        cx_2d::Cx2d,
+        turtle::{Walk, Size, Align},
+        font_atlas::{CxFontsAtlas, Font, ShapedGlyph},
+        shader::draw_text::DrawText,
+    },
+    makepad_rustybuzz::Direction,
+    unicode_bidi::{BidiInfo, Level},
+};
+
+// The style of a range of an AttributedText. Anything left unset is taken from the DrawText
+// that lays it out.
+#[derive(Clone, Debug, Default)]
+pub struct TextAttrs {
+    pub font: Option<Font>,
+    pub font_size: Option<f64>,
+    pub color: Option<Vec4>,
+    pub underline: bool,
+    pub background: Option<Vec4>,
+    pub link_id: Option<LiveId>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct TextSpan {
+    pub range: Range<usize>,
+    pub attrs: TextAttrs,
+}
+
+// A string with styled byte ranges, laid out and drawn as one paragraph by DrawText, so that
+// shaping, line breaking and hit testing all see the text as a whole.
+#[derive(Clone, Debug, Default)]
+pub struct AttributedText {
+    pub text: String,
+    // sorted and non overlapping
+    pub spans: Vec<TextSpan>,
+}
+
+impl AttributedText {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn clear(&mut self) {
+        self.text.clear();
+        self.spans.clear();
+    }
+
+    // Appends text with the given style.
+    pub fn push(&mut self, text: &str, attrs: TextAttrs) {
+        let start = self.text.len();
+        self.text.push_str(text);
+        self.spans.push(TextSpan {range: start..self.text.len(), attrs});
+    }
+
+    pub fn push_plain(&mut self, text: &str) {
+        self.push(text, TextAttrs::default());
+    }
+
+    // Changes the style of a byte range, splitting spans where the range starts and ends.
+    // Bytes not covered by any span get a span of their own first.
+    pub fn apply<F>(&mut self, range: Range<usize>, mut f: F) where F: FnMut(&mut TextAttrs) {
+        let range = range.start.min(self.text.len())..range.end.min(self.text.len());
+        if range.is_empty() {
+            return
+        }
+        self.fill_gaps();
+        let mut spans = Vec::with_capacity(self.spans.len() + 2);
+        for span in self.spans.drain(..) {
+            if span.range.end <= range.start || span.range.start >= range.end {
+                spans.push(span);
+                continue;
+            }
+            let inner = span.range.start.max(range.start)..span.range.end.min(range.end);
+            if span.range.start < inner.start {
+                spans.push(TextSpan {range: span.range.start..inner.start, attrs: span.attrs.clone()});
+            }
+            let mut attrs = span.attrs.clone();
+            f(&mut attrs);
+            spans.push(TextSpan {range: inner.clone(), attrs});
+            if inner.end < span.range.end {
+                spans.push(TextSpan {range: inner.end..span.range.end, attrs: span.attrs});
+            }
+        }
+        self.spans = spans;
+    }
+
+    fn fill_gaps(&mut self) {
+        let mut spans = Vec::with_capacity(self.spans.len());
+        let mut pos = 0;
+        for span in self.spans.drain(..) {
+            if span.range.start > pos {
+                spans.push(TextSpan {range: pos..span.range.start, attrs: TextAttrs::default()});
+            }
+            pos = span.range.end;
+            spans.push(span);
+        }
+        if pos < self.text.len() {
+            spans.push(TextSpan {range: pos..self.text.len(), attrs: TextAttrs::default()});
+        }
+        self.spans = spans;
+    }
+
+    pub fn span_at(&self, index: usize) -> Option<usize> {
+        self.spans.iter().position( | span | span.range.contains(&index))
+    }
+}
+
+pub enum RichTextDecoration {
+    Background(Vec4),
+    Underline(Vec4),
+}
+
+#[derive(Clone, Debug)]
+pub struct RichTextGlyph {
+    pub shaped: ShapedGlyph,
+    pub span: usize,
+    pub font_size: f64,
+    pub line: usize,
+    // the pen position, with y the top of the line as DrawText::draw_glyph expects it
+    pub pos: DVec2,
+}
+
+#[derive(Clone, Debug)]
+pub struct RichTextCluster {
+    pub range: Range<usize>,
+    pub line: usize,
+    pub span: usize,
+    pub x: f64,
+    pub width: f64,
+    pub rtl: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct RichTextLine {
+    // without the newline that ends it
+    pub range: Range<usize>,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub baseline: f64,
+    pub font_size: f64,
+}
+
+// The result of laying out an AttributedText. Clusters are in logical order, and all positions
+// are relative to `origin`, which drawing sets to where the text ended up.
+#[derive(Clone, Debug, Default)]
+pub struct RichTextLayout {
+    pub origin: DVec2,
+    pub size: DVec2,
+    pub glyphs: Vec<RichTextGlyph>,
+    pub clusters: Vec<RichTextCluster>,
+    pub lines: Vec<RichTextLine>,
+}
+
+impl RichTextLayout {
+    fn line_at(&self, y: f64) -> Option<usize> {
+        if self.lines.is_empty() {
+            return None
+        }
+        let y = y - self.origin.y;
+        Some(self.lines.iter().position( | line | y < line.y + line.height).unwrap_or(self.lines.len() - 1))
+    }
+
+    // Rects around the clusters for which `pick` is true, merged per line where they touch.
+    fn line_rects<F>(&self, pick: F) -> Vec<(usize, Rect)> where F: Fn(&RichTextCluster) -> bool {
+        let mut out: Vec<(usize, Rect)> = Vec::new();
+        for (line_index, line) in self.lines.iter().enumerate() {
+            let mut clusters: Vec<&RichTextCluster> = self.clusters.iter()
+                .filter( | cluster | cluster.line == line_index && pick(cluster))
+                .collect();
+            clusters.sort_by( | a, b | a.x.total_cmp(&b.x));
+            let first = out.len();
+            for cluster in clusters {
+                if let Some((_, last)) = out[first..].last_mut() {
+                    if (last.pos.x + last.size.x - self.origin.x - cluster.x).abs() < 0.01 {
+                        last.size.x += cluster.width;
+                        continue;
+                    }
+                }
+                out.push((line_index, Rect {
+                    pos: self.origin + dvec2(cluster.x, line.y),
+                    size: dvec2(cluster.width, line.height)
+                }));
+            }
+        }
+        out
+    }
+
+    // The rects covering a byte range, at most one per line unless bidi text splits it up.
+    pub fn range_rects(&self, range: Range<usize>) -> Vec<Rect> {
+        self.line_rects( | cluster | cluster.range.start < range.end && cluster.range.end > range.start)
+            .into_iter().map( | (_, rect) | rect).collect()
+    }
+
+    pub fn span_rects(&self, span: usize) -> Vec<Rect> {
+        self.line_rects( | cluster | cluster.span == span)
+            .into_iter().map( | (_, rect) | rect).collect()
+    }
+
+    // The span under a position, if any.
+    pub fn span_at(&self, pos: DVec2) -> Option<usize> {
+        let line = self.line_at(pos.y)?;
+        let x = pos.x - self.origin.x;
+        self.clusters.iter()
+            .find( | cluster | cluster.line == line && x >= cluster.x && x < cluster.x + cluster.width)
+            .map( | cluster | cluster.span)
+    }
+
+    pub fn link_at(&self, text: &AttributedText, pos: DVec2) -> Option<LiveId> {
+        text.spans.get(self.span_at(pos)?)?.attrs.link_id
+    }
+
+    // The byte offset a cursor should go to for a position, for clicks and drag selection.
+    pub fn offset_at(&self, pos: DVec2) -> usize {
+        let line_index = if let Some(line_index) = self.line_at(pos.y) {line_index} else {return 0};
+        let x = pos.x - self.origin.x;
+        let mut leftmost: Option<&RichTextCluster> = None;
+        let mut rightmost: Option<&RichTextCluster> = None;
+        for cluster in self.clusters.iter().filter( | cluster | cluster.line == line_index) {
+            if x >= cluster.x && x < cluster.x + cluster.width {
+                let before = (x < cluster.x + cluster.width * 0.5) != cluster.rtl;
+                return if before {cluster.range.start} else {cluster.range.end}
+            }
+            if leftmost.map_or(true, | l | cluster.x < l.x) {
+                leftmost = Some(cluster);
+            }
+            if rightmost.map_or(true, | r | cluster.x > r.x) {
+                rightmost = Some(cluster);
+            }
+        }
+        match (leftmost, rightmost) {
+            (Some(left), _) if x < left.x => if left.rtl {left.range.end} else {left.range.start},
+            (_, Some(right)) => if right.rtl {right.range.start} else {right.range.end},
+            _ => self.lines[line_index].range.start
+        }
+    }
+
+    // A zero width rect, the height of the line, where the cursor for a byte offset goes.
+    pub fn cursor_rect(&self, offset: usize) -> Rect {
+        let rect = | line: &RichTextLine, x: f64 | Rect {
+            pos: self.origin + dvec2(x, line.y),
+            size: dvec2(0.0, line.height)
+        };
+        if let Some(cluster) = self.clusters.iter().find( | cluster | cluster.range.start == offset) {
+            let x = if cluster.rtl {cluster.x + cluster.width} else {cluster.x};
+            return rect(&self.lines[cluster.line], x)
+        }
+        if let Some(cluster) = self.clusters.iter().find( | cluster | cluster.range.end == offset) {
+            let x = if cluster.rtl {cluster.x} else {cluster.x + cluster.width};
+            return rect(&self.lines[cluster.line], x)
+        }
+        let line = self.lines.iter().rev().find( | line | line.range.start <= offset).or(self.lines.first());
+        match line {
+            Some(line) => rect(line, line.x),
+            None => Rect {pos: self.origin, size: DVec2::default()}
+        }
+    }
+}
+
+// The font chain and size a span shapes with, spans that share one are shaped together.
+#[derive(Clone, PartialEq)]
+struct SpanStyle {
+    font_ids: SmallVec<[usize; 4]>,
+    font_size: f64,
+}
+
+// A cluster in logical order, before it is placed on a line.
+struct LogicalCluster {
+    range: Range<usize>,
+    span: usize,
+    level: Level,
+    width: f64,
+    // the glyphs of the cluster in visual order, with their advance in pixels
+    glyphs: Vec<(ShapedGlyph, f64)>,
+}
+
+fn is_ideographic(c: char) -> bool {
+    matches!(c,
+        '\u{2E80}'..='\u{9FFF}'
+        | '\u{AC00}'..='\u{D7AF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{FF00}'..='\u{FFEF}'
+        | '\u{20000}'..='\u{2FFFF}'
+    )
+}
+
+impl DrawText {
+    fn span_style(&self, text: &AttributedText, span: usize) -> SpanStyle {
+        let attrs = text.spans.get(span).map( | span | &span.attrs);
+        let mut font_ids = SmallVec::new();
+        if let Some(font_id) = attrs.and_then( | attrs | attrs.font.as_ref()).and_then( | font | font.font_id) {
+            font_ids.push(font_id);
+        }
+        for font_id in self.text_style.font_ids() {
+            if !font_ids.contains(&font_id) {
+                font_ids.push(font_id);
+            }
+        }
+        SpanStyle {
+            font_ids,
+            font_size: attrs.and_then( | attrs | attrs.font_size).unwrap_or(self.text_style.font_size),
+        }
+    }
+
+    pub fn layout_attributed(&self, cx: &Cx2d, text: &AttributedText, wrap_width: f64, align_x: f64) -> RichTextLayout {
+        self.layout_attributed_inner(text, wrap_width, align_x, &mut *cx.fonts_atlas_rc.0.borrow_mut())
+    }
+
+    fn layout_attributed_inner(&self, text: &AttributedText, wrap_width: f64, align_x: f64, fonts_atlas: &mut CxFontsAtlas) -> RichTextLayout {
+        let styles: Vec<SpanStyle> = (0..=text.spans.len()).map( | span | self.span_style(text, span)).collect();
+        // bytes outside of any span use the last style, which is the DrawText's own
+        let mut span_of_byte = vec![text.spans.len(); text.text.len()];
+        for (index, span) in text.spans.iter().enumerate() {
+            for byte in span_of_byte[span.range.start.min(text.text.len())..span.range.end.min(text.text.len())].iter_mut() {
+                *byte = index;
+            }
+        }
+
+        let mut layout = RichTextLayout::default();
+        let mut y = 0.0;
+        let mut paragraph_start = 0;
+        for paragraph in text.text.split('\n') {
+            let paragraph_range = paragraph_start..paragraph_start + paragraph.len();
+            paragraph_start = paragraph_range.end + 1;
+            let clusters = self.shape_paragraph(text, paragraph_range.clone(), &span_of_byte, &styles, fonts_atlas);
+
+            // break the paragraph into lines, greedily, at spaces and around ideographs
+            let mut lines: Vec<Range<usize>> = Vec::new();
+            let mut line_start = 0;
+            let mut width = 0.0;
+            let mut last_break = None;
+            for (index, cluster) in clusters.iter().enumerate() {
+                let cluster_text = &text.text[cluster.range.clone()];
+                let is_space = cluster_text.chars().all(char::is_whitespace);
+                let is_ideograph = cluster_text.chars().next().map_or(false, is_ideographic);
+                if is_ideograph && index > line_start {
+                    last_break = Some(index);
+                }
+                if !is_space && index > line_start && width + cluster.width > wrap_width {
+                    let at = last_break.filter( | at | *at > line_start).unwrap_or(index);
+                    lines.push(line_start..at);
+                    line_start = at;
+                    width = clusters[at..index].iter().map( | cluster | cluster.width).sum();
+                    last_break = None;
+                }
+                width += cluster.width;
+                if is_space || is_ideograph {
+                    last_break = Some(index + 1);
+                }
+            }
+            lines.push(line_start..clusters.len());
+
+            for line_clusters in lines {
+                let line_index = layout.lines.len();
+                let clusters = &clusters[line_clusters];
+                let font_size = clusters.iter()
+                    .map( | cluster | styles[cluster.span].font_size)
+                    .fold(None, | max: Option<f64>, size | Some(max.map_or(size, | max | max.max(size))))
+                    .unwrap_or_else( | | styles[span_of_byte.get(paragraph_range.start).copied().unwrap_or(text.spans.len())].font_size);
+                let height = font_size * self.text_style.height_factor * self.font_scale;
+                let baseline = font_size * self.font_scale * self.text_style.top_drop;
+
+                // trailing spaces hang past the end of the line
+                let trailing = clusters.iter().rev()
+                    .take_while( | cluster | text.text[cluster.range.clone()].chars().all(char::is_whitespace))
+                    .count();
+                let line_width: f64 = clusters[..clusters.len() - trailing].iter().map( | cluster | cluster.width).sum();
+
+                // reorder the clusters visually (rule L2 of the bidi algorithm)
+                let mut order: Vec<usize> = (0..clusters.len()).collect();
+                if let Some(max_level) = clusters.iter().map( | cluster | cluster.level.number()).max() {
+                    let min_odd = clusters.iter().map( | cluster | cluster.level.number()).filter( | level | level % 2 == 1).min();
+                    if let Some(min_odd) = min_odd {
+                        for level in (min_odd..=max_level).rev() {
+                            let mut i = 0;
+                            while i < order.len() {
+                                if clusters[order[i]].level.number() >= level {
+                                    let start = i;
+                                    while i < order.len() && clusters[order[i]].level.number() >= level {
+                                        i += 1;
+                                    }
+                                    order[start..i].reverse();
+                                }
+                                else {
+                                    i += 1;
+                                }
+                            }
+                        }
+                    }
+                }
+
+                let range = match (clusters.first(), clusters.last()) {
+                    (Some(first), Some(last)) => first.range.start..last.range.end,
+                    _ => paragraph_range.start..paragraph_range.start
+                };
+                let line_x = if wrap_width.is_finite() {(wrap_width - line_width).max(0.0) * align_x} else {0.0};
+                let mut x = line_x;
+                for &index in &order {
+                    let cluster = &clusters[index];
+                    let style = &styles[cluster.span];
+                    let mut pen_x = x;
+                    for (shaped, advance) in &cluster.glyphs {
+                        let scale = style.font_size * 96.0 / (72.0 * fonts_atlas.fonts[shaped.font_id].as_ref().unwrap().ttf_font.units_per_em) * self.font_scale;
+                        layout.glyphs.push(RichTextGlyph {
+                            shaped: *shaped,
+                            span: cluster.span,
+                            font_size: style.font_size,
+                            line: line_index,
+                            pos: dvec2(
+                                pen_x + shaped.x_offset * scale,
+                                y + baseline - style.font_size * self.font_scale * self.text_style.top_drop - shaped.y_offset * scale
+                            ),
+                        });
+                        pen_x += advance;
+                    }
+                    layout.clusters.push(RichTextCluster {
+                        range: cluster.range.clone(),
+                        line: line_index,
+                        span: cluster.span,
+                        x,
+                        width: cluster.width,
+                        rtl: cluster.level.is_rtl(),
+                    });
+                    x += cluster.width;
+                }
+                layout.lines.push(RichTextLine {range, x: line_x, y, width: line_width, height, baseline, font_size});
+                layout.size.x = layout.size.x.max(line_width);
+                layout.size.y = y + height;
+                y += height * self.text_style.line_spacing;
+            }
+        }
+        // keep the clusters in logical order, they were pushed in visual order per line
+        layout.clusters.sort_by_key( | cluster | cluster.range.start);
+        if wrap_width.is_finite() {
+            layout.size.x = layout.size.x.min(wrap_width);
+        }
+        else if align_x != 0.0 {
+            // without a wrap width, lines align within the widest one
+            for line_index in 0..layout.lines.len() {
+                let shift = (layout.size.x - layout.lines[line_index].width) * align_x;
+                layout.lines[line_index].x += shift;
+                for cluster in layout.clusters.iter_mut().filter( | cluster | cluster.line == line_index) {
+                    cluster.x += shift;
+                }
+                for glyph in layout.glyphs.iter_mut().filter( | glyph | glyph.line == line_index) {
+                    glyph.pos.x += shift;
+                }
+            }
+        }
+        layout
+    }
+
+    // Shapes a paragraph into clusters, in logical order. Neighbouring spans that shape with the
+    // same fonts and size are shaped as one run, so kerning and ligatures carry across them.
+    fn shape_paragraph(&self, text: &AttributedText, range: Range<usize>, span_of_byte: &[usize], styles: &[SpanStyle], fonts_atlas: &mut CxFontsAtlas) -> Vec<LogicalCluster> {
+        let mut clusters = Vec::new();
+        if range.is_empty() {
+            return clusters
+        }
+        let paragraph = &text.text[range.clone()];
+        let bidi_info = BidiInfo::new(paragraph, None);
+        let levels = &bidi_info.levels;
+
+        // split into runs of the same style and bidi level
+        let mut runs: Vec<Range<usize>> = Vec::new();
+        for (index, _) in paragraph.char_indices() {
+            let same = runs.last().map_or(false, | run: &Range<usize> | {
+                levels[run.start] == levels[index]
+                    && styles[span_of_byte[range.start + run.start]] == styles[span_of_byte[range.start + index]]
+            });
+            if same {
+                runs.last_mut().unwrap().end = index;
+            }
+            else {
+                runs.push(index..index);
+            }
+        }
+        for run in &mut runs {
+            run.end = paragraph[run.end..].chars().next().map_or(run.end, | c | run.end + c.len_utf8());
+        }
+
+        for run in runs {
+            let level = levels[run.start];
+            let style = &styles[span_of_byte[range.start + run.start]];
+            if style.font_ids.is_empty() {
+                continue;
+            }
+            let direction = if level.is_rtl() {Direction::RightToLeft} else {Direction::LeftToRight};
+            let glyphs = fonts_atlas.shape_with_fallbacks(&style.font_ids, direction, &paragraph[run.clone()]);
+
+            // the cluster starts in this run, each cluster runs up to the next one
+            let mut starts: Vec<usize> = glyphs.iter().map( | glyph | glyph.cluster).collect();
+            starts.sort_unstable();
+            starts.dedup();
+            let first = clusters.len();
+            for (i, &start) in starts.iter().enumerate() {
+                let end = starts.get(i + 1).copied().unwrap_or(run.len());
+                let start = range.start + run.start + start;
+                let end = range.start + run.start + end;
+                clusters.push(LogicalCluster {
+                    range: start..end,
+                    span: span_of_byte[start],
+                    level,
+                    width: 0.0,
+                    glyphs: Vec::new(),
+                });
+            }
+            for glyph in glyphs.iter() {
+                let units_per_em = fonts_atlas.fonts[glyph.font_id].as_ref().unwrap().ttf_font.units_per_em;
+                let advance = glyph.x_advance * style.font_size * 96.0 / (72.0 * units_per_em) * self.font_scale;
+                let index = first + starts.binary_search(&glyph.cluster).unwrap();
+                clusters[index].width += advance;
+                clusters[index].glyphs.push((*glyph, advance));
+            }
+        }
+        clusters
+    }
+
+    pub fn draw_attributed(&mut self, cx: &mut Cx2d, walk: Walk, align: Align, text: &AttributedText) -> RichTextLayout {
+        self.draw_attributed_with(cx, walk, align, text, | _, _, _ | {})
+    }
+
+    // Lays out and draws an attributed text as one paragraph. Backgrounds and underlines are
+    // handed to the callback to draw, backgrounds before the glyphs and underlines after.
+    pub fn draw_attributed_with<F>(&mut self, cx: &mut Cx2d, walk: Walk, align: Align, text: &AttributedText, mut cb: F) -> RichTextLayout where F: FnMut(&mut Cx2d, RichTextDecoration, Rect) {
+        if self.text_style.font.font_id.is_none() {
+            return RichTextLayout::default()
+        }
+        let fonts_atlas_rc = cx.fonts_atlas_rc.clone();
+
+        let eval_width = cx.turtle().eval_width(walk.width, walk.margin, cx.turtle().layout().flow);
+        let eval_height = cx.turtle().eval_height(walk.height, walk.margin, cx.turtle().layout().flow);
+        let wrap_width = if walk.width.is_fit() {f64::INFINITY} else {eval_width};
+
+        let mut layout = self.layout_attributed_inner(text, wrap_width, align.x, &mut fonts_atlas_rc.0.borrow_mut());
+        let width = if walk.width.is_fit() {layout.size.x} else {eval_width};
+        let height = if walk.height.is_fit() {layout.size.y} else {eval_height};
+        let rect = cx.walk_turtle(Walk {
+            abs_pos: walk.abs_pos,
+            margin: walk.margin,
+            width: Size::Fixed(width),
+            height: Size::Fixed(height)
+        });
+        layout.origin = rect.pos + dvec2(0.0, (height - layout.size.y) * align.y);
+
+        let base_color = self.color;
+        let mut backgrounds = Vec::new();
+        let mut underlines = Vec::new();
+        for (index, span) in text.spans.iter().enumerate() {
+            if let Some(color) = span.attrs.background {
+                for rect in layout.span_rects(index) {
+                    backgrounds.push((RichTextDecoration::Background(color), rect));
+                }
+            }
+            if span.attrs.underline {
+                let color = span.attrs.color.unwrap_or(base_color);
+                for (line_index, rect) in layout.line_rects( | cluster | cluster.span == index) {
+                    let line = &layout.lines[line_index];
+                    let thickness = (line.font_size * self.font_scale / 12.0).max(1.0);
+                    underlines.push((RichTextDecoration::Underline(color), Rect {
+                        pos: dvec2(rect.pos.x, rect.pos.y + line.baseline + thickness),
+                        size: dvec2(rect.size.x, thickness)
+                    }));
+                }
+            }
+        }
+
+        // the callbacks run without the atlas borrowed, so they can draw text themselves
+        for (decoration, rect) in backgrounds {
+            cb(cx, decoration, rect);
+        }
+
+        {
+            let mut fonts_atlas = fonts_atlas_rc.0.borrow_mut();
+            let fonts_atlas = &mut*fonts_atlas;
+            if self.many_instances.is_none() {
+                self.begin_many_instances_internal(cx, fonts_atlas);
+            }
+            let dpi_factor = cx.current_dpi_factor();
+            let mut char_depth = self.draw_depth;
+            let mut last_page = None;
+            for glyph in &layout.glyphs {
+                self.color = text.spans.get(glyph.span).and_then( | span | span.attrs.color).unwrap_or(base_color);
+                self.draw_glyph(fonts_atlas, dpi_factor, glyph.font_size, &glyph.shaped, layout.origin + glyph.pos, char_depth, &mut last_page);
+                char_depth += 0.00001;
+            }
+            self.color = base_color;
+        }
+        if self.many_instances.is_some() {
+            self.end_many_instances(cx)
+        }
+
+        for (decoration, rect) in underlines {
+            cb(cx, decoration, rect);
+        }
+        layout
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn apply_splits_spans() {
+        let mut text = AttributedText::new();
+        text.push_plain("hello ");
+        text.push("world", TextAttrs {underline: true, ..Default::default()});
+        text.apply(3..8, | attrs | attrs.link_id = Some(LiveId(1)));
+        let ranges: Vec<Range<usize>> = text.spans.iter().map( | span | span.range.clone()).collect();
+        assert_eq!(ranges, [0..3, 3..6, 6..8, 8..11]);
+        let links: Vec<Option<LiveId>> = text.spans.iter().map( | span | span.attrs.link_id).collect();
+        assert_eq!(links, [None, Some(LiveId(1)), Some(LiveId(1)), None]);
+        let underlines: Vec<bool> = text.spans.iter().map( | span | span.attrs.underline).collect();
+        assert_eq!(underlines, [false, false, true, true]);
+        assert_eq!(text.span_at(7), Some(2));
+        assert_eq!(text.span_at(11), None);
+    }
+
+    // "abc" over "d" and two right to left clusters, every cluster 10 wide and every line 20 high
+    fn layout() -> RichTextLayout {
+        let cluster = | range: Range<usize>, line, span, x, rtl | RichTextCluster {range, line, span, x, width: 10.0, rtl};
+        let line = | range: Range<usize>, y, width | RichTextLine {range, x: 0.0, y, width, height: 20.0, baseline: 15.0, font_size: 12.0};
+        RichTextLayout {
+            origin: dvec2(100.0, 50.0),
+            size: dvec2(30.0, 40.0),
+            glyphs: Vec::new(),
+            clusters: vec![
+                cluster(0..1, 0, 0, 0.0, false),
+                cluster(1..2, 0, 0, 10.0, false),
+                cluster(2..3, 0, 1, 20.0, false),
+                cluster(4..5, 1, 1, 0.0, false),
+                cluster(5..7, 1, 2, 20.0, true),
+                cluster(7..9, 1, 2, 10.0, true),
+            ],
+            lines: vec![line(0..3, 0.0, 30.0), line(4..9, 20.0, 30.0)],
+        }
+    }
+
+    #[test]
+    fn rects_merge_per_line() {
+        let layout = layout();
+        assert_eq!(layout.range_rects(0..3), [Rect {pos: dvec2(100.0, 50.0), size: dvec2(30.0, 20.0)}]);
+        assert_eq!(layout.span_rects(1), [
+            Rect {pos: dvec2(120.0, 50.0), size: dvec2(10.0, 20.0)},
+            Rect {pos: dvec2(100.0, 70.0), size: dvec2(10.0, 20.0)},
+        ]);
+        // the right to left clusters touch visually, in the opposite order
+        assert_eq!(layout.span_rects(2), [Rect {pos: dvec2(110.0, 70.0), size: dvec2(20.0, 20.0)}]);
+    }
+
+    #[test]
+    fn hit_tests() {
+        let layout = layout();
+        let mut text = AttributedText::new();
+        text.push_plain("ab");
+        text.push("c\nd", TextAttrs {link_id: Some(LiveId(7)), ..Default::default()});
+        assert_eq!(layout.span_at(dvec2(125.0, 55.0)), Some(1));
+        assert_eq!(layout.span_at(dvec2(135.0, 55.0)), None);
+        assert_eq!(layout.link_at(&text, dvec2(105.0, 75.0)), Some(LiveId(7)));
+        assert_eq!(layout.link_at(&text, dvec2(105.0, 55.0)), None);
+
+        assert_eq!(layout.offset_at(dvec2(104.0, 55.0)), 0);
+        assert_eq!(layout.offset_at(dvec2(106.0, 55.0)), 1);
+        assert_eq!(layout.offset_at(dvec2(90.0, 55.0)), 0);
+        assert_eq!(layout.offset_at(dvec2(200.0, 55.0)), 3);
+        // in right to left text the left half of a cluster is after it, below the last line
+        // is still the last line
+        assert_eq!(layout.offset_at(dvec2(112.0, 75.0)), 9);
+        assert_eq!(layout.offset_at(dvec2(118.0, 75.0)), 7);
+        assert_eq!(layout.offset_at(dvec2(200.0, 500.0)), 5);
+    }
+
+    #[test]
+    fn cursor_rects() {
+        let layout = layout();
+        let cursor = | x, y | Rect {pos: dvec2(x, y), size: dvec2(0.0, 20.0)};
+        assert_eq!(layout.cursor_rect(1), cursor(110.0, 50.0));
+        assert_eq!(layout.cursor_rect(3), cursor(130.0, 50.0));
+        assert_eq!(layout.cursor_rect(5), cursor(130.0, 70.0));
+        assert_eq!(layout.cursor_rect(9), cursor(110.0, 70.0));
+    }
+}
@@ -2,7 +2,8 @@ use {
     crate::{
         makepad_platform::*,
         turtle::{Walk, Size, Align},
-        font_atlas::{CxFontsAtlasTodo, CxFont, CxFontsAtlas, Font},
+        font_atlas::{CxFontsAtlasTodo, CxFontsAtlas, CxColorAtlasGlyph, Font, ShapedGlyph, COLOR_GLYPH_PAD},
+        color_glyph::fit_glyph_scale,
         draw_list_2d::ManyInstances,
         geometry::GeometryQuad2D,
         cx_2d::Cx2d
@@ -22,6 +23,7 @@ live_design!{
         uniform sdf_cutoff: float
         
         texture tex: texture2d
+        texture color_tex: texture2d
         
         varying tex_coord1: vec2
         varying tex_coord2: vec2
@@ -75,7 +77,22 @@ live_design!{
             return self.blend_color(vec4(s * col.rgb * self.brightness * col.a, s * col.a));
         }
         
+        fn sample_color_glyph(self, pos:vec2)->vec4{
+            // the color atlas is premultiplied, only the alpha of the text color applies, except
+            // for layers in the text color which come from a mask next to the glyph
+            let col = self.get_color();
+            let glyph = sample2d(self.color_tex, pos);
+            if self.color_glyph_tint > 0.0 {
+                let tint = sample2d(self.color_tex, pos + vec2(self.color_glyph_tint, 0.0)).x;
+                glyph = vec4(glyph.rgb + tint * col.rgb, glyph.a);
+            }
+            return self.blend_color(glyph * col.a);
+        }
+        
         fn pixel(self) -> vec4 {
+            if self.color_glyph > 0.5 {
+                return self.sample_color_glyph(self.tex_coord1.xy);
+            }
             let texel_coords = self.tex_coord1.xy;
             let dxt = length(dFdx(texel_coords));
             let dyt = length(dFdy(texel_coords));
@@ -142,6 +159,8 @@ const _: () = assert!(crate::font_atlas::ATLAS_HEIGHT == 4096);
 #[live_ignore]
 pub struct TextStyle {
     #[live()] pub font: Font,
+    // fonts tried in order for glyphs that `font` doesn't have
+    #[live] pub font_fallbacks: Vec<Font>,
     #[live(9.0)] pub font_size: f64,
     #[live(1.0)] pub brightness: f32,
     #[live(0.5)] pub curve: f32,
@@ -150,6 +169,32 @@ pub struct TextStyle {
     #[live(1.3)] pub height_factor: f64,
 }
 
+impl TextStyle {
+    // the loaded font chain, primary font first
+    pub fn font_ids(&self) -> SmallVec<[usize; 4]> {
+        let mut font_ids = SmallVec::new();
+        for font in std::iter::once(&self.font).chain(self.font_fallbacks.iter()) {
+            if let Some(font_id) = font.font_id {
+                if !font_ids.contains(&font_id) {
+                    font_ids.push(font_id);
+                }
+            }
+        }
+        font_ids
+    }
+}
+
+// A chunk of text as DrawText placed it, collected between `Cx2d::begin_text_runs` and
+// `end_text_runs` so a widget can hit test and select text it drew with several DrawTexts.
+#[derive(Clone, Debug, Default)]
+pub struct TextRun {
+    pub text: String,
+    // the line box of the chunk
+    pub rect: Rect,
+    // (byte range, x relative to the rect, width) of each cluster, in visual order
+    pub clusters: Vec<(std::ops::Range<usize>, f64, f64)>,
+}
+
 #[derive(Clone, Live, LiveHook)]
 #[live_ignore]
 pub enum TextWrap {
@@ -207,20 +252,20 @@ impl<'a> WordIterator<'a> {
         };
     }
     
-    fn next_word(&mut self, font: &mut CxFont) -> Option<WordItem> {
+    fn next_word(&mut self, fonts_atlas: &mut CxFontsAtlas, font_ids: &[usize]) -> Option<WordItem> {
         if self.last_char == '\0'{
             return None
         }
         else if self.last_char == '\n'{ // return newline
             self.next_char();
             if self.ignore_newlines{
-                return self.next_word(font);
+                return self.next_word(fonts_atlas, font_ids);
             }
             return Some(WordItem::Newline);
         }
         else if self.last_char == ' '{
-            let adv = if let Some(glyph) = font.get_glyph(' ') {
-                glyph.horizontal_metrics.advance_width * self.font_size_total
+            let adv = if let Some(advance) = fonts_atlas.get_glyph_advance(font_ids, ' ') {
+                advance * self.font_size_total
             }else {0.0};
             let start = self.last_index;
             let mut width = 0.0;
@@ -253,8 +298,8 @@ impl<'a> WordIterator<'a> {
             let start = self.last_index;
             let mut width = 0.0;
             while self.last_char != ' ' && self.last_char != '\0' && self.last_char != '\n' {
-                let adv = if let Some(glyph) = font.get_glyph(self.last_char) {
-                    glyph.horizontal_metrics.advance_width * self.font_size_total
+                let adv = if let Some(advance) = fonts_atlas.get_glyph_advance(font_ids, self.last_char) {
+                    advance * self.font_size_total
                 }else {0.0};
                 if width + adv >= self.eval_width{
                     if start == self.last_index{// advance atleast one char
@@ -312,6 +357,8 @@ pub struct DrawText {
     #[calc] pub delta: Vec2,
     #[calc] pub shader_font_size: f32,
     #[calc] pub advance: f32,
+    #[calc] pub color_glyph: f32,
+    #[calc] pub color_glyph_tint: f32,
 }
 
 impl LiveHook for DrawText {
@@ -352,7 +399,7 @@ impl DrawText {
         self.begin_many_instances_internal(cx, &*fonts_atlas);
     }
     
-    fn begin_many_instances_internal(&mut self, cx: &mut Cx2d, fonts_atlas: &CxFontsAtlas) {
+    pub(crate) fn begin_many_instances_internal(&mut self, cx: &mut Cx2d, fonts_atlas: &CxFontsAtlas) {
         self.update_draw_call_vars(fonts_atlas);
         let mi = cx.begin_many_aligned_instances(&self.draw_vars);
         self.many_instances = mi;
@@ -371,6 +418,7 @@ impl DrawText {
     
     pub fn update_draw_call_vars(&mut self, font_atlas: &CxFontsAtlas) {
         self.draw_vars.texture_slots[0] = Some(font_atlas.texture.clone());
+        self.draw_vars.texture_slots[1] = Some(font_atlas.color_texture.clone());
         self.draw_vars.user_uniforms[0] = self.text_style.brightness;
         self.draw_vars.user_uniforms[1] = self.text_style.curve;
         let (sdf_radius, sdf_cutoff) = font_atlas.alloc.sdf.as_ref()
@@ -388,9 +436,9 @@ impl DrawText {
         }
         //self.draw_clip = cx.turtle().draw_clip().into();
         //let in_many = self.many_instances.is_some();
-        let font_id = self.text_style.font.font_id.unwrap();
+        let font_ids = self.text_style.font_ids();
         
-        if fonts_atlas.fonts[font_id].is_none() {
+        if font_ids.first().is_none_or(|font_id| fonts_atlas.fonts[*font_id].is_none()) {
             return
         }
         
@@ -404,24 +452,15 @@ impl DrawText {
             self.begin_many_instances_internal(cx, fonts_atlas);
         }
         
-        let cxfont = fonts_atlas.fonts[font_id].as_mut().unwrap();
         let dpi_factor = cx.current_dpi_factor();
         
-        let atlas_page_id = cxfont.get_atlas_page_id(dpi_factor, self.text_style.font_size);
-        
-        let font = &mut cxfont.ttf_font;
-        let owned_font_face = &cxfont.owned_font_face;
-        
-        let font_size_logical = self.text_style.font_size * 96.0 / (72.0 * font.units_per_em);
-        let font_size_pixels = font_size_logical * dpi_factor;
-        
-        let atlas_page = &mut cxfont.atlas_pages[atlas_page_id];
-        
-        let mi = if let Some(mi) = &mut self.many_instances {mi} else {return};
+        if self.many_instances.is_none() {
+            return
+        }
         let zbias_step = 0.00001;
         let mut char_depth = self.draw_depth;
-        
-        let mut rustybuzz_buffer = makepad_rustybuzz::UnicodeBuffer::new();
+        let mut last_page = None;
+        let mut run = if cx.is_recording_text_runs() {Some(TextRun::default())} else {None};
         
         // This relies on the UBA ("Unicode Bidirectional Algorithm")
         // (see http://www.unicode.org/reports/tr9/#Basic_Display_Algorithm),
@@ -442,79 +481,164 @@ impl DrawText {
             for (run_level, run_range) in runs_with_level_and_range {
                 // FIXME(eddyb) UBA/`unicode_bidi` only offers a LTR/RTL distinction,
                 // even if `rustybuzz` has vertical `Direction`s as well.
-                let (glyph_ids, new_rustybuzz_buffer) = cxfont
-                    .shape_cache
-                    .get_or_compute_glyph_ids(
-                    (
-                            if run_level.is_rtl() {
-                                makepad_rustybuzz::Direction::RightToLeft
-                            } else {
-                                makepad_rustybuzz::Direction::LeftToRight
-                            },
-                            &bidi_info.text[run_range]
-                        ),
-                        rustybuzz_buffer,
-                        owned_font_face
-                    );
-                rustybuzz_buffer = new_rustybuzz_buffer;
-                for &glyph_id in glyph_ids {
-                    let glyph = owned_font_face.with_ref(|face| font.get_glyph_by_id(face, glyph_id).unwrap());
-                    
-                    let advance = glyph.horizontal_metrics.advance_width * font_size_logical * self.font_scale;
-                    
-                    // HACK(eddyb) this is a different padding from the SDF padding,
-                    // this allows the glyph rasterization to avoid touching the
-                    // edges of the raster area, while the SDF padding exists for
-                    // e.g. bilinear sampling to have excess texels to sample.
-                    let pad_dpx = 2.0;
-                    let w_dpx = ((glyph.bounds.p_max.x - glyph.bounds.p_min.x) * font_size_pixels).ceil() + pad_dpx * 2.0;
-                    let h_dpx = ((glyph.bounds.p_max.y - glyph.bounds.p_min.y) * font_size_pixels).ceil() + pad_dpx * 2.0;
-                    let (w_dpx, h_dpx) = if w_dpx <= pad_dpx * 2.0{(0.0,0.0)}else { (w_dpx, h_dpx) };
-                                        
-                    let tc = *atlas_page.atlas_glyphs.entry(glyph_id).or_insert_with(|| {
-                        // see if we can fit it
-                        // allocate slot
-                        fonts_atlas.alloc.alloc_atlas_glyph(w_dpx, h_dpx, CxFontsAtlasTodo {
-                            font_id,
-                            atlas_page_id,
-                            glyph_id,
-                        })
-                    });
-
-                    let pad = pad_dpx * self.font_scale / dpi_factor;
-                    let w = w_dpx * self.font_scale / dpi_factor;
-                    let h = h_dpx * self.font_scale / dpi_factor;
-                    
-                    let delta_x = font_size_logical * self.font_scale * glyph.bounds.p_min.x - pad;
-                    let delta_y = -(font_size_logical * self.font_scale * glyph.bounds.p_min.y - pad)
-                        + self.text_style.font_size * self.font_scale * self.text_style.top_drop;
-                    // give the callback a chance to do things
-                    //et scaled_min_pos_x = walk_x + delta_x;
-                    //let scaled_min_pos_y = pos.y - delta_y;
-                    self.font_t1 = tc.t1;
-                    self.font_t2 = tc.t2;
-                    self.rect_pos = dvec2(walk_x + delta_x, pos.y + delta_y).into();
-                    self.rect_size = dvec2(w, h).into();
-                    self.char_depth = char_depth;
-                    self.delta.x = delta_x as f32;
-                    self.delta.y = delta_y as f32;
-                    self.shader_font_size = self.text_style.font_size as f32;
-                    self.advance = advance as f32; //char_offset as f32;
-                    char_depth += zbias_step;
-                    mi.instances.extend_from_slice(self.draw_vars.as_slice());
+                // Every glyph comes from the first font in the chain that covers its cluster.
+                let glyphs = fonts_atlas.shape_with_fallbacks(
+                    &font_ids,
+                    if run_level.is_rtl() {
+                        makepad_rustybuzz::Direction::RightToLeft
+                    } else {
+                        makepad_rustybuzz::Direction::LeftToRight
+                    },
+                    &bidi_info.text[run_range.clone()]
+                );
+                for shaped in glyphs.iter() {
+                    let advance = self.draw_glyph(fonts_atlas, dpi_factor, self.text_style.font_size, shaped, dvec2(walk_x, pos.y), char_depth, &mut last_page);
+                    if let Some(run) = &mut run {
+                        let start = run_range.start + shaped.cluster;
+                        match run.clusters.last_mut() {
+                            Some(last) if last.0.start == start => last.2 += advance,
+                            _ => run.clusters.push((start..start, walk_x - pos.x, advance))
+                        }
+                    }
                     walk_x += advance;
+                    char_depth += zbias_step;
                 }
             }
         }
         
+        if let Some(mut run) = run {
+            // a cluster runs up to the start of the next one in logical order
+            let mut starts: Vec<usize> = run.clusters.iter().map( | cluster | cluster.0.start).collect();
+            starts.sort_unstable();
+            for cluster in &mut run.clusters {
+                let next = starts.partition_point( | start | *start <= cluster.0.start);
+                cluster.0.end = starts.get(next).copied().unwrap_or(chunk.len());
+            }
+            run.text = chunk.to_string();
+            run.rect = Rect {
+                pos,
+                size: dvec2(walk_x - pos.x, self.text_style.font_size * self.text_style.height_factor * self.font_scale)
+            };
+            cx.push_text_run(run);
+        }
     }
+    // Adds the instance of one shaped glyph, where `pos` is the top left of the line it sits on,
+    // and returns its advance. `last_page` remembers the font, size and atlas page of the previous
+    // glyph, so a run of glyphs doesn't look up the page for each of them.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn draw_glyph(&mut self, fonts_atlas: &mut CxFontsAtlas, dpi_factor: f64, font_size: f64, shaped: &ShapedGlyph, pos: DVec2, char_depth: f32, last_page: &mut Option<(usize, f64, usize)>) -> f64 {
+        let (font_id, glyph_id) = (shaped.font_id, shaped.glyph_id);
+        let cxfont = fonts_atlas.fonts[font_id].as_mut().unwrap();
+
+        let atlas_page_id = match *last_page {
+            Some((last_font_id, last_font_size, atlas_page_id)) if last_font_id == font_id && last_font_size == font_size => atlas_page_id,
+            _ => cxfont.get_atlas_page_id(dpi_factor, font_size)
+        };
+        *last_page = Some((font_id, font_size, atlas_page_id));
+
+        let font = &mut cxfont.ttf_font;
+        let font_size_logical = font_size * 96.0 / (72.0 * font.units_per_em);
+        let font_size_pixels = font_size_logical * dpi_factor;
+
+        let glyph = cxfont.owned_font_face.with_ref(|face| font.get_glyph_by_id(face, glyph_id).unwrap());
+
+        let advance = glyph.horizontal_metrics.advance_width * font_size_logical * self.font_scale;
+        let outline_bounds = glyph.bounds;
+
+        // glyphs that have a color version are drawn from the color atlas instead
+        let color_glyph = if cxfont.has_color_glyphs() {
+            if let Some(color_glyph) = cxfont.atlas_pages[atlas_page_id].color_glyphs.get(&glyph_id) {
+                *color_glyph
+            }
+            else {
+                let pixels_per_em = font_size_pixels * cxfont.ttf_font.units_per_em;
+                let is_tinted = cxfont.colr.as_ref().is_some_and(|colr| colr.has_foreground_layers(glyph_id));
+                let color_glyph = cxfont.get_color_glyph_bounds(glyph_id, pixels_per_em).map(|bounds| {
+                    let texture_size = fonts_atlas.color_alloc.texture_size;
+                    let pixels_per_unit = fit_glyph_scale(&bounds, font_size_pixels, COLOR_GLYPH_PAD, is_tinted, texture_size.x as usize, texture_size.y as usize);
+                    let w_dpx = ((bounds.p_max.x - bounds.p_min.x) * pixels_per_unit).ceil() as usize + COLOR_GLYPH_PAD * 2;
+                    let h_dpx = ((bounds.p_max.y - bounds.p_min.y) * pixels_per_unit).ceil() as usize + COLOR_GLYPH_PAD * 2;
+                    // the text color changes too often to bake it in, so these get room for their mask
+                    let mut tc = fonts_atlas.color_alloc.alloc_atlas_rect(if is_tinted {w_dpx * 2} else {w_dpx}, h_dpx, 0, CxFontsAtlasTodo {
+                        font_id,
+                        atlas_page_id,
+                        glyph_id,
+                    });
+                    let tint_offset = if is_tinted {(w_dpx as f64 / fonts_atlas.color_alloc.texture_size.x) as f32} else {0.0};
+                    tc.t2.x -= tint_offset;
+                    CxColorAtlasGlyph {bounds, tc, pixels_per_unit, tint_offset}
+                });
+                cxfont.atlas_pages[atlas_page_id].color_glyphs.insert(glyph_id, color_glyph);
+                color_glyph
+            }
+        }
+        else {
+            None
+        };
+
+        // HACK(eddyb) this is a different padding from the SDF padding,
+        // this allows the glyph rasterization to avoid touching the
+        // edges of the raster area, while the SDF padding exists for
+        // e.g. bilinear sampling to have excess texels to sample.
+        let (bounds, pad_dpx) = match &color_glyph {
+            // a raster drawn smaller gets stretched, border and all
+            Some(color_glyph) => (color_glyph.bounds, COLOR_GLYPH_PAD as f64 * font_size_pixels / color_glyph.pixels_per_unit),
+            None => (outline_bounds, 2.0)
+        };
+        let w_dpx = ((bounds.p_max.x - bounds.p_min.x) * font_size_pixels).ceil() + pad_dpx * 2.0;
+        let h_dpx = ((bounds.p_max.y - bounds.p_min.y) * font_size_pixels).ceil() + pad_dpx * 2.0;
+        let (w_dpx, h_dpx) = if w_dpx <= pad_dpx * 2.0{(0.0,0.0)}else { (w_dpx, h_dpx) };
+
+        let tc = if let Some(color_glyph) = &color_glyph {
+            color_glyph.tc
+        }
+        else {
+            *cxfont.atlas_pages[atlas_page_id].atlas_glyphs.entry(glyph_id).or_insert_with(|| {
+                // see if we can fit it
+                // allocate slot
+                fonts_atlas.alloc.alloc_atlas_glyph(w_dpx, h_dpx, CxFontsAtlasTodo {
+                    font_id,
+                    atlas_page_id,
+                    glyph_id,
+                })
+            })
+        };
+
+        let pad = pad_dpx * self.font_scale / dpi_factor;
+        let w = w_dpx * self.font_scale / dpi_factor;
+        let h = h_dpx * self.font_scale / dpi_factor;
+
+        let delta_x = font_size_logical * self.font_scale * bounds.p_min.x - pad;
+        let delta_y = -(font_size_logical * self.font_scale * bounds.p_min.y - pad)
+            + font_size * self.font_scale * self.text_style.top_drop;
+        // give the callback a chance to do things
+        //et scaled_min_pos_x = walk_x + delta_x;
+        //let scaled_min_pos_y = pos.y - delta_y;
+        self.font_t1 = tc.t1;
+        self.font_t2 = tc.t2;
+        self.rect_pos = dvec2(pos.x + delta_x, pos.y + delta_y).into();
+        self.rect_size = dvec2(w, h).into();
+        self.char_depth = char_depth;
+        self.delta.x = delta_x as f32;
+        self.delta.y = delta_y as f32;
+        self.shader_font_size = font_size as f32;
+        self.advance = advance as f32; //char_offset as f32;
+        self.color_glyph = if color_glyph.is_some() {1.0} else {0.0};
+        self.color_glyph_tint = color_glyph.map_or(0.0, |color_glyph| color_glyph.tint_offset);
+        if let Some(mi) = &mut self.many_instances {
+            mi.instances.extend_from_slice(self.draw_vars.as_slice());
+        }
+        advance
+    }
+    
     pub fn compute_geom(&self, cx: &Cx2d, walk: Walk, text: &str) -> Option<TextGeom> {
         self.compute_geom_inner(cx, walk, text, &mut *cx.fonts_atlas_rc.0.borrow_mut())
     }
     
     fn compute_geom_inner(&self, cx: &Cx2d, walk: Walk, text: &str, fonts_atlas: &mut CxFontsAtlas) -> Option<TextGeom> {
         // we include the align factor and the width/height
-        let font_id = self.text_style.font.font_id.unwrap();
+        let font_ids = self.text_style.font_ids();
+        let font_id = *font_ids.first()?;
         
         if fonts_atlas.fonts[font_id].is_none() {
             return None
@@ -527,8 +651,8 @@ impl DrawText {
         
         match if walk.width.is_fit() {&TextWrap::Line}else {&self.wrap} {
             TextWrap::Ellipsis => {
-                let ellip_width = if let Some(glyph) = fonts_atlas.fonts[font_id].as_mut().unwrap().get_glyph('.') {
-                    glyph.horizontal_metrics.advance_width * font_size_logical * self.font_scale
+                let ellip_width = if let Some(advance) = fonts_atlas.get_glyph_advance(&font_ids, '.') {
+                    advance * font_size_logical * self.font_scale
                 }
                 else {
                     0.0
@@ -541,8 +665,8 @@ impl DrawText {
                     if measured_width + ellip_width * 3.0 < eval_width {
                         ellip_pt = Some((i, measured_width, 3));
                     }
-                    if let Some(glyph) = fonts_atlas.fonts[font_id].as_mut().unwrap().get_glyph(c) {
-                        let adv = glyph.horizontal_metrics.advance_width * font_size_logical * self.font_scale;
+                    if let Some(advance) = fonts_atlas.get_glyph_advance(&font_ids, c) {
+                        let adv = advance * font_size_logical * self.font_scale;
                         // ok so now what.
                         if measured_width + adv >= eval_width { // we have to drop back to ellip_pt
                             // if we don't have an ellip_pt, set it to 0
@@ -584,7 +708,7 @@ impl DrawText {
                     self.ignore_newlines,
                     self.combine_spaces,
                 );
-                while let Some(word) = iter.next_word(fonts_atlas.fonts[font_id].as_mut().unwrap()) {
+                while let Some(word) = iter.next_word(fonts_atlas, &font_ids) {
                     match word{
                         WordItem::Newline=>{
                             measured_height += line_height * self.text_style.line_spacing;
@@ -620,8 +744,8 @@ impl DrawText {
                     if c == '\n' {
                         measured_height += line_height * self.text_style.line_spacing;
                     }
-                    if let Some(glyph) = fonts_atlas.fonts[font_id].as_mut().unwrap().get_glyph(c) {
-                        let adv = glyph.horizontal_metrics.advance_width * font_size_logical * self.font_scale;
+                    if let Some(advance) = fonts_atlas.get_glyph_advance(&font_ids, c) {
+                        let adv = advance * font_size_logical * self.font_scale;
                         measured_width += adv;
                     }
                     if measured_width > max_width {
@@ -652,6 +776,7 @@ impl DrawText {
             //log!("Draw text without font");
             return
         };
+        let font_ids = self.text_style.font_ids();
         let fonts_atlas_rc = cx.fonts_atlas_rc.clone();
         let mut fonts_atlas = fonts_atlas_rc.0.borrow_mut();
         let fonts_atlas = &mut*fonts_atlas;
@@ -671,7 +796,7 @@ impl DrawText {
             self.combine_spaces,
         );
         let mut last_rect = None;
-        while let Some(word) = iter.next_word(fonts_atlas.fonts[font_id].as_mut().unwrap()) {
+        while let Some(word) = iter.next_word(fonts_atlas, &font_ids) {
             match word{
                 WordItem::Newline=>{
                     cx.turtle_new_line();
@@ -717,6 +842,7 @@ impl DrawText {
             //log!("Draw text without font");
             return
         };
+        let font_ids = self.text_style.font_ids();
         let fonts_atlas_rc = cx.fonts_atlas_rc.clone();
         let mut fonts_atlas = fonts_atlas_rc.0.borrow_mut();
         let fonts_atlas = &mut*fonts_atlas;
@@ -797,7 +923,7 @@ impl DrawText {
                         self.ignore_newlines,
                         self.combine_spaces,    
                     );
-                    while let Some(word) = iter.next_word(fonts_atlas.fonts[font_id].as_mut().unwrap()) {
+                    while let Some(word) = iter.next_word(fonts_atlas, &font_ids) {
                         match word{
                             WordItem::Newline=>{
                                 pos.y += line_height * self.text_style.line_spacing;
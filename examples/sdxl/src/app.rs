@@ -532,6 +532,7 @@ impl App {
         start_http_server(HttpServer {
             listen_address: addr,
             post_max_size: 1024 * 1024,
+            web_socket_max_message_size: WEB_SOCKET_DEFAULT_MAX_MESSAGE_SIZE,
             request: tx_request
         });
         let remote_screens = self.remote_screens.clone();
@@ -556,7 +557,11 @@ impl App {
                     }
                     HttpServerRequest::Get {headers:_, response_sender:_} => {
                     }
-                    HttpServerRequest::Post {..} => { //headers, body, response}=>{
+                    HttpServerRequest::Post {response, ..} => {
+                        let _ = response.send(HttpServerResponse::method_not_allowed("GET"));
+                    }
+                    HttpServerRequest::Request {response_sender, ..} => {
+                        let _ = response_sender.send(HttpServerResponse::method_not_allowed("GET"));
                     }
                 }
             }
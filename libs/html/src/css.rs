@@ -0,0 +1,774 @@
+use {
+    makepad_live_id::*,
+    crate::{HtmlDoc, HtmlNode, HtmlError, is_void_element},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HtmlCombinator{
+    Descendant,
+    Child
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HtmlAttrOp{
+    Exists,
+    Equals,
+    // ~= one of the whitespace separated words
+    Includes,
+    // |= equal or followed by a -
+    DashMatch,
+    Prefix,
+    Suffix,
+    Contains
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HtmlAttrSelector{
+    pub lc: LiveId,
+    pub op: HtmlAttrOp,
+    pub value: String,
+}
+
+// tag#id.class[attr] without combinators, an empty one matches anything
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HtmlCompound{
+    pub tag_lc: Option<LiveId>,
+    pub id: Option<String>,
+    pub classes: Vec<String>,
+    pub attrs: Vec<HtmlAttrSelector>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HtmlSelectorPart{
+    // how this part relates to the part before it
+    pub combinator: HtmlCombinator,
+    pub compound: HtmlCompound,
+}
+
+// a selector like `div.note > p a[href^="http"], h1#title`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct HtmlSelector{
+    pub alternatives: Vec<Vec<HtmlSelectorPart>>,
+}
+
+impl HtmlCompound{
+    fn specificity(&self)->u32{
+        self.id.is_some() as u32 * 10000 + (self.classes.len() + self.attrs.len()) as u32 * 100 + self.tag_lc.is_some() as u32
+    }
+
+    pub fn matches(&self, doc:&HtmlDoc, index:usize)->bool{
+        if let Some(tag_lc) = self.tag_lc{
+            if doc.tag_lc(index) != Some(tag_lc){
+                return false
+            }
+        }
+        if let Some(id) = &self.id{
+            if doc.attr_lc(index, live_id!(id)) != Some(id.as_str()){
+                return false
+            }
+        }
+        if !self.classes.is_empty(){
+            let class = doc.attr_lc(index, live_id!(class)).unwrap_or("");
+            if !self.classes.iter().all(|c| class.split_whitespace().any(|v| v == c)){
+                return false
+            }
+        }
+        self.attrs.iter().all(|attr|{
+            let Some(v) = doc.attr_lc(index, attr.lc) else{
+                return false
+            };
+            let want = attr.value.as_str();
+            match attr.op{
+                HtmlAttrOp::Exists=>true,
+                HtmlAttrOp::Equals=>v == want,
+                HtmlAttrOp::Includes=>v.split_whitespace().any(|v| v == want),
+                HtmlAttrOp::DashMatch=>v == want || v.strip_prefix(want).is_some_and(|v| v.starts_with('-')),
+                HtmlAttrOp::Prefix=>!want.is_empty() && v.starts_with(want),
+                HtmlAttrOp::Suffix=>!want.is_empty() && v.ends_with(want),
+                HtmlAttrOp::Contains=>!want.is_empty() && v.contains(want),
+            }
+        })
+    }
+}
+
+impl HtmlSelector{
+    pub fn parse(selector:&str)->Result<Self, HtmlError>{
+        let b = selector.as_bytes();
+        let mut pos = 0;
+        let err = |message:&str, position:usize| HtmlError{message:message.into(), position};
+        fn is_ident(c:u8)->bool{
+            c.is_ascii_alphanumeric() || c == b'-' || c == b'_' || c >= 0x80
+        }
+        let ident = |pos:&mut usize|->&str{
+            let start = *pos;
+            while *pos < b.len() && is_ident(b[*pos]){
+                *pos += 1;
+            }
+            &selector[start..*pos]
+        };
+        let skip_ws = |pos:&mut usize|{
+            while *pos < b.len() && b[*pos].is_ascii_whitespace(){
+                *pos += 1;
+            }
+        };
+        let mut out = HtmlSelector::default();
+        let mut parts = Vec::new();
+        let mut combinator = HtmlCombinator::Descendant;
+        loop{
+            skip_ws(&mut pos);
+            if pos >= b.len() || b[pos] == b','{
+                if parts.is_empty() || combinator == HtmlCombinator::Child{
+                    return Err(err("Empty selector", pos))
+                }
+                out.alternatives.push(std::mem::take(&mut parts));
+                if pos >= b.len(){
+                    return Ok(out)
+                }
+                pos += 1;
+                continue;
+            }
+            if b[pos] == b'>'{
+                if parts.is_empty() || combinator == HtmlCombinator::Child{
+                    return Err(err("Unexpected >", pos))
+                }
+                combinator = HtmlCombinator::Child;
+                pos += 1;
+                continue;
+            }
+            let start = pos;
+            let mut compound = HtmlCompound::default();
+            while pos < b.len(){
+                match b[pos]{
+                    b'*' if pos == start=>{
+                        pos += 1;
+                    }
+                    b'#'=>{
+                        pos += 1;
+                        compound.id = Some(ident(&mut pos).to_string());
+                    }
+                    b'.'=>{
+                        pos += 1;
+                        compound.classes.push(ident(&mut pos).to_string());
+                    }
+                    b'['=>{
+                        pos += 1;
+                        skip_ws(&mut pos);
+                        let name = ident(&mut pos);
+                        skip_ws(&mut pos);
+                        let op = match b.get(pos){
+                            Some(b']')=>HtmlAttrOp::Exists,
+                            Some(b'=')=>HtmlAttrOp::Equals,
+                            Some(b'~')=>HtmlAttrOp::Includes,
+                            Some(b'|')=>HtmlAttrOp::DashMatch,
+                            Some(b'^')=>HtmlAttrOp::Prefix,
+                            Some(b'$')=>HtmlAttrOp::Suffix,
+                            Some(b'*')=>HtmlAttrOp::Contains,
+                            _=>return Err(err("Expected ] or an attribute operator", pos))
+                        };
+                        let mut value = String::new();
+                        if op != HtmlAttrOp::Exists{
+                            pos += if op == HtmlAttrOp::Equals{1}else{2};
+                            if op != HtmlAttrOp::Equals && b.get(pos - 1) != Some(&b'='){
+                                return Err(err("Expected =", pos - 1))
+                            }
+                            skip_ws(&mut pos);
+                            value = match b.get(pos){
+                                Some(q @ (b'"' | b'\''))=>{
+                                    let Some(end) = selector[pos + 1..].find(*q as char) else{
+                                        return Err(err("Unterminated string", pos))
+                                    };
+                                    let value = &selector[pos + 1..pos + 1 + end];
+                                    pos += end + 2;
+                                    value
+                                }
+                                _=>ident(&mut pos)
+                            }.to_string();
+                            skip_ws(&mut pos);
+                        }
+                        if name.is_empty() || b.get(pos) != Some(&b']'){
+                            return Err(err("Invalid attribute selector", pos))
+                        }
+                        pos += 1;
+                        compound.attrs.push(HtmlAttrSelector{lc:LiveId::from_str_lc(name), op, value});
+                    }
+                    c if is_ident(c) && pos == start=>{
+                        compound.tag_lc = Some(LiveId::from_str_lc(ident(&mut pos)));
+                    }
+                    c if c.is_ascii_whitespace() || c == b',' || c == b'>'=>break,
+                    _=>return Err(err("Unsupported selector", pos))
+                }
+            }
+            if compound.id.as_ref().is_some_and(|v| v.is_empty()) || compound.classes.iter().any(|v| v.is_empty()){
+                return Err(err("Expected a name", pos))
+            }
+            parts.push(HtmlSelectorPart{combinator, compound});
+            combinator = HtmlCombinator::Descendant;
+        }
+    }
+
+    // the highest specificity of the alternatives that match, None if none do
+    pub fn match_specificity(&self, doc:&HtmlDoc, index:usize, ancestors:&[usize])->Option<u32>{
+        fn match_parts(doc:&HtmlDoc, parts:&[HtmlSelectorPart], index:usize, ancestors:&[usize])->bool{
+            let (last, rest) = parts.split_last().unwrap();
+            if !last.compound.matches(doc, index){
+                return false
+            }
+            if rest.is_empty(){
+                return true
+            }
+            match last.combinator{
+                HtmlCombinator::Child=>ancestors.split_last().is_some_and(|(parent, up)| match_parts(doc, rest, *parent, up)),
+                HtmlCombinator::Descendant=>(0..ancestors.len()).rev().any(|i| match_parts(doc, rest, ancestors[i], &ancestors[..i]))
+            }
+        }
+        self.alternatives.iter()
+            .filter(|parts| match_parts(doc, parts, index, ancestors))
+            .map(|parts| parts.iter().map(|p| p.compound.specificity()).sum())
+            .max()
+    }
+
+    pub fn matches(&self, doc:&HtmlDoc, index:usize, ancestors:&[usize])->bool{
+        self.match_specificity(doc, index, ancestors).is_some()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CssLength{
+    Px(f64),
+    // relative to the font size of the element, percentages end up here too
+    Em(f64),
+    // relative to the root font size
+    Rem(f64),
+}
+
+impl CssLength{
+    pub const ROOT_FONT_PX: f64 = 16.0;
+
+    pub fn parse(value:&str)->Option<Self>{
+        let value = value.trim();
+        let split = value.find(|c:char| c.is_ascii_alphabetic() || c == '%').unwrap_or(value.len());
+        let num:f64 = value[..split].parse().ok()?;
+        Some(match &value[split..]{
+            "px"=>CssLength::Px(num),
+            ""  if num == 0.0=>CssLength::Px(0.0),
+            "pt"=>CssLength::Px(num * 4.0 / 3.0),
+            "em"=>CssLength::Em(num),
+            "%"=>CssLength::Em(num / 100.0),
+            "rem"=>CssLength::Rem(num),
+            _=>return None
+        })
+    }
+
+    pub fn to_px(&self, font_px:f64)->f64{
+        match self{
+            CssLength::Px(v)=>*v,
+            CssLength::Em(v)=>v * font_px,
+            CssLength::Rem(v)=>v * Self::ROOT_FONT_PX,
+        }
+    }
+}
+
+// the properties the html renderer understands, None where nothing was declared
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CssStyle{
+    // rgba as 0xRRGGBBAA
+    pub color: Option<u32>,
+    pub font_size: Option<CssLength>,
+    // 400 is normal, 700 is bold
+    pub font_weight: Option<u16>,
+    pub italic: Option<bool>,
+    pub underline: Option<bool>,
+    pub strikethrough: Option<bool>,
+    pub margin_top: Option<CssLength>,
+    pub margin_right: Option<CssLength>,
+    pub margin_bottom: Option<CssLength>,
+    pub margin_left: Option<CssLength>,
+    pub display_none: Option<bool>,
+}
+
+impl CssStyle{
+    // the body of a style attribute or of a rule, like `color: red; margin: 0 4px`
+    pub fn parse_declarations(decls:&str)->Self{
+        let mut style = CssStyle::default();
+        for decl in decls.split(';'){
+            if let Some((name, value)) = decl.split_once(':'){
+                let value = value.trim();
+                let value = value.strip_suffix("!important").unwrap_or(value).trim();
+                style.apply_declaration(&name.trim().to_ascii_lowercase(), value);
+            }
+        }
+        style
+    }
+
+    // unknown properties and values that don't parse are ignored like a browser would
+    pub fn apply_declaration(&mut self, name:&str, value:&str){
+        let lc = value.to_ascii_lowercase();
+        match name{
+            "color"=>if let Some(c) = parse_css_color(&lc){
+                self.color = Some(c)
+            }
+            "font-size"=>if let Some(v) = parse_font_size(&lc){
+                self.font_size = Some(v)
+            }
+            "font-weight"=>if let Some(v) = match lc.as_str(){
+                "normal" | "lighter"=>Some(400),
+                "bold" | "bolder"=>Some(700),
+                v=>v.parse().ok()
+            }{
+                self.font_weight = Some(v)
+            }
+            "font-style"=>self.italic = Some(lc == "italic" || lc == "oblique"),
+            "text-decoration" | "text-decoration-line"=>{
+                self.underline = Some(lc.contains("underline"));
+                self.strikethrough = Some(lc.contains("line-through"));
+            }
+            "margin"=>{
+                let v:Option<Vec<_>> = lc.split_whitespace().map(parse_margin).collect();
+                let (top, right, bottom, left) = match v.as_deref(){
+                    Some([a])=>(*a, *a, *a, *a),
+                    Some([a, b])=>(*a, *b, *a, *b),
+                    Some([a, b, c])=>(*a, *b, *c, *b),
+                    Some([a, b, c, d])=>(*a, *b, *c, *d),
+                    _=>return
+                };
+                self.margin_top = top;
+                self.margin_right = right;
+                self.margin_bottom = bottom;
+                self.margin_left = left;
+            }
+            "margin-top"=>if let Some(v) = parse_margin(&lc){self.margin_top = v},
+            "margin-right"=>if let Some(v) = parse_margin(&lc){self.margin_right = v},
+            "margin-bottom"=>if let Some(v) = parse_margin(&lc){self.margin_bottom = v},
+            "margin-left"=>if let Some(v) = parse_margin(&lc){self.margin_left = v},
+            "display"=>self.display_none = Some(lc == "none"),
+            _=>()
+        }
+    }
+
+    // the declarations in other win
+    pub fn merge(&mut self, other:&CssStyle){
+        fn set<T:Clone>(a:&mut Option<T>, b:&Option<T>){
+            if b.is_some(){
+                *a = b.clone();
+            }
+        }
+        set(&mut self.color, &other.color);
+        set(&mut self.font_size, &other.font_size);
+        set(&mut self.font_weight, &other.font_weight);
+        set(&mut self.italic, &other.italic);
+        set(&mut self.underline, &other.underline);
+        set(&mut self.strikethrough, &other.strikethrough);
+        set(&mut self.margin_top, &other.margin_top);
+        set(&mut self.margin_right, &other.margin_right);
+        set(&mut self.margin_bottom, &other.margin_bottom);
+        set(&mut self.margin_left, &other.margin_left);
+        set(&mut self.display_none, &other.display_none);
+    }
+
+    pub fn is_empty(&self)->bool{
+        *self == CssStyle::default()
+    }
+}
+
+// Some(None) is a valid margin of auto, which we don't lay out
+fn parse_margin(value:&str)->Option<Option<CssLength>>{
+    if value == "auto"{
+        return Some(None)
+    }
+    CssLength::parse(value).map(Some)
+}
+
+fn parse_font_size(value:&str)->Option<CssLength>{
+    Some(match value{
+        "xx-small"=>CssLength::Px(9.0),
+        "x-small"=>CssLength::Px(10.0),
+        "small"=>CssLength::Px(13.0),
+        "medium"=>CssLength::Px(16.0),
+        "large"=>CssLength::Px(18.0),
+        "x-large"=>CssLength::Px(24.0),
+        "xx-large"=>CssLength::Px(32.0),
+        "smaller"=>CssLength::Em(0.83),
+        "larger"=>CssLength::Em(1.2),
+        v=>CssLength::parse(v)?
+    })
+}
+
+pub fn parse_css_color(value:&str)->Option<u32>{
+    let value = value.trim();
+    if let Some(hex) = value.strip_prefix('#'){
+        if !hex.bytes().all(|c| c.is_ascii_hexdigit()){
+            return None
+        }
+        let v = u32::from_str_radix(hex, 16).ok()?;
+        // expand every nibble of the short forms into a byte
+        let expand = |v:u32, n:u32|->u32{
+            (0..n).rev().fold(0, |acc, i| {let d = (v >> (i * 4)) & 0xf; (acc << 8) | (d << 4) | d})
+        };
+        return match hex.len(){
+            3=>Some(expand(v, 3) << 8 | 0xff),
+            4=>Some(expand(v, 4)),
+            6=>Some(v << 8 | 0xff),
+            8=>Some(v),
+            _=>None
+        }
+    }
+    if let Some(args) = value.strip_prefix("rgba(").or_else(|| value.strip_prefix("rgb(")){
+        let args = args.strip_suffix(')')?;
+        let args:Vec<&str> = args.split([',', ' ', '/']).filter(|v| !v.is_empty()).collect();
+        if args.len() != 3 && args.len() != 4{
+            return None
+        }
+        let channel = |v:&str, scale:f64|->Option<u32>{
+            let v = match v.strip_suffix('%'){
+                Some(p)=>p.parse::<f64>().ok()? / 100.0 * 255.0,
+                None=>v.parse::<f64>().ok()? * scale
+            };
+            Some(v.round().clamp(0.0, 255.0) as u32)
+        };
+        let mut c = 0;
+        for v in &args[0..3]{
+            c = c << 8 | channel(v, 1.0)?;
+        }
+        let a = if let Some(a) = args.get(3){channel(a, 255.0)?}else{255};
+        return Some(c << 8 | a)
+    }
+    Some(match value{
+        "transparent"=>0x00000000,
+        "black"=>0x000000ff,
+        "white"=>0xffffffff,
+        "gray" | "grey"=>0x808080ff,
+        "darkgray" | "darkgrey"=>0xa9a9a9ff,
+        "lightgray" | "lightgrey"=>0xd3d3d3ff,
+        "silver"=>0xc0c0c0ff,
+        "red"=>0xff0000ff,
+        "darkred"=>0x8b0000ff,
+        "maroon"=>0x800000ff,
+        "orange"=>0xffa500ff,
+        "yellow"=>0xffff00ff,
+        "olive"=>0x808000ff,
+        "lime"=>0x00ff00ff,
+        "green"=>0x008000ff,
+        "darkgreen"=>0x006400ff,
+        "teal"=>0x008080ff,
+        "aqua" | "cyan"=>0x00ffffff,
+        "blue"=>0x0000ffff,
+        "darkblue"=>0x00008bff,
+        "navy"=>0x000080ff,
+        "purple"=>0x800080ff,
+        "fuchsia" | "magenta"=>0xff00ffff,
+        "pink"=>0xffc0cbff,
+        "brown"=>0xa52a2aff,
+        _=>return None
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CssRule{
+    pub selector: HtmlSelector,
+    pub style: CssStyle,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CssStyleSheet{
+    pub rules: Vec<CssRule>,
+}
+
+impl CssStyleSheet{
+    // rules with selectors we can't parse are skipped, so are at-rules like @media
+    pub fn parse(source:&str)->Self{
+        let mut sheet = CssStyleSheet::default();
+        sheet.append(source);
+        sheet
+    }
+
+    pub fn append(&mut self, source:&str){
+        // comments go first
+        let mut css = String::new();
+        let mut rest = source;
+        while let Some(start) = rest.find("/*"){
+            css.push_str(&rest[..start]);
+            rest = rest[start + 2..].find("*/").map(|end| &rest[start + 2 + end + 2..]).unwrap_or("");
+        }
+        css.push_str(rest);
+
+        let mut rest = css.trim_start();
+        while !rest.is_empty(){
+            if rest.starts_with('@'){
+                // either `@import ..;` or a block with nested braces
+                let semi = rest.find(';').unwrap_or(rest.len());
+                let open = rest.find('{').unwrap_or(rest.len());
+                if semi < open{
+                    rest = &rest[(semi + 1).min(rest.len())..];
+                }
+                else{
+                    let mut depth = 0;
+                    let mut end = rest.len();
+                    for (i, c) in rest.char_indices().skip_while(|(i, _)| *i < open){
+                        match c{
+                            '{'=>depth += 1,
+                            '}'=>{
+                                depth -= 1;
+                                if depth == 0{
+                                    end = i + 1;
+                                    break;
+                                }
+                            }
+                            _=>()
+                        }
+                    }
+                    rest = &rest[end..];
+                }
+            }
+            else{
+                let Some(open) = rest.find('{') else{
+                    break
+                };
+                let close = rest[open..].find('}').map(|v| open + v).unwrap_or(rest.len());
+                if let Ok(selector) = HtmlSelector::parse(&rest[..open]){
+                    self.rules.push(CssRule{
+                        selector,
+                        style: CssStyle::parse_declarations(&rest[open + 1..close])
+                    });
+                }
+                rest = &rest[(close + 1).min(rest.len())..];
+            }
+            rest = rest.trim_start();
+        }
+    }
+}
+
+impl HtmlDoc{
+    pub fn tag_lc(&self, index:usize)->Option<LiveId>{
+        match self.nodes.get(index){
+            Some(HtmlNode::OpenTag{lc,..})=>Some(*lc),
+            _=>None
+        }
+    }
+
+    // attribute of the open tag at index
+    pub fn attr_lc(&self, index:usize, flc:LiveId)->Option<&str>{
+        for node in &self.nodes[index + 1..]{
+            match node{
+                HtmlNode::Attribute{lc, start, end,..}=>if *lc == flc{
+                    return Some(&self.decoded[*start..*end])
+                }
+                _=>return None
+            }
+        }
+        None
+    }
+
+    // the text inside the element at index, with the text of its children
+    pub fn element_text(&self, index:usize)->String{
+        let mut out = String::new();
+        let mut depth = 0;
+        for node in &self.nodes[index + 1..]{
+            match node{
+                HtmlNode::OpenTag{lc,..} if !is_void_element(*lc)=>depth += 1,
+                HtmlNode::CloseTag{lc,..} if !is_void_element(*lc)=>{
+                    if depth == 0{
+                        break;
+                    }
+                    depth -= 1;
+                }
+                HtmlNode::Text{start, end,..}=>out.push_str(&self.decoded[*start..*end]),
+                _=>()
+            }
+        }
+        out
+    }
+
+    // calls back with the node index of every open tag and the indices of the elements it sits in.
+    // void elements like <br> don't contain anything and a stray close tag is ignored
+    pub fn for_each_element(&self, mut cb:impl FnMut(usize, &[usize])){
+        let mut stack = Vec::new();
+        for (index, node) in self.nodes.iter().enumerate(){
+            match node{
+                HtmlNode::OpenTag{lc,..}=>{
+                    cb(index, &stack);
+                    if !is_void_element(*lc){
+                        stack.push(index);
+                    }
+                }
+                HtmlNode::CloseTag{lc,..}=>{
+                    if let Some(pos) = stack.iter().rposition(|i| self.tag_lc(*i) == Some(*lc)){
+                        stack.truncate(pos);
+                    }
+                }
+                _=>()
+            }
+        }
+    }
+
+    // node indices of the open tags matching selector, in document order
+    pub fn select_all(&self, selector:&HtmlSelector)->Vec<usize>{
+        let mut out = Vec::new();
+        self.for_each_element(|index, ancestors|{
+            if selector.matches(self, index, ancestors){
+                out.push(index);
+            }
+        });
+        out
+    }
+
+    // like select_all, an invalid selector matches nothing
+    pub fn query_all(&self, selector:&str)->Vec<usize>{
+        HtmlSelector::parse(selector).map(|s| self.select_all(&s)).unwrap_or_default()
+    }
+
+    pub fn query(&self, selector:&str)->Option<usize>{
+        self.query_all(selector).first().copied()
+    }
+
+    // the style of every element from the given sheet, the <style> blocks in the document and its
+    // style attribute, indexed like nodes. inherited properties like color are left to the renderer
+    pub fn compute_styles(&self, sheet:&CssStyleSheet)->Vec<Option<CssStyle>>{
+        let mut doc_sheet = sheet.clone();
+        self.for_each_element(|index, _|{
+            if self.tag_lc(index) == Some(live_id!(style)){
+                doc_sheet.append(&self.element_text(index));
+            }
+        });
+        let mut styles = vec![None; self.nodes.len()];
+        let mut matched = Vec::new();
+        self.for_each_element(|index, ancestors|{
+            matched.clear();
+            for (order, rule) in doc_sheet.rules.iter().enumerate(){
+                if let Some(specificity) = rule.selector.match_specificity(self, index, ancestors){
+                    matched.push((specificity, order));
+                }
+            }
+            matched.sort();
+            let mut style = CssStyle::default();
+            for (_, order) in &matched{
+                style.merge(&doc_sheet.rules[*order].style);
+            }
+            if let Some(inline) = self.attr_lc(index, live_id!(style)){
+                style.merge(&CssStyle::parse_declarations(inline));
+            }
+            if !style.is_empty(){
+                styles[index] = Some(style);
+            }
+        });
+        styles
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::parse_html;
+    
+    fn doc(html:&str)->HtmlDoc{
+        parse_html(html, &mut None)
+    }
+    
+    #[test]
+    fn orders_by_specificity(){
+        let spec = |selector:&str|{
+            let parts = &HtmlSelector::parse(selector).unwrap().alternatives[0];
+            parts.iter().map(|p| p.compound.specificity()).sum::<u32>()
+        };
+        assert_eq!(spec("*"), 0);
+        assert_eq!(spec("p"), 1);
+        assert_eq!(spec("div p"), 2);
+        assert_eq!(spec(".a"), 100);
+        assert_eq!(spec("p[href]"), 101);
+        assert_eq!(spec("#x"), 10000);
+        assert_eq!(spec("div#x.a.b > p"), 10202);
+        assert!(spec("#x") > spec(".a.b.c.d") && spec(".a") > spec("div ul li p"));
+        
+        // the most specific alternative that matches counts
+        let d = doc("<p id=\"x\" class=\"a\">t</p>");
+        let p = d.query("p").unwrap();
+        let sel = HtmlSelector::parse("p, .a, #y").unwrap();
+        assert_eq!(sel.match_specificity(&d, p, &[]), Some(100));
+        assert_eq!(HtmlSelector::parse("#y").unwrap().match_specificity(&d, p, &[]), None);
+        
+        // an id rule beats a class rule that comes later, a later rule wins a tie
+        let d = doc("<style>#x{color:green} .a{color:blue} p{color:red; font-weight:bold} p{font-weight:300}</style><p id=\"x\" class=\"a\">t</p>");
+        let styles = d.compute_styles(&CssStyleSheet::default());
+        let style = styles[d.query("p").unwrap()].clone().unwrap();
+        assert_eq!(style.color, Some(0x008000ff));
+        assert_eq!(style.font_weight, Some(300));
+    }
+    
+    #[test]
+    fn matches_descendants_and_children(){
+        let d = doc("<div class=\"note\"><section><p>a</p></section><p>b</p></div><p>c</p>");
+        let texts = |selector:&str| d.query_all(selector).iter().map(|i| d.element_text(*i)).collect::<Vec<_>>();
+        assert_eq!(texts("p"), ["a", "b", "c"]);
+        assert_eq!(texts("div p"), ["a", "b"]);
+        assert_eq!(texts(".note p"), ["a", "b"]);
+        assert_eq!(texts("div > p"), ["b"]);
+        assert_eq!(texts("div > section > p"), ["a"]);
+        assert_eq!(texts("div section p"), ["a"]);
+        assert_eq!(texts("section div p"), Vec::<String>::new());
+        assert_eq!(texts("section p, div > p"), ["a", "b"]);
+        assert!(d.query("P").is_some());
+    }
+    
+    #[test]
+    fn matches_attributes(){
+        let d = doc("<a href=\"https://x.org/a.png\" lang=\"en-US\" class=\"big red\" rel=\"next\" hidden>l</a>");
+        let a = d.query("a").unwrap();
+        let is = |selector:&str| HtmlSelector::parse(selector).unwrap().matches(&d, a, &[]);
+        assert!(is("[hidden]") && is("a[HREF]"));
+        assert!(is("[rel=next]") && is("[rel = 'next']") && !is("[rel=nex]"));
+        assert!(is("[class~=red]") && !is("[class~=re]"));
+        assert!(is("[lang|=en]") && is("[lang|=\"en-US\"]") && !is("[lang|=e]"));
+        assert!(is("[href^=https]") && !is("[href^='http:']"));
+        assert!(is("[href$='.png']") && !is("[href$=jpg]"));
+        assert!(is("[href*=\"x.org\"]") && !is("[href*=y]"));
+        // an empty value never matches the substring operators
+        assert!(!is("[href^='']") && !is("[href*='']"));
+        assert!(!is("[title]") && is("a.big.red[rel]") && !is("a.big.blue"));
+    }
+    
+    #[test]
+    fn inline_style_overrides_style_blocks(){
+        let d = doc("<style>/* a comment */ @media print{p{color:red}} p{color:blue; margin:4px 8px; font-size:2em}</style><p style=\"color: #f00a; margin-left: 1px !important\">t</p><p>u</p>");
+        let sheet = CssStyleSheet::parse("p{font-weight:bold; color:green}");
+        let styles = d.compute_styles(&sheet);
+        let ps = d.query_all("p");
+        let first = styles[ps[0]].clone().unwrap();
+        assert_eq!(first.color, Some(0xff0000aa));
+        assert_eq!(first.margin_left, Some(CssLength::Px(1.0)));
+        assert_eq!(first.margin_top, Some(CssLength::Px(4.0)));
+        assert_eq!(first.font_size, Some(CssLength::Em(2.0)));
+        assert_eq!(first.font_weight, Some(700));
+        // the document's rules come after the given sheet, the @media block is skipped
+        let second = styles[ps[1]].clone().unwrap();
+        assert_eq!(second.color, Some(0x0000ffff));
+        assert_eq!(second.margin_right, Some(CssLength::Px(8.0)));
+        assert!(styles[d.query("style").unwrap()].is_none());
+    }
+    
+    #[test]
+    fn rejects_malformed_selectors(){
+        for (selector, message, position) in [
+            ("", "Empty selector", 0),
+            ("p,", "Empty selector", 2),
+            ("p, ,a", "Empty selector", 3),
+            ("> p", "Unexpected >", 0),
+            ("div > > p", "Unexpected >", 6),
+            ("div >", "Empty selector", 5),
+            ("#", "Expected a name", 1),
+            ("p.", "Expected a name", 2),
+            ("[=x]", "Invalid attribute selector", 3),
+            ("[a", "Expected ] or an attribute operator", 2),
+            ("[a~x]", "Expected =", 3),
+            ("[a='x]", "Unterminated string", 3),
+            ("[a=x y]", "Invalid attribute selector", 5),
+            ("p:hover", "Unsupported selector", 1),
+            ("a + b", "Unsupported selector", 2),
+        ]{
+            let err = HtmlSelector::parse(selector).unwrap_err();
+            assert_eq!((err.message.as_str(), err.position), (message, position), "{}", selector);
+        }
+        // a rule with a bad selector is dropped, the rest of the sheet still applies
+        let sheet = CssStyleSheet::parse("p:hover{color:red} p{color:blue}");
+        assert_eq!(sheet.rules.len(), 1);
+        assert!(doc("<p>x</p>").query_all("p >").is_empty());
+    }
+}
@@ -1,5 +1,8 @@
 use makepad_live_id::*;
 
+mod css;
+pub use css::*;
+
 #[derive(Debug)]
 pub struct HtmlError{
     pub message:String,
@@ -12,7 +15,7 @@ pub struct HtmlDoc{
     pub nodes: Vec<HtmlNode>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum HtmlNode{
     OpenTag{lc:LiveId, nc:LiveId},
     CloseTag{lc:LiveId, nc:LiveId},
@@ -256,12 +259,206 @@ impl<'a> HtmlWalker<'a>{
      }
  }
  
+ // elements that never have content or a close tag
+ pub fn is_void_element(lc:LiveId)->bool{
+     matches!(lc, live_id!(area) | live_id!(base) | live_id!(br) | live_id!(col) | live_id!(embed) | live_id!(hr) |
+         live_id!(img) | live_id!(input) | live_id!(link) | live_id!(meta) | live_id!(source) | live_id!(track) | live_id!(wbr))
+ }
+ 
  #[derive(Clone, Copy)]
  pub enum KeepWhitespace{
      Yes,
      No
  }
  
+ // elements whose content is text up to their close tag
+ pub fn is_raw_text_element(lc:LiveId)->bool{
+     matches!(lc, live_id!(script) | live_id!(style) | live_id!(xmp) | live_id!(iframe) | live_id!(noembed) | live_id!(noframes)) ||
+     is_escapable_raw_text_element(lc)
+ }
+ 
+ // raw text elements in which entities are still decoded
+ pub fn is_escapable_raw_text_element(lc:LiveId)->bool{
+     matches!(lc, live_id!(textarea) | live_id!(title))
+ }
+ 
+ // elements that get closed by the html5 implied end tag rules without a complaint
+ fn has_optional_end_tag(lc:LiveId)->bool{
+     matches!(lc, live_id!(p) | live_id!(li) | live_id!(dt) | live_id!(dd) | live_id!(option) | live_id!(optgroup) |
+         live_id!(tr) | live_id!(td) | live_id!(th) | live_id!(thead) | live_id!(tbody) | live_id!(tfoot) |
+         live_id!(colgroup) | live_id!(caption) | live_id!(rb) | live_id!(rt) | live_id!(rp) |
+         live_id!(html) | live_id!(head) | live_id!(body))
+ }
+ 
+ fn is_formatting_element(lc:LiveId)->bool{
+     matches!(lc, live_id!(a) | live_id!(b) | live_id!(big) | live_id!(code) | live_id!(em) | live_id!(font) | live_id!(i) |
+         live_id!(s) | live_id!(small) | live_id!(strike) | live_id!(strong) | live_id!(tt) | live_id!(u))
+ }
+ 
+ // opening one of these closes an open <p>
+ fn closes_p(lc:LiveId)->bool{
+     matches!(lc, live_id!(address) | live_id!(article) | live_id!(aside) | live_id!(blockquote) | live_id!(center) |
+         live_id!(details) | live_id!(dialog) | live_id!(dir) | live_id!(div) | live_id!(dl) | live_id!(fieldset) |
+         live_id!(figcaption) | live_id!(figure) | live_id!(footer) | live_id!(form) | live_id!(h1) | live_id!(h2) |
+         live_id!(h3) | live_id!(h4) | live_id!(h5) | live_id!(h6) | live_id!(header) | live_id!(hgroup) | live_id!(hr) |
+         live_id!(main) | live_id!(menu) | live_id!(nav) | live_id!(ol) | live_id!(p) | live_id!(pre) | live_id!(section) |
+         live_id!(summary) | live_id!(table) | live_id!(ul))
+ }
+ 
+ // implied end tags don't reach through these
+ fn is_scope_boundary(lc:LiveId)->bool{
+     matches!(lc, live_id!(html) | live_id!(table) | live_id!(td) | live_id!(th) | live_id!(caption) | live_id!(button) |
+         live_id!(object) | live_id!(marquee) | live_id!(applet) | live_id!(template))
+ }
+ 
+ struct OpenElement{
+     node: usize,
+     lc: LiveId,
+     name_start: usize,
+     name_end: usize,
+ }
+ 
+ // turns the tags coming out of the tokenizer into a balanced OpenTag/CloseTag stream
+ // following the html5 tree construction rules, in a simplified form
+ struct TreeBuilder<'a>{
+     body: &'a str,
+     nodes: Vec<HtmlNode>,
+     open: Vec<OpenElement>,
+ }
+ 
+ impl<'a> TreeBuilder<'a>{
+     fn warn(&self, message:String, position:usize, errors:&mut Option<Vec<HtmlError>>){
+         if let Some(errors) = errors{errors.push(HtmlError{message, position})};
+     }
+     
+     // closes the topmost open element, complaining if it needed an end tag
+     fn pop(&mut self, errors:&mut Option<Vec<HtmlError>>, implied:bool)->OpenElement{
+         let el = self.open.pop().unwrap();
+         if let HtmlNode::OpenTag{lc, nc} = self.nodes[el.node]{
+             self.nodes.push(HtmlNode::CloseTag{lc, nc});
+         }
+         if implied && !has_optional_end_tag(el.lc){
+             self.warn(format!("Element <{}> is not closed", &self.body[el.name_start..el.name_end]), el.name_start, errors);
+         }
+         el
+     }
+     
+     // closes the innermost element in targets with everything inside it, unless a scope boundary comes first
+     fn close_implied(&mut self, targets:&[LiveId], boundaries:&[LiveId], errors:&mut Option<Vec<HtmlError>>){
+         for i in (0..self.open.len()).rev(){
+             let lc = self.open[i].lc;
+             if targets.contains(&lc){
+                 while self.open.len() > i{
+                     self.pop(errors, true);
+                 }
+                 return
+             }
+             if boundaries.contains(&lc) || is_scope_boundary(lc){
+                 return
+             }
+         }
+     }
+     
+     fn open_tag(&mut self, name_start:usize, name_end:usize, errors:&mut Option<Vec<HtmlError>>){
+         let name = &self.body[name_start..name_end];
+         let lc = LiveId::from_str_lc(name);
+         if closes_p(lc){
+             self.close_implied(&[live_id!(p)], &[], errors);
+         }
+         match lc{
+             live_id!(li)=>self.close_implied(&[live_id!(li)], &[live_id!(ul), live_id!(ol)], errors),
+             live_id!(dt) | live_id!(dd)=>self.close_implied(&[live_id!(dt), live_id!(dd)], &[live_id!(dl)], errors),
+             live_id!(td) | live_id!(th)=>self.close_implied(&[live_id!(td), live_id!(th)], &[live_id!(tr)], errors),
+             live_id!(tr)=>{
+                 self.close_implied(&[live_id!(td), live_id!(th)], &[live_id!(tr)], errors);
+                 self.close_implied(&[live_id!(tr)], &[live_id!(thead), live_id!(tbody), live_id!(tfoot)], errors);
+             }
+             live_id!(thead) | live_id!(tbody) | live_id!(tfoot)=>{
+                 self.close_implied(&[live_id!(td), live_id!(th)], &[live_id!(tr)], errors);
+                 self.close_implied(&[live_id!(tr)], &[live_id!(thead), live_id!(tbody), live_id!(tfoot)], errors);
+                 self.close_implied(&[live_id!(thead), live_id!(tbody), live_id!(tfoot)], &[], errors);
+             }
+             live_id!(option)=>self.close_implied(&[live_id!(option)], &[live_id!(select)], errors),
+             live_id!(optgroup)=>self.close_implied(&[live_id!(option), live_id!(optgroup)], &[live_id!(select)], errors),
+             // headings don't nest
+             live_id!(h1) | live_id!(h2) | live_id!(h3) | live_id!(h4) | live_id!(h5) | live_id!(h6)
+                 if self.open.last().is_some_and(|el| matches!(el.lc, live_id!(h1) | live_id!(h2) | live_id!(h3) | live_id!(h4) | live_id!(h5) | live_id!(h6)))=>{
+                 self.pop(errors, true);
+             }
+             _=>()
+         }
+         self.open.push(OpenElement{node:self.nodes.len(), lc, name_start, name_end});
+         self.nodes.push(HtmlNode::OpenTag{lc, nc:LiveId::from_str(name)});
+     }
+     
+     // at the > of an open tag. closes void elements, returns the tag when raw text follows
+     fn end_open_tag(&mut self)->Option<LiveId>{
+         let lc = self.open.last()?.lc;
+         if is_void_element(lc){
+             self.pop(&mut None, false);
+             return None
+         }
+         is_raw_text_element(lc).then_some(lc)
+     }
+     
+     // <tag/>, which we allow on any element
+     fn self_close(&mut self){
+         if self.open.last().is_some_and(|el| el.node + 1 + self.attrs_after(el.node) == self.nodes.len()){
+             self.pop(&mut None, false);
+         }
+     }
+     
+     fn attrs_after(&self, node:usize)->usize{
+         self.nodes[node + 1..].iter().take_while(|n| matches!(n, HtmlNode::Attribute{..})).count()
+     }
+     
+     fn close_tag(&mut self, name_start:usize, name_end:usize, errors:&mut Option<Vec<HtmlError>>){
+         let name = &self.body[name_start..name_end];
+         let lc = LiveId::from_str_lc(name);
+         let found = self.open.iter().rposition(|el| el.lc == lc);
+         // the end of a table or row also ends the cells in it
+         let ends_cells = matches!(lc, live_id!(table) | live_id!(tr) | live_id!(thead) | live_id!(tbody) | live_id!(tfoot));
+         let in_scope = found.is_some_and(|i| !self.open[i + 1..].iter().any(|el|{
+             is_scope_boundary(el.lc) && !(ends_cells && matches!(el.lc, live_id!(td) | live_id!(th) | live_id!(caption)))
+         }));
+         if !in_scope{
+             match lc{
+                 // </br> and </p> without an open p act like <br> and <p></p>
+                 live_id!(br) | live_id!(p)=>{
+                     self.warn(format!("Unexpected </{}>", name), name_start, errors);
+                     self.open_tag(name_start, name_end, errors);
+                     self.pop(errors, false);
+                 }
+                 _=>self.warn(format!("Ignored </{}> without an open element", name), name_start, errors)
+             }
+             return
+         }
+         let index = found.unwrap();
+         // formatting elements closed early by a misnested end tag are opened again after it,
+         // so <b><i>x</b>y</i> keeps y italic
+         let mut reopen = Vec::new();
+         while self.open.len() > index + 1{
+             let el = self.pop(errors, true);
+             if is_formatting_element(lc) && is_formatting_element(el.lc){
+                 reopen.push(el);
+             }
+         }
+         self.pop(errors, false);
+         for el in reopen.into_iter().rev(){
+             let attrs = self.attrs_after(el.node);
+             let copy:Vec<HtmlNode> = self.nodes[el.node..el.node + 1 + attrs].to_vec();
+             self.open.push(OpenElement{node:self.nodes.len(), ..el});
+             self.nodes.extend(copy);
+         }
+     }
+     
+     fn finish(&mut self, errors:&mut Option<Vec<HtmlError>>){
+         while !self.open.is_empty(){
+             self.pop(errors, true);
+         }
+     }
+ }
+ 
  pub fn parse_html(body:&str, errors:  &mut Option<Vec<HtmlError>>)->HtmlDoc{
      enum State{
          Text(usize, usize, usize),
@@ -283,7 +480,17 @@ impl<'a> HtmlWalker<'a>{
          DocType,
          HeaderQuestion,
          HeaderAngle,
-         CommentBody
+         CommentBody,
+         // inside script, style and the like, until the matching close tag
+         RawText{lc:LiveId, dec_start:usize, last_non_whitespace:usize, entities:bool},
+     }
+     
+     // a raw text element doesn't parse tags until its close tag, void elements close right away
+     fn after_open_tag(tree:&mut TreeBuilder, i:usize, decoded:&str)->State{
+         match tree.end_open_tag(){
+             Some(lc)=>State::RawText{lc, dec_start:decoded.len(), last_non_whitespace:decoded.len(), entities:is_escapable_raw_text_element(lc)},
+             None=>State::Text(i, decoded.len(), decoded.len())
+         }
      }
              
      fn process_entity(c:char, body:&str, in_entity:&mut Option<usize>, i:usize, decoded:&mut String, errors:&mut Option<Vec<HtmlError>>, last_non_whitespace:&mut usize){
@@ -319,13 +526,39 @@ impl<'a> HtmlWalker<'a>{
          }
      }
      
-     let mut nodes = Vec::new();
+     let mut tree = TreeBuilder{body, nodes:Vec::new(), open:Vec::new()};
      let mut state = State::Text(0, 0, 0);
      let mut decoded = String::new();
      let mut in_entity = None;
      
      for (i, c) in body.char_indices(){
          state = match state{
+             State::RawText{lc, dec_start, last_non_whitespace, entities}=>{
+                 let closes = c == '<' && body[i+1..].strip_prefix('/').is_some_and(|rest|{
+                     let name = rest.split(|c:char| !c.is_ascii_alphanumeric()).next().unwrap();
+                     LiveId::from_str_lc(name) == lc
+                 });
+                 if closes{
+                     if let Some(start) = in_entity.take(){
+                         if let Some(errors) = errors{errors.push(HtmlError{message:"Unterminated entity".into(), position:start})};
+                     }
+                     tree.nodes.push(HtmlNode::Text{start:dec_start, end:decoded.len(), all_ws:dec_start == last_non_whitespace});
+                     State::ElementName(i+1)
+                 }
+                 else{
+                     let mut last_non_whitespace = last_non_whitespace;
+                     if entities{
+                         process_entity(c, &body, &mut in_entity, i, &mut decoded, errors, &mut last_non_whitespace);
+                     }
+                     else{
+                         decoded.push(c);
+                         if !c.is_whitespace(){
+                             last_non_whitespace = decoded.len();
+                         }
+                     }
+                     State::RawText{lc, dec_start, last_non_whitespace, entities}
+                 }
+             }
              State::DocType=>{
                  if c == '>'{
                      State::Text(i+1, decoded.len(), decoded.len())
@@ -355,7 +588,7 @@ impl<'a> HtmlWalker<'a>{
                     if let Some(start) = in_entity{
                         if let Some(errors) = errors{errors.push(HtmlError{message:"Unterminated entity".into(), position:start})};
                     }
-                    nodes.push(HtmlNode::Text{start:dec_start, end:decoded.len(), all_ws:dec_start == last_non_whitespace});
+                    tree.nodes.push(HtmlNode::Text{start:dec_start, end:decoded.len(), all_ws:dec_start == last_non_whitespace});
                     State::ElementName(i+1)
                  }
                  else{
@@ -380,17 +613,17 @@ impl<'a> HtmlWalker<'a>{
                          State::Text(i+1, decoded.len(), decoded.len())
                      }
                      else{
-                        nodes.push(HtmlNode::OpenTag{lc:LiveId::from_str_lc(&body[start..i]),nc:LiveId::from_str(&body[start..i])});
+                        tree.open_tag(start, i, errors);
                         State::ElementAttrs
                     }
                 }
                  else if c == '/'{
-                     nodes.push(HtmlNode::OpenTag{lc:LiveId::from_str_lc(&body[start..i]),nc:LiveId::from_str(&body[start..i])});
+                     tree.open_tag(start, i, errors);
                      State::ElementSelfClose
                  }
                  else if c == '>'{
-                     nodes.push(HtmlNode::OpenTag{lc:LiveId::from_str_lc(&body[start..i]),nc:LiveId::from_str(&body[start..i])});
-                     State::Text(i+1, decoded.len(), decoded.len())
+                     tree.open_tag(start, i, errors);
+                     after_open_tag(&mut tree, i+1, &decoded)
                  }
                  else{
                      State::ElementName(start)
@@ -398,11 +631,11 @@ impl<'a> HtmlWalker<'a>{
              }
              State::ElementClose(start)=>{
                  if c == '>'{
-                     nodes.push(HtmlNode::CloseTag{lc:LiveId::from_str_lc(&body[start..i]),nc:LiveId::from_str(&body[start..i])});
+                     tree.close_tag(start, i, errors);
                      State::Text(i+1, decoded.len(), decoded.len())
                  }
                  else if c.is_whitespace(){
-                     nodes.push(HtmlNode::CloseTag{lc:LiveId::from_str_lc(&body[start..i]),nc:LiveId::from_str(&body[start..i])});
+                     tree.close_tag(start, i, errors);
                      State::ElementCloseScanSpaces
                  }
                  else{
@@ -425,9 +658,7 @@ impl<'a> HtmlWalker<'a>{
                  if c != '>'{
                       if let Some(errors) = errors{errors.push(HtmlError{message:"Expected > after / self closed tag".into(), position:i})};
                  }
-                 // look backwards to the OpenTag
-                 let begin = nodes.iter().rev().find_map(|v| if let HtmlNode::OpenTag{lc,nc} = v{Some((lc,nc))}else{None}).unwrap();
-                 nodes.push(HtmlNode::CloseTag{lc:*begin.0,nc:*begin.1});
+                 tree.self_close();
                  State::Text(i+1, decoded.len(), decoded.len())
              }
              State::ElementAttrs=>{
@@ -436,7 +667,7 @@ impl<'a> HtmlWalker<'a>{
                      State::ElementSelfClose
                  }
                  else if c == '>'{
-                     State::Text(i+1, decoded.len(), decoded.len())
+                     after_open_tag(&mut tree, i+1, &decoded)
                  }
                  else if !c.is_whitespace(){
                      State::AttribName(i)
@@ -453,12 +684,12 @@ impl<'a> HtmlWalker<'a>{
                      State::AttribValueStart(LiveId::from_str_lc(&body[start..i]),LiveId::from_str_lc(&body[start..i]))
                  }
                  else if c == '/'{
-                     nodes.push(HtmlNode::Attribute{lc:LiveId::from_str_lc(&body[start..i]),nc:LiveId::from_str_lc(&body[start..i]),start:0,end:0});
+                     tree.nodes.push(HtmlNode::Attribute{lc:LiveId::from_str_lc(&body[start..i]),nc:LiveId::from_str_lc(&body[start..i]),start:0,end:0});
                      State::ElementSelfClose
                  }
                  else if c == '>'{
-                     nodes.push(HtmlNode::Attribute{lc:LiveId::from_str_lc(&body[start..i]),nc:LiveId::from_str_lc(&body[start..i]),start:0,end:0});
-                     State::Text(i+1, decoded.len(), decoded.len())
+                     tree.nodes.push(HtmlNode::Attribute{lc:LiveId::from_str_lc(&body[start..i]),nc:LiveId::from_str_lc(&body[start..i]),start:0,end:0});
+                     after_open_tag(&mut tree, i+1, &decoded)
                  }
                  else{
                      State::AttribName(start)
@@ -466,18 +697,18 @@ impl<'a> HtmlWalker<'a>{
              }
              State::AttribValueEq(lc,nc)=>{
                  if c == '/'{
-                     nodes.push(HtmlNode::Attribute{lc,nc,start:0,end:0});
+                     tree.nodes.push(HtmlNode::Attribute{lc,nc,start:0,end:0});
                      State::ElementSelfClose
                  }
                  else if c == '>'{
-                     nodes.push(HtmlNode::Attribute{lc,nc,start:0,end:0});
-                     State::Text(i+1, decoded.len(), decoded.len())
+                     tree.nodes.push(HtmlNode::Attribute{lc,nc,start:0,end:0});
+                     after_open_tag(&mut tree, i+1, &decoded)
                  }
                  else if c == '='{
                      State::AttribValueStart(lc,nc)
                  }
                  else if !c.is_whitespace(){
-                     nodes.push(HtmlNode::Attribute{lc,nc,start:0,end:0});
+                     tree.nodes.push(HtmlNode::Attribute{lc,nc,start:0,end:0});
                      State::AttribName(i)
                  }
                  else{
@@ -506,7 +737,7 @@ impl<'a> HtmlWalker<'a>{
                      if let Some(start) = in_entity{
                           if let Some(errors) = errors{errors.push(HtmlError{message:"Unterminated entity".into(), position:start})};
                      }
-                     nodes.push(HtmlNode::Attribute{lc,nc, start, end:decoded.len()});
+                     tree.nodes.push(HtmlNode::Attribute{lc,nc, start, end:decoded.len()});
                      State::ElementAttrs
                  }
                  else{
@@ -519,7 +750,7 @@ impl<'a> HtmlWalker<'a>{
                      if let Some(start) = in_entity{
                           if let Some(errors) = errors{errors.push(HtmlError{message:"Unterminated entity".into(), position:start})};
                      }
-                     nodes.push(HtmlNode::Attribute{lc,nc, start, end:decoded.len()});
+                     tree.nodes.push(HtmlNode::Attribute{lc,nc, start, end:decoded.len()});
                      State::ElementAttrs
                  }
                  else{
@@ -529,15 +760,15 @@ impl<'a> HtmlWalker<'a>{
              }
              State::AttribValueBare(lc,nc, start)=>{
                  if c == '/'{
-                     nodes.push(HtmlNode::Attribute{lc,nc, start, end:decoded.len()});
+                     tree.nodes.push(HtmlNode::Attribute{lc,nc, start, end:decoded.len()});
                      State::ElementSelfClose
                  }
                  else if c == '>'{
-                     nodes.push(HtmlNode::Attribute{lc,nc, start, end:decoded.len()});
-                     State::Text(i+1, decoded.len(), decoded.len())
+                     tree.nodes.push(HtmlNode::Attribute{lc,nc, start, end:decoded.len()});
+                     after_open_tag(&mut tree, i+1, &decoded)
                  }
                  else if c.is_whitespace(){
-                     nodes.push(HtmlNode::Attribute{lc,nc, start, end:decoded.len()});
+                     tree.nodes.push(HtmlNode::Attribute{lc,nc, start, end:decoded.len()});
                      State::ElementAttrs
                  }
                  else{
@@ -591,13 +822,18 @@ impl<'a> HtmlWalker<'a>{
           if let Some(errors) = errors{errors.push(HtmlError{message:"Unterminated entity".into(), position:start})};
      }
      if let State::Text(_, dec_start, last_non_whitespace) = state{
-        nodes.push(HtmlNode::Text{start:dec_start, end:decoded.len(), all_ws:dec_start == last_non_whitespace});
+        tree.nodes.push(HtmlNode::Text{start:dec_start, end:decoded.len(), all_ws:dec_start == last_non_whitespace});
+     }
+     else if let State::RawText{dec_start, last_non_whitespace,..} = state{
+        if let Some(errors) = errors{errors.push(HtmlError{message:"Unterminated raw text element".into(), position:body.len()})};
+        tree.nodes.push(HtmlNode::Text{start:dec_start, end:decoded.len(), all_ws:dec_start == last_non_whitespace});
      }
      else{ // if we didnt end in text state something is wrong
           if let Some(errors) = errors{errors.push(HtmlError{message:"HTML Parsing endstate is not HtmlNode::Text".into(), position:body.len()})};
      }
+     tree.finish(errors);
      HtmlDoc{
-         nodes,
+         nodes: tree.nodes,
          decoded,
      }
  }
@@ -2155,4 +2391,98 @@ impl<'a> HtmlWalker<'a>{
              }
          }
      })
- }
\ No newline at end of file
+ } 
+ #[cfg(test)]
+ mod tests{
+     use super::*;
+     
+     // the node stream as html again, so the implied tags show up
+     fn outline(doc:&HtmlDoc)->String{
+         let names = ["p", "li", "ul", "table", "tr", "td", "b", "i", "div", "br", "script"];
+         let name = |lc:LiveId| *names.iter().find(|n| LiveId::from_str(n) == lc).unwrap();
+         let mut out = String::new();
+         for node in &doc.nodes{
+             match node{
+                 HtmlNode::OpenTag{lc,..}=>out.push_str(&format!("<{}>", name(*lc))),
+                 HtmlNode::CloseTag{lc,..}=>out.push_str(&format!("</{}>", name(*lc))),
+                 HtmlNode::Text{start, end,..}=>out.push_str(&doc.decoded[*start..*end]),
+                 HtmlNode::Attribute{..}=>()
+             }
+         }
+         out
+     }
+     
+     // every open tag has to jump to its own close tag, and the top level walks to the end
+     fn assert_balanced(doc:&HtmlDoc){
+         for (index, node) in doc.nodes.iter().enumerate(){
+             if let HtmlNode::OpenTag{lc,..} = node{
+                 let mut walker = doc.new_walker_with_index(index);
+                 walker.jump_to_close();
+                 assert_eq!(walker.close_tag_lc(), Some(*lc), "open tag at {} isn't closed", index);
+             }
+         }
+         let mut walker = doc.new_walker();
+         while !walker.done(){
+             if walker.open_tag_lc().is_some(){
+                 walker.jump_to_close();
+             }
+             else{
+                 assert!(walker.close_tag_lc().is_none(), "close tag at {} without an open tag", walker.index());
+             }
+             walker.walk();
+         }
+     }
+     
+     fn parse(html:&str)->(HtmlDoc, Vec<String>){
+         let mut errors = Some(Vec::new());
+         let doc = parse_html(html, &mut errors);
+         assert_balanced(&doc);
+         (doc, errors.unwrap().into_iter().map(|e| e.message).collect())
+     }
+     
+     #[test]
+     fn closes_optional_end_tags(){
+         let (doc, errors) = parse("<p>a<p>b<div>c</div>d");
+         assert_eq!(outline(&doc), "<p>a</p><p>b</p><div>c</div>d");
+         assert!(errors.is_empty(), "{:?}", errors);
+         
+         let (doc, errors) = parse("<ul><li>a<li>b<ul><li>c</ul></ul><p>x");
+         assert_eq!(outline(&doc), "<ul><li>a</li><li>b<ul><li>c</li></ul></li></ul><p>x</p>");
+         assert!(errors.is_empty(), "{:?}", errors);
+         
+         let (doc, errors) = parse("<table><tr><td>a<td>b<tr><td>c</table>");
+         assert_eq!(outline(&doc), "<table><tr><td>a</td><td>b</td></tr><tr><td>c</td></tr></table>");
+         assert!(errors.is_empty(), "{:?}", errors);
+         
+         // void elements get their close tag right away
+         let (doc, _) = parse("<p>a<br>b</p>");
+         assert_eq!(outline(&doc), "<p>a<br></br>b</p>");
+     }
+     
+     #[test]
+     fn reopens_misnested_formatting(){
+         let (doc, errors) = parse("<b>x<i>y</b>z</i>");
+         assert_eq!(outline(&doc), "<b>x<i>y</i></b><i>z</i>");
+         assert_eq!(errors, ["Element <i> is not closed"]);
+         
+         // a close tag without an open element is dropped
+         let (doc, errors) = parse("<div>a</b></div></div>");
+         assert_eq!(outline(&doc), "<div>a</div>");
+         assert_eq!(errors, ["Ignored </b> without an open element", "Ignored </div> without an open element"]);
+         
+         let (doc, errors) = parse("<div><b>a");
+         assert_eq!(outline(&doc), "<div><b>a</b></div>");
+         assert_eq!(errors, ["Element <b> is not closed", "Element <div> is not closed"]);
+     }
+     
+     #[test]
+     fn closes_unterminated_raw_text(){
+         let (doc, errors) = parse("<p>a<script>if(a<b){}</p>");
+         assert_eq!(outline(&doc), "<p>a<script>if(a<b){}</p></script></p>");
+         assert_eq!(errors, ["Unterminated raw text element", "Element <script> is not closed"]);
+         
+         let (doc, errors) = parse("<script>x</SCRIPT >y");
+         assert_eq!(outline(&doc), "<script>x</script>y");
+         assert!(errors.is_empty(), "{:?}", errors);
+     }
+ }
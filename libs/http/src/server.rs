@@ -2,21 +2,78 @@
 
 use std::net::{TcpListener, TcpStream, SocketAddr, Shutdown};
 use std::io::prelude::*;
+use std::io::{BufReader, SeekFrom};
+use std::fs::File;
 use std::sync::{mpsc, mpsc::{RecvTimeoutError}};
 use std::time::Duration;
-pub use crate::websocket::{SERVER_WEB_SOCKET_PONG_MESSAGE, ServerWebSocket, ServerWebSocketMessage, ServerWebSocketMessageFormat, ServerWebSocketMessageHeader, SERVER_WEB_SOCKET_PING_MESSAGE};
+pub use crate::static_files::{HttpStaticFiles, mime_type_from_path};
+pub use crate::websocket::{SERVER_WEB_SOCKET_PONG_MESSAGE, WEB_SOCKET_DEFAULT_MAX_MESSAGE_SIZE, WEB_SOCKET_PERMESSAGE_DEFLATE, ServerWebSocket, ServerWebSocketMessage, ServerWebSocketMessageFormat, ServerWebSocketMessageHeader, SERVER_WEB_SOCKET_PING_MESSAGE};
 use crate::utils::*;
 
+// how long an idle persistent connection is kept open
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(30);
+
 #[derive(Clone)]
 pub struct HttpServer {
     pub listen_address: SocketAddr,
     pub request: mpsc::Sender<HttpServerRequest>,
-    pub post_max_size: u64
+    pub post_max_size: u64,
+    pub web_socket_max_message_size: usize,
 }
 
 pub struct HttpServerResponse {
     pub header: String,
-    pub body: Vec<u8>
+    pub body: Vec<u8>,
+    // sent after the body in pieces, so serving a large file doesn't read it into memory
+    pub file: Option<HttpServerFileBody>,
+}
+
+// len bytes of the file from start on
+pub struct HttpServerFileBody {
+    pub file: File,
+    pub start: u64,
+    pub len: u64,
+}
+
+impl HttpServerResponse {
+    // the answer for a request whose method isn't served, `allow` lists the ones that are
+    pub fn method_not_allowed(allow: &str) -> Self {
+        Self {
+            header: format!("HTTP/1.1 405 Method Not Allowed\r\nAllow: {}\r\nContent-Length: 0\r\n\r\n", allow),
+            body: Vec::new(),
+            file: None
+        }
+    }
+}
+
+pub enum HttpServerBody {
+    // the whole body, at most post_max_size bytes
+    Complete(Vec<u8>),
+    // larger bodies arrive in pieces, an empty piece marks the end and
+    // the channel closing before that means the client went away
+    Stream(mpsc::Receiver<Vec<u8>>),
+}
+
+impl HttpServerBody {
+    // collects a streamed body, None if the client went away or it grows beyond max_size
+    pub fn read_to_end(self, max_size: usize) -> Option<Vec<u8>> {
+        match self {
+            Self::Complete(body) => Some(body),
+            Self::Stream(receiver) => {
+                let mut body = Vec::new();
+                while let Ok(data) = receiver.recv() {
+                    if data.is_empty() {
+                        return Some(body)
+                    }
+                    if body.len() + data.len() > max_size {
+                        return None
+                    }
+                    body.extend_from_slice(&data);
+                }
+                None
+            }
+        }
+    }
 }
 
 pub enum HttpServerRequest {
@@ -33,14 +90,26 @@ pub enum HttpServerRequest {
         response_sender: mpsc::Sender<Vec<u8 >>,
         data: Vec<u8>
     },
+    // requests are routed on method and body: a GET always comes here, a body it carries is skipped
     Get {
         headers: HttpServerHeaders,
         response_sender: mpsc::Sender<HttpServerResponse>,
     },
+    // a POST with a Content-Length of at most post_max_size, read whole
     Post {
         headers: HttpServerHeaders,
         body: Vec<u8>,
         response: mpsc::Sender<HttpServerResponse>,
+    },
+    // every other method, and the POSTs that don't fit the above: chunked ones and
+    // ones larger than post_max_size. so a handler taking uploads has to answer both
+    // Post and Request { method: "POST", .. }
+    Request {
+        method: String,
+        headers: HttpServerHeaders,
+        query: Vec<(String, String)>,
+        body: HttpServerBody,
+        response_sender: mpsc::Sender<HttpServerResponse>,
     }
 }
 
@@ -64,23 +133,41 @@ pub fn start_http_server(
                 let http_server = http_server.clone();
                 connection_counter += 1;
                 let _read_thread = std::thread::spawn(move || {
-                    
-                    let headers = HttpServerHeaders::from_tcp_stream(&mut tcp_stream);
-                    if headers.is_none() {
-                        return http_error_out(tcp_stream, 500);
-                    }
-                    let headers = headers.unwrap();
-                    
-                    if headers.sec_websocket_key.is_some() {
-                        return handle_web_socket(http_server, tcp_stream, headers, connection_counter);
-                    }
-                    if headers.verb == "POST" {
-                        return handle_post(http_server, tcp_stream, headers);
-                    }
-                    if headers.verb == "GET" {
-                        return handle_get(http_server, tcp_stream, headers);
+                    let mut reader = BufReader::new(tcp_stream.try_clone().unwrap());
+                    let addr = tcp_stream.peer_addr().unwrap();
+                    let mut first_request = true;
+                    loop {
+                        let headers = HttpServerHeaders::from_buf_reader(&mut reader, addr);
+                        if headers.is_none() {
+                            // a persistent connection that goes away or idles out between requests isn't an error
+                            if first_request {
+                                return http_error_out(tcp_stream, 500);
+                            }
+                            break;
+                        }
+                        let headers = headers.unwrap();
+                        first_request = false;
+                        
+                        let keep_alive = if headers.sec_websocket_key.is_some() {
+                            let _ = tcp_stream.set_read_timeout(None);
+                            let leftover = reader.buffer().to_vec();
+                            return handle_web_socket(http_server, tcp_stream, headers, connection_counter, &leftover);
+                        }
+                        else if headers.verb == "POST" && headers.content_length.is_some_and( | v | v <= http_server.post_max_size) && !is_chunked(&headers) {
+                            handle_post(&http_server, &mut tcp_stream, &mut reader, headers)
+                        }
+                        else if headers.verb == "GET" {
+                            handle_get(&http_server, &mut tcp_stream, &mut reader, headers)
+                        }
+                        else {
+                            handle_request(&http_server, &mut tcp_stream, &mut reader, headers)
+                        };
+                        if !keep_alive {
+                            break;
+                        }
+                        let _ = tcp_stream.set_read_timeout(Some(KEEP_ALIVE_TIMEOUT));
                     }
-                    http_error_out(tcp_stream, 500)
+                    let _ = tcp_stream.shutdown(Shutdown::Both);
                 });
             }
         })
@@ -88,51 +175,274 @@ pub fn start_http_server(
     Some(listen_thread)
 }
 
-fn handle_post(http_server: HttpServer, mut tcp_stream: TcpStream, headers: HttpServerHeaders) {
+// writes the response and returns if the connection can be reused for the next request
+fn write_response(tcp_stream: &mut TcpStream, keep_alive: bool, response: &HttpServerResponse) -> bool {
+    if write_bytes_to_tcp_stream_no_error(tcp_stream, response.header.as_bytes()) ||
+    write_bytes_to_tcp_stream_no_error(tcp_stream, &response.body) {
+        return false
+    }
+    if let Some(file) = &response.file {
+        if !write_file_body(tcp_stream, file) {
+            return false
+        }
+    }
+    // without a length the body ends at connection close
+    let mut has_length = false;
+    for line in response.header.split("\r\n").skip(1) {
+        if let Some((name, value)) = line.split_once(':') {
+            let name = name.trim();
+            if name.eq_ignore_ascii_case("connection") && value.to_ascii_lowercase().contains("close") {
+                return false
+            }
+            if name.eq_ignore_ascii_case("content-length") {
+                has_length = true;
+            }
+        }
+    }
+    let status = response.header.split(' ').nth(1).unwrap_or("");
+    keep_alive && (has_length || status == "304" || status == "204")
+}
+
+fn write_file_body(tcp_stream: &mut TcpStream, body: &HttpServerFileBody) -> bool {
+    let mut file = &body.file;
+    if file.seek(SeekFrom::Start(body.start)).is_err() {
+        return false
+    }
+    let mut buffer = vec![0u8; 65536];
+    let mut left = body.len;
+    while left > 0 {
+        let len = left.min(buffer.len() as u64) as usize;
+        // a file that shrunk underneath us would leave the response short, so the connection can't be reused
+        if file.read_exact(&mut buffer[..len]).is_err() ||
+        write_bytes_to_tcp_stream_no_error(tcp_stream, &buffer[..len]) {
+            return false
+        }
+        left -= len as u64;
+    }
+    true
+}
+
+fn handle_post(http_server: &HttpServer, tcp_stream: &mut TcpStream, reader: &mut BufReader<TcpStream>, headers: HttpServerHeaders) -> bool {
     // we have to have a content-length or bust
     if headers.content_length.is_none() {
-        return http_error_out(tcp_stream, 500);
+        write_http_error(tcp_stream, 500);
+        return false
     }
     let content_length = headers.content_length.unwrap();
     if content_length > http_server.post_max_size {
-        return http_error_out(tcp_stream, 500);
+        write_http_error(tcp_stream, 500);
+        return false
     }
-    let bytes_total = content_length as usize;
-    let mut body = Vec::new();
-    body.resize(bytes_total, 0u8);
-    
-    let mut bytes_left = bytes_total;
-    while bytes_left > 0 {
-        let buf = &mut body[(bytes_total - bytes_left)..bytes_total];
-        let bytes_read = tcp_stream.read(buf);
-        if bytes_read.is_err() {
-            return http_error_out(tcp_stream, 500);
-        }
-        let bytes_read = bytes_read.unwrap();
-        if bytes_read == 0 {
-            return http_error_out(tcp_stream, 500);
-        }
-        bytes_left -= bytes_read;
+    let mut body = vec![0u8; content_length as usize];
+    write_continue_if_expected(tcp_stream, &headers);
+    if reader.read_exact(&mut body).is_err() {
+        write_http_error(tcp_stream, 500);
+        return false
     }
     
     let (tx_socket, rx_socket) = mpsc::channel::<HttpServerResponse> ();
+    let keep_alive = headers.keep_alive;
     if http_server.request.send(HttpServerRequest::Post {
         headers,
         body,
         response: tx_socket
     }).is_err() {
-        return http_error_out(tcp_stream, 500);
+        write_http_error(tcp_stream, 500);
+        return false
     };
     
     if let Ok(response) = rx_socket.recv() {
-        write_bytes_to_tcp_stream_no_error(&mut tcp_stream, response.header.as_bytes());
-        write_bytes_to_tcp_stream_no_error(&mut tcp_stream, &response.body);
+        return write_response(tcp_stream, keep_alive, &response)
     }
-    let _ = tcp_stream.shutdown(Shutdown::Both);
+    false
+}
+
+// clients that send Expect: 100-continue wait for this before sending the body
+fn write_continue_if_expected(tcp_stream: &mut TcpStream, headers: &HttpServerHeaders) {
+    if headers.get_header("Expect").is_some_and( | v | v.eq_ignore_ascii_case("100-continue")) {
+        write_bytes_to_tcp_stream_no_error(tcp_stream, b"HTTP/1.1 100 Continue\r\n\r\n");
+    }
+}
+
+fn is_chunked(headers: &HttpServerHeaders) -> bool {
+    headers.get_header("Transfer-Encoding").is_some_and( | v | v.to_ascii_lowercase().contains("chunked"))
 }
 
-fn handle_web_socket(http_server: HttpServer, mut tcp_stream: TcpStream, headers: HttpServerHeaders, web_socket_id: u64) {
-    let upgrade_response = ServerWebSocket::create_upgrade_response(headers.sec_websocket_key.as_ref().unwrap());
+// reads a request body delimited by either Content-Length or chunked transfer encoding
+enum RequestBody {
+    Length(u64),
+    // bytes left in the current chunk, 0 means a chunk size line comes next
+    Chunked(u64),
+    Done
+}
+
+impl RequestBody {
+    fn new(headers: &HttpServerHeaders) -> Self {
+        if is_chunked(headers) {
+            Self::Chunked(0)
+        }
+        else if let Some(len) = headers.content_length.filter( | v | *v > 0) {
+            Self::Length(len)
+        }
+        else {
+            Self::Done
+        }
+    }
+    
+    // appends the next piece of the body, returns false if the body is malformed or the connection broke
+    fn read(&mut self, reader: &mut BufReader<TcpStream>, out: &mut Vec<u8>) -> bool {
+        match self {
+            Self::Done => true,
+            Self::Length(left) => {
+                let len = (*left).min(65536);
+                if !Self::read_bytes(reader, len as usize, out) {
+                    return false
+                }
+                *left -= len;
+                if *left == 0 {
+                    *self = Self::Done;
+                }
+                true
+            }
+            Self::Chunked(0) => {
+                let mut line = String::new();
+                if reader.read_line(&mut line).is_err() || line.len() > 1024 {
+                    return false
+                }
+                let size = line.split(';').next().unwrap().trim();
+                let size = if let Ok(size) = u64::from_str_radix(size, 16) {size} else {
+                    return false
+                };
+                if size == 0 {
+                    // skip trailers up to the closing empty line
+                    loop {
+                        line.clear();
+                        match reader.read_line(&mut line) {
+                            Ok(0) | Err(_) => return false,
+                            Ok(_) if line == "\r\n" => break,
+                            Ok(_) => ()
+                        }
+                    }
+                    *self = Self::Done;
+                }
+                else {
+                    *self = Self::Chunked(size);
+                }
+                true
+            }
+            Self::Chunked(left) => {
+                let len = (*left).min(65536);
+                if !Self::read_bytes(reader, len as usize, out) {
+                    return false
+                }
+                *left -= len;
+                if *left == 0 {
+                    let mut crlf = [0u8; 2];
+                    if reader.read_exact(&mut crlf).is_err() || &crlf != b"\r\n" {
+                        return false
+                    }
+                }
+                true
+            }
+        }
+    }
+    
+    fn read_bytes(reader: &mut BufReader<TcpStream>, len: usize, out: &mut Vec<u8>) -> bool {
+        let start = out.len();
+        out.resize(start + len, 0);
+        reader.read_exact(&mut out[start..]).is_ok()
+    }
+    
+    fn is_done(&self) -> bool {
+        matches!(self, Self::Done)
+    }
+    
+    // skips the rest of the body so the next request on the connection starts at its head
+    fn drain(&mut self, reader: &mut BufReader<TcpStream>) -> bool {
+        let mut data = Vec::new();
+        while !self.is_done() {
+            data.clear();
+            if !self.read(reader, &mut data) {
+                return false
+            }
+        }
+        true
+    }
+}
+
+fn handle_request(http_server: &HttpServer, tcp_stream: &mut TcpStream, reader: &mut BufReader<TcpStream>, headers: HttpServerHeaders) -> bool {
+    let keep_alive = headers.keep_alive;
+    let is_head = headers.verb == "HEAD";
+    let query = headers.search.as_deref().map(parse_query_string).unwrap_or_default();
+    let mut request_body = RequestBody::new(&headers);
+    if !request_body.is_done() {
+        write_continue_if_expected(tcp_stream, &headers);
+    }
+    
+    // small bodies are handed over whole, anything beyond post_max_size gets streamed
+    let mut body = Vec::new();
+    while !request_body.is_done() && body.len() as u64 <= http_server.post_max_size {
+        if !request_body.read(reader, &mut body) {
+            write_http_error(tcp_stream, 400);
+            return false
+        }
+    }
+    let (tx_socket, rx_socket) = mpsc::channel::<HttpServerResponse> ();
+    let method = headers.verb.clone();
+    if request_body.is_done() && body.len() as u64 <= http_server.post_max_size {
+        if http_server.request.send(HttpServerRequest::Request {
+            method,
+            headers,
+            query,
+            body: HttpServerBody::Complete(body),
+            response_sender: tx_socket
+        }).is_err() {
+            write_http_error(tcp_stream, 500);
+            return false
+        }
+    }
+    else {
+        let (tx_body, rx_body) = mpsc::channel::<Vec<u8 >> ();
+        if http_server.request.send(HttpServerRequest::Request {
+            method,
+            headers,
+            query,
+            body: HttpServerBody::Stream(rx_body),
+            response_sender: tx_socket
+        }).is_err() {
+            write_http_error(tcp_stream, 500);
+            return false
+        }
+        let _ = tx_body.send(body);
+        while !request_body.is_done() {
+            let mut data = Vec::new();
+            if !request_body.read(reader, &mut data) {
+                // dropping tx_body without the end marker tells the app the upload broke off
+                return false
+            }
+            if !data.is_empty() {
+                let _ = tx_body.send(data);
+            }
+        }
+        let _ = tx_body.send(Vec::new());
+    }
+    
+    if let Ok(mut response) = rx_socket.recv() {
+        if is_head {
+            response.body.clear();
+            response.file = None;
+        }
+        return write_response(tcp_stream, keep_alive, &response)
+    }
+    false
+}
+
+fn handle_web_socket(http_server: HttpServer, mut tcp_stream: TcpStream, headers: HttpServerHeaders, web_socket_id: u64, leftover: &[u8]) {
+    let permessage_deflate = headers.sec_websocket_extensions.as_ref().is_some_and( | v | ServerWebSocket::accept_permessage_deflate(v));
+    let upgrade_response = ServerWebSocket::create_upgrade_response_with_extensions(
+        headers.sec_websocket_key.as_ref().unwrap(),
+        if permessage_deflate {Some(WEB_SOCKET_PERMESSAGE_DEFLATE)} else {None}
+    );
 
     write_bytes_to_tcp_stream_no_error(&mut tcp_stream, upgrade_response.as_bytes());
     
@@ -147,9 +457,8 @@ fn handle_web_socket(http_server: HttpServer, mut tcp_stream: TcpStream, headers
                     if data.is_empty(){
                         break
                     }
-                    let header = ServerWebSocketMessageHeader::from_len(data.len(), ServerWebSocketMessageFormat::Binary, false);
-                    write_bytes_to_tcp_stream_no_error(&mut write_tcp_stream, header.as_slice());
-                    write_bytes_to_tcp_stream_no_error(&mut write_tcp_stream, &data);
+                    let frame = ServerWebSocket::build_data_frame(&data, ServerWebSocketMessageFormat::Binary, false, permessage_deflate);
+                    write_bytes_to_tcp_stream_no_error(&mut write_tcp_stream, &frame);
                 },
                 Err(RecvTimeoutError::Timeout)=>{ 
                     write_bytes_to_tcp_stream_no_error(&mut write_tcp_stream, &SERVER_WEB_SOCKET_PING_MESSAGE);
@@ -172,9 +481,15 @@ fn handle_web_socket(http_server: HttpServer, mut tcp_stream: TcpStream, headers
     };
     
     let mut web_socket = ServerWebSocket::new();
+    web_socket.set_max_message_size(http_server.web_socket_max_message_size);
+    web_socket.set_permessage_deflate(permessage_deflate);
+    // frames the client sent right behind the upgrade request are already in the request reader
+    let mut data = [0u8; 65535];
+    data[0..leftover.len()].copy_from_slice(leftover);
+    let mut leftover_len = leftover.len();
     loop {
-        let mut data = [0u8; 65535];
-        match tcp_stream.read(&mut data) {
+        let read = if leftover_len > 0 {Ok(std::mem::take(&mut leftover_len))} else {tcp_stream.read(&mut data)};
+        match read {
             Ok(n) => {
                 if n == 0 {
                     let _ = tcp_stream.shutdown(Shutdown::Both);
@@ -201,7 +516,7 @@ fn handle_web_socket(http_server: HttpServer, mut tcp_stream: TcpStream, headers
                                 let _ = tx_socket.send(Vec::new());
                             };
                         },
-                        Ok(ServerWebSocketMessage::Close) => {
+                        Ok(ServerWebSocketMessage::Close {..}) => {
                             let _ = tcp_stream.shutdown(Shutdown::Both);
                         }
                         Err(e) => {
@@ -226,19 +541,182 @@ fn handle_web_socket(http_server: HttpServer, mut tcp_stream: TcpStream, headers
     });
 }
 
-fn handle_get(http_server: HttpServer, mut tcp_stream: TcpStream, headers: HttpServerHeaders) {
+fn handle_get(http_server: &HttpServer, tcp_stream: &mut TcpStream, reader: &mut BufReader<TcpStream>, headers: HttpServerHeaders) -> bool {
+    // a body on a get has no meaning, but it has to be read past to keep the connection usable
+    let mut request_body = RequestBody::new(&headers);
+    if !request_body.is_done() {
+        write_continue_if_expected(tcp_stream, &headers);
+    }
+    if !request_body.drain(reader) {
+        write_http_error(tcp_stream, 400);
+        return false
+    }
     // send our channel the post
     let (tx_socket, rx_socket) = mpsc::channel::<HttpServerResponse> ();
+    let keep_alive = headers.keep_alive;
     if http_server.request.send(HttpServerRequest::Get {
         headers,
         response_sender: tx_socket
     }).is_err() {
-        return http_error_out(tcp_stream, 500);
+        write_http_error(tcp_stream, 500);
+        return false
     };
     
     if let Ok(response) = rx_socket.recv() {
-        write_bytes_to_tcp_stream_no_error(&mut tcp_stream, response.header.as_bytes());
-        write_bytes_to_tcp_stream_no_error(&mut tcp_stream, &response.body);
+        return write_response(tcp_stream, keep_alive, &response)
     }
-    let _ = tcp_stream.shutdown(Shutdown::Both);
-}
\ No newline at end of file
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // starts a server on a free port that hands every request to handler
+    fn start(post_max_size: u64, web_socket_max_message_size: usize, handler: impl Fn(HttpServerRequest) + Send + 'static) -> SocketAddr {
+        let listen_address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let (tx_request, rx_request) = mpsc::channel();
+        start_http_server(HttpServer {
+            listen_address,
+            request: tx_request,
+            post_max_size,
+            web_socket_max_message_size,
+        }).unwrap();
+        std::thread::spawn(move || while let Ok(request) = rx_request.recv() {
+            handler(request)
+        });
+        listen_address
+    }
+
+    fn read_response(reader: &mut BufReader<TcpStream>, is_head: bool) -> (String, Vec<u8>) {
+        let mut header = String::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            assert!(!line.is_empty(), "connection closed in {:?}", header);
+            header.push_str(&line);
+            if line == "\r\n" {
+                break
+            }
+        }
+        let len = header.split("\r\n").find_map( | v | v.strip_prefix("Content-Length: ")).filter( | _ | !is_head).map_or(0, | v | v.parse().unwrap());
+        let mut body = vec![0u8; len];
+        reader.read_exact(&mut body).unwrap();
+        (header, body)
+    }
+
+    fn ok_response(body: &[u8]) -> HttpServerResponse {
+        HttpServerResponse {
+            header: format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", body.len()),
+            body: body.to_vec(),
+            file: None
+        }
+    }
+
+    #[test]
+    fn keeps_connections_alive() {
+        let file_path = std::env::temp_dir().join(format!("makepad-http-keep-alive-{}", std::process::id()));
+        std::fs::write(&file_path, b"0123456789").unwrap();
+        let file = File::open(&file_path).unwrap();
+        let _ = std::fs::remove_file(&file_path);
+        let addr = start(1024, WEB_SOCKET_DEFAULT_MAX_MESSAGE_SIZE, move | request | match request {
+            HttpServerRequest::Get {headers, response_sender} if headers.path == "/file" => {
+                let _ = response_sender.send(HttpServerResponse {
+                    header: "HTTP/1.1 206 Partial Content\r\nContent-Length: 4\r\n\r\n".to_string(),
+                    body: Vec::new(),
+                    file: Some(HttpServerFileBody {file: file.try_clone().unwrap(), start: 3, len: 4})
+                });
+            }
+            HttpServerRequest::Get {headers, response_sender} => {
+                let _ = response_sender.send(ok_response(headers.path.as_bytes()));
+            }
+            HttpServerRequest::Post {body, response, ..} => {
+                let _ = response.send(ok_response(&body));
+            }
+            HttpServerRequest::Request {response_sender, ..} => {
+                let _ = response_sender.send(ok_response(b"head"));
+            }
+            _ => ()
+        });
+
+        let mut tcp_stream = TcpStream::connect(addr).unwrap();
+        let mut reader = BufReader::new(tcp_stream.try_clone().unwrap());
+        // all requests go out at once, the bodies of the gets have to be skipped to find the next one
+        tcp_stream.write_all(
+            b"GET /a HTTP/1.1\r\nHost: x\r\nContent-Length: 5\r\n\r\nhello\
+            GET /b HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n0\r\n\r\n\
+            POST /c HTTP/1.1\r\nHost: x\r\nContent-Length: 3\r\n\r\nxyz\
+            GET /file HTTP/1.1\r\nHost: x\r\n\r\n\
+            HEAD /d HTTP/1.1\r\nHost: x\r\n\r\n\
+            GET /e HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n"
+        ).unwrap();
+        assert_eq!(read_response(&mut reader, false).1, b"/a");
+        assert_eq!(read_response(&mut reader, false).1, b"/b");
+        assert_eq!(read_response(&mut reader, false).1, b"xyz");
+        assert_eq!(read_response(&mut reader, false).1, b"3456");
+        // a head response keeps its Content-Length but has no body
+        let (header, _) = read_response(&mut reader, true);
+        assert!(header.contains("Content-Length: 4\r\n"));
+        assert_eq!(read_response(&mut reader, false).1, b"/e");
+        // and the server closes after the request that asked for it
+        assert_eq!(reader.read(&mut [0u8; 1]).unwrap(), 0);
+    }
+
+    #[test]
+    fn decodes_chunked_request_bodies() {
+        let addr = start(8, WEB_SOCKET_DEFAULT_MAX_MESSAGE_SIZE, | request | if let HttpServerRequest::Request {method, body, response_sender, ..} = request {
+            let kind = if matches!(body, HttpServerBody::Complete(_)) {"complete"} else {"stream"};
+            let body = body.read_to_end(1024).unwrap();
+            let _ = response_sender.send(ok_response(format!("{} {} {}", method, kind, String::from_utf8(body).unwrap()).as_bytes()));
+        });
+
+        let mut tcp_stream = TcpStream::connect(addr).unwrap();
+        let mut reader = BufReader::new(tcp_stream.try_clone().unwrap());
+        tcp_stream.write_all(
+            b"POST /small HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nwiki\r\n3;name=value\r\npe!\r\n0\r\nTrailer: x\r\n\r\n\
+            POST /large HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\nA\r\n0123456789\r\n5\r\nabcde\r\n0\r\n\r\n\
+            PUT /length HTTP/1.1\r\nHost: x\r\nContent-Length: 3\r\n\r\nabc\
+            POST /broken HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n"
+        ).unwrap();
+        // bodies up to post_max_size are handed over whole, larger ones get streamed
+        assert_eq!(read_response(&mut reader, false).1, b"POST complete wikipe!");
+        assert_eq!(read_response(&mut reader, false).1, b"POST stream 0123456789abcde");
+        assert_eq!(read_response(&mut reader, false).1, b"PUT complete abc");
+        let (header, _) = read_response(&mut reader, false);
+        assert!(header.starts_with("HTTP/1.1 400"));
+        assert_eq!(reader.read(&mut [0u8; 1]).unwrap(), 0);
+    }
+
+    #[test]
+    fn limits_web_socket_messages() {
+        let (tx_event, rx_event) = mpsc::channel();
+        let addr = start(1024, 16, move | request | match request {
+            HttpServerRequest::BinaryMessage {data, ..} => {
+                let _ = tx_event.send(Some(data));
+            }
+            HttpServerRequest::DisconnectWebSocket {..} => {
+                let _ = tx_event.send(None);
+            }
+            _ => ()
+        });
+
+        let mut tcp_stream = TcpStream::connect(addr).unwrap();
+        let mut reader = BufReader::new(tcp_stream.try_clone().unwrap());
+        tcp_stream.write_all(b"GET /ws HTTP/1.1\r\nHost: x\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n").unwrap();
+        let (header, _) = read_response(&mut reader, true);
+        assert!(header.starts_with("HTTP/1.1 101"));
+
+        let masked_frame = | payload: &[u8] | {
+            let mask = [1u8, 2, 3, 4];
+            let mut frame = vec![0x82, 0x80 | payload.len() as u8];
+            frame.extend_from_slice(&mask);
+            frame.extend(payload.iter().enumerate().map( | (i, v) | v ^ mask[i & 3]));
+            frame
+        };
+        tcp_stream.write_all(&masked_frame(&[7u8; 16])).unwrap();
+        assert_eq!(rx_event.recv_timeout(Duration::from_secs(5)).unwrap(), Some(vec![7u8; 16]));
+        // one byte over web_socket_max_message_size drops the connection without delivering it
+        tcp_stream.write_all(&masked_frame(&[8u8; 17])).unwrap();
+        assert_eq!(rx_event.recv_timeout(Duration::from_secs(5)).unwrap(), None);
+    }
+}
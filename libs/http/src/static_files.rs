@@ -0,0 +1,367 @@
+// serves files from a directory with ETag/304, single Range requests and precompressed .br/.gz variants
+// the file (or the requested range of it) is streamed from disk when the response is written
+
+use std::path::{Component, Path, PathBuf};
+use std::fs::File;
+use std::time::UNIX_EPOCH;
+use crate::utils::{HttpServerHeaders, percent_decode_path};
+use crate::server::{HttpServerResponse, HttpServerFileBody};
+
+pub struct HttpStaticFiles {
+    pub root: PathBuf,
+    // extra header lines (each ending in \r\n) added to every response, like cache or cross origin policies
+    pub headers: String,
+    // when not empty only files with one of these extensions are served
+    pub extensions: Vec<String>,
+}
+
+pub fn mime_type_from_path(path: &str) -> &'static str {
+    let ext = if let Some(pos) = path.rfind('.') {path[pos + 1..].to_ascii_lowercase()} else {String::new()};
+    match ext.as_str() {
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" | "mjs" => "text/javascript",
+        "json" => "application/json",
+        "wasm" => "application/wasm",
+        "txt" => "text/plain",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "ttf" => "font/ttf",
+        "otf" => "font/otf",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "ico" => "image/x-icon",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        _ => "application/octet-stream"
+    }
+}
+
+// parses a single range 'bytes=start-end', 'bytes=start-' or 'bytes=-suffix' into an inclusive range
+// Some(None) means the range can't be satisfied, None means the header should be ignored
+pub fn parse_range_header(range: &str, len: u64) -> Option<Option<(u64, u64)>> {
+    let range = range.trim().strip_prefix("bytes=") ?;
+    if range.contains(',') { // we don't do multipart ranges, serving the whole file is allowed
+        return None
+    }
+    let (start, end) = range.split_once('-') ?;
+    let (start, end) = (start.trim(), end.trim());
+    if start.is_empty() {
+        let suffix: u64 = end.parse().ok() ?;
+        if suffix == 0 || len == 0 {
+            return Some(None)
+        }
+        return Some(Some((len.saturating_sub(suffix), len - 1)))
+    }
+    let start: u64 = start.parse().ok() ?;
+    let end: u64 = if end.is_empty() {u64::MAX} else {end.parse().ok() ?};
+    if start >= len || end < start {
+        return Some(None)
+    }
+    Some(Some((start, end.min(len - 1))))
+}
+
+impl HttpStaticFiles {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            headers: String::new(),
+            extensions: Vec::new(),
+        }
+    }
+
+    pub fn with_headers(mut self, headers: &str) -> Self {
+        self.headers.push_str(headers);
+        self
+    }
+
+    // limits the served files to these extensions, like ["html", "wasm", "js"]
+    pub fn with_extensions(mut self, extensions: &[&str]) -> Self {
+        self.extensions.extend(extensions.iter().map( | v | v.to_ascii_lowercase()));
+        self
+    }
+
+    pub fn serve(&self, headers: &HttpServerHeaders) -> HttpServerResponse {
+        self.serve_path(&headers.path_no_slash, headers)
+    }
+
+    // serves path relative to the root, with a 404 if it is not a file that may be served
+    pub fn serve_path(&self, path: &str, headers: &HttpServerHeaders) -> HttpServerResponse {
+        self.serve_file(path, headers).unwrap_or_else( || HttpServerResponse {
+            header: format!("HTTP/1.1 404 Not Found\r\n{}Content-Length: 0\r\n\r\n", self.headers),
+            body: Vec::new(),
+            file: None
+        })
+    }
+
+    // hidden files and directories, like .git or .env, are never served
+    fn is_allowed(&self, path: &str) -> bool {
+        let path = Path::new(path);
+        if !path.components().all( | v | matches!(v, Component::Normal(v) if !v.to_string_lossy().starts_with('.'))) {
+            return false
+        }
+        self.extensions.is_empty() || path.extension().is_some_and( | ext | {
+            self.extensions.contains(&ext.to_string_lossy().to_ascii_lowercase())
+        })
+    }
+
+    // the file has to stay inside the root after following symbolic links
+    fn resolve(&self, file_path: &Path) -> Option<PathBuf> {
+        let root = self.root.canonicalize().ok() ?;
+        let file_path = file_path.canonicalize().ok() ?;
+        if file_path.starts_with(&root) && file_path.is_file() {Some(file_path)} else {None}
+    }
+
+    fn serve_file(&self, path: &str, headers: &HttpServerHeaders) -> Option<HttpServerResponse> {
+        let path = percent_decode_path(path) ?;
+        let path = path.trim_start_matches('/');
+        if path.contains('\\') || !self.is_allowed(path) {
+            return None
+        }
+        let joined_path = self.root.join(path);
+        let file_path = self.resolve(&joined_path) ?;
+        let mime_type = mime_type_from_path(path);
+
+        // pick a precompressed variant if the client takes it
+        let accept_encoding = headers.accept_encoding.as_deref().unwrap_or("");
+        let accepts = | encoding: &str | accept_encoding.split(',').any( | v | v.split(';').next().unwrap().trim() == encoding);
+        let mut encoding = None;
+        let mut serve_path = file_path;
+        for (name, ext) in [("br", "br"), ("gzip", "gz")] {
+            if accepts(name) {
+                if let Some(compressed) = self.resolve(&Self::with_extension(&joined_path, ext)) {
+                    encoding = Some(name);
+                    serve_path = compressed;
+                    break;
+                }
+            }
+        }
+
+        let file = File::open(&serve_path).ok() ?;
+        let meta = file.metadata().ok() ?;
+        let len = meta.len();
+        let modified = meta.modified().ok().and_then( | v | v.duration_since(UNIX_EPOCH).ok()).map_or(0, | v | v.as_nanos());
+        let etag = format!("\"{:x}-{:x}{}\"", modified, len, encoding.map_or(String::new(), | v | format!("-{}", v)));
+
+        let mut common = format!("ETag: {}\r\nAccept-Ranges: bytes\r\nVary: Accept-Encoding\r\n{}", etag, self.headers);
+        if let Some(encoding) = encoding {
+            common.push_str(&format!("Content-Encoding: {}\r\n", encoding));
+        }
+
+        if let Some(if_none_match) = headers.get_header("If-None-Match") {
+            if if_none_match.split(',').any( | v | {
+                let v = v.trim();
+                v == "*" || v.trim_start_matches("W/") == etag
+            }) {
+                return Some(HttpServerResponse {
+                    header: format!("HTTP/1.1 304 Not Modified\r\n{}\r\n", common),
+                    body: Vec::new(),
+                    file: None
+                })
+            }
+        }
+
+        // a Range only counts if an If-Range is absent or still matches
+        let range = headers.get_header("Range").filter( | _ | {
+            headers.get_header("If-Range").is_none_or( | v | v == etag)
+        });
+        if let Some(range) = range.and_then( | v | parse_range_header(v, len)) {
+            let (start, end) = if let Some(range) = range {range} else {
+                return Some(HttpServerResponse {
+                    header: format!("HTTP/1.1 416 Range Not Satisfiable\r\nContent-Range: bytes */{}\r\n{}Content-Length: 0\r\n\r\n", len, common),
+                    body: Vec::new(),
+                    file: None
+                })
+            };
+            return Some(HttpServerResponse {
+                header: format!(
+                    "HTTP/1.1 206 Partial Content\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n{}Content-Length: {}\r\n\r\n",
+                    mime_type,
+                    start,
+                    end,
+                    len,
+                    common,
+                    end - start + 1
+                ),
+                body: Vec::new(),
+                file: Some(HttpServerFileBody {file, start, len: end - start + 1})
+            })
+        }
+
+        Some(HttpServerResponse {
+            header: format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: {}\r\n{}Content-Length: {}\r\n\r\n",
+                mime_type,
+                common,
+                len
+            ),
+            body: Vec::new(),
+            file: Some(HttpServerFileBody {file, start: 0, len})
+        })
+    }
+
+    fn with_extension(path: &Path, ext: &str) -> PathBuf {
+        let mut path = path.as_os_str().to_owned();
+        path.push(".");
+        path.push(ext);
+        path.into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(format!("makepad-http-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&path);
+            std::fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn write(&self, name: &str, data: &[u8]) {
+            std::fs::write(self.0.join(name), data).unwrap();
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn data() -> Vec<u8> {
+        (0..100000u32).map( | v | (v % 251) as u8).collect()
+    }
+
+    fn request(files: &HttpStaticFiles, path: &str, extra_headers: &str) -> (String, Vec<u8>) {
+        let head = format!("GET {} HTTP/1.1\r\nHost: localhost\r\n{}\r\n", path, extra_headers);
+        let headers = HttpServerHeaders::from_buf_reader(&mut Cursor::new(head.into_bytes()), "127.0.0.1:80".parse().unwrap()).unwrap();
+        let response = files.serve(&headers);
+        let mut body = response.body;
+        if let Some(mut file) = response.file {
+            file.file.seek(SeekFrom::Start(file.start)).unwrap();
+            file.file.take(file.len).read_to_end(&mut body).unwrap();
+        }
+        (response.header, body)
+    }
+
+    fn status(header: &str) -> &str {
+        header.split(' ').nth(1).unwrap()
+    }
+
+    fn header_value<'a>(header: &'a str, name: &str) -> Option<&'a str> {
+        header.split("\r\n").find_map( | v | v.strip_prefix(name).and_then( | v | v.strip_prefix(": ")))
+    }
+
+    fn setup(name: &str) -> (TempDir, HttpStaticFiles) {
+        let dir = TempDir::new(name);
+        dir.write("index.html", b"<html></html>");
+        dir.write("a b+c.txt", b"spaces");
+        dir.write("app.js", b"console.log(1)");
+        dir.write("app.js.gz", b"gzipped");
+        dir.write("data.bin", &data());
+        dir.write(".env", b"secret");
+        let files = HttpStaticFiles::new(&dir.0);
+        (dir, files)
+    }
+
+    #[test]
+    fn serves_files() {
+        let (_dir, files) = setup("serves-files");
+        let (header, body) = request(&files, "/", "");
+        assert_eq!(status(&header), "200");
+        assert_eq!(header_value(&header, "Content-Type"), Some("text/html"));
+        assert_eq!(header_value(&header, "Content-Length"), Some("13"));
+        assert_eq!(body, b"<html></html>");
+
+        let (header, body) = request(&files, "/data.bin", "");
+        assert_eq!(header_value(&header, "Content-Length"), Some("100000"));
+        assert_eq!(body, data());
+
+        // the path is percent decoded, and a + in a path is just a +
+        assert_eq!(request(&files, "/a%20b+c.txt", "").1, b"spaces");
+
+        for path in ["/.env", "/%2eenv", "/../etc/passwd", "/%2e%2e/etc/passwd", "/missing.txt", "/index.html%00"] {
+            let (header, body) = request(&files, path, "");
+            assert_eq!(status(&header), "404", "{}", path);
+            assert!(body.is_empty());
+        }
+
+        let files = HttpStaticFiles::new(&files.root).with_extensions(&["html"]);
+        assert_eq!(status(&request(&files, "/index.html", "").0), "200");
+        assert_eq!(status(&request(&files, "/app.js", "").0), "404");
+    }
+
+    #[test]
+    fn serves_precompressed_files_and_not_modified() {
+        let (_dir, files) = setup("precompressed");
+        let (header, body) = request(&files, "/app.js", "Accept-Encoding: br;q=1, gzip\r\n");
+        assert_eq!(header_value(&header, "Content-Encoding"), Some("gzip"));
+        assert_eq!(header_value(&header, "Content-Type"), Some("text/javascript"));
+        assert_eq!(body, b"gzipped");
+
+        let (header, body) = request(&files, "/app.js", "");
+        assert_eq!(header_value(&header, "Content-Encoding"), None);
+        assert_eq!(body, b"console.log(1)");
+
+        let etag = header_value(&header, "ETag").unwrap().to_string();
+        let (header, body) = request(&files, "/app.js", &format!("If-None-Match: \"other\", W/{}\r\n", etag));
+        assert_eq!(status(&header), "304");
+        assert!(body.is_empty());
+        // the compressed variant is a different representation with its own tag
+        assert_eq!(status(&request(&files, "/app.js", &format!("Accept-Encoding: gzip\r\nIf-None-Match: {}\r\n", etag)).0), "200");
+    }
+
+    #[test]
+    fn serves_ranges() {
+        assert_eq!(parse_range_header("bytes=0-0", 10), Some(Some((0, 0))));
+        assert_eq!(parse_range_header("bytes=5-", 10), Some(Some((5, 9))));
+        assert_eq!(parse_range_header("bytes=-3", 10), Some(Some((7, 9))));
+        assert_eq!(parse_range_header("bytes=-30", 10), Some(Some((0, 9))));
+        assert_eq!(parse_range_header("bytes=8-30", 10), Some(Some((8, 9))));
+        assert_eq!(parse_range_header("bytes=10-", 10), Some(None));
+        assert_eq!(parse_range_header("bytes=5-4", 10), Some(None));
+        assert_eq!(parse_range_header("bytes=-0", 10), Some(None));
+        assert_eq!(parse_range_header("bytes=0-1,3-4", 10), None);
+        assert_eq!(parse_range_header("items=0-1", 10), None);
+
+        let (_dir, files) = setup("ranges");
+        let data = data();
+        let (header, body) = request(&files, "/data.bin", "Range: bytes=10-19\r\n");
+        assert_eq!(status(&header), "206");
+        assert_eq!(header_value(&header, "Content-Range"), Some("bytes 10-19/100000"));
+        assert_eq!(header_value(&header, "Content-Length"), Some("10"));
+        assert_eq!(body, &data[10..20]);
+
+        // an open ended range past the chunk size gets streamed like a whole file
+        let (header, body) = request(&files, "/data.bin", "Range: bytes=1000-\r\n");
+        assert_eq!(header_value(&header, "Content-Range"), Some("bytes 1000-99999/100000"));
+        assert_eq!(body, &data[1000..]);
+
+        let (header, body) = request(&files, "/data.bin", "Range: bytes=-5\r\n");
+        assert_eq!(body, &data[99995..]);
+        let etag = header_value(&header, "ETag").unwrap().to_string();
+
+        let (header, body) = request(&files, "/data.bin", "Range: bytes=100000-\r\n");
+        assert_eq!(status(&header), "416");
+        assert_eq!(header_value(&header, "Content-Range"), Some("bytes */100000"));
+        assert!(body.is_empty());
+
+        // a range for an older version of the file gets the whole current one
+        assert_eq!(status(&request(&files, "/data.bin", &format!("Range: bytes=0-9\r\nIf-Range: {}\r\n", etag)).0), "206");
+        assert_eq!(request(&files, "/data.bin", "Range: bytes=0-9\r\nIf-Range: \"old\"\r\n").1, data);
+    }
+}
@@ -21,10 +21,14 @@ pub fn write_bytes_to_tcp_stream_no_error(tcp_stream: &mut TcpStream, bytes: &[u
 }
 
 pub fn http_error_out(mut tcp_stream: TcpStream, code: usize) {
-    write_bytes_to_tcp_stream_no_error(&mut tcp_stream, format!("HTTP/1.1 {}\r\n\r\n", code).as_bytes());
+    write_http_error(&mut tcp_stream, code);
     let _ = tcp_stream.shutdown(Shutdown::Both);
 }
 
+pub fn write_http_error(tcp_stream: &mut TcpStream, code: usize) {
+    write_bytes_to_tcp_stream_no_error(tcp_stream, format!("HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", code).as_bytes());
+}
+
 
 pub fn split_header_line<'a>(inp: &'a str, what: &str) -> Option<&'a str> {
     let mut what_lc = what.to_string();
@@ -44,9 +48,9 @@ pub fn parse_url_path(url: &str) -> Option<(String, Option<String>)> {
     end_of_name ?;
     let end_of_name = end_of_name.unwrap();
     let mut search = None;
-    let end_of_name = if let Some(q) = url.find('?') {
-        search = Some(url[q..].to_string());
-        end_of_name.min(q)
+    let end_of_name = if let Some(q) = url[0..end_of_name].find('?') {
+        search = Some(url[q..end_of_name].to_string());
+        q
     }else {end_of_name};
     
     let mut url = url[0..end_of_name].to_string();
@@ -58,6 +62,48 @@ pub fn parse_url_path(url: &str) -> Option<(String, Option<String>)> {
     Some((url, search))
 }
 
+// splits a search string like ?a=1&b=x+y into decoded key value pairs
+pub fn parse_query_string(search: &str) -> Vec<(String, String)> {
+    let search = search.strip_prefix('?').unwrap_or(search);
+    search.split('&').filter( | v | !v.is_empty()).map( | pair | {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        (percent_decode(key), percent_decode(value))
+    }).collect()
+}
+
+pub fn percent_decode(input: &str) -> String {
+    String::from_utf8_lossy(&percent_decode_bytes(input, true)).into_owned()
+}
+
+// decodes the path of a url, where a + stays a +. None if it doesn't decode to
+// valid utf8 or contains a nul, as those can't name a file
+pub fn percent_decode_path(input: &str) -> Option<String> {
+    let path = String::from_utf8(percent_decode_bytes(input, false)).ok() ?;
+    if path.contains('\0') {None} else {Some(path)}
+}
+
+fn percent_decode_bytes(input: &str, plus_is_space: bool) -> Vec<u8> {
+    let bytes = input.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' if plus_is_space => out.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                if let Some(v) = std::str::from_utf8(&bytes[i + 1..i + 3]).ok().filter( | v | v.bytes().all( | c | c.is_ascii_hexdigit())).and_then( | v | u8::from_str_radix(v, 16).ok()) {
+                    out.push(v);
+                    i += 3;
+                    continue;
+                }
+                out.push(b'%');
+            }
+            v => out.push(v)
+        }
+        i += 1;
+    }
+    out
+}
+
 #[derive(Debug)]
 pub struct HttpServerHeaders {
     pub addr: SocketAddr,
@@ -68,21 +114,31 @@ pub struct HttpServerHeaders {
     pub search: Option<String>,
     pub content_length: Option<u64>,
     pub accept_encoding: Option<String>,
-    pub sec_websocket_key: Option<String>
+    pub sec_websocket_key: Option<String>,
+    pub sec_websocket_extensions: Option<String>,
+    pub keep_alive: bool,
 }
 
 impl HttpServerHeaders {
     pub fn from_tcp_stream(tcp_stream: &mut TcpStream) -> Option<HttpServerHeaders> {
         let addr = tcp_stream.peer_addr().unwrap();
         let mut reader = BufReader::new(tcp_stream);
-        
+        Self::from_buf_reader(&mut reader, addr)
+    }
+    
+    // reads exactly one request head, so a persistent connection can keep using the same reader for the body and the next request
+    pub fn from_buf_reader<R: BufRead>(reader: &mut R, addr: SocketAddr) -> Option<HttpServerHeaders> {
         let mut lines = Vec::new();
         let mut content_length = None;
         let mut accept_encoding = None;
         let mut sec_websocket_key = None;
+        let mut sec_websocket_extensions = None;
         let mut line = String::new();
         
-        while reader.read_line(&mut line).is_ok() { // TODO replace this with a non-line read
+        while let Ok(n) = reader.read_line(&mut line) {
+            if n == 0 { // connection closed
+                return None
+            }
             if line == "\r\n" { // the newline
                 break;
             }
@@ -97,6 +153,9 @@ impl HttpServerHeaders {
             if let Some(v) = split_header_line(&line, "sec-websocket-key: ") {
                 sec_websocket_key = Some(v.to_string());
             }
+            if let Some(v) = split_header_line(&line, "sec-websocket-extensions: ") {
+                sec_websocket_extensions = Some(v.to_string());
+            }
             if line.len() > 4096 || lines.len() > 4096 { // some overflow protection
                 return None
             }
@@ -106,30 +165,27 @@ impl HttpServerHeaders {
         if lines.len() <2 {
             return None;
         }
-        let verb;
-        let path;
-        if let Some(v) = split_header_line(&lines[0], "GET ") {
-            verb = "GET";
-            path = parse_url_path(v)
-        }
-        else if let Some(v) = split_header_line(&lines[0], "POST ") {
-            verb = "POST";
-            path = parse_url_path(v)
-        }
-        else if let Some(v) = split_header_line(&lines[0], "PUT ") {
-            verb = "PUT";
-            path = parse_url_path(v)
-        }
-        else if let Some(v) = split_header_line(&lines[0], "DELETE ") {
-            verb = "DELETE";
-            path = parse_url_path(v)
-        }
-        else {
-            return None
+        let mut verb_path = None;
+        for verb in ["GET", "POST", "PUT", "DELETE", "PATCH", "OPTIONS", "HEAD"] {
+            if let Some(v) = split_header_line(&lines[0], &format!("{} ", verb)) {
+                verb_path = Some((verb, parse_url_path(v)));
+                break;
+            }
         }
+        let (verb, path) = verb_path ?;
         path.as_ref() ?;
         let path = path.unwrap();
         
+        // HTTP/1.1 connections persist unless either side says otherwise
+        let mut keep_alive = lines[0].trim_end().ends_with("HTTP/1.1");
+        for line in &lines[1..] {
+            if let Some(v) = split_header_line(line, "Connection: ") {
+                if v.to_ascii_lowercase().contains("close") {
+                    keep_alive = false;
+                }
+            }
+        }
+        
         Some(HttpServerHeaders {
             addr,
             verb: verb.to_string(),
@@ -139,7 +195,64 @@ impl HttpServerHeaders {
             lines,
             content_length,
             accept_encoding,
-            sec_websocket_key
+            sec_websocket_key,
+            sec_websocket_extensions,
+            keep_alive
         })
     }
+    
+    // case insensitive lookup of any header line
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        for line in &self.lines[1..] {
+            if let Some((key, value)) = line.split_once(':') {
+                if key.trim().eq_ignore_ascii_case(name) {
+                    return Some(value.trim())
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn parses_query_strings() {
+        assert_eq!(parse_query_string("?a=1&b=x+y&&c&d=%41%2b%zz"), vec![
+            ("a".to_string(), "1".to_string()),
+            ("b".to_string(), "x y".to_string()),
+            ("c".to_string(), "".to_string()),
+            ("d".to_string(), "A+%zz".to_string()),
+        ]);
+        assert_eq!(parse_query_string(""), vec![]);
+        assert_eq!(percent_decode("%e2%82%ac%+1%4"), "€% 1%4");
+    }
+
+    #[test]
+    fn decodes_paths() {
+        assert_eq!(percent_decode_path("/a%20b+c.txt").as_deref(), Some("/a b+c.txt"));
+        assert_eq!(percent_decode_path("/%2e%2e/secret").as_deref(), Some("/../secret"));
+        assert_eq!(percent_decode_path("/a%00.txt"), None);
+        assert_eq!(percent_decode_path("/%ff"), None);
+    }
+
+    #[test]
+    fn parses_request_heads() {
+        let addr = "127.0.0.1:80".parse().unwrap();
+        let mut reader = Cursor::new(b"GET /dir/?q=1 HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\nPOST /x HTTP/1.0\r\nContent-Length: 5\r\n\r\n".to_vec());
+        let headers = HttpServerHeaders::from_buf_reader(&mut reader, addr).unwrap();
+        assert_eq!(headers.path, "/dir/index.html");
+        assert_eq!(headers.path_no_slash, "dir/index.html");
+        assert_eq!(headers.search.as_deref(), Some("?q=1"));
+        assert_eq!(headers.get_header("host"), Some("x"));
+        assert!(!headers.keep_alive);
+        let headers = HttpServerHeaders::from_buf_reader(&mut reader, addr).unwrap();
+        assert_eq!(headers.verb, "POST");
+        assert_eq!(headers.content_length, Some(5));
+        assert!(!headers.keep_alive);
+        assert!(HttpServerHeaders::from_buf_reader(&mut reader, addr).is_none());
+    }
 }
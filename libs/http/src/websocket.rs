@@ -1,6 +1,9 @@
 
 use crate::digest::{Sha1, base64_encode};
 use std::time::{SystemTime, UNIX_EPOCH};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
 
 #[derive(Debug, PartialEq)]
 enum State {
@@ -25,6 +28,14 @@ impl State {
     }
 }
 
+#[derive(Debug, PartialEq)]
+enum FrameKind {
+    Data,
+    Ping,
+    Pong,
+    Close
+}
+
 pub struct ServerWebSocket {
     head: [u8; 8],
     head_expected: usize,
@@ -33,11 +44,16 @@ pub struct ServerWebSocket {
     data_len: usize,
     input_read: usize,
     mask_counter: usize,
-    is_ping: bool,
-    is_pong: bool,
-    is_partial: bool,
-    is_text: bool,
+    frame_kind: FrameKind,
+    is_final: bool,
     is_masked: bool,
+    // the (possibly fragmented) data message being assembled
+    message: Vec<u8>,
+    in_message: bool,
+    message_is_text: bool,
+    message_is_compressed: bool,
+    max_message_size: usize,
+    permessage_deflate: bool,
     state: State
 }
 
@@ -46,21 +62,56 @@ pub enum ServerWebSocketMessage<'a> {
     Pong(&'a [u8]),
     Text(&'a str),
     Binary(&'a [u8]),
-    Close
+    Close {code: Option<u16>, reason: &'a str}
 }
 
 #[derive(Debug)]
 pub enum ServerWebSocketError<'a> {
     OpcodeNotSupported(u8),
     TextNotUTF8(&'a [u8]),
+    MessageTooLarge(usize),
+    UnexpectedContinuation,
+    ExpectedContinuation,
+    ReservedBitsSet,
+    InvalidControlFrame,
+    InflateFailed,
 }
 
 pub const SERVER_WEB_SOCKET_PING_MESSAGE:[u8;2] = [128 | 9,0];
 pub const SERVER_WEB_SOCKET_PONG_MESSAGE:[u8;2] = [128 | 10,0];
 
+pub const WEB_SOCKET_CLOSE_NORMAL: u16 = 1000;
+pub const WEB_SOCKET_CLOSE_GOING_AWAY: u16 = 1001;
+pub const WEB_SOCKET_CLOSE_PROTOCOL_ERROR: u16 = 1002;
+pub const WEB_SOCKET_CLOSE_TOO_LARGE: u16 = 1009;
+
+pub const WEB_SOCKET_DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+// we compress every message as a finished deflate stream, so we can't keep a sliding window across messages in either direction
+pub const WEB_SOCKET_PERMESSAGE_DEFLATE: &str = "permessage-deflate; server_no_context_takeover; client_no_context_takeover";
+// messages smaller than this aren't worth compressing
+const PERMESSAGE_DEFLATE_MIN_SIZE: usize = 64;
+
+const WEB_SOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
 pub enum ServerWebSocketMessageFormat {
     Binary,
-    Text
+    Text,
+    Close,
+    Ping,
+    Pong
+}
+
+// RandomState is seeded from the OS, so hashing a counter with a fresh one gives us unpredictable bytes without a rand dependency
+pub fn web_socket_random_bytes(out: &mut [u8]) {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    for chunk in out.chunks_mut(8) {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+        hasher.write_u32(SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0));
+        let bytes = hasher.finish().to_le_bytes();
+        chunk.copy_from_slice(&bytes[0..chunk.len()]);
+    }
 }
 
 pub struct ServerWebSocketMessageHeader {
@@ -77,6 +128,9 @@ impl ServerWebSocketMessageHeader {
         match format {
             ServerWebSocketMessageFormat::Binary => data[0] = 128 | 2,
             ServerWebSocketMessageFormat::Text => data[0] = 128 | 1,
+            ServerWebSocketMessageFormat::Close => data[0] = 128 | 8,
+            ServerWebSocketMessageFormat::Ping => data[0] = 128 | 9,
+            ServerWebSocketMessageFormat::Pong => data[0] = 128 | 10,
         }
 
         if masked {
@@ -108,9 +162,7 @@ impl ServerWebSocketMessageHeader {
         }
 
         if masked {
-            for i in header_len..header_len + 4 {
-                data[i] = Self::random_byte();
-            }
+            web_socket_random_bytes(&mut data[header_len..header_len + 4]);
             return ServerWebSocketMessageHeader{len: header_len + 4, data, format, masked}
         } else {
             return ServerWebSocketMessageHeader{len: header_len, data, format, masked}
@@ -125,7 +177,7 @@ impl ServerWebSocketMessageHeader {
         if self.masked {
             match self.len {
                 6 => Some(&self.data[2..6]),
-                10 => Some(&self.data[6..10]),
+                8 => Some(&self.data[4..8]),
                 14 => Some(&self.data[10..14]),
                 _ => None
             }
@@ -134,11 +186,6 @@ impl ServerWebSocketMessageHeader {
         }
     }
 
-    // TODO Improve this using a proper random number generator
-    fn random_byte() -> u8 {
-        let num = SystemTime::now().duration_since(UNIX_EPOCH).expect("duration_since failed").subsec_nanos();
-        num as u8
-    }
 }
 
 impl ServerWebSocket {
@@ -151,15 +198,72 @@ impl ServerWebSocket {
             data_len: 0,
             input_read: 0,
             mask_counter: 0,
-            is_ping: false,
-            is_pong: false,
+            frame_kind: FrameKind::Data,
+            is_final: false,
             is_masked: false,
-            is_partial: false,
-            is_text: false,
+            message: Vec::new(),
+            in_message: false,
+            message_is_text: false,
+            message_is_compressed: false,
+            max_message_size: WEB_SOCKET_DEFAULT_MAX_MESSAGE_SIZE,
+            permessage_deflate: false,
             state: State::Opcode
         }
     }
     
+    // upper bound for a reassembled (and decompressed) message
+    pub fn set_max_message_size(&mut self, max_message_size: usize) {
+        self.max_message_size = max_message_size;
+    }
+    
+    // accept messages with the RSV1 (compressed) bit once permessage-deflate has been negotiated
+    pub fn set_permessage_deflate(&mut self, permessage_deflate: bool) {
+        self.permessage_deflate = permessage_deflate;
+    }
+    
+    // returns true if a Sec-WebSocket-Extensions offer contains a permessage-deflate we can serve
+    pub fn accept_permessage_deflate(extensions: &str) -> bool {
+        extensions.split(',').any( | offer | {
+            let mut params = offer.split(';').map( | v | v.trim());
+            if params.next() != Some("permessage-deflate") {
+                return false
+            }
+            // we always compress with a full 32k window
+            params.all( | param | {
+                let (name, value) = param.split_once('=').unwrap_or((param, ""));
+                name.trim() != "server_max_window_bits" || value.trim().trim_matches('"') == "15"
+            })
+        })
+    }
+    
+    // compresses a message as permessage-deflate, a finished stream plus the trailing empty stored block header byte
+    pub fn deflate_message(data: &[u8]) -> Vec<u8> {
+        let mut out = makepad_miniz::compress_to_vec(data, 6);
+        out.push(0);
+        out
+    }
+    
+    pub fn inflate_message(data: &[u8], max_size: usize) -> Option<Vec<u8>> {
+        // restore the stripped sync flush marker and close with an empty final block,
+        // so both sync flushed and finished streams inflate to Done
+        let mut input = Vec::with_capacity(data.len() + 6);
+        input.extend_from_slice(data);
+        input.extend_from_slice(&[0x00, 0x00, 0xff, 0xff, 0x03, 0x00]);
+        makepad_miniz::inflate::decompress_to_vec_with_limit(&input, max_size).ok()
+    }
+    
+    // a single unfragmented data frame, compressed if permessage-deflate is on and it pays off
+    pub fn build_data_frame(data: &[u8], format: ServerWebSocketMessageFormat, masked: bool, permessage_deflate: bool) -> Vec<u8> {
+        if permessage_deflate && data.len() >= PERMESSAGE_DEFLATE_MIN_SIZE {
+            let compressed = Self::deflate_message(data);
+            let mut header = ServerWebSocketMessageHeader::from_len(compressed.len(), format, masked);
+            header.data[0] |= 64;
+            return Self::build_message(header, &compressed)
+        }
+        let header = ServerWebSocketMessageHeader::from_len(data.len(), format, masked);
+        Self::build_message(header, data)
+    }
+    
     pub fn message_to_frame(msg:ServerWebSocketMessage) ->Vec<u8>
     {
         match &msg{
@@ -175,18 +279,124 @@ impl ServerWebSocket {
         }  
     }
 
-    pub fn create_upgrade_response(key: &str) -> String {
-        let to_hash = format!("{}258EAFA5-E914-47DA-95CA-C5AB0DC85B11", key);
+    pub fn create_accept_key(key: &str) -> String {
+        let to_hash = format!("{}{}", key, WEB_SOCKET_GUID);
         let mut sha1 = Sha1::new();
         sha1.update(to_hash.as_bytes());
         let out_bytes = sha1.finalise();
-        let base64 = base64_encode(&out_bytes);
+        base64_encode(&out_bytes)
+    }
+    
+    pub fn create_upgrade_response(key: &str) -> String {
+        Self::create_upgrade_response_with_extensions(key, None)
+    }
+    
+    pub fn create_upgrade_response_with_extensions(key: &str, extensions: Option<&str>) -> String {
+        let extensions = if let Some(extensions) = extensions {
+            format!("Sec-WebSocket-Extensions: {}\r\n", extensions)
+        } else {String::new()};
         let response_ack = format!(
-            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
-            base64
+            "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n{}\r\n",
+            Self::create_accept_key(key),
+            extensions
         );
         response_ack
     }
+    
+    // the Sec-WebSocket-Key a client sends, 16 random bytes base64 encoded
+    pub fn create_client_key() -> String {
+        let mut nonce = [0u8; 16];
+        web_socket_random_bytes(&mut nonce);
+        base64_encode(&nonce)
+    }
+    
+    // checks the servers handshake response head (status line plus headers, without the body)
+    // and returns whether the server accepted permessage-deflate
+    pub fn validate_upgrade_response(response: &str, key: &str) -> Result<bool, String> {
+        let mut lines = response.split("\r\n");
+        let status = lines.next().unwrap_or("");
+        let mut parts = status.split_whitespace();
+        if !parts.next().is_some_and(|v| v.starts_with("HTTP/")) {
+            return Err(format!("Invalid handshake response: {}", status))
+        }
+        if parts.next() != Some("101") {
+            return Err(format!("Server refused websocket upgrade: {}", status))
+        }
+        let mut upgrade = false;
+        let mut connection = false;
+        let mut accept = None;
+        let mut permessage_deflate = false;
+        for line in lines {
+            if let Some((name, value)) = line.split_once(':') {
+                let value = value.trim();
+                if name.trim().eq_ignore_ascii_case("upgrade") {
+                    upgrade = value.eq_ignore_ascii_case("websocket");
+                }
+                else if name.trim().eq_ignore_ascii_case("connection") {
+                    connection = value.split(',').any(|v| v.trim().eq_ignore_ascii_case("upgrade"));
+                }
+                else if name.trim().eq_ignore_ascii_case("sec-websocket-accept") {
+                    accept = Some(value.to_string());
+                }
+                else if name.trim().eq_ignore_ascii_case("sec-websocket-extensions") {
+                    for extension in value.split(',') {
+                        if extension.split(';').next().map(|v| v.trim()) != Some("permessage-deflate") {
+                            return Err(format!("Server responded with an extension we didn't offer: {}", extension.trim()))
+                        }
+                        if permessage_deflate {
+                            return Err("Server responded with permessage-deflate more than once".to_string())
+                        }
+                        Self::validate_permessage_deflate_response(extension) ?;
+                        permessage_deflate = true;
+                    }
+                }
+            }
+        }
+        if !upgrade || !connection {
+            return Err("Handshake response is missing the websocket upgrade headers".to_string())
+        }
+        if accept.as_deref() != Some(&Self::create_accept_key(key)) {
+            return Err("Handshake response has an invalid Sec-WebSocket-Accept".to_string())
+        }
+        Ok(permessage_deflate)
+    }
+    
+    // the parameters a server accepts our WEB_SOCKET_PERMESSAGE_DEFLATE offer with. We inflate every message on its own,
+    // so the server has to agree to server_no_context_takeover, and it can't limit a client window we didn't offer to limit.
+    fn validate_permessage_deflate_response(extension: &str) -> Result<(), String> {
+        let mut server_no_context_takeover = false;
+        let mut seen = Vec::new();
+        for param in extension.split(';').skip(1) {
+            let (name, value) = param.split_once('=').map_or((param.trim(), None), | (name, value) | (name.trim(), Some(value.trim().trim_matches('"'))));
+            if seen.contains(&name) {
+                return Err(format!("Server repeated the permessage-deflate parameter {}", name))
+            }
+            seen.push(name);
+            match (name, value) {
+                ("server_no_context_takeover", None) => server_no_context_takeover = true,
+                ("client_no_context_takeover", None) => (),
+                // we can inflate any window size
+                ("server_max_window_bits", Some(bits)) if bits.parse::<u8>().is_ok_and( | bits | (8..=15).contains(&bits)) => (),
+                _ => return Err(format!("Server responded with a permessage-deflate parameter we didn't offer: {}", param.trim()))
+            }
+        }
+        if !server_no_context_takeover {
+            return Err("Server accepted permessage-deflate without server_no_context_takeover".to_string())
+        }
+        Ok(())
+    }
+    
+    pub fn build_close_frame(code: u16, reason: &str, masked: bool) -> Vec<u8> {
+        let mut payload = code.to_be_bytes().to_vec();
+        // control frame payloads are limited to 125 bytes
+        let mut end = reason.len().min(123);
+        while !reason.is_char_boundary(end) {
+            end -= 1;
+        }
+        payload.extend_from_slice(&reason.as_bytes()[0..end]);
+        let header = ServerWebSocketMessageHeader::from_len(payload.len(), ServerWebSocketMessageFormat::Close, masked);
+        Self::build_message(header, &payload)
+    }
 
     pub fn build_message(mut header: ServerWebSocketMessageHeader, data: &[u8])->Vec<u8>{
         let mut frame = header.as_slice().to_vec();
@@ -220,10 +430,8 @@ impl ServerWebSocket {
                 self.data.clear();
             }
             State::Opcode => {
-                self.is_ping = false;
-                self.is_pong = false;
-                self.is_partial = false;
-                self.is_text = false;
+                self.frame_kind = FrameKind::Data;
+                self.is_final = false;
                 self.is_masked = false;
             },
             _ => ()
@@ -233,6 +441,31 @@ impl ServerWebSocket {
         self.state = state;
     }
     
+    // after a protocol error the stream can't be trusted, so drop any partial message
+    fn reset(&mut self) {
+        self.in_message = false;
+        self.message.clear();
+        self.to_state(State::Opcode);
+    }
+    
+    fn frame_len_parsed(&mut self) -> Result<(), ServerWebSocketError<'static>> {
+        if self.frame_kind != FrameKind::Data {
+            if self.data_len > 125 {
+                return Err(ServerWebSocketError::InvalidControlFrame)
+            }
+        }
+        else if self.message.len() + self.data_len > self.max_message_size {
+            return Err(ServerWebSocketError::MessageTooLarge(self.message.len() + self.data_len))
+        }
+        if self.is_masked {
+            self.to_state(State::Mask);
+        }
+        else {
+            self.to_state(State::Data);
+        }
+        Ok(())
+    }
+    
     pub fn parse<F>(&mut self, input: &[u8], mut result: F) where F: FnMut(Result<ServerWebSocketMessage, ServerWebSocketError>){
         self.input_read = 0;
         // parse a header
@@ -243,25 +476,55 @@ impl ServerWebSocket {
                         break;
                     }
                     let opcode = self.head[0] & 15;
-                    if opcode <= 2 {
-                        self.is_partial = (self.head[0] & 128) != 0;
-                        self.is_text = opcode == 1;
-                        self.to_state(State::Len1);
-                    }
-                    else if opcode == 8 {
-                        result(Ok(ServerWebSocketMessage::Close));
+                    let is_compressed = (self.head[0] & 64) != 0;
+                    if (self.head[0] & 32) != 0 || (self.head[0] & 16) != 0 || (is_compressed && (opcode >= 8 || !self.permessage_deflate)) {
+                        result(Err(ServerWebSocketError::ReservedBitsSet));
+                        self.reset();
                         break;
                     }
-                    else if opcode == 9 {
-                        self.is_ping = true;
+                    self.is_final = (self.head[0] & 128) != 0;
+                    if opcode == 0 {
+                        if !self.in_message {
+                            result(Err(ServerWebSocketError::UnexpectedContinuation));
+                            self.reset();
+                            break;
+                        }
+                        if is_compressed {
+                            result(Err(ServerWebSocketError::ReservedBitsSet));
+                            self.reset();
+                            break;
+                        }
+                        self.to_state(State::Len1);
+                    }
+                    else if opcode <= 2 {
+                        if self.in_message {
+                            result(Err(ServerWebSocketError::ExpectedContinuation));
+                            self.reset();
+                            break;
+                        }
+                        self.in_message = true;
+                        self.message.clear();
+                        self.message_is_text = opcode == 1;
+                        self.message_is_compressed = is_compressed;
                         self.to_state(State::Len1);
                     }
-                    else if opcode == 10 {
-                        self.is_pong = true;
+                    else if (8..=10).contains(&opcode) {
+                        if !self.is_final {
+                            result(Err(ServerWebSocketError::InvalidControlFrame));
+                            self.reset();
+                            break;
+                        }
+                        let frame_kind = match opcode {
+                            8 => FrameKind::Close,
+                            9 => FrameKind::Ping,
+                            _ => FrameKind::Pong
+                        };
                         self.to_state(State::Len1);
+                        self.frame_kind = frame_kind;
                     }
                     else {
                         result(Err(ServerWebSocketError::OpcodeNotSupported(opcode)));
+                        self.reset();
                         break;
                     }
                 },
@@ -273,11 +536,10 @@ impl ServerWebSocket {
                     let len_type = self.head[0] & 127;
                     if len_type < 126 {
                         self.data_len = len_type as usize;
-                        if !self.is_masked {
-                            self.to_state(State::Data);
-                        }
-                        else {
-                            self.to_state(State::Mask);
+                        if let Err(e) = self.frame_len_parsed() {
+                            result(Err(e));
+                            self.reset();
+                            break;
                         }
                     }
                     else if len_type == 126 {
@@ -294,11 +556,10 @@ impl ServerWebSocket {
                     self.data_len = u16::from_be_bytes(
                         self.head[0..2].try_into().unwrap()
                     ) as usize;
-                    if self.is_masked {
-                        self.to_state(State::Mask);
-                    }
-                    else {
-                        self.to_state(State::Data);
+                    if let Err(e) = self.frame_len_parsed() {
+                        result(Err(e));
+                        self.reset();
+                        break;
                     }
                 },
                 State::Len8 => {
@@ -308,11 +569,10 @@ impl ServerWebSocket {
                     self.data_len = u64::from_be_bytes(
                         self.head[0..8].try_into().unwrap()
                     ) as usize;
-                    if self.is_masked {
-                        self.to_state(State::Mask);
-                    }
-                    else {
-                        self.to_state(State::Data);
+                    if let Err(e) = self.frame_len_parsed() {
+                        result(Err(e));
+                        self.reset();
+                        break;
                     }
                 },
                 State::Mask => {
@@ -322,43 +582,65 @@ impl ServerWebSocket {
                     self.to_state(State::Data);
                 },
                 State::Data => {
-                    if self.is_masked {
-                        while self.data.len() < self.data_len && self.input_read < input.len() {
-                            self.data.push(input[self.input_read] ^ self.head[self.mask_counter]);
+                    // data frames collect straight into the message, control frames into data
+                    let target = if self.frame_kind == FrameKind::Data {&mut self.message} else {&mut self.data};
+                    while self.data_len > 0 && self.input_read < input.len() {
+                        let byte = input[self.input_read];
+                        if self.is_masked {
+                            target.push(byte ^ self.head[self.mask_counter]);
                             self.mask_counter = (self.mask_counter + 1) & 3;
-                            self.input_read += 1;
                         }
-                    }
-                    else {
-                        while self.data.len() < self.data_len && self.input_read < input.len() {
-                            self.data.push(input[self.input_read]);
-                            self.input_read += 1;
+                        else {
+                            target.push(byte);
                         }
+                        self.input_read += 1;
+                        self.data_len -= 1;
                     }
-                    if self.data.len() < self.data_len { // not enough data yet
+                    if self.data_len > 0 { // not enough data yet
                         break;
                     }
-                    else {
-                        if self.is_ping {
+                    match self.frame_kind {
+                        FrameKind::Ping => {
                             result(Ok(ServerWebSocketMessage::Ping(&self.data)));
                         }
-                        else if self.is_pong {
+                        FrameKind::Pong => {
                             result(Ok(ServerWebSocketMessage::Pong(&self.data)));
                         }
-                        else if self.is_text{
-                            if let Ok(text) = std::str::from_utf8(&self.data){
-                                result(Ok(ServerWebSocketMessage::Text(text)));
+                        FrameKind::Close => {
+                            let code = if self.data.len() >= 2 {
+                                Some(u16::from_be_bytes([self.data[0], self.data[1]]))
+                            } else {None};
+                            let reason = if self.data.len() > 2 {
+                                std::str::from_utf8(&self.data[2..]).unwrap_or("")
+                            } else {""};
+                            result(Ok(ServerWebSocketMessage::Close {code, reason}));
+                        }
+                        FrameKind::Data => if self.is_final {
+                            self.in_message = false;
+                            if self.message_is_compressed {
+                                if let Some(message) = Self::inflate_message(&self.message, self.max_message_size) {
+                                    self.message = message;
+                                }
+                                else {
+                                    result(Err(ServerWebSocketError::InflateFailed));
+                                    self.reset();
+                                    break;
+                                }
                             }
-                            else{
-                                result(Err(ServerWebSocketError::TextNotUTF8(&self.data)))
+                            if self.message_is_text {
+                                if let Ok(text) = std::str::from_utf8(&self.message) {
+                                    result(Ok(ServerWebSocketMessage::Text(text)));
+                                }
+                                else {
+                                    result(Err(ServerWebSocketError::TextNotUTF8(&self.message)))
+                                }
+                            }
+                            else {
+                                result(Ok(ServerWebSocketMessage::Binary(&self.message)));
                             }
                         }
-                        else{
-                            result(Ok(ServerWebSocketMessage::Binary(&self.data)));
-                        }
-                        
-                        self.to_state(State::Opcode);
                     }
+                    self.to_state(State::Opcode);
                 },
             }
         }
@@ -372,3 +654,193 @@ impl Default for ServerWebSocket {
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upgrade_response(key: &str, extensions: Option<&str>) -> String {
+        let mut response = format!("HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}", ServerWebSocket::create_accept_key(key));
+        if let Some(extensions) = extensions {
+            response.push_str(&format!("\r\nSec-WebSocket-Extensions: {}", extensions));
+        }
+        response
+    }
+
+    #[test]
+    fn validates_upgrade_responses() {
+        let key = "dGhlIHNhbXBsZSBub25jZQ==";
+        assert_eq!(ServerWebSocket::validate_upgrade_response(&upgrade_response(key, None), key), Ok(false));
+        assert!(ServerWebSocket::validate_upgrade_response(&upgrade_response(key, None), "b3RoZXIga2V5IDEyMzQ1Ng==").is_err());
+        assert!(ServerWebSocket::validate_upgrade_response("HTTP/1.1 200 OK\r\nContent-Length: 0", key).is_err());
+        assert!(ServerWebSocket::validate_upgrade_response(&upgrade_response(key, Some("x-webkit-deflate-frame")), key).is_err());
+    }
+
+    #[test]
+    fn checks_permessage_deflate_parameters() {
+        let key = "dGhlIHNhbXBsZSBub25jZQ==";
+        let validate = | extensions | ServerWebSocket::validate_upgrade_response(&upgrade_response(key, Some(extensions)), key);
+        assert_eq!(validate(WEB_SOCKET_PERMESSAGE_DEFLATE), Ok(true));
+        assert_eq!(validate("permessage-deflate;server_no_context_takeover"), Ok(true));
+        assert_eq!(validate("permessage-deflate; server_no_context_takeover; server_max_window_bits=\"10\""), Ok(true));
+        // a window limit for the client we didn't offer, bad values and repeats fail the handshake
+        assert!(validate("permessage-deflate; server_no_context_takeover; client_max_window_bits=10").is_err());
+        assert!(validate("permessage-deflate; server_no_context_takeover; server_max_window_bits=16").is_err());
+        assert!(validate("permessage-deflate; server_no_context_takeover; server_no_context_takeover").is_err());
+        assert!(validate("permessage-deflate; server_no_context_takeover, permessage-deflate").is_err());
+        assert!(validate("permessage-deflate; server_no_context_takeover; unknown_param").is_err());
+        // we can't inflate with a window that carries over between messages
+        assert!(validate("permessage-deflate").is_err());
+    }
+
+    // the parse results, owned so they can be compared
+    #[derive(Clone, Debug, PartialEq)]
+    enum Parsed {
+        Ping(Vec<u8>),
+        Pong(Vec<u8>),
+        Text(String),
+        Binary(Vec<u8>),
+        Close(Option<u16>, String),
+        Error(String),
+    }
+
+    // feeds the input in pieces of `chunk_size` bytes, so every state has to cope with running out of input
+    fn parse(web_socket: &mut ServerWebSocket, input: &[u8], chunk_size: usize) -> Vec<Parsed> {
+        let mut out = Vec::new();
+        for chunk in input.chunks(chunk_size) {
+            web_socket.parse(chunk, | result | out.push(match result {
+                Ok(ServerWebSocketMessage::Ping(data)) => Parsed::Ping(data.to_vec()),
+                Ok(ServerWebSocketMessage::Pong(data)) => Parsed::Pong(data.to_vec()),
+                Ok(ServerWebSocketMessage::Text(text)) => Parsed::Text(text.to_string()),
+                Ok(ServerWebSocketMessage::Binary(data)) => Parsed::Binary(data.to_vec()),
+                Ok(ServerWebSocketMessage::Close {code, reason}) => Parsed::Close(code, reason.to_string()),
+                Err(e) => Parsed::Error(format!("{:?}", e)),
+            }));
+        }
+        out
+    }
+
+    // an unmasked frame with a short payload
+    fn frame(first_byte: u8, payload: &[u8]) -> Vec<u8> {
+        assert!(payload.len() < 126);
+        let mut frame = vec![first_byte, payload.len() as u8];
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    fn masked_frame(first_byte: u8, payload: &[u8], mask: [u8; 4]) -> Vec<u8> {
+        assert!(payload.len() < 126);
+        let mut frame = vec![first_byte, 128 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map( | (i, byte) | byte ^ mask[i % 4]));
+        frame
+    }
+
+    #[test]
+    fn reassembles_fragmented_messages() {
+        let mut input = frame(0x01, b"Hel");
+        input.extend(frame(0x00, b"lo, "));
+        input.extend(frame(0x80, b"world"));
+        input.extend(masked_frame(0x02, &[1, 2], [0x11, 0x22, 0x33, 0x44]));
+        input.extend(masked_frame(0x80, &[3, 4, 5], [0x55, 0x66, 0x77, 0x88]));
+        for chunk_size in [1, 3, input.len()] {
+            let mut web_socket = ServerWebSocket::new();
+            assert_eq!(parse(&mut web_socket, &input, chunk_size), [
+                Parsed::Text("Hello, world".to_string()),
+                Parsed::Binary(vec![1, 2, 3, 4, 5]),
+            ]);
+        }
+    }
+
+    #[test]
+    fn delivers_control_frames_between_fragments() {
+        let mut input = frame(0x02, &[1, 2]);
+        input.extend(frame(0x89, b"ping"));
+        input.extend(frame(0x00, &[3]));
+        input.extend(frame(0x8a, b""));
+        input.extend(frame(0x80, &[4]));
+        input.extend(frame(0x88, &[0x03, 0xe8, b'b', b'y', b'e']));
+        for chunk_size in [1, input.len()] {
+            let mut web_socket = ServerWebSocket::new();
+            assert_eq!(parse(&mut web_socket, &input, chunk_size), [
+                Parsed::Ping(b"ping".to_vec()),
+                Parsed::Pong(Vec::new()),
+                Parsed::Binary(vec![1, 2, 3, 4]),
+                Parsed::Close(Some(WEB_SOCKET_CLOSE_NORMAL), "bye".to_string()),
+            ]);
+        }
+    }
+
+    #[test]
+    fn rejects_broken_fragmentation() {
+        // a continuation without a message, a new message before the last one finished, a fragmented control frame
+        let mut web_socket = ServerWebSocket::new();
+        assert_eq!(parse(&mut web_socket, &frame(0x80, b"x"), 16), [Parsed::Error("UnexpectedContinuation".to_string())]);
+        let mut input = frame(0x01, b"a");
+        input.extend(frame(0x81, b"b"));
+        assert_eq!(parse(&mut web_socket, &input, 16), [Parsed::Error("ExpectedContinuation".to_string())]);
+        let mut web_socket = ServerWebSocket::new();
+        assert_eq!(parse(&mut web_socket, &frame(0x09, b""), 16), [Parsed::Error("InvalidControlFrame".to_string())]);
+        // fragments add up against the message size limit
+        let mut web_socket = ServerWebSocket::new();
+        web_socket.set_max_message_size(4);
+        let mut input = frame(0x02, &[1, 2, 3]);
+        input.extend(frame(0x80, &[4, 5]));
+        assert_eq!(parse(&mut web_socket, &input, 16), [Parsed::Error("MessageTooLarge(5)".to_string())]);
+    }
+
+    #[test]
+    fn inflates_the_rfc_7692_examples() {
+        // section 7.2.3.1, a single compressed frame and the same message fragmented
+        let hello = [0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00];
+        let mut input = frame(0xc1, &hello);
+        input.extend(frame(0x41, &hello[0..3]));
+        input.extend(frame(0x80, &hello[3..]));
+        // section 7.2.3.3, an uncompressed stored block, and 7.2.3.4, a finished stream
+        input.extend(frame(0xc1, &[0x00, 0x05, 0x00, 0xfa, 0xff, 0x48, 0x65, 0x6c, 0x6c, 0x6f, 0x00]));
+        input.extend(frame(0xc1, &[0xf3, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00, 0x00]));
+        for chunk_size in [1, input.len()] {
+            let mut web_socket = ServerWebSocket::new();
+            web_socket.set_permessage_deflate(true);
+            assert_eq!(parse(&mut web_socket, &input, chunk_size), vec![Parsed::Text("Hello".to_string()); 4]);
+        }
+        // the same frame is an error until permessage-deflate is negotiated
+        let mut web_socket = ServerWebSocket::new();
+        assert_eq!(parse(&mut web_socket, &frame(0xc1, &hello), 16), [Parsed::Error("ReservedBitsSet".to_string())]);
+        // only the first frame of a message carries RSV1
+        let mut web_socket = ServerWebSocket::new();
+        web_socket.set_permessage_deflate(true);
+        let mut input = frame(0x41, &hello[0..3]);
+        input.extend(frame(0xc0, &hello[3..]));
+        assert_eq!(parse(&mut web_socket, &input, 16), [Parsed::Error("ReservedBitsSet".to_string())]);
+    }
+
+    #[test]
+    fn inflates_streams_with_the_sync_flush_tail_removed() {
+        // "Hello" sync flushed ends in an empty stored block, 00 00 ff ff, which the sender strips
+        let flushed = [0xf2, 0x48, 0xcd, 0xc9, 0xc9, 0x07, 0x00, 0x00, 0x00, 0xff, 0xff];
+        assert_eq!(ServerWebSocket::inflate_message(&flushed[0..flushed.len() - 4], 1024).as_deref(), Some(&b"Hello"[..]));
+        assert_eq!(ServerWebSocket::inflate_message(&[0x00], 1024).as_deref(), Some(&b""[..]));
+        assert_eq!(ServerWebSocket::inflate_message(&[0xff, 0xff, 0xff], 1024), None);
+        // and the limit applies to the inflated size
+        let data = vec![b'a'; 4096];
+        assert_eq!(ServerWebSocket::inflate_message(&ServerWebSocket::deflate_message(&data), 1024), None);
+    }
+
+    #[test]
+    fn deflate_round_trips() {
+        let text = "permessage-deflate ".repeat(100);
+        let compressed = ServerWebSocket::deflate_message(text.as_bytes());
+        assert!(compressed.len() < text.len() / 4);
+        assert!(!compressed.ends_with(&[0x00, 0x00, 0xff, 0xff]));
+        assert_eq!(ServerWebSocket::inflate_message(&compressed, text.len()).as_deref(), Some(text.as_bytes()));
+
+        // a masked client frame through the parser, small messages go out uncompressed
+        for (data, is_compressed) in [(text.as_bytes(), true), (&b"short"[..], false)] {
+            let frame = ServerWebSocket::build_data_frame(data, ServerWebSocketMessageFormat::Text, true, true);
+            assert_eq!(frame[0] & 64 != 0, is_compressed);
+            let mut web_socket = ServerWebSocket::new();
+            web_socket.set_permessage_deflate(true);
+            assert_eq!(parse(&mut web_socket, &frame, 7), [Parsed::Text(String::from_utf8(data.to_vec()).unwrap())]);
+        }
+    }
+}
@@ -913,8 +913,9 @@ pub fn test(src: &[u8]) -> Option<(usize, usize)> {
         let length = from_be16(&src[sp + 2..sp + 4]) as usize;
         match marker {
             0xFFC0 | 0xFFC1 | 0xFFC2 => {
-                let width = from_be16(&src[sp + 5..sp + 7]) as usize;
-                let height = from_be16(&src[sp + 7..sp + 9]) as usize;
+                // the frame header stores the height first
+                let height = from_be16(&src[sp + 5..sp + 7]) as usize;
+                let width = from_be16(&src[sp + 7..sp + 9]) as usize;
                 let components = src[sp + 9];
                 if (components == 1) || (components == 3) { // does not support RGBA or CMYK JPEGs
                     return Some((width, height));
@@ -1235,6 +1236,364 @@ pub fn decode(src: &[u8]) -> Result<ImageBuffer, String> {
     Err("Invalid JPEG 11".to_string())
 }
 
-pub fn encode(_image: &ImageBuffer) -> Result<Vec<u8>, String> {
-    Err("not implemented yet".to_string())
+// Baseline encoder
+
+// natural order index of each zigzag position
+const ZIGZAG: [u8; 64] = [0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20, 13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59, 52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63];
+
+// the example quantization tables from Annex K, in natural order
+const QUANT_LUMINANCE: [u8; 64] = [16, 11, 10, 16, 24, 40, 51, 61, 12, 12, 14, 19, 26, 58, 60, 55, 14, 13, 16, 24, 40, 57, 69, 56, 14, 17, 22, 29, 51, 87, 80, 62, 18, 22, 37, 56, 68, 109, 103, 77, 24, 35, 55, 64, 81, 104, 113, 92, 49, 64, 78, 87, 103, 121, 120, 101, 72, 92, 95, 98, 112, 100, 103, 99];
+const QUANT_CHROMINANCE: [u8; 64] = [17, 18, 24, 47, 99, 99, 99, 99, 18, 21, 26, 66, 99, 99, 99, 99, 24, 26, 56, 99, 99, 99, 99, 99, 47, 66, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99, 99];
+
+// the typical Huffman tables from Annex K, as code counts per length and symbols
+const DC_LUMINANCE_BITS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const DC_LUMINANCE_VALUES: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+const DC_CHROMINANCE_BITS: [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const DC_CHROMINANCE_VALUES: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+const AC_LUMINANCE_BITS: [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7D];
+const AC_LUMINANCE_VALUES: [u8; 162] = [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xA1, 0x08, 0x23, 0x42, 0xB1, 0xC1, 0x15, 0x52, 0xD1, 0xF0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0A, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2A, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5, 0xA6, 0xA7,
+    0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3, 0xC4, 0xC5,
+    0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA, 0xE1, 0xE2,
+    0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF1, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+    0xF9, 0xFA
+];
+const AC_CHROMINANCE_BITS: [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+const AC_CHROMINANCE_VALUES: [u8; 162] = [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xA1, 0xB1, 0xC1, 0x09, 0x23, 0x33, 0x52, 0xF0,
+    0x15, 0x62, 0x72, 0xD1, 0x0A, 0x16, 0x24, 0x34, 0xE1, 0x25, 0xF1, 0x17, 0x18, 0x19, 0x1A, 0x26,
+    0x27, 0x28, 0x29, 0x2A, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5,
+    0xA6, 0xA7, 0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3,
+    0xC4, 0xC5, 0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA,
+    0xE2, 0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+    0xF9, 0xFA
+];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum JpegSubsampling {
+    // chroma at half resolution in both directions
+    Yuv420,
+    // chroma at full resolution
+    Yuv444,
+}
+
+// (code, length) per symbol
+struct HuffmanCodes {
+    codes: [(u16, u8); 256],
+}
+
+impl HuffmanCodes {
+    fn new(bits: &[u8; 16], values: &[u8]) -> HuffmanCodes {
+        let mut codes = [(0u16, 0u8); 256];
+        let mut code = 0u16;
+        let mut k = 0;
+        for (i, count) in bits.iter().enumerate() {
+            for _ in 0..*count {
+                codes[values[k] as usize] = (code, (i + 1) as u8);
+                code += 1;
+                k += 1;
+            }
+            code <<= 1;
+        }
+        HuffmanCodes {codes}
+    }
+}
+
+struct BitWriter {
+    data: Vec<u8>,
+    buffer: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, code: u16, length: u8) {
+        self.buffer = (self.buffer << length) | (code as u32 & ((1 << length) - 1));
+        self.count += length as u32;
+        while self.count >= 8 {
+            let byte = (self.buffer >> (self.count - 8)) as u8;
+            self.data.push(byte);
+            if byte == 0xFF { // byte stuffing
+                self.data.push(0);
+            }
+            self.count -= 8;
+        }
+    }
+    
+    fn flush(&mut self) {
+        // pad with ones
+        if self.count > 0 {
+            self.write(0x7F, (8 - self.count) as u8);
+        }
+    }
+}
+
+fn scale_quant_table(table: &[u8; 64], quality: u8) -> [u8; 64] {
+    let quality = quality.clamp(1, 100) as u32;
+    let scale = if quality < 50 {5000 / quality} else {200 - quality * 2};
+    let mut result = [0u8; 64];
+    for i in 0..64 {
+        result[i] = ((table[i] as u32 * scale + 50) / 100).clamp(1, 255) as u8;
+    }
+    result
+}
+
+fn forward_dct(block: &[f32; 64], cos_table: &[[f32; 8]; 8]) -> [f32; 64] {
+    let mut rows = [0f32; 64];
+    for y in 0..8 {
+        for u in 0..8 {
+            let mut sum = 0.0;
+            for x in 0..8 {
+                sum += block[y * 8 + x] * cos_table[u][x];
+            }
+            rows[y * 8 + u] = sum;
+        }
+    }
+    let mut result = [0f32; 64];
+    for u in 0..8 {
+        for v in 0..8 {
+            let mut sum = 0.0;
+            for y in 0..8 {
+                sum += rows[y * 8 + u] * cos_table[v][y];
+            }
+            result[v * 8 + u] = sum;
+        }
+    }
+    result
+}
+
+fn magnitude_category(value: i32) -> u8 {
+    (32 - value.unsigned_abs().leading_zeros()) as u8
+}
+
+fn encode_block(writer: &mut BitWriter, block: &[f32; 64], cos_table: &[[f32; 8]; 8], qtable: &[u8; 64], dc: &mut i32, dcht: &HuffmanCodes, acht: &HuffmanCodes) {
+    let coeffs = forward_dct(block, cos_table);
+    let mut quantized = [0i32; 64];
+    for i in 0..64 {
+        let n = ZIGZAG[i] as usize;
+        quantized[i] = (coeffs[n] / qtable[n] as f32).round() as i32;
+    }
+    
+    // DC as the difference to the previous block of this component
+    let diff = quantized[0] - *dc;
+    *dc = quantized[0];
+    let cat = magnitude_category(diff);
+    let (code, length) = dcht.codes[cat as usize];
+    writer.write(code, length);
+    if cat > 0 {
+        writer.write((if diff < 0 {diff - 1} else {diff}) as u16, cat);
+    }
+    
+    // AC as runs of zeros followed by a value
+    let mut run = 0;
+    for value in &quantized[1..] {
+        if *value == 0 {
+            run += 1;
+            continue;
+        }
+        while run >= 16 {
+            let (code, length) = acht.codes[0xF0];
+            writer.write(code, length);
+            run -= 16;
+        }
+        let cat = magnitude_category(*value);
+        let (code, length) = acht.codes[(run << 4) | cat as usize];
+        writer.write(code, length);
+        writer.write((if *value < 0 {*value - 1} else {*value}) as u16, cat);
+        run = 0;
+    }
+    if run > 0 { // end of block
+        let (code, length) = acht.codes[0x00];
+        writer.write(code, length);
+    }
+}
+
+fn push_marker(dst: &mut Vec<u8>, marker: u8, data: &[u8]) {
+    dst.push(0xFF);
+    dst.push(marker);
+    dst.extend_from_slice(&((data.len() + 2) as u16).to_be_bytes());
+    dst.extend_from_slice(data);
+}
+
+fn push_huffman_table(data: &mut Vec<u8>, class_id: u8, bits: &[u8; 16], values: &[u8]) {
+    data.push(class_id);
+    data.extend_from_slice(bits);
+    data.extend_from_slice(values);
+}
+
+pub fn encode(image: &ImageBuffer) -> Result<Vec<u8>, String> {
+    encode_with_quality(image, 90, JpegSubsampling::Yuv420)
+}
+
+// encodes a baseline JPEG, quality goes from 1 to 100, alpha is dropped
+pub fn encode_with_quality(image: &ImageBuffer, quality: u8, subsampling: JpegSubsampling) -> Result<Vec<u8>, String> {
+    let width = image.width;
+    let height = image.height;
+    if (width == 0) || (height == 0) || (width >= 65536) || (height >= 65536) {
+        return Err("invalid image size".to_string());
+    }
+    if image.data.len() < width * height {
+        return Err("image data too small".to_string());
+    }
+    let qtables = [scale_quant_table(&QUANT_LUMINANCE, quality), scale_quant_table(&QUANT_CHROMINANCE, quality)];
+    let luminance = (HuffmanCodes::new(&DC_LUMINANCE_BITS, &DC_LUMINANCE_VALUES), HuffmanCodes::new(&AC_LUMINANCE_BITS, &AC_LUMINANCE_VALUES));
+    let chrominance = (HuffmanCodes::new(&DC_CHROMINANCE_BITS, &DC_CHROMINANCE_VALUES), HuffmanCodes::new(&AC_CHROMINANCE_BITS, &AC_CHROMINANCE_VALUES));
+    
+    // DCT basis including the normalization
+    let mut cos_table = [[0f32; 8]; 8];
+    for (u, row) in cos_table.iter_mut().enumerate() {
+        let c = if u == 0 {std::f32::consts::FRAC_1_SQRT_2} else {1.0};
+        for (x, value) in row.iter_mut().enumerate() {
+            *value = 0.5 * c * (((2 * x + 1) as f32 * u as f32 * std::f32::consts::PI) / 16.0).cos();
+        }
+    }
+    
+    let mut dst: Vec<u8> = Vec::new();
+    dst.push(0xFF);
+    dst.push(0xD8); // SOI
+    push_marker(&mut dst, 0xE0, &[b'J', b'F', b'I', b'F', 0, 1, 1, 0, 0, 1, 0, 1, 0, 0]); // APP0
+    let mut dqt = Vec::new();
+    for (i, qtable) in qtables.iter().enumerate() {
+        dqt.push(i as u8);
+        for n in ZIGZAG {
+            dqt.push(qtable[n as usize]);
+        }
+    }
+    push_marker(&mut dst, 0xDB, &dqt);
+    let luma_sampling = if subsampling == JpegSubsampling::Yuv420 {0x22} else {0x11};
+    let mut sof = vec![8];
+    sof.extend_from_slice(&(height as u16).to_be_bytes());
+    sof.extend_from_slice(&(width as u16).to_be_bytes());
+    sof.extend_from_slice(&[3, 1, luma_sampling, 0, 2, 0x11, 1, 3, 0x11, 1]);
+    push_marker(&mut dst, 0xC0, &sof); // SOF0
+    let mut dht = Vec::new();
+    push_huffman_table(&mut dht, 0x00, &DC_LUMINANCE_BITS, &DC_LUMINANCE_VALUES);
+    push_huffman_table(&mut dht, 0x10, &AC_LUMINANCE_BITS, &AC_LUMINANCE_VALUES);
+    push_huffman_table(&mut dht, 0x01, &DC_CHROMINANCE_BITS, &DC_CHROMINANCE_VALUES);
+    push_huffman_table(&mut dht, 0x11, &AC_CHROMINANCE_BITS, &AC_CHROMINANCE_VALUES);
+    push_marker(&mut dst, 0xC4, &dht);
+    push_marker(&mut dst, 0xDA, &[3, 1, 0x00, 2, 0x11, 3, 0x11, 0, 63, 0]); // SOS
+    
+    // converts the pixel at (x, y), clamped to the image, into level shifted YCbCr
+    let ycbcr = | x: usize, y: usize | -> (f32, f32, f32) {
+        let pixel = image.data[y.min(height - 1) * width + x.min(width - 1)];
+        let r = ((pixel >> 16) & 255) as f32;
+        let g = ((pixel >> 8) & 255) as f32;
+        let b = (pixel & 255) as f32;
+        (
+            0.299 * r + 0.587 * g + 0.114 * b - 128.0,
+            -0.168736 * r - 0.331264 * g + 0.5 * b,
+            0.5 * r - 0.418688 * g - 0.081312 * b
+        )
+    };
+    
+    let mut writer = BitWriter {data: dst, buffer: 0, count: 0};
+    let mut dc = [0i32; 3];
+    let mcu_size = if subsampling == JpegSubsampling::Yuv420 {16} else {8};
+    let mut y_block = [0f32; 64];
+    let mut cb_block = [0f32; 64];
+    let mut cr_block = [0f32; 64];
+    for mcu_y in (0..height).step_by(mcu_size) {
+        for mcu_x in (0..width).step_by(mcu_size) {
+            if mcu_size == 16 {
+                for (by, bx) in [(0, 0), (0, 8), (8, 0), (8, 8)] {
+                    for (i, value) in y_block.iter_mut().enumerate() {
+                        *value = ycbcr(mcu_x + bx + (i & 7), mcu_y + by + (i >> 3)).0;
+                    }
+                    encode_block(&mut writer, &y_block, &cos_table, &qtables[0], &mut dc[0], &luminance.0, &luminance.1);
+                }
+                // average each 2x2 for the chroma
+                for i in 0..64 {
+                    let x = mcu_x + (i & 7) * 2;
+                    let y = mcu_y + (i >> 3) * 2;
+                    let (mut cb, mut cr) = (0.0, 0.0);
+                    for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let c = ycbcr(x + dx, y + dy);
+                        cb += c.1;
+                        cr += c.2;
+                    }
+                    cb_block[i] = cb * 0.25;
+                    cr_block[i] = cr * 0.25;
+                }
+            }
+            else {
+                for i in 0..64 {
+                    let c = ycbcr(mcu_x + (i & 7), mcu_y + (i >> 3));
+                    y_block[i] = c.0;
+                    cb_block[i] = c.1;
+                    cr_block[i] = c.2;
+                }
+                encode_block(&mut writer, &y_block, &cos_table, &qtables[0], &mut dc[0], &luminance.0, &luminance.1);
+            }
+            encode_block(&mut writer, &cb_block, &cos_table, &qtables[1], &mut dc[1], &chrominance.0, &chrominance.1);
+            encode_block(&mut writer, &cr_block, &cos_table, &qtables[1], &mut dc[2], &chrominance.0, &chrominance.1);
+        }
+    }
+    writer.flush();
+    let mut dst = writer.data;
+    dst.push(0xFF);
+    dst.push(0xD9); // EOI
+    Ok(dst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // the peak signal to noise ratio over the color channels, in dB
+    fn psnr(a: &ImageBuffer, b: &ImageBuffer) -> f64 {
+        let mut error = 0.0;
+        for (a, b) in a.data.iter().zip(b.data.iter()) {
+            for shift in [0, 8, 16] {
+                let d = ((a >> shift) & 255) as f64 - ((b >> shift) & 255) as f64;
+                error += d * d;
+            }
+        }
+        let mse = error / (a.data.len() * 3) as f64;
+        10.0 * (255.0 * 255.0 / mse).log10()
+    }
+    
+    // smooth color gradients with a hard edge, in a size that doesn't fill the last blocks
+    fn test_image() -> ImageBuffer {
+        let mut image = ImageBuffer::new(53, 37);
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let r = x * 255 / 52;
+                let g = y * 255 / 36;
+                let b = if x > 20 && x < 40 && y > 10 && y < 30 {220} else {40};
+                image.data[y * image.width + x] = 0x80000000 | ((r as u32) << 16) | ((g as u32) << 8) | b;
+            }
+        }
+        image
+    }
+    
+    // 4:2:0 loses most at the edge of the box, where the chroma changes within a pixel pair
+    #[test]
+    fn round_trips_within_psnr() {
+        let image = test_image();
+        for (quality, subsampling, min_psnr) in [
+            (90, JpegSubsampling::Yuv444, 36.0),
+            (90, JpegSubsampling::Yuv420, 26.0),
+            (50, JpegSubsampling::Yuv444, 29.0),
+            (50, JpegSubsampling::Yuv420, 23.0),
+        ] {
+            let jpeg = encode_with_quality(&image, quality, subsampling).unwrap();
+            assert_eq!(test(&jpeg), Some((image.width, image.height)));
+            let decoded = decode(&jpeg).unwrap();
+            assert_eq!((decoded.width, decoded.height), (image.width, image.height));
+            // alpha is dropped
+            assert!(decoded.data.iter().all( | pixel | pixel >> 24 == 255));
+            let psnr = psnr(&image, &decoded);
+            assert!(psnr >= min_psnr, "quality {} {:?}: {:.1} dB", quality, subsampling, psnr);
+        }
+    }
 }
@@ -14,23 +14,39 @@ pub enum MarkdownListLabel{
     Star,
     Number{digit:usize, start:usize, end:usize},
 }
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MarkdownTableAlign{
+    None,
+    Left,
+    Center,
+    Right,
+}
 #[derive(Debug, PartialEq)]
 pub enum MarkdownNode{
     BeginHead{level:usize},
     EndHead,
-    BeginListItem{label:MarkdownListLabel},
+    // checked is set for task list items like '- [x] done'
+    BeginListItem{label:MarkdownListLabel, checked:Option<bool>},
     EndListItem,
     BeginNormal,
     EndNormal,
+    // the text is decoded[start..url_start], the url decoded[url_start..end]
     Link{start:usize, url_start:usize, end:usize},
     Image{start:usize, url_start:usize, end:usize},
     BeginQuote,
     EndQuote,
     Separator, 
-    BeginUnderline,
-    EndUnderline,
-    BeginCode,
+    BeginStrikethrough,
+    EndStrikethrough,
+    // lang is the range of the fence info word in decoded, like 'rust' in ```rust
+    BeginCode{lang:Option<(usize,usize)>},
     EndCode,
+    BeginTable{columns:usize},
+    EndTable,
+    BeginTableRow,
+    EndTableRow,
+    BeginTableCell{align:MarkdownTableAlign, header:bool},
+    EndTableCell,
     BeginInlineCode,
     NewLine,
     EndInlineCode,
@@ -84,6 +100,144 @@ impl <'a> Cursor<'a>{
     }
 }
 
+// returns the rest of the line and a cursor past its newline
+fn scan_line<'a>(cursor:&Cursor<'a>)->(String, Cursor<'a>){
+    let mut scan = cursor.clone();
+    let mut line = String::new();
+    while scan.chars[0] != '\n' && !scan.at_end(){
+        line.push(scan.chars[0]);
+        scan.next();
+    }
+    scan.next();
+    (line, scan)
+}
+
+// splits a table row on the unescaped |'s, ignoring the optional outer ones
+fn split_table_row(line:&str)->Vec<String>{
+    let line = line.trim();
+    let line = line.strip_prefix('|').unwrap_or(line);
+    let line = if line.ends_with('|') && !line.ends_with("\\|"){&line[..line.len()-1]} else {line};
+    let mut cells = vec![String::new()];
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next(){
+        if c == '\\' && chars.peek() == Some(&'|'){
+            cells.last_mut().unwrap().push('|');
+            chars.next();
+        }
+        else if c == '|'{
+            cells.push(String::new());
+        }
+        else{
+            cells.last_mut().unwrap().push(c);
+        }
+    }
+    cells
+}
+
+// a table starts with a header row followed by a delimiter row like |:---|---:|
+fn scan_table_header(cursor:&Cursor)->Option<Vec<MarkdownTableAlign>>{
+    let (header, next) = scan_line(cursor);
+    let (delimiter, _) = scan_line(&next);
+    if !header.contains('|') || !delimiter.contains('|'){
+        return None
+    }
+    let mut aligns = Vec::new();
+    for cell in split_table_row(&delimiter){
+        let cell = cell.trim();
+        let left = cell.starts_with(':');
+        let right = cell.ends_with(':') && cell.len() > 1;
+        let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+        if dashes.is_empty() || dashes.chars().any( | c | c != '-'){
+            return None
+        }
+        aligns.push(match (left, right){
+            (true, true)=>MarkdownTableAlign::Center,
+            (true, false)=>MarkdownTableAlign::Left,
+            (false, true)=>MarkdownTableAlign::Right,
+            (false, false)=>MarkdownTableAlign::None,
+        });
+    }
+    if split_table_row(&header).len() != aligns.len(){
+        return None
+    }
+    Some(aligns)
+}
+
+// consumes a task list checkbox like '[ ] ' or '[x] ' at the start of a list item
+fn scan_task_checkbox(cursor:&mut Cursor)->Option<bool>{
+    let checked = match cursor.chars{
+        ['[',' ',']']=>false,
+        ['[','x',']'] | ['[','X',']']=>true,
+        _=>return None
+    };
+    let mut scan = cursor.clone();
+    scan.skip(3);
+    if scan.chars[0] == ' '{
+        scan.next();
+    }
+    else if scan.chars[0] != '\n' && !scan.at_end(){
+        return None
+    }
+    *cursor = scan;
+    Some(checked)
+}
+
+// scans an autolink like <https://makepad.dev>, or a bare www. or http(s):// url at the start of
+// a word, returns the text, the url and a cursor past it
+fn scan_autolink<'a>(cursor:&Cursor<'a>)->Option<(String, String, Cursor<'a>)>{
+    let mut scan = cursor.clone();
+    if cursor.chars[0] == '<'{
+        scan.next();
+        let mut text = String::new();
+        while scan.chars[0] != '>'{
+            if scan.at_end() || scan.chars[0].is_whitespace() || scan.chars[0] == '<'{
+                return None
+            }
+            text.push(scan.chars[0]);
+            scan.next();
+        }
+        scan.next();
+        // it needs a scheme of at least 2 characters, like https: or mailto:
+        let (scheme, _) = text.split_once(':') ?;
+        if scheme.len() < 2 || !scheme.starts_with( | c:char | c.is_ascii_alphabetic()) ||
+            !scheme.chars().all( | c | c.is_ascii_alphanumeric() || c == '+' || c == '.' || c == '-'){
+            return None
+        }
+        return Some((text.clone(), text, scan))
+    }
+    if !matches!(cursor.last_char, ' ' | '\n' | '\0' | '*' | '_' | '~' | '('){
+        return None
+    }
+    let mut chars = Vec::new();
+    while !scan.at_end() && !scan.chars[0].is_whitespace() && scan.chars[0] != '<' && scan.chars[0] != '|'{
+        chars.push(scan.chars[0]);
+        scan.next();
+    }
+    // trailing punctuation and unbalanced closing parentheses aren't part of the url
+    while let Some(&c) = chars.last(){
+        if matches!(c, '?' | '!' | '.' | ',' | ':' | '*' | '_' | '~') ||
+            (c == ')' && chars.iter().filter( | c | **c == ')').count() > chars.iter().filter( | c | **c == '(').count()){
+            chars.pop();
+        }
+        else{
+            break
+        }
+    }
+    let text:String = chars.iter().collect();
+    let url = if text.starts_with("www.") && text.len() > 4{
+        format!("http://{}", text)
+    }
+    else if text.strip_prefix("http://").or_else( || text.strip_prefix("https://")).is_some_and( | v | !v.is_empty()){
+        text.clone()
+    }
+    else{
+        return None
+    };
+    let mut scan = cursor.clone();
+    scan.skip(chars.len());
+    Some((text, url, scan))
+}
+
 pub fn parse_markdown(body:&str)->MarkdownDoc{
     let mut nodes = Vec::new();
     let mut decoded = String::new();
@@ -93,16 +247,18 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
     let mut cursor = Cursor::new(body);
     enum State{
         Root{spaces:usize},
-        Inline{kind:Kind, bold:usize, italic:usize, underline:usize}, // terminates
+        Inline{kind:Kind, bold:usize, italic:usize, strikethrough:usize}, // terminates
     }
     enum Kind{
         Normal,
         Head,
         Quote(usize),
-        List(usize)
+        List(usize),
+        TableCell{column:usize, header:bool}
     }
     
     let mut state = State::Root{spaces:0};
+    let mut table_aligns = Vec::new();
     
     fn push_char(nodes: &mut Vec<MarkdownNode>, decoded:&mut String, c:char){
         // ok so lets check our last node
@@ -145,7 +301,7 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
             cursor.next();
         }
         if !already_in_code{
-            nodes.push(MarkdownNode::BeginCode);
+            nodes.push(MarkdownNode::BeginCode{lang:None});
         }
         else{
             nodes.push(MarkdownNode::NewLine);
@@ -154,26 +310,80 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
         nodes.push(MarkdownNode::EndCode);
     }
     
+    fn end_styles(nodes: &mut Vec<MarkdownNode>, bold:&mut usize, italic:&mut usize, strikethrough:&mut usize){
+        for _ in 0..*bold{
+            nodes.push(MarkdownNode::EndBold);
+        }
+        for _ in 0..*italic{
+            nodes.push(MarkdownNode::EndItalic);
+        }
+        for _ in 0..*strikethrough{
+            nodes.push(MarkdownNode::EndStrikethrough);
+        }
+        *bold = 0;
+        *italic = 0;
+        *strikethrough = 0;
+    }
+    
+    fn begin_table_cell(nodes: &mut Vec<MarkdownNode>, cursor:&mut Cursor, aligns:&[MarkdownTableAlign], column:usize, header:bool){
+        while cursor.chars[0] == ' '{
+            cursor.next();
+        }
+        nodes.push(MarkdownNode::BeginTableCell{align:aligns[column], header});
+    }
+    
+    fn end_table_cell(nodes: &mut Vec<MarkdownNode>, decoded:&mut String){
+        // drop the whitespace before the |
+        if let Some(MarkdownNode::Text{start, end}) = nodes.last_mut(){
+            if *end == decoded.len() && decoded.ends_with(' '){
+                decoded.pop();
+                *end -= 1;
+                if *start == *end{
+                    nodes.pop();
+                }
+            }
+        }
+        nodes.push(MarkdownNode::EndTableCell);
+    }
+    
+    // pads and closes the row, returns true if the next line continues the table
+    fn end_table_row(nodes: &mut Vec<MarkdownNode>, cursor:&mut Cursor, aligns:&[MarkdownTableAlign], column:usize, header:bool)->bool{
+        for align in aligns.iter().skip(column + 1){
+            nodes.push(MarkdownNode::BeginTableCell{align:*align, header});
+            nodes.push(MarkdownNode::EndTableCell);
+        }
+        nodes.push(MarkdownNode::EndTableRow);
+        *cursor = scan_line(cursor).1;
+        if header{ // skip the delimiter row
+            *cursor = scan_line(cursor).1;
+        }
+        let mut scan = cursor.clone();
+        let mut spaces = 0;
+        while scan.chars[0] == ' '{
+            scan.next();
+            spaces += 1;
+        }
+        if spaces < 4 && scan_line(&scan).0.contains('|'){
+            if scan.chars[0] == '|'{
+                scan.next();
+            }
+            *cursor = scan;
+            nodes.push(MarkdownNode::BeginTableRow);
+            return true
+        }
+        nodes.push(MarkdownNode::EndTable);
+        false
+    }
+    
     loop{
         match &mut state{
-            State::Inline{kind, bold, italic, underline}=> match cursor.chars{
-                [' ',' ','\n']=>{
+            State::Inline{kind, bold, italic, strikethrough}=> match cursor.chars{
+                [' ',' ','\n'] if !matches!(kind, Kind::TableCell{..})=>{
                     nodes.push(MarkdownNode::NewLine);
                     cursor.skip(2);
                 }
                 ['\n',_,_] | ['\0',_,_]=>{
-                    for _ in 0..*bold{
-                        nodes.push(MarkdownNode::EndBold);
-                    }
-                    for _ in 0..*italic{
-                        nodes.push(MarkdownNode::EndItalic);
-                    }
-                    for _ in 0..*underline{
-                        nodes.push(MarkdownNode::EndUnderline);
-                    }
-                    *bold = 0;
-                    *italic = 0;
-                    *underline = 0;
+                    end_styles(&mut nodes, bold, italic, strikethrough);
                     
                     match kind{
                         Kind::Head=>{
@@ -219,7 +429,7 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
                                 cursor.next();
                                 spaces += 1;
                             }
-                            if cursor.chars[0] == '#'{
+                            if cursor.chars[0] == '#' || (spaces < 4 && scan_table_header(&cursor).is_some()){
                                 state = State::Root{spaces};
                                 nodes.push(MarkdownNode::EndNormal);
                             }
@@ -263,9 +473,47 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
                                 push_char(&mut nodes, &mut decoded, ' ');
                             }
                         }
+                        Kind::TableCell{column, header}=>{
+                            end_table_cell(&mut nodes, &mut decoded);
+                            if end_table_row(&mut nodes, &mut cursor, &table_aligns, *column, *header){
+                                begin_table_cell(&mut nodes, &mut cursor, &table_aligns, 0, false);
+                                state = State::Inline{kind:Kind::TableCell{column:0, header:false}, bold:0, italic:0, strikethrough:0};
+                            }
+                            else{
+                                state = State::Root{spaces:0};
+                            }
+                        }
                     }
                     
                 }
+                ['\\','|',_] if matches!(kind, Kind::TableCell{..})=>{
+                    push_char(&mut nodes, &mut decoded, '|');
+                    cursor.skip(2);
+                }
+                ['|',_,_] if matches!(kind, Kind::TableCell{..})=>{
+                    let Kind::TableCell{column, header} = kind else{unreachable!()};
+                    let (column, header) = (*column, *header);
+                    end_styles(&mut nodes, bold, italic, strikethrough);
+                    end_table_cell(&mut nodes, &mut decoded);
+                    cursor.next();
+                    while cursor.chars[0] == ' '{
+                        cursor.next();
+                    }
+                    if cursor.chars[0] == '\n' || cursor.at_end() || column + 1 >= table_aligns.len(){
+                        // the row ends here, cells past the column count are dropped
+                        if end_table_row(&mut nodes, &mut cursor, &table_aligns, column, header){
+                            begin_table_cell(&mut nodes, &mut cursor, &table_aligns, 0, false);
+                            state = State::Inline{kind:Kind::TableCell{column:0, header:false}, bold:0, italic:0, strikethrough:0};
+                        }
+                        else{
+                            state = State::Root{spaces:0};
+                        }
+                    }
+                    else{
+                        begin_table_cell(&mut nodes, &mut cursor, &table_aligns, column + 1, header);
+                        state = State::Inline{kind:Kind::TableCell{column:column + 1, header}, bold:0, italic:0, strikethrough:0};
+                    }
+                }
                 ['*','*',w] | ['_','_',w] if w != ' ' && w != '\n'=>{ // alright so have have 2 *'s
                     // this is the start of a bold block
                     nodes.push(MarkdownNode::BeginBold);
@@ -284,19 +532,19 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
                         cursor.next();
                     }
                 }
-                ['~','~',w] if w != ' ' && w != '\n'=>{ // alright so have have 2 *'s
-                    // this is the start of a bold block
-                    nodes.push(MarkdownNode::BeginUnderline);
-                    *underline += 1;
+                ['~','~',w] if w != ' ' && w != '\n'=>{
+                    // start of a strikethrough block
+                    nodes.push(MarkdownNode::BeginStrikethrough);
+                    *strikethrough += 1;
                     cursor.skip(2);
                 }
                 [w,'~','~'] if w != ' '&& w != '\n'=>{
-                    // end of a bold block
+                    // end of a strikethrough block
                     push_char(&mut nodes, &mut decoded, w);
-                    if *underline > 0{
-                        *underline -= 1;
+                    if *strikethrough > 0{
+                        *strikethrough -= 1;
                         cursor.skip(3);
-                        nodes.push(MarkdownNode::EndUnderline);
+                        nodes.push(MarkdownNode::EndStrikethrough);
                     }
                     else{
                         cursor.next();
@@ -359,6 +607,12 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
                                 nodes.push(MarkdownNode::EndListItem);
                             }
                         }
+                        Kind::TableCell{..} => {
+                            end_styles(&mut nodes, bold, italic, strikethrough);
+                            nodes.push(MarkdownNode::EndTableCell);
+                            nodes.push(MarkdownNode::EndTableRow);
+                            nodes.push(MarkdownNode::EndTable);
+                        }
                     }
                     state = State::Root{spaces:0};
                 }
@@ -389,7 +643,7 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
                 ['!', '[', _]=>{
                     // alright lets do it
                     let mut scan = cursor.clone();
-                    scan.skip(1);
+                    scan.skip(2);
                     
                     let start = decoded.len();
                     // lets first patternmatch it
@@ -418,9 +672,9 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
                         }
                     }
                     decoded.truncate(start);
-                    decoded.push(cursor.chars[0]);
-                    decoded.push(cursor.chars[1]);
-                    // parse inline image
+                    // not an image after all, keep the ![ as text
+                    push_char(&mut nodes, &mut decoded, '!');
+                    push_char(&mut nodes, &mut decoded, '[');
                     cursor.skip(2);
                 }
                 ['[',_,_]=>{ // possible named link
@@ -455,9 +709,23 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
                         }
                     }
                     decoded.truncate(start);
-                    decoded.push(cursor.chars[0]);
+                    push_char(&mut nodes, &mut decoded, '[');
                     cursor.next();
                 }
+                ['<',_,_] | ['h','t','t'] | ['w','w','w']=>{
+                    if let Some((text, url, next)) = scan_autolink(&cursor){
+                        let start = decoded.len();
+                        decoded.push_str(&text);
+                        let url_start = decoded.len();
+                        decoded.push_str(&url);
+                        nodes.push(MarkdownNode::Link{start, url_start, end:decoded.len()});
+                        cursor = next;
+                    }
+                    else{
+                        push_char(&mut nodes, &mut decoded, cursor.chars[0]);
+                        cursor.next();
+                    }
+                }
                 [' ',_,_]=>{
                     if cursor.last_char != ' '{
                         push_char(&mut nodes, &mut decoded, ' ');
@@ -504,7 +772,7 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
                         }
                         push_optional_char(&mut nodes, &mut decoded, ' ');
                         // alright now we know how deep in the block stack we need to be
-                        state = State::Inline{kind:Kind::Quote(blocks), bold:0, italic:0, underline:0};
+                        state = State::Inline{kind:Kind::Quote(blocks), bold:0, italic:0, strikethrough:0};
                     }
                 }
                 ['#',_,_]=>{
@@ -526,13 +794,13 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
                         else{
                             nodes.push(MarkdownNode::Text{start, end:decoded.len()});
                         }
-                        state = State::Inline{kind:Kind::Normal, bold:0, italic:0, underline:0};
+                        state = State::Inline{kind:Kind::Normal, bold:0, italic:0, strikethrough:0};
                     }
                     else {
                         cursor.next();
                         decoded.truncate(start);
                         nodes.push(MarkdownNode::BeginHead{level});
-                        state = State::Inline{kind:Kind::Head, bold:0, italic:0, underline:0};
+                        state = State::Inline{kind:Kind::Head, bold:0, italic:0, strikethrough:0};
                     }
                 }
                 ['-','-','-']=>{ // separator
@@ -545,7 +813,7 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
                         if cursor.chars[2] != '\n'{
                             nodes.push(MarkdownNode::BeginNormal);
                             push_char(&mut nodes, &mut decoded, '-');
-                            state = State::Inline{kind:Kind::Normal, bold:0, italic:0, underline:0};
+                            state = State::Inline{kind:Kind::Normal, bold:0, italic:0, strikethrough:0};
                         }
                         else{
                             cursor.skip(3);
@@ -556,7 +824,18 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
                 }
                 ['`','`','`']=>{ // begins or ends blocks of code. 
                     cursor.skip(3);
-                    nodes.push(MarkdownNode::BeginCode);
+                    // the first word of the info string is the language, it can't contain backticks
+                    let (info, next) = scan_line(&cursor);
+                    let mut lang = None;
+                    if !info.contains('`'){
+                        cursor = next;
+                        lang = info.split_whitespace().next().map( | lang | {
+                            let start = decoded.len();
+                            decoded.push_str(lang);
+                            (start, decoded.len())
+                        });
+                    }
+                    nodes.push(MarkdownNode::BeginCode{lang});
                     let start = decoded.len();
                     while cursor.chars != ['`','`','`'] && !cursor.at_end(){
                         if cursor.chars[0] == '\n' && start != decoded.len(){
@@ -593,7 +872,7 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
                         }
                         else{ // its normal 
                             nodes.push(MarkdownNode::BeginNormal);
-                            state = State::Inline{kind:Kind::Normal, bold:0, italic:0, underline:0};
+                            state = State::Inline{kind:Kind::Normal, bold:0, italic:0, strikethrough:0};
                         }
                     }
                     else{
@@ -602,15 +881,19 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
                                 nodes.pop();
                             }
                         }
-                        // we always push a begin list item on
-                        nodes.push(MarkdownNode::BeginListItem{label:match cursor.chars[0]{
+                        let label = match cursor.chars[0]{
                             '-'=>MarkdownListLabel::Minus,
                             '*'=>MarkdownListLabel::Star,
                             '+'=>MarkdownListLabel::Plus,
                             _=>panic!()
-                        }});
+                        };
+                        cursor.skip(2);
+                        let checked = scan_task_checkbox(&mut cursor);
+                        // we always push a begin list item on
+                        nodes.push(MarkdownNode::BeginListItem{label, checked});
                         
-                        state = State::Inline{kind:Kind::List(depth), bold:0, italic:0, underline:0}
+                        state = State::Inline{kind:Kind::List(depth), bold:0, italic:0, strikethrough:0};
+                        continue;
                     }
                     cursor.skip(2);
                     //push_optional_char(&mut nodes, &mut decoded, ' ');
@@ -653,7 +936,7 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
                             }
                             else{ // its normal 
                                 nodes.push(MarkdownNode::BeginNormal);
-                                state = State::Inline{kind:Kind::Normal, bold:0, italic:0, underline:0};
+                                state = State::Inline{kind:Kind::Normal, bold:0, italic:0, strikethrough:0};
                             }
                         }
                         else{ 
@@ -691,22 +974,33 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
                             write!(&mut decoded, "{}.", digit).unwrap();
                             let end = decoded.len();
                             
+                            let checked = scan_task_checkbox(&mut cursor);
                             nodes.push(MarkdownNode::BeginListItem{label:MarkdownListLabel::Number{
                                 digit,
                                 start,
                                 end
-                            }});
+                            }, checked});
                                                     
-                            state = State::Inline{kind:Kind::List(depth), bold:0, italic:0, underline:0}
+                            state = State::Inline{kind:Kind::List(depth), bold:0, italic:0, strikethrough:0}
                         }
                     }
                     else if *spaces>=4{ // its code
                         code_on_one_line(&mut nodes, &mut decoded, &mut cursor);
                         state = State::Root{spaces:0};
                     }
+                    else if let Some(aligns) = scan_table_header(&cursor){
+                        nodes.push(MarkdownNode::BeginTable{columns:aligns.len()});
+                        nodes.push(MarkdownNode::BeginTableRow);
+                        table_aligns = aligns;
+                        if cursor.chars[0] == '|'{
+                            cursor.next();
+                        }
+                        begin_table_cell(&mut nodes, &mut cursor, &table_aligns, 0, true);
+                        state = State::Inline{kind:Kind::TableCell{column:0, header:true}, bold:0, italic:0, strikethrough:0};
+                    }
                     else{
                         nodes.push(MarkdownNode::BeginNormal);
-                        state = State::Inline{kind:Kind::Normal, bold:0, italic:0, underline:0};
+                        state = State::Inline{kind:Kind::Normal, bold:0, italic:0, strikethrough:0};
                     }
                 }
             }
@@ -716,4 +1010,127 @@ pub fn parse_markdown(body:&str)->MarkdownDoc{
         nodes,
         decoded,
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    // the nodes of a document with their text filled in
+    fn parse(body:&str)->Vec<String>{
+        let doc = parse_markdown(body);
+        doc.nodes.iter().map( | node | match node{
+            MarkdownNode::Text{start, end}=>format!("{:?}", &doc.decoded[*start..*end]),
+            MarkdownNode::Link{start, url_start, end}=>format!("Link({}, {})", &doc.decoded[*start..*url_start], &doc.decoded[*url_start..*end]),
+            MarkdownNode::Image{start, url_start, end}=>format!("Image({}, {})", &doc.decoded[*start..*url_start], &doc.decoded[*url_start..*end]),
+            MarkdownNode::BeginCode{lang:Some((start, end))}=>format!("BeginCode({})", &doc.decoded[*start..*end]),
+            MarkdownNode::BeginListItem{label:MarkdownListLabel::Number{start, end, ..}, checked}=>format!("BeginListItem({}, {:?})", &doc.decoded[*start..*end], checked),
+            node=>format!("{:?}", node)
+        }).collect()
+    }
+    
+    #[test]
+    fn parses_tables(){
+        assert_eq!(parse("| a | b |\n|:--|--:|\n| 1 | **2** |\n3 \\| 4\n\nafter\n"), vec![
+            "BeginTable { columns: 2 }",
+            "BeginTableRow",
+            "BeginTableCell { align: Left, header: true }", "\"a\"", "EndTableCell",
+            "BeginTableCell { align: Right, header: true }", "\"b\"", "EndTableCell",
+            "EndTableRow",
+            "BeginTableRow",
+            "BeginTableCell { align: Left, header: false }", "\"1\"", "EndTableCell",
+            "BeginTableCell { align: Right, header: false }", "BeginBold", "\"2\"", "EndBold", "EndTableCell",
+            "EndTableRow",
+            // an escaped | stays in the cell and missing cells are padded
+            "BeginTableRow",
+            "BeginTableCell { align: Left, header: false }", "\"3 | 4\"", "EndTableCell",
+            "BeginTableCell { align: Right, header: false }", "EndTableCell",
+            "EndTableRow",
+            "EndTable",
+            "BeginNormal", "\"after\"", "EndNormal",
+        ]);
+        assert_eq!(parse("a | b | c\n:-: | --- | ---:\n"), vec![
+            "BeginTable { columns: 3 }",
+            "BeginTableRow",
+            "BeginTableCell { align: Center, header: true }", "\"a\"", "EndTableCell",
+            "BeginTableCell { align: None, header: true }", "\"b\"", "EndTableCell",
+            "BeginTableCell { align: Right, header: true }", "\"c\"", "EndTableCell",
+            "EndTableRow",
+            "EndTable",
+        ]);
+        // the delimiter row has to match the header
+        assert_eq!(parse("a | b\n--- | --- | ---\n"), vec!["BeginNormal", "\"a | b --- | --- | ---\"", "EndNormal"]);
+        assert_eq!(parse("a | b\n--- | -x-\n"), vec!["BeginNormal", "\"a | b --- | -x-\"", "EndNormal"]);
+    }
+    
+    #[test]
+    fn parses_task_lists(){
+        assert_eq!(parse("- [ ] todo\n- [x] done\n1. [X] first\n- [y] not\n-  [ ]\n"), vec![
+            "BeginListItem { label: Minus, checked: Some(false) }", "\"todo\"", "EndListItem",
+            "BeginListItem { label: Minus, checked: Some(true) }", "\"done\"", "EndListItem",
+            "BeginListItem(1., Some(true))", "\"first\"", "EndListItem",
+            "BeginListItem { label: Minus, checked: None }", "\"[y] not\"", "EndListItem",
+            "BeginListItem { label: Minus, checked: None }", "\"[ ]\"", "EndListItem",
+        ]);
+    }
+    
+    #[test]
+    fn parses_strikethrough(){
+        assert_eq!(parse("~~gone~~ and ~ single ~~ not~~ x\n"), vec![
+            "BeginNormal",
+            "BeginStrikethrough", "\"gone\"", "EndStrikethrough",
+            "\" and ~ single ~~ not~~ x\"",
+            "EndNormal",
+        ]);
+        // styles still open at the end of the line are closed there
+        assert_eq!(parse("a ~~b **c\n"), vec![
+            "BeginNormal", "\"a \"", "BeginStrikethrough", "\"b \"", "BeginBold", "\"c\"", "EndBold", "EndStrikethrough", "EndNormal",
+        ]);
+    }
+    
+    #[test]
+    fn parses_autolinks(){
+        assert_eq!(parse("<https://a.b/c> see www.makepad.dev, and (https://x.y/a_(b)). <mailto:me@x.y>\n"), vec![
+            "BeginNormal",
+            "Link(https://a.b/c, https://a.b/c)",
+            "\" see \"",
+            "Link(www.makepad.dev, http://www.makepad.dev)",
+            "\", and (\"",
+            "Link(https://x.y/a_(b), https://x.y/a_(b))",
+            "\"). \"",
+            "Link(mailto:me@x.y, mailto:me@x.y)",
+            "EndNormal",
+        ]);
+        // not links: no scheme, spaces, mid word, nothing after the prefix
+        assert_eq!(parse("<a b> <x> awww.x.y https:// www.\n"), vec![
+            "BeginNormal", "\"<a b> <x> awww.x.y https:// www.\"", "EndNormal",
+        ]);
+        assert_eq!(parse("[name](https://a.b) ![alt](c.png) [not a link\n"), vec![
+            "BeginNormal",
+            "Link(name, https://a.b)",
+            "\" \"",
+            "Image(alt, c.png)",
+            "\" [not a link\"",
+            "EndNormal",
+        ]);
+        assert_eq!(parse("a | b\n--|--\nhttps://c.d|<https://e.f>\n"), vec![
+            "BeginTable { columns: 2 }",
+            "BeginTableRow",
+            "BeginTableCell { align: None, header: true }", "\"a\"", "EndTableCell",
+            "BeginTableCell { align: None, header: true }", "\"b\"", "EndTableCell",
+            "EndTableRow",
+            "BeginTableRow",
+            "BeginTableCell { align: None, header: false }", "Link(https://c.d, https://c.d)", "EndTableCell",
+            "BeginTableCell { align: None, header: false }", "Link(https://e.f, https://e.f)", "EndTableCell",
+            "EndTableRow",
+            "EndTable",
+        ]);
+    }
+    
+    #[test]
+    fn parses_code_fence_languages(){
+        assert_eq!(parse("```rust\nfn x(){}\n```\n```\ny\n```\n"), vec![
+            "BeginCode(rust)", "\"fn x(){}\"", "EndCode",
+            "BeginCode { lang: None }", "\"y\"", "EndCode",
+        ]);
+    }
+}
@@ -0,0 +1,200 @@
+use proc_macro::{TokenStream, TokenTree};
+use makepad_micro_proc_macro::*;
+
+// the field and container attributes shared by the json, ron and bin derives
+
+pub struct FieldAttrs {
+    pub rename: Option<String>,
+    pub default: Option<TokenStream>,
+    pub skip: bool,
+    pub flatten: bool,
+    // the field or variant id in the #[versioned] binary layout
+    pub id: Option<u16>,
+}
+
+pub enum EnumTagging {
+    // {"Variant":[...]} in json, Variant(...) in ron
+    External,
+    // {"tag":"Variant", ...fields} in json, (tag:"Variant", ...fields) in ron
+    Internal(String),
+    // just the variant content, deserializing tries each variant in order
+    Untagged,
+}
+
+pub struct ContainerAttrs {
+    pub rename_all: Option<String>,
+    pub tagging: EnumTagging,
+    // SerBin/DeBin write field ids and lengths instead of the positional layout
+    pub versioned: bool,
+}
+
+// the string literal of #[name = "x"] or #[name("x")]
+fn attr_string(attr: &Attribute) -> Option<String> {
+    for tt in attr.args.clone()? {
+        if let TokenTree::Literal(lit) = tt {
+            let lit = lit.to_string();
+            if lit.len() >= 2 && lit.starts_with('"') && lit.ends_with('"') {
+                return Some(lit[1..lit.len() - 1].to_string())
+            }
+        }
+    }
+    None
+}
+
+// the integer literal of #[name = 3] or #[name(3)]
+fn attr_u16(attr: &Attribute) -> Option<u16> {
+    for tt in attr.args.clone()? {
+        if let TokenTree::Literal(lit) = tt {
+            return lit.to_string().trim_end_matches("u16").replace('_', "").parse().ok()
+        }
+    }
+    None
+}
+
+pub fn parse_fields(fields: Vec<StructField>) -> Result<Vec<(StructField, FieldAttrs)>, TokenStream> {
+    let mut out = Vec::new();
+    for field in fields {
+        let attrs = FieldAttrs::parse(&field.attrs) ?;
+        out.push((field, attrs));
+    }
+    Ok(out)
+}
+
+pub fn is_option(field: &StructField) -> bool {
+    field.ty.clone().into_iter().next().is_some_and( | v | v.to_string() == "Option")
+}
+
+impl FieldAttrs {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self, TokenStream> {
+        let mut ret = Self {rename: None, default: None, skip: false, flatten: false, id: None};
+        for attr in attrs {
+            match attr.name.as_ref() {
+                "rename" => if let Some(name) = attr_string(attr) {
+                    ret.rename = Some(name)
+                }
+                else {
+                    return Err(error("rename expects a string like #[rename = \"name\"]"))
+                },
+                "default" => ret.default = Some(if let Some(args) = &attr.args {
+                    args.clone()
+                }
+                else {
+                    let mut tb = TokenBuilder::new();
+                    tb.add("std :: default :: Default :: default ( )");
+                    tb.end()
+                }),
+                "skip" => ret.skip = true,
+                "flatten" => ret.flatten = true,
+                "id" => if let Some(id) = attr_u16(attr) {
+                    ret.id = Some(id)
+                }
+                else {
+                    return Err(error("id expects a number like #[id = 3]"))
+                },
+                _ => ()
+            }
+        }
+        Ok(ret)
+    }
+
+    // the value used when the field is skipped or missing
+    pub fn default_value(&self) -> TokenStream {
+        let mut tb = TokenBuilder::new();
+        if let Some(default) = &self.default {
+            tb.add("(").stream(Some(default.clone())).add(")");
+        }
+        else {
+            tb.add("std :: default :: Default :: default ( )");
+        }
+        tb.end()
+    }
+}
+
+impl ContainerAttrs {
+    pub fn parse(attrs: &[Attribute]) -> Result<Self, TokenStream> {
+        let mut ret = Self {rename_all: None, tagging: EnumTagging::External, versioned: false};
+        for attr in attrs {
+            match attr.name.as_ref() {
+                "rename_all" => if let Some(case) = attr_string(attr) {
+                    if rename_case("a_b", &case).is_none() {
+                        return Err(error(&format!("rename_all does not support {}", case)))
+                    }
+                    ret.rename_all = Some(case)
+                }
+                else {
+                    return Err(error("rename_all expects a string like #[rename_all = \"camelCase\"]"))
+                },
+                "tag" => if let Some(tag) = attr_string(attr) {
+                    ret.tagging = EnumTagging::Internal(tag)
+                }
+                else {
+                    return Err(error("tag expects a string like #[tag = \"type\"]"))
+                },
+                "untagged" => ret.tagging = EnumTagging::Untagged,
+                "versioned" => ret.versioned = true,
+                _ => ()
+            }
+        }
+        Ok(ret)
+    }
+
+    pub fn field_name(&self, name: &str, attrs: &FieldAttrs) -> String {
+        if let Some(rename) = &attrs.rename {
+            return rename.clone()
+        }
+        if let Some(case) = &self.rename_all {
+            return rename_case(name, case).unwrap()
+        }
+        name.to_string()
+    }
+
+    pub fn variant_name(&self, name: &str, attrs: &FieldAttrs) -> String {
+        if let Some(rename) = &attrs.rename {
+            return rename.clone()
+        }
+        if let Some(case) = &self.rename_all {
+            return rename_case(name, case).unwrap()
+        }
+        name.to_string()
+    }
+}
+
+// converts a snake_case field or PascalCase variant name to the given case
+pub fn rename_case(name: &str, case: &str) -> Option<String> {
+    // split into lowercase words on _ and on uppercase letters
+    let mut words: Vec<String> = Vec::new();
+    let mut word = String::new();
+    for c in name.chars() {
+        if c == '_' {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+        else if c.is_uppercase() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            word.extend(c.to_lowercase());
+        }
+        else {
+            word.push(c);
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    let capitalize = | w: &String | {
+        let mut c = w.chars();
+        c.next().map(| f | f.to_uppercase().chain(c).collect::<String>()).unwrap_or_default()
+    };
+    Some(match case {
+        "lowercase" => words.concat(),
+        "UPPERCASE" => words.concat().to_uppercase(),
+        "snake_case" => words.join("_"),
+        "SCREAMING_SNAKE_CASE" => words.join("_").to_uppercase(),
+        "kebab-case" => words.join("-"),
+        "camelCase" => words.iter().enumerate().map( | (i, w) | if i == 0 {w.clone()} else {capitalize(w)}).collect(),
+        "PascalCase" => words.iter().map(capitalize).collect(),
+        _ => return None
+    })
+}
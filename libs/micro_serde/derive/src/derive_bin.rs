@@ -1,11 +1,179 @@
 use proc_macro::{TokenStream};
 use makepad_micro_proc_macro::*;
+use crate::attrs::*;
+
+// bin has no names so only #[skip], #[default], #[id] and #[versioned] matter here.
+// the default layout is positional, #[versioned] types write a field count and then
+// every field as id, byte length and data so older readers skip what they don't know
+// and newer readers default what is missing
+
+struct BinField {
+    // None for tuple fields
+    name: Option<String>,
+    id: u16,
+    skip: bool,
+    default: TokenStream,
+}
+
+fn named_bin_fields(fields: Vec<StructField>, versioned: bool) -> Result<Vec<BinField>, TokenStream> {
+    let mut out: Vec<BinField> = Vec::new();
+    for (index, (field, attrs)) in parse_fields(fields) ?.into_iter().enumerate() {
+        let id = if attrs.skip {index as u16} else {bin_id(&attrs, versioned, index, &format!("field {}", field.name)) ?};
+        if !attrs.skip && out.iter().any( | f | !f.skip && f.id == id) {
+            return Err(error(&format!("duplicate bin id {} on field {}", id, field.name)))
+        }
+        out.push(BinField {name: Some(field.name), id, skip: attrs.skip, default: attrs.default_value()});
+    }
+    Ok(out)
+}
+
+// the id of a named field or variant on the wire. #[versioned] types have to spell them out,
+// positions would change meaning when a field is inserted, moved or removed
+fn bin_id(attrs: &FieldAttrs, versioned: bool, index: usize, what: &str) -> Result<u16, TokenStream> {
+    match attrs.id {
+        Some(id) => Ok(id),
+        None if versioned => Err(error(&format!("#[versioned] needs an #[id = n] on {}", what))),
+        None => Ok(index as u16)
+    }
+}
+
+fn variant_id(attrs: &FieldAttrs, versioned: bool, index: usize, variant: &str, used: &mut Vec<u16>) -> Result<u16, TokenStream> {
+    let id = bin_id(attrs, versioned, index, &format!("variant {}", variant)) ?;
+    if used.contains(&id) {
+        return Err(error(&format!("duplicate bin id {} on variant {}", id, variant)))
+    }
+    used.push(id);
+    Ok(id)
+}
+
+// a missing tuple field is an error, they don't have to implement Default. Tuple fields
+// keep their positions as ids, like the fields of a tuple they can only be appended
+fn tuple_bin_fields(len: usize, label: &str) -> Vec<BinField> {
+    (0..len).map( | index | {
+        let mut tb = TokenBuilder::new();
+        tb.add("return std :: result :: Result :: Err ( DeBinErr { o : * o , l : 0 , s : d . len ( ) , msg :");
+        tb.string(&format!("missing field {}.{}", label, index)).add(". to_string ( ) } )");
+        BinField {name: None, id: index as u16, skip: false, default: tb.end()}
+    }).collect()
+}
+
+// the struct field name or the bound variant name of field i
+fn bin_binding(tb: &mut TokenBuilder, prefix: &str, i: usize, field: &BinField) {
+    if !prefix.is_empty() {
+        tb.add(prefix);
+    }
+    match &field.name {
+        Some(name) => {tb.ident(name);},
+        None if prefix.is_empty() => {tb.ident(&format!("n{}", i));},
+        None => {tb.unsuf_usize(i);}
+    }
+}
+
+fn ser_bin_fields(tb: &mut TokenBuilder, fields: &[BinField], versioned: bool, prefix: &str) {
+    if versioned {
+        let count = fields.iter().filter( | f | !f.skip).count();
+        tb.suf_u16(count as u16).add(". ser_bin ( s ) ;");
+    }
+    for (i, field) in fields.iter().enumerate() {
+        if field.skip {
+            continue;
+        }
+        bin_binding(tb, prefix, i, field);
+        if versioned {
+            tb.add(". ser_bin_tagged (").suf_u16(field.id).add(", s ) ;");
+        }
+        else {
+            tb.add(". ser_bin ( s ) ;");
+        }
+    }
+}
+
+fn bin_field_label(label: &str, i: usize, field: &BinField) -> String {
+    match &field.name {
+        Some(name) => format!("{}.{}", label, name),
+        None => format!("{}.{}", label, i)
+    }
+}
+
+// reads the fields into locals _v0.. in the order they are in the data
+fn de_bin_fields(tb: &mut TokenBuilder, fields: &[BinField], versioned: bool, label: &str) {
+    if !versioned {
+        for (i, field) in fields.iter().enumerate() {
+            if !field.skip {
+                tb.add("let").ident(&format!("_v{}", i)).add("= DeBin :: de_bin ( o , d )");
+                tb.add(". map_err ( | e | e . in_field (").string(&bin_field_label(label, i, field)).add(") ) ? ;");
+            }
+        }
+        return
+    }
+    let read = fields.iter().any( | f | !f.skip);
+    for (i, field) in fields.iter().enumerate() {
+        if !field.skip {
+            tb.add("let mut").ident(&format!("_v{}", i)).add("= None ;");
+        }
+    }
+    tb.add("let count : u16 = DeBin :: de_bin ( o , d ) ? ;");
+    tb.add("for _ in 0 .. count {");
+    tb.add(if read {"let id : u16 = DeBin :: de_bin ( o , d ) ? ;"} else {"let _ : u16 = DeBin :: de_bin ( o , d ) ? ;"});
+    tb.add("let len : u32 = DeBin :: de_bin ( o , d ) ? ;");
+    tb.add("let end = * o + len as usize ;");
+    tb.add("if end > d . len ( ) {");
+    tb.add("return std :: result :: Result :: Err ( DeBinErr { o : * o , l : len as usize , s : d . len ( ) , msg :").string(label).add(". to_string ( ) } )");
+    tb.add("}");
+    if read {
+        tb.add("match id {");
+        for (i, field) in fields.iter().enumerate() {
+            if !field.skip {
+                tb.suf_u16(field.id).add("=>").ident(&format!("_v{}", i)).add("= Some ( DeBin :: de_bin ( o , & d [ .. end ] )");
+                tb.add(". map_err ( | e | e . in_field (").string(&bin_field_label(label, i, field)).add(") ) ? ) ,");
+            }
+        }
+        tb.add("_ => ( )");
+        tb.add("}");
+    }
+    tb.add("* o = end ;");
+    tb.add("}");
+}
+
+fn de_bin_value(tb: &mut TokenBuilder, fields: &[BinField], versioned: bool, i: usize) {
+    let field = &fields[i];
+    if field.skip {
+        tb.stream(Some(field.default.clone()));
+    }
+    else if versioned {
+        tb.add("if let Some ( t ) =").ident(&format!("_v{}", i)).add("{ t } else {").stream(Some(field.default.clone())).add("}");
+    }
+    else {
+        tb.ident(&format!("_v{}", i));
+    }
+}
+
+// builds path { a: _v0, .. } or path ( _v0, .. ) from the locals
+fn de_bin_construct(tb: &mut TokenBuilder, fields: &[BinField], versioned: bool, named: bool) {
+    if named {
+        tb.add("{");
+        for (i, field) in fields.iter().enumerate() {
+            tb.ident(field.name.as_ref().unwrap()).add(":");
+            de_bin_value(tb, fields, versioned, i);
+            tb.add(",");
+        }
+        tb.add("}");
+    }
+    else {
+        tb.add("(");
+        for i in 0..fields.len() {
+            de_bin_value(tb, fields, versioned, i);
+            tb.add(",");
+        }
+        tb.add(")");
+    }
+}
 
 pub fn derive_ser_bin_impl(input: TokenStream) -> TokenStream {
     let mut parser = TokenParser::new(input);
     let mut tb = TokenBuilder::new();
-    
-    parser.eat_attributes();
+
+    let container = match ContainerAttrs::parse(&parser.eat_attributes()) {Ok(v) => v, Err(e) => return e};
     parser.eat_ident("pub");
     if parser.eat_ident("struct"){
         if let Some(name) = parser.eat_any_ident(){
@@ -19,19 +187,16 @@ pub fn derive_ser_bin_impl(input: TokenStream) -> TokenStream {
             tb.add("{ fn ser_bin ( & self , s : & mut Vec < u8 > ) {");
 
             if let Some(types) = types{
-                for i in 0..types.len(){
-                     tb.add("self .").unsuf_usize(i).add(". ser_bin ( s ) ;");
-                }
+                ser_bin_fields(&mut tb, &tuple_bin_fields(types.len(), &name), container.versioned, "self .");
             }
-            else if let Some(fields) = parser.eat_all_struct_fields(){ 
-                for field in fields{
-                    tb.add("self .").ident(&field.name).add(". ser_bin ( s ) ;");
-                }
+            else if let Some(fields) = parser.eat_all_struct_fields(){
+                let fields = match named_bin_fields(fields, container.versioned) {Ok(v) => v, Err(e) => return e};
+                ser_bin_fields(&mut tb, &fields, container.versioned, "self .");
             }
             else{
                 return parser.unexpected()
             }
-            tb.add("} } ;"); 
+            tb.add("} } ;");
             return tb.end();
         }
     }
@@ -39,45 +204,48 @@ pub fn derive_ser_bin_impl(input: TokenStream) -> TokenStream {
         if let Some(name) = parser.eat_any_ident(){
             let generic = parser.eat_generic();
             let where_clause = parser.eat_where_clause(Some("SerBin"));
-            
+
             tb.add("impl").stream(generic.clone());
             tb.add("SerBin for").ident(&name).stream(generic).stream(where_clause);
             tb.add("{ fn ser_bin ( & self , s : & mut Vec < u8 > ) {");
             tb.add("match self {");
-            
+
             if !parser.open_brace(){
                 return parser.unexpected()
             }
             let mut index = 0;
+            let mut used_ids = Vec::new();
             while !parser.eat_eot(){
-                parser.eat_attributes();
+                let attrs = match FieldAttrs::parse(&parser.eat_attributes()) {Ok(v) => v, Err(e) => return e};
                 // parse ident
                 if let Some(variant) = parser.eat_any_ident(){
+                    let id = match variant_id(&attrs, container.versioned, index, &variant, &mut used_ids) {Ok(v) => v, Err(e) => return e};
                     if let Some(types) = parser.eat_all_types(){
                         tb.add("Self ::").ident(&variant).add("(");
                         for i in 0..types.len(){
                             tb.ident(&format!("n{}", i)).add(",");
                         }
-                        tb.add(") => {").suf_u16(index).add(". ser_bin ( s ) ;");
-                        for i in 0..types.len(){
-                            tb.ident(&format!("n{}", i)).add(". ser_bin ( s ) ;");
-                        }
+                        tb.add(") => {").suf_u16(id).add(". ser_bin ( s ) ;");
+                        ser_bin_fields(&mut tb, &tuple_bin_fields(types.len(), &variant), container.versioned, "");
                         tb.add("}");
                     }
                     else if let Some(fields) = parser.eat_all_struct_fields(){ // named variant
+                        let fields = match named_bin_fields(fields, container.versioned) {Ok(v) => v, Err(e) => return e};
                         tb.add("Self ::").ident(&variant).add("{");
                         for field in fields.iter(){
-                            tb.ident(&field.name).add(",");
-                        }
-                        tb.add("} => {").suf_u16(index).add(". ser_bin ( s ) ;");
-                        for field in fields{
-                            tb.ident(&field.name).add(". ser_bin ( s ) ;");
+                            if !field.skip {
+                                tb.ident(field.name.as_ref().unwrap()).add(",");
+                            }
                         }
+                        tb.add(".. } => {").suf_u16(id).add(". ser_bin ( s ) ;");
+                        ser_bin_fields(&mut tb, &fields, container.versioned, "");
                         tb.add("}");
                     }
                     else if parser.is_punct_alone(',') || parser.is_eot(){ // bare variant
                         tb.add("Self ::").ident(&variant).add("=> {");
-                        tb.suf_u16(index).add(". ser_bin ( s ) ; }");
+                        tb.suf_u16(id).add(". ser_bin ( s ) ;");
+                        ser_bin_fields(&mut tb, &[], container.versioned, "");
+                        tb.add("}");
                     }
                     else{
                         return parser.unexpected();
@@ -94,13 +262,13 @@ pub fn derive_ser_bin_impl(input: TokenStream) -> TokenStream {
         }
     }
     parser.unexpected()
-} 
+}
 
 pub fn derive_de_bin_impl(input: TokenStream) -> TokenStream {
     let mut parser = TokenParser::new(input);
     let mut tb = TokenBuilder::new();
-    
-    parser.eat_attributes();
+
+    let container = match ContainerAttrs::parse(&parser.eat_attributes()) {Ok(v) => v, Err(e) => return e};
     parser.eat_ident("pub");
     if parser.eat_ident("struct"){
         if let Some(name) = parser.eat_any_ident(){
@@ -112,26 +280,20 @@ pub fn derive_de_bin_impl(input: TokenStream) -> TokenStream {
             tb.add("DeBin for").ident(&name).stream(generic).stream(where_clause);
             tb.add("{ fn de_bin ( o : & mut usize , d : & [ u8 ] )");
             tb.add("-> std :: result :: Result < Self , DeBinErr > { ");
-            tb.add("std :: result :: Result :: Ok ( Self");
 
-            if let Some(types) = types{
-                tb.add("(");
-                for _ in 0..types.len(){
-                     tb.add("DeBin :: de_bin ( o , d ) ? ,");
-                }
-                tb.add(")");
+            let (fields, named) = if let Some(types) = types{
+                (tuple_bin_fields(types.len(), &name), false)
             }
-            else if let Some(fields) = parser.eat_all_struct_fields(){ 
-                tb.add("{");
-                for field in fields{
-                    tb.ident(&field.name).add(": DeBin :: de_bin ( o , d ) ? ,");
-                }
-                tb.add("}");
+            else if let Some(fields) = parser.eat_all_struct_fields(){
+                match named_bin_fields(fields, container.versioned) {Ok(v) => (v, true), Err(e) => return e}
             }
             else{
                 return parser.unexpected()
-            }
-            tb.add(") } } ;"); 
+            };
+            de_bin_fields(&mut tb, &fields, container.versioned, &name);
+            tb.add("std :: result :: Result :: Ok ( Self");
+            de_bin_construct(&mut tb, &fields, container.versioned, named);
+            tb.add(") } } ;");
             return tb.end();
         }
     }
@@ -139,45 +301,46 @@ pub fn derive_de_bin_impl(input: TokenStream) -> TokenStream {
         if let Some(name) = parser.eat_any_ident(){
             let generic = parser.eat_generic();
             let where_clause = parser.eat_where_clause(Some("DeBin"));
-            
+
             tb.add("impl").stream(generic.clone());
             tb.add("DeBin for").ident(&name).stream(generic).stream(where_clause);
             tb.add("{ fn de_bin ( o : & mut usize , d : & [ u8 ] )");
             tb.add("-> std :: result :: Result < Self , DeBinErr > {");
             tb.add("let id : u16 = DeBin :: de_bin ( o , d ) ? ;");
             tb.add("match id {");
-            
+
             if !parser.open_brace(){
                 return parser.unexpected()
             }
             let mut index = 0;
+            let mut used_ids = Vec::new();
             while !parser.eat_eot(){
+                let attrs = match FieldAttrs::parse(&parser.eat_attributes()) {Ok(v) => v, Err(e) => return e};
                 // parse ident
-                parser.eat_attributes();
                 if let Some(variant) = parser.eat_any_ident(){
-                    tb.suf_u16(index as u16).add("=> {");
-                    tb.add("std :: result :: Result :: Ok ( Self ::");
+                    let id = match variant_id(&attrs, container.versioned, index, &variant, &mut used_ids) {Ok(v) => v, Err(e) => return e};
+                    let label = format!("{}::{}", name, variant);
+                    tb.suf_u16(id).add("=> {");
                     if let Some(types) = parser.eat_all_types(){
-                        tb.ident(&variant).add("(");
-                        for _ in 0..types.len(){
-                            tb.add("DeBin :: de_bin ( o , d ) ? ,");
-                        }
-                        tb.add(")");
+                        let fields = tuple_bin_fields(types.len(), &label);
+                        de_bin_fields(&mut tb, &fields, container.versioned, &label);
+                        tb.add("std :: result :: Result :: Ok ( Self ::").ident(&variant);
+                        de_bin_construct(&mut tb, &fields, container.versioned, false);
                     }
                     else if let Some(fields) = parser.eat_all_struct_fields(){ // named variant
-                        tb.ident(&variant).add("{");
-                        for field in fields.iter(){
-                            tb.ident(&field.name).add(": DeBin :: de_bin ( o , d ) ? ,");
-                        }
-                        tb.add("}");
+                        let fields = match named_bin_fields(fields, container.versioned) {Ok(v) => v, Err(e) => return e};
+                        de_bin_fields(&mut tb, &fields, container.versioned, &label);
+                        tb.add("std :: result :: Result :: Ok ( Self ::").ident(&variant);
+                        de_bin_construct(&mut tb, &fields, container.versioned, true);
                     }
                     else if parser.is_punct_alone(',') || parser.is_eot(){ // bare variant
-                        tb.ident(&variant);
+                        de_bin_fields(&mut tb, &[], container.versioned, &label);
+                        tb.add("std :: result :: Result :: Ok ( Self ::").ident(&variant);
                     }
                     else{
                         return parser.unexpected();
                     }
-                    
+
                     tb.add(") }");
                     index += 1;
                     parser.eat_punct_alone(',');
@@ -185,9 +348,9 @@ pub fn derive_de_bin_impl(input: TokenStream) -> TokenStream {
                 else{
                     return parser.unexpected()
                 }
-            } 
+            }
             tb.add("_ => std :: result :: Result :: Err ( DeBinErr { o : * o , l :");
-            tb.unsuf_usize(1).add(", s : d . len ( ) , msg : ").string(&name).add(". to_string ( ) } )");
+            tb.unsuf_usize(0).add(", s : d . len ( ) , msg : format ! (").string(&format!("unknown variant {{}} of {}", name)).add(", id ) } )");
             tb.add("} } } ;");
             return tb.end();
         }
@@ -1,16 +1,109 @@
 use proc_macro::TokenStream;
 use makepad_micro_proc_macro::*;
+use crate::attrs::*;
+
+// json keys lose a leading _ so fields like _type can map to reserved words
+fn json_name(container: &ContainerAttrs, field: &StructField, attrs: &FieldAttrs) -> String {
+    container.field_name(field.name.strip_prefix('_').unwrap_or(&field.name), attrs)
+}
+
+// writes the fields of a named struct or variant, prefix is 'self .' or empty for bound variant fields
+fn ser_json_fields(tb: &mut TokenBuilder, container: &ContainerAttrs, fields: &[(StructField, FieldAttrs)], prefix: &str) {
+    for (field, attrs) in fields {
+        if attrs.skip {
+            continue;
+        }
+        let json_name = json_name(container, field, attrs);
+        if attrs.flatten {
+            tb.add("s . flatten ( d ,").add("&").add(prefix).ident(&field.name).add(") ;");
+        }
+        else if is_option(field) {
+            tb.add("if let Some ( t ) = ").add("&").add(prefix).ident(&field.name).add("{");
+            tb.add("s . field ( d + 1 ,").string(&json_name).add(") ;");
+            tb.add("t . ser_json ( d + 1 , s ) ; s . conl ( ) ; } ;");
+        }
+        else {
+            tb.add("s . field ( d + 1 ,").string(&json_name).add(" ) ;");
+            tb.add(prefix).ident(&field.name).add(". ser_json ( d + 1 , s ) ; s . conl ( ) ;");
+        }
+    }
+}
+
+// the key:value pairs nobody claims are kept for the #[flatten] fields
+fn de_json_capture_key(tb: &mut TokenBuilder) {
+    tb.add("let key = s . as_string ( ) ? ; s . next_colon ( i ) ? ;");
+    tb.add("key . ser_json ( 0 , & mut flat ) ; flat . out . push (").chr(':').add(") ;");
+    tb.add("s . capture_value ( i , & mut flat . out ) ? ; flat . conl ( ) ;");
+}
+
+// reads a json object into a named struct or variant expression like 'Self { .. }'
+fn de_json_fields(tb: &mut TokenBuilder, container: &ContainerAttrs, fields: &[(StructField, FieldAttrs)], path: &str) {
+    let has_flatten = fields.iter().any( | (_, attrs) | attrs.flatten && !attrs.skip);
+    tb.add("{ let skip_unknown = s . skip_unknown ;");
+    tb.add("s . curly_open ( i ) ? ;");
+    if has_flatten {
+        tb.add("let mut flat = SerJsonState { out : String :: new ( ) } ;");
+    }
+    for (field, attrs) in fields {
+        if !attrs.skip && !attrs.flatten {
+            tb.add("let mut").ident(&format!("_{}", field.name)).add("= None ;");
+        }
+    }
+    tb.add("while let Some ( _ ) = s . next_str ( ) {");
+    tb.add("match s . strbuf . as_ref ( ) {");
+    for (field, attrs) in fields {
+        if !attrs.skip && !attrs.flatten {
+            tb.string(&json_name(container, field, attrs)).add("=> { s . next_colon ( i ) ? ;");
+            tb.ident(&format!("_{}", field.name)).add("= Some ( DeJson :: de_json ( s , i ) ? ) ; } ,");
+        }
+    }
+    tb.add("_ => {");
+    if has_flatten {
+        de_json_capture_key(tb);
+    }
+    else {
+        tb.add("if skip_unknown { s . next_colon ( i ) ? ; s . skip_value ( i ) ? ; }");
+        tb.add("else { return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) ) }");
+    }
+    tb.add("} } ; s . eat_comma_curly ( i ) ? ;");
+    tb.add("} ; s . curly_close ( i ) ? ;");
+
+    tb.add(path).add("{");
+    for (field, attrs) in fields {
+        tb.ident(&field.name).add(":");
+        if attrs.skip {
+            tb.stream(Some(attrs.default_value())).add(",");
+        }
+        else if attrs.flatten {
+            tb.add("de_json_flattened ( & flat . out ) ? ,");
+        }
+        else {
+            tb.add("if let Some ( t ) =").ident(&format!("_{}", field.name)).add("{ t } else {");
+            if attrs.default.is_some() {
+                tb.stream(Some(attrs.default_value()));
+            }
+            else if is_option(field) {
+                tb.add("None");
+            }
+            else {
+                tb.add("return Err ( s . err_nf (").string(&json_name(container, field, attrs)).add(") )");
+            }
+            tb.add("} ,");
+        }
+    }
+    tb.add("} }");
+}
 
 pub fn derive_ser_json_impl(input: TokenStream) -> TokenStream {
 
     let mut parser = TokenParser::new(input);
     let mut tb = TokenBuilder::new();
-    
-    parser.eat_attributes();
+
+    let container = match ContainerAttrs::parse(&parser.eat_attributes()) {Ok(v) => v, Err(e) => return e};
     parser.eat_ident("pub");
     if parser.eat_ident("struct"){
         if let Some(name) = parser.eat_any_ident(){
-            
+
             let generic = parser.eat_generic();
             let types = parser.eat_all_types();
             let where_clause = parser.eat_where_clause(Some("SerJson"));
@@ -18,7 +111,7 @@ pub fn derive_ser_json_impl(input: TokenStream) -> TokenStream {
             tb.add("impl").stream(generic.clone());
             tb.add("SerJson for").ident(&name).stream(generic).stream(where_clause);
             tb.add("{ fn ser_json ( & self , d : usize , s : & mut SerJsonState ) {");
-            
+
             if let Some(types) = types{
                 tb.add("s . out . push (").chr('[').add(") ;");
                 for i in 0..types.len(){
@@ -30,27 +123,10 @@ pub fn derive_ser_json_impl(input: TokenStream) -> TokenStream {
                 tb.add("s . out . push (").chr(']').add(") ;");
             }
             else if let Some(fields) = parser.eat_all_struct_fields(){
-                let fields_len = fields.len();
-                tb.add("s . st_pre ( ) ;");
+                let fields = match parse_fields(fields) {Ok(v) => v, Err(e) => return e};
                 // named struct
-                for (i, field) in fields.into_iter().enumerate() {
-                    if field.ty.into_iter().next().unwrap().to_string() == "Option"{
-                        tb.add("if let Some ( t ) = ").add("& self .").ident(&field.name).add("{");
-                        tb.add("s . field ( d + 1 ,").string(&field.name).add(") ;");
-                        tb.add("t . ser_json ( d + 1 , s ) ;");
-                        if i != fields_len - 1 {
-                            tb.add("s . conl ( ) ;");
-                        }
-                        tb.add("} ;");
-                    }
-                    else{
-                        tb.add("s . field ( d + 1 ,").string(&field.name).add(" ) ;");
-                        tb.add("self .").ident(&field.name).add(". ser_json ( d + 1 , s ) ;");
-                        if i != fields_len - 1 {
-                            tb.add("s . conl ( ) ;");
-                        }
-                    }
-                }
+                tb.add("s . st_pre ( ) ;");
+                ser_json_fields(&mut tb, &container, &fields, "self .");
                 tb.add("s . st_post ( d ) ;");
             }
             else{
@@ -68,74 +144,109 @@ pub fn derive_ser_json_impl(input: TokenStream) -> TokenStream {
             tb.add("impl").stream(generic.clone());
             tb.add("SerJson for").ident(&name).stream(generic).stream(where_clause);
             tb.add("{ fn ser_json ( & self , d : usize , s : & mut SerJsonState ) {");
-            tb.add("s . out . push (").chr('{').add(") ;");
             tb.add("match self {");
-            
+
             if !parser.open_brace(){
                 return parser.unexpected()
             }
 
             while !parser.eat_eot(){
                 // parse ident
-                parser.eat_attributes();
+                let attrs = match FieldAttrs::parse(&parser.eat_attributes()) {Ok(v) => v, Err(e) => return e};
                 if let Some(variant) = parser.eat_any_ident(){
+                    let label = container.variant_name(&variant, &attrs);
                     if let Some(types) = parser.eat_all_types(){
-                        
+
                         tb.add("Self ::").ident(&variant).add("(");
                         for i in 0..types.len(){
                             tb.ident(&format!("n{}", i)).add(",");
                         }
                         tb.add(") => {");
-                        tb.add("s . label (").string(&variant).add(") ;");
-                        tb.add("s . out . push (").chr(':').add(") ;");
-                        tb.add("s . out . push (").chr('[').add(") ;");
-                        
-                        for i in 0..types.len(){
-                            tb.ident(&format!("n{}", i)).add(". ser_json ( d , s ) ;");
-                            if i != types.len() - 1{
-                                tb.add("s . out . push (").chr(',').add(") ;");
+                        match &container.tagging {
+                            EnumTagging::Internal(tag) => {
+                                if types.len() != 1 {
+                                    return error("an internally tagged enum can only have tuple variants with one field")
+                                }
+                                tb.add("s . st_pre ( ) ; s . field ( d + 1 ,").string(tag).add(") ;");
+                                tb.add("s . label (").string(&label).add(") ; s . conl ( ) ;");
+                                tb.add("s . flatten ( d , n0 ) ; s . st_post ( d ) ;");
+                            }
+                            EnumTagging::Untagged if types.len() == 1 => {
+                                tb.add("n0 . ser_json ( d , s ) ;");
+                            }
+                            tagging => {
+                                let external = matches!(tagging, EnumTagging::External);
+                                if external {
+                                    tb.add("s . out . push (").chr('{').add(") ;");
+                                    tb.add("s . label (").string(&label).add(") ;");
+                                    tb.add("s . out . push (").chr(':').add(") ;");
+                                }
+                                tb.add("s . out . push (").chr('[').add(") ;");
+                                for i in 0..types.len(){
+                                    tb.ident(&format!("n{}", i)).add(". ser_json ( d , s ) ;");
+                                    if i != types.len() - 1{
+                                        tb.add("s . out . push (").chr(',').add(") ;");
+                                    }
+                                }
+                                tb.add("s . out . push (").chr(']').add(") ;");
+                                if external {
+                                    tb.add("s . out . push (").chr('}').add(") ;");
+                                }
                             }
                         }
-                        tb.add("s . out . push (").chr(']').add(") ;");
                         tb.add("}");
                     }
                     else if let Some(fields) = parser.eat_all_struct_fields(){ // named variant
+                        let fields = match parse_fields(fields) {Ok(v) => v, Err(e) => return e};
                         tb.add("Self ::").ident(&variant).add("{");
-                        for field in fields.iter(){
-                            tb.ident(&field.name).add(",");
+                        for (field, attrs) in fields.iter(){
+                            if !attrs.skip {
+                                tb.ident(&field.name).add(",");
+                            }
                         }
-                        tb.add("} => {");
-                        
-                        tb.add("s . label (").string(&variant).add(") ;");
-                        tb.add("s . out . push (").chr(':').add(") ;");
-                        tb.add("s . st_pre ( ) ;");
-
-                        let fields_len = fields.len();
-                        for (i, field) in fields.into_iter().enumerate() {
-                            let field_strip = if let Some(v) = field.name.strip_prefix("_"){v}else{&field.name};
-                            if field.ty.into_iter().next().unwrap().to_string() == "Option"{
-                                tb.add("if let Some ( t ) = ").ident(&field.name).add("{");
-                                tb.add("s . field ( d + 1 ,").string(&field_strip).add(") ;");
-                                tb.add("t . ser_json ( d + 1 , s ) ;");
-                                if i != fields_len - 1 {
-                                    tb.add("s . conl ( ) ;");
-                                }
-                                tb.add("} ;");
+                        tb.add(".. } => {");
+                        match &container.tagging {
+                            EnumTagging::External => {
+                                tb.add("s . out . push (").chr('{').add(") ;");
+                                tb.add("s . label (").string(&label).add(") ;");
+                                tb.add("s . out . push (").chr(':').add(") ;");
+                                tb.add("s . st_pre ( ) ;");
+                                ser_json_fields(&mut tb, &container, &fields, "");
+                                tb.add("s . st_post ( d ) ;");
+                                tb.add("s . out . push (").chr('}').add(") ;");
                             }
-                            else{
-                                tb.add("s . field ( d + 1 ,").string(&field_strip).add(" ) ;");
-                                tb.ident(&field.name).add(". ser_json ( d + 1 , s ) ;");
-                                if i != fields_len - 1 {
-                                    tb.add("s . conl ( ) ;");
-                                }
+                            EnumTagging::Internal(tag) => {
+                                tb.add("s . st_pre ( ) ; s . field ( d + 1 ,").string(tag).add(") ;");
+                                tb.add("s . label (").string(&label).add(") ; s . conl ( ) ;");
+                                ser_json_fields(&mut tb, &container, &fields, "");
+                                tb.add("s . st_post ( d ) ;");
+                            }
+                            EnumTagging::Untagged => {
+                                tb.add("s . st_pre ( ) ;");
+                                ser_json_fields(&mut tb, &container, &fields, "");
+                                tb.add("s . st_post ( d ) ;");
                             }
                         }
-                        tb.add("s . st_post ( d ) ; }");
+                        tb.add("}");
                     }
                     else if parser.is_punct_alone(',') || parser.is_eot(){ // bare variant
                         tb.add("Self ::").ident(&variant).add("=> {");
-                        tb.add("s . label (").string(&variant).add(") ;");
-                        tb.add("s . out . push_str (").string(":[]").add(") ; }");
+                        match &container.tagging {
+                            EnumTagging::External => {
+                                tb.add("s . out . push (").chr('{').add(") ;");
+                                tb.add("s . label (").string(&label).add(") ;");
+                                tb.add("s . out . push_str (").string(":[]").add(") ;");
+                                tb.add("s . out . push (").chr('}').add(") ;");
+                            }
+                            EnumTagging::Internal(tag) => {
+                                tb.add("s . st_pre ( ) ; s . field ( d + 1 ,").string(tag).add(") ;");
+                                tb.add("s . label (").string(&label).add(") ; s . st_post ( d ) ;");
+                            }
+                            EnumTagging::Untagged => {
+                                tb.add("s . out . push_str (").string("null").add(") ;");
+                            }
+                        }
+                        tb.add("}");
                     }
                     else{
                         return parser.unexpected();
@@ -147,7 +258,6 @@ pub fn derive_ser_json_impl(input: TokenStream) -> TokenStream {
                 }
             }
             tb.add("}");
-            tb.add("s . out . push (").chr('}').add(") ;");
             tb.add("} } ;");
             return tb.end();
         }
@@ -158,8 +268,8 @@ pub fn derive_ser_json_impl(input: TokenStream) -> TokenStream {
 pub fn derive_de_json_impl(input: TokenStream) -> TokenStream {
     let mut parser = TokenParser::new(input);
     let mut tb = TokenBuilder::new();
-    
-    parser.eat_attributes();
+
+    let container = match ContainerAttrs::parse(&parser.eat_attributes()) {Ok(v) => v, Err(e) => return e};
     parser.eat_ident("pub");
     if parser.eat_ident("struct"){
         if let Some(name) = parser.eat_any_ident(){
@@ -183,46 +293,21 @@ pub fn derive_de_json_impl(input: TokenStream) -> TokenStream {
                 tb.add("s . block_close ( i ) ? ;");
                 tb.add("std :: result :: Result :: Ok ( r )");
             }
-            else if let Some(fields) = parser.eat_all_struct_fields(){ 
-                tb.add("s . curly_open ( i ) ? ;");
-                for field in &fields{
-                    tb.add("let mut").ident(&format!("_{}",field.name)).add("= None ;");
-                }
-                tb.add("while let Some ( _ ) = s . next_str ( ) {");
-                tb.add("match s . strbuf . as_ref ( ) {");
-                for field in &fields{
-                    let field_strip = if let Some(v) = field.name.strip_prefix("_"){v}else{&field.name};
-                    tb.string(&field_strip).add("=> { s . next_colon ( i ) ? ;");
-                    tb.ident(&format!("_{}",field.name)).add("= Some (DeJson :: de_json ( s , i ) ? ) ; } ,");
-                }
-                tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) )");
-                tb.add("} ; s . eat_comma_curly ( i ) ? ;");
-                tb.add("} ; s . curly_close ( i ) ? ;");
-                
-                tb.add("std :: result :: Result :: Ok ( Self {");
-                for field in fields{
-                    tb.ident(&field.name).add(":");
-                    if field.ty.into_iter().next().unwrap().to_string() == "Option"{
-                        tb.add("if let Some ( t ) =").ident(&format!("_{}",field.name));
-                        tb.add("{ t } else { None } ,");
-                    }
-                    else{
-                        tb.add("if let Some ( t ) =").ident(&format!("_{}",field.name));
-                        tb.add("{ t } else { return Err ( s . err_nf (");
-                        tb.string(&field.name).add(") ) } ,");
-                    }
-                }
-                tb.add("} )");
+            else if let Some(fields) = parser.eat_all_struct_fields(){
+                let fields = match parse_fields(fields) {Ok(v) => v, Err(e) => return e};
+                tb.add("std :: result :: Result :: Ok (");
+                de_json_fields(&mut tb, &container, &fields, "Self");
+                tb.add(")");
             }
             else{
                 return parser.unexpected()
             }
-            tb.add("} } ;"); 
+            tb.add("} } ;");
             return tb.end();
         }
     }
     else if parser.eat_ident("enum"){
-        
+
         if let Some(name) = parser.eat_any_ident(){
             let generic = parser.eat_generic();
             let where_clause = parser.eat_where_clause(Some("DeJson"));
@@ -231,77 +316,114 @@ pub fn derive_de_json_impl(input: TokenStream) -> TokenStream {
             tb.add("DeJson for").ident(&name).stream(generic).stream(where_clause);
             tb.add("{ fn de_json ( s : & mut  DeJsonState , i : & mut std :: str :: Chars )");
             tb.add("-> std :: result :: Result < Self , DeJsonErr > { ");
-            tb.add("s . curly_open ( i ) ? ;");
-            tb.add("let _ = s . string ( i ) ? ;");
-            tb.add("s . colon ( i ) ? ;");
-            tb.add("let r = std :: result :: Result :: Ok ( match s . strbuf . as_ref ( ) {");
-            
+
             if !parser.open_brace(){
                 return parser.unexpected()
             }
+            // the label and the expression that reads each variant
+            let mut variants = Vec::new();
             while !parser.eat_eot(){
                 // parse ident
-                parser.eat_attributes();
+                let attrs = match FieldAttrs::parse(&parser.eat_attributes()) {Ok(v) => v, Err(e) => return e};
                 if let Some(variant) = parser.eat_any_ident(){
-                    tb.string(&variant).add("=> {");
+                    let label = container.variant_name(&variant, &attrs);
+                    let mut vb = TokenBuilder::new();
                     if let Some(types) = parser.eat_all_types(){
-                        
-                        tb.add("s . block_open ( i ) ? ;");
-                        tb.add("let r = Self ::").ident(&variant).add("(");
-                        for _ in 0..types.len(){
-                            tb.add("{ let r = DeJson :: de_json ( s , i ) ? ; s . eat_comma_block ( i ) ? ; r } ,");
-                        }
-                        tb.add(") ;");
-                        tb.add("s . block_close ( i ) ? ; r");
-                    }
-                    else if let Some(fields) = parser.eat_all_struct_fields(){ // named variant
-                        tb.add("s . curly_open ( i ) ? ;");
-                        for field in &fields{
-                            tb.add("let mut").ident(&format!("_{}",field.name)).add("= None ;");
-                        }
-                        tb.add("while let Some ( _ ) = s . next_str ( ) {");
-                        tb.add("match s . strbuf . as_ref ( ) {");
-                        for field in &fields{
-                            tb.string(&field.name).add("=> { s . next_colon ( i ) ? ;");
-                            tb.ident(&format!("_{}",field.name)).add("= Some (DeJson :: de_json ( s , i ) ? ) ; } ,");
+                        if types.len() == 1 && !matches!(container.tagging, EnumTagging::External) {
+                            vb.add("Self ::").ident(&variant).add("( DeJson :: de_json ( s , i ) ? )");
                         }
-                        tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) )");
-                        tb.add("} s . eat_comma_curly ( i ) ? ;");
-                        tb.add("} s . curly_close ( i ) ? ;");
-                        
-                        tb.add("Self ::").ident(&variant).add("{");
-                        for field in fields{
-                            tb.ident(&field.name).add(":");
-                            if field.ty.into_iter().next().unwrap().to_string() == "Option"{
-                                tb.add("if let Some ( t ) =").ident(&format!("_{}",field.name));
-                                tb.add("{ t } else { None } ,");
+                        else {
+                            if let EnumTagging::Internal(_) = container.tagging {
+                                return error("an internally tagged enum can only have tuple variants with one field")
                             }
-                            else{
-                                tb.add("if let Some ( t ) =").ident(&format!("_{}",field.name));
-                                tb.add("{ t } else { return Err ( s . err_nf (");
-                                tb.string(&field.name).add(") ) } ,");
+                            vb.add("{ s . block_open ( i ) ? ;");
+                            vb.add("let r = Self ::").ident(&variant).add("(");
+                            for _ in 0..types.len(){
+                                vb.add("{ let r = DeJson :: de_json ( s , i ) ? ; s . eat_comma_block ( i ) ? ; r } ,");
                             }
+                            vb.add(") ;");
+                            vb.add("s . block_close ( i ) ? ; r }");
                         }
-                        tb.add("}");
+                    }
+                    else if let Some(fields) = parser.eat_all_struct_fields(){ // named variant
+                        let fields = match parse_fields(fields) {Ok(v) => v, Err(e) => return e};
+                        de_json_fields(&mut vb, &container, &fields, &format!("Self :: {}", variant));
                     }
                     else if parser.is_punct_alone(',') || parser.is_eot(){ // bare variant
-                        tb.add("s . block_open ( i ) ? ; s . block_close ( i ) ? ; Self ::").ident(&variant);
+                        match container.tagging {
+                            EnumTagging::External => {
+                                vb.add("{ s . block_open ( i ) ? ; s . block_close ( i ) ? ; Self ::").ident(&variant).add("}");
+                            }
+                            EnumTagging::Internal(_) => {
+                                vb.add("Self ::").ident(&variant);
+                            }
+                            EnumTagging::Untagged => {
+                                vb.add("{ if s . tok != DeJsonTok :: Null { return Err ( s . err_token (").string("null").add(") ) }");
+                                vb.add("s . next_tok ( i ) ? ; Self ::").ident(&variant).add("}");
+                            }
+                        }
                     }
                     else{
                         return parser.unexpected();
                     }
-                    
-                    tb.add("}");
+                    variants.push((label, vb.end()));
                     parser.eat_punct_alone(',');
                 }
                 else{
                     return parser.unexpected()
                 }
             }
-            tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) )");
-            tb.add("} ) ; s . curly_close ( i ) ? ; r } }");
+            match &container.tagging {
+                EnumTagging::External => {
+                    tb.add("s . curly_open ( i ) ? ;");
+                    tb.add("let _ = s . string ( i ) ? ;");
+                    tb.add("s . colon ( i ) ? ;");
+                    tb.add("let r = std :: result :: Result :: Ok ( match s . strbuf . as_ref ( ) {");
+                    for (label, expr) in variants {
+                        tb.string(&label).add("=>").stream(Some(expr)).add(",");
+                    }
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) )");
+                    tb.add("} ) ; s . curly_close ( i ) ? ; r");
+                }
+                EnumTagging::Internal(tag) => {
+                    // collect everything but the tag, then read the variant from the rest
+                    tb.add("s . curly_open ( i ) ? ;");
+                    tb.add("let mut tag : Option < String > = None ;");
+                    tb.add("let mut flat = SerJsonState { out : String :: new ( ) } ;");
+                    tb.add("while let Some ( _ ) = s . next_str ( ) {");
+                    tb.add("if s . strbuf ==").string(tag).add("{ s . next_colon ( i ) ? ; tag = Some ( DeJson :: de_json ( s , i ) ? ) ; }");
+                    tb.add("else {");
+                    de_json_capture_key(&mut tb);
+                    tb.add("} s . eat_comma_curly ( i ) ? ;");
+                    tb.add("} s . curly_close ( i ) ? ;");
+                    tb.add("let tag = if let Some ( tag ) = tag { tag } else { return Err ( s . err_nf (").string(tag).add(") ) } ;");
+                    tb.add("let src = format ! (").string("{{{}}}").add(", flat . out ) ;");
+                    tb.add("let mut fs = DeJsonState :: default ( ) ; let mut fi = src . chars ( ) ;");
+                    tb.add("fs . next ( & mut fi ) ; fs . next_tok ( & mut fi ) ? ;");
+                    tb.add("let ( s , i ) = ( & mut fs , & mut fi ) ;");
+                    tb.add("std :: result :: Result :: Ok ( match tag . as_ref ( ) {");
+                    for (label, expr) in variants {
+                        tb.string(&label).add("=>").stream(Some(expr)).add(",");
+                    }
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_enum ( & tag ) )");
+                    tb.add("} )");
+                }
+                EnumTagging::Untagged => {
+                    // try the variants in order on a copy of the parser
+                    for (_, expr) in variants {
+                        tb.add("{ let mut ts = s . clone ( ) ; let mut ti = i . clone ( ) ;");
+                        tb.add("let r = ( | | -> std :: result :: Result < Self , DeJsonErr > {");
+                        tb.add("let ( s , i ) = ( & mut ts , & mut ti ) ;");
+                        tb.add("std :: result :: Result :: Ok (").stream(Some(expr)).add(") } ) ( ) ;");
+                        tb.add("if let std :: result :: Result :: Ok ( r ) = r { * s = ts ; * i = ti ; return std :: result :: Result :: Ok ( r ) } }");
+                    }
+                    tb.add("std :: result :: Result :: Err ( s . err_msg (").string(&format!("Data did not match any variant of {}", name)).add(") )");
+                }
+            }
+            tb.add("} } ;");
             return tb.end();
         }
     }
     parser.unexpected()
 }
+
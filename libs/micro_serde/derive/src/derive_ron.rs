@@ -1,11 +1,129 @@
+use crate::attrs::*;
 use makepad_micro_proc_macro::*;
 use proc_macro::TokenStream;
 
+// writes the fields of a named struct or variant, prefix is 'self .' or empty for bound variant fields
+fn ser_ron_fields(
+    tb: &mut TokenBuilder,
+    container: &ContainerAttrs,
+    fields: &[(StructField, FieldAttrs)],
+    prefix: &str,
+) {
+    for (field, attrs) in fields {
+        if attrs.skip {
+            continue;
+        }
+        let ron_name = container.field_name(&field.name, attrs);
+        if attrs.flatten {
+            tb.add("s . flatten ( d , &")
+                .add(prefix)
+                .ident(&field.name)
+                .add(") ;");
+        } else if is_option(field) {
+            tb.add("if let Some ( t ) = &")
+                .add(prefix)
+                .ident(&field.name)
+                .add("{");
+            tb.add("s . field ( d + 1 ,").string(&ron_name).add(") ;");
+            tb.add("t . ser_ron ( d + 1 , s ) ; s . conl ( ) ; } ;");
+        } else {
+            tb.add("s . field ( d + 1 ,").string(&ron_name).add(" ) ;");
+            tb.add(prefix)
+                .ident(&field.name)
+                .add(". ser_ron ( d + 1 , s ) ; s . conl ( ) ;");
+        }
+    }
+}
+
+// writes the tag field of an internally tagged variant
+fn ser_ron_tag(tb: &mut TokenBuilder, tag: &str, label: &str) {
+    tb.add("s . field ( d + 1 ,").string(tag).add(") ;");
+    tb.add("s . out . push_str (")
+        .string(&format!("\"{}\"", label))
+        .add(") ; s . conl ( ) ;");
+}
+
+// the key:value pairs nobody claims are kept for the #[flatten] fields
+fn de_ron_capture_key(tb: &mut TokenBuilder) {
+    tb.add("flat . out . push_str ( & s . identbuf ) ; s . next_colon ( i ) ? ;");
+    tb.add("flat . out . push (").chr(':').add(") ;");
+    tb.add("s . capture_value ( i , & mut flat . out ) ? ; flat . conl ( ) ;");
+}
+
+// reads a ron struct into a named struct or variant expression like 'Self { .. }'
+fn de_ron_fields(
+    tb: &mut TokenBuilder,
+    container: &ContainerAttrs,
+    fields: &[(StructField, FieldAttrs)],
+    path: &str,
+) {
+    let has_flatten = fields.iter().any(|(_, attrs)| attrs.flatten && !attrs.skip);
+    tb.add("{ let skip_unknown = s . skip_unknown ;");
+    tb.add("s . paren_open ( i ) ? ;");
+    if has_flatten {
+        tb.add("let mut flat = SerRonState { out : String :: new ( ) } ;");
+    }
+    for (field, attrs) in fields {
+        if !attrs.skip && !attrs.flatten {
+            tb.add("let mut")
+                .ident(&format!("_{}", field.name))
+                .add("= None ;");
+        }
+    }
+    tb.add("while let Some ( _ ) = s . next_ident ( ) {");
+    tb.add("match s . identbuf . as_ref ( ) {");
+    for (field, attrs) in fields {
+        if !attrs.skip && !attrs.flatten {
+            tb.string(&container.field_name(&field.name, attrs))
+                .add("=> { s . next_colon ( i ) ? ;");
+            tb.ident(&format!("_{}", field.name))
+                .add("= Some ( DeRon :: de_ron ( s , i ) ? ) ; } ,");
+        }
+    }
+    tb.add("_ => {");
+    if has_flatten {
+        de_ron_capture_key(tb);
+    } else {
+        tb.add("if skip_unknown { s . next_colon ( i ) ? ; s . skip_value ( i ) ? ; }");
+        tb.add("else { return std :: result :: Result :: Err ( s . err_exp ( & s . identbuf ) ) }");
+    }
+    tb.add("} } ; s . eat_comma_paren ( i ) ? ;");
+    tb.add("} ; s . paren_close ( i ) ? ;");
+
+    tb.add(path).add("{");
+    for (field, attrs) in fields {
+        tb.ident(&field.name).add(":");
+        if attrs.skip {
+            tb.stream(Some(attrs.default_value())).add(",");
+        } else if attrs.flatten {
+            tb.add("de_ron_flattened ( & flat . out ) ? ,");
+        } else {
+            tb.add("if let Some ( t ) =")
+                .ident(&format!("_{}", field.name))
+                .add("{ t } else {");
+            if attrs.default.is_some() {
+                tb.stream(Some(attrs.default_value()));
+            } else if is_option(field) {
+                tb.add("None");
+            } else {
+                tb.add("return Err ( s . err_nf (")
+                    .string(&container.field_name(&field.name, attrs))
+                    .add(") )");
+            }
+            tb.add("} ,");
+        }
+    }
+    tb.add("} }");
+}
+
 pub fn derive_ser_ron_impl(input: TokenStream) -> TokenStream {
     let mut parser = TokenParser::new(input);
     let mut tb = TokenBuilder::new();
 
-    parser.eat_attributes();
+    let container = match ContainerAttrs::parse(&parser.eat_attributes()) {
+        Ok(v) => v,
+        Err(e) => return e,
+    };
     parser.eat_ident("pub");
     if parser.eat_ident("struct") {
         if let Some(name) = parser.eat_any_ident() {
@@ -31,26 +149,13 @@ pub fn derive_ser_ron_impl(input: TokenStream) -> TokenStream {
                 }
                 tb.add("s.out.push(").chr(')').add(");");
             } else if let Some(fields) = parser.eat_all_struct_fields() {
-                tb.add("s.st_pre( ) ;");
-
+                let fields = match parse_fields(fields) {
+                    Ok(v) => v,
+                    Err(e) => return e,
+                };
                 // named struct
-                for field in fields {
-                    if field.ty.into_iter().next().unwrap().to_string() == "Option" {
-                        tb.add("if let Some ( t ) = ")
-                            .add("& self .")
-                            .ident(&field.name)
-                            .add("{");
-                        tb.add("s.field ( d + 1 ,").string(&field.name).add(") ;");
-                        tb.add("t.ser_ron ( d + 1 , s ) ; s . conl ( ) ; } ;");
-                    } else {
-                        tb.add("s . field ( d + 1 ,")
-                            .string(&field.name)
-                            .add(" ) ;");
-                        tb.add("self .")
-                            .ident(&field.name)
-                            .add(". ser_ron ( d + 1 , s ) ; s . conl ( ) ;");
-                    }
-                }
+                tb.add("s.st_pre( ) ;");
+                ser_ron_fields(&mut tb, &container, &fields, "self .");
                 tb.add("s . st_post ( d ) ;");
             } else {
                 return parser.unexpected();
@@ -77,55 +182,90 @@ pub fn derive_ser_ron_impl(input: TokenStream) -> TokenStream {
 
             while !parser.eat_eot() {
                 // parse ident
-                parser.eat_attributes();
+                let attrs = match FieldAttrs::parse(&parser.eat_attributes()) {
+                    Ok(v) => v,
+                    Err(e) => return e,
+                };
                 if let Some(variant) = parser.eat_any_ident() {
+                    let label = container.variant_name(&variant, &attrs);
                     if let Some(types) = parser.eat_all_types() {
                         tb.add("Self ::").ident(&variant).add("(");
                         for i in 0..types.len() {
                             tb.ident(&format!("n{}", i)).add(",");
                         }
                         tb.add(") => {");
-                        tb.add("s . out . push_str (").string(&variant).add(") ;");
-                        tb.add("s . out . push (").chr('(').add(") ;");
-
-                        for i in 0..types.len() {
-                            tb.ident(&format!("n{}", i)).add(". ser_ron ( d , s ) ;");
-                            if i != types.len() - 1 {
-                                tb.add("s . out . push_str (").string(", ").add(") ;");
+                        match &container.tagging {
+                            EnumTagging::Internal(tag) => {
+                                if types.len() != 1 {
+                                    return error("an internally tagged enum can only have tuple variants with one field");
+                                }
+                                tb.add("s . st_pre ( ) ;");
+                                ser_ron_tag(&mut tb, tag, &label);
+                                tb.add("s . flatten ( d , n0 ) ; s . st_post ( d ) ;");
+                            }
+                            EnumTagging::Untagged if types.len() == 1 => {
+                                tb.add("n0 . ser_ron ( d , s ) ;");
+                            }
+                            tagging => {
+                                if let EnumTagging::External = tagging {
+                                    tb.add("s . out . push_str (").string(&label).add(") ;");
+                                }
+                                tb.add("s . out . push (").chr('(').add(") ;");
+                                for i in 0..types.len() {
+                                    tb.ident(&format!("n{}", i)).add(". ser_ron ( d , s ) ;");
+                                    if i != types.len() - 1 {
+                                        tb.add("s . out . push_str (").string(", ").add(") ;");
+                                    }
+                                }
+                                tb.add("s . out . push (").chr(')').add(") ;");
                             }
                         }
-                        tb.add("s . out . push (").chr(')').add(") ;");
                         tb.add("}");
                     } else if let Some(fields) = parser.eat_all_struct_fields() {
                         // named variant
+                        let fields = match parse_fields(fields) {
+                            Ok(v) => v,
+                            Err(e) => return e,
+                        };
                         tb.add("Self ::").ident(&variant).add("{");
-                        for field in fields.iter() {
-                            tb.ident(&field.name).add(",");
+                        for (field, attrs) in fields.iter() {
+                            if !attrs.skip {
+                                tb.ident(&field.name).add(",");
+                            }
                         }
-                        tb.add("} => {");
-
-                        tb.add("s . out . push_str (").string(&variant).add(") ;");
-                        tb.add("s . st_pre ( ) ;");
-
-                        for field in fields {
-                            if field.ty.into_iter().next().unwrap().to_string() == "Option" {
-                                tb.add("if ").ident(&field.name).add(". is_some ( ) {");
-                                tb.add("s . field ( d + 1 ,").string(&field.name).add(") ;");
-                                tb.ident(&field.name)
-                                    .add(" . ser_ron ( d + 1 , s ) ; s . conl ( ) ; } ;");
-                            } else {
-                                tb.add("s . field ( d + 1 ,")
-                                    .string(&field.name)
-                                    .add(" ) ;");
-                                tb.ident(&field.name)
-                                    .add(". ser_ron ( d + 1 , s ) ; s . conl ( ) ;");
+                        tb.add(".. } => {");
+                        match &container.tagging {
+                            EnumTagging::External => {
+                                tb.add("s . out . push_str (").string(&label).add(") ;");
+                                tb.add("s . st_pre ( ) ;");
+                            }
+                            EnumTagging::Internal(tag) => {
+                                tb.add("s . st_pre ( ) ;");
+                                ser_ron_tag(&mut tb, tag, &label);
+                            }
+                            EnumTagging::Untagged => {
+                                tb.add("s . st_pre ( ) ;");
                             }
                         }
+                        ser_ron_fields(&mut tb, &container, &fields, "");
                         tb.add("s . st_post ( d ) ; }");
                     } else if parser.is_punct_alone(',') || parser.is_eot() {
                         // bare variant
                         tb.add("Self ::").ident(&variant).add("=> {");
-                        tb.add("s . out . push_str (").string(&variant).add(") ; }");
+                        match &container.tagging {
+                            EnumTagging::External => {
+                                tb.add("s . out . push_str (").string(&label).add(") ;");
+                            }
+                            EnumTagging::Internal(tag) => {
+                                tb.add("s . st_pre ( ) ;");
+                                ser_ron_tag(&mut tb, tag, &label);
+                                tb.add("s . st_post ( d ) ;");
+                            }
+                            EnumTagging::Untagged => {
+                                tb.add("s . out . push_str (").string("()").add(") ;");
+                            }
+                        }
+                        tb.add("}");
                     } else {
                         return parser.unexpected();
                     }
@@ -145,7 +285,11 @@ pub fn derive_ser_ron_impl(input: TokenStream) -> TokenStream {
 pub fn derive_de_ron_impl(input: TokenStream) -> TokenStream {
     let mut parser = TokenParser::new(input);
     let mut tb = TokenBuilder::new();
-    parser.eat_attributes();
+
+    let container = match ContainerAttrs::parse(&parser.eat_attributes()) {
+        Ok(v) => v,
+        Err(e) => return e,
+    };
     parser.eat_ident("pub");
     if parser.eat_ident("struct") {
         if let Some(name) = parser.eat_any_ident() {
@@ -172,40 +316,13 @@ pub fn derive_de_ron_impl(input: TokenStream) -> TokenStream {
                 tb.add("s . paren_close ( i ) ? ;");
                 tb.add("std :: result :: Result :: Ok ( r ) ");
             } else if let Some(fields) = parser.eat_all_struct_fields() {
-                tb.add("s . paren_open ( i ) ? ;");
-                for field in &fields {
-                    tb.add("let mut")
-                        .ident(&format!("_{}", field.name))
-                        .add("= None ;");
-                }
-                tb.add("while let Some ( _ ) = s . next_ident ( ) {");
-                tb.add("match s . identbuf . as_ref ( ) {");
-                for field in &fields {
-                    tb.string(&field.name).add("=> { s . next_colon ( i ) ? ;");
-                    tb.ident(&format!("_{}", field.name))
-                        .add("= Some ( DeRon :: de_ron ( s , i ) ? ) ; } ,");
-                }
-                tb.add(
-                    "_ => return std :: result :: Result :: Err ( s . err_exp ( & s . identbuf ) )",
-                );
-                tb.add("} ; s . eat_comma_paren ( i ) ? ;");
-                tb.add("} ; s . paren_close ( i ) ? ;");
-
-                tb.add("std :: result :: Result :: Ok ( Self {");
-                for field in fields {
-                    tb.ident(&field.name).add(":");
-                    if field.ty.into_iter().next().unwrap().to_string() == "Option" {
-                        tb.add("if let Some ( t ) =")
-                            .ident(&format!("_{}", field.name));
-                        tb.add("{ t } else { None } ,");
-                    } else {
-                        tb.add("if let Some ( t ) =")
-                            .ident(&format!("_{}", field.name));
-                        tb.add("{ t } else { return Err ( s . err_nf (");
-                        tb.string(&field.name).add(") ) } ,");
-                    }
-                }
-                tb.add("} )");
+                let fields = match parse_fields(fields) {
+                    Ok(v) => v,
+                    Err(e) => return e,
+                };
+                tb.add("std :: result :: Result :: Ok (");
+                de_ron_fields(&mut tb, &container, &fields, "Self");
+                tb.add(")");
             } else {
                 return parser.unexpected();
             }
@@ -224,76 +341,117 @@ pub fn derive_de_ron_impl(input: TokenStream) -> TokenStream {
                 .stream(where_clause);
             tb.add("{ fn de_ron ( s : & mut  DeRonState , i : & mut std :: str :: Chars )");
             tb.add("-> std :: result :: Result < Self , DeRonErr > { ");
-            tb.add("s . ident ( i ) ? ;");
-            tb.add("std :: result :: Result :: Ok ( match s . identbuf . as_ref ( ) {");
 
             if !parser.open_brace() {
                 return parser.unexpected();
             }
+            // the label and the expression that reads each variant
+            let mut variants = Vec::new();
             while !parser.eat_eot() {
                 // parse ident
-                parser.eat_attributes();
+                let attrs = match FieldAttrs::parse(&parser.eat_attributes()) {
+                    Ok(v) => v,
+                    Err(e) => return e,
+                };
                 if let Some(variant) = parser.eat_any_ident() {
-                    tb.string(&variant).add("=> {");
+                    let label = container.variant_name(&variant, &attrs);
+                    let mut vb = TokenBuilder::new();
                     if let Some(types) = parser.eat_all_types() {
-                        tb.add("s . paren_open ( i ) ? ;");
-                        tb.add("let r = Self ::").ident(&variant).add("(");
-                        for _ in 0..types.len() {
-                            tb.add("{ let r = DeRon :: de_ron ( s , i ) ? ; s . eat_comma_paren ( i ) ? ; r } ,");
+                        if types.len() == 1 && !matches!(container.tagging, EnumTagging::External) {
+                            vb.add("Self ::")
+                                .ident(&variant)
+                                .add("( DeRon :: de_ron ( s , i ) ? )");
+                        } else {
+                            if let EnumTagging::Internal(_) = container.tagging {
+                                return error("an internally tagged enum can only have tuple variants with one field");
+                            }
+                            vb.add("{ s . paren_open ( i ) ? ;");
+                            vb.add("let r = Self ::").ident(&variant).add("(");
+                            for _ in 0..types.len() {
+                                vb.add("{ let r = DeRon :: de_ron ( s , i ) ? ; s . eat_comma_paren ( i ) ? ; r } ,");
+                            }
+                            vb.add(") ;");
+                            vb.add("s . paren_close ( i ) ? ; r }");
                         }
-                        tb.add(") ;");
-                        tb.add("s . paren_close ( i ) ? ; r");
                     } else if let Some(fields) = parser.eat_all_struct_fields() {
                         // named variant
-                        tb.add("s . paren_open ( i ) ? ;");
-                        for field in &fields {
-                            tb.add("let mut")
-                                .ident(&format!("_{}", field.name))
-                                .add("= None ;");
-                        }
-                        tb.add("while let Some ( _ ) = s . next_ident ( ) {");
-                        tb.add("match s . identbuf . as_ref ( ) {");
-                        for field in &fields {
-                            tb.string(&field.name).add("=> { s . next_colon ( i ) ? ;");
-                            tb.ident(&format!("_{}", field.name))
-                                .add("= Some ( DeRon :: de_ron ( s , i ) ? ) ; } ,");
-                        }
-                        tb.add("_ => return std :: result :: Result :: Err ( s . err_exp ( & s . strbuf ) )");
-                        tb.add("} ; s . eat_comma_paren ( i ) ? ;");
-                        tb.add("} ; s . paren_close ( i ) ? ;");
-
-                        tb.add("Self ::").ident(&variant).add("{");
-                        for field in fields {
-                            tb.ident(&field.name).add(":");
-                            if field.ty.into_iter().next().unwrap().to_string() == "Option" {
-                                tb.add("if let Some ( t ) =")
-                                    .ident(&format!("_{}", field.name));
-                                tb.add("{ t } else { None } ,");
-                            } else {
-                                tb.add("if let Some ( t ) =")
-                                    .ident(&format!("_{}", field.name));
-                                tb.add("{ t } else { return Err ( s . err_nf (");
-                                tb.string(&field.name).add(") ) } ,");
-                            }
-                        }
-                        tb.add("}");
+                        let fields = match parse_fields(fields) {
+                            Ok(v) => v,
+                            Err(e) => return e,
+                        };
+                        de_ron_fields(&mut vb, &container, &fields, &format!("Self :: {}", variant));
                     } else if parser.is_punct_alone(',') || parser.is_eot() {
                         // bare variant
-                        tb.add("Self ::").ident(&variant);
+                        if let EnumTagging::Untagged = container.tagging {
+                            vb.add("{ s . paren_open ( i ) ? ; s . paren_close ( i ) ? ; Self ::")
+                                .ident(&variant)
+                                .add("}");
+                        } else {
+                            vb.add("Self ::").ident(&variant);
+                        }
                     } else {
                         return parser.unexpected();
                     }
-
-                    tb.add("}");
+                    variants.push((label, vb.end()));
                     parser.eat_punct_alone(',');
                 } else {
                     return parser.unexpected();
                 }
             }
-            tb.add(
-                "_ => return std :: result :: Result :: Err ( s . err_enum ( & s . identbuf ) )",
-            );
-            tb.add("} ) } }");
+            match &container.tagging {
+                EnumTagging::External => {
+                    tb.add("s . ident ( i ) ? ;");
+                    tb.add("std :: result :: Result :: Ok ( match s . identbuf . as_ref ( ) {");
+                    for (label, expr) in variants {
+                        tb.string(&label).add("=>").stream(Some(expr)).add(",");
+                    }
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_enum ( & s . identbuf ) )");
+                    tb.add("} )");
+                }
+                EnumTagging::Internal(tag) => {
+                    // collect everything but the tag, then read the variant from the rest
+                    tb.add("s . paren_open ( i ) ? ;");
+                    tb.add("let mut tag : Option < String > = None ;");
+                    tb.add("let mut flat = SerRonState { out : String :: new ( ) } ;");
+                    tb.add("while let Some ( _ ) = s . next_ident ( ) {");
+                    tb.add("if s . identbuf ==").string(tag).add("{ s . next_colon ( i ) ? ;");
+                    tb.add("tag = Some ( if s . tok == DeRonTok :: Ident { let v = s . identbuf . clone ( ) ; s . next_tok ( i ) ? ; v }");
+                    tb.add("else { DeRon :: de_ron ( s , i ) ? } ) ; }");
+                    tb.add("else {");
+                    de_ron_capture_key(&mut tb);
+                    tb.add("} s . eat_comma_paren ( i ) ? ;");
+                    tb.add("} s . paren_close ( i ) ? ;");
+                    tb.add("let tag = if let Some ( tag ) = tag { tag } else { return Err ( s . err_nf (")
+                        .string(tag)
+                        .add(") ) } ;");
+                    tb.add("let src = format ! (").string("({})").add(", flat . out ) ;");
+                    tb.add("let mut fs = DeRonState :: default ( ) ; let mut fi = src . chars ( ) ;");
+                    tb.add("fs . next ( & mut fi ) ; fs . next_tok ( & mut fi ) ? ;");
+                    tb.add("let ( s , i ) = ( & mut fs , & mut fi ) ;");
+                    tb.add("std :: result :: Result :: Ok ( match tag . as_ref ( ) {");
+                    for (label, expr) in variants {
+                        tb.string(&label).add("=>").stream(Some(expr)).add(",");
+                    }
+                    tb.add("_ => return std :: result :: Result :: Err ( s . err_enum ( & tag ) )");
+                    tb.add("} )");
+                }
+                EnumTagging::Untagged => {
+                    // try the variants in order on a copy of the parser
+                    for (_, expr) in variants {
+                        tb.add("{ let mut ts = s . clone ( ) ; let mut ti = i . clone ( ) ;");
+                        tb.add("let r = ( | | -> std :: result :: Result < Self , DeRonErr > {");
+                        tb.add("let ( s , i ) = ( & mut ts , & mut ti ) ;");
+                        tb.add("std :: result :: Result :: Ok (")
+                            .stream(Some(expr))
+                            .add(") } ) ( ) ;");
+                        tb.add("if let std :: result :: Result :: Ok ( r ) = r { * s = ts ; * i = ti ; return std :: result :: Result :: Ok ( r ) } }");
+                    }
+                    tb.add("std :: result :: Result :: Err ( s . err_parse (")
+                        .string(&format!("any variant of {}", name))
+                        .add(") )");
+                }
+            }
+            tb.add("} } ;");
             return tb.end();
         }
     }
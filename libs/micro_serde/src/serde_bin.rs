@@ -19,6 +19,17 @@ pub trait SerBin {
     }
     
     fn ser_bin(&self, s: &mut Vec<u8>);
+    
+    // writes one field of a #[versioned] type as its id, its byte length and its data,
+    // so a reader that doesn't know the id can skip it
+    fn ser_bin_tagged(&self, id: u16, s: &mut Vec<u8>) {
+        id.ser_bin(s);
+        let at = s.len();
+        0u32.ser_bin(s);
+        self.ser_bin(s);
+        let len = (s.len() - at - 4) as u32;
+        s[at..at + 4].copy_from_slice(&len.to_le_bytes());
+    }
 }
 
 pub trait DeBin:Sized {
@@ -37,6 +48,14 @@ pub struct DeBinErr{
     pub s: usize
 }
 
+impl DeBinErr {
+    // names the field that was being read, nested fields read inside out
+    pub fn in_field(mut self, field: &str) -> Self {
+        self.msg = format!("{} in {}", self.msg, field);
+        self
+    }
+}
+
 impl std::fmt::Display for DeBinErr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "Error deserializing {} ", self.msg)?;
@@ -194,6 +213,36 @@ impl<T> DeBin for Vec<T> where T:DeBin{
     }
 }
 
+// a list written with the byte length of every item, so a reader skips the items it can't
+// read, like variants of a #[versioned] enum added by a newer writer, and keeps the rest
+pub fn ser_bin_framed_vec<T: SerBin>(items: &[T], s: &mut Vec<u8>) {
+    (items.len() as u64).ser_bin(s);
+    for item in items {
+        let at = s.len();
+        0u32.ser_bin(s);
+        item.ser_bin(s);
+        let len = (s.len() - at - 4) as u32;
+        s[at..at + 4].copy_from_slice(&len.to_le_bytes());
+    }
+}
+
+pub fn de_bin_framed_vec<T: DeBin>(o: &mut usize, d: &[u8]) -> Result<Vec<T>, DeBinErr> {
+    let len: u64 = DeBin::de_bin(o, d)?;
+    let mut out = Vec::new();
+    for _ in 0..len {
+        let item_len: u32 = DeBin::de_bin(o, d)?;
+        let end = *o + item_len as usize;
+        if end > d.len() {
+            return Err(DeBinErr {o: *o, l: item_len as usize, s: d.len(), msg: "framed item".to_string()})
+        }
+        if let Ok(item) = DeBin::de_bin(&mut o.clone(), &d[..end]) {
+            out.push(item);
+        }
+        *o = end;
+    }
+    Ok(out)
+}
+
 impl<T> SerBin for Option<T> where T: SerBin {
     fn ser_bin(&self, s: &mut Vec<u8>) {
         match self {
@@ -36,10 +36,25 @@ impl SerJsonState {
     }
     
     pub fn st_post(&mut self, d: usize) {
+        // fields all end in a comma so skipped and optional fields don't need bookkeeping
+        if self.out.ends_with(','){
+            self.out.pop();
+        }
         self.indent(d);
         self.out.push('}');
     }
     
+    // writes the fields of value's object into the object being serialized, used by #[flatten]
+    pub fn flatten<T: SerJson + ?Sized>(&mut self, d: usize, value: &T) {
+        let mut inner = SerJsonState {out: String::new()};
+        value.ser_json(d, &mut inner);
+        if let Some(fields) = inner.out.strip_prefix('{').and_then( | v | v.strip_suffix('}')) {
+            if !fields.is_empty() {
+                self.out.push_str(fields);
+                self.conl();
+            }
+        }
+    }
 }
 
 pub trait SerJson {
@@ -70,7 +85,17 @@ pub trait DeJson: Sized {
     DeJsonErr>;
 }
 
-#[derive(PartialEq, Debug)]
+// deserializes a #[flatten] field from the "key":value pairs its parent didn't know
+pub fn de_json_flattened<T: DeJson>(fields: &str) -> Result<T, DeJsonErr> {
+    let mut state = DeJsonState {skip_unknown: true, ..Default::default()};
+    let src = format!("{{{}}}", fields);
+    let mut chars = src.chars();
+    state.next(&mut chars);
+    state.next_tok(&mut chars) ?;
+    DeJson::de_json(&mut state, &mut chars)
+}
+
+#[derive(PartialEq, Debug, Clone)]
 #[derive(Default)]
 pub enum DeJsonTok {
     Str,
@@ -92,7 +117,7 @@ pub enum DeJsonTok {
     Eof
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct DeJsonState {
     pub cur: char,
     pub tok: DeJsonTok,
@@ -100,7 +125,10 @@ pub struct DeJsonState {
     pub numbuf:String,
     pub identbuf:String,
     pub line: usize,
-    pub col: usize
+    pub col: usize,
+    // lets the next derived object ignore keys it doesn't know, set for #[flatten] fields
+    // and cleared by any [ or { so it never reaches nested values
+    pub skip_unknown: bool,
 }
 
 pub struct DeJsonErr{
@@ -116,6 +144,16 @@ impl std::fmt::Debug for DeJsonErr {
 }
 
 impl DeJsonState {
+    // reads the 4 hex digits after a \u, the cursor ends on the last one
+    fn hex_escape(&mut self, i: &mut Chars) -> Result<u32, DeJsonErr> {
+        let mut code = 0;
+        for _ in 0..4 {
+            self.next(i);
+            code = code * 16 + self.cur.to_digit(16).ok_or_else( || self.err_parse("string")) ?;
+        }
+        Ok(code)
+    }
+    
     pub fn next(&mut self, i: &mut Chars) {
         if let Some(c) = i.next() {
             self.cur = c;
@@ -237,6 +275,7 @@ impl DeJsonState {
     }
     
     pub fn block_open(&mut self, i: &mut Chars) -> Result<(), DeJsonErr> {
+        self.skip_unknown = false;
         if self.tok == DeJsonTok::BlockOpen {
             self.next_tok(i) ?;
             return Ok(())
@@ -254,6 +293,7 @@ impl DeJsonState {
     }
     
     pub fn curly_open(&mut self, i: &mut Chars) -> Result<(), DeJsonErr> {
+        self.skip_unknown = false;
         if self.tok == DeJsonTok::CurlyOpen {
             self.next_tok(i) ?;
             return Ok(())
@@ -334,6 +374,47 @@ impl DeJsonState {
         Err(self.err_token("ident"))
     }
     
+    // copies the value at the current token to out as json and moves past it
+    pub fn capture_value(&mut self, i: &mut Chars, out: &mut String) -> Result<(), DeJsonErr> {
+        let mut depth = 0;
+        loop {
+            match &self.tok {
+                DeJsonTok::Str => {
+                    let mut ser = SerJsonState {out: std::mem::take(out)};
+                    self.strbuf.ser_json(0, &mut ser);
+                    *out = ser.out;
+                }
+                DeJsonTok::Char(c) => out.push(*c),
+                DeJsonTok::U64(_) | DeJsonTok::I64(_) | DeJsonTok::F64(_) => out.push_str(&self.numbuf),
+                DeJsonTok::Bool(v) => out.push_str(if *v {"true"} else {"false"}),
+                DeJsonTok::BareIdent => out.push_str(&self.identbuf),
+                DeJsonTok::Null => out.push_str("null"),
+                DeJsonTok::Colon => out.push(':'),
+                DeJsonTok::Comma => out.push(','),
+                DeJsonTok::CurlyOpen | DeJsonTok::BlockOpen => {
+                    out.push(if self.tok == DeJsonTok::CurlyOpen {'{'} else {'['});
+                    depth += 1;
+                }
+                DeJsonTok::CurlyClose | DeJsonTok::BlockClose => {
+                    if depth == 0 {
+                        return Err(self.err_token("value"))
+                    }
+                    out.push(if self.tok == DeJsonTok::CurlyClose {'}'} else {']'});
+                    depth -= 1;
+                }
+                DeJsonTok::Bof | DeJsonTok::Eof => return Err(self.err_token("value"))
+            }
+            self.next_tok(i) ?;
+            if depth == 0 {
+                return Ok(())
+            }
+        }
+    }
+    
+    pub fn skip_value(&mut self, i: &mut Chars) -> Result<(), DeJsonErr> {
+        self.capture_value(i, &mut String::new())
+    }
+    
     pub fn next_tok(&mut self, i: &mut Chars) -> Result<(), DeJsonErr> {
         while self.cur == '\n' || self.cur == '\r' || self.cur == '\t' || self.cur == ' ' {
             self.next(i);
@@ -387,39 +468,49 @@ impl DeJsonState {
                     self.numbuf.push(self.cur);
                     self.next(i);
                 }
+                let mut is_float = false;
                 if self.cur == '.' {
+                    is_float = true;
                     self.numbuf.push(self.cur);
                     self.next(i);
                     while self.cur >= '0' && self.cur <= '9' {
                         self.numbuf.push(self.cur);
                         self.next(i);
                     }
-                    if let Ok(num) = self.numbuf.parse() {
-                        self.tok = DeJsonTok::F64(num);
-                        Ok(())
+                }
+                if self.cur == 'e' || self.cur == 'E' {
+                    is_float = true;
+                    self.numbuf.push(self.cur);
+                    self.next(i);
+                    if self.cur == '+' || self.cur == '-' {
+                        self.numbuf.push(self.cur);
+                        self.next(i);
                     }
-                    else {
-                        Err(self.err_parse("number"))
+                    while self.cur >= '0' && self.cur <= '9' {
+                        self.numbuf.push(self.cur);
+                        self.next(i);
                     }
                 }
-                else {
+                if !is_float {
                     if is_neg {
                         if let Ok(num) = self.numbuf.parse() {
                             self.tok = DeJsonTok::I64(num);
                             return Ok(())
                         }
-                        else {
-                            return Err(self.err_parse("number"));
-                        }
                     }
-                    if let Ok(num) = self.numbuf.parse() {
+                    else if let Ok(num) = self.numbuf.parse() {
                         self.tok = DeJsonTok::U64(num);
-                        Ok(())
-                    }
-                    else {
-                        Err(self.err_parse("number"))
+                        return Ok(())
                     }
                 }
+                // integers that don't fit 64 bits are read as floats too
+                if let Ok(num) = self.numbuf.parse() {
+                    self.tok = DeJsonTok::F64(num);
+                    Ok(())
+                }
+                else {
+                    Err(self.err_parse("number"))
+                }
             },
             'a'..='z' | 'A'..='Z' | '_' => {
                 self.identbuf.clear();
@@ -454,7 +545,23 @@ impl DeJsonState {
                             'n'=>self.strbuf.push('\n'),
                             'r'=>self.strbuf.push('\r'),
                             't'=>self.strbuf.push('\t'),
+                            'b'=>self.strbuf.push('\u{8}'),
+                            'f'=>self.strbuf.push('\u{c}'),
                             '0'=>self.strbuf.push('\0'),
+                            'u'=>{
+                                let mut code = self.hex_escape(i) ?;
+                                // characters outside the basic plane come as a pair of escapes
+                                if (0xd800..0xdc00).contains(&code) {
+                                    let mut peek = i.clone();
+                                    if peek.next() == Some('\\') && peek.next() == Some('u') {
+                                        self.next(i);
+                                        self.next(i);
+                                        let low = self.hex_escape(i) ?;
+                                        code = if (0xdc00..0xe000).contains(&low) {0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00)} else {0xfffd};
+                                    }
+                                }
+                                self.strbuf.push(char::from_u32(code).unwrap_or('\u{fffd}'));
+                            }
                             '\0'=>{
                                 return Err(self.err_parse("string"));
                             },
@@ -603,9 +710,10 @@ impl SerJson for String {
                 '\n'=>{s.out.push('\\');s.out.push('n');},
                 '\r'=>{s.out.push('\\');s.out.push('r');},
                 '\t'=>{s.out.push('\\');s.out.push('t');},
-                '\0'=>{s.out.push('\\');s.out.push('0');},
                 '\\'=>{s.out.push('\\');s.out.push('\\');},
                 '"'=>{s.out.push('\\');s.out.push('"');},
+                // json has no \0, control characters are written as \u escapes
+                c if (c as u32) < 0x20=>s.out.push_str(&format!("\\u{:04x}", c as u32)),
                 _=>s.out.push(c)
             }
         }
@@ -652,7 +760,7 @@ impl<T> DeJson for Vec<T> where T: DeJson {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum JsonValue{
     String(String),
     Char(char),
@@ -663,6 +771,7 @@ pub enum JsonValue{
     BareIdent(String),
     Null,
     Undefined,
+    // the key order of the text isn't kept, serializing writes the keys sorted
     Object(HashMap<String, JsonValue>),
     Array(Vec<JsonValue>)
 }
@@ -674,18 +783,225 @@ impl JsonValue{
         }
         None
     }
+    pub fn object_mut(&mut self)->Option<&mut HashMap<String, JsonValue>>{
+        if let JsonValue::Object(obj) = self{
+            return Some(obj)
+        }
+        None
+    }
+    pub fn array(&self)->Option<&Vec<JsonValue>>{
+        if let JsonValue::Array(arr) = self{
+            return Some(arr)
+        }
+        None
+    }
+    pub fn array_mut(&mut self)->Option<&mut Vec<JsonValue>>{
+        if let JsonValue::Array(arr) = self{
+            return Some(arr)
+        }
+        None
+    }
     pub fn string(&self)->Option<&String>{
         if let JsonValue::String(obj) = self{
             return Some(obj)
         }
         None
     }
+    pub fn bool(&self)->Option<bool>{
+        if let JsonValue::Bool(v) = self{
+            return Some(*v)
+        }
+        None
+    }
+    // the numeric accessors convert between the number kinds as long as the value fits
+    pub fn u64(&self)->Option<u64>{
+        match self{
+            JsonValue::U64(v)=>Some(*v),
+            JsonValue::I64(v)=>u64::try_from(*v).ok(),
+            // u64::MAX as f64 rounds up to 2^64, which doesn't fit
+            JsonValue::F64(v) if v.fract() == 0.0 && *v >= 0.0 && *v < u64::MAX as f64=>Some(*v as u64),
+            _=>None
+        }
+    }
+    pub fn i64(&self)->Option<i64>{
+        match self{
+            JsonValue::U64(v)=>i64::try_from(*v).ok(),
+            JsonValue::I64(v)=>Some(*v),
+            JsonValue::F64(v) if v.fract() == 0.0 && *v >= i64::MIN as f64 && *v < i64::MAX as f64=>Some(*v as i64),
+            _=>None
+        }
+    }
+    pub fn f64(&self)->Option<f64>{
+        match self{
+            JsonValue::U64(v)=>Some(*v as f64),
+            JsonValue::I64(v)=>Some(*v as f64),
+            JsonValue::F64(v)=>Some(*v),
+            _=>None
+        }
+    }
+    pub fn is_null(&self)->bool{
+        matches!(self, JsonValue::Null | JsonValue::Undefined)
+    }
     pub fn key(&self, key:&str)->Option<&JsonValue>{
         if let JsonValue::Object(obj) = self{
             return obj.get(key)
         }
         None
     }
+    pub fn key_mut(&mut self, key:&str)->Option<&mut JsonValue>{
+        if let JsonValue::Object(obj) = self{
+            return obj.get_mut(key)
+        }
+        None
+    }
+    pub fn index(&self, index:usize)->Option<&JsonValue>{
+        if let JsonValue::Array(arr) = self{
+            return arr.get(index)
+        }
+        None
+    }
+    pub fn index_mut(&mut self, index:usize)->Option<&mut JsonValue>{
+        if let JsonValue::Array(arr) = self{
+            return arr.get_mut(index)
+        }
+        None
+    }
+    
+    // looks up a json pointer like /choices/0/message/content, ~1 and ~0 escape / and ~
+    pub fn pointer(&self, pointer:&str)->Option<&JsonValue>{
+        let mut value = self;
+        for part in json_pointer_parts(pointer)?{
+            value = match value{
+                JsonValue::Object(obj)=>obj.get(&part)?,
+                JsonValue::Array(arr)=>arr.get(part.parse::<usize>().ok()?)?,
+                _=>return None
+            };
+        }
+        Some(value)
+    }
+    
+    pub fn pointer_mut(&mut self, pointer:&str)->Option<&mut JsonValue>{
+        let mut value = self;
+        for part in json_pointer_parts(pointer)?{
+            value = match value{
+                JsonValue::Object(obj)=>obj.get_mut(&part)?,
+                JsonValue::Array(arr)=>arr.get_mut(part.parse::<usize>().ok()?)?,
+                _=>return None
+            };
+        }
+        Some(value)
+    }
+    
+    // sets a key, turning a null value into an object first. returns the old value
+    pub fn insert(&mut self, key:&str, value:JsonValue)->Option<JsonValue>{
+        if self.is_null(){
+            *self = JsonValue::Object(HashMap::new());
+        }
+        self.object_mut()?.insert(key.to_string(), value)
+    }
+    
+    pub fn remove(&mut self, key:&str)->Option<JsonValue>{
+        self.object_mut()?.remove(key)
+    }
+    
+    // appends to an array, turning a null value into an array first
+    pub fn push(&mut self, value:JsonValue){
+        if self.is_null(){
+            *self = JsonValue::Array(Vec::new());
+        }
+        if let JsonValue::Array(arr) = self{
+            arr.push(value);
+        }
+    }
+    
+    // moves the value out and leaves null behind
+    pub fn take(&mut self)->JsonValue{
+        std::mem::replace(self, JsonValue::Null)
+    }
+}
+
+fn json_pointer_parts(pointer:&str)->Option<Vec<String>>{
+    if pointer.is_empty(){
+        return Some(Vec::new())
+    }
+    Some(pointer.strip_prefix('/')?.split('/').map(|v| v.replace("~1", "/").replace("~0", "~")).collect())
+}
+
+impl From<&str> for JsonValue{
+    fn from(v:&str)->Self{JsonValue::String(v.to_string())}
+}
+
+impl From<String> for JsonValue{
+    fn from(v:String)->Self{JsonValue::String(v)}
+}
+
+impl From<bool> for JsonValue{
+    fn from(v:bool)->Self{JsonValue::Bool(v)}
+}
+
+impl From<u64> for JsonValue{
+    fn from(v:u64)->Self{JsonValue::U64(v)}
+}
+
+impl From<i64> for JsonValue{
+    fn from(v:i64)->Self{JsonValue::I64(v)}
+}
+
+impl From<f64> for JsonValue{
+    fn from(v:f64)->Self{JsonValue::F64(v)}
+}
+
+impl From<Vec<JsonValue>> for JsonValue{
+    fn from(v:Vec<JsonValue>)->Self{JsonValue::Array(v)}
+}
+
+impl From<HashMap<String, JsonValue>> for JsonValue{
+    fn from(v:HashMap<String, JsonValue>)->Self{JsonValue::Object(v)}
+}
+
+impl SerJson for JsonValue{
+    fn ser_json(&self, d: usize, s: &mut SerJsonState) {
+        match self{
+            JsonValue::String(v)=>v.ser_json(d, s),
+            JsonValue::Char(c)=>c.to_string().ser_json(d, s),
+            JsonValue::U64(v)=>v.ser_json(d, s),
+            JsonValue::I64(v)=>v.ser_json(d, s),
+            JsonValue::F64(v)=>{
+                // keep a float a float when it is read back, json has no nan or infinity
+                if v.is_finite(){
+                    let num = v.to_string();
+                    s.out.push_str(&num);
+                    if !num.contains(['.', 'e', 'E']){
+                        s.out.push_str(".0");
+                    }
+                }
+                else{
+                    s.out.push_str("null");
+                }
+            }
+            JsonValue::Bool(v)=>v.ser_json(d, s),
+            JsonValue::BareIdent(v)=>s.out.push_str(v),
+            JsonValue::Null | JsonValue::Undefined=>s.out.push_str("null"),
+            JsonValue::Object(obj)=>{
+                // sorted so the same value always gives the same text
+                let mut keys:Vec<&String> = obj.keys().collect();
+                keys.sort();
+                s.out.push('{');
+                for (index, key) in keys.iter().enumerate(){
+                    s.indent(d + 1);
+                    key.ser_json(d + 1, s);
+                    s.out.push(':');
+                    obj[*key].ser_json(d + 1, s);
+                    if index != keys.len() - 1{
+                        s.conl();
+                    }
+                }
+                s.indent(d);
+                s.out.push('}');
+            }
+            JsonValue::Array(arr)=>arr.ser_json(d, s),
+        }
+    }
 }
 
 impl DeJson for JsonValue{
@@ -776,7 +1092,7 @@ impl DeJson for JsonValue{
 impl<T> SerJson for [T] where T: SerJson {
     fn ser_json(&self, d: usize, s: &mut SerJsonState) {
         s.out.push('[');
-        let last = self.len() -1;
+        let last = self.len().saturating_sub(1);
         for (index,item) in self.iter().enumerate() {
             item.ser_json(d + 1, s);
             if index != last{
@@ -894,7 +1210,7 @@ impl<K, V> SerJson for HashMap<K, V> where K: SerJson,
 V: SerJson {
     fn ser_json(&self, d: usize, s: &mut SerJsonState) {
         s.out.push('{');
-        let last = self.len() - 1;
+        let last = self.len().saturating_sub(1);
         for (index, (k, v)) in self.iter().enumerate() {
             s.indent(d + 1);
             k.ser_json(d + 1, s);
@@ -938,3 +1254,268 @@ impl<T> DeJson for Box<T> where T: DeJson {
         Ok(Box::new(DeJson::de_json(s, i) ?))
     }
 }
+
+// a pull reader over json text that hands out one event at a time, so big documents
+// like a huge top level array can be walked or deserialized item by item without ever
+// building the whole JsonValue tree
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum JsonEvent {
+    ObjectStart,
+    ObjectEnd,
+    ArrayStart,
+    ArrayEnd,
+    Key(String),
+    // any value that isn't an object or array
+    Value(JsonValue),
+    End,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum JsonNest {
+    Object,
+    Array,
+}
+
+pub struct JsonReader<'a> {
+    state: DeJsonState,
+    chars: Chars<'a>,
+    nest: Vec<JsonNest>,
+    started: bool,
+    // in an object after a key, so a string is a value and not the next key
+    in_value: bool,
+}
+
+impl<'a> JsonReader<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self {
+            state: DeJsonState::default(),
+            chars: input.chars(),
+            nest: Vec::new(),
+            started: false,
+            in_value: false,
+        }
+    }
+    
+    fn start(&mut self) -> Result<(), DeJsonErr> {
+        if !self.started {
+            self.started = true;
+            self.state.next(&mut self.chars);
+            self.state.next_tok(&mut self.chars) ?;
+        }
+        Ok(())
+    }
+    
+    // eats the comma after a value in its object or array
+    fn end_value(&mut self) -> Result<(), DeJsonErr> {
+        self.in_value = false;
+        match self.nest.last() {
+            Some(JsonNest::Object) => self.state.eat_comma_curly(&mut self.chars),
+            Some(JsonNest::Array) => self.state.eat_comma_block(&mut self.chars),
+            None => Ok(())
+        }
+    }
+    
+    fn is_key(&self) -> bool {
+        self.nest.last() == Some(&JsonNest::Object) && !self.in_value
+    }
+    
+    // the depth of objects and arrays the reader is in
+    pub fn depth(&self) -> usize {
+        self.nest.len()
+    }
+    
+    pub fn next_event(&mut self) -> Result<JsonEvent, DeJsonErr> {
+        self.start() ?;
+        let is_key = self.is_key();
+        let in_array = self.nest.last() == Some(&JsonNest::Array);
+        let s = &mut self.state;
+        let i = &mut self.chars;
+        match s.tok {
+            DeJsonTok::CurlyOpen if !is_key => {
+                s.curly_open(i) ?;
+                self.nest.push(JsonNest::Object);
+                self.in_value = false;
+                Ok(JsonEvent::ObjectStart)
+            }
+            DeJsonTok::BlockOpen if !is_key => {
+                s.block_open(i) ?;
+                self.nest.push(JsonNest::Array);
+                self.in_value = false;
+                Ok(JsonEvent::ArrayStart)
+            }
+            DeJsonTok::CurlyClose if is_key => {
+                s.curly_close(i) ?;
+                self.nest.pop();
+                self.end_value() ?;
+                Ok(JsonEvent::ObjectEnd)
+            }
+            DeJsonTok::BlockClose if in_array => {
+                s.block_close(i) ?;
+                self.nest.pop();
+                self.end_value() ?;
+                Ok(JsonEvent::ArrayEnd)
+            }
+            DeJsonTok::Str if is_key => {
+                let key = s.as_string() ?;
+                s.next_colon(i) ?;
+                self.in_value = true;
+                Ok(JsonEvent::Key(key))
+            }
+            DeJsonTok::Eof if self.nest.is_empty() => Ok(JsonEvent::End),
+            _ if is_key => Err(s.err_token("key")),
+            _ => {
+                let value = JsonValue::de_json(s, i) ?;
+                self.end_value() ?;
+                Ok(JsonEvent::Value(value))
+            }
+        }
+    }
+    
+    // deserializes the value the reader is at, objects and arrays included
+    pub fn read<T: DeJson>(&mut self) -> Result<T, DeJsonErr> {
+        self.start() ?;
+        if self.is_key() {
+            return Err(self.state.err_token("value"))
+        }
+        let value = T::de_json(&mut self.state, &mut self.chars) ?;
+        self.end_value() ?;
+        Ok(value)
+    }
+    
+    pub fn skip(&mut self) -> Result<(), DeJsonErr> {
+        self.start() ?;
+        if self.is_key() {
+            return Err(self.state.err_token("value"))
+        }
+        self.state.skip_value(&mut self.chars) ?;
+        self.end_value()
+    }
+    
+    // steps into the array the reader is at and deserializes its items one by one
+    pub fn array_items<T: DeJson>(&mut self) -> Result<JsonArrayItems<'_, 'a, T>, DeJsonErr> {
+        match self.next_event() ? {
+            JsonEvent::ArrayStart => Ok(JsonArrayItems {reader: self, done: false, item: std::marker::PhantomData}),
+            _ => Err(self.state.err_token("["))
+        }
+    }
+}
+
+pub struct JsonArrayItems<'r, 'a, T> {
+    reader: &'r mut JsonReader<'a>,
+    done: bool,
+    item: std::marker::PhantomData<T>,
+}
+
+impl<T: DeJson> Iterator for JsonArrayItems<'_, '_, T> {
+    type Item = Result<T, DeJsonErr>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None
+        }
+        if self.reader.state.tok == DeJsonTok::BlockClose {
+            self.done = true;
+            return match self.reader.next_event() {
+                Ok(_) => None,
+                Err(e) => Some(Err(e))
+            }
+        }
+        let item = self.reader.read();
+        // after an error the position in the text is unknown
+        self.done = item.is_err();
+        Some(item)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn looks_up_json_pointers() {
+        let mut value = JsonValue::deserialize_json(r#"{"a/b":{"m~n":[1,{"x":2}]},"":3,"~1":4}"#).unwrap();
+        assert_eq!(value.pointer("/a~1b/m~0n/1/x"), Some(&JsonValue::U64(2)));
+        assert_eq!(value.pointer("/a~1b/m~0n/0"), Some(&JsonValue::U64(1)));
+        // ~01 is an escaped ~ followed by a 1, not an escaped /
+        assert_eq!(value.pointer("/~01"), Some(&JsonValue::U64(4)));
+        assert_eq!(value.pointer("/"), Some(&JsonValue::U64(3)));
+        assert_eq!(value.pointer(""), Some(&value));
+        assert_eq!(value.pointer("a~1b"), None);
+        assert_eq!(value.pointer("/a/b"), None);
+        assert_eq!(value.pointer("/a~1b/m~0n/2"), None);
+        assert_eq!(value.pointer("/a~1b/m~0n/x"), None);
+
+        *value.pointer_mut("/a~1b/m~0n/1/x").unwrap() = "y".into();
+        assert_eq!(value.pointer("/a~1b/m~0n/1/x"), Some(&JsonValue::String("y".into())));
+    }
+
+    #[test]
+    fn converts_numbers_within_bounds() {
+        let value = JsonValue::deserialize_json("[18446744073709551615, 9223372036854775807, -9223372036854775808, -1, 3.0, 1.5, 1e20, 18446744073709551616.0, 9223372036854775808.0]").unwrap();
+        let numbers = value.array().unwrap();
+        assert_eq!(numbers[0], JsonValue::U64(u64::MAX));
+        assert_eq!((numbers[0].u64(), numbers[0].i64()), (Some(u64::MAX), None));
+        assert_eq!((numbers[1].u64(), numbers[1].i64()), (Some(i64::MAX as u64), Some(i64::MAX)));
+        assert_eq!((numbers[2].u64(), numbers[2].i64()), (None, Some(i64::MIN)));
+        assert_eq!((numbers[3].u64(), numbers[3].i64()), (None, Some(-1)));
+        assert_eq!((numbers[4].u64(), numbers[4].i64()), (Some(3), Some(3)));
+        assert_eq!((numbers[5].u64(), numbers[5].i64(), numbers[5].f64()), (None, None, Some(1.5)));
+        assert_eq!((numbers[6].u64(), numbers[6].i64()), (None, None));
+        // 2^64 and 2^63 are just past the ends and must not saturate
+        assert_eq!(numbers[7].u64(), None);
+        assert_eq!((numbers[8].u64(), numbers[8].i64()), (Some(1 << 63), None));
+        assert_eq!(JsonValue::String("1".into()).u64(), None);
+    }
+
+    #[test]
+    fn round_trips_values() {
+        let text = r#"{"b":[1,-2,2.5,1e-7,true,null,"\"q\"\n\u00e9€",[],{}],"a":{"z":0.0,"y":-0.5}}"#;
+        let value = JsonValue::deserialize_json(text).unwrap();
+        let json = value.serialize_json();
+        // keys come out sorted, and floats stay floats
+        assert_eq!(json, r#"{"a":{"y":-0.5,"z":0.0},"b":[1,-2,2.5,0.0000001,true,null,"\"q\"\né€",[],{}]}"#);
+        assert_eq!(JsonValue::deserialize_json(&json).unwrap(), value);
+        assert_eq!(JsonValue::F64(f64::NAN).serialize_json(), "null");
+
+        // escapes, including pairs for characters outside the basic plane
+        let text = r#""\ud83d\ude00 \ud83d \u0001\b\f\/""#;
+        let value = String::deserialize_json(text).unwrap();
+        assert_eq!(value, "😀 \u{fffd} \u{1}\u{8}\u{c}/");
+        assert_eq!(value.serialize_json(), "\"😀 \u{fffd} \\u0001\\u0008\\u000c/\"");
+        assert_eq!(String::deserialize_json(&value.serialize_json()).unwrap(), value);
+        assert!(String::deserialize_json(r#""\u12""#).is_err());
+
+        let mut built = JsonValue::Null;
+        built.insert("list", JsonValue::Null);
+        built.key_mut("list").unwrap().push(2u64.into());
+        built.insert("name", "x".into());
+        assert_eq!(built.serialize_json(), r#"{"list":[2],"name":"x"}"#);
+        assert_eq!(JsonValue::deserialize_json(&built.serialize_json()).unwrap(), built);
+    }
+
+    #[test]
+    fn reads_top_level_array_items() {
+        let mut reader = JsonReader::new(r#"[1, 2, 3]"#);
+        let items: Vec<u32> = reader.array_items().unwrap().collect::<Result<_, _>>().unwrap();
+        assert_eq!(items, [1, 2, 3]);
+        assert_eq!(reader.next_event().unwrap(), JsonEvent::End);
+
+        let mut reader = JsonReader::new(r#"[]"#);
+        assert_eq!(reader.array_items::<u32>().unwrap().count(), 0);
+
+        // an error ends the items, the ones before it were already handed out
+        let mut reader = JsonReader::new(r#"[{"a":1}, {"a":"x"}, {"a":3}]"#);
+        let mut items = reader.array_items::<HashMap<String, u32>>().unwrap();
+        assert_eq!(items.next().unwrap().unwrap()["a"], 1);
+        assert!(items.next().unwrap().is_err());
+        assert!(items.next().is_none());
+
+        let mut reader = JsonReader::new(r#"[1, 2"#);
+        let mut items = reader.array_items::<u32>().unwrap();
+        assert_eq!(items.next().unwrap().unwrap(), 1);
+        assert!(items.next().unwrap().is_err());
+        assert!(items.next().is_none());
+
+        assert!(JsonReader::new(r#"{"a":1}"#).array_items::<u32>().is_err());
+    }
+}
@@ -32,6 +32,14 @@ impl SerRonState {
         self.out.push(')');
     }
     
+    // writes the fields of value's struct into the struct being serialized, used by #[flatten]
+    pub fn flatten<T: SerRon + ?Sized>(&mut self, d: usize, value: &T) {
+        let mut inner = SerRonState {out: String::new()};
+        value.ser_ron(d, &mut inner);
+        if let Some(fields) = inner.out.strip_prefix("(\n").and_then( | v | v.strip_suffix(')')) {
+            self.out.push_str(fields.trim_end_matches(' '));
+        }
+    }
 }
 
 pub trait SerRon {
@@ -62,7 +70,17 @@ pub trait DeRon: Sized {
     DeRonErr>;
 }
 
-#[derive(PartialEq, Debug)]
+// deserializes a #[flatten] field from the key:value pairs its parent didn't know
+pub fn de_ron_flattened<T: DeRon>(fields: &str) -> Result<T, DeRonErr> {
+    let mut state = DeRonState {skip_unknown: true, ..Default::default()};
+    let src = format!("({})", fields);
+    let mut chars = src.chars();
+    state.next(&mut chars);
+    state.next_tok(&mut chars) ?;
+    DeRon::de_ron(&mut state, &mut chars)
+}
+
+#[derive(PartialEq, Debug, Clone)]
 #[derive(Default)]
 pub enum DeRonTok {
     Ident,
@@ -87,7 +105,7 @@ pub enum DeRonTok {
 
 
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct DeRonState {
     pub cur: char,
     pub tok: DeRonTok,
@@ -95,7 +113,10 @@ pub struct DeRonState {
     pub numbuf: String,
     pub identbuf: String,
     pub line: usize,
-    pub col: usize
+    pub col: usize,
+    // lets the next derived struct ignore fields it doesn't know, set for #[flatten] fields
+    // and cleared by any (, [ or { so it never reaches nested values
+    pub skip_unknown: bool,
 }
 
 pub struct DeRonErr {
@@ -240,6 +261,7 @@ impl DeRonState {
     }
     
     pub fn paren_open(&mut self, i: &mut Chars) -> Result<(), DeRonErr> {
+        self.skip_unknown = false;
         if self.tok == DeRonTok::ParenOpen {
             self.next_tok(i) ?;
             return Ok(())
@@ -257,6 +279,7 @@ impl DeRonState {
     }
     
     pub fn block_open(&mut self, i: &mut Chars) -> Result<(), DeRonErr> {
+        self.skip_unknown = false;
         if self.tok == DeRonTok::BlockOpen {
             self.next_tok(i) ?;
             return Ok(())
@@ -274,6 +297,7 @@ impl DeRonState {
     }
     
     pub fn curly_open(&mut self, i: &mut Chars) -> Result<(), DeRonErr> {
+        self.skip_unknown = false;
         if self.tok == DeRonTok::CurlyOpen {
             self.next_tok(i) ?;
             return Ok(())
@@ -349,6 +373,59 @@ impl DeRonState {
         Err(self.err_token("string"))
     }
     
+    // copies the value at the current token to out as ron and moves past it
+    pub fn capture_value(&mut self, i: &mut Chars, out: &mut String) -> Result<(), DeRonErr> {
+        let mut depth = 0;
+        loop {
+            let mut is_ident = false;
+            match &self.tok {
+                DeRonTok::Ident => {
+                    out.push_str(&self.identbuf);
+                    is_ident = true;
+                }
+                DeRonTok::Str => {
+                    let mut ser = SerRonState {out: std::mem::take(out)};
+                    self.strbuf.ser_ron(0, &mut ser);
+                    *out = ser.out;
+                }
+                DeRonTok::Char(c) => {
+                    out.push('\'');
+                    if *c == '\'' || *c == '\\' {
+                        out.push('\\');
+                    }
+                    out.push(*c);
+                    out.push('\'');
+                }
+                DeRonTok::U64(_) | DeRonTok::I64(_) | DeRonTok::F64(_) => out.push_str(&self.numbuf),
+                DeRonTok::Bool(v) => out.push_str(if *v {"true"} else {"false"}),
+                DeRonTok::Colon => out.push(':'),
+                DeRonTok::Comma => out.push(','),
+                DeRonTok::ParenOpen | DeRonTok::CurlyOpen | DeRonTok::BlockOpen => {
+                    out.push(match self.tok {DeRonTok::ParenOpen => '(', DeRonTok::CurlyOpen => '{', _ => '['});
+                    depth += 1;
+                }
+                DeRonTok::ParenClose | DeRonTok::CurlyClose | DeRonTok::BlockClose => {
+                    if depth == 0 {
+                        return Err(self.err_token("value"))
+                    }
+                    out.push(match self.tok {DeRonTok::ParenClose => ')', DeRonTok::CurlyClose => '}', _ => ']'});
+                    depth -= 1;
+                }
+                DeRonTok::Bof | DeRonTok::Eof => return Err(self.err_token("value"))
+            }
+            self.next_tok(i) ?;
+            // an ident can be followed by the contents of an enum variant or named struct
+            let opens = matches!(self.tok, DeRonTok::ParenOpen | DeRonTok::CurlyOpen);
+            if depth == 0 && !(is_ident && opens) {
+                return Ok(())
+            }
+        }
+    }
+    
+    pub fn skip_value(&mut self, i: &mut Chars) -> Result<(), DeRonErr> {
+        self.capture_value(i, &mut String::new())
+    }
+    
     pub fn next_tok(&mut self, i: &mut Chars) -> Result<(), DeRonErr> {
         loop {
             while self.cur == '\n' || self.cur == '\r' || self.cur == '\t' || self.cur == ' ' {
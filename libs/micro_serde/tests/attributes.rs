@@ -0,0 +1,130 @@
+use makepad_micro_serde::*;
+
+#[derive(SerJson, DeJson, SerRon, DeRon, SerBin, DeBin, Debug, PartialEq)]
+struct Window {
+    width: u32,
+    height: u32,
+}
+
+#[derive(SerJson, DeJson, SerRon, DeRon, SerBin, DeBin, Debug, PartialEq)]
+#[rename_all = "camelCase"]
+struct Settings {
+    user_name: String,
+    #[rename = "ver"]
+    version: u32,
+    #[default(7)]
+    max_retries: u32,
+    #[skip]
+    #[default(vec![1, 2])]
+    cache: Vec<u32>,
+    #[flatten]
+    window: Window,
+}
+
+#[derive(SerJson, DeJson, SerRon, DeRon, SerBin, DeBin, Debug, PartialEq)]
+#[tag = "type"]
+#[rename_all = "snake_case"]
+enum Shape {
+    Circle {radius: f64},
+    RoundRect {width: u32, height: u32},
+    Empty,
+}
+
+#[derive(SerJson, DeJson, SerRon, DeRon, SerBin, DeBin, Debug, PartialEq)]
+#[untagged]
+enum Value {
+    Pair {a: u32, b: u32},
+    Num(u32),
+    Text(String),
+}
+
+fn settings() -> Settings {
+    Settings {user_name: "me".into(), version: 2, max_retries: 3, cache: vec![9], window: Window {width: 10, height: 20}}
+}
+
+// what comes back: skipped fields always get their default
+fn read_back() -> Settings {
+    Settings {cache: vec![1, 2], ..settings()}
+}
+
+#[test]
+fn struct_attributes_in_json() {
+    let json = settings().serialize_json();
+    assert_eq!(json, r#"{"userName":"me","ver":2,"maxRetries":3,"width":10,"height":20}"#);
+    assert_eq!(Settings::deserialize_json(&json).unwrap(), read_back());
+
+    // flattened fields can come in any order, a missing field with a default gets it
+    let settings = Settings::deserialize_json(r#"{"height":20,"ver":2,"width":10,"userName":"me"}"#).unwrap();
+    assert_eq!(settings, Settings {max_retries: 7, ..read_back()});
+
+    // the rust names aren't accepted, and fields without a default are required
+    assert!(Settings::deserialize_json(r#"{"user_name":"me","ver":2,"width":10,"height":20}"#).is_err());
+    assert!(Settings::deserialize_json(r#"{"userName":"me","version":2,"width":10,"height":20}"#).is_err());
+    assert!(Settings::deserialize_json(r#"{"userName":"me","ver":2,"width":10}"#).is_err());
+}
+
+#[test]
+fn struct_attributes_in_ron() {
+    let ron = settings().serialize_ron();
+    assert_eq!(ron, "(\n    userName:\"me\",\n    ver:2,\n    maxRetries:3,\n    width:10,\n    height:20,\n)");
+    assert_eq!(Settings::deserialize_ron(&ron).unwrap(), read_back());
+
+    let settings = Settings::deserialize_ron("(height:20, ver:2, width:10, userName:\"me\")").unwrap();
+    assert_eq!(settings, Settings {max_retries: 7, ..read_back()});
+
+    assert!(Settings::deserialize_ron("(user_name:\"me\", ver:2, width:10, height:20)").is_err());
+    assert!(Settings::deserialize_ron("(userName:\"me\", ver:2, width:10)").is_err());
+}
+
+#[test]
+fn struct_attributes_in_bin() {
+    // bin has no names, so only skip and default change what comes back
+    let bin = settings().serialize_bin();
+    assert_eq!(Settings::deserialize_bin(&bin).unwrap(), read_back());
+    assert!(Settings::deserialize_bin(&bin[..bin.len() - 1]).is_err());
+}
+
+#[test]
+fn internally_tagged_enums() {
+    let shapes = [Shape::Circle {radius: 1.5}, Shape::RoundRect {width: 1, height: 2}, Shape::Empty];
+    let json = shapes.iter().map( | v | v.serialize_json()).collect::<Vec<_>>();
+    assert_eq!(json, [
+        r#"{"type":"circle","radius":1.5}"#,
+        r#"{"type":"round_rect","width":1,"height":2}"#,
+        r#"{"type":"empty"}"#,
+    ]);
+    for shape in shapes {
+        assert_eq!(Shape::deserialize_json(&shape.serialize_json()).unwrap(), shape);
+        assert_eq!(Shape::deserialize_ron(&shape.serialize_ron()).unwrap(), shape);
+        assert_eq!(Shape::deserialize_bin(&shape.serialize_bin()).unwrap(), shape);
+    }
+    assert_eq!(Shape::Circle {radius: 1.5}.serialize_ron(), "(\n    type:\"circle\",\n    radius:1.5,\n)");
+
+    // the tag doesn't have to be the first key
+    assert_eq!(Shape::deserialize_json(r#"{"width":1,"height":2,"type":"round_rect"}"#).unwrap(), Shape::RoundRect {width: 1, height: 2});
+    assert_eq!(Shape::deserialize_json(r#"{"radius":2.5,"type":"circle"}"#).unwrap(), Shape::Circle {radius: 2.5});
+    assert_eq!(Shape::deserialize_ron("(width:1, type:\"round_rect\", height:2)").unwrap(), Shape::RoundRect {width: 1, height: 2});
+
+    assert!(Shape::deserialize_json(r#"{"type":"RoundRect","width":1,"height":2}"#).is_err());
+    assert!(Shape::deserialize_json(r#"{"width":1,"height":2}"#).is_err());
+    assert!(Shape::deserialize_ron("(type:\"square\")").is_err());
+}
+
+#[test]
+fn untagged_enums() {
+    let values = [Value::Pair {a: 1, b: 2}, Value::Num(3), Value::Text("x".into())];
+    let json = values.iter().map( | v | v.serialize_json()).collect::<Vec<_>>();
+    assert_eq!(json, [r#"{"a":1,"b":2}"#, "3", r#""x""#]);
+    let ron = values.iter().map( | v | v.serialize_ron()).collect::<Vec<_>>();
+    assert_eq!(ron, ["(\n    a:1,\n    b:2,\n)", "3", r#""x""#]);
+    for value in values {
+        assert_eq!(Value::deserialize_json(&value.serialize_json()).unwrap(), value);
+        assert_eq!(Value::deserialize_ron(&value.serialize_ron()).unwrap(), value);
+        assert_eq!(Value::deserialize_bin(&value.serialize_bin()).unwrap(), value);
+    }
+    // the first variant that fits wins, nothing fitting is an error
+    assert_eq!(Value::deserialize_json(r#"{"b":2,"a":1}"#).unwrap(), Value::Pair {a: 1, b: 2});
+    assert!(Value::deserialize_json("true").is_err());
+    assert!(Value::deserialize_json(r#"{"a":1}"#).is_err());
+    assert!(Value::deserialize_ron("[1]").is_err());
+}
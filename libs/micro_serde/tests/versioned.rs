@@ -0,0 +1,106 @@
+use makepad_micro_serde::*;
+
+// the same types as written by an older and a newer revision of a program
+mod old {
+    use makepad_micro_serde::*;
+
+    #[derive(SerBin, DeBin, Debug, PartialEq, Default)]
+    #[versioned]
+    pub struct Message {
+        #[id = 0] pub name: String,
+        #[id = 1] pub count: u32,
+    }
+
+    #[derive(SerBin, DeBin, Debug, PartialEq)]
+    #[versioned]
+    pub enum Event {
+        #[id = 0] Ping,
+        #[id = 1] Log {#[id = 0] text: String},
+    }
+}
+
+mod new {
+    use makepad_micro_serde::*;
+
+    #[derive(SerBin, DeBin, Debug, PartialEq)]
+    #[versioned]
+    pub struct Message {
+        #[id = 0] pub name: String,
+        #[id = 2] pub tags: Vec<String>,
+        #[id = 1] pub count: u32,
+        #[default(5)]
+        #[id = 3] pub retries: u32,
+    }
+
+    #[derive(SerBin, DeBin, Debug, PartialEq)]
+    #[versioned]
+    pub enum Event {
+        #[id = 0] Ping,
+        #[id = 1] Log {#[id = 0] text: String, #[id = 1] level: u8},
+        #[id = 2] Progress(f32),
+    }
+}
+
+use old::Message as OldMessage;
+
+#[derive(SerBin, DeBin, Debug, PartialEq)]
+#[versioned]
+struct Envelope {
+    #[id = 0] message: OldMessage,
+}
+
+#[derive(SerBin, DeBin, Debug, PartialEq)]
+struct Plain {
+    a: u32,
+    b: String,
+}
+
+#[test]
+fn older_readers_skip_unknown_fields() {
+    let message = new::Message {name: "a".into(), tags: vec!["x".into(), "y".into()], count: 3, retries: 1};
+    assert_eq!(old::Message::deserialize_bin(&message.serialize_bin()).unwrap(), old::Message {name: "a".into(), count: 3});
+
+    let event = new::Event::Log {text: "hi".into(), level: 2};
+    assert_eq!(old::Event::deserialize_bin(&event.serialize_bin()).unwrap(), old::Event::Log {text: "hi".into()});
+}
+
+#[test]
+fn newer_readers_default_missing_fields() {
+    let message = old::Message {name: "a".into(), count: 3};
+    assert_eq!(new::Message::deserialize_bin(&message.serialize_bin()).unwrap(), new::Message {name: "a".into(), tags: vec![], count: 3, retries: 5});
+
+    let event = old::Event::Log {text: "hi".into()};
+    assert_eq!(new::Event::deserialize_bin(&event.serialize_bin()).unwrap(), new::Event::Log {text: "hi".into(), level: 0});
+}
+
+#[test]
+fn framed_lists_skip_unknown_variants() {
+    let events = [new::Event::Ping, new::Event::Progress(0.5), new::Event::Log {text: "hi".into(), level: 2}];
+    // a variant the reader doesn't know fails on its own
+    let err = old::Event::deserialize_bin(&events[1].serialize_bin()).unwrap_err();
+    assert!(err.msg.contains("unknown variant 2 of Event"), "{}", err);
+
+    let mut bin = Vec::new();
+    ser_bin_framed_vec(&events, &mut bin);
+    let read: Vec<old::Event> = de_bin_framed_vec(&mut 0, &bin).unwrap();
+    assert_eq!(read, [old::Event::Ping, old::Event::Log {text: "hi".into()}]);
+    let read: Vec<new::Event> = de_bin_framed_vec(&mut 0, &bin).unwrap();
+    assert_eq!(read, events);
+    // but a list cut short is still an error
+    assert!(de_bin_framed_vec::<old::Event>(&mut 0, &bin[..bin.len() - 1]).is_err());
+}
+
+#[test]
+fn errors_name_the_field() {
+    let bin = Plain {a: 1, b: "text".into()}.serialize_bin();
+    let err = Plain::deserialize_bin(&bin[..bin.len() - 1]).unwrap_err();
+    assert!(err.msg.ends_with("in Plain.b"), "{}", err);
+
+    // the string length inside the nested message is broken, the error reads inside out
+    let mut bin = Envelope {message: old::Message {name: "a".into(), count: 3}}.serialize_bin();
+    // from the end: the count, its length and id, the "a" and then the string length
+    let at = bin.len() - 4 - 4 - 2 - 1 - 8;
+    bin[at..at + 8].copy_from_slice(&100u64.to_le_bytes());
+    let err = Envelope::deserialize_bin(&bin).unwrap_err();
+    assert!(err.msg.ends_with("in Message.name in Envelope.message"), "{}", err);
+}
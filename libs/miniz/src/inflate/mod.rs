@@ -150,13 +150,12 @@ fn decompress_to_vec_inner(
 
             TINFLStatus::HasMoreOutput => {
                 // We need more space, so check if we can resize the buffer and do it.
-                let new_len = ret
-                    .len()
-                    .checked_add(out_pos)
-                    .ok_or(TINFLStatus::HasMoreOutput)?;
-                if new_len > max_output_size {
+                // The buffer only fails to fit once it is already at the limit, growing
+                // it is capped at the limit so output that fits is never rejected.
+                if ret.len() >= max_output_size {
                     return Err(TINFLStatus::HasMoreOutput);
                 };
+                let new_len = ret.len().saturating_mul(2).max(1).min(max_output_size);
                 ret.resize(new_len, 0);
             }
 
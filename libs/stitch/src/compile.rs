@@ -4,9 +4,11 @@ use {
         code,
         code::{
             BinOpInfo, BlockType, CompiledCode, InstrSlot, InstrVisitor, LoadInfo, MemArg,
-            StoreInfo, UnOpInfo, UncompiledCode,
+            StoreInfo, UnOpInfo, UncompiledCode, VecLaneOpInfo, VecMemLaneOpInfo, VecMemOpInfo,
+            VecOpInfo,
         },
         decode::DecodeError,
+        engine::FuelCosts,
         exec,
         exec::ThreadedInstr,
         extern_ref::ExternRef,
@@ -70,6 +72,7 @@ impl Compiler {
         }
         let local_count = locals.len() - type_.params().len();
 
+        let config = *store.engine().config();
         let mut compile = Compile {
             store,
             type_: type_.clone(),
@@ -82,6 +85,9 @@ impl Compiler {
             first_temp_stack_idx: local_count,
             max_stack_height: local_count,
             regs: [None; 2],
+            fuel_costs: config.fuel_costs,
+            is_interruptible: config.interruptible,
+            fuel_idx: None,
             code: Vec::new(),
         };
         compile.push_block(
@@ -97,10 +103,16 @@ impl Compiler {
                 .mem(0)
                 .map(|mem| mem.to_unguarded(store.id())),
         );
+        compile.emit_interrupt_check();
+        compile.emit_fuel_check();
 
         let mut decoder = Decoder::new(&code.expr);
         while !compile.blocks.is_empty() {
+            let is_unreachable = compile.block(0).is_unreachable;
             code::decode_instr(&mut decoder, &mut self.label_idxs, &mut compile).unwrap();
+            if !is_unreachable {
+                compile.charge_fuel(compile.fuel_costs.map_or(0, |costs| costs.instr));
+            }
         }
 
         for (result_idx, result_type) in type_.clone().results().iter().copied().enumerate().rev() {
@@ -112,7 +124,7 @@ impl Compiler {
 
         let mut code: AliasableBox<[InstrSlot]> = AliasableBox::from_box(Box::from(compile.code));
         for fixup_idx in compile.fixup_idxs.drain(..) {
-            code[fixup_idx] += code.as_ptr() as usize;
+            code[fixup_idx] += code.as_ptr() as InstrSlot;
         }
 
         CompiledCode {
@@ -142,6 +154,9 @@ struct Compile<'a> {
     first_temp_stack_idx: usize,
     max_stack_height: usize,
     regs: [Option<usize>; 2],
+    fuel_costs: Option<FuelCosts>,
+    is_interruptible: bool,
+    fuel_idx: Option<usize>,
     code: Vec<InstrSlot>,
 }
 
@@ -230,21 +245,24 @@ impl<'a> Compile<'a> {
 
     /// Pushes the hole with the given index onto the block with the given index.
     fn push_hole(&mut self, block_idx: usize, hole_idx: usize) {
-        // We use the hole itself to store the index of the next hole. The value `usize::MAX` is
-        // used to indicate the absence of a next hole.
-        self.code[hole_idx] = self.block(block_idx).first_hole_idx.unwrap_or(usize::MAX);
+        // We use the hole itself to store the index of the next hole. The value `InstrSlot::MAX`
+        // is used to indicate the absence of a next hole.
+        self.code[hole_idx] = self
+            .block(block_idx)
+            .first_hole_idx
+            .map_or(InstrSlot::MAX, |hole_idx| hole_idx as InstrSlot);
         self.block_mut(block_idx).first_hole_idx = Some(hole_idx);
     }
 
     /// Pops a hole from the block with the given index.
     fn pop_hole(&mut self, block_idx: usize) -> Option<usize> {
         if let Some(hole_idx) = self.block(block_idx).first_hole_idx {
-            // We use the hole itself to store the index of the next hole. The value `usize::MAX` is
-            // used to indicate the absence of a next hole.
-            self.block_mut(block_idx).first_hole_idx = if self.code[hole_idx] == usize::MAX {
+            // We use the hole itself to store the index of the next hole. The value
+            // `InstrSlot::MAX` is used to indicate the absence of a next hole.
+            self.block_mut(block_idx).first_hole_idx = if self.code[hole_idx] == InstrSlot::MAX {
                 None
             } else {
-                Some(self.code[hole_idx])
+                Some(self.code[hole_idx] as usize)
             };
             Some(hole_idx)
         } else {
@@ -495,6 +513,11 @@ impl<'a> Compile<'a> {
             UnguardedVal::I64(val) => self.emit(val),
             UnguardedVal::F32(val) => self.emit(val),
             UnguardedVal::F64(val) => self.emit(val),
+            UnguardedVal::V128(val) => {
+                // A `v128` does not fit in a single instruction slot, so we split it in two.
+                self.emit(val as u64);
+                self.emit((val >> 64) as u64);
+            }
             UnguardedVal::FuncRef(val) => self.emit(val),
             UnguardedVal::ExternRef(val) => self.emit(val),
         }
@@ -534,7 +557,7 @@ impl<'a> Compile<'a> {
     /// Patches the hole with the given index with the offset of the current instruction.
     fn patch_hole(&mut self, hole_idx: usize) {
         self.fixup_idxs.push(hole_idx);
-        self.code[hole_idx] = self.code.len() * mem::size_of::<usize>();
+        self.code[hole_idx] = (self.code.len() * mem::size_of::<InstrSlot>()) as InstrSlot;
     }
 
     /// Emits the offset of the instruction with the given index.
@@ -542,6 +565,73 @@ impl<'a> Compile<'a> {
         self.fixup_idxs.push(self.code.len());
         self.emit(instr_idx * mem::size_of::<InstrSlot>());
     }
+
+    // Methods for metering fuel and checking for interrupts.
+
+    /// Emits a `check_interrupt` instruction, if the code is interruptible.
+    fn emit_interrupt_check(&mut self) {
+        if self.is_interruptible {
+            self.emit(exec::check_interrupt as ThreadedInstr);
+        }
+    }
+
+    /// Emits a `consume_fuel` instruction at the start of a basic block, if fuel is metered.
+    ///
+    /// We don't yet know how much fuel the basic block consumes, so we emit an amount of 0 instead,
+    /// and add to it as we compile each instruction in the basic block.
+    fn emit_fuel_check(&mut self) {
+        if self.fuel_costs.is_some() {
+            self.emit(exec::consume_fuel as ThreadedInstr);
+            self.fuel_idx = Some(self.code.len());
+            self.emit(0u64);
+        }
+    }
+
+    /// Adds the given amount of fuel to the amount consumed by the current basic block.
+    fn charge_fuel(&mut self, amount: u64) {
+        if let Some(fuel_idx) = self.fuel_idx {
+            self.code[fuel_idx] += amount as InstrSlot;
+        }
+    }
+
+    /// Adds the fuel consumed by a branch to the amount consumed by the current basic block.
+    fn charge_branch_fuel(&mut self) {
+        self.charge_fuel(self.fuel_costs.map_or(0, |costs| costs.branch));
+    }
+
+    // Methods for compiling vector instructions.
+
+    /// Emits a vector instruction and its inputs, and pops the inputs from the stack.
+    ///
+    /// Vector instructions have only one variant, which reads all its operands from the stack, so
+    /// we need to ensure that all operands are neither immediate nor register operands. Unlike for
+    /// other instructions, the inputs are emitted in the order in which they were pushed.
+    fn emit_vec_op(&mut self, info: VecOpInfo) {
+        let input_count = info.input_types.len();
+        for opd_depth in 0..input_count {
+            self.ensure_opd_not_imm(opd_depth);
+            self.ensure_opd_not_reg(opd_depth);
+        }
+        self.emit(info.instr);
+        for opd_depth in (0..input_count).rev() {
+            self.emit_opd(opd_depth);
+        }
+        for _ in 0..input_count {
+            self.pop_opd();
+        }
+    }
+
+    /// Pushes the output of a vector instruction onto the stack, if it has one, and emits its
+    /// stack offset.
+    ///
+    /// Vector instructions always write their output to the stack, since a `v128` does not fit in
+    /// a register.
+    fn push_vec_op_output(&mut self, info: VecOpInfo) {
+        if let Some(output_type) = info.output_type {
+            self.push_opd(output_type);
+            self.emit_stack_offset(self.opd_stack_idx(0));
+        }
+    }
 }
 
 impl<'a> InstrVisitor for Compile<'a> {
@@ -613,6 +703,13 @@ impl<'a> InstrVisitor for Compile<'a> {
 
         self.push_block(BlockKind::Loop, type_);
 
+        // This is the start of a basic block, and the target of every branch to the loop, so this
+        // is where we check for interrupts and consume fuel for each iteration.
+        if !self.block(1).is_unreachable {
+            self.emit_interrupt_check();
+            self.emit_fuel_check();
+        }
+
         Ok(())
     }
 
@@ -725,6 +822,11 @@ impl<'a> InstrVisitor for Compile<'a> {
         // Push the `else` block on the stack.
         self.push_block(BlockKind::Block, block.type_);
 
+        // This is the start of a basic block.
+        if !self.block(1).is_unreachable {
+            self.emit_fuel_check();
+        }
+
         // Copy the list of holes for the `if` block to that of the `else` block, so that they will
         // be patched when we reach the first instruction after the end of the `else` block.
         self.block_mut(0).first_hole_idx = block.first_hole_idx;
@@ -768,6 +870,11 @@ impl<'a> InstrVisitor for Compile<'a> {
             self.push_opd(result_type);
         }
 
+        // This is the start of a basic block, unless this was the end of the function.
+        if !self.blocks.is_empty() && !self.block(0).is_unreachable {
+            self.emit_fuel_check();
+        }
+
         Ok(())
     }
 
@@ -778,6 +885,8 @@ impl<'a> InstrVisitor for Compile<'a> {
             return Ok(());
         }
 
+        self.charge_branch_fuel();
+
         // Wasm uses `u32` indices for labels, but we use `usize` indices.
         let label_idx = label_idx as usize;
 
@@ -808,6 +917,8 @@ impl<'a> InstrVisitor for Compile<'a> {
             return Ok(());
         }
 
+        self.charge_branch_fuel();
+
         // Wasm uses `u32` indices for labels, but we use `usize` indices.
         let label_idx = label_idx as usize;
 
@@ -866,6 +977,8 @@ impl<'a> InstrVisitor for Compile<'a> {
             return Ok(());
         }
 
+        self.charge_branch_fuel();
+
         // Wasm uses `u32` indices for labels, but we use `usize` indices.
         let default_label_idx = default_label_idx as usize;
 
@@ -941,6 +1054,8 @@ impl<'a> InstrVisitor for Compile<'a> {
             return Ok(());
         }
 
+        self.charge_branch_fuel();
+
         for (result_idx, result_type) in self
             .type_
             .clone()
@@ -973,6 +1088,8 @@ impl<'a> InstrVisitor for Compile<'a> {
             return Ok(());
         }
 
+        self.charge_branch_fuel();
+
         // Obtain the [`Func`] for this instruction.
         let func = self.instance.func(func_idx).unwrap();
 
@@ -1018,6 +1135,8 @@ impl<'a> InstrVisitor for Compile<'a> {
             return Ok(());
         }
 
+        self.charge_branch_fuel();
+
         // Obtain the [`Table`] for this instruction.
         let table = self.instance.table(table_idx).unwrap();
 
@@ -1151,6 +1270,27 @@ impl<'a> InstrVisitor for Compile<'a> {
 
         let type_ = type_.unwrap_or_else(|| self.opd(1).type_);
 
+        // A `v128` never lives in a register or as an immediate, so selecting between two of them
+        // is handled by a single variant that reads all its operands from the stack, and writes
+        // its output to the stack.
+        if type_ == ValType::V128 {
+            self.ensure_opd_not_imm(0);
+            self.ensure_opd_not_reg(0);
+            self.emit(select_select(
+                type_,
+                self.opd(2).kind(),
+                self.opd(1).kind(),
+                self.opd(0).kind(),
+            ));
+            for _ in 0..3 {
+                self.emit_opd(0);
+                self.pop_opd();
+            }
+            self.push_opd(type_);
+            self.emit_stack_offset(self.opd_stack_idx(0));
+            return Ok(());
+        }
+
         // The `select` instruction does not have any _{sri}{sri}i variants.
         //
         // For instance, the following sequence of instructions:
@@ -2049,6 +2189,133 @@ impl<'a> InstrVisitor for Compile<'a> {
 
         Ok(())
     }
+
+    // Vector instructions
+
+    /// Compiles a `v128.const` instruction.
+    fn visit_v128_const(&mut self, val: u128) -> Result<(), DecodeError> {
+        // Skip this instruction if it is unreachable.
+        if self.block(0).is_unreachable {
+            return Ok(());
+        }
+
+        // Unlike other constants, a `v128` is too large to be carried around as an immediate
+        // operand, so we copy it to the stack right away.
+        self.emit(exec::copy_imm_to_stack_v128 as ThreadedInstr);
+        self.emit_val(UnguardedVal::V128(val));
+
+        // Push the output onto the stack and emit its stack offset.
+        self.push_opd(ValType::V128);
+        self.emit_stack_offset(self.opd_stack_idx(0));
+
+        Ok(())
+    }
+
+    /// Compiles an `i8x16.shuffle` instruction.
+    fn visit_i8x16_shuffle(&mut self, lane_idxs: [u8; 16]) -> Result<(), DecodeError> {
+        // Skip this instruction if it is unreachable.
+        if self.block(0).is_unreachable {
+            return Ok(());
+        }
+
+        let info = VecOpInfo {
+            _name: "i8x16_shuffle",
+            input_types: &[ValType::V128, ValType::V128],
+            output_type: Some(ValType::V128),
+            instr: exec::i8x16_shuffle,
+        };
+
+        // Emit the instruction, and emit the inputs and pop them from the stack.
+        self.emit_vec_op(info);
+
+        // Emit the lane indices, 8 at a time.
+        self.emit(<[u8; 8]>::try_from(&lane_idxs[..8]).unwrap());
+        self.emit(<[u8; 8]>::try_from(&lane_idxs[8..]).unwrap());
+
+        // Push the output onto the stack and emit its stack offset.
+        self.push_vec_op_output(info);
+
+        Ok(())
+    }
+
+    /// Compiles a vector operation.
+    fn visit_vec_op(&mut self, info: VecOpInfo) -> Result<(), DecodeError> {
+        // Skip this instruction if it is unreachable.
+        if self.block(0).is_unreachable {
+            return Ok(());
+        }
+
+        // Emit the instruction, and emit the inputs and pop them from the stack.
+        self.emit_vec_op(info);
+
+        // Push the output onto the stack and emit its stack offset.
+        self.push_vec_op_output(info);
+
+        Ok(())
+    }
+
+    /// Compiles a vector operation with a lane index.
+    fn visit_vec_lane_op(&mut self, lane_idx: u8, info: VecLaneOpInfo) -> Result<(), DecodeError> {
+        // Skip this instruction if it is unreachable.
+        if self.block(0).is_unreachable {
+            return Ok(());
+        }
+
+        // Emit the instruction, and emit the inputs and pop them from the stack.
+        self.emit_vec_op(info.op);
+
+        // Emit the lane index.
+        self.emit(lane_idx);
+
+        // Push the output onto the stack and emit its stack offset.
+        self.push_vec_op_output(info.op);
+
+        Ok(())
+    }
+
+    /// Compiles a vector load or store instruction.
+    fn visit_vec_mem_op(&mut self, arg: MemArg, info: VecMemOpInfo) -> Result<(), DecodeError> {
+        // Skip this instruction if it is unreachable.
+        if self.block(0).is_unreachable {
+            return Ok(());
+        }
+
+        // Emit the instruction, and emit the inputs and pop them from the stack.
+        self.emit_vec_op(info.op);
+
+        // Emit the static offset.
+        self.emit(arg.offset);
+
+        // Push the output onto the stack and emit its stack offset.
+        self.push_vec_op_output(info.op);
+
+        Ok(())
+    }
+
+    /// Compiles a vector load or store lane instruction.
+    fn visit_vec_mem_lane_op(
+        &mut self,
+        arg: MemArg,
+        lane_idx: u8,
+        info: VecMemLaneOpInfo,
+    ) -> Result<(), DecodeError> {
+        // Skip this instruction if it is unreachable.
+        if self.block(0).is_unreachable {
+            return Ok(());
+        }
+
+        // Emit the instruction, and emit the inputs and pop them from the stack.
+        self.emit_vec_op(info.op);
+
+        // Emit the static offset and the lane index.
+        self.emit(arg.offset);
+        self.emit(lane_idx);
+
+        // Push the output onto the stack and emit its stack offset.
+        self.push_vec_op_output(info.op);
+
+        Ok(())
+    }
 }
 
 /// A local on the stack.
@@ -2405,6 +2672,10 @@ fn select_select(
             exec::select_extern_ref_iir
         }
 
+        // A `v128` is always read from the stack, so there is only one variant for it.
+        (ValType::V128, OpdKind::Stack, OpdKind::Stack, OpdKind::Stack) => exec::select_v128_sss,
+        (ValType::V128, _, _, _) => panic!("no suitable instruction found"),
+
         // The first operand is an integer or a reference, and the third operand is an integer,
         // both of which are stored in a register. Since we only have one integer register
         // available, there is no variant of this instruction that can handle this case.
@@ -2434,6 +2705,7 @@ fn select_global_get(type_: ValType) -> ThreadedInstr {
         ValType::I64 => exec::global_get_i64,
         ValType::F32 => exec::global_get_f32,
         ValType::F64 => exec::global_get_f64,
+        ValType::V128 => exec::global_get_v128,
         ValType::FuncRef => exec::global_get_func_ref,
         ValType::ExternRef => exec::global_get_extern_ref,
     }
@@ -2453,6 +2725,8 @@ fn select_global_set(type_: ValType, kind: OpdKind) -> ThreadedInstr {
         (ValType::F64, OpdKind::Stack) => exec::global_set_f64_s,
         (ValType::F64, OpdKind::Reg) => exec::global_set_f64_r,
         (ValType::F64, OpdKind::Imm) => exec::global_set_f64_i,
+        (ValType::V128, OpdKind::Stack) => exec::global_set_v128_s,
+        (ValType::V128, OpdKind::Reg | OpdKind::Imm) => panic!("no suitable instruction found"),
         (ValType::FuncRef, OpdKind::Stack) => exec::global_set_func_ref_s,
         (ValType::FuncRef, OpdKind::Reg) => exec::global_set_func_ref_r,
         (ValType::FuncRef, OpdKind::Imm) => exec::global_set_func_ref_i,
@@ -2583,6 +2857,7 @@ fn select_copy_imm_to_stack(type_: ValType) -> ThreadedInstr {
         ValType::I64 => exec::copy_imm_to_stack_i64,
         ValType::F32 => exec::copy_imm_to_stack_f32,
         ValType::F64 => exec::copy_imm_to_stack_f64,
+        ValType::V128 => exec::copy_imm_to_stack_v128,
         ValType::FuncRef => exec::copy_imm_to_stack_func_ref,
         ValType::ExternRef => exec::copy_imm_to_stack_extern_ref,
     }
@@ -2594,6 +2869,7 @@ fn select_copy_stack(type_: ValType) -> ThreadedInstr {
         ValType::I64 => exec::copy_stack_i64,
         ValType::F32 => exec::copy_stack_f32,
         ValType::F64 => exec::copy_stack_f64,
+        ValType::V128 => exec::copy_stack_v128,
         ValType::FuncRef => exec::copy_stack_func_ref,
         ValType::ExternRef => exec::copy_stack_extern_ref,
     }
@@ -2605,6 +2881,7 @@ fn select_copy_reg_to_stack(type_: ValType) -> ThreadedInstr {
         ValType::I64 => exec::copy_reg_to_stack_i64,
         ValType::F32 => exec::copy_reg_to_stack_f32,
         ValType::F64 => exec::copy_reg_to_stack_f64,
+        ValType::V128 => panic!("no suitable instruction found"),
         ValType::FuncRef => exec::copy_reg_to_stack_func_ref,
         ValType::ExternRef => exec::copy_reg_to_stack_extern_ref,
     }
@@ -233,6 +233,15 @@ impl Decode for f64 {
     }
 }
 
+impl Decode for u128 {
+    #[inline]
+    fn decode(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
+        Ok(Self::from_le_bytes(
+            decoder.read_bytes(16)?.try_into().unwrap(),
+        ))
+    }
+}
+
 impl Decode for Arc<[u8]> {
     fn decode(decoder: &mut Decoder<'_>) -> Result<Self, DecodeError> {
         Ok(decoder.decode_bytes()?.into())
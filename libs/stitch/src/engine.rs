@@ -21,14 +21,25 @@ pub struct Engine {
 impl Engine {
     /// Creates a new [`Engine`].
     pub fn new() -> Engine {
+        Engine::with_config(EngineConfig::default())
+    }
+
+    /// Creates a new [`Engine`] with the given [`EngineConfig`].
+    pub fn with_config(config: EngineConfig) -> Engine {
         Engine {
             inner: Arc::new(EngineInner {
+                config,
                 validators: Mutex::new(Pool::new()),
                 compilers: Mutex::new(Pool::new()),
             }),
         }
     }
 
+    /// Returns the [`EngineConfig`] of this [`Engine`].
+    pub fn config(&self) -> &EngineConfig {
+        &self.inner.config
+    }
+
     pub(crate) fn validate(
         &self,
         type_: &FuncType,
@@ -61,8 +72,45 @@ impl Default for Engine {
     }
 }
 
+/// Configuration for an [`Engine`].
+///
+/// These options affect how code is compiled, so they are fixed for the lifetime of the
+/// [`Engine`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct EngineConfig {
+    /// If set, compiled code consumes fuel from its [`Store`] at the given [`FuelCosts`], and traps
+    /// with [`Trap::OutOfFuel`](crate::Trap::OutOfFuel) once the [`Store`] runs out of fuel.
+    pub fuel_costs: Option<FuelCosts>,
+    /// If `true`, compiled code checks whether its [`Store`] has been interrupted on every function
+    /// entry and loop iteration, and traps with [`Trap::Interrupted`](crate::Trap::Interrupted) if
+    /// it has.
+    pub interruptible: bool,
+}
+
+/// The amount of fuel consumed by executing code.
+///
+/// Fuel is charged up front for each straight-line sequence of instructions, so code that traps
+/// halfway through such a sequence may have consumed slightly more fuel than it actually used.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FuelCosts {
+    /// The fuel consumed by every instruction.
+    pub instr: u64,
+    /// The additional fuel consumed by every branch, call, or return.
+    pub branch: u64,
+}
+
+impl Default for FuelCosts {
+    fn default() -> Self {
+        Self {
+            instr: 1,
+            branch: 0,
+        }
+    }
+}
+
 #[derive(Debug)]
 struct EngineInner {
+    config: EngineConfig,
     validators: Mutex<Pool<Validator>>,
     compilers: Mutex<Pool<Compiler>>,
 }
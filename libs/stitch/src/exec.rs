@@ -11,7 +11,7 @@ use {
         func::{Func, FuncEntity, UnguardedFunc},
         func_ref::UnguardedFuncRef,
         global::UnguardedGlobal,
-        mem::UnguardedMem,
+        mem::{Mem, UnguardedMem},
         ops::*,
         stack::{Stack, StackGuard, StackSlot},
         store::{Handle, Store, UnguardedInternedFuncType},
@@ -40,8 +40,14 @@ use {
 /// On 64-bit Windows platforms, the "C" ABI corresponds to the "win64" ABI. This ABI allows only
 /// the first 4 arguments to be passed in hardware registers, regardless of their type. This is
 /// insufficient for our needs, so on Windows platforms, we use the "sysv64" ABI instead.
+///
+/// On platforms where LLVM cannot be relied upon to perform sibling call optimisation, such as
+/// 32-bit platforms, or when the `trampoline` feature is enabled, a `ThreadedInstr` does not call
+/// the next `ThreadedInstr` directly. Instead, it saves the virtual registers to the [`Context`],
+/// and returns to a trampoline, which then calls the next `ThreadedInstr`. This is considerably
+/// slower, but does not rely on sibling call optimisation to avoid overflowing the native stack.
 
-#[cfg(not(windows))]
+#[cfg(not(all(windows, target_arch = "x86_64")))]
 pub(crate) type ThreadedInstr = unsafe extern "C" fn(
     ip: Ip,
     sp: Sp,
@@ -53,7 +59,7 @@ pub(crate) type ThreadedInstr = unsafe extern "C" fn(
     cx: Cx,
 ) -> ControlFlowBits;
 
-#[cfg(windows)]
+#[cfg(all(windows, target_arch = "x86_64"))]
 pub(crate) type ThreadedInstr = unsafe extern "sysv64" fn(
     ip: Ip,
     sp: Sp,
@@ -118,6 +124,7 @@ pub(crate) struct Context<'a> {
 #[derive(Clone, Copy, Debug)]
 pub(crate) enum ControlFlow {
     Stop,
+    Continue,
     Trap(Trap),
     Error,
 }
@@ -127,6 +134,8 @@ impl ControlFlow {
     pub(crate) fn from_bits(bits: usize) -> Option<Self> {
         if bits == 0 {
             Some(Self::Stop)
+        } else if bits == 1 {
+            Some(Self::Continue)
         } else if bits & 0x03 == 2 {
             Trap::from_usize(bits >> 2).map(Self::Trap)
         } else if bits & 0x03 == 3 {
@@ -140,6 +149,7 @@ impl ControlFlow {
     pub(crate) fn to_bits(self) -> ControlFlowBits {
         match self {
             Self::Stop => 0,
+            Self::Continue => 1,
             Self::Trap(trap) => trap.to_usize() << 2 | 2,
             Self::Error => 3,
         }
@@ -195,10 +205,10 @@ pub(crate) fn exec(
 
             // Create a trampoline for the [`WasmFuncEntity`].
             let mut trampoline = [
-                call as InstrSlot,
+                call as ThreadedInstr as usize as InstrSlot,
                 code.code.as_mut_ptr() as InstrSlot,
-                type_.call_frame_size() * mem::size_of::<StackSlot>(),
-                stop as InstrSlot,
+                (type_.call_frame_size() * mem::size_of::<StackSlot>()) as InstrSlot,
+                stop as ThreadedInstr as usize as InstrSlot,
             ];
 
             // Create an execution context.
@@ -216,9 +226,13 @@ pub(crate) fn exec(
             };
 
             // Main interpreter loop
+            //
+            // When using threaded code, the instructions call each other directly, so we only get
+            // here again once execution stops. When using a trampoline, we get here again after
+            // every instruction.
             loop {
                 match ControlFlow::from_bits(unsafe {
-                    next_instr(
+                    exec_instr(
                         context.ip,
                         context.sp,
                         context.md,
@@ -231,6 +245,7 @@ pub(crate) fn exec(
                 })
                 .unwrap()
                 {
+                    ControlFlow::Continue => {}
                     ControlFlow::Stop => {
                         stack = context.stack.take().unwrap();
 
@@ -248,7 +263,9 @@ pub(crate) fn exec(
                         return Err(trap)?;
                     }
                     ControlFlow::Error => {
-                        stack = context.stack.take().unwrap();
+                        // A host function that failed has already released the stack, so we
+                        // might have to lock it again.
+                        stack = context.stack.take().unwrap_or_else(Stack::lock);
 
                         // Reset the stack to the start of the call frame.
                         stack.set_ptr(ptr);
@@ -288,7 +305,7 @@ pub(crate) fn exec(
 // Helper macros
 
 /// A helper macro for defining a `ThreadedInstr` with the correct ABI.
-#[cfg(windows)]
+#[cfg(all(windows, target_arch = "x86_64"))]
 macro_rules! threaded_instr {
     ($name:ident(
         $ip:ident: Ip,
@@ -312,7 +329,7 @@ macro_rules! threaded_instr {
         ) -> ControlFlowBits $body
     };
 }
-#[cfg(not(windows))]
+#[cfg(not(all(windows, target_arch = "x86_64")))]
 macro_rules! threaded_instr {
     ($name:ident(
         $ip:ident: Ip,
@@ -479,10 +496,9 @@ threaded_instr!(br_table_s(
     // Read operands
     let (target_idx, ip): (u32, _) = read_stack(ip, sp);
     let (target_count, ip): (u32, _) = read_imm(ip);
-    let targets: *mut Ip = ip.cast();
 
     // Branch to target
-    let ip = *targets.add(target_idx.min(target_count) as usize);
+    let ip = *ip.add(target_idx.min(target_count) as usize).cast();
 
     // Execute next instruction
     next_instr(ip, sp, md, ms, ix, sx, dx, cx)
@@ -501,10 +517,9 @@ threaded_instr!(br_table_r(
     // Read operands
     let target_idx: u32 = read_reg(ix, sx, dx);
     let (target_count, ip): (u32, _) = read_imm(ip);
-    let targets: *mut Ip = ip.cast();
 
     // Branch to target
-    let ip = *targets.add(target_idx.min(target_count) as usize);
+    let ip = *ip.add(target_idx.min(target_count) as usize).cast();
 
     // Execute next instruction
     next_instr(ip, sp, md, ms, ix, sx, dx, cx)
@@ -580,7 +595,12 @@ threaded_instr!(call_host(
     let FuncEntity::Host(func) = func.as_ref() else {
         hint::unreachable_unchecked();
     };
-    let stack = match func.trampoline().clone().call((*cx).store, stack) {
+    let caller_mem = (*cx)
+        .store
+        .replace_caller_mem(mem.map(|mem| Mem::from_unguarded(mem, (*cx).store.id())));
+    let result = func.trampoline().clone().call((*cx).store, stack);
+    (*cx).store.replace_caller_mem(caller_mem);
+    let stack = match result {
         Ok(stack) => stack,
         Err(error) => {
             (*cx).error = Some(error);
@@ -660,7 +680,12 @@ threaded_instr!(call_indirect(
         FuncEntity::Host(func) => {
             let mut stack = (*cx).stack.take().unwrap_unchecked();
             stack.set_ptr(sp.cast::<u8>().add(stack_offset).cast());
-            let stack = match func.trampoline().clone().call((*cx).store, stack) {
+            let caller_mem = (*cx)
+                .store
+                .replace_caller_mem(mem.map(|mem| Mem::from_unguarded(mem, (*cx).store.id())));
+            let result = func.trampoline().clone().call((*cx).store, stack);
+            (*cx).store.replace_caller_mem(caller_mem);
+            let stack = match result {
                 Ok(stack) => stack,
                 Err(error) => {
                     (*cx).error = Some(error);
@@ -1306,6 +1331,31 @@ select!(
     UnguardedExternRef
 );
 
+threaded_instr!(select_v128_sss(
+    ip: Ip,
+    sp: Sp,
+    md: Md,
+    ms: Ms,
+    ix: Ix,
+    sx: Sx,
+    dx: Dx,
+    cx: Cx,
+) -> ControlFlowBits {
+    // Read operands
+    let (cond, ip): (u32, _) = read_stack(ip, sp);
+    let (x1, ip): (u128, _) = read_stack(ip, sp);
+    let (x0, ip): (u128, _) = read_stack(ip, sp);
+
+    // Perform operation
+    let y = if cond != 0 { x0 } else { x1 };
+
+    // Write result
+    let ip = write_stack(ip, sp, y);
+
+    // Execute next instruction
+    next_instr(ip, sp, md, ms, ix, sx, dx, cx)
+});
+
 // Variable instructions
 
 macro_rules! global_get {
@@ -1343,6 +1393,7 @@ global_get!(global_get_i32, i32);
 global_get!(global_get_i64, i64);
 global_get!(global_get_f32, f32);
 global_get!(global_get_f64, f64);
+global_get!(global_get_v128, u128);
 global_get!(global_get_func_ref, UnguardedFuncRef);
 global_get!(global_get_extern_ref, UnguardedExternRef);
 
@@ -1429,6 +1480,30 @@ global_set!(global_set_i32_s, global_set_i32_r, global_set_i32_i, i32);
 global_set!(global_set_i64_s, global_set_i64_r, global_set_i64_i, i64);
 global_set!(global_set_f32_s, global_set_f32_r, global_set_f32_i, f32);
 global_set!(global_set_f64_s, global_set_f64_r, global_set_f64_i, f64);
+threaded_instr!(global_set_v128_s(
+    ip: Ip,
+    sp: Sp,
+    md: Md,
+    ms: Ms,
+    ix: Ix,
+    sx: Sx,
+    dx: Dx,
+    cx: Cx,
+) -> ControlFlowBits {
+    // Read operands
+    let (val, ip): (u128, _) = read_stack(ip, sp);
+    let (mut global, ip): (UnguardedGlobal, _) = read_imm(ip);
+
+    // Perform operation
+    global
+        .as_mut()
+        .downcast_mut::<u128>()
+        .unwrap_unchecked()
+        .set(val);
+
+    // Execute next instruction
+    next_instr(ip, sp, md, ms, ix, sx, dx, cx)
+});
 global_set!(
     global_set_func_ref_s,
     global_set_func_ref_r,
@@ -1855,12 +1930,21 @@ macro_rules! table_grow {
             let (mut table, ip): (UnguardedTable, _) = read_imm(ip);
 
             // Perform operation
-            let old_size = table
-                .as_mut()
-                .downcast_mut::<$T>()
-                .unwrap_unchecked()
-                .grow(val, count)
-                .unwrap_or(u32::MAX);
+            let limits = table.as_ref().limits();
+            let is_allowed = match limits.min.checked_add(count) {
+                Some(desired) => (*cx).store.table_growing(limits.min, desired, limits.max),
+                None => true,
+            };
+            let old_size = if is_allowed {
+                table
+                    .as_mut()
+                    .downcast_mut::<$T>()
+                    .unwrap_unchecked()
+                    .grow(val, count)
+                    .unwrap_or(u32::MAX)
+            } else {
+                u32::MAX
+            };
 
             // Write result
             let ip = write_stack(ip, sp, old_size);
@@ -2562,11 +2646,19 @@ threaded_instr!(memory_grow(
     let (mut mem, ip): (UnguardedMem, _) = read_imm(ip);
 
     // Perform operation
-    (*cx).stack.as_mut().unwrap_unchecked().set_ptr(sp);
-    let old_size = mem
-        .as_mut()
-        .grow_with_stack(count, (*cx).stack.as_mut().unwrap_unchecked())
-        .unwrap_or(u32::MAX);
+    let limits = mem.as_ref().limits();
+    let is_allowed = match limits.min.checked_add(count) {
+        Some(desired) => (*cx).store.mem_growing(limits.min, desired, limits.max),
+        None => true,
+    };
+    let old_size = if is_allowed {
+        (*cx).stack.as_mut().unwrap_unchecked().set_ptr(sp);
+        mem.as_mut()
+            .grow_with_stack(count, (*cx).stack.as_mut().unwrap_unchecked())
+            .unwrap_or(u32::MAX)
+    } else {
+        u32::MAX
+    };
     let bytes = mem.as_mut().bytes_mut();
     let md = bytes.as_mut_ptr();
     let ms = bytes.len() as u32;
@@ -3817,6 +3909,696 @@ un_op!(
     <u64 as Trunc<f64>>::trunc_sat
 );
 
+// Vector instructions
+
+macro_rules! vec_op {
+    ($vec_op:ident, |$($x:ident: $T:ty),*| $([$($imm:ident: $I:ty),*])? -> $U:ty $f:block) => {
+        threaded_instr!($vec_op(
+            ip: Ip,
+            sp: Sp,
+            md: Md,
+            ms: Ms,
+            ix: Ix,
+            sx: Sx,
+            dx: Dx,
+            cx: Cx,
+        ) -> ControlFlowBits {
+            // Read operands
+            $(let ($x, ip): ($T, _) = read_stack(ip, sp);)*
+            $($(let ($imm, ip): ($I, _) = read_imm(ip);)*)?
+
+            // Perform operation
+            fn op($($x: $T,)* $($($imm: $I,)*)?) -> Result<$U, Trap> $f
+            let y = r#try!(op($($x,)* $($($imm,)*)?));
+
+            // Write result
+            let ip = write_stack(ip, sp, y);
+
+            // Execute next instruction
+            next_instr(ip, sp, md, ms, ix, sx, dx, cx)
+        });
+    };
+}
+
+vec_op!(i8x16_swizzle, |x: u128, y: u128| -> u128 {
+    let x = to_lanes::<u8, 16>(x);
+    map_lanes::<u8, u8, 16>(y, |i| Ok(x.get(i as usize).copied().unwrap_or(0)))
+});
+vec_op!(i8x16_shuffle, |x: u128, y: u128| [lo: [u8; 8], hi: [u8; 8]] -> u128 {
+    let mut lanes = [0u8; 32];
+    lanes[..16].copy_from_slice(&x.to_le_bytes());
+    lanes[16..].copy_from_slice(&y.to_le_bytes());
+    let lane_idxs = from_lanes::<u8, 16>(std::array::from_fn(|i| {
+        if i < 8 {
+            lo[i]
+        } else {
+            hi[i - 8]
+        }
+    }));
+    map_lanes::<u8, u8, 16>(lane_idxs, |i| Ok(lanes[i as usize]))
+});
+
+vec_op!(i8x16_splat, |x: i32| -> u128 {
+    Ok(from_lanes([x as i8; 16]))
+});
+vec_op!(i16x8_splat, |x: i32| -> u128 {
+    Ok(from_lanes([x as i16; 8]))
+});
+vec_op!(i32x4_splat, |x: i32| -> u128 { Ok(from_lanes([x; 4])) });
+vec_op!(i64x2_splat, |x: i64| -> u128 { Ok(from_lanes([x; 2])) });
+vec_op!(f32x4_splat, |x: f32| -> u128 { Ok(from_lanes([x; 4])) });
+vec_op!(f64x2_splat, |x: f64| -> u128 { Ok(from_lanes([x; 2])) });
+
+macro_rules! extract_lane {
+    ($extract_lane:ident, $T:ty, $N:literal, $U:ty) => {
+        vec_op!($extract_lane, |x: u128| [lane_idx: u8] -> $U {
+            Ok(to_lanes::<$T, $N>(x)[lane_idx as usize] as $U)
+        });
+    };
+}
+
+extract_lane!(i8x16_extract_lane_s, i8, 16, i32);
+extract_lane!(i8x16_extract_lane_u, u8, 16, i32);
+extract_lane!(i16x8_extract_lane_s, i16, 8, i32);
+extract_lane!(i16x8_extract_lane_u, u16, 8, i32);
+extract_lane!(i32x4_extract_lane, i32, 4, i32);
+extract_lane!(i64x2_extract_lane, i64, 2, i64);
+extract_lane!(f32x4_extract_lane, f32, 4, f32);
+extract_lane!(f64x2_extract_lane, f64, 2, f64);
+
+macro_rules! replace_lane {
+    ($replace_lane:ident, $T:ty, $N:literal, $U:ty) => {
+        vec_op!($replace_lane, |x: u128, y: $U| [lane_idx: u8] -> u128 {
+            let mut lanes = to_lanes::<$T, $N>(x);
+            lanes[lane_idx as usize] = y as $T;
+            Ok(from_lanes(lanes))
+        });
+    };
+}
+
+replace_lane!(i8x16_replace_lane, i8, 16, i32);
+replace_lane!(i16x8_replace_lane, i16, 8, i32);
+replace_lane!(i32x4_replace_lane, i32, 4, i32);
+replace_lane!(i64x2_replace_lane, i64, 2, i64);
+replace_lane!(f32x4_replace_lane, f32, 4, f32);
+replace_lane!(f64x2_replace_lane, f64, 2, f64);
+
+macro_rules! vec_un_op {
+    ($vec_un_op:ident, $T:ty, $U:ty, $N:literal, $f:expr) => {
+        vec_op!($vec_un_op, |x: u128| -> u128 {
+            map_lanes::<$T, $U, $N>(x, $f)
+        });
+    };
+}
+
+macro_rules! vec_bin_op {
+    ($vec_bin_op:ident, $T:ty, $U:ty, $N:literal, $f:expr) => {
+        vec_op!($vec_bin_op, |x: u128, y: u128| -> u128 {
+            zip_lanes::<$T, $U, $N>(x, y, $f)
+        });
+    };
+}
+
+macro_rules! vec_shift_op {
+    ($vec_shift_op:ident, $T:ty, $N:literal, $f:expr) => {
+        vec_op!($vec_shift_op, |x: u128, y: i32| -> u128 {
+            map_lanes::<$T, $T, $N>(x, |x| Ok($f(x, y as u32)))
+        });
+    };
+}
+
+vec_bin_op!(i8x16_eq, i8, i8, 16, |x, y| lane_mask(x == y));
+vec_bin_op!(i8x16_ne, i8, i8, 16, |x, y| lane_mask(x != y));
+vec_bin_op!(i8x16_lt_s, i8, i8, 16, |x, y| lane_mask(x < y));
+vec_bin_op!(i8x16_lt_u, u8, i8, 16, |x, y| lane_mask(x < y));
+vec_bin_op!(i8x16_gt_s, i8, i8, 16, |x, y| lane_mask(x > y));
+vec_bin_op!(i8x16_gt_u, u8, i8, 16, |x, y| lane_mask(x > y));
+vec_bin_op!(i8x16_le_s, i8, i8, 16, |x, y| lane_mask(x <= y));
+vec_bin_op!(i8x16_le_u, u8, i8, 16, |x, y| lane_mask(x <= y));
+vec_bin_op!(i8x16_ge_s, i8, i8, 16, |x, y| lane_mask(x >= y));
+vec_bin_op!(i8x16_ge_u, u8, i8, 16, |x, y| lane_mask(x >= y));
+vec_bin_op!(i16x8_eq, i16, i16, 8, |x, y| lane_mask(x == y));
+vec_bin_op!(i16x8_ne, i16, i16, 8, |x, y| lane_mask(x != y));
+vec_bin_op!(i16x8_lt_s, i16, i16, 8, |x, y| lane_mask(x < y));
+vec_bin_op!(i16x8_lt_u, u16, i16, 8, |x, y| lane_mask(x < y));
+vec_bin_op!(i16x8_gt_s, i16, i16, 8, |x, y| lane_mask(x > y));
+vec_bin_op!(i16x8_gt_u, u16, i16, 8, |x, y| lane_mask(x > y));
+vec_bin_op!(i16x8_le_s, i16, i16, 8, |x, y| lane_mask(x <= y));
+vec_bin_op!(i16x8_le_u, u16, i16, 8, |x, y| lane_mask(x <= y));
+vec_bin_op!(i16x8_ge_s, i16, i16, 8, |x, y| lane_mask(x >= y));
+vec_bin_op!(i16x8_ge_u, u16, i16, 8, |x, y| lane_mask(x >= y));
+vec_bin_op!(i32x4_eq, i32, i32, 4, |x, y| lane_mask(x == y));
+vec_bin_op!(i32x4_ne, i32, i32, 4, |x, y| lane_mask(x != y));
+vec_bin_op!(i32x4_lt_s, i32, i32, 4, |x, y| lane_mask(x < y));
+vec_bin_op!(i32x4_lt_u, u32, i32, 4, |x, y| lane_mask(x < y));
+vec_bin_op!(i32x4_gt_s, i32, i32, 4, |x, y| lane_mask(x > y));
+vec_bin_op!(i32x4_gt_u, u32, i32, 4, |x, y| lane_mask(x > y));
+vec_bin_op!(i32x4_le_s, i32, i32, 4, |x, y| lane_mask(x <= y));
+vec_bin_op!(i32x4_le_u, u32, i32, 4, |x, y| lane_mask(x <= y));
+vec_bin_op!(i32x4_ge_s, i32, i32, 4, |x, y| lane_mask(x >= y));
+vec_bin_op!(i32x4_ge_u, u32, i32, 4, |x, y| lane_mask(x >= y));
+vec_bin_op!(i64x2_eq, i64, i64, 2, |x, y| lane_mask(x == y));
+vec_bin_op!(i64x2_ne, i64, i64, 2, |x, y| lane_mask(x != y));
+vec_bin_op!(i64x2_lt_s, i64, i64, 2, |x, y| lane_mask(x < y));
+vec_bin_op!(i64x2_gt_s, i64, i64, 2, |x, y| lane_mask(x > y));
+vec_bin_op!(i64x2_le_s, i64, i64, 2, |x, y| lane_mask(x <= y));
+vec_bin_op!(i64x2_ge_s, i64, i64, 2, |x, y| lane_mask(x >= y));
+vec_bin_op!(f32x4_eq, f32, i32, 4, |x, y| lane_mask(x == y));
+vec_bin_op!(f32x4_ne, f32, i32, 4, |x, y| lane_mask(x != y));
+vec_bin_op!(f32x4_lt, f32, i32, 4, |x, y| lane_mask(x < y));
+vec_bin_op!(f32x4_gt, f32, i32, 4, |x, y| lane_mask(x > y));
+vec_bin_op!(f32x4_le, f32, i32, 4, |x, y| lane_mask(x <= y));
+vec_bin_op!(f32x4_ge, f32, i32, 4, |x, y| lane_mask(x >= y));
+vec_bin_op!(f64x2_eq, f64, i64, 2, |x, y| lane_mask(x == y));
+vec_bin_op!(f64x2_ne, f64, i64, 2, |x, y| lane_mask(x != y));
+vec_bin_op!(f64x2_lt, f64, i64, 2, |x, y| lane_mask(x < y));
+vec_bin_op!(f64x2_gt, f64, i64, 2, |x, y| lane_mask(x > y));
+vec_bin_op!(f64x2_le, f64, i64, 2, |x, y| lane_mask(x <= y));
+vec_bin_op!(f64x2_ge, f64, i64, 2, |x, y| lane_mask(x >= y));
+
+vec_op!(v128_not, |x: u128| -> u128 { Ok(!x) });
+vec_op!(v128_and, |x: u128, y: u128| -> u128 { Ok(x & y) });
+vec_op!(v128_andnot, |x: u128, y: u128| -> u128 { Ok(x & !y) });
+vec_op!(v128_or, |x: u128, y: u128| -> u128 { Ok(x | y) });
+vec_op!(v128_xor, |x: u128, y: u128| -> u128 { Ok(x ^ y) });
+vec_op!(v128_bitselect, |x: u128, y: u128, c: u128| -> u128 {
+    Ok(x & c | y & !c)
+});
+vec_op!(v128_any_true, |x: u128| -> i32 { Ok((x != 0).into()) });
+
+macro_rules! all_true {
+    ($all_true:ident, $T:ty, $N:literal) => {
+        vec_op!($all_true, |x: u128| -> i32 {
+            Ok(to_lanes::<$T, $N>(x).iter().all(|&x| x != 0).into())
+        });
+    };
+}
+
+all_true!(i8x16_all_true, i8, 16);
+all_true!(i16x8_all_true, i16, 8);
+all_true!(i32x4_all_true, i32, 4);
+all_true!(i64x2_all_true, i64, 2);
+
+macro_rules! bitmask {
+    ($bitmask:ident, $T:ty, $N:literal) => {
+        vec_op!($bitmask, |x: u128| -> i32 {
+            Ok(to_lanes::<$T, $N>(x)
+                .iter()
+                .enumerate()
+                .fold(0, |mask, (i, &x)| mask | ((x < 0) as i32) << i))
+        });
+    };
+}
+
+bitmask!(i8x16_bitmask, i8, 16);
+bitmask!(i16x8_bitmask, i16, 8);
+bitmask!(i32x4_bitmask, i32, 4);
+bitmask!(i64x2_bitmask, i64, 2);
+
+macro_rules! narrow {
+    ($narrow:ident, $T:ty, $U:ty, $N:literal, $M:literal) => {
+        vec_op!($narrow, |x: u128, y: u128| -> u128 {
+            let x = to_lanes::<$T, $N>(x);
+            let y = to_lanes::<$T, $N>(y);
+            Ok(from_lanes::<$U, $M>(std::array::from_fn(|i| {
+                let z = if i < $N { x[i] } else { y[i - $N] };
+                z.clamp(<$U>::MIN as $T, <$U>::MAX as $T) as $U
+            })))
+        });
+    };
+}
+
+narrow!(i8x16_narrow_i16x8_s, i16, i8, 8, 16);
+narrow!(i8x16_narrow_i16x8_u, i16, u8, 8, 16);
+narrow!(i16x8_narrow_i32x4_s, i32, i16, 4, 8);
+narrow!(i16x8_narrow_i32x4_u, i32, u16, 4, 8);
+
+macro_rules! extend {
+    ($extend:ident, $T:ty, $U:ty, $N:literal, $high:literal) => {
+        vec_op!($extend, |x: u128| -> u128 {
+            Ok(from_lanes::<$U, $N>(
+                to_half_lanes::<$T, $N>(x, $high).map(|x| x as $U),
+            ))
+        });
+    };
+}
+
+extend!(i16x8_extend_low_i8x16_s, i8, i16, 8, false);
+extend!(i16x8_extend_high_i8x16_s, i8, i16, 8, true);
+extend!(i16x8_extend_low_i8x16_u, u8, u16, 8, false);
+extend!(i16x8_extend_high_i8x16_u, u8, u16, 8, true);
+extend!(i32x4_extend_low_i16x8_s, i16, i32, 4, false);
+extend!(i32x4_extend_high_i16x8_s, i16, i32, 4, true);
+extend!(i32x4_extend_low_i16x8_u, u16, u32, 4, false);
+extend!(i32x4_extend_high_i16x8_u, u16, u32, 4, true);
+extend!(i64x2_extend_low_i32x4_s, i32, i64, 2, false);
+extend!(i64x2_extend_high_i32x4_s, i32, i64, 2, true);
+extend!(i64x2_extend_low_i32x4_u, u32, u64, 2, false);
+extend!(i64x2_extend_high_i32x4_u, u32, u64, 2, true);
+
+macro_rules! extmul {
+    ($extmul:ident, $T:ty, $U:ty, $N:literal, $high:literal) => {
+        vec_op!($extmul, |x: u128, y: u128| -> u128 {
+            let x = to_half_lanes::<$T, $N>(x, $high);
+            let y = to_half_lanes::<$T, $N>(y, $high);
+            Ok(from_lanes::<$U, $N>(std::array::from_fn(|i| {
+                (x[i] as $U).wrapping_mul(y[i] as $U)
+            })))
+        });
+    };
+}
+
+extmul!(i16x8_extmul_low_i8x16_s, i8, i16, 8, false);
+extmul!(i16x8_extmul_high_i8x16_s, i8, i16, 8, true);
+extmul!(i16x8_extmul_low_i8x16_u, u8, u16, 8, false);
+extmul!(i16x8_extmul_high_i8x16_u, u8, u16, 8, true);
+extmul!(i32x4_extmul_low_i16x8_s, i16, i32, 4, false);
+extmul!(i32x4_extmul_high_i16x8_s, i16, i32, 4, true);
+extmul!(i32x4_extmul_low_i16x8_u, u16, u32, 4, false);
+extmul!(i32x4_extmul_high_i16x8_u, u16, u32, 4, true);
+extmul!(i64x2_extmul_low_i32x4_s, i32, i64, 2, false);
+extmul!(i64x2_extmul_high_i32x4_s, i32, i64, 2, true);
+extmul!(i64x2_extmul_low_i32x4_u, u32, u64, 2, false);
+extmul!(i64x2_extmul_high_i32x4_u, u32, u64, 2, true);
+
+macro_rules! extadd_pairwise {
+    ($extadd_pairwise:ident, $T:ty, $U:ty, $N:literal) => {
+        vec_op!($extadd_pairwise, |x: u128| -> u128 {
+            let x = to_lanes::<$T, { 2 * $N }>(x);
+            Ok(from_lanes::<$U, $N>(std::array::from_fn(|i| {
+                x[2 * i] as $U + x[2 * i + 1] as $U
+            })))
+        });
+    };
+}
+
+extadd_pairwise!(i16x8_extadd_pairwise_i8x16_s, i8, i16, 8);
+extadd_pairwise!(i16x8_extadd_pairwise_i8x16_u, u8, u16, 8);
+extadd_pairwise!(i32x4_extadd_pairwise_i16x8_s, i16, i32, 4);
+extadd_pairwise!(i32x4_extadd_pairwise_i16x8_u, u16, u32, 4);
+
+vec_op!(i32x4_dot_i16x8_s, |x: u128, y: u128| -> u128 {
+    let x = to_lanes::<i16, 8>(x);
+    let y = to_lanes::<i16, 8>(y);
+    Ok(from_lanes::<i32, 4>(std::array::from_fn(|i| {
+        (x[2 * i] as i32 * y[2 * i] as i32).wrapping_add(x[2 * i + 1] as i32 * y[2 * i + 1] as i32)
+    })))
+});
+
+vec_un_op!(i8x16_abs, i8, i8, 16, |x: i8| Ok(x.wrapping_abs()));
+vec_un_op!(i8x16_neg, i8, i8, 16, |x: i8| Ok(x.wrapping_neg()));
+vec_un_op!(i8x16_popcnt, u8, u8, 16, |x: u8| Ok(x.count_ones() as u8));
+vec_shift_op!(i8x16_shl, i8, 16, i8::wrapping_shl);
+vec_shift_op!(i8x16_shr_s, i8, 16, i8::wrapping_shr);
+vec_shift_op!(i8x16_shr_u, u8, 16, u8::wrapping_shr);
+vec_bin_op!(i8x16_add, i8, i8, 16, |x: i8, y| Ok(x.wrapping_add(y)));
+vec_bin_op!(i8x16_add_sat_s, i8, i8, 16, |x: i8, y| Ok(
+    x.saturating_add(y)
+));
+vec_bin_op!(i8x16_add_sat_u, u8, u8, 16, |x: u8, y| Ok(
+    x.saturating_add(y)
+));
+vec_bin_op!(i8x16_sub, i8, i8, 16, |x: i8, y| Ok(x.wrapping_sub(y)));
+vec_bin_op!(i8x16_sub_sat_s, i8, i8, 16, |x: i8, y| Ok(
+    x.saturating_sub(y)
+));
+vec_bin_op!(i8x16_sub_sat_u, u8, u8, 16, |x: u8, y| Ok(
+    x.saturating_sub(y)
+));
+vec_bin_op!(i8x16_min_s, i8, i8, 16, |x: i8, y| Ok(x.min(y)));
+vec_bin_op!(i8x16_min_u, u8, u8, 16, |x: u8, y| Ok(x.min(y)));
+vec_bin_op!(i8x16_max_s, i8, i8, 16, |x: i8, y| Ok(x.max(y)));
+vec_bin_op!(i8x16_max_u, u8, u8, 16, |x: u8, y| Ok(x.max(y)));
+vec_bin_op!(i8x16_avgr_u, u8, u8, 16, |x, y| Ok(
+    (x as u16 + y as u16).div_ceil(2) as u8
+));
+
+vec_un_op!(i16x8_abs, i16, i16, 8, |x: i16| Ok(x.wrapping_abs()));
+vec_un_op!(i16x8_neg, i16, i16, 8, |x: i16| Ok(x.wrapping_neg()));
+vec_bin_op!(i16x8_q15mulr_sat_s, i16, i16, 8, |x, y| {
+    Ok(((x as i32 * y as i32 + 0x4000) >> 15).clamp(i16::MIN as i32, i16::MAX as i32) as i16)
+});
+vec_shift_op!(i16x8_shl, i16, 8, i16::wrapping_shl);
+vec_shift_op!(i16x8_shr_s, i16, 8, i16::wrapping_shr);
+vec_shift_op!(i16x8_shr_u, u16, 8, u16::wrapping_shr);
+vec_bin_op!(i16x8_add, i16, i16, 8, |x: i16, y| Ok(x.wrapping_add(y)));
+vec_bin_op!(i16x8_add_sat_s, i16, i16, 8, |x: i16, y| Ok(
+    x.saturating_add(y)
+));
+vec_bin_op!(i16x8_add_sat_u, u16, u16, 8, |x: u16, y| Ok(
+    x.saturating_add(y)
+));
+vec_bin_op!(i16x8_sub, i16, i16, 8, |x: i16, y| Ok(x.wrapping_sub(y)));
+vec_bin_op!(i16x8_sub_sat_s, i16, i16, 8, |x: i16, y| Ok(
+    x.saturating_sub(y)
+));
+vec_bin_op!(i16x8_sub_sat_u, u16, u16, 8, |x: u16, y| Ok(
+    x.saturating_sub(y)
+));
+vec_bin_op!(i16x8_mul, i16, i16, 8, |x: i16, y| Ok(x.wrapping_mul(y)));
+vec_bin_op!(i16x8_min_s, i16, i16, 8, |x: i16, y| Ok(x.min(y)));
+vec_bin_op!(i16x8_min_u, u16, u16, 8, |x: u16, y| Ok(x.min(y)));
+vec_bin_op!(i16x8_max_s, i16, i16, 8, |x: i16, y| Ok(x.max(y)));
+vec_bin_op!(i16x8_max_u, u16, u16, 8, |x: u16, y| Ok(x.max(y)));
+vec_bin_op!(i16x8_avgr_u, u16, u16, 8, |x, y| Ok(
+    (x as u32 + y as u32).div_ceil(2) as u16
+));
+
+vec_un_op!(i32x4_abs, i32, i32, 4, |x: i32| Ok(x.wrapping_abs()));
+vec_un_op!(i32x4_neg, i32, i32, 4, |x: i32| Ok(x.wrapping_neg()));
+vec_shift_op!(i32x4_shl, i32, 4, i32::wrapping_shl);
+vec_shift_op!(i32x4_shr_s, i32, 4, i32::wrapping_shr);
+vec_shift_op!(i32x4_shr_u, u32, 4, u32::wrapping_shr);
+vec_bin_op!(i32x4_add, i32, i32, 4, IntOps::add);
+vec_bin_op!(i32x4_sub, i32, i32, 4, IntOps::sub);
+vec_bin_op!(i32x4_mul, i32, i32, 4, IntOps::mul);
+vec_bin_op!(i32x4_min_s, i32, i32, 4, |x: i32, y| Ok(x.min(y)));
+vec_bin_op!(i32x4_min_u, u32, u32, 4, |x: u32, y| Ok(x.min(y)));
+vec_bin_op!(i32x4_max_s, i32, i32, 4, |x: i32, y| Ok(x.max(y)));
+vec_bin_op!(i32x4_max_u, u32, u32, 4, |x: u32, y| Ok(x.max(y)));
+
+vec_un_op!(i64x2_abs, i64, i64, 2, |x: i64| Ok(x.wrapping_abs()));
+vec_un_op!(i64x2_neg, i64, i64, 2, |x: i64| Ok(x.wrapping_neg()));
+vec_shift_op!(i64x2_shl, i64, 2, i64::wrapping_shl);
+vec_shift_op!(i64x2_shr_s, i64, 2, i64::wrapping_shr);
+vec_shift_op!(i64x2_shr_u, u64, 2, u64::wrapping_shr);
+vec_bin_op!(i64x2_add, i64, i64, 2, IntOps::add);
+vec_bin_op!(i64x2_sub, i64, i64, 2, IntOps::sub);
+vec_bin_op!(i64x2_mul, i64, i64, 2, IntOps::mul);
+
+vec_un_op!(f32x4_abs, f32, f32, 4, FloatOps::abs);
+vec_un_op!(f32x4_neg, f32, f32, 4, FloatOps::neg);
+vec_un_op!(f32x4_sqrt, f32, f32, 4, FloatOps::sqrt);
+vec_un_op!(f32x4_ceil, f32, f32, 4, FloatOps::ceil);
+vec_un_op!(f32x4_floor, f32, f32, 4, FloatOps::floor);
+vec_un_op!(f32x4_trunc, f32, f32, 4, FloatOps::trunc);
+vec_un_op!(f32x4_nearest, f32, f32, 4, FloatOps::nearest);
+vec_bin_op!(f32x4_add, f32, f32, 4, FloatOps::add);
+vec_bin_op!(f32x4_sub, f32, f32, 4, FloatOps::sub);
+vec_bin_op!(f32x4_mul, f32, f32, 4, FloatOps::mul);
+vec_bin_op!(f32x4_div, f32, f32, 4, FloatOps::div);
+vec_bin_op!(f32x4_min, f32, f32, 4, FloatOps::min);
+vec_bin_op!(f32x4_max, f32, f32, 4, FloatOps::max);
+vec_bin_op!(f32x4_pmin, f32, f32, 4, |x, y| Ok(if y < x {
+    y
+} else {
+    x
+}));
+vec_bin_op!(f32x4_pmax, f32, f32, 4, |x, y| Ok(if x < y {
+    y
+} else {
+    x
+}));
+
+vec_un_op!(f64x2_abs, f64, f64, 2, FloatOps::abs);
+vec_un_op!(f64x2_neg, f64, f64, 2, FloatOps::neg);
+vec_un_op!(f64x2_sqrt, f64, f64, 2, FloatOps::sqrt);
+vec_un_op!(f64x2_ceil, f64, f64, 2, FloatOps::ceil);
+vec_un_op!(f64x2_floor, f64, f64, 2, FloatOps::floor);
+vec_un_op!(f64x2_trunc, f64, f64, 2, FloatOps::trunc);
+vec_un_op!(f64x2_nearest, f64, f64, 2, FloatOps::nearest);
+vec_bin_op!(f64x2_add, f64, f64, 2, FloatOps::add);
+vec_bin_op!(f64x2_sub, f64, f64, 2, FloatOps::sub);
+vec_bin_op!(f64x2_mul, f64, f64, 2, FloatOps::mul);
+vec_bin_op!(f64x2_div, f64, f64, 2, FloatOps::div);
+vec_bin_op!(f64x2_min, f64, f64, 2, FloatOps::min);
+vec_bin_op!(f64x2_max, f64, f64, 2, FloatOps::max);
+vec_bin_op!(f64x2_pmin, f64, f64, 2, |x, y| Ok(if y < x {
+    y
+} else {
+    x
+}));
+vec_bin_op!(f64x2_pmax, f64, f64, 2, |x, y| Ok(if x < y {
+    y
+} else {
+    x
+}));
+
+vec_un_op!(
+    i32x4_trunc_sat_f32x4_s,
+    f32,
+    i32,
+    4,
+    <i32 as Trunc<f32>>::trunc_sat
+);
+vec_un_op!(
+    i32x4_trunc_sat_f32x4_u,
+    f32,
+    u32,
+    4,
+    <u32 as Trunc<f32>>::trunc_sat
+);
+vec_un_op!(
+    f32x4_convert_i32x4_s,
+    i32,
+    f32,
+    4,
+    <f32 as Convert<i32>>::convert
+);
+vec_un_op!(
+    f32x4_convert_i32x4_u,
+    u32,
+    f32,
+    4,
+    <f32 as Convert<u32>>::convert
+);
+
+macro_rules! vec_narrow_conversion {
+    ($conversion:ident, $T:ty, $U:ty, $f:expr) => {
+        vec_op!($conversion, |x: u128| -> u128 {
+            let [x0, x1] = to_lanes::<$T, 2>(x);
+            Ok(from_lanes::<$U, 4>([
+                $f(x0)?,
+                $f(x1)?,
+                <$U>::default(),
+                <$U>::default(),
+            ]))
+        });
+    };
+}
+
+vec_narrow_conversion!(
+    i32x4_trunc_sat_f64x2_s_zero,
+    f64,
+    i32,
+    <i32 as Trunc<f64>>::trunc_sat
+);
+vec_narrow_conversion!(
+    i32x4_trunc_sat_f64x2_u_zero,
+    f64,
+    u32,
+    <u32 as Trunc<f64>>::trunc_sat
+);
+vec_narrow_conversion!(
+    f32x4_demote_f64x2_zero,
+    f64,
+    f32,
+    <f32 as Demote<f64>>::demote
+);
+
+macro_rules! vec_widen_conversion {
+    ($conversion:ident, $T:ty, $U:ty, $f:expr) => {
+        vec_op!($conversion, |x: u128| -> u128 {
+            let [x0, x1] = to_half_lanes::<$T, 2>(x, false);
+            Ok(from_lanes::<$U, 2>([$f(x0)?, $f(x1)?]))
+        });
+    };
+}
+
+vec_widen_conversion!(
+    f64x2_convert_low_i32x4_s,
+    i32,
+    f64,
+    <f64 as Convert<i32>>::convert
+);
+vec_widen_conversion!(
+    f64x2_convert_low_i32x4_u,
+    u32,
+    f64,
+    <f64 as Convert<u32>>::convert
+);
+vec_widen_conversion!(
+    f64x2_promote_low_f32x4,
+    f32,
+    f64,
+    <f64 as Promote<f32>>::promote
+);
+
+macro_rules! vec_load {
+    ($vec_load:ident, $N:expr, $f:expr) => {
+        threaded_instr!($vec_load(
+            ip: Ip,
+            sp: Sp,
+            md: Md,
+            ms: Ms,
+            ix: Ix,
+            sx: Sx,
+            dx: Dx,
+            cx: Cx,
+        ) -> ControlFlowBits {
+            // Read operands
+            let (dyn_offset, ip): (u32, _) = read_stack(ip, sp);
+            let (static_offset, ip): (u32, _) = read_imm(ip);
+
+            // Perform operation
+            let offset = dyn_offset as u64 + static_offset as u64;
+            if offset + $N as u64 > ms as u64 {
+                return ControlFlow::Trap(Trap::MemAccessOutOfBounds).to_bits();
+            }
+            let mut bytes = [0u8; $N];
+            ptr::copy_nonoverlapping(md.add(offset as usize), bytes.as_mut_ptr(), bytes.len());
+            let y: u128 = $f(bytes);
+
+            // Write result
+            let ip = write_stack(ip, sp, y);
+
+            // Execute next instruction
+            next_instr(ip, sp, md, ms, ix, sx, dx, cx)
+        });
+    };
+}
+
+macro_rules! vec_load_extend {
+    ($vec_load_extend:ident, $T:ty, $U:ty, $N:literal) => {
+        vec_load!($vec_load_extend, 8, |bytes| {
+            from_lanes::<$U, $N>(
+                to_half_lanes::<$T, $N>(u64::from_le_bytes(bytes) as u128, false).map(|x| x as $U),
+            )
+        });
+    };
+}
+
+macro_rules! vec_load_splat {
+    ($vec_load_splat:ident, $T:ty, $N:literal) => {
+        vec_load!($vec_load_splat, { mem::size_of::<$T>() }, |bytes| {
+            from_lanes([<$T>::from_le_bytes(bytes); $N])
+        });
+    };
+}
+
+vec_load!(v128_load, 16, u128::from_le_bytes);
+vec_load_extend!(v128_load8x8_s, i8, i16, 8);
+vec_load_extend!(v128_load8x8_u, u8, u16, 8);
+vec_load_extend!(v128_load16x4_s, i16, i32, 4);
+vec_load_extend!(v128_load16x4_u, u16, u32, 4);
+vec_load_extend!(v128_load32x2_s, i32, i64, 2);
+vec_load_extend!(v128_load32x2_u, u32, u64, 2);
+vec_load_splat!(v128_load8_splat, u8, 16);
+vec_load_splat!(v128_load16_splat, u16, 8);
+vec_load_splat!(v128_load32_splat, u32, 4);
+vec_load_splat!(v128_load64_splat, u64, 2);
+vec_load!(v128_load32_zero, 4, |bytes| u32::from_le_bytes(bytes)
+    as u128);
+vec_load!(v128_load64_zero, 8, |bytes| u64::from_le_bytes(bytes)
+    as u128);
+
+threaded_instr!(v128_store(
+    ip: Ip,
+    sp: Sp,
+    md: Md,
+    ms: Ms,
+    ix: Ix,
+    sx: Sx,
+    dx: Dx,
+    cx: Cx,
+) -> ControlFlowBits {
+    // Read operands
+    let (dyn_offset, ip): (u32, _) = read_stack(ip, sp);
+    let (x, ip): (u128, _) = read_stack(ip, sp);
+    let (static_offset, ip): (u32, _) = read_imm(ip);
+
+    // Perform operation
+    let offset = dyn_offset as u64 + static_offset as u64;
+    if offset + 16 > ms as u64 {
+        return ControlFlow::Trap(Trap::MemAccessOutOfBounds).to_bits();
+    }
+    let bytes = x.to_le_bytes();
+    ptr::copy_nonoverlapping(bytes.as_ptr(), md.add(offset as usize), bytes.len());
+
+    // Execute next instruction
+    next_instr(ip, sp, md, ms, ix, sx, dx, cx)
+});
+
+macro_rules! vec_load_lane {
+    ($vec_load_lane:ident, $T:ty, $N:literal) => {
+        threaded_instr!($vec_load_lane(
+            ip: Ip,
+            sp: Sp,
+            md: Md,
+            ms: Ms,
+            ix: Ix,
+            sx: Sx,
+            dx: Dx,
+            cx: Cx,
+        ) -> ControlFlowBits {
+            // Read operands
+            let (dyn_offset, ip): (u32, _) = read_stack(ip, sp);
+            let (x, ip): (u128, _) = read_stack(ip, sp);
+            let (static_offset, ip): (u32, _) = read_imm(ip);
+            let (lane_idx, ip): (u8, _) = read_imm(ip);
+
+            // Perform operation
+            let offset = dyn_offset as u64 + static_offset as u64;
+            if offset + mem::size_of::<$T>() as u64 > ms as u64 {
+                return ControlFlow::Trap(Trap::MemAccessOutOfBounds).to_bits();
+            }
+            let mut bytes = [0u8; mem::size_of::<$T>()];
+            ptr::copy_nonoverlapping(md.add(offset as usize), bytes.as_mut_ptr(), bytes.len());
+            let mut lanes = to_lanes::<$T, $N>(x);
+            lanes[lane_idx as usize] = <$T>::from_le_bytes(bytes);
+            let y = from_lanes(lanes);
+
+            // Write result
+            let ip = write_stack(ip, sp, y);
+
+            // Execute next instruction
+            next_instr(ip, sp, md, ms, ix, sx, dx, cx)
+        });
+    };
+}
+
+vec_load_lane!(v128_load8_lane, u8, 16);
+vec_load_lane!(v128_load16_lane, u16, 8);
+vec_load_lane!(v128_load32_lane, u32, 4);
+vec_load_lane!(v128_load64_lane, u64, 2);
+
+macro_rules! vec_store_lane {
+    ($vec_store_lane:ident, $T:ty, $N:literal) => {
+        threaded_instr!($vec_store_lane(
+            ip: Ip,
+            sp: Sp,
+            md: Md,
+            ms: Ms,
+            ix: Ix,
+            sx: Sx,
+            dx: Dx,
+            cx: Cx,
+        ) -> ControlFlowBits {
+            // Read operands
+            let (dyn_offset, ip): (u32, _) = read_stack(ip, sp);
+            let (x, ip): (u128, _) = read_stack(ip, sp);
+            let (static_offset, ip): (u32, _) = read_imm(ip);
+            let (lane_idx, ip): (u8, _) = read_imm(ip);
+
+            // Perform operation
+            let offset = dyn_offset as u64 + static_offset as u64;
+            if offset + mem::size_of::<$T>() as u64 > ms as u64 {
+                return ControlFlow::Trap(Trap::MemAccessOutOfBounds).to_bits();
+            }
+            let bytes = to_lanes::<$T, $N>(x)[lane_idx as usize].to_le_bytes();
+            ptr::copy_nonoverlapping(bytes.as_ptr(), md.add(offset as usize), bytes.len());
+
+            // Execute next instruction
+            next_instr(ip, sp, md, ms, ix, sx, dx, cx)
+        });
+    };
+}
+
+vec_store_lane!(v128_store8_lane, u8, 16);
+vec_store_lane!(v128_store16_lane, u16, 8);
+vec_store_lane!(v128_store32_lane, u32, 4);
+vec_store_lane!(v128_store64_lane, u64, 2);
+
 // Miscellaneous instructions
 
 macro_rules! copy_imm_to_stack {
@@ -3850,6 +4632,28 @@ copy_imm_to_stack!(copy_imm_to_stack_f64, f64);
 copy_imm_to_stack!(copy_imm_to_stack_func_ref, UnguardedFuncRef);
 copy_imm_to_stack!(copy_imm_to_stack_extern_ref, UnguardedExternRef);
 
+threaded_instr!(copy_imm_to_stack_v128(
+    ip: Ip,
+    sp: Sp,
+    md: Md,
+    ms: Ms,
+    ix: Ix,
+    sx: Sx,
+    dx: Dx,
+    cx: Cx,
+) -> ControlFlowBits {
+    // Read immediate value
+    let (lo, ip): (u64, _) = read_imm(ip);
+    let (hi, ip): (u64, _) = read_imm(ip);
+    let x = (hi as u128) << 64 | lo as u128;
+
+    // Write value to stack
+    let ip = write_stack(ip, sp, x);
+
+    // Execute next instruction
+    next_instr(ip, sp, md, ms, ix, sx, dx, cx)
+});
+
 macro_rules! copy_stack {
     ($copy_stack_t:ident, $T:ty) => {
         threaded_instr!($copy_stack_t(
@@ -3878,6 +4682,7 @@ copy_stack!(copy_stack_i32, i32);
 copy_stack!(copy_stack_i64, i64);
 copy_stack!(copy_stack_f32, f32);
 copy_stack!(copy_stack_f64, f64);
+copy_stack!(copy_stack_v128, u128);
 copy_stack!(copy_stack_func_ref, UnguardedFuncRef);
 copy_stack!(copy_stack_extern_ref, UnguardedExternRef);
 
@@ -3912,6 +4717,49 @@ copy_reg_to_stack!(copy_reg_to_stack_f64, f64);
 copy_reg_to_stack!(copy_reg_to_stack_func_ref, UnguardedFuncRef);
 copy_reg_to_stack!(copy_reg_to_stack_extern_ref, UnguardedExternRef);
 
+threaded_instr!(consume_fuel(
+    ip: Ip,
+    sp: Sp,
+    md: Md,
+    ms: Ms,
+    ix: Ix,
+    sx: Sx,
+    dx: Dx,
+    cx: Cx,
+) -> ControlFlowBits {
+    // Read operands
+    let (amount, ip): (u64, _) = read_imm(ip);
+
+    // Perform operation
+    let store = &mut *(*cx).store;
+    let Some(fuel) = store.fuel().checked_sub(amount) else {
+        return ControlFlow::Trap(Trap::OutOfFuel).to_bits();
+    };
+    store.set_fuel(fuel);
+
+    // Execute next instruction
+    next_instr(ip, sp, md, ms, ix, sx, dx, cx)
+});
+
+threaded_instr!(check_interrupt(
+    ip: Ip,
+    sp: Sp,
+    md: Md,
+    ms: Ms,
+    ix: Ix,
+    sx: Sx,
+    dx: Dx,
+    cx: Cx,
+) -> ControlFlowBits {
+    // Perform operation
+    if (*cx).store.take_interrupt() {
+        return ControlFlow::Trap(Trap::Interrupted).to_bits();
+    }
+
+    // Execute next instruction
+    next_instr(ip, sp, md, ms, ix, sx, dx, cx)
+});
+
 threaded_instr!(stop(
     _ip: Ip,
     _sp: Sp,
@@ -3994,7 +4842,23 @@ threaded_instr!(enter(
 
 // Helper functions
 
+/// Executes the instruction at `ip`.
+unsafe fn exec_instr(
+    ip: Ip,
+    sp: Sp,
+    md: Md,
+    ms: Ms,
+    ix: Ix,
+    sx: Sx,
+    dx: Dx,
+    cx: Cx,
+) -> ControlFlowBits {
+    let (instr, ip): (ThreadedInstr, _) = read_imm(ip);
+    (instr)(ip, sp, md, ms, ix, sx, dx, cx)
+}
+
 /// Executes the next instruction.
+#[cfg(not(any(feature = "trampoline", not(target_pointer_width = "64"))))]
 pub(crate) unsafe fn next_instr(
     ip: Ip,
     sp: Sp,
@@ -4009,6 +4873,29 @@ pub(crate) unsafe fn next_instr(
     (instr)(ip, sp, md, ms, ix, sx, dx, cx)
 }
 
+/// Saves the virtual registers to the [`Context`], and returns to the trampoline in [`exec`], which
+/// then executes the next instruction.
+#[cfg(any(feature = "trampoline", not(target_pointer_width = "64")))]
+pub(crate) unsafe fn next_instr(
+    ip: Ip,
+    sp: Sp,
+    md: Md,
+    ms: Ms,
+    ix: Ix,
+    sx: Sx,
+    dx: Dx,
+    cx: Cx,
+) -> ControlFlowBits {
+    (*cx).ip = ip;
+    (*cx).sp = sp;
+    (*cx).md = md;
+    (*cx).ms = ms;
+    (*cx).ix = ix;
+    (*cx).sx = sx;
+    (*cx).dx = dx;
+    ControlFlow::Continue.to_bits()
+}
+
 /// Reads an immediate value.
 unsafe fn read_imm<T>(ip: Ip) -> (T, Ip)
 where
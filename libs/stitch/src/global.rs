@@ -50,6 +50,9 @@ impl Global {
             (ValType::F64, UnguardedVal::F64(val)) => Ok(Self(
                 store.insert_global(GlobalEntity::F64(GlobalEntityT::new(type_.mut_, val))),
             )),
+            (ValType::V128, UnguardedVal::V128(val)) => Ok(Self(
+                store.insert_global(GlobalEntity::V128(GlobalEntityT::new(type_.mut_, val))),
+            )),
             (ValType::FuncRef, UnguardedVal::FuncRef(val)) => Ok(Self(
                 store.insert_global(GlobalEntity::FuncRef(GlobalEntityT::new(type_.mut_, val))),
             )),
@@ -79,6 +82,10 @@ impl Global {
                 mut_: global.mut_(),
                 val: ValType::F64,
             },
+            GlobalEntity::V128(global) => GlobalType {
+                mut_: global.mut_(),
+                val: ValType::V128,
+            },
             GlobalEntity::FuncRef(global) => GlobalType {
                 mut_: global.mut_(),
                 val: ValType::FuncRef,
@@ -102,6 +109,7 @@ impl Global {
             GlobalEntity::I64(global) => UnguardedVal::I64(global.get()),
             GlobalEntity::F32(global) => UnguardedVal::F32(global.get()),
             GlobalEntity::F64(global) => UnguardedVal::F64(global.get()),
+            GlobalEntity::V128(global) => UnguardedVal::V128(global.get()),
             GlobalEntity::FuncRef(global) => UnguardedVal::FuncRef(global.get()),
             GlobalEntity::ExternRef(global) => UnguardedVal::ExternRef(global.get()),
         }
@@ -131,6 +139,7 @@ impl Global {
             (GlobalEntity::I64(global), UnguardedVal::I64(val)) => Ok(global.set(val)),
             (GlobalEntity::F32(global), UnguardedVal::F32(val)) => Ok(global.set(val)),
             (GlobalEntity::F64(global), UnguardedVal::F64(val)) => Ok(global.set(val)),
+            (GlobalEntity::V128(global), UnguardedVal::V128(val)) => Ok(global.set(val)),
             (GlobalEntity::FuncRef(global), UnguardedVal::FuncRef(val)) => Ok(global.set(val)),
             (GlobalEntity::ExternRef(global), UnguardedVal::ExternRef(val)) => Ok(global.set(val)),
             _ => Err(GlobalError::ValTypeMismatch),
@@ -221,6 +230,7 @@ pub(crate) enum GlobalEntity {
     I64(GlobalEntityT<i64>),
     F32(GlobalEntityT<f32>),
     F64(GlobalEntityT<f64>),
+    V128(GlobalEntityT<u128>),
     FuncRef(GlobalEntityT<UnguardedFuncRef>),
     ExternRef(GlobalEntityT<UnguardedExternRef>),
 }
@@ -349,6 +359,24 @@ impl DowncastMut<GlobalEntity> for GlobalEntityT<f64> {
     }
 }
 
+impl DowncastRef<GlobalEntity> for GlobalEntityT<u128> {
+    fn downcast_ref(global: &GlobalEntity) -> Option<&GlobalEntityT<u128>> {
+        match global {
+            GlobalEntity::V128(global) => Some(global),
+            _ => None,
+        }
+    }
+}
+
+impl DowncastMut<GlobalEntity> for GlobalEntityT<u128> {
+    fn downcast_mut(global: &mut GlobalEntity) -> Option<&mut GlobalEntityT<u128>> {
+        match global {
+            GlobalEntity::V128(global) => Some(global),
+            _ => None,
+        }
+    }
+}
+
 impl DowncastRef<GlobalEntity> for GlobalEntityT<UnguardedFuncRef> {
     fn downcast_ref(global: &GlobalEntity) -> Option<&GlobalEntityT<UnguardedFuncRef>> {
         match global {
@@ -18,6 +18,7 @@ mod func_ref;
 mod global;
 mod instance;
 mod into_host_func;
+mod limiter;
 mod limits;
 mod linker;
 mod mem;
@@ -30,10 +31,12 @@ mod table;
 mod trap;
 mod val;
 mod validate;
+#[cfg(feature = "wasi")]
+mod wasi;
 
 pub use self::{
     decode::DecodeError,
-    engine::Engine,
+    engine::{Engine, EngineConfig, FuelCosts},
     error::Error,
     extern_ref::ExternRef,
     extern_val::{ExternType, ExternVal},
@@ -41,12 +44,17 @@ pub use self::{
     func_ref::FuncRef,
     global::{Global, GlobalError, GlobalType, Mut},
     instance::{Instance, InstanceExports},
+    limiter::{ResourceLimiter, ResourceUsage},
     limits::Limits,
     linker::{InstantiateError, Linker},
     mem::{Mem, MemError, MemType},
     module::{Module, ModuleExports, ModuleImports},
     ref_::{Ref, RefType},
-    store::Store,
+    store::{InterruptHandle, Store},
     table::{Table, TableError, TableType},
+    trap::Trap,
     val::{Val, ValType},
 };
+
+#[cfg(feature = "wasi")]
+pub use self::wasi::{Wasi, WasiOutput};
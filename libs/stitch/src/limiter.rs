@@ -0,0 +1,50 @@
+use std::fmt;
+
+/// Decides how large the memories and tables in a [`Store`](crate::Store) are allowed to grow.
+///
+/// A [`ResourceLimiter`] is consulted for every memory and table that is created when a module is
+/// instantiated, and every time a memory or table grows, either through a `memory.grow` or
+/// `table.grow` instruction, or through [`Mem::grow`](crate::Mem::grow) or
+/// [`Table::grow`](crate::Table::grow).
+///
+/// During instantiation, a [`ResourceLimiter`] is consulted right before each memory or table is
+/// created. If it refuses, instantiation fails, but the memories and tables that were created
+/// before that remain in the store, and still count towards its [`ResourceUsage`].
+///
+/// Memories and tables that are created directly by the host are not checked when they are
+/// created, but are checked when they grow.
+pub trait ResourceLimiter {
+    /// Returns `true` if a memory is allowed to grow from `current` to `desired` pages.
+    ///
+    /// `max` is the maximum size of the memory in pages, if it has one. The memory fails to grow if
+    /// `desired` exceeds `max`, regardless of what this method returns.
+    fn mem_growing(&mut self, current: u32, desired: u32, max: Option<u32>) -> bool;
+
+    /// Returns `true` if a table is allowed to grow from `current` to `desired` elements.
+    ///
+    /// `max` is the maximum size of the table in elements, if it has one. The table fails to grow
+    /// if `desired` exceeds `max`, regardless of what this method returns.
+    fn table_growing(&mut self, current: u32, desired: u32, max: Option<u32>) -> bool;
+}
+
+/// The total amount of resources used by the memories and tables in a [`Store`](crate::Store).
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct ResourceUsage {
+    /// The number of memories.
+    pub mem_count: usize,
+    /// The total size of all memories, in number of pages.
+    pub mem_pages: u64,
+    /// The number of tables.
+    pub table_count: usize,
+    /// The total size of all tables, in number of elements.
+    pub table_elems: u64,
+}
+
+/// A boxed [`ResourceLimiter`], so that a [`Store`](crate::Store) can be debug printed.
+pub(crate) struct BoxedResourceLimiter(pub(crate) Box<dyn ResourceLimiter>);
+
+impl fmt::Debug for BoxedResourceLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ResourceLimiter").finish()
+    }
+}
@@ -51,6 +51,7 @@ pub enum InstantiateError {
     GlobalTypeMismatch,
     TableTypeMismatch,
     MemTypeMismatch,
+    ResourceLimitExceeded,
 }
 
 impl fmt::Display for InstantiateError {
@@ -62,6 +63,7 @@ impl fmt::Display for InstantiateError {
             Self::GlobalTypeMismatch => write!(f, "global type mismatch"),
             Self::TableTypeMismatch => write!(f, "table type mismatch"),
             Self::MemTypeMismatch => write!(f, "memory type mismatch"),
+            Self::ResourceLimitExceeded => write!(f, "resource limit exceeded"),
         }
     }
 }
@@ -1,27 +1,51 @@
 use {
-    makepad_stitch::{Engine, Linker, Module, Store, Val, ValType},
-    std::{env, fs},
+    makepad_stitch::{Engine, Error, Linker, Module, Store, Val, ValType},
+    std::{env, fs, process},
 };
 
 fn main() {
     let mut args = env::args();
     args.next().unwrap();
-    let wasm_file = args.next().unwrap();
+    let mut dirs = Vec::new();
+    let mut envs = Vec::new();
+    let wasm_file = loop {
+        let arg = args.next().unwrap();
+        match arg.as_str() {
+            "--dir" => dirs.push(args.next().unwrap()),
+            "--env" => envs.push(args.next().unwrap()),
+            _ => break arg,
+        }
+    };
     let func_name = args.next().unwrap();
     let args: Vec<_> = args.collect();
     let engine = Engine::new();
     let mut store = Store::new(engine);
-    let bytes = fs::read(wasm_file).unwrap();
+    let bytes = fs::read(&wasm_file).unwrap();
     let module = Module::new(store.engine(), &bytes).unwrap();
-    let linker = Linker::new();
+    #[allow(unused_mut)]
+    let mut linker = Linker::new();
+    #[cfg(feature = "wasi")]
+    {
+        let mut wasi = makepad_stitch::Wasi::new();
+        wasi.set_args(std::iter::once(wasm_file.clone()).chain(args.iter().cloned()));
+        for env in &envs {
+            let (key, value) = env.split_once('=').unwrap_or((env, ""));
+            wasi.set_env(key, value);
+        }
+        for dir in &dirs {
+            let (host_path, guest_path) = dir.split_once("::").unwrap_or((dir, dir));
+            wasi.preopen_dir(host_path, guest_path).unwrap();
+        }
+        wasi.add_to_linker(&mut store, &mut linker);
+    }
     let instance = linker.instantiate(&mut store, &module).unwrap();
     let func = instance.exported_func(&func_name).unwrap();
     let args: Vec<_> = func
         .type_(&store)
         .params()
         .iter()
-        .zip(args)
-        .map(|(type_, string)| parse_val(*type_, &string))
+        .zip(&args)
+        .map(|(type_, string)| parse_val(*type_, string))
         .collect();
     let mut results: Vec<_> = func
         .type_(&store)
@@ -29,7 +53,11 @@ fn main() {
         .iter()
         .map(|type_| Val::default(*type_))
         .collect();
-    func.call(&mut store, &args, &mut results).unwrap();
+    match func.call(&mut store, &args, &mut results) {
+        Ok(()) => {}
+        Err(Error::Exit(code)) => process::exit(code),
+        Err(error) => panic!("{}", error),
+    }
     for result in results {
         print_val(result);
     }
@@ -41,6 +69,7 @@ fn parse_val(type_: ValType, string: &str) -> Val {
         ValType::I64 => string.parse::<i64>().unwrap().into(),
         ValType::F32 => string.parse::<f32>().unwrap().into(),
         ValType::F64 => string.parse::<f64>().unwrap().into(),
+        ValType::V128 => string.parse::<u128>().unwrap().into(),
         ValType::FuncRef => unimplemented!(),
         ValType::ExternRef => unimplemented!(),
     }
@@ -52,6 +81,7 @@ fn print_val(val: Val) {
         Val::I64(val) => println!("{}", val),
         Val::F32(val) => println!("{}", val),
         Val::F64(val) => println!("{}", val),
+        Val::V128(val) => println!("{:#034x}", val),
         Val::FuncRef(_) => unimplemented!(),
         Val::ExternRef(_) => unimplemented!(),
     }
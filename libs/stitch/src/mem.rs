@@ -56,6 +56,12 @@ impl Mem {
     ///
     /// If this [`Mem`] failed to grow.
     pub fn grow(self, store: &mut Store, count: u32) -> Result<u32, MemError> {
+        let limits = self.0.as_ref(store).limits();
+        if let Some(desired) = limits.min.checked_add(count) {
+            if !store.mem_growing(limits.min, desired, limits.max) {
+                return Err(MemError::FailedToGrow);
+            }
+        }
         self.0.as_mut(store).grow(count)
     }
 
@@ -159,7 +165,7 @@ impl MemEntity {
     }
 
     /// Returns the [`Limits`] of this [`MemEntity`].
-    fn limits(&self) -> Limits {
+    pub(crate) fn limits(&self) -> Limits {
         Limits {
             min: self.size(),
             max: self.max,
@@ -287,10 +287,18 @@ impl Module {
                 _ => {}
             }
         }
+        // The resource limiter is consulted right before each table and memory is created, so that
+        // it is never told about a table or memory that does not end up in the store.
         for type_ in self.internal_tables() {
+            if !store.table_growing(0, type_.limits.min, type_.limits.max) {
+                return Err(InstantiateError::ResourceLimitExceeded)?;
+            }
             initer.push_table(Table::new(store, type_, Ref::null(type_.elem)).unwrap());
         }
         for type_ in self.internal_memories() {
+            if !store.mem_growing(0, type_.limits.min, type_.limits.max) {
+                return Err(InstantiateError::ResourceLimitExceeded)?;
+            }
             initer.push_mem(Mem::new(store, type_));
         }
         for ((type_, _), init_val) in self.internal_globals().zip(global_init_vals) {
@@ -178,18 +178,32 @@ macro_rules! impl_float_ops {
             }
 
             fn ceil(self) -> Result<Self, Trap> {
+                // The rounding functions in `std` may return a signalling NaN for a signalling NaN
+                // operand, whereas Wasm requires an arithmetic NaN.
+                if self.is_nan() {
+                    return Ok(<$T>::NAN);
+                }
                 Ok(self.ceil())
             }
 
             fn floor(self) -> Result<Self, Trap> {
+                if self.is_nan() {
+                    return Ok(<$T>::NAN);
+                }
                 Ok(self.floor())
             }
 
             fn trunc(self) -> Result<Self, Trap> {
+                if self.is_nan() {
+                    return Ok(<$T>::NAN);
+                }
                 Ok(self.trunc())
             }
 
             fn nearest(self) -> Result<Self, Trap> {
+                if self.is_nan() {
+                    return Ok(<$T>::NAN);
+                }
                 let round = self.round();
                 if self.fract().abs() != 0.5 {
                     Ok(round)
@@ -449,3 +463,124 @@ impl_extend_n!(i8, i64);
 impl_extend_n!(i16, i32);
 impl_extend_n!(i16, i64);
 impl_extend_n!(i32, i64);
+
+/// A lane of a `v128`.
+pub(crate) trait Lane: Copy + Default {
+    const SIZE: usize;
+
+    fn from_le_bytes(bytes: &[u8]) -> Self;
+
+    fn to_le_bytes(self, bytes: &mut [u8]);
+}
+
+macro_rules! impl_lane {
+    ($T:ty) => {
+        impl Lane for $T {
+            const SIZE: usize = mem::size_of::<$T>();
+
+            fn from_le_bytes(bytes: &[u8]) -> Self {
+                Self::from_le_bytes(bytes.try_into().unwrap())
+            }
+
+            fn to_le_bytes(self, bytes: &mut [u8]) {
+                bytes.copy_from_slice(&self.to_le_bytes());
+            }
+        }
+    };
+}
+
+impl_lane!(i8);
+impl_lane!(u8);
+impl_lane!(i16);
+impl_lane!(u16);
+impl_lane!(i32);
+impl_lane!(u32);
+impl_lane!(i64);
+impl_lane!(u64);
+impl_lane!(f32);
+impl_lane!(f64);
+
+/// Splits a `v128` into its lanes, with lane 0 being the least significant one.
+pub(crate) fn to_lanes<T, const N: usize>(x: u128) -> [T; N]
+where
+    T: Lane,
+{
+    let bytes = x.to_le_bytes();
+    let mut lanes = [T::default(); N];
+    for (lane, bytes) in lanes.iter_mut().zip(bytes.chunks_exact(T::SIZE)) {
+        *lane = T::from_le_bytes(bytes);
+    }
+    lanes
+}
+
+/// Joins the given lanes into a `v128`, with lane 0 being the least significant one.
+pub(crate) fn from_lanes<T, const N: usize>(lanes: [T; N]) -> u128
+where
+    T: Lane,
+{
+    let mut bytes = [0u8; 16];
+    for (lane, bytes) in lanes.into_iter().zip(bytes.chunks_exact_mut(T::SIZE)) {
+        lane.to_le_bytes(bytes);
+    }
+    u128::from_le_bytes(bytes)
+}
+
+/// Splits the low or high half of a `v128` into its lanes, with lane 0 being the least significant
+/// one.
+pub(crate) fn to_half_lanes<T, const N: usize>(x: u128, high: bool) -> [T; N]
+where
+    T: Lane,
+{
+    let bytes = x.to_le_bytes();
+    let bytes = if high { &bytes[8..] } else { &bytes[..8] };
+    let mut lanes = [T::default(); N];
+    for (lane, bytes) in lanes.iter_mut().zip(bytes.chunks_exact(T::SIZE)) {
+        *lane = T::from_le_bytes(bytes);
+    }
+    lanes
+}
+
+/// Applies the given operation to every lane of a `v128`.
+pub(crate) fn map_lanes<T, U, const N: usize>(
+    x: u128,
+    f: impl Fn(T) -> Result<U, Trap>,
+) -> Result<u128, Trap>
+where
+    T: Lane,
+    U: Lane,
+{
+    let mut lanes = [U::default(); N];
+    for (lane, x) in lanes.iter_mut().zip(to_lanes::<T, N>(x)) {
+        *lane = f(x)?;
+    }
+    Ok(from_lanes(lanes))
+}
+
+/// Applies the given operation to every pair of corresponding lanes of two `v128`s.
+pub(crate) fn zip_lanes<T, U, const N: usize>(
+    x: u128,
+    y: u128,
+    f: impl Fn(T, T) -> Result<U, Trap>,
+) -> Result<u128, Trap>
+where
+    T: Lane,
+    U: Lane,
+{
+    let mut lanes = [U::default(); N];
+    for ((lane, x), y) in lanes
+        .iter_mut()
+        .zip(to_lanes::<T, N>(x))
+        .zip(to_lanes::<T, N>(y))
+    {
+        *lane = f(x, y)?;
+    }
+    Ok(from_lanes(lanes))
+}
+
+/// Returns a lane with all bits set if `cond` is `true`, or no bits set otherwise.
+pub(crate) fn lane_mask<T>(cond: bool) -> Result<T, Trap>
+where
+    T: From<i8>,
+{
+    Ok(T::from(-(cond as i8)))
+}
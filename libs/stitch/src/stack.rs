@@ -70,7 +70,11 @@ impl Drop for StackGuard {
     }
 }
 
-pub(crate) type StackSlot = u64;
+/// A single slot on the [`Stack`].
+///
+/// Every value takes up exactly one slot, so a slot must be large enough (and aligned enough) to
+/// hold a `v128`.
+pub(crate) type StackSlot = u128;
 
 thread_local! {
     static STACK: Cell<Option<Stack>> = Cell::new(Some(Stack::new()));
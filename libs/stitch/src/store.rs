@@ -7,14 +7,20 @@ use {
         extern_::ExternEntity,
         func::{FuncEntity, FuncType},
         global::GlobalEntity,
-        mem::MemEntity,
+        limiter::{BoxedResourceLimiter, ResourceLimiter, ResourceUsage},
+        mem::{Mem, MemEntity},
         table::TableEntity,
     },
     std::{
         collections::HashMap,
         fmt,
         hash::{Hash, Hasher},
+        mem,
         ptr::NonNull,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
     },
 };
 
@@ -31,6 +37,10 @@ pub struct Store {
     elems: Vec<AliasableBox<ElemEntity>>,
     datas: Vec<AliasableBox<DataEntity>>,
     externs: Vec<AliasableBox<ExternEntity>>,
+    caller_mem: Option<Mem>,
+    fuel: u64,
+    interrupted: Arc<AtomicBool>,
+    limiter: Option<BoxedResourceLimiter>,
 }
 
 impl Store {
@@ -47,6 +57,10 @@ impl Store {
             elems: Vec::new(),
             datas: Vec::new(),
             externs: Vec::new(),
+            caller_mem: None,
+            fuel: 0,
+            interrupted: Arc::new(AtomicBool::new(false)),
+            limiter: None,
         }
     }
 
@@ -54,6 +68,79 @@ impl Store {
         &self.engine
     }
 
+    /// Returns the memory of the instance whose Wasm code called the host function that is
+    /// currently running, if that instance has a memory.
+    ///
+    /// This is how host functions get at the memory that their pointer arguments point into.
+    pub fn caller_mem(&self) -> Option<Mem> {
+        self.caller_mem
+    }
+
+    /// Returns the amount of fuel left in this [`Store`].
+    ///
+    /// Fuel is only consumed if the [`Engine`] was configured with
+    /// [`FuelCosts`](crate::FuelCosts). A new [`Store`] starts out with no fuel.
+    pub fn fuel(&self) -> u64 {
+        self.fuel
+    }
+
+    /// Sets the amount of fuel left in this [`Store`].
+    pub fn set_fuel(&mut self, fuel: u64) {
+        self.fuel = fuel;
+    }
+
+    /// Returns an [`InterruptHandle`] that can be used to interrupt Wasm code running in this
+    /// [`Store`] from another thread.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle {
+            interrupted: self.interrupted.clone(),
+        }
+    }
+
+    /// Clears the interrupt flag of this [`Store`], returning whether it was set.
+    pub(crate) fn take_interrupt(&self) -> bool {
+        self.interrupted.swap(false, Ordering::Relaxed)
+    }
+
+    /// Sets the [`ResourceLimiter`] of this [`Store`].
+    ///
+    /// This replaces the previous [`ResourceLimiter`], if any.
+    pub fn set_limiter(&mut self, limiter: impl ResourceLimiter + 'static) {
+        self.limiter = Some(BoxedResourceLimiter(Box::new(limiter)));
+    }
+
+    /// Returns the total amount of resources used by the memories and tables in this [`Store`].
+    pub fn resource_usage(&self) -> ResourceUsage {
+        ResourceUsage {
+            mem_count: self.mems.len(),
+            mem_pages: self.mems.iter().map(|mem| mem.size() as u64).sum(),
+            table_count: self.tables.len(),
+            table_elems: self.tables.iter().map(|table| table.size() as u64).sum(),
+        }
+    }
+
+    /// Returns `true` if the [`ResourceLimiter`] of this [`Store`], if any, allows a memory to grow
+    /// from `current` to `desired` pages.
+    pub(crate) fn mem_growing(&mut self, current: u32, desired: u32, max: Option<u32>) -> bool {
+        match &mut self.limiter {
+            Some(limiter) => limiter.0.mem_growing(current, desired, max),
+            None => true,
+        }
+    }
+
+    /// Returns `true` if the [`ResourceLimiter`] of this [`Store`], if any, allows a table to grow
+    /// from `current` to `desired` elements.
+    pub(crate) fn table_growing(&mut self, current: u32, desired: u32, max: Option<u32>) -> bool {
+        match &mut self.limiter {
+            Some(limiter) => limiter.0.table_growing(current, desired, max),
+            None => true,
+        }
+    }
+
+    pub(crate) fn replace_caller_mem(&mut self, mem: Option<Mem>) -> Option<Mem> {
+        mem::replace(&mut self.caller_mem, mem)
+    }
+
     pub(crate) fn id(&self) -> StoreId {
         self.id
     }
@@ -138,6 +225,23 @@ impl Store {
     }
 }
 
+/// A handle for interrupting Wasm code running in a [`Store`].
+///
+/// Interrupts are only observed if the [`Engine`] was configured to be interruptible. Wasm code
+/// running in the [`Store`] then traps with [`Trap::Interrupted`](crate::Trap::Interrupted) at the
+/// next function entry or loop iteration. If no Wasm code is running, the next call traps instead.
+#[derive(Clone, Debug)]
+pub struct InterruptHandle {
+    interrupted: Arc<AtomicBool>,
+}
+
+impl InterruptHandle {
+    /// Interrupts the Wasm code running in the [`Store`] for this [`InterruptHandle`].
+    pub fn interrupt(&self) {
+        self.interrupted.store(true, Ordering::Relaxed);
+    }
+}
+
 /// A unique identifier for a [`Store`].
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct StoreId(usize);
@@ -136,6 +136,12 @@ impl Table {
     ///
     /// - If the given initialization [`Ref`] is not owned by the given [`Store`].
     pub fn grow(self, store: &mut Store, val: Ref, count: u32) -> Result<(), TableError> {
+        let limits = self.0.as_ref(store).limits();
+        if let Some(desired) = limits.min.checked_add(count) {
+            if !store.table_growing(limits.min, desired, limits.max) {
+                return Err(TableError::FailedToGrow);
+            }
+        }
         unsafe { self.grow_unguarded(store, val.to_unguarded(store.id()), count) }
     }
 
@@ -268,6 +274,22 @@ pub(crate) enum TableEntity {
 }
 
 impl TableEntity {
+    /// Returns the [`Limits`] of this [`TableEntity`].
+    pub(crate) fn limits(&self) -> Limits {
+        match self {
+            Self::FuncRef(table) => table.limits(),
+            Self::ExternRef(table) => table.limits(),
+        }
+    }
+
+    /// Returns the size of this [`TableEntity`] in number of elements.
+    pub(crate) fn size(&self) -> u32 {
+        match self {
+            Self::FuncRef(table) => table.size(),
+            Self::ExternRef(table) => table.size(),
+        }
+    }
+
     /// Returns a reference to the inner value of this [`TableEntity`] if it is a
     /// [`TableEntityT<T>`].
     pub(crate) fn downcast_ref<T>(&self) -> Option<&TableEntityT<T>>
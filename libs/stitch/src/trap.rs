@@ -11,6 +11,11 @@ pub enum Trap {
     TableAccessOutOfBounds,
     MemAccessOutOfBounds,
     StackOverflow,
+    /// The [`Store`](crate::Store) ran out of fuel.
+    OutOfFuel,
+    /// The [`Store`](crate::Store) was interrupted through an
+    /// [`InterruptHandle`](crate::InterruptHandle).
+    Interrupted,
 }
 
 impl Trap {
@@ -25,6 +30,8 @@ impl Trap {
             6 => Some(Self::TableAccessOutOfBounds),
             7 => Some(Self::MemAccessOutOfBounds),
             8 => Some(Self::StackOverflow),
+            9 => Some(Self::OutOfFuel),
+            10 => Some(Self::Interrupted),
             _ => None,
         }
     }
@@ -46,6 +53,8 @@ impl fmt::Display for Trap {
             Self::TableAccessOutOfBounds => write!(f, "table access out of bounds"),
             Self::MemAccessOutOfBounds => write!(f, "memory access out of bounds"),
             Self::StackOverflow => write!(f, "stack overflow"),
+            Self::OutOfFuel => write!(f, "out of fuel"),
+            Self::Interrupted => write!(f, "interrupted"),
         }
     }
 }
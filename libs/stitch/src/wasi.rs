@@ -0,0 +1,1437 @@
+//! A host implementation of WASI preview1 (`wasi_snapshot_preview1`).
+
+use {
+    crate::{error::Error, func::Func, linker::Linker, store::Store},
+    std::{
+        collections::hash_map::RandomState,
+        fs::{self, File, FileTimes, OpenOptions},
+        hash::{BuildHasher, Hasher},
+        io::{self, Read, Seek, SeekFrom, Write},
+        path::{Component, Path, PathBuf},
+        sync::{Arc, Mutex},
+        thread,
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    },
+};
+
+const MODULE: &str = "wasi_snapshot_preview1";
+
+type Errno = u16;
+
+const ERRNO_SUCCESS: Errno = 0;
+const ERRNO_2BIG: Errno = 1;
+const ERRNO_ACCES: Errno = 2;
+const ERRNO_BADF: Errno = 8;
+const ERRNO_EXIST: Errno = 20;
+const ERRNO_FAULT: Errno = 21;
+const ERRNO_ILSEQ: Errno = 25;
+const ERRNO_INVAL: Errno = 28;
+const ERRNO_IO: Errno = 29;
+const ERRNO_ISDIR: Errno = 31;
+const ERRNO_LOOP: Errno = 32;
+const ERRNO_NOENT: Errno = 44;
+const ERRNO_NOSYS: Errno = 52;
+const ERRNO_NOTDIR: Errno = 54;
+const ERRNO_NOTEMPTY: Errno = 55;
+const ERRNO_SPIPE: Errno = 70;
+const ERRNO_NOTCAPABLE: Errno = 76;
+
+const FILETYPE_UNKNOWN: u8 = 0;
+const FILETYPE_CHARACTER_DEVICE: u8 = 2;
+const FILETYPE_DIRECTORY: u8 = 3;
+const FILETYPE_REGULAR_FILE: u8 = 4;
+const FILETYPE_SYMBOLIC_LINK: u8 = 7;
+
+const CLOCK_REALTIME: u32 = 0;
+const CLOCK_MONOTONIC: u32 = 1;
+const CLOCK_PROCESS_CPUTIME: u32 = 2;
+const CLOCK_THREAD_CPUTIME: u32 = 3;
+
+const FDFLAGS_APPEND: u16 = 1 << 0;
+
+const OFLAGS_CREAT: u16 = 1 << 0;
+const OFLAGS_DIRECTORY: u16 = 1 << 1;
+const OFLAGS_EXCL: u16 = 1 << 2;
+const OFLAGS_TRUNC: u16 = 1 << 3;
+
+const LOOKUPFLAGS_SYMLINK_FOLLOW: u32 = 1 << 0;
+
+const FSTFLAGS_ATIM: u16 = 1 << 0;
+const FSTFLAGS_ATIM_NOW: u16 = 1 << 1;
+const FSTFLAGS_MTIM: u16 = 1 << 2;
+const FSTFLAGS_MTIM_NOW: u16 = 1 << 3;
+
+const RIGHTS_FD_READ: u64 = 1 << 1;
+const RIGHTS_FD_WRITE: u64 = 1 << 6;
+const RIGHTS_ALL: u64 = (1 << 30) - 1;
+
+const EVENTTYPE_CLOCK: u8 = 0;
+const EVENTTYPE_FD_READ: u8 = 1;
+const EVENTTYPE_FD_WRITE: u8 = 2;
+
+const SUBCLOCKFLAGS_ABSTIME: u16 = 1 << 0;
+
+/// A WASI preview1 environment, for running `wasm32-wasi` modules.
+///
+/// Files are only reachable through directories that were preopened with
+/// [`Wasi::preopen_dir`], and paths can not escape them.
+///
+/// A [`Wasi`] is a handle, so clones of it share the same environment.
+#[derive(Clone, Debug)]
+pub struct Wasi {
+    state: Arc<Mutex<WasiState>>,
+}
+
+/// Where the output of a WASI module on stdout or stderr goes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WasiOutput {
+    /// Writes to the stream of the host process.
+    Inherit,
+    /// Discards the output.
+    Null,
+    /// Keeps the output, to be taken with [`Wasi::take_stdout`] or [`Wasi::take_stderr`].
+    Capture,
+}
+
+impl Wasi {
+    /// Creates a new [`Wasi`] without arguments, environment variables or preopened directories,
+    /// that inherits the standard streams of the host process.
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(Mutex::new(WasiState::new())),
+        }
+    }
+
+    /// Sets the command line arguments, including the program name.
+    pub fn set_args<I, S>(&mut self, args: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.state.lock().unwrap().args = args.into_iter().map(|arg| arg.into()).collect();
+    }
+
+    /// Sets an environment variable.
+    pub fn set_env(&mut self, key: &str, value: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.env.retain(|(env_key, _)| env_key != key);
+        state.env.push((key.to_string(), value.to_string()));
+    }
+
+    /// Gives the module access to the host directory at `host_path`, under the name `guest_path`.
+    ///
+    /// # Errors
+    ///
+    /// If `host_path` is not a directory.
+    pub fn preopen_dir(&mut self, host_path: impl AsRef<Path>, guest_path: &str) -> io::Result<()> {
+        let root = host_path.as_ref().canonicalize()?;
+        if !root.is_dir() {
+            return Err(io::Error::other("not a directory"));
+        }
+        self.state.lock().unwrap().push_fd(FdEntry::Dir {
+            path: root.clone(),
+            root,
+            preopen: Some(guest_path.to_string()),
+        });
+        Ok(())
+    }
+
+    /// Makes the module read stdin from the given bytes instead of the stdin of the host process.
+    pub fn set_stdin(&mut self, bytes: impl Into<Vec<u8>>) {
+        self.state.lock().unwrap().stdin = Some((bytes.into(), 0));
+    }
+
+    /// Sets where the output of the module on stdout goes.
+    pub fn set_stdout(&mut self, output: WasiOutput) {
+        self.state.lock().unwrap().stdout.0 = output;
+    }
+
+    /// Sets where the output of the module on stderr goes.
+    pub fn set_stderr(&mut self, output: WasiOutput) {
+        self.state.lock().unwrap().stderr.0 = output;
+    }
+
+    /// Takes the output captured on stdout so far.
+    pub fn take_stdout(&self) -> Vec<u8> {
+        std::mem::take(&mut self.state.lock().unwrap().stdout.1)
+    }
+
+    /// Takes the output captured on stderr so far.
+    pub fn take_stderr(&self) -> Vec<u8> {
+        std::mem::take(&mut self.state.lock().unwrap().stderr.1)
+    }
+
+    /// Returns the exit code the module passed to `proc_exit`, if it called it.
+    pub fn exit_code(&self) -> Option<i32> {
+        self.state.lock().unwrap().exit_code
+    }
+
+    /// Defines all functions of `wasi_snapshot_preview1` in the given [`Linker`].
+    ///
+    /// The functions work on the memory of the instance that calls them.
+    pub fn add_to_linker(&self, store: &mut Store, linker: &mut Linker) {
+        let state = self.state.clone();
+        linker.define(
+            MODULE,
+            "proc_exit",
+            Func::wrap(store, move |code: i32| -> Result<(), Error> {
+                state.lock().unwrap().exit_code = Some(code);
+                Err(Error::Exit(code))
+            }),
+        );
+        define_funcs(&self.state, store, linker);
+    }
+}
+
+impl Default for Wasi {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Runs a WASI function on the memory of the calling instance, and turns its result into an errno.
+fn call(
+    state: &Mutex<WasiState>,
+    store: &mut Store,
+    f: impl FnOnce(&mut WasiState, GuestMem) -> Result<(), Errno>,
+) -> i32 {
+    let Some(mem) = store.caller_mem() else {
+        return ERRNO_FAULT as i32;
+    };
+    let mut state = state.lock().unwrap();
+    match f(&mut state, GuestMem(mem.bytes_mut(store))) {
+        Ok(()) => ERRNO_SUCCESS as i32,
+        Err(errno) => errno as i32,
+    }
+}
+
+macro_rules! wasi_funcs {
+    ($($name:ident($($arg:ident: $T:ty),*);)*) => {
+        fn define_funcs(state: &Arc<Mutex<WasiState>>, store: &mut Store, linker: &mut Linker) {
+            $(
+                let state_ = state.clone();
+                linker.define(
+                    MODULE,
+                    stringify!($name),
+                    Func::wrap(store, move |store: &mut Store, $($arg: $T),*| -> i32 {
+                        call(&state_, store, |state, mem| state.$name(mem, $($arg),*))
+                    }),
+                );
+            )*
+        }
+    };
+}
+
+wasi_funcs! {
+    args_get(argv: u32, argv_buf: u32);
+    args_sizes_get(argc_ptr: u32, argv_buf_size_ptr: u32);
+    environ_get(environ: u32, environ_buf: u32);
+    environ_sizes_get(environ_count_ptr: u32, environ_buf_size_ptr: u32);
+    clock_res_get(id: u32, resolution_ptr: u32);
+    clock_time_get(id: u32, precision: u64, time_ptr: u32);
+    fd_advise(fd: u32, offset: u64, len: u64, advice: u32);
+    fd_allocate(fd: u32, offset: u64, len: u64);
+    fd_close(fd: u32);
+    fd_datasync(fd: u32);
+    fd_fdstat_get(fd: u32, fdstat_ptr: u32);
+    fd_fdstat_set_flags(fd: u32, flags: u32);
+    fd_fdstat_set_rights(fd: u32, rights_base: u64, rights_inheriting: u64);
+    fd_filestat_get(fd: u32, filestat_ptr: u32);
+    fd_filestat_set_size(fd: u32, size: u64);
+    fd_filestat_set_times(fd: u32, atim: u64, mtim: u64, fst_flags: u32);
+    fd_pread(fd: u32, iovs: u32, iovs_len: u32, offset: u64, nread_ptr: u32);
+    fd_prestat_get(fd: u32, prestat_ptr: u32);
+    fd_prestat_dir_name(fd: u32, path: u32, path_len: u32);
+    fd_pwrite(fd: u32, iovs: u32, iovs_len: u32, offset: u64, nwritten_ptr: u32);
+    fd_read(fd: u32, iovs: u32, iovs_len: u32, nread_ptr: u32);
+    fd_readdir(fd: u32, buf: u32, buf_len: u32, cookie: u64, bufused_ptr: u32);
+    fd_renumber(fd: u32, to: u32);
+    fd_seek(fd: u32, offset: i64, whence: u32, newoffset_ptr: u32);
+    fd_sync(fd: u32);
+    fd_tell(fd: u32, offset_ptr: u32);
+    fd_write(fd: u32, iovs: u32, iovs_len: u32, nwritten_ptr: u32);
+    path_create_directory(fd: u32, path: u32, path_len: u32);
+    path_filestat_get(fd: u32, flags: u32, path: u32, path_len: u32, filestat_ptr: u32);
+    path_filestat_set_times(fd: u32, flags: u32, path: u32, path_len: u32, atim: u64, mtim: u64, fst_flags: u32);
+    path_link(old_fd: u32, old_flags: u32, old_path: u32, old_path_len: u32, new_fd: u32, new_path: u32, new_path_len: u32);
+    path_open(fd: u32, dirflags: u32, path: u32, path_len: u32, oflags: u32, rights_base: u64, rights_inheriting: u64, fdflags: u32, fd_ptr: u32);
+    path_readlink(fd: u32, path: u32, path_len: u32, buf: u32, buf_len: u32, bufused_ptr: u32);
+    path_remove_directory(fd: u32, path: u32, path_len: u32);
+    path_rename(fd: u32, old_path: u32, old_path_len: u32, new_fd: u32, new_path: u32, new_path_len: u32);
+    path_symlink(old_path: u32, old_path_len: u32, fd: u32, new_path: u32, new_path_len: u32);
+    path_unlink_file(fd: u32, path: u32, path_len: u32);
+    poll_oneoff(subscriptions: u32, events: u32, subscription_count: u32, event_count_ptr: u32);
+    proc_raise(sig: u32);
+    sched_yield();
+    random_get(buf: u32, buf_len: u32);
+    sock_accept(fd: u32, flags: u32, fd_ptr: u32);
+    sock_recv(fd: u32, ri_data: u32, ri_data_len: u32, ri_flags: u32, ro_datalen_ptr: u32, ro_flags_ptr: u32);
+    sock_send(fd: u32, si_data: u32, si_data_len: u32, si_flags: u32, so_datalen_ptr: u32);
+    sock_shutdown(fd: u32, how: u32);
+}
+
+/// A view of the memory of the calling instance, with bounds checked accessors.
+struct GuestMem<'a>(&'a mut [u8]);
+
+impl GuestMem<'_> {
+    fn slice(&self, ptr: u32, len: u32) -> Result<&[u8], Errno> {
+        let start = ptr as usize;
+        let end = start.checked_add(len as usize).ok_or(ERRNO_FAULT)?;
+        self.0.get(start..end).ok_or(ERRNO_FAULT)
+    }
+
+    fn slice_mut(&mut self, ptr: u32, len: u32) -> Result<&mut [u8], Errno> {
+        let start = ptr as usize;
+        let end = start.checked_add(len as usize).ok_or(ERRNO_FAULT)?;
+        self.0.get_mut(start..end).ok_or(ERRNO_FAULT)
+    }
+
+    fn read_u32(&self, ptr: u32) -> Result<u32, Errno> {
+        Ok(u32::from_le_bytes(self.slice(ptr, 4)?.try_into().unwrap()))
+    }
+
+    fn read_u64(&self, ptr: u32) -> Result<u64, Errno> {
+        Ok(u64::from_le_bytes(self.slice(ptr, 8)?.try_into().unwrap()))
+    }
+
+    fn read_str(&self, ptr: u32, len: u32) -> Result<&str, Errno> {
+        std::str::from_utf8(self.slice(ptr, len)?).map_err(|_| ERRNO_ILSEQ)
+    }
+
+    fn write_bytes(&mut self, ptr: u32, bytes: &[u8]) -> Result<(), Errno> {
+        self.slice_mut(ptr, bytes.len() as u32)?
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn write_u8(&mut self, ptr: u32, val: u8) -> Result<(), Errno> {
+        self.write_bytes(ptr, &[val])
+    }
+
+    fn write_u16(&mut self, ptr: u32, val: u16) -> Result<(), Errno> {
+        self.write_bytes(ptr, &val.to_le_bytes())
+    }
+
+    fn write_u32(&mut self, ptr: u32, val: u32) -> Result<(), Errno> {
+        self.write_bytes(ptr, &val.to_le_bytes())
+    }
+
+    fn write_u64(&mut self, ptr: u32, val: u64) -> Result<(), Errno> {
+        self.write_bytes(ptr, &val.to_le_bytes())
+    }
+
+    /// Reads an array of `iovec`s or `ciovec`s, as (pointer, length) pairs.
+    fn iovecs(&self, iovs: u32, iovs_len: u32) -> Result<Vec<(u32, u32)>, Errno> {
+        (0..iovs_len)
+            .map(|index| {
+                let iov = iovs
+                    .checked_add(index.checked_mul(8).ok_or(ERRNO_FAULT)?)
+                    .ok_or(ERRNO_FAULT)?;
+                Ok((self.read_u32(iov)?, self.read_u32(iov + 4)?))
+            })
+            .collect()
+    }
+
+    /// Gathers the bytes of an array of `ciovec`s.
+    fn gather(&self, iovs: u32, iovs_len: u32) -> Result<Vec<u8>, Errno> {
+        let mut bytes = Vec::new();
+        for (ptr, len) in self.iovecs(iovs, iovs_len)? {
+            bytes.extend_from_slice(self.slice(ptr, len)?);
+        }
+        Ok(bytes)
+    }
+}
+
+#[derive(Debug)]
+enum FdEntry {
+    Stdin,
+    Stdout,
+    Stderr,
+    Dir {
+        path: PathBuf,
+        // the preopened directory this one was opened through, paths can't leave it
+        root: PathBuf,
+        // the guest name, if this is a preopened directory
+        preopen: Option<String>,
+    },
+    File {
+        file: File,
+        append: bool,
+    },
+}
+
+#[derive(Debug)]
+struct WasiState {
+    args: Vec<String>,
+    env: Vec<(String, String)>,
+    fds: Vec<Option<FdEntry>>,
+    // None reads from the stdin of the host process
+    stdin: Option<(Vec<u8>, usize)>,
+    stdout: (WasiOutput, Vec<u8>),
+    stderr: (WasiOutput, Vec<u8>),
+    exit_code: Option<i32>,
+    start: Instant,
+    random: RandomState,
+    random_counter: u64,
+}
+
+// The methods for WASI functions take the same arguments as the functions themselves.
+#[allow(clippy::too_many_arguments)]
+impl WasiState {
+    fn new() -> Self {
+        Self {
+            args: Vec::new(),
+            env: Vec::new(),
+            fds: vec![
+                Some(FdEntry::Stdin),
+                Some(FdEntry::Stdout),
+                Some(FdEntry::Stderr),
+            ],
+            stdin: None,
+            stdout: (WasiOutput::Inherit, Vec::new()),
+            stderr: (WasiOutput::Inherit, Vec::new()),
+            exit_code: None,
+            start: Instant::now(),
+            random: RandomState::new(),
+            random_counter: 0,
+        }
+    }
+
+    fn push_fd(&mut self, entry: FdEntry) -> u32 {
+        match self.fds.iter().position(|fd| fd.is_none()) {
+            Some(fd) => {
+                self.fds[fd] = Some(entry);
+                fd as u32
+            }
+            None => {
+                self.fds.push(Some(entry));
+                (self.fds.len() - 1) as u32
+            }
+        }
+    }
+
+    fn fd(&mut self, fd: u32) -> Result<&mut FdEntry, Errno> {
+        self.fds
+            .get_mut(fd as usize)
+            .and_then(|fd| fd.as_mut())
+            .ok_or(ERRNO_BADF)
+    }
+
+    fn file(&mut self, fd: u32) -> Result<&mut File, Errno> {
+        match self.fd(fd)? {
+            FdEntry::File { file, .. } => Ok(file),
+            FdEntry::Dir { .. } => Err(ERRNO_ISDIR),
+            _ => Err(ERRNO_SPIPE),
+        }
+    }
+
+    /// Resolves a guest path relative to a directory fd to a host path, without leaving the
+    /// preopened directory the fd belongs to, also not through symbolic links.
+    fn resolve(&mut self, fd: u32, path: &str, follow: bool) -> Result<(PathBuf, PathBuf), Errno> {
+        let FdEntry::Dir {
+            path: dir, root, ..
+        } = self.fd(fd)?
+        else {
+            return Err(ERRNO_NOTDIR);
+        };
+        if path.starts_with('/') {
+            return Err(ERRNO_NOTCAPABLE);
+        }
+        let mut parts: Vec<String> = dir
+            .strip_prefix(&*root)
+            .map_err(|_| ERRNO_NOTCAPABLE)?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        for part in path.split('/') {
+            match part {
+                "" | "." => {}
+                ".." => {
+                    parts.pop().ok_or(ERRNO_NOTCAPABLE)?;
+                }
+                part => {
+                    if Path::new(part)
+                        .components()
+                        .any(|component| !matches!(component, Component::Normal(_)))
+                    {
+                        return Err(ERRNO_NOTCAPABLE);
+                    }
+                    parts.push(part.to_string());
+                }
+            }
+        }
+        let root = root.clone();
+        let host_path = parts
+            .iter()
+            .fold(root.clone(), |path, part| path.join(part));
+
+        // symbolic links on the way must not point out of the root either
+        let mut check = host_path.parent();
+        while let Some(path) = check {
+            match path.canonicalize() {
+                Ok(path) => {
+                    if !path.starts_with(&root) {
+                        return Err(ERRNO_NOTCAPABLE);
+                    }
+                    break;
+                }
+                Err(_) => check = path.parent(),
+            }
+        }
+
+        // neither may the last component, if it is a symbolic link that we follow. if it is one
+        // that we don't follow, the caller must take care not to follow it by accident.
+        let is_symlink = fs::symlink_metadata(&host_path)
+            .is_ok_and(|metadata| metadata.file_type().is_symlink());
+        if follow && is_symlink {
+            let Ok(path) = host_path.canonicalize() else {
+                return Err(ERRNO_NOTCAPABLE);
+            };
+            if !path.starts_with(&root) {
+                return Err(ERRNO_NOTCAPABLE);
+            }
+            return Ok((path, root));
+        }
+        Ok((host_path, root))
+    }
+
+    fn read_path(
+        &mut self,
+        mem: &GuestMem,
+        fd: u32,
+        path: u32,
+        path_len: u32,
+        follow: bool,
+    ) -> Result<(PathBuf, PathBuf), Errno> {
+        let path = mem.read_str(path, path_len)?.to_string();
+        self.resolve(fd, &path, follow)
+    }
+
+    fn random_u64(&mut self) -> u64 {
+        let mut hasher = self.random.build_hasher();
+        hasher.write_u64(self.random_counter);
+        self.random_counter += 1;
+        hasher.finish()
+    }
+
+    fn now(&self, id: u32) -> Result<u64, Errno> {
+        match id {
+            CLOCK_REALTIME => Ok(SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_nanos() as u64)),
+            CLOCK_MONOTONIC | CLOCK_PROCESS_CPUTIME | CLOCK_THREAD_CPUTIME => {
+                Ok(self.start.elapsed().as_nanos() as u64)
+            }
+            _ => Err(ERRNO_INVAL),
+        }
+    }
+
+    fn args_get(&mut self, mut mem: GuestMem, argv: u32, argv_buf: u32) -> Result<(), Errno> {
+        write_strings(&mut mem, self.args.iter().cloned(), argv, argv_buf)
+    }
+
+    fn args_sizes_get(
+        &mut self,
+        mut mem: GuestMem,
+        argc_ptr: u32,
+        argv_buf_size_ptr: u32,
+    ) -> Result<(), Errno> {
+        mem.write_u32(argc_ptr, self.args.len() as u32)?;
+        mem.write_u32(
+            argv_buf_size_ptr,
+            self.args.iter().map(|arg| arg.len() as u32 + 1).sum(),
+        )
+    }
+
+    fn environ_get(
+        &mut self,
+        mut mem: GuestMem,
+        environ: u32,
+        environ_buf: u32,
+    ) -> Result<(), Errno> {
+        write_strings(
+            &mut mem,
+            self.env
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value)),
+            environ,
+            environ_buf,
+        )
+    }
+
+    fn environ_sizes_get(
+        &mut self,
+        mut mem: GuestMem,
+        environ_count_ptr: u32,
+        environ_buf_size_ptr: u32,
+    ) -> Result<(), Errno> {
+        mem.write_u32(environ_count_ptr, self.env.len() as u32)?;
+        mem.write_u32(
+            environ_buf_size_ptr,
+            self.env
+                .iter()
+                .map(|(key, value)| (key.len() + value.len()) as u32 + 2)
+                .sum(),
+        )
+    }
+
+    fn clock_res_get(
+        &mut self,
+        mut mem: GuestMem,
+        id: u32,
+        resolution_ptr: u32,
+    ) -> Result<(), Errno> {
+        self.now(id)?;
+        mem.write_u64(resolution_ptr, 1_000)
+    }
+
+    fn clock_time_get(
+        &mut self,
+        mut mem: GuestMem,
+        id: u32,
+        _precision: u64,
+        time_ptr: u32,
+    ) -> Result<(), Errno> {
+        mem.write_u64(time_ptr, self.now(id)?)
+    }
+
+    fn fd_advise(
+        &mut self,
+        _mem: GuestMem,
+        fd: u32,
+        _offset: u64,
+        _len: u64,
+        _advice: u32,
+    ) -> Result<(), Errno> {
+        self.file(fd)?;
+        Ok(())
+    }
+
+    fn fd_allocate(&mut self, _mem: GuestMem, fd: u32, offset: u64, len: u64) -> Result<(), Errno> {
+        let file = self.file(fd)?;
+        let size = offset.checked_add(len).ok_or(ERRNO_INVAL)?;
+        if file.metadata().map_err(errno)?.len() < size {
+            file.set_len(size).map_err(errno)?;
+        }
+        Ok(())
+    }
+
+    fn fd_close(&mut self, _mem: GuestMem, fd: u32) -> Result<(), Errno> {
+        self.fd(fd)?;
+        self.fds[fd as usize] = None;
+        Ok(())
+    }
+
+    fn fd_datasync(&mut self, _mem: GuestMem, fd: u32) -> Result<(), Errno> {
+        self.file(fd)?.sync_data().map_err(errno)
+    }
+
+    fn fd_fdstat_get(&mut self, mut mem: GuestMem, fd: u32, fdstat_ptr: u32) -> Result<(), Errno> {
+        let (filetype, flags) = match self.fd(fd)? {
+            FdEntry::Stdin | FdEntry::Stdout | FdEntry::Stderr => (FILETYPE_CHARACTER_DEVICE, 0),
+            FdEntry::Dir { .. } => (FILETYPE_DIRECTORY, 0),
+            FdEntry::File { append, .. } => (
+                FILETYPE_REGULAR_FILE,
+                if *append { FDFLAGS_APPEND } else { 0 },
+            ),
+        };
+        mem.write_bytes(fdstat_ptr, &[0; 24])?;
+        mem.write_u8(fdstat_ptr, filetype)?;
+        mem.write_u16(fdstat_ptr + 2, flags)?;
+        mem.write_u64(fdstat_ptr + 8, RIGHTS_ALL)?;
+        mem.write_u64(fdstat_ptr + 16, RIGHTS_ALL)
+    }
+
+    fn fd_fdstat_set_flags(&mut self, _mem: GuestMem, fd: u32, flags: u32) -> Result<(), Errno> {
+        if let FdEntry::File { append, .. } = self.fd(fd)? {
+            *append = flags as u16 & FDFLAGS_APPEND != 0;
+        }
+        Ok(())
+    }
+
+    fn fd_fdstat_set_rights(
+        &mut self,
+        _mem: GuestMem,
+        fd: u32,
+        _rights_base: u64,
+        _rights_inheriting: u64,
+    ) -> Result<(), Errno> {
+        self.fd(fd)?;
+        Ok(())
+    }
+
+    fn fd_filestat_get(
+        &mut self,
+        mut mem: GuestMem,
+        fd: u32,
+        filestat_ptr: u32,
+    ) -> Result<(), Errno> {
+        let metadata = match self.fd(fd)? {
+            FdEntry::Stdin | FdEntry::Stdout | FdEntry::Stderr => None,
+            FdEntry::Dir { path, .. } => Some(fs::metadata(path).map_err(errno)?),
+            FdEntry::File { file, .. } => Some(file.metadata().map_err(errno)?),
+        };
+        write_filestat(&mut mem, filestat_ptr, metadata.as_ref())
+    }
+
+    fn fd_filestat_set_size(&mut self, _mem: GuestMem, fd: u32, size: u64) -> Result<(), Errno> {
+        self.file(fd)?.set_len(size).map_err(errno)
+    }
+
+    fn fd_filestat_set_times(
+        &mut self,
+        _mem: GuestMem,
+        fd: u32,
+        atim: u64,
+        mtim: u64,
+        fst_flags: u32,
+    ) -> Result<(), Errno> {
+        match self.fd(fd)? {
+            FdEntry::File { file, .. } => set_times(file, atim, mtim, fst_flags as u16),
+            FdEntry::Dir { path, .. } => set_times(
+                &File::open(path).map_err(errno)?,
+                atim,
+                mtim,
+                fst_flags as u16,
+            ),
+            _ => Err(ERRNO_BADF),
+        }
+    }
+
+    fn fd_pread(
+        &mut self,
+        mut mem: GuestMem,
+        fd: u32,
+        iovs: u32,
+        iovs_len: u32,
+        offset: u64,
+        nread_ptr: u32,
+    ) -> Result<(), Errno> {
+        let iovecs = mem.iovecs(iovs, iovs_len)?;
+        let file = self.file(fd)?;
+        let position = file.stream_position().map_err(errno)?;
+        file.seek(SeekFrom::Start(offset)).map_err(errno)?;
+        let result = read_iovecs(&mut mem, &iovecs, file);
+        file.seek(SeekFrom::Start(position)).map_err(errno)?;
+        mem.write_u32(nread_ptr, result?)
+    }
+
+    fn fd_prestat_get(
+        &mut self,
+        mut mem: GuestMem,
+        fd: u32,
+        prestat_ptr: u32,
+    ) -> Result<(), Errno> {
+        let FdEntry::Dir {
+            preopen: Some(name),
+            ..
+        } = self.fd(fd)?
+        else {
+            return Err(ERRNO_BADF);
+        };
+        let len = name.len() as u32;
+        mem.write_bytes(prestat_ptr, &[0; 8])?;
+        mem.write_u32(prestat_ptr + 4, len)
+    }
+
+    fn fd_prestat_dir_name(
+        &mut self,
+        mut mem: GuestMem,
+        fd: u32,
+        path: u32,
+        path_len: u32,
+    ) -> Result<(), Errno> {
+        let FdEntry::Dir {
+            preopen: Some(name),
+            ..
+        } = self.fd(fd)?
+        else {
+            return Err(ERRNO_BADF);
+        };
+        let name = name.as_bytes();
+        let len = name.len().min(path_len as usize);
+        mem.write_bytes(path, &name[..len])
+    }
+
+    fn fd_pwrite(
+        &mut self,
+        mut mem: GuestMem,
+        fd: u32,
+        iovs: u32,
+        iovs_len: u32,
+        offset: u64,
+        nwritten_ptr: u32,
+    ) -> Result<(), Errno> {
+        let bytes = mem.gather(iovs, iovs_len)?;
+        let file = self.file(fd)?;
+        let position = file.stream_position().map_err(errno)?;
+        file.seek(SeekFrom::Start(offset)).map_err(errno)?;
+        let result = file.write(&bytes).map_err(errno);
+        file.seek(SeekFrom::Start(position)).map_err(errno)?;
+        mem.write_u32(nwritten_ptr, result? as u32)
+    }
+
+    fn fd_read(
+        &mut self,
+        mut mem: GuestMem,
+        fd: u32,
+        iovs: u32,
+        iovs_len: u32,
+        nread_ptr: u32,
+    ) -> Result<(), Errno> {
+        let iovecs = mem.iovecs(iovs, iovs_len)?;
+        let stdin = &mut self.stdin;
+        let nread = match self
+            .fds
+            .get_mut(fd as usize)
+            .and_then(|fd| fd.as_mut())
+            .ok_or(ERRNO_BADF)?
+        {
+            FdEntry::Stdin => match stdin {
+                Some((bytes, position)) => {
+                    let mut nread = 0;
+                    for &(ptr, len) in &iovecs {
+                        let rest = &bytes[*position..];
+                        let count = rest.len().min(len as usize);
+                        mem.write_bytes(ptr, &rest[..count])?;
+                        *position += count;
+                        nread += count as u32;
+                    }
+                    nread
+                }
+                // only fill the first buffer, so we don't block on more input than we need
+                None => match iovecs.iter().find(|(_, len)| *len > 0) {
+                    Some(&(ptr, len)) => {
+                        io::stdin().read(mem.slice_mut(ptr, len)?).map_err(errno)? as u32
+                    }
+                    None => 0,
+                },
+            },
+            FdEntry::File { file, .. } => read_iovecs(&mut mem, &iovecs, file)?,
+            FdEntry::Dir { .. } => return Err(ERRNO_ISDIR),
+            _ => return Err(ERRNO_BADF),
+        };
+        mem.write_u32(nread_ptr, nread)
+    }
+
+    fn fd_readdir(
+        &mut self,
+        mut mem: GuestMem,
+        fd: u32,
+        buf: u32,
+        buf_len: u32,
+        cookie: u64,
+        bufused_ptr: u32,
+    ) -> Result<(), Errno> {
+        let FdEntry::Dir { path, .. } = self.fd(fd)? else {
+            return Err(ERRNO_NOTDIR);
+        };
+        let mut entries = vec![
+            (".".to_string(), FILETYPE_DIRECTORY, 0),
+            ("..".to_string(), FILETYPE_DIRECTORY, 0),
+        ];
+        let mut dir_entries = Vec::new();
+        for entry in fs::read_dir(&*path).map_err(errno)? {
+            let entry = entry.map_err(errno)?;
+            let metadata = entry.metadata().map_err(errno)?;
+            dir_entries.push((
+                entry.file_name().to_string_lossy().into_owned(),
+                filetype(&metadata),
+                ino(&metadata),
+            ));
+        }
+        // sorted, so the cookies stay valid between calls
+        dir_entries.sort();
+        entries.extend(dir_entries);
+
+        let mut bytes = Vec::new();
+        for (index, (name, filetype, ino)) in entries.iter().enumerate().skip(cookie as usize) {
+            if bytes.len() >= buf_len as usize {
+                break;
+            }
+            bytes.extend_from_slice(&(index as u64 + 1).to_le_bytes());
+            bytes.extend_from_slice(&ino.to_le_bytes());
+            bytes.extend_from_slice(&(name.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(&[*filetype, 0, 0, 0]);
+            bytes.extend_from_slice(name.as_bytes());
+        }
+        // a full buffer tells the guest to call again for the rest
+        bytes.truncate(buf_len as usize);
+        mem.write_bytes(buf, &bytes)?;
+        mem.write_u32(bufused_ptr, bytes.len() as u32)
+    }
+
+    fn fd_renumber(&mut self, _mem: GuestMem, fd: u32, to: u32) -> Result<(), Errno> {
+        self.fd(fd)?;
+        self.fd(to)?;
+        self.fds[to as usize] = self.fds[fd as usize].take();
+        Ok(())
+    }
+
+    fn fd_seek(
+        &mut self,
+        mut mem: GuestMem,
+        fd: u32,
+        offset: i64,
+        whence: u32,
+        newoffset_ptr: u32,
+    ) -> Result<(), Errno> {
+        let from = match whence {
+            0 => SeekFrom::Start(u64::try_from(offset).map_err(|_| ERRNO_INVAL)?),
+            1 => SeekFrom::Current(offset),
+            2 => SeekFrom::End(offset),
+            _ => return Err(ERRNO_INVAL),
+        };
+        let position = self.file(fd)?.seek(from).map_err(errno)?;
+        mem.write_u64(newoffset_ptr, position)
+    }
+
+    fn fd_sync(&mut self, _mem: GuestMem, fd: u32) -> Result<(), Errno> {
+        self.file(fd)?.sync_all().map_err(errno)
+    }
+
+    fn fd_tell(&mut self, mut mem: GuestMem, fd: u32, offset_ptr: u32) -> Result<(), Errno> {
+        let position = self.file(fd)?.stream_position().map_err(errno)?;
+        mem.write_u64(offset_ptr, position)
+    }
+
+    fn fd_write(
+        &mut self,
+        mut mem: GuestMem,
+        fd: u32,
+        iovs: u32,
+        iovs_len: u32,
+        nwritten_ptr: u32,
+    ) -> Result<(), Errno> {
+        let bytes = mem.gather(iovs, iovs_len)?;
+        let (stdout, stderr) = (&mut self.stdout, &mut self.stderr);
+        match self
+            .fds
+            .get_mut(fd as usize)
+            .and_then(|fd| fd.as_mut())
+            .ok_or(ERRNO_BADF)?
+        {
+            FdEntry::Stdout => write_output(stdout, io::stdout(), &bytes)?,
+            FdEntry::Stderr => write_output(stderr, io::stderr(), &bytes)?,
+            FdEntry::File { file, append, .. } => {
+                if *append {
+                    file.seek(SeekFrom::End(0)).map_err(errno)?;
+                }
+                file.write_all(&bytes).map_err(errno)?;
+            }
+            FdEntry::Dir { .. } => return Err(ERRNO_ISDIR),
+            FdEntry::Stdin => return Err(ERRNO_BADF),
+        }
+        mem.write_u32(nwritten_ptr, bytes.len() as u32)
+    }
+
+    fn path_create_directory(
+        &mut self,
+        mem: GuestMem,
+        fd: u32,
+        path: u32,
+        path_len: u32,
+    ) -> Result<(), Errno> {
+        let (path, _) = self.read_path(&mem, fd, path, path_len, false)?;
+        fs::create_dir(path).map_err(errno)
+    }
+
+    fn path_filestat_get(
+        &mut self,
+        mut mem: GuestMem,
+        fd: u32,
+        flags: u32,
+        path: u32,
+        path_len: u32,
+        filestat_ptr: u32,
+    ) -> Result<(), Errno> {
+        let follow = flags & LOOKUPFLAGS_SYMLINK_FOLLOW != 0;
+        let (path, _) = self.read_path(&mem, fd, path, path_len, follow)?;
+        let metadata = if follow {
+            fs::metadata(path)
+        } else {
+            fs::symlink_metadata(path)
+        };
+        write_filestat(&mut mem, filestat_ptr, Some(&metadata.map_err(errno)?))
+    }
+
+    fn path_filestat_set_times(
+        &mut self,
+        mem: GuestMem,
+        fd: u32,
+        flags: u32,
+        path: u32,
+        path_len: u32,
+        atim: u64,
+        mtim: u64,
+        fst_flags: u32,
+    ) -> Result<(), Errno> {
+        let (path, _) = self.read_path(
+            &mem,
+            fd,
+            path,
+            path_len,
+            flags & LOOKUPFLAGS_SYMLINK_FOLLOW != 0,
+        )?;
+        set_times(
+            &File::open(path).map_err(errno)?,
+            atim,
+            mtim,
+            fst_flags as u16,
+        )
+    }
+
+    fn path_link(
+        &mut self,
+        mem: GuestMem,
+        old_fd: u32,
+        old_flags: u32,
+        old_path: u32,
+        old_path_len: u32,
+        new_fd: u32,
+        new_path: u32,
+        new_path_len: u32,
+    ) -> Result<(), Errno> {
+        let (old_path, _) = self.read_path(
+            &mem,
+            old_fd,
+            old_path,
+            old_path_len,
+            old_flags & LOOKUPFLAGS_SYMLINK_FOLLOW != 0,
+        )?;
+        let (new_path, _) = self.read_path(&mem, new_fd, new_path, new_path_len, false)?;
+        fs::hard_link(old_path, new_path).map_err(errno)
+    }
+
+    fn path_open(
+        &mut self,
+        mut mem: GuestMem,
+        fd: u32,
+        dirflags: u32,
+        path: u32,
+        path_len: u32,
+        oflags: u32,
+        rights_base: u64,
+        _rights_inheriting: u64,
+        fdflags: u32,
+        fd_ptr: u32,
+    ) -> Result<(), Errno> {
+        let (path, root) = self.read_path(
+            &mem,
+            fd,
+            path,
+            path_len,
+            dirflags & LOOKUPFLAGS_SYMLINK_FOLLOW != 0,
+        )?;
+        let oflags = oflags as u16;
+        let fdflags = fdflags as u16;
+        let read = rights_base & RIGHTS_FD_READ != 0;
+        let write = rights_base & RIGHTS_FD_WRITE != 0 || oflags & OFLAGS_TRUNC != 0;
+        if dirflags & LOOKUPFLAGS_SYMLINK_FOLLOW == 0
+            && fs::symlink_metadata(&path).is_ok_and(|metadata| metadata.file_type().is_symlink())
+        {
+            return Err(ERRNO_LOOP);
+        }
+        let entry =
+            if oflags & OFLAGS_DIRECTORY != 0 || (path.is_dir() && oflags & OFLAGS_CREAT == 0) {
+                if !path.is_dir() {
+                    return Err(if path.exists() {
+                        ERRNO_NOTDIR
+                    } else {
+                        ERRNO_NOENT
+                    });
+                }
+                if write {
+                    return Err(ERRNO_ISDIR);
+                }
+                FdEntry::Dir {
+                    path,
+                    root,
+                    preopen: None,
+                }
+            } else {
+                let mut options = OpenOptions::new();
+                if dirflags & LOOKUPFLAGS_SYMLINK_FOLLOW == 0 {
+                    no_follow(&mut options);
+                }
+                let file = options
+                    .read(read || !write)
+                    .write(write)
+                    .create(oflags & OFLAGS_CREAT != 0 && oflags & OFLAGS_EXCL == 0)
+                    .create_new(oflags & OFLAGS_CREAT != 0 && oflags & OFLAGS_EXCL != 0)
+                    .truncate(oflags & OFLAGS_TRUNC != 0)
+                    .open(path)
+                    .map_err(errno)?;
+                FdEntry::File {
+                    file,
+                    append: fdflags & FDFLAGS_APPEND != 0,
+                }
+            };
+        let fd = self.push_fd(entry);
+        mem.write_u32(fd_ptr, fd)
+    }
+
+    fn path_readlink(
+        &mut self,
+        mut mem: GuestMem,
+        fd: u32,
+        path: u32,
+        path_len: u32,
+        buf: u32,
+        buf_len: u32,
+        bufused_ptr: u32,
+    ) -> Result<(), Errno> {
+        let (path, _) = self.read_path(&mem, fd, path, path_len, false)?;
+        let target = fs::read_link(path).map_err(errno)?;
+        let target = target.to_string_lossy();
+        let len = target.len().min(buf_len as usize);
+        mem.write_bytes(buf, &target.as_bytes()[..len])?;
+        mem.write_u32(bufused_ptr, len as u32)
+    }
+
+    fn path_remove_directory(
+        &mut self,
+        mem: GuestMem,
+        fd: u32,
+        path: u32,
+        path_len: u32,
+    ) -> Result<(), Errno> {
+        let (path, _) = self.read_path(&mem, fd, path, path_len, false)?;
+        fs::remove_dir(path).map_err(errno)
+    }
+
+    fn path_rename(
+        &mut self,
+        mem: GuestMem,
+        fd: u32,
+        old_path: u32,
+        old_path_len: u32,
+        new_fd: u32,
+        new_path: u32,
+        new_path_len: u32,
+    ) -> Result<(), Errno> {
+        let (old_path, _) = self.read_path(&mem, fd, old_path, old_path_len, false)?;
+        let (new_path, _) = self.read_path(&mem, new_fd, new_path, new_path_len, false)?;
+        fs::rename(old_path, new_path).map_err(errno)
+    }
+
+    fn path_symlink(
+        &mut self,
+        mem: GuestMem,
+        old_path: u32,
+        old_path_len: u32,
+        fd: u32,
+        new_path: u32,
+        new_path_len: u32,
+    ) -> Result<(), Errno> {
+        let target = mem.read_str(old_path, old_path_len)?.to_string();
+        let (new_path, _) = self.read_path(&mem, fd, new_path, new_path_len, false)?;
+        // guest paths are relative to a preopened directory, so an absolute target can't mean what
+        // the guest intends. a relative target may still point out of the preopened directory
+        // (possibly through other links), but `resolve` never lets a path through it.
+        if target.starts_with('/') {
+            return Err(ERRNO_NOTCAPABLE);
+        }
+        symlink(&target, &new_path)
+    }
+
+    fn path_unlink_file(
+        &mut self,
+        mem: GuestMem,
+        fd: u32,
+        path: u32,
+        path_len: u32,
+    ) -> Result<(), Errno> {
+        let (path, _) = self.read_path(&mem, fd, path, path_len, false)?;
+        if fs::symlink_metadata(&path).map_err(errno)?.is_dir() {
+            return Err(ERRNO_ISDIR);
+        }
+        fs::remove_file(path).map_err(errno)
+    }
+
+    fn poll_oneoff(
+        &mut self,
+        mut mem: GuestMem,
+        subscriptions: u32,
+        events: u32,
+        subscription_count: u32,
+        event_count_ptr: u32,
+    ) -> Result<(), Errno> {
+        if subscription_count == 0 {
+            return Err(ERRNO_INVAL);
+        }
+        // (userdata, event type, nanoseconds left for clocks)
+        let mut pending = Vec::new();
+        for index in 0..subscription_count {
+            let subscription = subscriptions
+                .checked_add(index.checked_mul(48).ok_or(ERRNO_FAULT)?)
+                .ok_or(ERRNO_FAULT)?;
+            let userdata = mem.read_u64(subscription)?;
+            let tag = mem.slice(subscription + 8, 1)?[0];
+            match tag {
+                EVENTTYPE_CLOCK => {
+                    let id = mem.read_u32(subscription + 16)?;
+                    let timeout = mem.read_u64(subscription + 24)?;
+                    let flags =
+                        u16::from_le_bytes(mem.slice(subscription + 40, 2)?.try_into().unwrap());
+                    let left = if flags & SUBCLOCKFLAGS_ABSTIME != 0 {
+                        timeout.saturating_sub(self.now(id)?)
+                    } else {
+                        self.now(id)?;
+                        timeout
+                    };
+                    pending.push((userdata, tag, left));
+                }
+                EVENTTYPE_FD_READ | EVENTTYPE_FD_WRITE => {
+                    self.fd(mem.read_u32(subscription + 16)?)?;
+                    // files and the standard streams are always ready
+                    pending.push((userdata, tag, 0));
+                }
+                _ => return Err(ERRNO_INVAL),
+            }
+        }
+        let wait = pending.iter().map(|(_, _, left)| *left).min().unwrap();
+        if wait > 0 {
+            thread::sleep(Duration::from_nanos(wait));
+        }
+        let mut count = 0;
+        for (userdata, tag, left) in pending {
+            if left > wait {
+                continue;
+            }
+            let event = events.checked_add(count * 32).ok_or(ERRNO_FAULT)?;
+            mem.write_bytes(event, &[0; 32])?;
+            mem.write_u64(event, userdata)?;
+            mem.write_u8(event + 10, tag)?;
+            count += 1;
+        }
+        mem.write_u32(event_count_ptr, count)
+    }
+
+    fn proc_raise(&mut self, _mem: GuestMem, _sig: u32) -> Result<(), Errno> {
+        Err(ERRNO_NOSYS)
+    }
+
+    fn sched_yield(&mut self, _mem: GuestMem) -> Result<(), Errno> {
+        thread::yield_now();
+        Ok(())
+    }
+
+    fn random_get(&mut self, mut mem: GuestMem, buf: u32, buf_len: u32) -> Result<(), Errno> {
+        mem.slice(buf, buf_len)?;
+        for offset in (0..buf_len).step_by(8) {
+            let bytes = self.random_u64().to_le_bytes();
+            let len = (buf_len - offset).min(8);
+            mem.write_bytes(buf + offset, &bytes[..len as usize])?;
+        }
+        Ok(())
+    }
+
+    fn sock_accept(
+        &mut self,
+        _mem: GuestMem,
+        _fd: u32,
+        _flags: u32,
+        _fd_ptr: u32,
+    ) -> Result<(), Errno> {
+        Err(ERRNO_NOSYS)
+    }
+
+    fn sock_recv(
+        &mut self,
+        _mem: GuestMem,
+        _fd: u32,
+        _ri_data: u32,
+        _ri_data_len: u32,
+        _ri_flags: u32,
+        _ro_datalen_ptr: u32,
+        _ro_flags_ptr: u32,
+    ) -> Result<(), Errno> {
+        Err(ERRNO_NOSYS)
+    }
+
+    fn sock_send(
+        &mut self,
+        _mem: GuestMem,
+        _fd: u32,
+        _si_data: u32,
+        _si_data_len: u32,
+        _si_flags: u32,
+        _so_datalen_ptr: u32,
+    ) -> Result<(), Errno> {
+        Err(ERRNO_NOSYS)
+    }
+
+    fn sock_shutdown(&mut self, _mem: GuestMem, _fd: u32, _how: u32) -> Result<(), Errno> {
+        Err(ERRNO_NOSYS)
+    }
+}
+
+/// Writes null terminated strings to `buf`, and pointers to them to `ptrs`.
+fn write_strings(
+    mem: &mut GuestMem,
+    strings: impl Iterator<Item = String>,
+    mut ptrs: u32,
+    mut buf: u32,
+) -> Result<(), Errno> {
+    for string in strings {
+        mem.write_u32(ptrs, buf)?;
+        mem.write_bytes(buf, string.as_bytes())?;
+        mem.write_u8(buf + string.len() as u32, 0)?;
+        ptrs += 4;
+        buf += string.len() as u32 + 1;
+    }
+    Ok(())
+}
+
+fn write_output(
+    output: &mut (WasiOutput, Vec<u8>),
+    mut stream: impl Write,
+    bytes: &[u8],
+) -> Result<(), Errno> {
+    match output.0 {
+        WasiOutput::Inherit => {
+            stream.write_all(bytes).map_err(errno)?;
+            stream.flush().map_err(errno)
+        }
+        WasiOutput::Null => Ok(()),
+        WasiOutput::Capture => {
+            output.1.extend_from_slice(bytes);
+            Ok(())
+        }
+    }
+}
+
+fn read_iovecs(mem: &mut GuestMem, iovecs: &[(u32, u32)], file: &mut File) -> Result<u32, Errno> {
+    let mut nread = 0;
+    for &(ptr, len) in iovecs {
+        let count = file.read(mem.slice_mut(ptr, len)?).map_err(errno)?;
+        nread += count as u32;
+        if count < len as usize {
+            break;
+        }
+    }
+    Ok(nread)
+}
+
+fn write_filestat(
+    mem: &mut GuestMem,
+    ptr: u32,
+    metadata: Option<&fs::Metadata>,
+) -> Result<(), Errno> {
+    mem.write_bytes(ptr, &[0; 64])?;
+    let Some(metadata) = metadata else {
+        mem.write_u8(ptr + 16, FILETYPE_CHARACTER_DEVICE)?;
+        return Ok(());
+    };
+    let nanos = |time: io::Result<SystemTime>| {
+        time.ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_nanos() as u64)
+    };
+    mem.write_u64(ptr + 8, ino(metadata))?;
+    mem.write_u8(ptr + 16, filetype(metadata))?;
+    mem.write_u64(ptr + 24, 1)?;
+    mem.write_u64(ptr + 32, metadata.len())?;
+    mem.write_u64(ptr + 40, nanos(metadata.accessed()))?;
+    mem.write_u64(ptr + 48, nanos(metadata.modified()))?;
+    mem.write_u64(
+        ptr + 56,
+        nanos(metadata.created().or_else(|_| metadata.modified())),
+    )
+}
+
+fn set_times(file: &File, atim: u64, mtim: u64, fst_flags: u16) -> Result<(), Errno> {
+    let time = |nanos: u64, set: u16, now: u16| {
+        if fst_flags & now != 0 {
+            Some(SystemTime::now())
+        } else if fst_flags & set != 0 {
+            Some(UNIX_EPOCH + Duration::from_nanos(nanos))
+        } else {
+            None
+        }
+    };
+    let mut times = FileTimes::new();
+    if let Some(time) = time(atim, FSTFLAGS_ATIM, FSTFLAGS_ATIM_NOW) {
+        times = times.set_accessed(time);
+    }
+    if let Some(time) = time(mtim, FSTFLAGS_MTIM, FSTFLAGS_MTIM_NOW) {
+        times = times.set_modified(time);
+    }
+    file.set_times(times).map_err(errno)
+}
+
+fn filetype(metadata: &fs::Metadata) -> u8 {
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        FILETYPE_DIRECTORY
+    } else if file_type.is_file() {
+        FILETYPE_REGULAR_FILE
+    } else if file_type.is_symlink() {
+        FILETYPE_SYMBOLIC_LINK
+    } else {
+        FILETYPE_UNKNOWN
+    }
+}
+
+#[cfg(unix)]
+fn ino(metadata: &fs::Metadata) -> u64 {
+    std::os::unix::fs::MetadataExt::ino(metadata)
+}
+
+#[cfg(not(unix))]
+fn ino(_metadata: &fs::Metadata) -> u64 {
+    0
+}
+
+/// Makes opening a file not follow a symbolic link in its last path component.
+#[cfg(unix)]
+fn no_follow(options: &mut OpenOptions) {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    const O_NOFOLLOW: i32 = if cfg!(any(
+        target_arch = "arm",
+        target_arch = "aarch64",
+        target_arch = "powerpc",
+        target_arch = "powerpc64"
+    )) {
+        0o100000
+    } else {
+        0o400000
+    };
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    const O_NOFOLLOW: i32 = 0x0100;
+    std::os::unix::fs::OpenOptionsExt::custom_flags(options, O_NOFOLLOW);
+}
+
+#[cfg(windows)]
+fn no_follow(options: &mut OpenOptions) {
+    const FILE_FLAG_OPEN_REPARSE_POINT: u32 = 0x00200000;
+    std::os::windows::fs::OpenOptionsExt::custom_flags(options, FILE_FLAG_OPEN_REPARSE_POINT);
+}
+
+#[cfg(not(any(unix, windows)))]
+fn no_follow(_options: &mut OpenOptions) {}
+
+#[cfg(unix)]
+fn symlink(target: &str, path: &Path) -> Result<(), Errno> {
+    std::os::unix::fs::symlink(target, path).map_err(errno)
+}
+
+#[cfg(not(unix))]
+fn symlink(_target: &str, _path: &Path) -> Result<(), Errno> {
+    Err(ERRNO_NOSYS)
+}
+
+fn errno(error: io::Error) -> Errno {
+    match error.kind() {
+        io::ErrorKind::NotFound => ERRNO_NOENT,
+        io::ErrorKind::PermissionDenied => ERRNO_ACCES,
+        io::ErrorKind::AlreadyExists => ERRNO_EXIST,
+        io::ErrorKind::InvalidInput => ERRNO_INVAL,
+        io::ErrorKind::NotADirectory => ERRNO_NOTDIR,
+        io::ErrorKind::IsADirectory => ERRNO_ISDIR,
+        io::ErrorKind::DirectoryNotEmpty => ERRNO_NOTEMPTY,
+        io::ErrorKind::ArgumentListTooLong => ERRNO_2BIG,
+        _ => ERRNO_IO,
+    }
+}
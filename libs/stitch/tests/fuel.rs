@@ -0,0 +1,144 @@
+use {
+    makepad_stitch::{
+        Engine, EngineConfig, Error, FuelCosts, Func, Linker, Module, Store, Trap, Val,
+    },
+    std::{thread, time::Duration},
+    wast::{parser, parser::ParseBuffer, Wat},
+};
+
+fn instantiate(config: EngineConfig, wat: &str) -> (Store, Func) {
+    let buf = ParseBuffer::new(wat).unwrap();
+    let mut wat = parser::parse::<Wat>(&buf).unwrap();
+    let bytes = wat.encode().unwrap();
+    let mut store = Store::new(Engine::with_config(config));
+    let module = Module::new(store.engine(), &bytes).unwrap();
+    let instance = Linker::new().instantiate(&mut store, &module).unwrap();
+    let func = instance.exported_func("run").unwrap();
+    (store, func)
+}
+
+const COUNT: &str = r#"
+    (module
+        (func (export "run") (param $n i32) (result i32)
+            (local $i i32)
+            (loop $loop
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br_if $loop (i32.lt_u (local.get $i) (local.get $n)))
+            )
+            (local.get $i)
+        )
+    )
+"#;
+
+const SPIN: &str = r#"
+    (module
+        (func (export "run") (param i32) (result i32)
+            (loop $loop
+                (br $loop)
+            )
+            (unreachable)
+        )
+    )
+"#;
+
+#[test]
+fn consumes_fuel() {
+    let (mut store, func) = instantiate(
+        EngineConfig {
+            fuel_costs: Some(FuelCosts::default()),
+            ..EngineConfig::default()
+        },
+        COUNT,
+    );
+    let mut results = [Val::I32(0)];
+    store.set_fuel(1_000);
+    func.call(&mut store, &[Val::I32(10)], &mut results)
+        .unwrap();
+    assert_eq!(results[0], Val::I32(10));
+    let fuel_for_10 = 1_000 - store.fuel();
+    store.set_fuel(1_000);
+    func.call(&mut store, &[Val::I32(20)], &mut results)
+        .unwrap();
+    let fuel_for_20 = 1_000 - store.fuel();
+    assert!(fuel_for_20 > fuel_for_10);
+}
+
+#[test]
+fn branches_consume_extra_fuel() {
+    let fuel_for = |branch| {
+        let (mut store, func) = instantiate(
+            EngineConfig {
+                fuel_costs: Some(FuelCosts { instr: 1, branch }),
+                ..EngineConfig::default()
+            },
+            COUNT,
+        );
+        store.set_fuel(1_000);
+        func.call(&mut store, &[Val::I32(10)], &mut [Val::I32(0)])
+            .unwrap();
+        1_000 - store.fuel()
+    };
+    assert_eq!(fuel_for(1), fuel_for(0) + 10);
+}
+
+#[test]
+fn traps_when_out_of_fuel() {
+    let (mut store, func) = instantiate(
+        EngineConfig {
+            fuel_costs: Some(FuelCosts::default()),
+            ..EngineConfig::default()
+        },
+        SPIN,
+    );
+    store.set_fuel(10_000);
+    let result = func.call(&mut store, &[Val::I32(0)], &mut [Val::I32(0)]);
+    assert!(matches!(result, Err(Error::Trap(Trap::OutOfFuel))));
+    assert!(store.fuel() < 10_000);
+}
+
+#[test]
+fn does_not_consume_fuel_by_default() {
+    let (mut store, func) = instantiate(EngineConfig::default(), COUNT);
+    let mut results = [Val::I32(0)];
+    func.call(&mut store, &[Val::I32(10)], &mut results)
+        .unwrap();
+    assert_eq!(results[0], Val::I32(10));
+    assert_eq!(store.fuel(), 0);
+}
+
+#[test]
+fn interrupts_from_another_thread() {
+    let (mut store, func) = instantiate(
+        EngineConfig {
+            interruptible: true,
+            ..EngineConfig::default()
+        },
+        SPIN,
+    );
+    let handle = store.interrupt_handle();
+    let thread = thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+        handle.interrupt();
+    });
+    let result = func.call(&mut store, &[Val::I32(0)], &mut [Val::I32(0)]);
+    thread.join().unwrap();
+    assert!(matches!(result, Err(Error::Trap(Trap::Interrupted))));
+}
+
+#[test]
+fn interrupt_is_cleared_once_observed() {
+    let (mut store, func) = instantiate(
+        EngineConfig {
+            interruptible: true,
+            ..EngineConfig::default()
+        },
+        COUNT,
+    );
+    let mut results = [Val::I32(0)];
+    store.interrupt_handle().interrupt();
+    let result = func.call(&mut store, &[Val::I32(10)], &mut results);
+    assert!(matches!(result, Err(Error::Trap(Trap::Interrupted))));
+    func.call(&mut store, &[Val::I32(10)], &mut results)
+        .unwrap();
+    assert_eq!(results[0], Val::I32(10));
+}
@@ -0,0 +1,155 @@
+use {
+    makepad_stitch::{
+        Engine, Error, Instance, InstantiateError, Limits, Linker, Mem, MemType, Module,
+        ResourceLimiter, ResourceUsage, Store, Val,
+    },
+    std::{cell::Cell, rc::Rc},
+    wast::{parser, parser::ParseBuffer, Wat},
+};
+
+struct MaxLimiter {
+    max_mem_pages: u32,
+    max_table_elems: u32,
+}
+
+impl ResourceLimiter for MaxLimiter {
+    fn mem_growing(&mut self, _current: u32, desired: u32, _max: Option<u32>) -> bool {
+        desired <= self.max_mem_pages
+    }
+
+    fn table_growing(&mut self, _current: u32, desired: u32, _max: Option<u32>) -> bool {
+        desired <= self.max_table_elems
+    }
+}
+
+// Allows tables to grow, but not memories, and counts the elements it allowed tables to grow by.
+struct CountingLimiter {
+    table_elems: Rc<Cell<u64>>,
+}
+
+impl ResourceLimiter for CountingLimiter {
+    fn mem_growing(&mut self, _current: u32, _desired: u32, _max: Option<u32>) -> bool {
+        false
+    }
+
+    fn table_growing(&mut self, current: u32, desired: u32, _max: Option<u32>) -> bool {
+        self.table_elems
+            .set(self.table_elems.get() + (desired - current) as u64);
+        true
+    }
+}
+
+fn instantiate(store: &mut Store, wat: &str) -> Result<Instance, Error> {
+    let buf = ParseBuffer::new(wat).unwrap();
+    let mut wat = parser::parse::<Wat>(&buf).unwrap();
+    let bytes = wat.encode().unwrap();
+    let module = Module::new(store.engine(), &bytes).unwrap();
+    Linker::new().instantiate(store, &module)
+}
+
+fn call(store: &mut Store, instance: &Instance, name: &str, arg: i32) -> i32 {
+    let func = instance.exported_func(name).unwrap();
+    let mut results = [Val::I32(0)];
+    func.call(store, &[Val::I32(arg)], &mut results).unwrap();
+    results[0].to_i32().unwrap()
+}
+
+const GROW: &str = r#"
+    (module
+        (memory 1)
+        (table 1 funcref)
+        (func (export "memory_grow") (param i32) (result i32)
+            (memory.grow (local.get 0))
+        )
+        (func (export "table_grow") (param i32) (result i32)
+            (table.grow (ref.null func) (local.get 0))
+        )
+    )
+"#;
+
+#[test]
+fn limits_memory_grow() {
+    let mut store = Store::new(Engine::new());
+    store.set_limiter(MaxLimiter {
+        max_mem_pages: 4,
+        max_table_elems: 10,
+    });
+    let instance = instantiate(&mut store, GROW).unwrap();
+    assert_eq!(call(&mut store, &instance, "memory_grow", 3), 1);
+    assert_eq!(call(&mut store, &instance, "memory_grow", 1), -1);
+}
+
+#[test]
+fn limits_table_grow() {
+    let mut store = Store::new(Engine::new());
+    store.set_limiter(MaxLimiter {
+        max_mem_pages: 4,
+        max_table_elems: 10,
+    });
+    let instance = instantiate(&mut store, GROW).unwrap();
+    assert_eq!(call(&mut store, &instance, "table_grow", 9), 1);
+    assert_eq!(call(&mut store, &instance, "table_grow", 1), -1);
+}
+
+#[test]
+fn limits_host_memory_grow() {
+    let mut store = Store::new(Engine::new());
+    store.set_limiter(MaxLimiter {
+        max_mem_pages: 2,
+        max_table_elems: 10,
+    });
+    let mem = Mem::new(
+        &mut store,
+        MemType {
+            limits: Limits { min: 1, max: None },
+        },
+    );
+    assert_eq!(mem.grow(&mut store, 1).unwrap(), 1);
+    assert!(mem.grow(&mut store, 1).is_err());
+}
+
+#[test]
+fn limits_instantiation() {
+    let mut store = Store::new(Engine::new());
+    store.set_limiter(MaxLimiter {
+        max_mem_pages: 0,
+        max_table_elems: 10,
+    });
+    let result = instantiate(&mut store, GROW);
+    assert!(matches!(
+        result,
+        Err(Error::Instantiate(InstantiateError::ResourceLimitExceeded))
+    ));
+}
+
+#[test]
+fn counts_only_created_items_when_instantiation_fails() {
+    let mut store = Store::new(Engine::new());
+    let table_elems = Rc::new(Cell::new(0));
+    store.set_limiter(CountingLimiter {
+        table_elems: table_elems.clone(),
+    });
+    let result = instantiate(&mut store, GROW);
+    assert!(matches!(
+        result,
+        Err(Error::Instantiate(InstantiateError::ResourceLimitExceeded))
+    ));
+    assert_eq!(store.resource_usage().table_elems, table_elems.get());
+}
+
+#[test]
+fn reports_resource_usage() {
+    let mut store = Store::new(Engine::new());
+    let instance = instantiate(&mut store, GROW).unwrap();
+    call(&mut store, &instance, "memory_grow", 2);
+    call(&mut store, &instance, "table_grow", 4);
+    assert_eq!(
+        store.resource_usage(),
+        ResourceUsage {
+            mem_count: 1,
+            mem_pages: 3,
+            table_count: 1,
+            table_elems: 5,
+        }
+    );
+}
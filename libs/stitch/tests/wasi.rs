@@ -0,0 +1,188 @@
+#![cfg(feature = "wasi")]
+
+use {
+    makepad_stitch::{Engine, Error, Linker, Module, Store, Val, Wasi, WasiOutput},
+    std::{env, fs, process},
+    wast::{parser, parser::ParseBuffer, Wat},
+};
+
+fn run(wasi: &Wasi, wat: &str) -> (Result<(), Error>, Val) {
+    let buf = ParseBuffer::new(wat).unwrap();
+    let mut wat = parser::parse::<Wat>(&buf).unwrap();
+    let bytes = wat.encode().unwrap();
+    let mut store = Store::new(Engine::new());
+    let module = Module::new(store.engine(), &bytes).unwrap();
+    let mut linker = Linker::new();
+    wasi.add_to_linker(&mut store, &mut linker);
+    let instance = linker.instantiate(&mut store, &module).unwrap();
+    let func = instance.exported_func("run").unwrap();
+    let mut results = [Val::I32(0)];
+    let result = func.call(&mut store, &[], &mut results);
+    (result, results[0])
+}
+
+#[test]
+fn fd_write_and_proc_exit() {
+    let mut wasi = Wasi::new();
+    wasi.set_stdout(WasiOutput::Capture);
+    let (result, _) = run(
+        &wasi,
+        r#"
+        (module
+            (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+            (memory (export "memory") 1)
+            (data (i32.const 16) "hello\n")
+            (func (export "run") (result i32)
+                (i32.store (i32.const 0) (i32.const 16))
+                (i32.store (i32.const 4) (i32.const 6))
+                (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))
+                (call $proc_exit (i32.const 3))
+                (i32.const 0)
+            )
+        )
+        "#,
+    );
+    assert!(matches!(result, Err(Error::Exit(3))));
+    assert_eq!(wasi.exit_code(), Some(3));
+    assert_eq!(wasi.take_stdout(), b"hello\n");
+}
+
+#[test]
+fn args_get() {
+    let mut wasi = Wasi::new();
+    wasi.set_args(["program", "argument"]);
+    wasi.set_stdout(WasiOutput::Capture);
+    let (result, val) = run(
+        &wasi,
+        r#"
+        (module
+            (import "wasi_snapshot_preview1" "args_sizes_get" (func $args_sizes_get (param i32 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "args_get" (func $args_get (param i32 i32) (result i32)))
+            (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+            (memory (export "memory") 1)
+            (func (export "run") (result i32)
+                (drop (call $args_sizes_get (i32.const 0) (i32.const 4)))
+                (drop (call $args_get (i32.const 16) (i32.const 64)))
+                ;; print the second argument, without its null terminator
+                (i32.store (i32.const 32) (i32.load (i32.const 20)))
+                (i32.store (i32.const 36) (i32.sub (i32.add (i32.const 64) (i32.load (i32.const 4))) (i32.add (i32.load (i32.const 20)) (i32.const 1))))
+                (drop (call $fd_write (i32.const 1) (i32.const 32) (i32.const 1) (i32.const 8)))
+                (i32.load (i32.const 0))
+            )
+        )
+        "#,
+    );
+    result.unwrap();
+    assert_eq!(val, Val::I32(2));
+    assert_eq!(wasi.take_stdout(), b"argument");
+}
+
+// Opens `path` relative to the preopened directory (fd 3) with the given lookup flags, `O_CREAT`,
+// and write rights, writes "data" to it, and returns the errno of the open.
+fn open_and_write(wasi: &Wasi, path: &str, dirflags: u32) -> Val {
+    let (result, errno) = run(
+        wasi,
+        &format!(
+            r#"
+            (module
+                (import "wasi_snapshot_preview1" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+                (import "wasi_snapshot_preview1" "fd_close" (func $fd_close (param i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 16) "data")
+                (data (i32.const 32) "{path}")
+                (func (export "run") (result i32)
+                    (local $errno i32)
+                    (local.set $errno (call $path_open (i32.const 3) (i32.const {dirflags}) (i32.const 32) (i32.const {len}) (i32.const 1) (i64.const 64) (i64.const 0) (i32.const 0) (i32.const 0)))
+                    (if (i32.eqz (local.get $errno))
+                        (then
+                            (i32.store (i32.const 8) (i32.const 16))
+                            (i32.store (i32.const 12) (i32.const 4))
+                            (drop (call $fd_write (i32.load (i32.const 0)) (i32.const 8) (i32.const 1) (i32.const 4)))
+                            (drop (call $fd_close (i32.load (i32.const 0))))
+                        )
+                    )
+                    (local.get $errno)
+                )
+            )
+            "#,
+            path = path,
+            dirflags = dirflags,
+            len = path.len(),
+        ),
+    );
+    result.unwrap();
+    errno
+}
+
+// Creates a symbolic link at `path` relative to the preopened directory (fd 3), pointing to
+// `target`, and returns the errno.
+fn symlink(wasi: &Wasi, target: &str, path: &str) -> Val {
+    let (result, errno) = run(
+        wasi,
+        &format!(
+            r#"
+            (module
+                (import "wasi_snapshot_preview1" "path_symlink" (func $path_symlink (param i32 i32 i32 i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 0) "{target}")
+                (data (i32.const 256) "{path}")
+                (func (export "run") (result i32)
+                    (call $path_symlink (i32.const 0) (i32.const {target_len}) (i32.const 3) (i32.const 256) (i32.const {path_len}))
+                )
+            )
+            "#,
+            target = target,
+            target_len = target.len(),
+            path = path,
+            path_len = path.len(),
+        ),
+    );
+    result.unwrap();
+    errno
+}
+
+#[test]
+fn path_open_is_sandboxed() {
+    let dir = env::temp_dir().join(format!("makepad-stitch-wasi-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let mut wasi = Wasi::new();
+    wasi.preopen_dir(&dir, "/sandbox").unwrap();
+    assert_eq!(open_and_write(&wasi, "file.txt", 1), Val::I32(0));
+    assert_eq!(fs::read(dir.join("file.txt")).unwrap(), b"data");
+    assert_eq!(
+        open_and_write(&wasi, "sub/../../escaped.txt", 1),
+        Val::I32(76)
+    );
+    assert_eq!(open_and_write(&wasi, "/escaped.txt", 1), Val::I32(76));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn symlinks_can_not_escape_sandbox() {
+    let base = env::temp_dir().join(format!("makepad-stitch-wasi-symlink-{}", process::id()));
+    let dir = base.join("sandbox");
+    fs::create_dir_all(dir.join("sub/sub2")).unwrap();
+    fs::write(base.join("secret.txt"), b"secret").unwrap();
+    std::os::unix::fs::symlink(base.join("secret.txt"), dir.join("secret.txt")).unwrap();
+    let mut wasi = Wasi::new();
+    wasi.preopen_dir(&dir, "/sandbox").unwrap();
+
+    // a link in the last path component is not opened when not following links, and must stay in
+    // the sandbox when following them
+    assert_eq!(open_and_write(&wasi, "secret.txt", 0), Val::I32(32));
+    assert_eq!(open_and_write(&wasi, "secret.txt", 1), Val::I32(76));
+
+    // chained links that point out of the sandbox can be created, but not used
+    assert_eq!(symlink(&wasi, "../..", "sub/sub2/x"), Val::I32(0));
+    assert_eq!(symlink(&wasi, "sub/sub2/x/../..", "l"), Val::I32(0));
+    assert_eq!(open_and_write(&wasi, "l/escaped.txt", 0), Val::I32(76));
+    assert_eq!(open_and_write(&wasi, "l/escaped.txt", 1), Val::I32(76));
+    assert_eq!(open_and_write(&wasi, "l", 1), Val::I32(76));
+
+    assert_eq!(fs::read(base.join("secret.txt")).unwrap(), b"secret");
+    assert!(!base.join("escaped.txt").exists());
+    fs::remove_dir_all(&base).unwrap();
+}
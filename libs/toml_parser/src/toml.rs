@@ -1,62 +1,46 @@
 use std::collections::{HashMap};
-use std::str::Chars;
+use std::ops::Range;
 
-#[derive(Default)]
-pub struct TomlParser {
-    pub cur: char,
-    pub pos: usize,
-}
-
-#[derive(PartialEq, Debug, Clone)]
+// byte range in the toml source
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
 pub struct TomlSpan {
     pub start: usize,
     pub len: usize
 }
 
-#[derive(PartialEq, Debug)]
-pub struct TomlTokWithSpan {
-    pub span: TomlSpan,
-    pub tok: TomlTok
-}
-
-#[derive(PartialEq, Debug)]
-pub enum TomlTok {
-    Ident(String),
-    Str(String),
-    U64(u64),
-    I64(i64),
-    F64(f64),
-    Bool(bool),
-    Nan(bool),
-    Inf(bool),
-    Date(String),
-    Equals,
-    BlockOpen,
-    BlockClose,
-    ObjectOpen,
-    ObjectClose,
-    Comma,
-    Bof,
-    Eof
+impl TomlSpan {
+    pub fn end(&self) -> usize {
+        self.start + self.len
+    }
 }
 
-
 #[derive(PartialEq, Debug, Clone)]
 pub enum Toml {
     Str(String, TomlSpan),
     Bool(bool, TomlSpan),
-    Num(f64, TomlSpan),
+    Int(i64, TomlSpan),
+    Float(f64, TomlSpan),
+    // dates and times are kept as written
     Date(String, TomlSpan),
-    Array(Vec<Toml>),
+    Array(Vec<Toml>, TomlSpan),
+    // a [table], an entry of an [[array.of.tables]] or a table made by a dotted key
+    Table(TomlTable),
+    InlineTable(TomlTable),
 }
 
-impl Toml {
-    pub fn into_str(self) -> Option<String> {
-        match self {
-            Self::Str(v, _) => Some(v),
-            _ => None
-        }
-    }
+#[derive(PartialEq, Debug, Clone)]
+pub struct TomlEntry {
+    pub key: String,
+    pub key_span: TomlSpan,
+    pub value: Toml,
+}
+
+// entries are kept in source order
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct TomlTable {
+    pub entries: Vec<TomlEntry>,
+    // the [header] or {..} in the source, empty for the root and for implicit tables
+    pub span: TomlSpan,
 }
 
 pub struct TomlErr {
@@ -70,328 +54,1230 @@ impl std::fmt::Debug for TomlErr {
     }
 }
 
-pub fn parse_toml(data: &str) -> Result<HashMap<String, Toml>, TomlErr> {
-    let i = &mut data.chars();
-    let mut t = TomlParser::default();
-    t.next(i);
-    let mut out = HashMap::new();
-    let mut local_scope = String::new();
-    loop {
-        let tok = t.next_tok(i) ?;
-        match tok.tok {
-            TomlTok::Eof => { // at eof.
-                return Ok(out);
-            },
-            TomlTok::BlockOpen => { // its a scope
-                // we should expect an ident or a string
-                let tok = t.next_tok(i) ?;
-                let (tok, double_block) = if let TomlTok::BlockOpen = tok.tok {
-                    (t.next_tok(i) ?, true)
+impl Toml {
+    pub fn into_str(self) -> Option<String> {
+        match self {
+            Self::Str(v, _) => Some(v),
+            _ => None
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Str(v, _) => Some(v),
+            _ => None
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Self::Bool(v, _) => Some(*v),
+            _ => None
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Self::Int(v, _) => Some(*v),
+            _ => None
+        }
+    }
+
+    // integers are numbers too
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Int(v, _) => Some(*v as f64),
+            Self::Float(v, _) => Some(*v),
+            _ => None
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&Vec<Toml>> {
+        match self {
+            Self::Array(v, _) => Some(v),
+            _ => None
+        }
+    }
+
+    pub fn as_table(&self) -> Option<&TomlTable> {
+        match self {
+            Self::Table(v) | Self::InlineTable(v) => Some(v),
+            _ => None
+        }
+    }
+
+    pub fn span(&self) -> TomlSpan {
+        match self {
+            Self::Str(_, span) | Self::Bool(_, span) | Self::Int(_, span) | Self::Float(_, span) |
+            Self::Date(_, span) | Self::Array(_, span) => *span,
+            Self::Table(table) | Self::InlineTable(table) => table.span
+        }
+    }
+
+    // a [table] or [[array of tables]], which live in their own sections instead of after a key
+    pub fn is_section(&self) -> bool {
+        match self {
+            Self::Table(_) => true,
+            Self::Array(items, _) => !items.is_empty() && items.iter().all( | v | matches!(v, Self::Table(_))),
+            _ => false
+        }
+    }
+
+    // the value as toml source, tables are written inline
+    pub fn to_toml(&self) -> String {
+        let mut out = String::new();
+        self.write_value(&mut out);
+        out
+    }
+
+    fn write_value(&self, out: &mut String) {
+        match self {
+            Self::Str(v, _) => write_toml_string(v, out),
+            Self::Bool(v, _) => out.push_str(if *v {"true"} else {"false"}),
+            Self::Int(v, _) => out.push_str(&v.to_string()),
+            Self::Float(v, _) => {
+                if v.is_nan() {
+                    out.push_str("nan");
                 }
-                else {(tok, false)};
-                
-                match tok.tok {
-                    TomlTok::Str(key) => { // a key
-                        local_scope = key;
-                    },
-                    TomlTok::Ident(key) => { // also a key
-                        local_scope = key;
-                    },
-                    _ => return Err(t.err_token(tok))
+                else if v.is_infinite() {
+                    out.push_str(if *v > 0.0 {"inf"} else {"-inf"});
                 }
-                let tok = t.next_tok(i) ?;
-                
-                if tok.tok != TomlTok::BlockClose {
-                    return Err(t.err_token(tok))
+                else {
+                    // debug formatting keeps a .0 and switches to an exponent for large and small numbers
+                    out.push_str(&format!("{:?}", v));
                 }
-                if double_block {
-                    let tok = t.next_tok(i) ?;
-                    if tok.tok != TomlTok::BlockClose {
-                        return Err(t.err_token(tok))
+            }
+            Self::Date(v, _) => out.push_str(v),
+            Self::Array(items, _) => {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i != 0 {
+                        out.push_str(", ");
                     }
+                    item.write_value(out);
                 }
-            },
-            TomlTok::Str(key) => { // a key
-                t.parse_key_value(&local_scope, key, i, &mut out) ?;
-            },
-            TomlTok::Ident(key) => { // also a key
-                t.parse_key_value(&local_scope, key, i, &mut out) ?;
-            },
-            _ => return Err(t.err_token(tok))
+                out.push(']');
+            }
+            Self::Table(table) | Self::InlineTable(table) => {
+                if table.entries.is_empty() {
+                    out.push_str("{}");
+                    return
+                }
+                out.push_str("{ ");
+                for (i, entry) in table.entries.iter().enumerate() {
+                    if i != 0 {
+                        out.push_str(", ");
+                    }
+                    out.push_str(&toml_key(&entry.key));
+                    out.push_str(" = ");
+                    entry.value.write_value(out);
+                }
+                out.push_str(" }");
+            }
         }
     }
 }
 
-impl TomlParser {
-    pub fn to_val(&mut self, tok: TomlTokWithSpan, i: &mut Chars) -> Result<Toml, TomlErr> {
-        match tok.tok {
-            TomlTok::BlockOpen => {
-                let mut vals = Vec::new();
-                loop {
-                    let tok = self.next_tok(i) ?;
-                    match tok.tok {
-                        TomlTok::BlockClose | TomlTok::Eof => {
-                            break;
+impl From<&str> for Toml {
+    fn from(v: &str) -> Self {Toml::Str(v.to_string(), TomlSpan::default())}
+}
+
+impl From<String> for Toml {
+    fn from(v: String) -> Self {Toml::Str(v, TomlSpan::default())}
+}
+
+impl From<bool> for Toml {
+    fn from(v: bool) -> Self {Toml::Bool(v, TomlSpan::default())}
+}
+
+impl From<i64> for Toml {
+    fn from(v: i64) -> Self {Toml::Int(v, TomlSpan::default())}
+}
+
+impl From<f64> for Toml {
+    fn from(v: f64) -> Self {Toml::Float(v, TomlSpan::default())}
+}
+
+impl From<Vec<Toml>> for Toml {
+    fn from(v: Vec<Toml>) -> Self {Toml::Array(v, TomlSpan::default())}
+}
+
+impl TomlTable {
+    pub fn get(&self, key: &str) -> Option<&Toml> {
+        self.entries.iter().find( | e | e.key == key).map( | e | &e.value)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut Toml> {
+        self.entries.iter_mut().find( | e | e.key == key).map( | e | &mut e.value)
+    }
+
+    // looks up a dotted path like dependencies.serde.version or target.'cfg(windows)'.dependencies,
+    // a number picks an entry from an array like bin.0.name
+    pub fn get_path(&self, path: &str) -> Option<&Toml> {
+        let keys = parse_toml_path(path).ok() ?;
+        let (first, rest) = keys.split_first() ?;
+        let mut value = self.get(first) ?;
+        for key in rest {
+            value = match value {
+                Toml::Table(table) | Toml::InlineTable(table) => table.get(key) ?,
+                Toml::Array(items, _) => items.get(key.parse::<usize>().ok() ?) ?,
+                _ => return None
+            };
+        }
+        Some(value)
+    }
+
+    // replaces the value of an existing key or adds it at the end
+    pub fn insert(&mut self, key: &str, value: Toml) -> Option<Toml> {
+        if let Some(old) = self.get_mut(key) {
+            return Some(std::mem::replace(old, value))
+        }
+        self.entries.push(TomlEntry {key: key.to_string(), key_span: TomlSpan::default(), value});
+        None
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Toml> {
+        let index = self.entries.iter().position( | e | e.key == key) ?;
+        Some(self.entries.remove(index).value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Toml)> {
+        self.entries.iter().map( | e | (e.key.as_str(), &e.value))
+    }
+
+    // the table as a toml document, plain values first and then the [sections]
+    pub fn to_toml(&self) -> String {
+        let mut out = String::new();
+        self.write_section(&mut Vec::new(), &mut out);
+        out
+    }
+
+    fn write_section(&self, path: &mut Vec<String>, out: &mut String) {
+        for entry in &self.entries {
+            if !entry.value.is_section() {
+                out.push_str(&toml_key(&entry.key));
+                out.push_str(" = ");
+                entry.value.write_value(out);
+                out.push('\n');
+            }
+        }
+        for entry in &self.entries {
+            path.push(toml_key(&entry.key));
+            match &entry.value {
+                Toml::Table(table) => {
+                    // a table that only holds other tables doesn't need a header
+                    if table.entries.is_empty() || table.entries.iter().any( | e | !e.value.is_section()) {
+                        if !out.is_empty() {
+                            out.push('\n');
                         }
-                        TomlTok::Comma => {}
-                        _ => {
-                            vals.push(self.to_val(tok, i) ?);
+                        out.push_str(&format!("[{}]\n", path.join(".")));
+                    }
+                    table.write_section(path, out);
+                }
+                Toml::Array(items, _) if entry.value.is_section() => for item in items {
+                    if let Toml::Table(table) = item {
+                        if !out.is_empty() {
+                            out.push('\n');
                         }
+                        out.push_str(&format!("[[{}]]\n", path.join(".")));
+                        table.write_section(path, out);
                     }
                 }
-                Ok(Toml::Array(vals))
-            },
-            TomlTok::Str(v) => Ok(Toml::Str(v, tok.span)),
-            TomlTok::U64(v) => Ok(Toml::Num(v as f64, tok.span)),
-            TomlTok::I64(v) => Ok(Toml::Num(v as f64, tok.span)),
-            TomlTok::F64(v) => Ok(Toml::Num(v, tok.span)),
-            TomlTok::Bool(v) => Ok(Toml::Bool(v, tok.span)),
-            TomlTok::Nan(v) => Ok(Toml::Num(if v {-std::f64::NAN}else {std::f64::NAN}, tok.span)),
-            TomlTok::Inf(v) => Ok(Toml::Num(if v {-std::f64::INFINITY}else {std::f64::INFINITY}, tok.span)),
-            TomlTok::Date(v) => Ok(Toml::Date(v, tok.span)),
-            _ => Err(self.err_token(tok))
-        }
-    }
-    
-    pub fn parse_key_value(&mut self, local_scope: &str, key: String, i: &mut Chars, out: &mut HashMap<String, Toml>) -> Result<(), TomlErr> {
-        let tok = self.next_tok(i) ?;
-        if tok.tok != TomlTok::Equals {
-            return Err(self.err_token(tok));
-        }
-        let tok = self.next_tok(i) ?;
-        // if we are an ObjectOpen we do a subscope
-        if let TomlTok::ObjectOpen = tok.tok {
-            let local_scope = if !local_scope.is_empty() {
-                format!("{}.{}", local_scope, key)
+                _ => ()
+            }
+            path.pop();
+        }
+    }
+}
+
+// a key as toml source, quoted when it isn't a bare key
+pub fn toml_key(key: &str) -> String {
+    if !key.is_empty() && key.bytes().all(is_bare_key_byte) {
+        return key.to_string()
+    }
+    if !key.contains(['\'', '\n', '\r']) {
+        return format!("'{}'", key)
+    }
+    let mut out = String::new();
+    write_toml_string(key, &mut out);
+    out
+}
+
+fn write_toml_string(v: &str, out: &mut String) {
+    out.push('"');
+    for c in v.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+}
+
+fn is_bare_key_byte(c: u8) -> bool {
+    c.is_ascii_alphanumeric() || c == b'_' || c == b'-'
+}
+
+fn is_date_time(text: &str) -> bool {
+    let b = text.as_bytes();
+    let digits = | r: Range<usize> | b.get(r).is_some_and( | v | v.iter().all(u8::is_ascii_digit));
+    // 1979-05-27 or 07:32:00 followed by anything
+    digits(0..4) && b.get(4) == Some(&b'-') && digits(5..7) && b.get(7) == Some(&b'-') && digits(8..10) ||
+    digits(0..2) && b.get(2) == Some(&b':') && digits(3..5)
+}
+
+pub fn parse_toml_path(path: &str) -> Result<Vec<String>, TomlErr> {
+    let mut parser = TomlParser::new(path);
+    let (keys, _) = parser.parse_key() ?;
+    parser.skip_ws();
+    if !parser.at_end() {
+        return Err(parser.err_expected("end of path"))
+    }
+    Ok(keys)
+}
+
+// the document as a tree of tables
+pub fn parse_toml_table(data: &str) -> Result<TomlTable, TomlErr> {
+    let mut p = TomlParser::new(data);
+    if data.starts_with('\u{feff}') {
+        p.pos = 3;
+    }
+    let mut root = TomlTable::default();
+    // the table the key values go into
+    let mut current = Vec::new();
+    // tables defined by a [header], which can't be defined again
+    let mut defined: Vec<Vec<String>> = Vec::new();
+    loop {
+        p.skip_trivia();
+        if p.at_end() {
+            return Ok(root)
+        }
+        if p.peek_byte(0) == b'[' {
+            let start = p.pos;
+            let is_array = p.starts_with("[[");
+            p.pos += if is_array {2} else {1};
+            let (path, _) = p.parse_key() ?;
+            p.skip_ws();
+            let close = if is_array {"]]"} else {"]"};
+            if !p.starts_with(close) {
+                return Err(p.err_expected(close))
+            }
+            p.pos += close.len();
+            let span = TomlSpan {start, len: p.pos - start};
+            let err = | msg | TomlErr {msg, span};
+            let (last, parents) = path.split_last().unwrap();
+            let parent = table_at(&mut root, parents, span) .map_err(err) ?;
+            let new_table = Toml::Table(TomlTable {entries: Vec::new(), span});
+            if is_array {
+                match parent.get_mut(last) {
+                    None => {
+                        parent.entries.push(TomlEntry {key: last.clone(), key_span: span, value: Toml::Array(vec![new_table], span)});
+                    }
+                    Some(value @ Toml::Array(..)) if value.is_section() => if let Toml::Array(items, _) = value {
+                        items.push(new_table);
+                    }
+                    Some(_) => return Err(err(format!("{} is not an array of tables", last)))
+                }
+                // the tables under the previous entry can be defined again under this one
+                defined.retain( | d | !d.starts_with(&path));
             }
             else {
-                key
-            };
-            loop {
-                let tok = self.next_tok(i) ?;
-                match tok.tok {
-                    TomlTok::ObjectClose | TomlTok::Eof => {
-                        break;
+                if defined.contains(&path) {
+                    return Err(err(format!("table {} is defined twice", last)))
+                }
+                match parent.get_mut(last) {
+                    None => {
+                        parent.entries.push(TomlEntry {key: last.clone(), key_span: span, value: new_table});
                     }
-                    TomlTok::Comma => {}
-                    TomlTok::Str(key) => { // a key
-                        self.parse_key_value(&local_scope, key, i, out) ?;
-                    },
-                    TomlTok::Ident(key) => { // also a key
-                        self.parse_key_value(&local_scope, key, i, out) ?;
-                    },
-                    _ => return Err(self.err_token(tok))
+                    // it was implied by an earlier [a.b.c] and now gets its own section
+                    Some(Toml::Table(table)) => table.span = span,
+                    Some(_) => return Err(err(format!("{} is not a table", last)))
                 }
+                defined.push(path.clone());
             }
+            current = path;
         }
         else {
-            let val = self.to_val(tok, i) ?;
-            let key = if !local_scope.is_empty() {
-                format!("{}.{}", local_scope, key)
+            let table = table_at(&mut root, &current, TomlSpan::default()).map_err( | msg | p.err(&msg)) ?;
+            p.parse_key_value(table) ?;
+        }
+        p.line_end() ?;
+    }
+}
+
+// the document as a flat map of dotted keys like package.metadata.x, arrays of tables stay arrays
+pub fn parse_toml(data: &str) -> Result<HashMap<String, Toml>, TomlErr> {
+    let mut out = HashMap::new();
+    flatten_table(&parse_toml_table(data) ?, "", &mut out);
+    Ok(out)
+}
+
+fn flatten_table(table: &TomlTable, prefix: &str, out: &mut HashMap<String, Toml>) {
+    for entry in &table.entries {
+        let key = format!("{}{}", prefix, toml_key(&entry.key));
+        match &entry.value {
+            Toml::Table(table) | Toml::InlineTable(table) => flatten_table(table, &format!("{}.", key), out),
+            value => {
+                out.insert(key, value.clone());
             }
-            else {
-                key
-            };
-            out.insert(key, val);
         }
-        Ok(())
     }
-    
-    pub fn next(&mut self, i: &mut Chars) {
-        if let Some(c) = i.next() {
-            self.cur = c;
+}
+
+// walks down to the table at path, making implicit tables for keys that don't exist yet.
+// for an array of tables it is the last table in it
+fn table_at<'a>(mut table: &'a mut TomlTable, path: &[String], key_span: TomlSpan) -> Result<&'a mut TomlTable, String> {
+    for key in path {
+        let index = match table.entries.iter().position( | e | e.key == *key) {
+            Some(index) => index,
+            None => {
+                table.entries.push(TomlEntry {key: key.clone(), key_span, value: Toml::Table(TomlTable::default())});
+                table.entries.len() - 1
+            }
+        };
+        table = match &mut table.entries[index].value {
+            Toml::Table(table) => table,
+            Toml::Array(items, _) => match items.last_mut() {
+                Some(Toml::Table(table)) => table,
+                _ => return Err(format!("{} is not an array of tables", key))
+            },
+            Toml::InlineTable(_) => return Err(format!("inline table {} can't be extended", key)),
+            _ => return Err(format!("{} is not a table", key))
+        };
+    }
+    Ok(table)
+}
+
+pub struct TomlParser<'a> {
+    pub data: &'a str,
+    pub pos: usize,
+}
+
+impl<'a> TomlParser<'a> {
+    pub fn new(data: &'a str) -> Self {
+        Self {data, pos: 0}
+    }
+
+    pub fn at_end(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    fn peek_byte(&self, offset: usize) -> u8 {
+        self.data.as_bytes().get(self.pos + offset).copied().unwrap_or(0)
+    }
+
+    fn starts_with(&self, what: &str) -> bool {
+        self.data[self.pos..].starts_with(what)
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.data[self.pos..].chars().next() ?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    pub fn err(&self, msg: &str) -> TomlErr {
+        TomlErr {msg: msg.to_string(), span: TomlSpan {start: self.pos, len: 0}}
+    }
+
+    pub fn err_expected(&self, what: &str) -> TomlErr {
+        match self.data[self.pos..].chars().next() {
+            Some(c) => self.err(&format!("Expected {} but found {:?}", what, c)),
+            None => self.err(&format!("Expected {} but found the end", what))
+        }
+    }
+
+    pub fn skip_ws(&mut self) {
+        while matches!(self.peek_byte(0), b' ' | b'\t') {
             self.pos += 1;
         }
-        else {
-            self.cur = '\0';
+    }
+
+    fn skip_comment(&mut self) {
+        if self.peek_byte(0) == b'#' {
+            while !self.at_end() && self.peek_byte(0) != b'\n' {
+                self.pos += 1;
+            }
+        }
+    }
+
+    fn eat_newline(&mut self) -> bool {
+        if self.starts_with("\n") {
+            self.pos += 1;
+            return true
+        }
+        if self.starts_with("\r\n") {
+            self.pos += 2;
+            return true
         }
+        false
     }
-    
-    pub fn err_token(&self, tok: TomlTokWithSpan) -> TomlErr {
-        TomlErr {msg: format!("Unexpected token {:?} ", tok), span: tok.span}
+
+    // whitespace, comments and newlines
+    fn skip_trivia(&mut self) {
+        loop {
+            self.skip_ws();
+            self.skip_comment();
+            if !self.eat_newline() {
+                break
+            }
+        }
     }
-    
-    pub fn err_parse(&self, what: &str) -> TomlErr {
-        TomlErr {msg: format!("Cannot parse toml {} ", what), span: TomlSpan {start: self.pos, len: 0}}
+
+    fn line_end(&mut self) -> Result<(), TomlErr> {
+        self.skip_ws();
+        self.skip_comment();
+        if self.at_end() || self.eat_newline() {
+            return Ok(())
+        }
+        Err(self.err_expected("a new line"))
     }
-    
-    pub fn next_tok(&mut self, i: &mut Chars) -> Result<TomlTokWithSpan, TomlErr> {
-        
-        while self.cur == '\n' || self.cur == '\r' || self.cur == '\t' || self.cur == ' ' || self.cur == '#' {
-            if self.cur == '#' {
-                while self.cur != '\n' && self.cur != '\0' {
-                    self.next(i);
+
+    // a bare, quoted or dotted key
+    pub fn parse_key(&mut self) -> Result<(Vec<String>, TomlSpan), TomlErr> {
+        self.skip_ws();
+        let start = self.pos;
+        let mut keys = Vec::new();
+        loop {
+            self.skip_ws();
+            match self.peek_byte(0) {
+                b'"' if !self.starts_with("\"\"\"") => keys.push(self.parse_basic_string() ?),
+                b'\'' if !self.starts_with("'''") => keys.push(self.parse_literal_string() ?),
+                c if is_bare_key_byte(c) => {
+                    let key_start = self.pos;
+                    while is_bare_key_byte(self.peek_byte(0)) {
+                        self.pos += 1;
+                    }
+                    keys.push(self.data[key_start..self.pos].to_string());
                 }
-                continue
+                _ => return Err(self.err_expected("a key"))
+            }
+            let end = self.pos;
+            self.skip_ws();
+            if self.peek_byte(0) != b'.' {
+                return Ok((keys, TomlSpan {start, len: end - start}))
             }
-            self.next(i);
+            self.pos += 1;
         }
+    }
+
+    // key = value into table, a dotted key makes implicit tables on the way
+    fn parse_key_value(&mut self, table: &mut TomlTable) -> Result<(), TomlErr> {
+        let (keys, key_span) = self.parse_key() ?;
+        self.skip_ws();
+        if self.peek_byte(0) != b'=' {
+            return Err(self.err_expected("="))
+        }
+        self.pos += 1;
+        self.skip_ws();
+        let value = self.parse_value() ?;
+        let (last, parents) = keys.split_last().unwrap();
+        let table = table_at(table, parents, key_span).map_err( | msg | TomlErr {msg, span: key_span}) ?;
+        if table.get(last).is_some() {
+            return Err(TomlErr {msg: format!("key {} is defined twice", last), span: key_span})
+        }
+        table.entries.push(TomlEntry {key: last.clone(), key_span, value});
+        Ok(())
+    }
+
+    pub fn parse_value(&mut self) -> Result<Toml, TomlErr> {
         let start = self.pos;
-        match self.cur {
-            '\0' => {
-                Ok(TomlTokWithSpan {tok: TomlTok::Eof, span: TomlSpan {start, len: 0}})
-            }
-            ',' => {
-                self.next(i);
-                Ok(TomlTokWithSpan {tok: TomlTok::Comma, span: TomlSpan {start, len: 1}})
-            }
-            '[' => {
-                self.next(i);
-                Ok(TomlTokWithSpan {tok: TomlTok::BlockOpen, span: TomlSpan {start, len: 1}})
-            }
-            ']' => {
-                self.next(i);
-                Ok(TomlTokWithSpan {tok: TomlTok::BlockClose, span: TomlSpan {start, len: 1}})
-            }
-            '{' => {
-                self.next(i);
-                Ok(TomlTokWithSpan {tok: TomlTok::ObjectOpen, span: TomlSpan {start, len: 1}})
-            }
-            '}' => {
-                self.next(i);
-                Ok(TomlTokWithSpan {tok: TomlTok::ObjectClose, span: TomlSpan {start, len: 1}})
-            }
-            '=' => {
-                self.next(i);
-                Ok(TomlTokWithSpan {tok: TomlTok::Equals, span: TomlSpan {start, len: 1}})
-            }
-            '+' | '-' | '0'..='9' => {
-                let mut num = String::new();
-                let is_neg = if self.cur == '-' {
-                    num.push(self.cur);
-                    self.next(i);
-                    true
+        match self.peek_byte(0) {
+            b'"' => {
+                let v = if self.starts_with("\"\"\"") {self.parse_multiline_basic_string() ?} else {self.parse_basic_string() ?};
+                Ok(Toml::Str(v, TomlSpan {start, len: self.pos - start}))
+            }
+            b'\'' => {
+                let v = if self.starts_with("'''") {self.parse_multiline_literal_string() ?} else {self.parse_literal_string() ?};
+                Ok(Toml::Str(v, TomlSpan {start, len: self.pos - start}))
+            }
+            b'[' => self.parse_array(),
+            b'{' => self.parse_inline_table(),
+            b't' if self.starts_with("true") => {
+                self.pos += 4;
+                Ok(Toml::Bool(true, TomlSpan {start, len: 4}))
+            }
+            b'f' if self.starts_with("false") => {
+                self.pos += 5;
+                Ok(Toml::Bool(false, TomlSpan {start, len: 5}))
+            }
+            b'0'..=b'9' | b'+' | b'-' | b'i' | b'n' => self.parse_number_or_date(),
+            _ => Err(self.err_expected("a value"))
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Toml, TomlErr> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut items = Vec::new();
+        loop {
+            self.skip_trivia();
+            if self.peek_byte(0) == b']' {
+                self.pos += 1;
+                break;
+            }
+            items.push(self.parse_value() ?);
+            self.skip_trivia();
+            match self.peek_byte(0) {
+                b',' => self.pos += 1,
+                b']' => {
+                    self.pos += 1;
+                    break;
                 }
-                else {
-                    if self.cur == '+' {
-                        self.next(i);
-                    }
-                    false
-                };
-                if self.cur == 'n' {
-                    self.next(i);
-                    if self.cur == 'a' {
-                        self.next(i);
-                        if self.cur == 'n' {
-                            self.next(i);
-                            return Ok(TomlTokWithSpan {tok: TomlTok::Nan(is_neg), span: TomlSpan {start, len: self.pos - start}})
-                        }
-                        else {
-                            return Err(self.err_parse("nan"))
-                        }
-                    }
-                    else {
-                        return Err(self.err_parse("nan"))
-                    }
+                _ => return Err(self.err_expected(", or ]"))
+            }
+        }
+        Ok(Toml::Array(items, TomlSpan {start, len: self.pos - start}))
+    }
+
+    fn parse_inline_table(&mut self) -> Result<Toml, TomlErr> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut table = TomlTable::default();
+        loop {
+            self.skip_trivia();
+            if self.peek_byte(0) == b'}' {
+                self.pos += 1;
+                break;
+            }
+            self.parse_key_value(&mut table) ?;
+            self.skip_trivia();
+            match self.peek_byte(0) {
+                b',' => self.pos += 1,
+                b'}' => {
+                    self.pos += 1;
+                    break;
                 }
-                if self.cur == 'i' {
-                    self.next(i);
-                    if self.cur == 'n' {
-                        self.next(i);
-                        if self.cur == 'f' {
-                            self.next(i);
-                            return Ok(TomlTokWithSpan {tok: TomlTok::Inf(is_neg), span: TomlSpan {start, len: self.pos - start}})
-                        }
-                        else {
-                            return Err(self.err_parse("inf"))
-                        }
-                    }
-                    else {
-                        return Err(self.err_parse("nan"))
-                    }
+                _ => return Err(self.err_expected(", or }"))
+            }
+        }
+        table.span = TomlSpan {start, len: self.pos - start};
+        Ok(Toml::InlineTable(table))
+    }
+
+    fn parse_number_or_date(&mut self) -> Result<Toml, TomlErr> {
+        let start = self.pos;
+        while matches!(self.peek_byte(0), b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z' | b'_' | b'+' | b'-' | b'.' | b':') {
+            self.pos += 1;
+            // a date and a time can be separated by a space
+            if self.pos - start == 10 && self.peek_byte(0) == b' ' && self.peek_byte(1).is_ascii_digit() && is_date_time(&self.data[start..self.pos]) {
+                self.pos += 1;
+            }
+        }
+        let text = &self.data[start..self.pos];
+        let span = TomlSpan {start, len: self.pos - start};
+        let err = | what: &str | TomlErr {msg: format!("Cannot parse {} {}", what, text), span};
+        if is_date_time(text) {
+            return Ok(Toml::Date(text.to_string(), span))
+        }
+        let (negative, body) = match text.as_bytes()[0] {
+            b'-' => (true, &text[1..]),
+            b'+' => (false, &text[1..]),
+            _ => (false, text)
+        };
+        match body {
+            "inf" => return Ok(Toml::Float(if negative {f64::NEG_INFINITY} else {f64::INFINITY}, span)),
+            "nan" => return Ok(Toml::Float(if negative {-f64::NAN} else {f64::NAN}, span)),
+            _ => ()
+        }
+        // underscores only between digits
+        let b = body.as_bytes();
+        for (i, c) in b.iter().enumerate() {
+            if *c == b'_' && (i == 0 || i == b.len() - 1 || !b[i - 1].is_ascii_alphanumeric() || !b[i + 1].is_ascii_alphanumeric()) {
+                return Err(err("number"))
+            }
+        }
+        let digits = body.replace('_', "");
+        for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+            if let Some(v) = digits.strip_prefix(prefix) {
+                if body.len() != text.len() || v.starts_with(['+', '-']) {
+                    return Err(err("integer"))
+                }
+                return i64::from_str_radix(v, radix).map( | v | Toml::Int(v, span)).map_err( | _ | err("integer"))
+            }
+        }
+        let b = digits.as_bytes();
+        if digits.contains(['.', 'e', 'E']) {
+            // a dot needs digits on both sides
+            for (i, c) in b.iter().enumerate() {
+                if *c == b'.' && (i == 0 || !b[i - 1].is_ascii_digit() || !b.get(i + 1).is_some_and(u8::is_ascii_digit)) {
+                    return Err(err("float"))
                 }
-                while self.cur >= '0' && self.cur <= '9' || self.cur == '_' {
-                    if self.cur != '_' {
-                        num.push(self.cur);
+            }
+            if b.len() > 1 && b[0] == b'0' && b[1].is_ascii_digit() || !b.iter().all( | c | c.is_ascii_digit() || matches!(c, b'.' | b'e' | b'E' | b'+' | b'-')) {
+                return Err(err("float"))
+            }
+            return digits.parse::<f64>().map( | v | Toml::Float(if negative {-v} else {v}, span)).map_err( | _ | err("float"))
+        }
+        if b.is_empty() || b.len() > 1 && b[0] == b'0' || !b.iter().all(u8::is_ascii_digit) {
+            return Err(err("integer"))
+        }
+        let digits = if negative {format!("-{}", digits)} else {digits};
+        digits.parse::<i64>().map( | v | Toml::Int(v, span)).map_err( | _ | err("integer"))
+    }
+
+    fn parse_hex_char(&mut self, len: usize) -> Result<char, TomlErr> {
+        let hex = self.data.get(self.pos..self.pos + len).filter( | v | v.bytes().all( | c | c.is_ascii_hexdigit()));
+        let c = hex.and_then( | v | char::from_u32(u32::from_str_radix(v, 16).ok() ?));
+        if let Some(c) = c {
+            self.pos += len;
+            return Ok(c)
+        }
+        Err(self.err("invalid unicode escape"))
+    }
+
+    // after the backslash
+    fn parse_escape(&mut self, out: &mut String) -> Result<(), TomlErr> {
+        match self.next_char() {
+            Some('b') => out.push('\u{8}'),
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some('f') => out.push('\u{c}'),
+            Some('r') => out.push('\r'),
+            Some('e') => out.push('\u{1b}'),
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            Some('u') => out.push(self.parse_hex_char(4) ?),
+            Some('U') => out.push(self.parse_hex_char(8) ?),
+            _ => return Err(self.err("invalid escape"))
+        }
+        Ok(())
+    }
+
+    fn parse_basic_string(&mut self) -> Result<String, TomlErr> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            match self.next_char() {
+                None | Some('\n') => return Err(self.err("unterminated string")),
+                Some('"') => return Ok(out),
+                Some('\\') => self.parse_escape(&mut out) ?,
+                Some(c) => out.push(c)
+            }
+        }
+    }
+
+    fn parse_multiline_basic_string(&mut self) -> Result<String, TomlErr> {
+        self.pos += 3;
+        // a newline right after the opening quotes isn't part of the string
+        self.eat_newline();
+        let mut out = String::new();
+        loop {
+            if self.starts_with("\"\"\"") {
+                self.pos += 3;
+                // up to two quotes can sit right against the closing ones
+                for _ in 0..2 {
+                    if self.peek_byte(0) == b'"' {
+                        out.push('"');
+                        self.pos += 1;
                     }
-                    self.next(i);
                 }
-                if self.cur == '.' {
-                    num.push(self.cur);
-                    self.next(i);
-                    while self.cur >= '0' && self.cur <= '9' || self.cur == '_' {
-                        if self.cur != '_' {
-                            num.push(self.cur);
+                return Ok(out)
+            }
+            match self.next_char() {
+                None => return Err(self.err("unterminated string")),
+                Some('\\') => {
+                    // a backslash at the end of a line eats the newline and the whitespace after it
+                    let after = self.pos;
+                    self.skip_ws();
+                    if self.eat_newline() {
+                        loop {
+                            self.skip_ws();
+                            if !self.eat_newline() {
+                                break
+                            }
                         }
-                        self.next(i);
-                    }
-                    if let Ok(num) = num.parse() {
-                        Ok(TomlTokWithSpan {tok: TomlTok::F64(num), span: TomlSpan {start, len: self.pos - start}})
                     }
                     else {
-                        Err(self.err_parse("number"))
+                        self.pos = after;
+                        self.parse_escape(&mut out) ?;
                     }
                 }
-                else if self.cur == '-' { // lets assume its a date. whatever. i don't feel like more parsing today
-                    num.push(self.cur);
-                    self.next(i);
-                    while self.cur >= '0' && self.cur <= '9' || self.cur == ':' || self.cur == '-' || self.cur == 'T' {
-                        num.push(self.cur);
-                        self.next(i);
+                Some('\r') if self.peek_byte(0) == b'\n' => (),
+                Some(c) => out.push(c)
+            }
+        }
+    }
+
+    fn parse_literal_string(&mut self) -> Result<String, TomlErr> {
+        self.pos += 1;
+        let start = self.pos;
+        loop {
+            match self.next_char() {
+                None | Some('\n') => return Err(self.err("unterminated string")),
+                Some('\'') => return Ok(self.data[start..self.pos - 1].to_string()),
+                _ => ()
+            }
+        }
+    }
+
+    fn parse_multiline_literal_string(&mut self) -> Result<String, TomlErr> {
+        self.pos += 3;
+        self.eat_newline();
+        let start = self.pos;
+        loop {
+            if self.starts_with("'''") {
+                let mut end = self.pos;
+                self.pos += 3;
+                for _ in 0..2 {
+                    if self.peek_byte(0) == b'\'' {
+                        end += 1;
+                        self.pos += 1;
                     }
-                    return Ok(TomlTokWithSpan {tok: TomlTok::Date(num), span: TomlSpan {start, len: self.pos - start}})
                 }
-                else {
-                    if is_neg {
-                        if let Ok(num) = num.parse() {
-                            return Ok(TomlTokWithSpan {tok: TomlTok::I64(num), span: TomlSpan {start, len: self.pos - start}})
-                        }
-                        else {
-                            return Err(self.err_parse("number"));
-                        }
-                    }
-                    if let Ok(num) = num.parse() {
-                        return Ok(TomlTokWithSpan {tok: TomlTok::U64(num), span: TomlSpan {start, len: self.pos - start}})
-                    }
-                    else {
-                        return Err(self.err_parse("number"));
-                    }
+                return Ok(self.data[start..end].replace("\r\n", "\n"))
+            }
+            if self.next_char().is_none() {
+                return Err(self.err("unterminated string"))
+            }
+        }
+    }
+}
+
+// edits a toml document in place. values are replaced, added and removed in the source text
+// so comments, formatting and the order of everything else stay as they were
+pub struct TomlEditor {
+    source: String,
+    root: TomlTable,
+}
+
+impl TomlEditor {
+    pub fn new(source: &str) -> Result<Self, TomlErr> {
+        Ok(Self {root: parse_toml_table(source) ?, source: source.to_string()})
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn into_string(self) -> String {
+        self.source
+    }
+
+    pub fn root(&self) -> &TomlTable {
+        &self.root
+    }
+
+    pub fn get(&self, path: &str) -> Option<&Toml> {
+        self.root.get_path(path)
+    }
+
+    // replaces a range of the source and parses it again, nothing changes if the result is not valid toml
+    fn splice(&mut self, range: Range<usize>, text: &str) -> Result<(), TomlErr> {
+        let mut source = self.source.clone();
+        source.replace_range(range, text);
+        self.root = parse_toml_table(&source) ?;
+        self.source = source;
+        Ok(())
+    }
+
+    fn err(&self, msg: String) -> TomlErr {
+        TomlErr {msg, span: TomlSpan::default()}
+    }
+
+    // sets the value at a dotted path. an existing value is replaced where it is, a new one is
+    // added at the end of the deepest [section] or inline table on its path, using a dotted key
+    // for the rest of the path
+    pub fn set(&mut self, path: &str, value: Toml) -> Result<(), TomlErr> {
+        let keys = parse_toml_path(path) ?;
+        let (range, text) = self.plan_set(&keys, &value) ?;
+        self.splice(range, &text)
+    }
+
+    fn plan_set(&self, keys: &[String], value: &Toml) -> Result<(Range<usize>, String), TomlErr> {
+        let mut table = &self.root;
+        let mut section = &self.root;
+        let mut section_depth = 0;
+        let mut inline = None;
+        for (depth, key) in keys.iter().enumerate() {
+            let Some(entry) = table.entries.iter().find( | e | e.key == *key) else {
+                break
+            };
+            if depth == keys.len() - 1 {
+                if entry.value.is_section() {
+                    return Err(self.err(format!("{} is a table section and can't be replaced by a value", key)))
                 }
-            },
-            'a'..='z' | 'A'..='Z' | '_' => {
-                let mut ident = String::new();
-                while self.cur >= 'a' && self.cur <= 'z'
-                    || self.cur >= 'A' && self.cur <= 'Z'
-                    || self.cur >= '0' && self.cur <= '9'
-                    || self.cur == '_' || self.cur == '-' || self.cur == '.'
-                    || self.cur == '\'' || self.cur == '(' || self.cur == ')' {
-                    ident.push(self.cur);
-                    self.next(i);
+                let span = entry.value.span();
+                return Ok((span.start..span.end(), value.to_toml()))
+            }
+            table = match &entry.value {
+                Toml::Table(table) => table,
+                Toml::Array(items, _) if entry.value.is_section() => items.last().and_then(Toml::as_table).unwrap(),
+                Toml::InlineTable(table) => {
+                    inline = Some((table, depth + 1));
+                    table
                 }
-                if ident == "true" {
-                    return Ok(TomlTokWithSpan {tok: TomlTok::Bool(true), span: TomlSpan {start, len: self.pos - start}})
+                _ => return Err(self.err(format!("{} is not a table", key)))
+            };
+            if inline.is_none() && table.span.len > 0 {
+                section = table;
+                section_depth = depth + 1;
+            }
+        }
+        if let Some((table, depth)) = inline {
+            let line = format!("{} = {}", toml_dotted_key(&keys[depth..]), value.to_toml());
+            return Ok(match inline_table_end(table) {
+                Some(end) => (end..end, format!(", {}", line)),
+                None => (table.span.start..table.span.end(), format!("{{ {} }}", line))
+            })
+        }
+        let line = format!("{} = {}", toml_dotted_key(&keys[section_depth..]), value.to_toml());
+        // new lines follow the line endings of the file
+        let newline = if self.source.contains("\r\n") {"\r\n"} else {"\n"};
+        Ok(match self.section_end(section) {
+            Some(end) => (end..end, format!("{}{}", newline, line)),
+            None => (0..0, format!("{}{}", line, newline))
+        })
+    }
+
+    // the end of the last line of a [section], None for a root without any values
+    fn section_end(&self, section: &TomlTable) -> Option<usize> {
+        let mut headers = Vec::new();
+        let mut values = Vec::new();
+        collect_spans(&self.root, &mut headers, &mut values);
+        let header_end = section.span.end();
+        let next_header = headers.iter().map( | s | s.start).filter( | start | *start >= header_end).min().unwrap_or(self.source.len());
+        let last = values.iter().map( | s | s.end()).filter( | end | *end > header_end && *end <= next_header).max();
+        let last = match last {
+            Some(last) => last,
+            None if section.span.len > 0 => header_end,
+            None => return None
+        };
+        Some(self.source[last..].find('\n').map( | v | {
+            // keep a \r\n line ending together
+            if self.source[..last + v].ends_with('\r') {last + v - 1} else {last + v}
+        }).unwrap_or(self.source.len()))
+    }
+
+    // removes the key at path with its line, or a whole [section]. returns the old value
+    pub fn remove(&mut self, path: &str) -> Result<Option<Toml>, TomlErr> {
+        let keys = parse_toml_path(path) ?;
+        let (last, parents) = keys.split_last().unwrap();
+        let mut table = &self.root;
+        let mut inline = false;
+        for key in parents {
+            let Some(value) = table.get(key) else {
+                return Ok(None)
+            };
+            table = match value {
+                Toml::Table(table) => table,
+                Toml::InlineTable(table) => {
+                    inline = true;
+                    table
+                }
+                Toml::Array(items, _) if value.is_section() => items.last().and_then(Toml::as_table).unwrap(),
+                _ => return Ok(None)
+            };
+        }
+        let Some(index) = table.entries.iter().position( | e | e.key == *last) else {
+            return Ok(None)
+        };
+        let entry = &table.entries[index];
+        let range = if inline {
+            // take the comma before it along, or the one after it for the first entry
+            if index > 0 {
+                let prev = entry_end(&table.entries[index - 1]);
+                prev..entry_end(entry)
+            }
+            else if let Some(next) = table.entries.get(1) {
+                entry.key_span.start..next.key_span.start
+            }
+            else {
+                entry.key_span.start..entry_end(entry)
+            }
+        }
+        else {
+            match &entry.value {
+                Toml::Table(section) if section.span.len > 0 => {
+                    let mut headers = Vec::new();
+                    collect_spans(&self.root, &mut headers, &mut Vec::new());
+                    let next = headers.iter().map( | s | s.start).filter( | start | *start > section.span.start).min().unwrap_or(self.source.len());
+                    self.line_start(section.span.start)..next
                 }
-                if ident == "false" {
-                    return Ok(TomlTokWithSpan {tok: TomlTok::Bool(false), span: TomlSpan {start, len: self.pos - start}})
+                value if value.is_section() || matches!(value, Toml::Table(_)) => {
+                    return Err(self.err(format!("{} is made of several sections and can't be removed at once", last)))
                 }
-                if ident == "inf" {
-                    return Ok(TomlTokWithSpan {tok: TomlTok::Inf(false), span: TomlSpan {start, len: self.pos - start}})
+                value => {
+                    let end = value.span().end();
+                    let line_end = self.source[end..].find('\n').map( | v | end + v + 1).unwrap_or(self.source.len());
+                    self.line_start(entry.key_span.start)..line_end
                 }
-                if ident == "nan" {
-                    return Ok(TomlTokWithSpan {tok: TomlTok::Nan(false), span: TomlSpan {start, len: self.pos - start}})
+            }
+        };
+        let old = entry.value.clone();
+        self.splice(range, "") ?;
+        Ok(Some(old))
+    }
+
+    fn line_start(&self, pos: usize) -> usize {
+        self.source[..pos].rfind('\n').map( | v | v + 1).unwrap_or(0)
+    }
+}
+
+fn toml_dotted_key(keys: &[String]) -> String {
+    keys.iter().map( | k | toml_key(k)).collect::<Vec<_>>().join(".")
+}
+
+// where the source of an entry ends, for dotted keys in an inline table that is its last value
+fn entry_end(entry: &TomlEntry) -> usize {
+    match &entry.value {
+        Toml::Table(table) if table.span.len == 0 => {
+            table.entries.iter().map(entry_end).max().unwrap_or(entry.key_span.end())
+        }
+        value => value.span().end()
+    }
+}
+
+fn inline_table_end(table: &TomlTable) -> Option<usize> {
+    table.entries.iter().map(entry_end).max()
+}
+
+// the spans of all [headers] and of all values outside of them
+fn collect_spans(table: &TomlTable, headers: &mut Vec<TomlSpan>, values: &mut Vec<TomlSpan>) {
+    for entry in &table.entries {
+        match &entry.value {
+            Toml::Table(table) => {
+                if table.span.len > 0 {
+                    headers.push(table.span);
                 }
-                Ok(TomlTokWithSpan {tok: TomlTok::Ident(ident), span: TomlSpan {start, len: self.pos - start}})
-            },
-            
-            '"' => {
-                let mut val = String::new();
-                self.next(i);
-                while self.cur != '"' {
-                    if self.cur == '\\' {
-                        self.next(i);
-                    }
-                    if self.cur == '\0' {
-                        return Err(self.err_parse("string"));
-                    }
-                    val.push(self.cur);
-                    self.next(i);
+                collect_spans(table, headers, values);
+            }
+            Toml::Array(items, _) if entry.value.is_section() => for item in items {
+                if let Toml::Table(table) = item {
+                    headers.push(table.span);
+                    collect_spans(table, headers, values);
                 }
-                self.next(i);
-                Ok(TomlTokWithSpan {tok: TomlTok::Str(val), span: TomlSpan {start, len: self.pos - start}})
-            },
-            _ => {
-                Err(self.err_parse("tokenizer"))
             }
+            value => values.push(value.span())
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_arrays_of_tables() {
+        let root = parse_toml_table("\
+[[bin]]
+name = \"a\"
+[bin.extra]
+x = 1
+
+[[bin]]
+name = \"b\"
+[bin.extra]
+x = 2
+
+[[target.linux.deps]]
+name = \"c\"
+").unwrap();
+        let bins = root.get("bin").unwrap().as_array().unwrap();
+        assert_eq!(bins.len(), 2);
+        assert_eq!(root.get_path("bin.0.name").unwrap().as_str(), Some("a"));
+        assert_eq!(root.get_path("bin.1.name").unwrap().as_str(), Some("b"));
+        // the [bin.extra] after each [[bin]] belongs to that entry
+        assert_eq!(root.get_path("bin.0.extra.x").unwrap().as_i64(), Some(1));
+        assert_eq!(root.get_path("bin.1.extra.x").unwrap().as_i64(), Some(2));
+        assert_eq!(root.get_path("target.linux.deps.0.name").unwrap().as_str(), Some("c"));
+        assert!(parse_toml_table("a = 1\n[[a]]\n").is_err());
+        assert!(parse_toml_table("[a]\n[a]\n").is_err());
+    }
+
+    #[test]
+    fn parses_inline_tables() {
+        let root = parse_toml_table("dep = { version = \"1.0\", features = [\"a\", \"b\"], opt.x = true }\n").unwrap();
+        let dep = root.get("dep").unwrap();
+        assert!(matches!(dep, Toml::InlineTable(_)));
+        assert_eq!(root.get_path("dep.version").unwrap().as_str(), Some("1.0"));
+        assert_eq!(root.get_path("dep.features.1").unwrap().as_str(), Some("b"));
+        assert_eq!(root.get_path("dep.opt.x").unwrap().as_bool(), Some(true));
+        // inline tables are complete, they can't be added to later
+        assert!(parse_toml_table("dep = { a = 1 }\n[dep.b]\n").is_err());
+        assert!(parse_toml_table("dep = { a = 1 }\ndep.b = 2\n").is_err());
+        let flat = parse_toml("[package]\nname = 'x'\nmeta = { a = 1 }\n").unwrap();
+        assert_eq!(flat.get("package.name").unwrap().as_str(), Some("x"));
+        assert_eq!(flat.get("package.meta.a").unwrap().as_i64(), Some(1));
+    }
+
+    #[test]
+    fn parses_strings() {
+        let root = parse_toml_table("\
+a = \"\"\"
+one
+two\"\"\"
+b = \"\"\"one \\
+    two\"\"\"
+c = '''
+C:\\path\\''''
+d = \"tab\\there \\u00e9\"
+e = 'raw\\n'
+f = \"\"\"\"quoted\"\"\"\"
+").unwrap();
+        let get = | key | root.get(key).unwrap().as_str().unwrap().to_string();
+        assert_eq!(get("a"), "one\ntwo");
+        assert_eq!(get("b"), "one two");
+        assert_eq!(get("c"), "C:\\path\\'");
+        assert_eq!(get("d"), "tab\there \u{e9}");
+        assert_eq!(get("e"), "raw\\n");
+        assert_eq!(get("f"), "\"quoted\"");
+        // a \r\n inside a multi-line string reads as \n
+        let root = parse_toml_table("a = \"\"\"\r\nx\r\ny\"\"\"\r\nb = '''x\r\ny'''\r\n").unwrap();
+        assert_eq!(root.get("a").unwrap().as_str(), Some("x\ny"));
+        assert_eq!(root.get("b").unwrap().as_str(), Some("x\ny"));
+        assert!(parse_toml_table("a = \"open\nb = 1\n").is_err());
+        assert!(parse_toml_table("a = \"\\q\"\n").is_err());
+    }
+
+    #[test]
+    fn parses_integers_and_floats() {
+        let root = parse_toml_table("\
+a = 42
+b = -17
+c = 1_000
+d = 0xff
+e = 0o17
+f = 0b101
+g = 1.5
+h = -0.25
+i = 1e3
+j = 6.02E+23
+k = inf
+l = -inf
+m = 1979-05-27
+n = 1979-05-27 07:32:00Z
+").unwrap();
+        let get = | key | root.get(key).unwrap().clone();
+        assert!(matches!(get("a"), Toml::Int(42, _)));
+        assert!(matches!(get("b"), Toml::Int(-17, _)));
+        assert!(matches!(get("c"), Toml::Int(1000, _)));
+        assert!(matches!(get("d"), Toml::Int(255, _)));
+        assert!(matches!(get("e"), Toml::Int(15, _)));
+        assert!(matches!(get("f"), Toml::Int(5, _)));
+        assert_eq!(get("g"), Toml::Float(1.5, get("g").span()));
+        assert_eq!(get("h").as_f64(), Some(-0.25));
+        assert!(matches!(get("i"), Toml::Float(v, _) if v == 1000.0));
+        assert!(matches!(get("j"), Toml::Float(v, _) if v == 6.02e23));
+        assert_eq!(get("k").as_f64(), Some(f64::INFINITY));
+        assert_eq!(get("l").as_f64(), Some(f64::NEG_INFINITY));
+        // an integer is a number but not the other way around
+        assert_eq!(get("a").as_f64(), Some(42.0));
+        assert_eq!(get("g").as_i64(), None);
+        assert_eq!(get("m"), Toml::Date("1979-05-27".into(), get("m").span()));
+        assert!(matches!(get("n"), Toml::Date(v, _) if v == "1979-05-27 07:32:00Z"));
+        for bad in ["01", "1__0", "_1", "1.", ".5", "1._5", "0x-1", "99999999999999999999"] {
+            assert!(parse_toml_table(&format!("a = {}\n", bad)).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn writes_tables_that_parse_back() {
+        let source = "\
+title = \"a \\\"b\\\"\\n\"
+numbers = [1, 2.5, -3]
+'key with space' = true
+dep = { version = \"1\", opt = false }
+
+[package]
+name = \"x\"
+
+[package.metadata.docs]
+all = true
+
+[[bin]]
+name = \"a\"
+
+[[bin]]
+name = \"b\"
+path = 'src/b.rs'
+";
+        let table = parse_toml_table(source).unwrap();
+        let written = table.to_toml();
+        let parsed = parse_toml_table(&written).unwrap();
+        // the spans differ, so compare what both write
+        assert_eq!(parsed.to_toml(), written);
+        assert_eq!(parsed.get_path("title").unwrap().as_str(), Some("a \"b\"\n"));
+        assert_eq!(parsed.get_path("numbers.1").unwrap().as_f64(), Some(2.5));
+        assert_eq!(parsed.get_path("'key with space'").unwrap().as_bool(), Some(true));
+        assert_eq!(parsed.get_path("dep.opt").unwrap().as_bool(), Some(false));
+        assert_eq!(parsed.get_path("package.metadata.docs.all").unwrap().as_bool(), Some(true));
+        assert_eq!(parsed.get_path("bin.1.path").unwrap().as_str(), Some("src/b.rs"));
+        // package only holds values and a table, metadata only a table so it gets no header
+        assert!(written.contains("[package]\n"));
+        assert!(!written.contains("[package.metadata]\n"));
+        assert!(written.contains("[package.metadata.docs]\n"));
+        assert_eq!(written.matches("[[bin]]\n").count(), 2);
+        assert_eq!(Toml::from(1e30).to_toml(), "1e30");
+        assert_eq!(Toml::from(2.0).to_toml(), "2.0");
+    }
+
+    #[test]
+    fn edits_keep_comments_and_order() {
+        let source = "\
+# the package
+[package]
+name = \"x\" # the name
+version = \"0.1.0\"
+
+# dependencies follow
+[dependencies]
+b = \"1\"
+a = { version = \"2\" }
+";
+        let mut editor = TomlEditor::new(source).unwrap();
+        editor.set("package.version", "0.2.0".into()).unwrap();
+        editor.set("dependencies.a.version", "3".into()).unwrap();
+        editor.set("dependencies.a.features", vec!["f".into()].into()).unwrap();
+        editor.set("package.edition", "2021".into()).unwrap();
+        editor.set("package.metadata.x", true.into()).unwrap();
+        editor.set("dependencies.c", "4".into()).unwrap();
+        assert_eq!(editor.source(), "\
+# the package
+[package]
+name = \"x\" # the name
+version = \"0.2.0\"
+edition = \"2021\"
+metadata.x = true
+
+# dependencies follow
+[dependencies]
+b = \"1\"
+a = { version = \"3\", features = [\"f\"] }
+c = \"4\"
+");
+        assert_eq!(editor.get("package.metadata.x").unwrap().as_bool(), Some(true));
+        assert_eq!(editor.remove("dependencies.b").unwrap().unwrap().as_str(), Some("1"));
+        assert_eq!(editor.remove("dependencies.a.version").unwrap().unwrap().as_str(), Some("3"));
+        assert!(editor.remove("dependencies.z").unwrap().is_none());
+        assert!(editor.source().ends_with("[dependencies]\na = { features = [\"f\"] }\nc = \"4\"\n"));
+        // a section can't be replaced by a value, and a failed edit leaves the source alone
+        let before = editor.source().to_string();
+        assert!(editor.set("package", 1i64.into()).is_err());
+        assert!(editor.set("package.name.x", 1i64.into()).is_err());
+        assert_eq!(editor.source(), before);
+    }
+
+    #[test]
+    fn edits_keep_crlf_line_endings() {
+        let source = "[package]\r\nname = \"x\"\r\n\r\n[[bin]]\r\nname = \"a\"\r\n";
+        let mut editor = TomlEditor::new(source).unwrap();
+        editor.set("package.version", "1".into()).unwrap();
+        editor.set("bin.name", "b".into()).unwrap();
+        editor.set("bin.path", "p".into()).unwrap();
+        assert_eq!(editor.source(), "[package]\r\nname = \"x\"\r\nversion = \"1\"\r\n\r\n[[bin]]\r\nname = \"b\"\r\npath = \"p\"\r\n");
+        editor.remove("package.name").unwrap();
+        assert_eq!(editor.source(), "[package]\r\nversion = \"1\"\r\n\r\n[[bin]]\r\nname = \"b\"\r\npath = \"p\"\r\n");
+        // a root without values gets new keys at the top
+        let mut editor = TomlEditor::new("[a]\r\nx = 1\r\n").unwrap();
+        editor.set("top", 1i64.into()).unwrap();
+        assert_eq!(editor.source(), "top = 1\r\n[a]\r\nx = 1\r\n");
+    }
+}
@@ -75,6 +75,9 @@ pub enum CxOsOp {
         request_id: LiveId,
         request: HttpRequest,
     },
+    CancelHttpRequest {
+        request_id: LiveId,
+    },
 
     PrepareVideoPlayback(LiveId, VideoSource, u32, bool, bool),
     BeginVideoPlayback(LiveId),
@@ -494,6 +497,12 @@ impl Cx {
             request,
         });
     }
+    
+    pub fn cancel_http_request(&mut self, request_id: LiveId) {
+        self.platform_ops.push(CxOsOp::CancelHttpRequest {
+            request_id,
+        });
+    }
     /*
         pub fn web_socket_open(&mut self, request_id: LiveId, request: HttpRequest) {
             self.platform_ops.push(CxOsOp::WebSocketOpen{
@@ -15,7 +15,12 @@ pub type NetworkResponsesEvent = Vec<NetworkResponseItem>;
 #[derive(Clone, Debug)]
 pub enum NetworkResponse{
     HttpRequestError(String),
+    HttpRequestCancelled,
+    HttpRequestTimedOut,
     HttpResponse(HttpResponse),
+    HttpStreamResponse(HttpResponse),
+    HttpServerSentEvent(ServerSentEvent),
+    HttpStreamComplete(HttpResponse),
     HttpProgress{loaded:u64, total:u64},
 }
 
@@ -57,6 +62,8 @@ pub struct HttpRequest {
     pub method: HttpMethod,
     pub headers: BTreeMap<String, Vec<String>>,
     pub ignore_ssl_cert: bool,
+    pub is_streaming: bool,
+    pub timeout: Option<f64>,
     pub body: Option<Vec<u8>>,
 }
 
@@ -76,6 +83,8 @@ impl HttpRequest {
             url,
             method,
             ignore_ssl_cert: false,
+            is_streaming: false,
+            timeout: None,
             headers: BTreeMap::new(),
             body: None
         }
@@ -109,6 +118,16 @@ impl HttpRequest {
         self.ignore_ssl_cert = true
     }
     
+    // deliver the body in pieces as HttpStreamResponse items, followed by a HttpStreamComplete
+    pub fn set_is_streaming(&mut self){
+        self.is_streaming = true
+    }
+    
+    // in seconds, after which the request is aborted with HttpRequestTimedOut
+    pub fn set_timeout(&mut self, timeout: f64){
+        self.timeout = Some(timeout)
+    }
+    
     pub fn set_metadata_id(&mut self, id: LiveId){
         self.metadata_id = id;
     }
@@ -210,7 +229,136 @@ impl HttpResponse {
     }
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ServerSentEvent {
+    pub event: String,
+    pub data: String,
+    pub id: Option<String>,
+    pub retry: Option<u64>,
+}
+
+// incrementally splits a text/event-stream body into events, chunks may be cut at any byte
+#[derive(Default)]
+pub struct ServerSentEventParser {
+    buffer: Vec<u8>,
+    event: String,
+    data: String,
+    last_event_id: Option<String>,
+    retry: Option<u64>,
+}
+
+impl ServerSentEventParser {
+    pub fn parse(&mut self, input: &[u8]) -> Vec<ServerSentEvent> {
+        self.buffer.extend_from_slice(input);
+        let mut events = Vec::new();
+        let mut start = 0;
+        let mut i = 0;
+        while i < self.buffer.len() {
+            let c = self.buffer[i];
+            if c == b'\n' || c == b'\r' {
+                let end = i;
+                if c == b'\r' {
+                    // a trailing \r might be the first half of a \r\n
+                    if i + 1 == self.buffer.len() {
+                        break;
+                    }
+                    if self.buffer[i + 1] == b'\n' {
+                        i += 1;
+                    }
+                }
+                let line = String::from_utf8_lossy(&self.buffer[start..end]).to_string();
+                self.parse_line(&line, &mut events);
+                start = i + 1;
+            }
+            i += 1;
+        }
+        self.buffer.drain(0..start);
+        events
+    }
+    
+    fn parse_line(&mut self, line: &str, events: &mut Vec<ServerSentEvent>) {
+        if line.is_empty() {
+            if !self.data.is_empty() {
+                self.data.pop();
+                events.push(ServerSentEvent {
+                    event: if self.event.is_empty() {"message".to_string()} else {self.event.clone()},
+                    data: std::mem::take(&mut self.data),
+                    id: self.last_event_id.clone(),
+                    retry: self.retry,
+                });
+            }
+            self.event.clear();
+            return
+        }
+        if line.starts_with(':') {
+            return
+        }
+        let (field, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match field {
+            "event" => self.event = value.to_string(),
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+            }
+            "id" => if !value.contains('\0') {
+                self.last_event_id = Some(value.to_string());
+            }
+            "retry" => if let Ok(retry) = value.parse() {
+                self.retry = Some(retry);
+            }
+            _ => ()
+        }
+    }
+}
+
+// turns the body of a streaming request into HttpStreamResponse items as it arrives,
+// a text/event-stream body is also parsed into HttpServerSentEvent items
+pub struct HttpStream {
+    request_id: LiveId,
+    response: HttpResponse,
+    events: Option<ServerSentEventParser>,
+}
+
+impl HttpStream {
+    pub fn new(request_id: LiveId, response: HttpResponse) -> Self {
+        let is_event_stream = response.headers.iter().any( | (key, values) | {
+            key.eq_ignore_ascii_case("Content-Type") && values.iter().any( | value | value.trim().starts_with("text/event-stream"))
+        });
+        Self {
+            request_id,
+            response,
+            events: if is_event_stream {Some(ServerSentEventParser::default())} else {None},
+        }
+    }
+    
+    pub fn data(&mut self, data: &[u8]) -> Vec<NetworkResponseItem> {
+        let mut response = self.response.clone();
+        response.body = Some(data.to_vec());
+        let mut items = vec![NetworkResponseItem {
+            request_id: self.request_id,
+            response: NetworkResponse::HttpStreamResponse(response)
+        }];
+        if let Some(events) = &mut self.events {
+            for event in events.parse(data) {
+                items.push(NetworkResponseItem {
+                    request_id: self.request_id,
+                    response: NetworkResponse::HttpServerSentEvent(event)
+                });
+            }
+        }
+        items
+    }
+    
+    pub fn complete(self) -> NetworkResponseItem {
+        NetworkResponseItem {
+            request_id: self.request_id,
+            response: NetworkResponse::HttpStreamComplete(self.response)
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
 pub enum HttpMethod{
     GET,
     HEAD,
@@ -161,6 +161,8 @@ pub use {
             HttpMethod,
             NetworkResponse,
             NetworkResponsesEvent,
+            ServerSentEvent,
+            ServerSentEventParser,
             Margin,
             KeyCode,
             Event,
@@ -2,6 +2,7 @@ use {
     std::{
         rc::Rc,
         cell::RefCell,
+        collections::HashMap,
         time::Instant
     },
     makepad_objc_sys::{
@@ -26,7 +27,7 @@ use {
                     macos_window::MacosWindow
                 },
                 apple_classes::init_apple_classes_global,
-                url_session::{make_http_request},
+                url_session::{make_http_request, HttpRequestCancel},
             },
             metal_xpc::start_xpc_service,
             apple_media::CxAppleMedia,
@@ -40,6 +41,7 @@ use {
             WindowGeom,
             MouseUpEvent,
             Event,
+            NetworkResponse,
             NetworkResponseChannel
         },
         window::CxWindowPool,
@@ -218,6 +220,12 @@ impl Cx {
     pub (crate) fn handle_networking_events(&mut self) {
         let mut out = Vec::new();
         while let Ok(event) = self.os.network_response.receiver.try_recv() {
+            match &event.response {
+                NetworkResponse::HttpProgress{..} | NetworkResponse::HttpStreamResponse(_) | NetworkResponse::HttpServerSentEvent(_) => (),
+                _ => {
+                    self.os.http_requests.remove(&event.request_id);
+                }
+            }
             out.push(event);
         }
         if out.len()>0 {
@@ -513,7 +521,13 @@ impl Cx {
                     get_macos_app_global().update_macos_menu(&menu)
                 },
                 CxOsOp::HttpRequest {request_id, request} => {
-                    make_http_request(request_id, request, self.os.network_response.sender.clone());
+                    let cancel = make_http_request(request_id, request, self.os.network_response.sender.clone());
+                    self.os.http_requests.insert(request_id, cancel);
+                },
+                CxOsOp::CancelHttpRequest {request_id} => {
+                    if let Some(cancel) = self.os.http_requests.remove(&request_id) {
+                        cancel.cancel();
+                    }
                 },
                 CxOsOp::ShowClipboardActions(_request) => {
                     crate::log!("Show clipboard actions not supported yet");
@@ -607,6 +621,7 @@ pub struct CxOs {
     pub (crate) bytes_written: usize,
     pub (crate) draw_calls_done: usize,
     pub (crate) network_response: NetworkResponseChannel,
+    pub (crate) http_requests: HashMap<LiveId, HttpRequestCancel>,
     pub (crate) stdin_timers: PollTimers,
     pub (crate) start_time: Option<Instant>,
     pub metal_device: Option<ObjcId>,
@@ -412,7 +412,13 @@ impl Cx {
                     self.os.stdin_timers.timers.remove(&timer_id);
                 },
                 CxOsOp::HttpRequest {request_id, request} => {
-                    make_http_request(request_id, request, self.os.network_response.sender.clone());
+                    let cancel = make_http_request(request_id, request, self.os.network_response.sender.clone());
+                    self.os.http_requests.insert(request_id, cancel);
+                },
+                CxOsOp::CancelHttpRequest {request_id} => {
+                    if let Some(cancel) = self.os.http_requests.remove(&request_id) {
+                        cancel.cancel();
+                    }
                 },
                 _ => ()
                 /*
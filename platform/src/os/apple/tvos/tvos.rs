@@ -3,12 +3,13 @@ use {
         time::Instant,
         rc::Rc,
         cell::{RefCell},
+        collections::HashMap,
         io::prelude::*,
         fs::File,
     }, 
  
     crate::{ 
-        //makepad_live_id::*,
+        makepad_live_id::*,
         os::{
             apple::apple_sys::*,
             apple::apple_util::{nsstring_to_string,str_to_nsstring},
@@ -18,7 +19,7 @@ use {
                     tvos_event::TvosEvent,
                     tvos_app::{TvosApp, init_tvos_app_global,get_tvos_app_global}
                 },
-                url_session::{make_http_request},
+                url_session::{make_http_request, HttpRequestCancel},
             },
             apple_classes::init_apple_classes_global,
             apple_media::CxAppleMedia,
@@ -29,6 +30,7 @@ use {
         window::CxWindowPool,
         event::{
             Event,
+            NetworkResponse,
             NetworkResponseChannel
         },
         cx_api::{CxOsApi, CxOsOp},
@@ -95,6 +97,12 @@ impl Cx {
     pub(crate) fn handle_networking_events(&mut self) {
         let mut out = Vec::new();
         while let Ok(event) = self.os.network_response.receiver.try_recv(){
+            match &event.response {
+                NetworkResponse::HttpProgress{..} | NetworkResponse::HttpStreamResponse(_) | NetworkResponse::HttpServerSentEvent(_) => (),
+                _ => {
+                    self.os.http_requests.remove(&event.request_id);
+                }
+            }
             out.push(event);
         }
         if out.len()>0{
@@ -254,7 +262,13 @@ impl Cx {
                 CxOsOp::UpdateMacosMenu(_menu) => {
                 },
                 CxOsOp::HttpRequest{request_id, request} => {
-                    make_http_request(request_id, request, self.os.network_response.sender.clone());
+                    let cancel = make_http_request(request_id, request, self.os.network_response.sender.clone());
+                    self.os.http_requests.insert(request_id, cancel);
+                },
+                CxOsOp::CancelHttpRequest {request_id} => {
+                    if let Some(cancel) = self.os.http_requests.remove(&request_id) {
+                        cancel.cancel();
+                    }
                 },
                 CxOsOp::ShowClipboardActions(_request) => {
                     crate::log!("Show clipboard actions not supported yet");
@@ -330,5 +344,6 @@ pub struct CxOs {
     pub (crate) bytes_written: usize,
     pub (crate) draw_calls_done: usize,
     pub (crate) network_response: NetworkResponseChannel,
+    pub (crate) http_requests: HashMap<LiveId, HttpRequestCancel>,
 }
 
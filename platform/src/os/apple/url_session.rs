@@ -2,8 +2,10 @@
 use {
     std::{
         ptr,
+        ptr::NonNull,
         sync::mpsc::{Sender},
         sync::Arc,
+        os::raw::c_void,
     },
     crate::{
         thread::SignalToUI,
@@ -23,7 +25,8 @@ use {
             NetworkResponseItem,
             NetworkResponse,
             HttpRequest,
-            HttpResponse
+            HttpResponse,
+            HttpStream
         },
     }
 };
@@ -104,6 +107,10 @@ unsafe fn make_ns_request(request: &HttpRequest) -> ObjcId {
         }
     }
     
+    if let Some(timeout) = request.timeout {
+        let () = msg_send![ns_request, setTimeoutInterval: timeout];
+    }
+    
     if let Some(body) = request.body.as_ref() {
         let nsdata: ObjcId = msg_send![class!(NSData), dataWithBytes: body.as_ptr() length: body.len()];
         let () = msg_send![ns_request, setHTTPBody: nsdata];
@@ -214,18 +221,208 @@ impl OsWebSocket{
 }
 
 
-pub fn make_http_request(request_id: LiveId, request: HttpRequest, networking_sender: Sender<NetworkResponseItem>) {
+// error codes of the NSURLErrorDomain
+const NS_URL_ERROR_CANCELLED: isize = -999;
+const NS_URL_ERROR_TIMED_OUT: isize = -1001;
+
+pub struct HttpRequestCancel {
+    data_task: RcObjcId,
+}
+
+impl HttpRequestCancel {
+    // the task then completes with NSURLErrorCancelled, which is reported as HttpRequestCancelled
+    pub fn cancel(&self) {
+        unsafe {
+            let () = msg_send![self.data_task.as_id(), cancel];
+        }
+    }
+}
+
+// an NSHTTPURLResponse with its headers, and the body when there is one
+unsafe fn make_http_response(metadata_id: LiveId, response: ObjcId, body: Option<Vec<u8>>) -> HttpResponse {
+    let response_code: u16 = msg_send![response, statusCode];
+    let headers: ObjcId = msg_send![response, allHeaderFields];
+    let mut response = HttpResponse::new(
+        metadata_id,
+        response_code,
+        "".to_string(),
+        body,
+    );
+    
+    let key_enumerator: ObjcId = msg_send![headers, keyEnumerator];
+    let mut key: ObjcId = msg_send![key_enumerator, nextObject];
+    while key != ptr::null_mut() {
+        let value: ObjcId = msg_send![headers, objectForKey: key];
+        let key_str = nsstring_to_string(key);
+        let value_str = nsstring_to_string(value);
+        response.set_header(key_str, value_str);
+        
+        key = msg_send![key_enumerator, nextObject];
+    }
+    response
+}
+
+unsafe fn error_response(error: ObjcId) -> NetworkResponse {
+    let code: isize = msg_send![error, code];
+    match code {
+        NS_URL_ERROR_CANCELLED => NetworkResponse::HttpRequestCancelled,
+        NS_URL_ERROR_TIMED_OUT => NetworkResponse::HttpRequestTimedOut,
+        _ => NetworkResponse::HttpRequestError(nsstring_to_string(msg_send![error, localizedDescription]))
+    }
+}
+
+// NSURLSessionResponseDisposition and NSURLSessionAuthChallengeDisposition values
+const NS_URL_SESSION_RESPONSE_ALLOW: isize = 1;
+const NS_URL_SESSION_AUTH_CHALLENGE_USE_CREDENTIAL: isize = 0;
+const NS_URL_SESSION_AUTH_CHALLENGE_PERFORM_DEFAULT_HANDLING: isize = 1;
+
+// a streaming request, owned by the data delegate of its session until the task completes
+struct HttpStreamDelegateState {
+    request_id: LiveId,
+    metadata_id: LiveId,
+    ignore_ssl_cert: bool,
+    networking_sender: Sender<NetworkResponseItem>,
+    stream: Option<HttpStream>,
+}
+
+// receives the body of a streaming request piece by piece. The session calls it on its own serial queue.
+pub fn define_url_session_data_delegate() -> *const Class {
+    unsafe fn get_state(this: &Object) -> Option<&mut HttpStreamDelegateState> {
+        let ptr: *mut c_void = *this.get_ivar("state");
+        (ptr as *mut HttpStreamDelegateState).as_mut()
+    }
+    
+    extern fn did_receive_response(this: &Object, _: Sel, _session: ObjcId, _data_task: ObjcId, response: ObjcId, completion: ObjcId) {
+        unsafe {
+            if let Some(state) = get_state(this) {
+                let response = make_http_response(state.metadata_id, response, None);
+                // a redirect or an authentication retry can bring a new response, its body starts over
+                state.stream = Some(HttpStream::new(state.request_id, response));
+            }
+            objc_block_invoke!(completion, invoke(
+                (NS_URL_SESSION_RESPONSE_ALLOW): isize
+            ));
+        }
+    }
+    
+    extern fn did_receive_data(this: &Object, _: Sel, _session: ObjcId, _data_task: ObjcId, data: ObjcId) {
+        unsafe {
+            let Some(state) = get_state(this) else {return};
+            let Some(stream) = &mut state.stream else {return};
+            let bytes: *const u8 = msg_send![data, bytes];
+            let length: usize = msg_send![data, length];
+            if length == 0 {
+                return
+            }
+            for item in stream.data(std::slice::from_raw_parts(bytes, length)) {
+                let _ = state.networking_sender.send(item);
+            }
+            SignalToUI::set_ui_signal();
+        }
+    }
+    
+    extern fn did_complete_with_error(this: &mut Object, _: Sel, _session: ObjcId, _task: ObjcId, error: ObjcId) {
+        unsafe {
+            let ptr: *mut c_void = *this.get_ivar("state");
+            if ptr.is_null() {
+                return
+            }
+            this.set_ivar("state", ptr::null_mut::<c_void>());
+            let state = Box::from_raw(ptr as *mut HttpStreamDelegateState);
+            let item = match (error != ptr::null_mut(), state.stream) {
+                (false, Some(stream)) => stream.complete(),
+                (true, _) => NetworkResponseItem {
+                    request_id: state.request_id,
+                    response: error_response(error)
+                },
+                (false, None) => NetworkResponseItem {
+                    request_id: state.request_id,
+                    response: NetworkResponse::HttpRequestError("The request completed without a response".to_string())
+                }
+            };
+            let _ = state.networking_sender.send(item);
+            SignalToUI::set_ui_signal();
+        }
+    }
+    
+    // like the url session delegate, lets locally signed SSL certificates pass when the request asks for it
+    extern fn did_receive_challenge(this: &Object, _: Sel, _session: ObjcId, challenge: ObjcId, completion: ObjcId) {
+        unsafe {
+            let ignore_ssl_cert = get_state(this).is_some_and( | state | state.ignore_ssl_cert);
+            let pspace: ObjcId = msg_send![challenge, protectionSpace];
+            let trust: ObjcId = msg_send![pspace, serverTrust];
+            if ignore_ssl_cert && trust != nil {
+                let credential: ObjcId = msg_send![class!(NSURLCredential), credentialForTrust:trust];
+                objc_block_invoke!(completion, invoke(
+                    (NS_URL_SESSION_AUTH_CHALLENGE_USE_CREDENTIAL): isize,
+                    (credential): ObjcId
+                ));
+            }
+            else {
+                objc_block_invoke!(completion, invoke(
+                    (NS_URL_SESSION_AUTH_CHALLENGE_PERFORM_DEFAULT_HANDLING): isize,
+                    (nil): ObjcId
+                ));
+            }
+        }
+    }
+    
+    let superclass = class!(NSObject);
+    let mut decl = ClassDecl::new("NSURLSessionDataDelegate", superclass).unwrap();
+    
+    // Add callback methods
+    unsafe {
+        decl.add_method(sel!(URLSession: dataTask: didReceiveResponse: completionHandler:), did_receive_response as extern fn(&Object, Sel, ObjcId, ObjcId, ObjcId, ObjcId));
+        decl.add_method(sel!(URLSession: dataTask: didReceiveData:), did_receive_data as extern fn(&Object, Sel, ObjcId, ObjcId, ObjcId));
+        decl.add_method(sel!(URLSession: task: didCompleteWithError:), did_complete_with_error as extern fn(&mut Object, Sel, ObjcId, ObjcId, ObjcId));
+        decl.add_method(sel!(URLSession: didReceiveChallenge: completionHandler:), did_receive_challenge as extern fn(&Object, Sel, ObjcId, ObjcId, ObjcId));
+    }
+    decl.add_ivar::<*mut c_void>("state");
+    
+    return decl.register();
+}
+
+// streaming requests get a session of their own, whose data delegate hands over the body as it arrives
+unsafe fn make_streaming_http_request(request_id: LiveId, request: HttpRequest, networking_sender: Sender<NetworkResponseItem>) -> ObjcId {
+    let ns_request = make_ns_request(&request);
+    let state = Box::new(HttpStreamDelegateState {
+        request_id,
+        metadata_id: request.metadata_id,
+        ignore_ssl_cert: request.ignore_ssl_cert,
+        networking_sender,
+        stream: None,
+    });
+    let delegate: ObjcId = msg_send![get_apple_class_global().url_session_data_delegate, new];
+    let delegate = RcObjcId::from_owned(NonNull::new(delegate).unwrap());
+    (*delegate.as_id()).set_ivar("state", Box::into_raw(state) as *mut c_void);
+    
+    let config: ObjcId = msg_send![class!(NSURLSessionConfiguration), defaultSessionConfiguration];
+    let session: ObjcId = msg_send![class!(NSURLSession), sessionWithConfiguration: config delegate: delegate.as_id() delegateQueue:nil];
+    let data_task: ObjcId = msg_send![session, dataTaskWithRequest: ns_request];
+    let () = msg_send![data_task, resume];
+    // the session keeps its delegate alive until the task is done, and then lets go of both
+    let () = msg_send![session, finishTasksAndInvalidate];
+    data_task
+}
+
+pub fn make_http_request(request_id: LiveId, request: HttpRequest, networking_sender: Sender<NetworkResponseItem>) -> HttpRequestCancel {
     unsafe {
+        if request.is_streaming {
+            let data_task = make_streaming_http_request(request_id, request, networking_sender);
+            return HttpRequestCancel {
+                data_task: RcObjcId::from_unowned(NonNull::new(data_task).unwrap())
+            }
+        }
+        
         let ignore_ssl_cert = request.ignore_ssl_cert;
         let ns_request = make_ns_request(&request);
                 
         // Build the NSURLSessionDataTask instance
         let response_handler = objc_block!(move | data: ObjcId, response: ObjcId, error: ObjcId | {
             if error != ptr::null_mut() {
-                let error_str: String = nsstring_to_string(msg_send![error, localizedDescription]);
                 let message = NetworkResponseItem {
                     request_id,
-                    response: NetworkResponse::HttpRequestError(error_str)
+                    response: error_response(error)
                 };
                 networking_sender.send(message).unwrap();
                 return;
@@ -234,26 +431,7 @@ pub fn make_http_request(request_id: LiveId, request: HttpRequest, networking_se
             let bytes: *const u8 = msg_send![data, bytes];
             let length: usize = msg_send![data, length];
             let data_bytes: &[u8] = std::slice::from_raw_parts(bytes, length);
-            let response_code: u16 = msg_send![response, statusCode];
-            let headers: ObjcId = msg_send![response, allHeaderFields];
-            let mut response = HttpResponse::new(
-                request.metadata_id,
-                response_code,
-                "".to_string(),
-                Some(data_bytes.to_vec()),
-            );
-                        
-            let key_enumerator: ObjcId = msg_send![headers, keyEnumerator];
-            let mut key: ObjcId = msg_send![key_enumerator, nextObject];
-            while key != ptr::null_mut() {
-                let value: ObjcId = msg_send![headers, objectForKey: key];
-                let key_str = nsstring_to_string(key);
-                let value_str = nsstring_to_string(value);
-                response.set_header(key_str, value_str);
-                                
-                key = msg_send![key_enumerator, nextObject];
-            }
-                        
+            let response = make_http_response(request.metadata_id, response, Some(data_bytes.to_vec()));
             let message = NetworkResponseItem {
                 request_id,
                 response: NetworkResponse::HttpResponse(response)
@@ -274,5 +452,8 @@ pub fn make_http_request(request_id: LiveId, request: HttpRequest, networking_se
                 
         // Run the request task
         let () = msg_send![data_task, resume];
+        HttpRequestCancel {
+            data_task: RcObjcId::from_unowned(NonNull::new(data_task).unwrap())
+        }
     }
-}
\ No newline at end of file
+}
@@ -32,6 +32,7 @@ use {
             NetworkResponseItem,
             NetworkResponse,
             HttpResponse,
+            HttpStream,
             TouchPoint,
             TouchUpdateEvent,
             WindowGeomChangeEvent,
@@ -250,20 +251,43 @@ impl Cx {
                         }
                     }
                     FromJavaMessage::HttpResponse {request_id, metadata_id, status_code, headers, body} => {
+                        let response = HttpResponse::new(
+                            LiveId(metadata_id),
+                            status_code,
+                            headers,
+                            Some(body)
+                        );
                         let e = Event::NetworkResponses(vec![
                             NetworkResponseItem {
                                 request_id: LiveId(request_id),
-                                response: NetworkResponse::HttpResponse(HttpResponse::new(
-                                    LiveId(metadata_id),
-                                    status_code,
-                                    headers,
-                                    Some(body)
-                                ))
+                                response: NetworkResponse::HttpResponse(response)
                             }
                         ]);
                         self.call_event_handler(&e);
                     }
+                    FromJavaMessage::HttpStreamResponse {request_id, metadata_id, status_code, headers} => {
+                        let response = HttpResponse::new(
+                            LiveId(metadata_id),
+                            status_code,
+                            headers,
+                            None
+                        );
+                        self.os.streaming_http_requests.insert(LiveId(request_id), HttpStream::new(LiveId(request_id), response));
+                    }
+                    FromJavaMessage::HttpStreamData {request_id, data} => {
+                        if let Some(stream) = self.os.streaming_http_requests.get_mut(&LiveId(request_id)) {
+                            let e = Event::NetworkResponses(stream.data(&data));
+                            self.call_event_handler(&e);
+                        }
+                    }
+                    FromJavaMessage::HttpStreamComplete {request_id} => {
+                        if let Some(stream) = self.os.streaming_http_requests.remove(&LiveId(request_id)) {
+                            let e = Event::NetworkResponses(vec![stream.complete()]);
+                            self.call_event_handler(&e);
+                        }
+                    }
                     FromJavaMessage::HttpRequestError {request_id, error, ..} => {
+                        self.os.streaming_http_requests.remove(&LiveId(request_id));
                         let e = Event::NetworkResponses(vec![
                             NetworkResponseItem {
                                 request_id: LiveId(request_id),
@@ -818,6 +842,7 @@ impl Default for CxOs {
             fullscreen: false,
             timers: Default::default(),
             video_surfaces: HashMap::new(),
+            streaming_http_requests: HashMap::new(),
         }
     }
 }
@@ -846,6 +871,8 @@ pub struct CxOs {
     pub (crate) display: Option<CxAndroidDisplay>,
     pub (crate) media: CxAndroidMedia,
     pub (crate) video_surfaces: HashMap<LiveId, jobject>,
+    // streaming requests whose response head arrived
+    pub (crate) streaming_http_requests: HashMap<LiveId, HttpStream>,
 }
 
 impl CxAndroidDisplay {
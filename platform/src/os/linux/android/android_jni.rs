@@ -66,6 +66,20 @@ pub enum FromJavaMessage {
         metadata_id: u64,
         error: String,
     },
+    // the head of the response to a streaming request, its body follows in HttpStreamData pieces
+    HttpStreamResponse {
+        request_id: u64,
+        metadata_id: u64,
+        status_code: u16,
+        headers: String,
+    },
+    HttpStreamData {
+        request_id: u64,
+        data: Vec<u8>,
+    },
+    HttpStreamComplete {
+        request_id: u64,
+    },
     WebSocketMessage {
         message: Vec<u8>,
         sender: Box<Sender<WebSocketMessage>>,
@@ -406,6 +420,51 @@ extern "C" fn Java_dev_makepad_android_MakepadNative_onHttpRequestError(
     });
 }
 
+#[no_mangle]
+extern "C" fn Java_dev_makepad_android_MakepadNative_onHttpStreamResponse(
+    env: *mut jni_sys::JNIEnv,
+    _: jni_sys::jobject,
+    request_id: jni_sys::jlong,
+    metadata_id: jni_sys::jlong,
+    status_code: jni_sys::jint,
+    headers: jni_sys::jstring,
+) {
+    let headers = unsafe { jstring_to_string(env, headers) };
+
+    send_from_java_message(FromJavaMessage::HttpStreamResponse {
+        request_id: request_id as u64,
+        metadata_id: metadata_id as u64,
+        status_code: status_code as u16,
+        headers,
+    });
+}
+
+#[no_mangle]
+extern "C" fn Java_dev_makepad_android_MakepadNative_onHttpStreamData(
+    env: *mut jni_sys::JNIEnv,
+    _: jni_sys::jobject,
+    request_id: jni_sys::jlong,
+    data: jni_sys::jobject,
+) {
+    let data = unsafe { java_byte_array_to_vec(env, data) };
+
+    send_from_java_message(FromJavaMessage::HttpStreamData {
+        request_id: request_id as u64,
+        data,
+    });
+}
+
+#[no_mangle]
+extern "C" fn Java_dev_makepad_android_MakepadNative_onHttpStreamComplete(
+    _: *mut jni_sys::JNIEnv,
+    _: jni_sys::jobject,
+    request_id: jni_sys::jlong,
+) {
+    send_from_java_message(FromJavaMessage::HttpStreamComplete {
+        request_id: request_id as u64,
+    });
+}
+
 #[no_mangle]
 extern "C" fn Java_dev_makepad_android_MakepadNative_onWebSocketMessage(
     env: *mut jni_sys::JNIEnv,
@@ -632,13 +691,14 @@ pub unsafe fn to_java_http_request(request_id: LiveId, request: HttpRequest) {
         env,
         get_activity(),
         "requestHttp",
-        "(JJLjava/lang/String;Ljava/lang/String;Ljava/lang/String;[B)V",
+        "(JJLjava/lang/String;Ljava/lang/String;Ljava/lang/String;[BZ)V",
         request_id.get_value() as jni_sys::jlong,
         request.metadata_id.get_value() as jni_sys::jlong,
         url,
         method,
         headers,
-        java_body as jni_sys::jobject
+        java_body as jni_sys::jobject,
+        request.is_streaming as jni_sys::jboolean
     );
 }
 
@@ -38,6 +38,16 @@ pub struct fd_set {
 
 pub const RTLD_LAZY: c_int = 1;
 pub const RTLD_LOCAL: c_int = 0;
+
+pub const POLLIN: i16 = 0x1;
+pub const POLLOUT: i16 = 0x4;
+
+#[repr(C)]
+pub struct pollfd {
+    pub fd: c_int,
+    pub events: i16,
+    pub revents: i16,
+}
     
 extern "C"{
     pub fn dlopen(filename: *const c_char, flag: c_int) -> *mut c_void;
@@ -55,6 +65,7 @@ extern "C"{
         timeout: *mut timeval,
     ) -> c_int;
     pub fn read(fd: c_int, buf: *mut c_void, count: size_t) -> c_int;
+    pub fn poll(fds: *mut pollfd, nfds: c_ulong, timeout: c_int) -> c_int;
 }
 
 pub unsafe fn FD_SET(fd: c_int, set: *mut fd_set) -> () {
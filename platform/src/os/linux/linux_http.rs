@@ -0,0 +1,472 @@
+use {
+    std::{
+        io,
+        io::{BufRead, BufReader, Read, Write},
+        net::{TcpStream, Shutdown, ToSocketAddrs},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            mpsc::Sender,
+            Arc,
+            Mutex,
+        },
+        time::{Duration, Instant},
+    },
+    self::super::tls_stream::{SocketStream, TlsStream},
+    crate::{
+        makepad_live_id::*,
+        thread::SignalToUI,
+        event::{
+            NetworkResponseItem,
+            NetworkResponse,
+            HttpRequest,
+            HttpResponse,
+            HttpMethod,
+            HttpStream,
+        },
+    }
+};
+
+// lets the UI thread abort a request that is still in flight
+#[derive(Clone, Default)]
+pub struct HttpRequestCancel {
+    cancelled: Arc<AtomicBool>,
+    stream: Arc<Mutex<Option<TcpStream >> >,
+}
+
+impl HttpRequestCancel {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        // shutting down the socket unblocks the reading thread
+        if let Some(stream) = self.stream.lock().unwrap().as_ref() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+enum HttpError {
+    Failed(String),
+    Cancelled,
+    TimedOut,
+}
+
+struct HttpConnection {
+    reader: BufReader<SocketStream>,
+    deadline: Option<Instant>,
+    cancel: HttpRequestCancel,
+}
+
+impl HttpConnection {
+    // `verify` only applies to secure connections, and checks the server certificate against the system's trusted ones
+    fn connect(host: &str, port: &str, is_secure: bool, verify: bool, deadline: Option<Instant>, cancel: HttpRequestCancel) -> Result<Self, HttpError> {
+        let stream = if let Some(deadline) = deadline {
+            let addrs = format!("{}:{}", host, port).to_socket_addrs()
+                .map_err( | e | HttpError::Failed(format!("Error resolving {}:{} - {}", host, port, e))) ?;
+            let mut result = Err(HttpError::Failed(format!("Error resolving {}:{}", host, port)));
+            for addr in addrs {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    result = Err(HttpError::TimedOut);
+                    break;
+                }
+                match TcpStream::connect_timeout(&addr, remaining) {
+                    Ok(stream) => {
+                        result = Ok(stream);
+                        break;
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::TimedOut => result = Err(HttpError::TimedOut),
+                    Err(e) => result = Err(HttpError::Failed(format!("Error connecting to {}:{} - {}", host, port, e)))
+                }
+            }
+            result ?
+        }
+        else {
+            TcpStream::connect(format!("{}:{}", host, port))
+                .map_err( | e | HttpError::Failed(format!("Error connecting to {}:{} - {}", host, port, e))) ?
+        };
+        // cancelling shuts down the socket itself, which also ends a TLS handshake or read in progress
+        *cancel.stream.lock().unwrap() = stream.try_clone().ok();
+        let mut connection = Self {
+            reader: BufReader::new(SocketStream::Tcp(stream)),
+            deadline,
+            cancel
+        };
+        connection.check() ?;
+        if is_secure {
+            let SocketStream::Tcp(stream) = connection.reader.into_inner() else {unreachable!()};
+            let stream = TlsStream::connect(stream, host, verify).map_err( | e | {
+                if connection.cancel.is_cancelled() {
+                    HttpError::Cancelled
+                }
+                else if deadline.is_some_and(| deadline | Instant::now() >= deadline) {
+                    HttpError::TimedOut
+                }
+                else {
+                    HttpError::Failed(e)
+                }
+            }) ?;
+            connection.reader = BufReader::new(SocketStream::Tls(stream));
+            connection.check() ?;
+        }
+        Ok(connection)
+    }
+
+    // bails out on cancel or an expired deadline, and bounds the next socket operation by the time left
+    fn check(&mut self) -> Result<(), HttpError> {
+        if self.cancel.is_cancelled() {
+            return Err(HttpError::Cancelled)
+        }
+        if let Some(deadline) = self.deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(HttpError::TimedOut)
+            }
+            let stream = self.reader.get_mut();
+            let _ = stream.set_read_timeout(Some(remaining));
+            let _ = stream.set_write_timeout(Some(remaining));
+        }
+        Ok(())
+    }
+
+    fn error(&self, what: &str, e: io::Error) -> HttpError {
+        if self.cancel.is_cancelled() {
+            return HttpError::Cancelled
+        }
+        if self.deadline.is_some() && (e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut) {
+            return HttpError::TimedOut
+        }
+        HttpError::Failed(format!("{}: {}", what, e))
+    }
+
+    fn write(&mut self, what: &str, bytes: &[u8]) -> Result<(), HttpError> {
+        self.check() ?;
+        if let Err(e) = self.reader.get_mut().write_all(bytes) {
+            return Err(self.error(what, e))
+        }
+        Ok(())
+    }
+
+    fn read_line(&mut self, line: &mut String) -> Result<(), HttpError> {
+        self.check() ?;
+        line.clear();
+        match self.reader.read_line(line) {
+            Ok(0) => {
+                self.check() ?;
+                Err(HttpError::Failed("Connection closed while reading response".to_string()))
+            }
+            Ok(_) => {
+                while line.ends_with('\n') || line.ends_with('\r') {
+                    line.pop();
+                }
+                Ok(())
+            }
+            Err(e) => Err(self.error("Error reading response", e))
+        }
+    }
+
+    fn read(&mut self, buffer: &mut [u8]) -> Result<usize, HttpError> {
+        self.check() ?;
+        match self.reader.read(buffer) {
+            Ok(0) => {
+                self.check() ?;
+                Ok(0)
+            }
+            Ok(n) => Ok(n),
+            Err(e) => Err(self.error("Error reading response body", e))
+        }
+    }
+
+    fn read_exact(&mut self, buffer: &mut [u8]) -> Result<(), HttpError> {
+        let mut pos = 0;
+        while pos < buffer.len() {
+            match self.read(&mut buffer[pos..]) ? {
+                0 => return Err(HttpError::Failed("Connection closed before the response body was complete".to_string())),
+                n => pos += n
+            }
+        }
+        Ok(())
+    }
+}
+
+// an HTTP/1.1 client over plain or TLS connections, every request runs on its own thread and reports back via the networking channel
+// streaming requests deliver each piece of the body as it arrives instead of one final response
+pub fn make_http_request(request_id: LiveId, request: HttpRequest, networking_sender: Sender<NetworkResponseItem>) -> HttpRequestCancel {
+    let cancel = HttpRequestCancel::default();
+    let thread_cancel = cancel.clone();
+    std::thread::spawn(move || {
+        let response = match http_request_blocking(request_id, &request, &networking_sender, thread_cancel) {
+            Ok(Some(response)) => NetworkResponse::HttpResponse(response),
+            // streaming requests have already sent their final item
+            Ok(None) => return,
+            Err(HttpError::Failed(error)) => NetworkResponse::HttpRequestError(error),
+            Err(HttpError::Cancelled) => NetworkResponse::HttpRequestCancelled,
+            Err(HttpError::TimedOut) => NetworkResponse::HttpRequestTimedOut,
+        };
+        send_response(&networking_sender, NetworkResponseItem {
+            request_id,
+            response
+        });
+    });
+    cancel
+}
+
+// hands a response to the UI thread, and wakes it up to pick it up
+fn send_response(networking_sender: &Sender<NetworkResponseItem>, item: NetworkResponseItem) {
+    let _ = networking_sender.send(item);
+    SignalToUI::set_ui_signal();
+}
+
+fn has_header(request: &HttpRequest, name: &str) -> bool {
+    request.headers.keys().any( | key | key.eq_ignore_ascii_case(name))
+}
+
+fn build_request_head(request: &HttpRequest) -> String {
+    let split = request.split_url();
+    let mut head = format!("{} /{} HTTP/1.1\r\n", request.method.to_string(), split.file);
+    if !has_header(request, "Host") {
+        let default_port = if split.proto == "https" {"443"} else {"80"};
+        if split.port == default_port {
+            head.push_str(&format!("Host: {}\r\n", split.host));
+        }
+        else {
+            head.push_str(&format!("Host: {}:{}\r\n", split.host, split.port));
+        }
+    }
+    if !has_header(request, "Content-Length") {
+        if let Some(body) = &request.body {
+            head.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        }
+        else if request.method == HttpMethod::POST || request.method == HttpMethod::PUT || request.method == HttpMethod::PATCH {
+            head.push_str("Content-Length: 0\r\n");
+        }
+    }
+    if !has_header(request, "Connection") {
+        head.push_str("Connection: close\r\n");
+    }
+    head.push_str(&request.get_headers_string());
+    head.push_str("\r\n");
+    head
+}
+
+// the most redirects followed for a single request before giving up
+const MAX_REDIRECTS: usize = 10;
+
+fn http_request_blocking(
+    request_id: LiveId,
+    request: &HttpRequest,
+    networking_sender: &Sender<NetworkResponseItem>,
+    cancel: HttpRequestCancel
+) -> Result<Option<HttpResponse>, HttpError> {
+    let deadline = request.timeout.map( | timeout | Instant::now() + Duration::from_secs_f64(timeout.max(0.0)));
+
+    // follow redirects like the http clients of the other platforms do
+    let mut redirected: Option<HttpRequest> = None;
+    let mut redirects = 0;
+    let (mut connection, mut response) = loop {
+        let current = redirected.as_ref().unwrap_or(request);
+        let (connection, response) = send_request(current, deadline, cancel.clone()) ?;
+        match redirect_location(current, &response) {
+            Some(url) => {
+                if redirects == MAX_REDIRECTS {
+                    return Err(HttpError::Failed(format!("Too many redirects, last one to {}", url)))
+                }
+                redirects += 1;
+                redirected = Some(redirect_request(current, response.status_code, url));
+            }
+            None => break (connection, response)
+        }
+    };
+    let request = redirected.as_ref().unwrap_or(request);
+
+    let is_chunked = response_header(&response, "Transfer-Encoding").is_some_and(| v | v.to_ascii_lowercase().contains("chunked"));
+    let content_length = response_header(&response, "Content-Length").and_then( | v | v.parse::<u64>().ok());
+
+    let has_body = request.method != HttpMethod::HEAD
+        && response.status_code != 204
+        && response.status_code != 304
+        && response.status_code != 101;
+    if !has_body {
+        response.body = Some(Vec::new());
+        if request.is_streaming {
+            send_response(networking_sender, HttpStream::new(request_id, response).complete());
+            return Ok(None)
+        }
+        return Ok(Some(response))
+    }
+
+    if request.is_streaming {
+        let mut stream = HttpStream::new(request_id, response);
+        read_body(&mut connection, is_chunked, content_length, | data | {
+            for item in stream.data(data) {
+                send_response(networking_sender, item);
+            }
+        }) ?;
+        send_response(networking_sender, stream.complete());
+        return Ok(None)
+    }
+
+    let total = content_length.unwrap_or(0);
+    let mut body = Vec::new();
+    read_body(&mut connection, is_chunked, content_length, | data | {
+        body.extend_from_slice(data);
+        send_response(networking_sender, NetworkResponseItem {
+            request_id,
+            response: NetworkResponse::HttpProgress {loaded: body.len() as u64, total}
+        });
+    }) ?;
+    response.body = Some(body);
+    Ok(Some(response))
+}
+
+// connects, writes the request and reads the head of the final (non informational) response
+fn send_request(request: &HttpRequest, deadline: Option<Instant>, cancel: HttpRequestCancel) -> Result<(HttpConnection, HttpResponse), HttpError> {
+    let split = request.split_url();
+    let is_secure = match split.proto {
+        "https" => true,
+        "http" => false,
+        _ => return Err(HttpError::Failed(format!("Protocol {} is not supported by the linux http client", split.proto)))
+    };
+    let mut connection = HttpConnection::connect(split.host, split.port, is_secure, !request.ignore_ssl_cert, deadline, cancel) ?;
+
+    connection.write("Error writing request headers", build_request_head(request).as_bytes()) ?;
+    if let Some(body) = &request.body {
+        connection.write("Error writing request body", body) ?;
+    }
+
+    let mut line = String::new();
+
+    // skip over any informational (1xx) responses
+    loop {
+        connection.read_line(&mut line) ?;
+        let mut parts = line.split_whitespace();
+        if !parts.next().is_some_and(| v | v.starts_with("HTTP/")) {
+            return Err(HttpError::Failed(format!("Invalid response status line: {}", line)))
+        }
+        let status_code: u16 = parts.next().and_then( | v | v.parse().ok())
+            .ok_or_else( || HttpError::Failed(format!("Invalid response status line: {}", line))) ?;
+
+        let mut response = HttpResponse::new(request.metadata_id, status_code, String::new(), None);
+        loop {
+            connection.read_line(&mut line) ?;
+            if line.is_empty() {
+                break;
+            }
+            if let Some((key, value)) = line.split_once(':') {
+                response.set_header(key.trim().to_string(), value.trim().to_string());
+            }
+        }
+        if status_code >= 200 || status_code == 101 {
+            return Ok((connection, response))
+        }
+    }
+}
+
+fn response_header(response: &HttpResponse, name: &str) -> Option<String> {
+    response.headers.iter()
+        .find( | (key, _) | key.eq_ignore_ascii_case(name))
+        .and_then( | (_, values) | values.last().cloned())
+}
+
+// the absolute url a redirect response points to, if it is one
+fn redirect_location(request: &HttpRequest, response: &HttpResponse) -> Option<String> {
+    if !matches!(response.status_code, 301 | 302 | 303 | 307 | 308) {
+        return None
+    }
+    let location = response_header(response, "Location") ?;
+    let split = request.split_url();
+    Some(if location.contains("://") {
+        location
+    }
+    else if let Some(location) = location.strip_prefix("//") {
+        format!("{}://{}", split.proto, location)
+    }
+    else if location.starts_with('/') {
+        format!("{}://{}:{}{}", split.proto, split.host, split.port, location)
+    }
+    else {
+        // relative to the directory of the current path
+        let dir = split.file.rsplit_once('/').map_or("", | (dir, _) | dir);
+        if dir.is_empty() {
+            format!("{}://{}:{}/{}", split.proto, split.host, split.port, location)
+        }
+        else {
+            format!("{}://{}:{}/{}/{}", split.proto, split.host, split.port, dir, location)
+        }
+    })
+}
+
+// a 303, or a 301/302 after a POST, turns the request into a GET without a body, other redirects repeat it as is
+fn redirect_request(request: &HttpRequest, status_code: u16, url: String) -> HttpRequest {
+    let becomes_get = request.method != HttpMethod::HEAD && (status_code == 303
+        || (request.method == HttpMethod::POST && (status_code == 301 || status_code == 302)));
+    let mut headers = request.headers.clone();
+    if becomes_get {
+        headers.retain( | key, _ | !key.eq_ignore_ascii_case("Content-Length") && !key.eq_ignore_ascii_case("Content-Type"));
+    }
+    // the host may have changed
+    headers.retain( | key, _ | !key.eq_ignore_ascii_case("Host"));
+    HttpRequest {
+        metadata_id: request.metadata_id,
+        url,
+        method: if becomes_get {HttpMethod::GET} else {request.method.clone()},
+        headers,
+        ignore_ssl_cert: request.ignore_ssl_cert,
+        is_streaming: request.is_streaming,
+        timeout: request.timeout,
+        body: if becomes_get {None} else {request.body.clone()},
+    }
+}
+
+fn read_body<F>(connection: &mut HttpConnection, is_chunked: bool, content_length: Option<u64>, mut on_data: F) -> Result<(), HttpError>
+where F: FnMut(&[u8]) {
+    let mut line = String::new();
+    let mut buffer = [0u8; 65536];
+    if is_chunked {
+        let mut chunk = Vec::new();
+        loop {
+            connection.read_line(&mut line) ?;
+            let size = line.split(';').next().unwrap_or("").trim();
+            let size = usize::from_str_radix(size, 16)
+                .map_err( | _ | HttpError::Failed(format!("Invalid chunk size: {}", line))) ?;
+            if size == 0 {
+                // skip the trailer headers
+                loop {
+                    connection.read_line(&mut line) ?;
+                    if line.is_empty() {
+                        break;
+                    }
+                }
+                return Ok(())
+            }
+            chunk.resize(size, 0u8);
+            connection.read_exact(&mut chunk) ?;
+            connection.read_line(&mut line) ?;
+            on_data(&chunk);
+        }
+    }
+    else if let Some(content_length) = content_length {
+        let mut bytes_left = content_length as usize;
+        while bytes_left > 0 {
+            let want = bytes_left.min(buffer.len());
+            match connection.read(&mut buffer[0..want]) ? {
+                0 => return Err(HttpError::Failed("Connection closed before the response body was complete".to_string())),
+                n => {
+                    bytes_left -= n;
+                    on_data(&buffer[0..n]);
+                }
+            }
+        }
+        Ok(())
+    }
+    else {
+        loop {
+            match connection.read(&mut buffer) ? {
+                0 => return Ok(()),
+                n => on_data(&buffer[0..n])
+            }
+        }
+    }
+}
@@ -30,8 +30,14 @@ pub mod pulse_audio;
 #[cfg(not(target_os="android"))]
 pub mod pulse_sys;
 
+#[cfg(not(target_os="android"))]
+pub mod openssl_sys;
+#[cfg(not(target_os="android"))]
+pub mod tls_stream;
 #[cfg(not(target_os="android"))]
 mod web_socket;
+#[cfg(not(target_os="android"))]
+pub mod linux_http;
 
 #[cfg(target_os="android")]
 pub mod android;
@@ -0,0 +1,123 @@
+#![allow(non_camel_case_types)]
+#![allow(non_snake_case)]
+
+// the parts of libssl (OpenSSL 1.1 and 3) needed for a TLS client. The library is loaded at
+// runtime, so applications build and run without it and only secure connections need it.
+
+use std::{
+    ffi::CString,
+    os::raw::{c_char, c_int, c_long, c_ulong, c_void},
+    ptr::NonNull,
+};
+use self::super::libc_sys::{dlclose, dlopen, dlsym, RTLD_LAZY, RTLD_LOCAL};
+
+pub enum SSL_METHOD {}
+pub enum SSL_CTX {}
+pub enum SSL {}
+
+pub const SSL_VERIFY_NONE: c_int = 0;
+pub const SSL_VERIFY_PEER: c_int = 1;
+
+pub const SSL_ERROR_SSL: c_int = 1;
+pub const SSL_ERROR_WANT_READ: c_int = 2;
+pub const SSL_ERROR_WANT_WRITE: c_int = 3;
+pub const SSL_ERROR_SYSCALL: c_int = 5;
+pub const SSL_ERROR_ZERO_RETURN: c_int = 6;
+
+pub const SSL_CTRL_SET_TLSEXT_HOSTNAME: c_int = 55;
+pub const TLSEXT_NAMETYPE_HOST_NAME: c_long = 0;
+
+pub const X509_V_OK: c_long = 0;
+
+// OpenSSL 3 only, 1.1 already reports a close without close_notify as end of file
+pub const SSL_OP_IGNORE_UNEXPECTED_EOF: u64 = 1 << 7;
+
+type SslVerifyCallback = Option<unsafe extern "C" fn(c_int, *mut c_void) -> c_int>;
+
+struct Module(NonNull<c_void>);
+
+// a loaded library can be used from any thread
+unsafe impl Send for Module {}
+unsafe impl Sync for Module {}
+
+impl Module {
+    fn load(path: &str) -> Result<Self, ()> {
+        let path = CString::new(path).unwrap();
+        let module = unsafe {dlopen(path.as_ptr(), RTLD_LAZY | RTLD_LOCAL)};
+        NonNull::new(module).map(Module).ok_or(())
+    }
+
+    // also finds the libcrypto symbols, as libssl depends on it
+    fn get_symbol<F: Sized>(&self, name: &str) -> Result<F, ()> {
+        let name = CString::new(name).unwrap();
+        let symbol = unsafe {dlsym(self.0.as_ptr(), name.as_ptr())};
+        if symbol.is_null() {
+            return Err(());
+        }
+        Ok(unsafe {std::mem::transmute_copy::<_, F>(&symbol)})
+    }
+}
+
+impl Drop for Module {
+    fn drop(&mut self) {
+        unsafe {dlclose(self.0.as_ptr())};
+    }
+}
+
+pub struct LibSsl {
+    pub TLS_client_method: unsafe extern "C" fn() -> *const SSL_METHOD,
+    pub SSL_CTX_new: unsafe extern "C" fn(*const SSL_METHOD) -> *mut SSL_CTX,
+    pub SSL_CTX_free: unsafe extern "C" fn(*mut SSL_CTX),
+    pub SSL_CTX_set_default_verify_paths: unsafe extern "C" fn(*mut SSL_CTX) -> c_int,
+    pub SSL_new: unsafe extern "C" fn(*mut SSL_CTX) -> *mut SSL,
+    pub SSL_free: unsafe extern "C" fn(*mut SSL),
+    pub SSL_set_fd: unsafe extern "C" fn(*mut SSL, c_int) -> c_int,
+    pub SSL_set_verify: unsafe extern "C" fn(*mut SSL, c_int, SslVerifyCallback),
+    pub SSL_set1_host: unsafe extern "C" fn(*mut SSL, *const c_char) -> c_int,
+    pub SSL_ctrl: unsafe extern "C" fn(*mut SSL, c_int, c_long, *mut c_void) -> c_long,
+    pub SSL_connect: unsafe extern "C" fn(*mut SSL) -> c_int,
+    pub SSL_read: unsafe extern "C" fn(*mut SSL, *mut c_void, c_int) -> c_int,
+    pub SSL_write: unsafe extern "C" fn(*mut SSL, *const c_void, c_int) -> c_int,
+    pub SSL_shutdown: unsafe extern "C" fn(*mut SSL) -> c_int,
+    pub SSL_get_error: unsafe extern "C" fn(*const SSL, c_int) -> c_int,
+    pub SSL_get_verify_result: unsafe extern "C" fn(*const SSL) -> c_long,
+    pub X509_verify_cert_error_string: unsafe extern "C" fn(c_long) -> *const c_char,
+    pub ERR_get_error: unsafe extern "C" fn() -> c_ulong,
+    pub ERR_error_string_n: unsafe extern "C" fn(c_ulong, *mut c_char, usize),
+    pub SSL_CTX_set_options: unsafe extern "C" fn(*mut SSL_CTX, u64) -> u64,
+    pub OPENSSL_version_major: Option<unsafe extern "C" fn() -> u32>,
+    _keep_module_alive: Module,
+}
+
+impl LibSsl {
+    pub fn try_load() -> Option<LibSsl> {
+        let module = Module::load("libssl.so.3")
+            .or_else( | _ | Module::load("libssl.so.1.1"))
+            .or_else( | _ | Module::load("libssl.so"))
+            .ok() ?;
+        Some(LibSsl {
+            TLS_client_method: module.get_symbol("TLS_client_method").ok() ?,
+            SSL_CTX_new: module.get_symbol("SSL_CTX_new").ok() ?,
+            SSL_CTX_free: module.get_symbol("SSL_CTX_free").ok() ?,
+            SSL_CTX_set_default_verify_paths: module.get_symbol("SSL_CTX_set_default_verify_paths").ok() ?,
+            SSL_new: module.get_symbol("SSL_new").ok() ?,
+            SSL_free: module.get_symbol("SSL_free").ok() ?,
+            SSL_set_fd: module.get_symbol("SSL_set_fd").ok() ?,
+            SSL_set_verify: module.get_symbol("SSL_set_verify").ok() ?,
+            SSL_set1_host: module.get_symbol("SSL_set1_host").ok() ?,
+            SSL_ctrl: module.get_symbol("SSL_ctrl").ok() ?,
+            SSL_connect: module.get_symbol("SSL_connect").ok() ?,
+            SSL_read: module.get_symbol("SSL_read").ok() ?,
+            SSL_write: module.get_symbol("SSL_write").ok() ?,
+            SSL_shutdown: module.get_symbol("SSL_shutdown").ok() ?,
+            SSL_get_error: module.get_symbol("SSL_get_error").ok() ?,
+            SSL_get_verify_result: module.get_symbol("SSL_get_verify_result").ok() ?,
+            X509_verify_cert_error_string: module.get_symbol("X509_verify_cert_error_string").ok() ?,
+            ERR_get_error: module.get_symbol("ERR_get_error").ok() ?,
+            ERR_error_string_n: module.get_symbol("ERR_error_string_n").ok() ?,
+            SSL_CTX_set_options: module.get_symbol("SSL_CTX_set_options").ok() ?,
+            OPENSSL_version_major: module.get_symbol("OPENSSL_version_major").ok(),
+            _keep_module_alive: module,
+        })
+    }
+}
@@ -0,0 +1,291 @@
+use {
+    std::{
+        ffi::{CStr, CString},
+        io,
+        io::{Read, Write},
+        net::{IpAddr, Shutdown, TcpStream},
+        os::{
+            raw::{c_char, c_int, c_void},
+            unix::io::AsRawFd,
+        },
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+    self::super::{
+        libc_sys::{poll, pollfd, POLLIN, POLLOUT},
+        openssl_sys::*,
+    },
+};
+
+// how long a blocked read or write waits for the socket before trying again, the other handle
+// might have pulled the bytes it waits for into the TLS buffers in the meantime
+const RETRY_INTERVAL: Duration = Duration::from_millis(100);
+
+struct TlsConnection {
+    lib: LibSsl,
+    ctx: *mut SSL_CTX,
+    ssl: Mutex<*mut SSL>,
+    stream: TcpStream,
+}
+
+// the SSL object is only used with its lock held
+unsafe impl Send for TlsConnection {}
+unsafe impl Sync for TlsConnection {}
+
+impl Drop for TlsConnection {
+    fn drop(&mut self) {
+        unsafe {
+            (self.lib.SSL_free)(*self.ssl.lock().unwrap());
+            (self.lib.SSL_CTX_free)(self.ctx);
+        }
+    }
+}
+
+// a TLS client connection over a TcpStream, using the system OpenSSL. Cloned handles share the
+// connection, so one thread can read while another one writes.
+#[derive(Clone)]
+pub struct TlsStream {
+    connection: Arc<TlsConnection>,
+    read_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+}
+
+impl TlsStream {
+    // does the TLS handshake with the socket's own timeouts, when `verify` is set the server
+    // certificate has to be trusted by the system and match `host`
+    pub fn connect(stream: TcpStream, host: &str, verify: bool) -> Result<Self, String> {
+        let lib = LibSsl::try_load().ok_or_else( || "Secure connections need libssl from OpenSSL 1.1 or 3, which could not be loaded".to_string()) ?;
+        let host_c = CString::new(host).map_err( | _ | format!("Invalid host {}", host)) ?;
+        unsafe {
+            let ctx = (lib.SSL_CTX_new)((lib.TLS_client_method)());
+            if ctx.is_null() {
+                return Err(ssl_error(&lib, "Error creating TLS context"))
+            }
+            // plenty of servers just close the connection
+            if lib.OPENSSL_version_major.is_some_and(| version | version() >= 3) {
+                (lib.SSL_CTX_set_options)(ctx, SSL_OP_IGNORE_UNEXPECTED_EOF);
+            }
+            if verify && (lib.SSL_CTX_set_default_verify_paths)(ctx) != 1 {
+                let error = ssl_error(&lib, "Error loading the system certificates");
+                (lib.SSL_CTX_free)(ctx);
+                return Err(error)
+            }
+            let ssl = (lib.SSL_new)(ctx);
+            if ssl.is_null() {
+                let error = ssl_error(&lib, "Error creating TLS connection");
+                (lib.SSL_CTX_free)(ctx);
+                return Err(error)
+            }
+            // from here on dropping the connection frees both
+            let connection = TlsConnection {lib, ctx, ssl: Mutex::new(ssl), stream};
+            let lib = &connection.lib;
+            if verify {
+                (lib.SSL_set_verify)(ssl, SSL_VERIFY_PEER, None);
+                if (lib.SSL_set1_host)(ssl, host_c.as_ptr()) != 1 {
+                    return Err(ssl_error(lib, "Error setting the TLS host name"))
+                }
+            }
+            else {
+                (lib.SSL_set_verify)(ssl, SSL_VERIFY_NONE, None);
+            }
+            // server name indication is only for names, not addresses
+            if host.parse::<IpAddr>().is_err() {
+                (lib.SSL_ctrl)(ssl, SSL_CTRL_SET_TLSEXT_HOSTNAME, TLSEXT_NAMETYPE_HOST_NAME, host_c.as_ptr() as *mut c_void);
+            }
+            if (lib.SSL_set_fd)(ssl, connection.stream.as_raw_fd()) != 1 {
+                return Err(ssl_error(lib, "Error setting up TLS"))
+            }
+            if (lib.SSL_connect)(ssl) != 1 {
+                let verify_result = (lib.SSL_get_verify_result)(ssl);
+                if verify_result != X509_V_OK {
+                    let reason = CStr::from_ptr((lib.X509_verify_cert_error_string)(verify_result));
+                    return Err(format!("TLS certificate of {} is not trusted: {}", host, reason.to_string_lossy()))
+                }
+                return Err(ssl_error(lib, "TLS handshake failed"))
+            }
+            connection.stream.set_nonblocking(true).map_err( | e | format!("Error setting up TLS: {}", e)) ?;
+            Ok(Self {
+                connection: Arc::new(connection),
+                read_timeout: None,
+                write_timeout: None,
+            })
+        }
+    }
+
+    // only applies to this handle
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.read_timeout = timeout;
+    }
+
+    // only applies to this handle
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) {
+        self.write_timeout = timeout;
+    }
+
+    // a shutdown for writing first tells the server with a TLS close_notify
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        if how != Shutdown::Read {
+            let ssl = self.connection.ssl.lock().unwrap();
+            unsafe {(self.connection.lib.SSL_shutdown)(*ssl);}
+        }
+        self.connection.stream.shutdown(how)
+    }
+
+    // runs an SSL_read or SSL_write until it transfers data, waiting for the socket whenever
+    // OpenSSL needs to read or write first. Returns 0 once the server closed the connection.
+    fn transfer<F>(&self, timeout: Option<Duration>, mut op: F) -> io::Result<usize>
+    where F: FnMut(&LibSsl, *mut SSL) -> c_int {
+        let lib = &self.connection.lib;
+        let deadline = timeout.map( | timeout | Instant::now() + timeout);
+        loop {
+            let events = {
+                let ssl = self.connection.ssl.lock().unwrap();
+                let ret = op(lib, *ssl);
+                if ret > 0 {
+                    return Ok(ret as usize)
+                }
+                match unsafe {(lib.SSL_get_error)(*ssl, ret)} {
+                    SSL_ERROR_WANT_READ => POLLIN,
+                    SSL_ERROR_WANT_WRITE => POLLOUT,
+                    SSL_ERROR_ZERO_RETURN => return Ok(0),
+                    SSL_ERROR_SYSCALL if ret == 0 => return Ok(0),
+                    SSL_ERROR_SYSCALL => return Err(io::Error::last_os_error()),
+                    _ => return Err(io::Error::other(unsafe {ssl_error(lib, "TLS error")}))
+                }
+            };
+            let wait = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        return Err(io::Error::new(io::ErrorKind::TimedOut, "TLS connection timed out"))
+                    }
+                    remaining.min(RETRY_INTERVAL)
+                }
+                None => RETRY_INTERVAL
+            };
+            let mut fd = pollfd {
+                fd: self.connection.stream.as_raw_fd(),
+                events,
+                revents: 0
+            };
+            if unsafe {poll(&mut fd, 1, wait.as_millis() as c_int)} < 0 {
+                let error = io::Error::last_os_error();
+                if error.kind() != io::ErrorKind::Interrupted {
+                    return Err(error)
+                }
+            }
+        }
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+        let len = buf.len().min(c_int::MAX as usize) as c_int;
+        self.transfer(self.read_timeout, | lib, ssl | unsafe {
+            (lib.SSL_read)(ssl, buf.as_mut_ptr() as *mut c_void, len)
+        })
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+        // a retried SSL_write has to be passed the same bytes again, which this does
+        let len = buf.len().min(c_int::MAX as usize) as c_int;
+        self.transfer(self.write_timeout, | lib, ssl | unsafe {
+            (lib.SSL_write)(ssl, buf.as_ptr() as *const c_void, len)
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// a plain or a TLS connection, cloning a handle shares the connection like TcpStream::try_clone does
+pub enum SocketStream {
+    Tcp(TcpStream),
+    Tls(TlsStream),
+}
+
+impl SocketStream {
+    pub fn try_clone(&self) -> io::Result<Self> {
+        match self {
+            Self::Tcp(stream) => Ok(Self::Tcp(stream.try_clone() ?)),
+            Self::Tls(stream) => Ok(Self::Tls(stream.clone())),
+        }
+    }
+
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.set_read_timeout(timeout),
+            Self::Tls(stream) => {
+                stream.set_read_timeout(timeout);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn set_write_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.set_write_timeout(timeout),
+            Self::Tls(stream) => {
+                stream.set_write_timeout(timeout);
+                Ok(())
+            }
+        }
+    }
+
+    pub fn shutdown(&self, how: Shutdown) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.shutdown(how),
+            Self::Tls(stream) => stream.shutdown(how),
+        }
+    }
+}
+
+impl Read for SocketStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.read(buf),
+            Self::Tls(stream) => stream.read(buf),
+        }
+    }
+}
+
+impl Write for SocketStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tcp(stream) => stream.write(buf),
+            Self::Tls(stream) => stream.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tcp(stream) => stream.flush(),
+            Self::Tls(stream) => stream.flush(),
+        }
+    }
+}
+
+// the message with the reasons OpenSSL queued up on this thread
+unsafe fn ssl_error(lib: &LibSsl, message: &str) -> String {
+    let mut message = message.to_string();
+    loop {
+        let error = (lib.ERR_get_error)();
+        if error == 0 {
+            break
+        }
+        let mut buf = [0 as c_char; 256];
+        (lib.ERR_error_string_n)(error, buf.as_mut_ptr(), buf.len());
+        message.push_str(": ");
+        message.push_str(&CStr::from_ptr(buf.as_ptr()).to_string_lossy());
+    }
+    message
+}
@@ -1,129 +1,252 @@
-
 use crate::event::HttpRequest;
 use crate::web_socket::{WebSocketMessage};
-use std::sync::mpsc::{channel, Sender};
-use std::net::TcpStream;
-use std::io::{Read};
-use makepad_http::utils::write_bytes_to_tcp_stream_no_error;
-use makepad_http::websocket::{ServerWebSocket, ServerWebSocketMessageFormat, ServerWebSocketMessageHeader, ServerWebSocketMessage, SERVER_WEB_SOCKET_PONG_MESSAGE};
+use std::sync::mpsc::{channel, Sender, RecvTimeoutError};
+use std::net::{TcpStream, Shutdown};
+use std::io::{Read, Write};
+use std::time::Duration;
+use self::super::tls_stream::{SocketStream, TlsStream};
+use makepad_http::websocket::{
+    ServerWebSocket,
+    ServerWebSocketMessageFormat,
+    ServerWebSocketMessageHeader,
+    ServerWebSocketMessage,
+    ServerWebSocketError,
+    WEB_SOCKET_CLOSE_NORMAL,
+    WEB_SOCKET_CLOSE_GOING_AWAY,
+    WEB_SOCKET_CLOSE_PROTOCOL_ERROR,
+    WEB_SOCKET_CLOSE_TOO_LARGE,
+    WEB_SOCKET_PERMESSAGE_DEFLATE,
+};
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+const PING_INTERVAL: Duration = Duration::from_secs(15);
+
+enum WriterMessage {
+    Message(WebSocketMessage),
+    Pong(Vec<u8>),
+    Close(u16),
+}
 
 pub struct OsWebSocket{
-    sender: Option<Sender<WebSocketMessage>>
+    sender: Option<Sender<WriterMessage>>
+}
+
+impl Drop for OsWebSocket{
+    fn drop(&mut self){
+        if let Some(sender) = &self.sender{
+            let _ = sender.send(WriterMessage::Close(WEB_SOCKET_CLOSE_NORMAL));
+        }
+    }
 }
 
 impl OsWebSocket{
     pub fn send_message(&mut self, message:WebSocketMessage)->Result<(),()>{
         // lets encode the message into a membuffer and send it to the write thread
         if let Some(sender) = &mut self.sender{
-            if sender.send(message).is_err(){
+            if sender.send(WriterMessage::Message(message)).is_err(){
                 return Err(());
             }
             return Ok(())
         }
         Err(())
     }
-                    
-    pub fn open(_socket_id:u64, request: HttpRequest, rx_sender:Sender<WebSocketMessage>)->OsWebSocket{
-        // parse the url
+
+    // writes the upgrade request and reads the response head, returning any frame bytes that came in right after it
+    // and whether the server accepted permessage-deflate
+    fn handshake(stream: &mut SocketStream, request: &HttpRequest) -> Result<(Vec<u8>, bool), String> {
         let split = request.split_url();
-        // strip off any hashes
-        // alright we have proto, host, port and hash now
-        // lets open a tcpstream
-        let stream = TcpStream::connect(format!("{}:{}", split.host, split.port));
-        // alright lets construct a http request
-        // lets join the headers
-        
-        let mut http_request = format!("GET {} HTTP/1.1\r\nHost: {}\r\nConnection: Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Key: SxJdXBRtW7Q4awLDhflO0Q==\r\n", split.file, split.host);
+        let key = ServerWebSocket::create_client_key();
+        let default_port = if let SocketStream::Tls(_) = stream {"443"} else {"80"};
+        let host = if split.port == default_port {split.host.to_string()} else {format!("{}:{}", split.host, split.port)};
+        let mut http_request = format!("GET /{} HTTP/1.1\r\nHost: {}\r\nConnection: Upgrade\r\nUpgrade: websocket\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Extensions: {}\r\n", split.file, host, key, WEB_SOCKET_PERMESSAGE_DEFLATE);
         http_request.push_str(&request.get_headers_string());
-        http_request.push_str("\r\n"); 
-        
-        // lets write the http request
-        if stream.is_err(){
-            rx_sender.send(WebSocketMessage::Error("Error connecting websocket tcpstream".into())).unwrap();
-            return OsWebSocket{sender:None}
-        }
-        let mut stream = stream.unwrap();
-        if write_bytes_to_tcp_stream_no_error(&mut stream, http_request.as_bytes()){
-            rx_sender.send(WebSocketMessage::Error("Error writing request to websocket".into())).unwrap();
-            return OsWebSocket{sender:None}
+        http_request.push_str("\r\n");
+        if stream.write_all(http_request.as_bytes()).is_err(){
+            return Err("Error writing request to websocket".into())
         }
-        
-        // lets start the thread
-        let mut input_stream = stream.try_clone().unwrap();
-        let mut output_stream = stream.try_clone().unwrap();
+
+        let _ = stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT));
+        let mut response = Vec::new();
+        let mut buffer = [0u8; 4096];
+        let head_end = loop {
+            if let Some(pos) = response.windows(4).position( | w | w == b"\r\n\r\n") {
+                break pos;
+            }
+            if response.len() > 65536 {
+                return Err("Websocket handshake response too large".into())
+            }
+            match stream.read(&mut buffer) {
+                Ok(0) => return Err("Websocket connection closed during handshake".into()),
+                Ok(n) => response.extend_from_slice(&buffer[0..n]),
+                Err(e) => return Err(format!("Error reading websocket handshake: {}", e))
+            }
+        };
+        let _ = stream.set_read_timeout(None);
+        let head = String::from_utf8_lossy(&response[0..head_end]);
+        let permessage_deflate = ServerWebSocket::validate_upgrade_response(&head, &key) ?;
+        Ok((response[head_end + 4..].to_vec(), permessage_deflate))
+    }
+
+    pub fn open(_socket_id:u64, request: HttpRequest, rx_sender:Sender<WebSocketMessage>)->OsWebSocket{
         let (sender, receiver) = channel();
-        
-        let _writer_thread = std::thread::spawn(move || {
-            while let Ok(msg) = receiver.recv(){
-                match msg{
-                    WebSocketMessage::Binary(data)=>{
-                        let header = ServerWebSocketMessageHeader::from_len(data.len(), ServerWebSocketMessageFormat::Binary, false);
-                        if write_bytes_to_tcp_stream_no_error(&mut output_stream, header.as_slice()) ||
-                        write_bytes_to_tcp_stream_no_error(&mut output_stream, &data){
-                            break;
-                        }
+        let sender_pong = sender.clone();
+
+        // connecting and the handshake happen on the reader thread, messages sent meanwhile queue up for the writer
+        let _reader_thread = std::thread::spawn(move || {
+            let split = request.split_url();
+            let is_secure = match split.proto {
+                "wss" | "https" => true,
+                "ws" | "http" => false,
+                _ => {
+                    let _ = rx_sender.send(WebSocketMessage::Error(format!("Unsupported websocket protocol {}", split.proto)));
+                    return
+                }
+            };
+            let stream = TcpStream::connect(format!("{}:{}", split.host, split.port));
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    let _ = rx_sender.send(WebSocketMessage::Error(format!("Error connecting websocket tcpstream: {}", e)));
+                    return
+                }
+            };
+            let mut stream = if is_secure {
+                let _ = stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT));
+                let _ = stream.set_write_timeout(Some(HANDSHAKE_TIMEOUT));
+                match TlsStream::connect(stream, split.host, !request.ignore_ssl_cert) {
+                    Ok(stream) => SocketStream::Tls(stream),
+                    Err(e) => {
+                        let _ = rx_sender.send(WebSocketMessage::Error(e));
+                        return
                     }
-                    WebSocketMessage::String(data)=>{
-                        let header = ServerWebSocketMessageHeader::from_len(data.len(), ServerWebSocketMessageFormat::Binary, false);
-                        if write_bytes_to_tcp_stream_no_error(&mut output_stream, header.as_slice()) ||
-                        write_bytes_to_tcp_stream_no_error(&mut output_stream, &data.as_bytes()){
-                            break;
+                }
+            }
+            else {
+                SocketStream::Tcp(stream)
+            };
+            let (leftover, permessage_deflate) = match Self::handshake(&mut stream, &request) {
+                Ok(result) => result,
+                Err(e) => {
+                    let _ = rx_sender.send(WebSocketMessage::Error(e));
+                    let _ = stream.shutdown(Shutdown::Both);
+                    return
+                }
+            };
+            let _ = rx_sender.send(WebSocketMessage::Opened);
+
+            let mut output_stream = stream.try_clone().unwrap();
+            let writer_thread = std::thread::spawn(move || {
+                let mut close_code = WEB_SOCKET_CLOSE_GOING_AWAY;
+                loop{
+                    let frame = match receiver.recv_timeout(PING_INTERVAL){
+                        Ok(WriterMessage::Message(WebSocketMessage::Binary(data)))=>{
+                            ServerWebSocket::build_data_frame(&data, ServerWebSocketMessageFormat::Binary, true, permessage_deflate)
                         }
-                    }
-                    _=>{
-                        crate::error!("WebSocketMessage of this type sending not implemented");
+                        Ok(WriterMessage::Message(WebSocketMessage::String(data)))=>{
+                            ServerWebSocket::build_data_frame(data.as_bytes(), ServerWebSocketMessageFormat::Text, true, permessage_deflate)
+                        }
+                        Ok(WriterMessage::Message(_))=>{
+                            crate::error!("WebSocketMessage of this type sending not implemented");
+                            continue
+                        }
+                        Ok(WriterMessage::Pong(data))=>{
+                            let header = ServerWebSocketMessageHeader::from_len(data.len(), ServerWebSocketMessageFormat::Pong, true);
+                            ServerWebSocket::build_message(header, &data)
+                        }
+                        Ok(WriterMessage::Close(code))=>{
+                            close_code = code;
+                            break
+                        }
+                        Err(RecvTimeoutError::Timeout)=>{
+                            let header = ServerWebSocketMessageHeader::from_len(0, ServerWebSocketMessageFormat::Ping, true);
+                            ServerWebSocket::build_message(header, &[])
+                        }
+                        Err(RecvTimeoutError::Disconnected)=>{
+                            break
+                        }
+                    };
+                    if output_stream.write_all(&frame).is_err(){
+                        return
                     }
                 }
-            }
-        });
-        
-        let _reader_thread = std::thread::spawn(move || {
+                // the socket is shut down only once the close frame is out
+                let _ = output_stream.write_all(&ServerWebSocket::build_close_frame(close_code, "", true));
+                let _ = output_stream.shutdown(Shutdown::Write);
+            });
+
             let mut web_socket = ServerWebSocket::new();
+            web_socket.set_permessage_deflate(permessage_deflate);
             let mut done = false;
+            let mut closed = false;
+            // whether the writer was told to send a close frame
+            let mut close_sent = false;
+            let mut parse = | data: &[u8], done: &mut bool, closed: &mut bool, close_sent: &mut bool | {
+                web_socket.parse(data, | result | {
+                    match result {
+                        Ok(ServerWebSocketMessage::Ping(data)) => {
+                            let _ = sender_pong.send(WriterMessage::Pong(data.to_vec()));
+                        },
+                        Ok(ServerWebSocketMessage::Pong(_)) => {
+                        },
+                        Ok(ServerWebSocketMessage::Text(text)) => {
+                            if rx_sender.send(WebSocketMessage::String(text.into())).is_err(){
+                                *done = true;
+                            };
+                        },
+                        Ok(ServerWebSocketMessage::Binary(data)) => {
+                            if rx_sender.send(WebSocketMessage::Binary(data.into())).is_err(){
+                                *done = true;
+                            };
+                        },
+                        Ok(ServerWebSocketMessage::Close {code, reason}) => {
+                            // echo the code back, and report anything but a normal close
+                            let code = code.unwrap_or(WEB_SOCKET_CLOSE_NORMAL);
+                            let _ = sender_pong.send(WriterMessage::Close(code));
+                            *close_sent = true;
+                            if code != WEB_SOCKET_CLOSE_NORMAL && code != WEB_SOCKET_CLOSE_GOING_AWAY {
+                                let _ = rx_sender.send(WebSocketMessage::Error(format!("Websocket closed with code {} {}", code, reason)));
+                            }
+                            let _ = rx_sender.send(WebSocketMessage::Closed);
+                            *closed = true;
+                            *done = true;
+                        },
+                        Err(e) => {
+                            let code = if let ServerWebSocketError::MessageTooLarge(_) = e {WEB_SOCKET_CLOSE_TOO_LARGE} else {WEB_SOCKET_CLOSE_PROTOCOL_ERROR};
+                            let _ = sender_pong.send(WriterMessage::Close(code));
+                            *close_sent = true;
+                            let _ = rx_sender.send(WebSocketMessage::Error(format!("Websocket error {:?}", e)));
+                            *done = true;
+                        }
+                    }
+                });
+            };
+            parse(&leftover, &mut done, &mut closed, &mut close_sent);
             while !done {
                 let mut buffer = [0u8; 65535];
-                match input_stream.read(&mut buffer) {
+                match stream.read(&mut buffer) {
+                    Ok(0) => {
+                        done = true;
+                    }
                     Ok(bytes_read) => {
-                        web_socket.parse(&buffer[0..bytes_read], | result | {
-                            match result {
-                                Ok(ServerWebSocketMessage::Ping(_)) => {
-                                    println!("ping!");
-                                    if write_bytes_to_tcp_stream_no_error(&mut input_stream, &SERVER_WEB_SOCKET_PONG_MESSAGE){
-                                        done = true;
-                                        let _ = rx_sender.send(WebSocketMessage::Error("Pong message send failed".into()));
-                                    }
-                                },
-                                Ok(ServerWebSocketMessage::Pong(_)) => {
-                                },
-                                Ok(ServerWebSocketMessage::Text(text)) => {
-                                    if rx_sender.send(WebSocketMessage::String(text.into())).is_err(){
-                                        done = true;
-                                    };
-                                    println!("text => {}", text);
-                                },
-                                Ok(ServerWebSocketMessage::Binary(data)) => {
-                                    if rx_sender.send(WebSocketMessage::Binary(data.into())).is_err(){
-                                        done = true;
-                                    };
-                                    println!("binary!");
-                                },
-                                Ok(ServerWebSocketMessage::Close) => {
-                                    let _ = rx_sender.send(WebSocketMessage::Closed);
-                                    done = true;
-                                },
-                                Err(e) => {
-                                    eprintln!("Websocket error {:?}", e);
-                                }
-                            }
-                        });                            
+                        parse(&buffer[0..bytes_read], &mut done, &mut closed, &mut close_sent);
                     }
                     Err(e) => {
-                        eprintln!("Failed to receive data: {}", e);
+                        let _ = rx_sender.send(WebSocketMessage::Error(format!("Failed to receive data: {}", e)));
+                        done = true;
                     }
                 }
             }
+            if !closed {
+                let _ = rx_sender.send(WebSocketMessage::Closed);
+            }
+            if close_sent {
+                // let the writer get the close frame out before the socket goes away
+                let _ = writer_thread.join();
+            }
+            // otherwise this makes the writer thread stop at its next write
+            let _ = stream.shutdown(Shutdown::Both);
         });
-                
+
         OsWebSocket{sender:Some(sender)}
     }
-}
\ No newline at end of file
+}
@@ -1,5 +1,6 @@
 use {
     std::cell::RefCell,
+    std::collections::HashMap,
     std::time::Instant,
     std::rc::Rc,
     self::super::opengl_x11::{
@@ -11,14 +12,15 @@ use {
         x11::xlib_event::*,
         x11::xlib_app::*,
         x11::x11_sys,
-        linux_media::CxLinuxMedia
+        linux_media::CxLinuxMedia,
+        linux_http::{make_http_request, HttpRequestCancel},
     },
     crate::{
         cx_api::{CxOsOp, CxOsApi}, 
         makepad_math::dvec2,
         makepad_live_id::*,
         thread::SignalToUI,
-        event::Event,
+        event::{Event, NetworkResponseChannel, NetworkResponse},
         pass::CxPassParent,
         cx::{Cx, OsType,LinuxWindowParams}, 
         os::cx_stdin::{PollTimers},
@@ -204,6 +206,7 @@ impl Cx {
                         self.handle_media_signals();
                         self.call_event_handler(&Event::Signal);
                     }
+                    self.handle_networking_events();
                 }
                 else{
                     self.call_event_handler(&Event::Timer(e))
@@ -220,6 +223,19 @@ impl Cx {
     }
 
     pub(crate) fn handle_networking_events(&mut self) {
+        let mut out = Vec::new();
+        while let Ok(event) = self.os.network_response.receiver.try_recv() {
+            match &event.response {
+                NetworkResponse::HttpProgress{..} | NetworkResponse::HttpStreamResponse(_) | NetworkResponse::HttpServerSentEvent(_) => (),
+                _ => {
+                    self.os.http_requests.remove(&event.request_id);
+                }
+            }
+            out.push(event);
+        }
+        if out.len()>0 {
+            self.call_event_handler(&Event::NetworkResponses(out))
+        }
     }
     
     pub (crate) fn handle_repaint(&mut self, opengl_windows: &mut Vec<OpenglWindow>) {
@@ -336,8 +352,14 @@ impl Cx {
                 },
                 CxOsOp::UpdateMacosMenu(_menu) => {
                 },
-                CxOsOp::HttpRequest{request_id:_, request:_} => {
-                    todo!()
+                CxOsOp::HttpRequest{request_id, request} => {
+                    let cancel = make_http_request(request_id, request, self.os.network_response.sender.clone());
+                    self.os.http_requests.insert(request_id, cancel);
+                },
+                CxOsOp::CancelHttpRequest{request_id} => {
+                    if let Some(cancel) = self.os.http_requests.remove(&request_id) {
+                        cancel.cancel();
+                    }
                 },
                 CxOsOp::PrepareVideoPlayback(_, _, _, _, _) => todo!(),
                 CxOsOp::BeginVideoPlayback(_) => todo!(),
@@ -380,6 +402,8 @@ pub struct CxOs {
     pub(crate) media: CxLinuxMedia,
     pub (crate) stdin_timers: PollTimers,
     pub (crate) start_time: Option<Instant>,
+    pub (crate) network_response: NetworkResponseChannel,
+    pub (crate) http_requests: HashMap<LiveId, HttpRequestCancel>,
     // HACK(eddyb) generalize this to EGL, properly.
     pub(super) opengl_cx: Option<OpenglCx>,
 }
@@ -19,6 +19,7 @@ use {
         cx_api::CxOsOp,
         cx::Cx,
         gl_sys,
+        linux_http::make_http_request,
     } 
 };
 
@@ -282,6 +283,15 @@ impl Cx {
                 CxOsOp::StopTimer(timer_id) => {
                     self.os.stdin_timers.timers.remove(&timer_id);
                 },
+                CxOsOp::HttpRequest {request_id, request} => {
+                    let cancel = make_http_request(request_id, request, self.os.network_response.sender.clone());
+                    self.os.http_requests.insert(request_id, cancel);
+                },
+                CxOsOp::CancelHttpRequest {request_id} => {
+                    if let Some(cancel) = self.os.http_requests.remove(&request_id) {
+                        cancel.cancel();
+                    }
+                },
                 _ => ()
                 /*
                 CxOsOp::CloseWindow(_window_id) => {},
@@ -162,6 +162,16 @@ pub struct FromWasmHTTPRequest {
     pub method: String,
     pub headers: String,
     pub body: WasmDataU8,
+    // in seconds, 0 means no timeout
+    pub timeout: f64,
+    // streaming requests go through fetch, and hand over the body as it arrives
+    pub is_streaming: bool,
+}
+
+#[derive(FromWasm)]
+pub struct FromWasmCancelHTTPRequest {
+    pub request_id_lo: u32,
+    pub request_id_hi: u32,
 }
 
 
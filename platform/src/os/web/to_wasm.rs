@@ -613,6 +613,30 @@ pub struct ToWasmHTTPResponse {
     pub body: WasmDataU8
 }
 
+// the head of the response to a streaming request, its body follows in ToWasmHttpStreamData pieces
+#[derive(ToWasm)]
+pub struct ToWasmHttpStreamResponse {
+    pub request_id_lo: u32,
+    pub request_id_hi: u32,
+    pub metadata_id_lo: u32,
+    pub metadata_id_hi: u32,
+    pub status: u32,
+    pub headers: String,
+}
+
+#[derive(ToWasm)]
+pub struct ToWasmHttpStreamData {
+    pub request_id_lo: u32,
+    pub request_id_hi: u32,
+    pub data: WasmDataU8
+}
+
+#[derive(ToWasm)]
+pub struct ToWasmHttpStreamComplete {
+    pub request_id_lo: u32,
+    pub request_id_hi: u32,
+}
+
 #[derive(ToWasm)]
 pub struct ToWasmHttpRequestError {
     pub request_id_lo: u32,
@@ -620,6 +644,18 @@ pub struct ToWasmHttpRequestError {
     pub error: String
 }
 
+#[derive(ToWasm)]
+pub struct ToWasmHttpRequestCancelled {
+    pub request_id_lo: u32,
+    pub request_id_hi: u32,
+}
+
+#[derive(ToWasm)]
+pub struct ToWasmHttpRequestTimedOut {
+    pub request_id_lo: u32,
+    pub request_id_hi: u32,
+}
+
 #[derive(ToWasm)]
 pub struct ToWasmHttpResponseProgress {
     pub request_id_lo: u32,
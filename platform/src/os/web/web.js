@@ -24,6 +24,7 @@ export class WasmWebBrowser extends WasmBridge {
         this.timers = [];
         this.text_copy_response = "";
         this.web_sockets = [];
+        this.http_requests = {};
         this.window_info = {}
         this.xr_capabilities = {
             vr_supported: false,
@@ -501,9 +502,21 @@ export class WasmWebBrowser extends WasmBridge {
     }
 
     FromWasmHTTPRequest(args) {
+        if (args.is_streaming) {
+            return this.http_request_streaming(args);
+        }
         const req = new XMLHttpRequest();
         req.open(args.method, args.url);
         req.responseType = "arraybuffer";
+        if (args.timeout > 0) {
+            req.timeout = args.timeout * 1000.0;
+        }
+        // kept around so the request can be aborted
+        const request_key = args.request_id_lo + "_" + args.request_id_hi;
+        this.http_requests[request_key] = req;
+        req.addEventListener("loadend", event => {
+            delete this.http_requests[request_key];
+        });
         this.parse_and_set_headers(req, args.headers);
 
         // TODO decode in appropiate format
@@ -538,19 +551,17 @@ export class WasmWebBrowser extends WasmBridge {
         });
 
         req.addEventListener("timeout", event => {
-            this.to_wasm.ToWasmHttpRequestError({
+            this.to_wasm.ToWasmHttpRequestTimedOut({
                 request_id_lo: args.request_id_lo,
                 request_id_hi: args.request_id_hi,
-                error: "The HTTP request timed out.",
             });
             this.do_wasm_pump();
         });
 
         req.addEventListener("abort", event => {
-            this.to_wasm.ToWasmHttpRequestError({
+            this.to_wasm.ToWasmHttpRequestCancelled({
                 request_id_lo: args.request_id_lo,
                 request_id_hi: args.request_id_hi,
-                error: "The HTTP request was aborted.",
             });
             this.do_wasm_pump();
         });
@@ -583,6 +594,100 @@ export class WasmWebBrowser extends WasmBridge {
         req.send(body);
         this.free_data_u8(args.body);
     }
+
+    // fetch hands over the body of the response as it arrives, unlike XMLHttpRequest
+    http_request_streaming(args) {
+        const ids = {
+            request_id_lo: args.request_id_lo,
+            request_id_hi: args.request_id_hi,
+        };
+        // kept around so the request can be aborted, like an XMLHttpRequest
+        const request_key = args.request_id_lo + "_" + args.request_id_hi;
+        const controller = new AbortController();
+        this.http_requests[request_key] = controller;
+        let timed_out = false;
+        let timer = null;
+        if (args.timeout > 0) {
+            timer = setTimeout(() => {
+                timed_out = true;
+                controller.abort();
+            }, args.timeout * 1000.0);
+        }
+        const finish = () => {
+            delete this.http_requests[request_key];
+            if (timer !== null) {
+                clearTimeout(timer);
+            }
+        };
+
+        const headers = new Headers();
+        for (let line of args.headers.split("\r\n")) {
+            let parts = line.split(": ");
+            if (parts.length == 2) {
+                headers.append(parts[0], parts[1]);
+            }
+        }
+        const has_body = args.method != "GET" && args.method != "HEAD";
+        const body = has_body ? this.clone_data_u8(args.body) : undefined;
+        this.free_data_u8(args.body);
+
+        fetch(args.url, {
+            method: args.method,
+            headers,
+            body,
+            signal: controller.signal,
+        }).then(async response => {
+            let response_headers = "";
+            response.headers.forEach((value, key) => {
+                response_headers += key + ": " + value + "\r\n";
+            });
+            this.to_wasm.ToWasmHttpStreamResponse({
+                ...ids,
+                metadata_id_lo: args.metadata_id_lo,
+                metadata_id_hi: args.metadata_id_hi,
+                status: response.status,
+                headers: response_headers,
+            });
+            this.do_wasm_pump();
+            if (response.body !== null) {
+                const reader = response.body.getReader();
+                for (;;) {
+                    const {done, value} = await reader.read();
+                    if (done) {
+                        break;
+                    }
+                    this.to_wasm.ToWasmHttpStreamData({...ids, data: value});
+                    this.do_wasm_pump();
+                }
+            }
+            finish();
+            this.to_wasm.ToWasmHttpStreamComplete(ids);
+            this.do_wasm_pump();
+        }).catch(error => {
+            finish();
+            if (timed_out) {
+                this.to_wasm.ToWasmHttpRequestTimedOut(ids);
+            }
+            else if (controller.signal.aborted) {
+                this.to_wasm.ToWasmHttpRequestCancelled(ids);
+            }
+            else {
+                this.to_wasm.ToWasmHttpRequestError({
+                    ...ids,
+                    error: navigator.onLine ? "" + error : "The browser is offline.",
+                });
+            }
+            this.do_wasm_pump();
+        });
+    }
+
+    FromWasmCancelHTTPRequest(args) {
+        // an XMLHttpRequest, or the AbortController of a streaming fetch
+        const req = this.http_requests[args.request_id_lo + "_" + args.request_id_hi];
+        if (req !== undefined) {
+            req.abort();
+        }
+    }
     
     // calling into wasm
     
@@ -3,6 +3,7 @@ use {
     std::panic,
     std::rc::Rc,
     std::cell::RefCell,
+    std::collections::HashMap,
     self::super::{
         web_media::CxWebMedia,
         from_wasm::*,
@@ -19,6 +20,7 @@ use {
             ToWasmMsgEvent,
             NetworkResponseItem,
             HttpResponse,
+            HttpStream,
             NetworkResponse,
             Event,
             XRInput,
@@ -222,25 +224,74 @@ impl Cx {
 
                 live_id!(ToWasmHTTPResponse) => {
                     let tw = ToWasmHTTPResponse::read_to_wasm(&mut to_wasm);
+                    let request_id = LiveId::from_lo_hi(tw.request_id_lo, tw.request_id_hi);
+                    let response = HttpResponse::new(
+                        LiveId::from_lo_hi(tw.metadata_id_lo, tw.metadata_id_hi),
+                        tw.status as u16,
+                        tw.headers,
+                        Some(tw.body.into_vec_u8())
+                    );
                     network_responses.push(NetworkResponseItem{
-                        request_id: LiveId::from_lo_hi(tw.request_id_lo, tw.request_id_hi),
-                        response: NetworkResponse::HttpResponse(HttpResponse::new(
-                            LiveId::from_lo_hi(tw.metadata_id_lo, tw.metadata_id_hi),
-                            tw.status as u16,
-                            tw.headers,
-                            Some(tw.body.into_vec_u8())
-                        ))
+                        request_id,
+                        response: NetworkResponse::HttpResponse(response)
                     });
                 }
 
+                live_id!(ToWasmHttpStreamResponse) => {
+                    let tw = ToWasmHttpStreamResponse::read_to_wasm(&mut to_wasm);
+                    let request_id = LiveId::from_lo_hi(tw.request_id_lo, tw.request_id_hi);
+                    let response = HttpResponse::new(
+                        LiveId::from_lo_hi(tw.metadata_id_lo, tw.metadata_id_hi),
+                        tw.status as u16,
+                        tw.headers,
+                        None
+                    );
+                    self.os.streaming_http_requests.insert(request_id, HttpStream::new(request_id, response));
+                }
+
+                live_id!(ToWasmHttpStreamData) => {
+                    let tw = ToWasmHttpStreamData::read_to_wasm(&mut to_wasm);
+                    let request_id = LiveId::from_lo_hi(tw.request_id_lo, tw.request_id_hi);
+                    if let Some(stream) = self.os.streaming_http_requests.get_mut(&request_id) {
+                        network_responses.extend(stream.data(&tw.data.into_vec_u8()));
+                    }
+                }
+
+                live_id!(ToWasmHttpStreamComplete) => {
+                    let tw = ToWasmHttpStreamComplete::read_to_wasm(&mut to_wasm);
+                    let request_id = LiveId::from_lo_hi(tw.request_id_lo, tw.request_id_hi);
+                    if let Some(stream) = self.os.streaming_http_requests.remove(&request_id) {
+                        network_responses.push(stream.complete());
+                    }
+                }
+
                 live_id!(ToWasmHttpRequestError) => {
                     let tw = ToWasmHttpRequestError::read_to_wasm(&mut to_wasm);
+                    self.os.streaming_http_requests.remove(&LiveId::from_lo_hi(tw.request_id_lo, tw.request_id_hi));
                     network_responses.push(NetworkResponseItem{
                         request_id: LiveId::from_lo_hi(tw.request_id_lo, tw.request_id_hi),
                         response: NetworkResponse::HttpRequestError(tw.error)
                     });
                 }
 
+                live_id!(ToWasmHttpRequestCancelled) => {
+                    let tw = ToWasmHttpRequestCancelled::read_to_wasm(&mut to_wasm);
+                    self.os.streaming_http_requests.remove(&LiveId::from_lo_hi(tw.request_id_lo, tw.request_id_hi));
+                    network_responses.push(NetworkResponseItem{
+                        request_id: LiveId::from_lo_hi(tw.request_id_lo, tw.request_id_hi),
+                        response: NetworkResponse::HttpRequestCancelled
+                    });
+                }
+
+                live_id!(ToWasmHttpRequestTimedOut) => {
+                    let tw = ToWasmHttpRequestTimedOut::read_to_wasm(&mut to_wasm);
+                    self.os.streaming_http_requests.remove(&LiveId::from_lo_hi(tw.request_id_lo, tw.request_id_hi));
+                    network_responses.push(NetworkResponseItem{
+                        request_id: LiveId::from_lo_hi(tw.request_id_lo, tw.request_id_hi),
+                        response: NetworkResponse::HttpRequestTimedOut
+                    });
+                }
+
                 live_id!(ToWasmHttpResponseProgress) => {
                     let tw = ToWasmHttpResponseProgress::read_to_wasm(&mut to_wasm);
                     network_responses.push(NetworkResponseItem{
@@ -470,6 +521,14 @@ impl Cx {
                         method: request.method.to_string().into(),
                         headers: headers,
                         body: WasmDataU8::from_vec_u8(request.body.unwrap_or(Vec::new())),
+                        timeout: request.timeout.unwrap_or(0.0),
+                        is_streaming: request.is_streaming,
+                    });
+                },
+                CxOsOp::CancelHttpRequest {request_id} => {
+                    self.os.from_wasm(FromWasmCancelHTTPRequest {
+                        request_id_lo: request_id.lo(),
+                        request_id_hi: request_id.hi(),
                     });
                 },
                 /*
@@ -545,7 +604,12 @@ impl CxOsApi for Cx {
             ToWasmAppGotFocus::to_js_code(),
             ToWasmAppLostFocus::to_js_code(),
             ToWasmHTTPResponse::to_js_code(),
+            ToWasmHttpStreamResponse::to_js_code(),
+            ToWasmHttpStreamData::to_js_code(),
+            ToWasmHttpStreamComplete::to_js_code(),
             ToWasmHttpRequestError::to_js_code(),
+            ToWasmHttpRequestCancelled::to_js_code(),
+            ToWasmHttpRequestTimedOut::to_js_code(),
             ToWasmHttpResponseProgress::to_js_code(),
             ToWasmHttpUploadProgress::to_js_code(),
             /*ToWasmWebSocketOpen::to_js_code(),
@@ -573,6 +637,7 @@ impl CxOsApi for Cx {
             FromWasmHideTextIME::to_js_code(),
             FromWasmCreateThread::to_js_code(),
             FromWasmHTTPRequest::to_js_code(),
+            FromWasmCancelHTTPRequest::to_js_code(),
             /*FromWasmWebSocketOpen::to_js_code(),
             FromWasmWebSocketSendString::to_js_code(),
             FromWasmWebSocketSendBinary::to_js_code(),*/
@@ -677,6 +742,8 @@ pub struct CxOs {
     pub (crate) from_wasm_js: Vec<String>,
     
     pub (crate) media: CxWebMedia,
+    // streaming requests whose response head arrived
+    pub (crate) streaming_http_requests: HashMap<LiveId, HttpStream>,
 }
 
 impl Default for CxOs{
@@ -696,6 +763,7 @@ impl Default for CxOs{
             from_wasm_js: Vec::new(),
                     
             media: CxWebMedia::default(),
+            streaming_http_requests: HashMap::new(),
         }
     }
 }
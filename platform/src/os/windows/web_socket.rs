@@ -107,7 +107,7 @@ impl OsWebSocket{
                                     };
                                     println!("binary!");
                                 },
-                                Ok(ServerWebSocketMessage::Close) => {
+                                Ok(ServerWebSocketMessage::Close {..}) => {
                                     let _ = rx_sender.send(WebSocketMessage::Closed);
                                     done = true;
                                 },
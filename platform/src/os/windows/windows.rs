@@ -284,6 +284,13 @@ impl Cx {
     }
     
     pub (crate) fn handle_networking_events(&mut self) {
+        let mut out = Vec::new();
+        while let Ok(event) = self.os.network_response.receiver.try_recv() {
+            out.push(event);
+        }
+        if out.len()>0 {
+            self.call_event_handler(&Event::NetworkResponses(out))
+        }
     }
     
     fn handle_platform_ops(&mut self, d3d11_windows: &mut Vec<D3d11Window>, d3d11_cx: &D3d11Cx) -> EventFlow {
@@ -380,8 +387,16 @@ impl Cx {
                 },
                 CxOsOp::UpdateMacosMenu(_menu) => {
                 },
-                CxOsOp::HttpRequest {request_id: _, request: _} => {
-                    todo!("HttpRequest not implemented yet on windows, we'll get there");
+                // there is no http client on windows yet, so requests fail right away
+                // instead of leaving the caller waiting for a response
+                CxOsOp::HttpRequest {request_id, request: _} => {
+                    let _ = self.os.network_response.sender.send(NetworkResponseItem {
+                        request_id,
+                        response: NetworkResponse::HttpRequestError("HttpRequest not implemented yet on windows".to_string())
+                    });
+                },
+                // requests already got their error response above, so none is ever left to cancel
+                CxOsOp::CancelHttpRequest {request_id: _} => {
                 },
                 CxOsOp::PrepareVideoPlayback(_, _, _, _, _) => todo!(),
                 CxOsOp::BeginVideoPlayback(_) => todo!(),
@@ -426,5 +441,6 @@ pub struct CxOs {
     pub (crate) start_time: Option<Instant>,
     pub (crate) media: CxWindowsMedia,
     pub (crate) d3d11_device: Option<ID3D11Device>,
+    pub (crate) network_response: NetworkResponseChannel,
    //pub (crate) new_frame_being_rendered: Option<crate::cx_stdin::PresentableDraw>,
 }
@@ -1,81 +1,115 @@
 use crate::makepad_micro_serde::*;
 use crate::log::LogLevel;
 pub use crate::makepad_live_compiler::live_node::LiveDesignInfo;
-// communication enums for studio
+// communication enums for studio, versioned so studio and apps built from
+// different revisions can still talk to each other
 
 #[derive(SerBin, DeBin, Debug)]
+#[versioned]
 pub struct EventSample{
-    pub event_u32: u32,
-    pub event_meta: u64,
-    pub start: f64,
-    pub end: f64,
+    #[id = 0] pub event_u32: u32,
+    #[id = 1] pub event_meta: u64,
+    #[id = 2] pub start: f64,
+    #[id = 3] pub end: f64,
 }
 
 #[derive(SerBin, DeBin, Debug)]
+#[versioned]
 pub struct GPUSample{
-    pub start: f64,
-    pub end: f64,
+    #[id = 0] pub start: f64,
+    #[id = 1] pub end: f64,
 }
 
 #[derive(SerBin, DeBin, Debug)]
+#[versioned]
 pub struct StudioLogItem{
-    pub file_name:String,
-    pub line_start: u32,
-    pub line_end: u32,
-    pub column_start: u32,
-    pub column_end: u32,
-    pub message: String,
-    pub level: LogLevel
+    #[id = 0] pub file_name:String,
+    #[id = 1] pub line_start: u32,
+    #[id = 2] pub line_end: u32,
+    #[id = 3] pub column_start: u32,
+    #[id = 4] pub column_end: u32,
+    #[id = 5] pub message: String,
+    #[default(LogLevel::Log)]
+    #[id = 6] pub level: LogLevel
 }
 
 #[derive(SerBin, DeBin, Debug, Clone)]
+#[versioned]
 pub struct JumpToFile{
-    pub file_name: String,
-    pub line: u32,
-    pub column: u32    
+    #[id = 0] pub file_name: String,
+    #[id = 1] pub line: u32,
+    #[id = 2] pub column: u32    
 }
 
 #[derive(SerBin, DeBin, Debug, Clone)]
+#[versioned]
 pub struct PatchFile{
-    pub file_name: String,
-    pub line: u32,
-    pub column_start: u32,
-    pub column_end: u32,
-    pub undo_group: u64,
-    pub replace: String
+    #[id = 0] pub file_name: String,
+    #[id = 1] pub line: u32,
+    #[id = 2] pub column_start: u32,
+    #[id = 3] pub column_end: u32,
+    #[id = 4] pub undo_group: u64,
+    #[id = 5] pub replace: String
 }
 
 #[derive(SerBin, DeBin, Debug, Clone)]
+#[versioned]
 pub struct EditFile{
-    pub file_name: String,
-    pub line_start: u32,
-    pub line_end: u32,
-    pub column_start: u32,
-    pub column_end: u32,
-    pub replace: String
+    #[id = 0] pub file_name: String,
+    #[id = 1] pub line_start: u32,
+    #[id = 2] pub line_end: u32,
+    #[id = 3] pub column_start: u32,
+    #[id = 4] pub column_end: u32,
+    #[id = 5] pub replace: String
 }
 
 #[derive(SerBin, DeBin, Debug)]
+#[versioned]
 pub enum AppToStudio{
-    LogItem(StudioLogItem),
-    EventSample(EventSample),
-    GPUSample(GPUSample),
-    JumpToFile(JumpToFile),
-    PatchFile(PatchFile),
-    EditFile(EditFile),
-    FocusDesign
+    #[id = 0] LogItem(StudioLogItem),
+    #[id = 1] EventSample(EventSample),
+    #[id = 2] GPUSample(GPUSample),
+    #[id = 3] JumpToFile(JumpToFile),
+    #[id = 4] PatchFile(PatchFile),
+    #[id = 5] EditFile(EditFile),
+    #[id = 6] FocusDesign
 }
 
-#[derive(SerBin, DeBin)]
+// the batches carry the length of every message, so a studio or app from an older
+// revision skips the variants it doesn't know and still reads the rest
 pub struct AppToStudioVec(pub Vec<AppToStudio>);
 
+impl SerBin for AppToStudioVec {
+    fn ser_bin(&self, s: &mut Vec<u8>) {
+        ser_bin_framed_vec(&self.0, s)
+    }
+}
+
+impl DeBin for AppToStudioVec {
+    fn de_bin(o: &mut usize, d: &[u8]) -> Result<Self, DeBinErr> {
+        Ok(Self(de_bin_framed_vec(o, d)?))
+    }
+}
+
 #[derive(SerBin, DeBin)]
+#[versioned]
 pub enum StudioToApp{
-    LiveChange{
-        file_name: String,
-        content: String
+    #[id = 0] LiveChange{
+        #[id = 0] file_name: String,
+        #[id = 1] content: String
     }
 }
 
-#[derive(SerBin, DeBin)]
 pub struct StudioToAppVec(pub Vec<StudioToApp>);
+
+impl SerBin for StudioToAppVec {
+    fn ser_bin(&self, s: &mut Vec<u8>) {
+        ser_bin_framed_vec(&self.0, s)
+    }
+}
+
+impl DeBin for StudioToAppVec {
+    fn de_bin(o: &mut usize, d: &[u8]) -> Result<Self, DeBinErr> {
+        Ok(Self(de_bin_framed_vec(o, d)?))
+    }
+}
@@ -0,0 +1,309 @@
+// runs the linux http client against a local server, so the whole network path is covered in CI
+#![cfg(target_os = "linux")]
+
+use {
+    makepad_platform::{
+        event::{HttpMethod, HttpRequest, NetworkResponse, NetworkResponseItem},
+        makepad_live_id::LiveId,
+        os::linux::linux_http::make_http_request,
+    },
+    std::{
+        fs,
+        io::{BufRead, BufReader, Read, Write},
+        net::{TcpListener, TcpStream},
+        path::PathBuf,
+        process::{Child, Command, Stdio},
+        sync::mpsc::channel,
+        thread,
+        time::Duration,
+    },
+};
+
+// a request as the server received it
+struct ServerRequest {
+    head: String,
+    body: Vec<u8>,
+}
+
+impl ServerRequest {
+    fn request_line(&self) -> &str {
+        self.head.lines().next().unwrap_or("")
+    }
+}
+
+// serves `count` connections, answering each one with the raw bytes `respond` returns
+fn serve<F>(count: usize, respond: F) -> (String, thread::JoinHandle<Vec<ServerRequest>>)
+where F: Fn(usize, &ServerRequest) -> Vec<u8> + Send + 'static {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+    let handle = thread::spawn(move || {
+        let mut requests = Vec::new();
+        for index in 0..count {
+            let (stream, _) = listener.accept().unwrap();
+            let request = read_request(&stream);
+            let response = respond(index, &request);
+            let mut stream = stream;
+            if !response.is_empty() {
+                stream.write_all(&response).unwrap();
+            }
+            else {
+                // never answer, so the client has to time out
+                thread::sleep(Duration::from_millis(1000));
+            }
+            requests.push(request);
+        }
+        requests
+    });
+    (url, handle)
+}
+
+fn read_request(stream: &TcpStream) -> ServerRequest {
+    let mut reader = BufReader::new(stream);
+    let mut head = String::new();
+    loop {
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        head.push_str(&line);
+        if line == "\r\n" || line.is_empty() {
+            break;
+        }
+    }
+    let content_length = head.lines()
+        .filter_map( | line | line.split_once(':'))
+        .find( | (key, _) | key.eq_ignore_ascii_case("Content-Length"))
+        .map_or(0, | (_, value) | value.trim().parse().unwrap());
+    let mut body = vec![0u8; content_length];
+    reader.read_exact(&mut body).unwrap();
+    ServerRequest {head, body}
+}
+
+// sends the request, and collects everything the client reports until its final response
+fn request(request: HttpRequest) -> Vec<NetworkResponse> {
+    let (sender, receiver) = channel::<NetworkResponseItem>();
+    make_http_request(LiveId(1), request, sender);
+    let mut responses = Vec::new();
+    loop {
+        let item = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+        assert_eq!(item.request_id, LiveId(1));
+        let is_final = !matches!(item.response, NetworkResponse::HttpProgress {..} | NetworkResponse::HttpStreamResponse(_) | NetworkResponse::HttpServerSentEvent(_));
+        responses.push(item.response);
+        if is_final {
+            return responses
+        }
+    }
+}
+
+fn final_body(responses: &[NetworkResponse]) -> (u16, String) {
+    match responses.last() {
+        Some(NetworkResponse::HttpResponse(response)) => (response.status_code, response.get_string_body().unwrap()),
+        other => panic!("expected a response, got {:?}", other)
+    }
+}
+
+#[test]
+fn sends_method_headers_and_body() {
+    let (url, server) = serve(1, | _, _ | {
+        b"HTTP/1.1 201 Created\r\nContent-Length: 5\r\nX-Reply: yes\r\n\r\nhello".to_vec()
+    });
+    let mut http_request = HttpRequest::new(format!("{}/api/items?x=1", url), HttpMethod::POST);
+    http_request.set_metadata_id(LiveId(42));
+    http_request.set_header("X-Test".to_string(), "value".to_string());
+    http_request.set_body_string("payload");
+    let responses = request(http_request);
+
+    let Some(NetworkResponse::HttpResponse(response)) = responses.last() else {
+        panic!("expected a response, got {:?}", responses.last())
+    };
+    assert_eq!(response.status_code, 201);
+    assert_eq!(response.metadata_id, LiveId(42));
+    assert_eq!(response.headers.get("X-Reply"), Some(&vec!["yes".to_string()]));
+    assert_eq!(response.get_string_body().unwrap(), "hello");
+    assert!(responses.iter().any( | response | matches!(response, NetworkResponse::HttpProgress {loaded: 5, total: 5})));
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests[0].request_line(), "POST /api/items?x=1 HTTP/1.1");
+    assert!(requests[0].head.contains("X-Test: value\r\n"));
+    assert_eq!(requests[0].body, b"payload");
+}
+
+#[test]
+fn reads_chunked_bodies() {
+    let (url, server) = serve(1, | _, _ | {
+        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5;ext=1\r\nhello\r\n7\r\n, world\r\n0\r\nTrailer: x\r\n\r\n".to_vec()
+    });
+    let responses = request(HttpRequest::new(url, HttpMethod::GET));
+    assert_eq!(final_body(&responses), (200, "hello, world".to_string()));
+    server.join().unwrap();
+}
+
+#[test]
+fn streams_chunked_bodies() {
+    let (url, server) = serve(1, | _, _ | {
+        b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n3\r\ndef\r\n0\r\n\r\n".to_vec()
+    });
+    let mut http_request = HttpRequest::new(url, HttpMethod::GET);
+    http_request.set_is_streaming();
+    let responses = request(http_request);
+    let chunks: Vec<String> = responses.iter().filter_map( | response | match response {
+        NetworkResponse::HttpStreamResponse(response) => response.get_string_body(),
+        _ => None
+    }).collect();
+    assert_eq!(chunks, ["abc", "def"]);
+    assert!(matches!(responses.last(), Some(NetworkResponse::HttpStreamComplete(_))));
+    server.join().unwrap();
+}
+
+#[test]
+fn parses_event_streams() {
+    let (url, server) = serve(1, | _, _ | {
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nTransfer-Encoding: chunked\r\n\r\n9\r\ndata: one\r\n8\r\n\n\ndata: \r\n5\r\ntwo\n\n\r\n0\r\n\r\n".to_vec()
+    });
+    let mut http_request = HttpRequest::new(url, HttpMethod::GET);
+    http_request.set_is_streaming();
+    let responses = request(http_request);
+    let events: Vec<String> = responses.iter().filter_map( | response | match response {
+        NetworkResponse::HttpServerSentEvent(event) => Some(event.data.clone()),
+        _ => None
+    }).collect();
+    assert_eq!(events, ["one", "two"]);
+    assert!(matches!(responses.last(), Some(NetworkResponse::HttpStreamComplete(_))));
+    server.join().unwrap();
+}
+
+#[test]
+fn follows_redirects() {
+    let (url, server) = serve(3, | index, _ | match index {
+        0 => b"HTTP/1.1 301 Moved Permanently\r\nLocation: /moved/here\r\nContent-Length: 0\r\n\r\n".to_vec(),
+        1 => b"HTTP/1.1 303 See Other\r\nLocation: final\r\nContent-Length: 0\r\n\r\n".to_vec(),
+        _ => b"HTTP/1.1 200 OK\r\nContent-Length: 4\r\n\r\ndone".to_vec(),
+    });
+    let mut http_request = HttpRequest::new(format!("{}/start", url), HttpMethod::PUT);
+    http_request.set_body_string("data");
+    let responses = request(http_request);
+    assert_eq!(final_body(&responses), (200, "done".to_string()));
+
+    let requests = server.join().unwrap();
+    // a 301 repeats a PUT as is, a 303 turns it into a GET without a body
+    assert_eq!(requests[0].request_line(), "PUT /start HTTP/1.1");
+    assert_eq!(requests[1].request_line(), "PUT /moved/here HTTP/1.1");
+    assert_eq!(requests[1].body, b"data");
+    assert_eq!(requests[2].request_line(), "GET /moved/final HTTP/1.1");
+    assert!(requests[2].body.is_empty());
+}
+
+#[test]
+fn gives_up_on_redirect_loops() {
+    let (url, server) = serve(11, | _, _ | {
+        b"HTTP/1.1 302 Found\r\nLocation: /again\r\nContent-Length: 0\r\n\r\n".to_vec()
+    });
+    let responses = request(HttpRequest::new(url, HttpMethod::GET));
+    assert!(matches!(responses.last(), Some(NetworkResponse::HttpRequestError(_))));
+    server.join().unwrap();
+}
+
+#[test]
+fn times_out() {
+    let (url, server) = serve(1, | _, _ | Vec::new());
+    let mut http_request = HttpRequest::new(url, HttpMethod::GET);
+    http_request.set_timeout(0.2);
+    let responses = request(http_request);
+    assert!(matches!(responses.last(), Some(NetworkResponse::HttpRequestTimedOut)));
+    server.join().unwrap();
+}
+
+#[test]
+fn reports_errors() {
+    // nothing listens on a port that was just released
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://127.0.0.1:{}", listener.local_addr().unwrap().port());
+    drop(listener);
+    let responses = request(HttpRequest::new(url, HttpMethod::GET));
+    assert!(matches!(responses.last(), Some(NetworkResponse::HttpRequestError(_))));
+
+    let (url, server) = serve(1, | _, _ | b"garbage\r\n\r\n".to_vec());
+    let responses = request(HttpRequest::new(url, HttpMethod::GET));
+    assert!(matches!(responses.last(), Some(NetworkResponse::HttpRequestError(_))));
+    server.join().unwrap();
+
+    let (url, server) = serve(1, | _, _ | b"HTTP/1.1 200 OK\r\nContent-Length: 10\r\n\r\nshort".to_vec());
+    let responses = request(HttpRequest::new(url, HttpMethod::GET));
+    assert!(matches!(responses.last(), Some(NetworkResponse::HttpRequestError(_))));
+    server.join().unwrap();
+}
+
+// serves the files of a temporary directory over https with `openssl s_server` and a self signed certificate
+struct TlsServer {
+    child: Child,
+    dir: PathBuf,
+    url: String,
+}
+
+impl TlsServer {
+    // None when the openssl command line tool is not installed
+    fn start(files: &[(&str, &str)]) -> Option<Self> {
+        let dir = std::env::temp_dir().join(format!("makepad_linux_http_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for (name, content) in files {
+            fs::write(dir.join(name), content).unwrap();
+        }
+        let status = Command::new("openssl")
+            .args(["req", "-x509", "-newkey", "ec", "-pkeyopt", "ec_paramgen_curve:prime256v1", "-nodes"])
+            .args(["-keyout", "key.pem", "-out", "cert.pem", "-days", "1", "-subj", "/CN=localhost"])
+            .current_dir(&dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        if !status.is_ok_and(| status | status.success()) {
+            let _ = fs::remove_dir_all(&dir);
+            return None
+        }
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let mut child = Command::new("openssl")
+            .args(["s_server", "-accept", &format!("127.0.0.1:{}", port), "-cert", "cert.pem", "-key", "key.pem", "-WWW"])
+            .current_dir(&dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+        // it is listening once it says so, after that its output is just drained
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut line = String::new();
+        while !line.starts_with("ACCEPT") {
+            line.clear();
+            if stdout.read_line(&mut line).unwrap() == 0 {
+                panic!("openssl s_server exited before accepting connections")
+            }
+        }
+        thread::spawn(move || std::io::copy(&mut stdout, &mut std::io::sink()));
+        Some(Self {child, dir, url: format!("https://localhost:{}", port)})
+    }
+}
+
+impl Drop for TlsServer {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+#[test]
+fn requests_over_tls() {
+    let Some(server) = TlsServer::start(&[("hello.txt", "hello over tls")]) else {
+        eprintln!("skipping requests_over_tls, openssl is not installed");
+        return
+    };
+
+    let mut http_request = HttpRequest::new(format!("{}/hello.txt", server.url), HttpMethod::GET);
+    http_request.set_ignore_ssl_cert();
+    let responses = request(http_request);
+    assert_eq!(final_body(&responses), (200, "hello over tls".to_string()));
+
+    // the self signed certificate is only accepted when asked to
+    let responses = request(HttpRequest::new(format!("{}/hello.txt", server.url), HttpMethod::GET));
+    match responses.last() {
+        Some(NetworkResponse::HttpRequestError(error)) => assert!(error.contains("not trusted"), "{}", error),
+        other => panic!("expected a certificate error, got {:?}", other)
+    }
+}
@@ -0,0 +1,39 @@
+use makepad_platform::{ServerSentEvent, ServerSentEventParser};
+
+fn parse_chunks(chunks: &[&[u8]]) -> Vec<ServerSentEvent> {
+    let mut parser = ServerSentEventParser::default();
+    chunks.iter().flat_map( | chunk | parser.parse(chunk)).collect()
+}
+
+#[test]
+fn joins_multi_line_data() {
+    let events = parse_chunks(&[b"event: update\ndata: first\ndata:second\nid: 7\nretry: 1000\n\n"]);
+    assert_eq!(events, [ServerSentEvent {
+        event: "update".to_string(),
+        data: "first\nsecond".to_string(),
+        id: Some("7".to_string()),
+        retry: Some(1000),
+    }]);
+}
+
+#[test]
+fn skips_comments_and_empty_events() {
+    let events = parse_chunks(&[b": keep alive\n\nevent: ignored\n\ndata: x\n\n"]);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].event, "message");
+    assert_eq!(events[0].data, "x");
+}
+
+#[test]
+fn handles_crlf_split_across_chunks() {
+    let events = parse_chunks(&[b"data: a\r", b"\ndata: b\r", b"\n\r", b"\ndata: c\r\r\n"]);
+    let data: Vec<&str> = events.iter().map( | event | event.data.as_str()).collect();
+    assert_eq!(data, ["a\nb", "c"]);
+}
+
+#[test]
+fn handles_lines_split_across_chunks() {
+    let events = parse_chunks(&[b"da", b"ta: hel", b"lo\n", b"\n"]);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].data, "hello");
+}
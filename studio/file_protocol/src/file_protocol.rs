@@ -29,16 +29,17 @@ use {
  
 /// A type for representing a request to the collab server.
 #[derive(Clone, Debug, SerBin, DeBin)]
+#[versioned]
 pub enum FileRequest {
     /// Requests the collab server to return its file tree. 
-    LoadFileTree{ with_data: bool },
+    #[id = 0] LoadFileTree{ #[id = 0] with_data: bool },
     /// Requests the collab server to add the client as a participant to the file with the given id.
     /// If the client is the first participant for the file, this also causes the file to be opened
     /// on the server.
-    OpenFile(String, u64),
+    #[id = 1] OpenFile(String, u64),
     /// Requests the collab server to apply the given delta to the given revision of the file with
     /// the given id.
-    SaveFile(String, String, u64, bool),
+    #[id = 2] SaveFile(String, String, u64, bool),
 
 }
 
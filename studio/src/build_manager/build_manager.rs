@@ -495,6 +495,7 @@ impl BuildManager {
         start_http_server(HttpServer {
             listen_address: addr,
             post_max_size: 1024 * 1024,
+            web_socket_max_message_size: WEB_SOCKET_DEFAULT_MAX_MESSAGE_SIZE,
             request: tx_request
         });
         /*
@@ -569,12 +570,12 @@ impl BuildManager {
                             let header = "HTTP/1.1 200 OK\r\n\
                                 Cache-Control: max-age:0\r\n\
                                 Connection: close\r\n\r\n".to_string();
-                            let _ = response_sender.send(HttpServerResponse {header, body: vec![]});
+                            let _ = response_sender.send(HttpServerResponse {header, body: vec![], file: None});
                             continue
                         }
                         if path == "/favicon.ico" {
                             let header = "HTTP/1.1 200 OK\r\n\r\n".to_string();
-                            let _ = response_sender.send(HttpServerResponse {header, body: vec![]});
+                            let _ = response_sender.send(HttpServerResponse {header, body: vec![], file: None});
                             continue
                         }
                         
@@ -615,13 +616,17 @@ impl BuildManager {
                                         mime_type,
                                         body.len()
                                     );
-                                    let _ = response_sender.send(HttpServerResponse {header, body});
+                                    let _ = response_sender.send(HttpServerResponse {header, body, file: None});
                                 }
                             }
                         }
                         
                     }
-                    HttpServerRequest::Post {..} => { //headers, body, response}=>{
+                    HttpServerRequest::Post {response, ..} => {
+                        let _ = response.send(HttpServerResponse::method_not_allowed("GET"));
+                    }
+                    HttpServerRequest::Request {response_sender, ..} => {
+                        let _ = response_sender.send(HttpServerResponse::method_not_allowed("GET"));
                     }
                 }
             }
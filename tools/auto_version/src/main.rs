@@ -1,7 +1,7 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use makepad_toml_parser::Toml;
+use makepad_toml_parser::{Toml, TomlEditor};
 use makepad_digest::sha1;
 use makepad_base64::base64;
 use std::io::prelude::*;
@@ -178,20 +178,11 @@ fn main() {
 
 fn patch_cargo(cargo: &Path, toml_path: &str, with: &str, write: bool) {
     let old_cargo = fs::read_to_string(cargo).unwrap();
-    let toml = makepad_toml_parser::parse_toml(&old_cargo).unwrap();
+    let mut editor = TomlEditor::new(&old_cargo).unwrap();
     
-    if let Some(Toml::Str(_, span)) = toml.get(toml_path) {
-        let mut new_cargo = String::new();
-        for (i, c) in old_cargo.chars().enumerate() {
-            if i == span.start {
-                for c in with.chars() {
-                    new_cargo.push(c);
-                }
-            }
-            if i < span.start || i >= span.start + span.len - 2 {
-                new_cargo.push(c);
-            }
-        }
+    if let Some(Toml::Str(..)) = editor.get(toml_path) {
+        editor.set(toml_path, with.into()).unwrap();
+        let new_cargo = editor.into_string();
         // lets write it back to disk
         if write {
             fs::File::create(cargo).unwrap().write_all(new_cargo.as_bytes()).unwrap();
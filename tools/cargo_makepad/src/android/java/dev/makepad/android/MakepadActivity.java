@@ -398,10 +398,29 @@ MidiManager.OnDeviceOpenedListener{
         return appName.toString();
     }
 
-    public void requestHttp(long id, long metadataId, String url, String method, String headers, byte[] body) {
+    public void requestHttp(long id, long metadataId, String url, String method, String headers, byte[] body, boolean streaming) {
         try {
             MakepadNetwork network = new MakepadNetwork();
 
+            if (streaming) {
+                // the callbacks come from the network thread, posting them keeps their order
+                network.performStreamingHttpRequest(url, method, headers, body, new MakepadNetwork.StreamListener() {
+                    public void onResponse(int statusCode, String responseHeaders) {
+                        runOnUiThread(() -> MakepadNative.onHttpStreamResponse(id, metadataId, statusCode, responseHeaders));
+                    }
+                    public void onData(byte[] data) {
+                        runOnUiThread(() -> MakepadNative.onHttpStreamData(id, data));
+                    }
+                    public void onComplete() {
+                        runOnUiThread(() -> MakepadNative.onHttpStreamComplete(id));
+                    }
+                    public void onError(Exception e) {
+                        runOnUiThread(() -> MakepadNative.onHttpRequestError(id, metadataId, e.toString()));
+                    }
+                });
+                return;
+            }
+
             CompletableFuture<HttpResponse> future = network.performHttpRequest(url, method, headers, body);
 
             future.thenAccept(response -> {
@@ -31,6 +31,9 @@ public class MakepadNative {
     // networking
     public native static void onHttpResponse(long id, long metadata_id, int status_code, String headers, byte[] body);
     public native static void onHttpRequestError(long id, long metadata_id, String error);
+    public native static void onHttpStreamResponse(long id, long metadata_id, int status_code, String headers);
+    public native static void onHttpStreamData(long id, byte[] data);
+    public native static void onHttpStreamComplete(long id);
     public native static void onWebSocketMessage(byte[] message, long callback);
     public native static void onWebSocketClosed(long callback);
     public native static void onWebSocketError(String error, long callback);
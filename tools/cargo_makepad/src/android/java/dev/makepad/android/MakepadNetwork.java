@@ -8,6 +8,7 @@ import java.io.InputStream;
 import java.io.OutputStream;
 import java.net.HttpURLConnection;
 import java.net.URL;
+import java.util.Arrays;
 import java.util.concurrent.CompletableFuture;
 import java.util.concurrent.ExecutionException;
 import java.util.Map;
@@ -42,36 +43,56 @@ class HttpResponse {
 
 public class MakepadNetwork {
 
+    // receives the response to a streaming request piece by piece, on the network thread
+    public interface StreamListener {
+        void onResponse(int statusCode, String headers);
+        void onData(byte[] data);
+        void onComplete();
+        void onError(Exception e);
+    }
+
     public MakepadNetwork() {
     }
 
+    public void performStreamingHttpRequest(String url, String method, String headers, byte[] body, StreamListener listener) {
+        CompletableFuture.runAsync(() -> {
+            HttpURLConnection connection = null;
+            try {
+                connection = openConnection(url, method, headers, body);
+
+                int statusCode = connection.getResponseCode();
+                listener.onResponse(statusCode, getHeadersAsString(connection.getHeaderFields()));
+
+                InputStream inputStream = statusCode >= 400 ? connection.getErrorStream() : connection.getInputStream();
+                if (inputStream != null) {
+                    try {
+                        byte[] buffer = new byte[4096];
+                        int bytesRead;
+                        while ((bytesRead = inputStream.read(buffer)) != -1) {
+                            listener.onData(Arrays.copyOf(buffer, bytesRead));
+                        }
+                    } finally {
+                        inputStream.close();
+                    }
+                }
+                listener.onComplete();
+            } catch (IOException e) {
+                listener.onError(e);
+            } finally {
+                if (connection != null) {
+                    connection.disconnect();
+                }
+            }
+        });
+    }
+
     public CompletableFuture<HttpResponse> performHttpRequest(String url, String method, String headers, byte[] body) {
         return CompletableFuture.supplyAsync(() -> {
             HttpURLConnection connection = null;
             HttpResponse response = null;
 
             try {
-                URL urlObj = new URL(url);
-                connection = (HttpURLConnection) urlObj.openConnection();
-                connection.setRequestMethod(method);
-
-                String[] headerPairs = headers.split("\r\n");
-
-                for (String headerPair : headerPairs) {
-                    String[] parts = headerPair.split(":");
-                    if (parts.length == 2) {
-                        String key = parts[0].trim();
-                        String value = parts[1].trim();
-                        connection.setRequestProperty(key, value);
-                    }
-                }
-
-                if (body != null) {
-                    connection.setDoOutput(true);
-                    try (OutputStream outputStream = connection.getOutputStream()) {
-                        outputStream.write(body);
-                    }
-                }
+                connection = openConnection(url, method, headers, body);
 
                 int statusCode = connection.getResponseCode();
 
@@ -97,6 +118,31 @@ public class MakepadNetwork {
         });
     }
 
+    private HttpURLConnection openConnection(String url, String method, String headers, byte[] body) throws IOException {
+        URL urlObj = new URL(url);
+        HttpURLConnection connection = (HttpURLConnection) urlObj.openConnection();
+        connection.setRequestMethod(method);
+
+        String[] headerPairs = headers.split("\r\n");
+
+        for (String headerPair : headerPairs) {
+            String[] parts = headerPair.split(":");
+            if (parts.length == 2) {
+                String key = parts[0].trim();
+                String value = parts[1].trim();
+                connection.setRequestProperty(key, value);
+            }
+        }
+
+        if (body != null) {
+            connection.setDoOutput(true);
+            try (OutputStream outputStream = connection.getOutputStream()) {
+                outputStream.write(body);
+            }
+        }
+        return connection;
+    }
+
     private byte[] readBytesFromStream(InputStream inputStream) throws IOException {
         ByteArrayOutputStream outputStream = new ByteArrayOutputStream();
         byte[] buffer = new byte[4096];
@@ -3,8 +3,6 @@ use makepad_http::server::*;
 use std::{
     net::SocketAddr,
     sync::mpsc,
-    io::prelude::*,
-    fs::File,
 };
 
 fn main() {
@@ -25,6 +23,7 @@ fn main() {
     start_http_server(HttpServer{
         listen_address:addr,
         post_max_size: 1024*1024,
+        web_socket_max_message_size: WEB_SOCKET_DEFAULT_MAX_MESSAGE_SIZE,
         request: tx_request
     });
     println!("Server listening on {}", addr);
@@ -35,13 +34,18 @@ fn main() {
     
     
     let abs_makepad_path = std::env::current_dir().unwrap().join(makepad_path.clone()).canonicalize().unwrap().to_str().unwrap().to_string();
+    let headers = "Cross-Origin-Embedder-Policy: require-corp\r\n\
+        Cross-Origin-Opener-Policy: same-origin\r\n\
+        Cache-Control: max-age:0\r\n";
+    // everything else in the makepad directory, like .git or keys, stays private
+    let extensions = ["html", "wasm", "css", "js", "ttf", "png", "jpg", "svg"];
     let remaps = [
         (format!("/makepad/{}/",abs_makepad_path),makepad_path.clone()),
-        (format!("/makepad/{}/",std::env::current_dir().unwrap().display()),"".to_string()),
+        (format!("/makepad/{}/",std::env::current_dir().unwrap().display()),".".to_string()),
         ("/makepad//".to_string(),makepad_path.clone()),
         ("/makepad/".to_string(),makepad_path.clone()),
-        ("/".to_string(),"".to_string())
-    ];
+        ("/".to_string(),".".to_string())
+    ].map(|(prefix, dir)| (prefix, HttpStaticFiles::new(dir).with_headers(headers).with_extensions(&extensions)));
     while let Ok(message) = rx_request.recv() {
         match message{
             HttpServerRequest::ConnectWebSocket {web_socket_id:_, response_sender:_, headers:_}=>{
@@ -61,59 +65,28 @@ fn main() {
                     let header = "HTTP/1.1 200 OK\r\n\
                             Cache-Control: max-age:0\r\n\
                             Connection: close\r\n\r\n".to_string();
-                    let _ = response_sender.send(HttpServerResponse{header, body:vec![]});
+                    let _ = response_sender.send(HttpServerResponse{header, body:vec![], file:None});
                     continue
                 }
                 
                 if path == "/favicon.ico"{
                     let header = "HTTP/1.1 200 OK\r\n\r\n".to_string();
-                    let _ = response_sender.send(HttpServerResponse{header, body:vec![]});
+                    let _ = response_sender.send(HttpServerResponse{header, body:vec![], file:None});
                     continue
                 }
                 
-                let mime_type = if path.ends_with(".html") {"text/html"}
-                else if path.ends_with(".wasm") {"application/wasm"}
-                else if path.ends_with(".css") {"text/css"}
-                else if path.ends_with(".js") {"text/javascript"}
-                else if path.ends_with(".ttf") {"application/ttf"}
-                else if path.ends_with(".png") {"image/png"}
-                else if path.ends_with(".jpg") {"image/jpg"}
-                else if path.ends_with(".svg") {"image/svg+xml"}
-                else {continue};
-
-                if path.contains("..") || path.contains('\\'){
-                    continue
-                }
-                
-                let mut strip = None;
-                for remap in &remaps{
-                    if let Some(s) = path.strip_prefix(&remap.0){
-                        strip = Some(format!("{}{}",remap.1, s));
+                for (prefix, static_files) in &remaps{
+                    if let Some(s) = path.strip_prefix(prefix){
+                        let _ = response_sender.send(static_files.serve_path(s, &headers));
                         break;
                     }
                 }
-                if let Some(base) = strip{
-                    if let Ok(mut file_handle) = File::open(base) {
-                        let mut body = Vec::<u8>::new();
-                        if file_handle.read_to_end(&mut body).is_ok() {
-                            let header = format!(
-                                "HTTP/1.1 200 OK\r\n\
-                                Content-Type: {}\r\n\
-                                Cross-Origin-Embedder-Policy: require-corp\r\n\
-                                Cross-Origin-Opener-Policy: same-origin\r\n\
-                                Content-encoding: none\r\n\
-                                Cache-Control: max-age:0\r\n\
-                                Content-Length: {}\r\n\
-                                Connection: close\r\n\r\n",
-                                mime_type,
-                                body.len()
-                            );
-                            let _ = response_sender.send(HttpServerResponse{header, body});
-                        }
-                    }
-                }
             }
-            HttpServerRequest::Post{..}=>{//headers, body, response}=>{
+            HttpServerRequest::Post{response, ..}=>{
+                let _ = response.send(HttpServerResponse::method_not_allowed("GET"));
+            }
+            HttpServerRequest::Request{response_sender, ..}=>{
+                let _ = response_sender.send(HttpServerResponse::method_not_allowed("GET"));
             }
         }
     }
@@ -63,6 +63,13 @@ pub struct Html {
 
     /// The stack of list levels encountered so far, used to track nested lists.
     #[rust] list_stack: Vec<ListLevel>,
+
+    /// CSS applied underneath the `<style>` blocks and `style` attributes of the body.
+    #[live] style_sheet: String,
+    /// The style of every element of `doc`, indexed like its nodes.
+    #[rust] styles: Vec<Option<CssStyle>>,
+    /// The styles of the currently open elements, popped again at their close tags.
+    #[rust] css_stack: Vec<Option<CssStyle>>,
 }
 
 // alright lets parse the HTML
@@ -74,6 +81,7 @@ impl LiveHook for Html {
             self.doc = new_doc;
             self.text_flow.clear_items();
         }
+        self.styles = self.doc.compute_styles(&CssStyleSheet::parse(&self.style_sheet));
         if errors.as_ref().unwrap().len()>0{
             log!("HTML parser returned errors {:?}", errors)
         }
@@ -311,6 +319,77 @@ impl Html {
         None
     }
     
+    // css lengths are scaled so that 16px is the font size of the text flow
+    fn css_px(tf: &TextFlow, length: CssLength) -> f64 {
+        let font_size = *tf.font_sizes.last().unwrap_or(&tf.font_size);
+        length.to_px(font_size / tf.font_size * CssLength::ROOT_FONT_PX) * tf.font_size / CssLength::ROOT_FONT_PX
+    }
+
+    fn css_vertical_margin(cx: &mut Cx2d, tf: &TextFlow, margin: Option<CssLength>) {
+        if let Some(margin) = margin {
+            cx.turtle_new_line();
+            cx.walk_turtle(Walk::size(Size::Fixed(0.0), Size::Fixed(Self::css_px(tf, margin))));
+            cx.turtle_new_line();
+        }
+    }
+
+    fn css_horizontal_margin(cx: &mut Cx2d, tf: &TextFlow, margin: Option<CssLength>) {
+        if let Some(margin) = margin {
+            cx.walk_turtle(Walk::size(Size::Fixed(Self::css_px(tf, margin)), Size::Fixed(0.0)));
+        }
+    }
+
+    /// Applies the style of an element on top of what its tag already did.
+    fn push_css(cx: &mut Cx2d, tf: &mut TextFlow, style: &CssStyle) {
+        Self::css_vertical_margin(cx, tf, style.margin_top);
+        Self::css_horizontal_margin(cx, tf, style.margin_left);
+        if let Some(color) = style.color {
+            tf.font_colors.push(Vec4::from_u32(color));
+        }
+        match style.font_size {
+            Some(CssLength::Px(px)) => tf.push_size_abs_scale(px / CssLength::ROOT_FONT_PX),
+            Some(CssLength::Em(em)) => tf.push_size_rel_scale(em),
+            Some(CssLength::Rem(rem)) => tf.push_size_abs_scale(rem),
+            None => ()
+        }
+        if style.font_weight.is_some_and(|w| w >= 600) {
+            tf.bold.push();
+        }
+        if style.italic == Some(true) {
+            tf.italic.push();
+        }
+        if style.underline == Some(true) {
+            tf.underline.push();
+        }
+        if style.strikethrough == Some(true) {
+            tf.strikethrough.push();
+        }
+    }
+
+    /// Undoes `push_css` at the close tag of the element.
+    fn pop_css(cx: &mut Cx2d, tf: &mut TextFlow, style: &CssStyle) {
+        if style.strikethrough == Some(true) {
+            tf.strikethrough.pop();
+        }
+        if style.underline == Some(true) {
+            tf.underline.pop();
+        }
+        if style.italic == Some(true) {
+            tf.italic.pop();
+        }
+        if style.font_weight.is_some_and(|w| w >= 600) {
+            tf.bold.pop();
+        }
+        if style.font_size.is_some() {
+            tf.font_sizes.pop();
+        }
+        if style.color.is_some() {
+            tf.font_colors.pop();
+        }
+        Self::css_horizontal_margin(cx, tf, style.margin_right);
+        Self::css_vertical_margin(cx, tf, style.margin_bottom);
+    }
+
     pub fn handle_text_node(
         cx: &mut Cx2d,
         tf: &mut TextFlow,
@@ -344,14 +423,33 @@ impl Widget for Html {
         // alright lets iterate the html doc and draw it
         let mut node = self.doc.new_walker();
         let mut auto_id = 0;
+        self.css_stack.clear();
         while !node.done() {
-            let mut trim = TrimWhitespaceInText::default();
-            match Self::handle_open_tag(cx, tf, &mut node, &mut self.list_stack, &self.ul_markers, &self.ol_markers, &self.ol_separator) {
-                (Some(_), _tws) => {
-                    handle_custom_widget(cx, scope, tf, &self.doc, &mut node, &mut auto_id); 
+            let style = self.styles.get(node.index()).cloned().flatten();
+            if let Some(lc) = node.open_tag_lc() {
+                // skip elements that aren't displayed, together with their content
+                if style.as_ref().is_some_and(|s| s.display_none == Some(true))
+                    || matches!(lc, live_id!(head) | live_id!(style) | live_id!(script) | live_id!(title)) {
+                    if !is_void_element(lc) {
+                        node.jump_to_close();
+                    }
+                    node.walk();
+                    continue;
+                }
+            }
+            let (custom, trim) = Self::handle_open_tag(cx, tf, &mut node, &mut self.list_stack, &self.ul_markers, &self.ol_markers, &self.ol_separator);
+            if node.open_tag_lc().is_some_and(|lc| !is_void_element(lc)) {
+                if let Some(style) = &style {
+                    Self::push_css(cx, tf, style);
                 }
-                (None, tws) => {
-                    trim = tws;
+                self.css_stack.push(style);
+            }
+            if custom.is_some() {
+                handle_custom_widget(cx, scope, tf, &self.doc, &mut node, &mut auto_id);
+            }
+            if node.close_tag_lc().is_some_and(|lc| !is_void_element(lc)) {
+                if let Some(Some(style)) = self.css_stack.pop() {
+                    Self::pop_css(cx, tf, &style);
                 }
             }
             match Self::handle_close_tag(cx, tf, &mut node, &mut self.list_stack) {
@@ -372,6 +470,7 @@ impl Widget for Html {
         self.body = Rc::new(v.to_string());
         let mut errors = Some(Vec::new());
         self.doc = parse_html(&*self.body, &mut errors);
+        self.styles = self.doc.compute_styles(&CssStyleSheet::parse(&self.style_sheet));
         if errors.as_ref().unwrap().len()>0{
             log!("HTML parser returned errors {:?}", errors)
         }
@@ -399,16 +498,23 @@ fn handle_custom_widget(
     let mut scope_with_attrs = Scope::with_props_index(doc, node.index);
     // log!("FOUND CUSTOM WIDGET! template: {template:?}, id: {id:?}, attrs: {attrs:?}");
 
+    // tags without a template, like div or span, just draw their content
     if let Some(item) = tf.item_with_scope(cx, &mut scope_with_attrs, id, template) {
         item.set_text(node.find_text().unwrap_or(""));
         item.draw_all(cx, scope);
+        node.jump_to_close();
     }
-
-    node.jump_to_close();
 }
 
 
 
+impl HtmlRef {
+    pub fn selected_text(&self) -> String {
+        let Some(inner) = self.borrow() else { return String::new() };
+        inner.selected_text()
+    }
+}
+
 #[derive(Live, Widget)]
 struct HtmlLink {
     #[deref] link: LinkLabel,
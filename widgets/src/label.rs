@@ -2,7 +2,8 @@ use {
     crate::{
         makepad_derive_widget::*,
         makepad_draw::*,
-        widget::*
+        widget::*,
+        text_selection::TextSelection,
     }
 };
 
@@ -18,12 +19,42 @@ pub struct Label {
     #[live] padding: Padding,
     //margin: Margin,
     #[live] text: RcStringMut,
+    // lets the user select and copy the text
+    #[live] selectable: bool,
+    #[live] draw_selection: DrawColor,
+    #[rust] area: Area,
+    #[rust] selection: TextSelection,
 } 
 
 impl Widget for Label {
 
+    fn handle_event(&mut self, cx: &mut Cx, event: &Event, _scope: &mut Scope) {
+        if self.selectable && self.selection.handle_event(cx, event, self.area) {
+            self.draw_text.redraw(cx);
+        }
+    }
+
     fn draw_walk(&mut self, cx: &mut Cx2d, _scope: &mut Scope, walk:Walk)->DrawStep{
-        self.draw_text.draw_walk(cx, walk.with_add_padding(self.padding), self.align, self.text.as_ref());
+        if !self.selectable {
+            self.draw_text.draw_walk(cx, walk.with_add_padding(self.padding), self.align, self.text.as_ref());
+            return DrawStep::done()
+        }
+        // the turtle gives us an area to hit test the selection with
+        cx.begin_turtle(walk.with_add_padding(self.padding), Layout::default());
+        // this makes sure the selection goes behind the text
+        self.draw_selection.append_to_draw_call(cx);
+        cx.begin_text_runs();
+        let inner_walk = Walk::size(
+            if walk.width.is_fit() {Size::Fit} else {Size::Fill},
+            if walk.height.is_fit() {Size::Fit} else {Size::Fill}
+        );
+        self.draw_text.draw_walk(cx, inner_walk, self.align, self.text.as_ref());
+        let runs = cx.end_text_runs();
+        cx.end_turtle_with_area(&mut self.area);
+        self.selection.set_runs(runs, self.area.rect(cx));
+        for rect in self.selection.selection_rects() {
+            self.draw_selection.draw_abs(cx, rect);
+        }
         DrawStep::done()
     }
     
@@ -35,3 +66,25 @@ impl Widget for Label {
         self.text.as_mut_empty().push_str(v);
     }
 }
+
+impl Label {
+    pub fn selected_text(&self) -> String {
+        self.selection.selected_text()
+    }
+}
+
+impl LabelRef {
+    pub fn selected_text(&self) -> String {
+        if let Some(inner) = self.borrow(){
+            inner.selected_text()
+        } else {
+            String::new()
+        }
+    }
+    
+    pub fn copy_selection(&self, cx: &mut Cx) {
+        if let Some(inner) = self.borrow(){
+            inner.selection.copy_to_clipboard(cx);
+        }
+    }
+}
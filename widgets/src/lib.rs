@@ -39,6 +39,7 @@ pub mod window_menu;
 pub mod html;
 pub mod markdown;
 pub mod text_flow;
+pub mod text_selection;
 // Only available on Android at the moment
 // #[cfg(target_os="android")]
 pub mod video;
@@ -89,6 +90,7 @@ pub use crate::{
     slider::*,
     root::*,
     text_flow::*,
+    text_selection::TextSelection,
     html::*,
     check_box::*,
     drop_down::*,
@@ -39,6 +39,8 @@ impl Widget for Markdown {
     fn draw_walk(&mut self, cx: &mut Cx2d, _scope: &mut Scope, walk:Walk)->DrawStep{
         let tf = &mut self.text_flow;
         tf.begin(cx, walk); 
+        let mut table_columns = 0;
+        let mut in_header_cell = false;
         // alright lets walk the markdown
         for node in &self.doc.nodes{
             match node{
@@ -65,7 +67,11 @@ impl Widget for Markdown {
                 MarkdownNode::EndNormal=>{
                     
                 },
-                MarkdownNode::BeginListItem{label}=>{
+                MarkdownNode::BeginListItem{checked: Some(checked), ..}=>{
+                    cx.turtle_new_line();
+                    tf.begin_list_item(cx, if *checked{"[x]"} else {"[ ]"}, 2.5);
+                },
+                MarkdownNode::BeginListItem{label, checked: None}=>{
                     cx.turtle_new_line();
                     let str = match label{
                         MarkdownListLabel::Plus=>"+",
@@ -102,11 +108,11 @@ impl Widget for Markdown {
                 MarkdownNode::EndQuote=>{
                     tf.end_quote(cx);
                 },
-                MarkdownNode::BeginUnderline=>{
-                    tf.underline.push();
+                MarkdownNode::BeginStrikethrough=>{
+                    tf.strikethrough.push();
                 },
-                MarkdownNode::EndUnderline=>{
-                    tf.underline.pop();
+                MarkdownNode::EndStrikethrough=>{
+                    tf.strikethrough.pop();
                 },
                 MarkdownNode::BeginInlineCode=>{
                     tf.fixed.push();
@@ -116,7 +122,7 @@ impl Widget for Markdown {
                     tf.fixed.pop();
                     tf.inline_code.pop();                 
                 },
-                MarkdownNode::BeginCode=>{
+                MarkdownNode::BeginCode{..}=>{
                     cx.turtle_new_line_with_spacing(self.paragraph_spacing);
                     tf.combine_spaces.push(false);
                     tf.fixed.push();
@@ -133,6 +139,38 @@ impl Widget for Markdown {
                     tf.combine_spaces.pop();
                     tf.end_code(cx);
                 },
+                MarkdownNode::BeginTable{columns}=>{
+                    cx.turtle_new_line_with_spacing(self.paragraph_spacing);
+                    table_columns = *columns;
+                    tf.begin_table(cx);
+                },
+                MarkdownNode::EndTable=>{
+                    tf.end_table(cx);
+                },
+                MarkdownNode::BeginTableRow=>{
+                    tf.begin_table_row(cx);
+                },
+                MarkdownNode::EndTableRow=>{
+                    tf.end_table_row(cx);
+                },
+                MarkdownNode::BeginTableCell{align, header}=>{
+                    let align_x = match align{
+                        MarkdownTableAlign::Center=>0.5,
+                        MarkdownTableAlign::Right=>1.0,
+                        MarkdownTableAlign::Left | MarkdownTableAlign::None=>0.0,
+                    };
+                    tf.begin_table_cell(cx, table_columns, align_x);
+                    in_header_cell = *header;
+                    if in_header_cell{
+                        tf.bold.push();
+                    }
+                },
+                MarkdownNode::EndTableCell=>{
+                    if in_header_cell{
+                        tf.bold.pop();
+                    }
+                    tf.end_table_cell(cx);
+                },
                 MarkdownNode::BeginBold=>{
                     tf.bold.push();
                 },
@@ -180,5 +218,10 @@ impl MarkdownRef {
         let Some(mut inner) = self.borrow_mut() else { return };
         inner.set_text(v)
     }
+    
+    pub fn selected_text(&self) -> String {
+        let Some(inner) = self.borrow() else { return String::new() };
+        inner.selected_text()
+    }
 }
  
\ No newline at end of file
@@ -2,6 +2,7 @@ use crate::{
     makepad_derive_widget::*,
     makepad_draw::*,
     widget::*,
+    text_selection::TextSelection,
 }; 
     
 live_design!{
@@ -23,7 +24,8 @@ pub enum FlowBlockType {
     Code = shader_enum(3),
     InlineCode = shader_enum(4),
     Underline = shader_enum(5),
-    Strikethrough = shader_enum(6)
+    Strikethrough = shader_enum(6),
+    TableCell = shader_enum(7)
 }
 
 #[derive(Live, LiveHook, LiveRegister)]
@@ -68,11 +70,12 @@ pub struct TextFlow {
     
     #[live] draw_block: DrawFlowBlock,
     
-    #[live] font_size: f64,
+    #[live] pub font_size: f64,
     #[walk] walk: Walk,
     
     #[rust] area_stack: SmallVec<[Area;4]>,
     #[rust] pub font_sizes: SmallVec<[f64;8]>,
+    #[rust] pub font_colors: SmallVec<[Vec4;4]>,
    // #[rust] pub font: SmallVec<[Font;2]>,
     #[rust] pub top_drop: SmallVec<[f64;4]>,
     #[rust] pub combine_spaces: SmallVec<[bool;4]>,
@@ -93,8 +96,15 @@ pub struct TextFlow {
     #[live] sep_walk: Walk, 
     #[live] list_item_layout: Layout,
     #[live] list_item_walk: Walk,
+    #[live] table_walk: Walk,
+    #[live] table_cell_layout: Layout,
     #[live] inline_code_padding: Padding,
     #[live] inline_code_margin: Margin,
+    
+    // lets the user select and copy the text, across all the runs drawn into the flow
+    #[live] pub selectable: bool,
+    #[live] draw_selection: DrawColor,
+    #[rust] selection: TextSelection,
         
     #[redraw] #[rust] area:Area,
     #[rust] draw_state: DrawStateWrap<DrawState>,
@@ -163,6 +173,9 @@ impl Widget for TextFlow {
                 entry.handle_event(cx, event, scope);
             });
         }
+        if self.selectable && self.selection.handle_event(cx, event, self.area) {
+            self.area.redraw(cx);
+        }
     }
 }
 
@@ -174,6 +187,11 @@ impl TextFlow{
         cx.begin_turtle(walk, self.layout);
         self.draw_state.set(DrawState::Drawing);
         self.draw_block.append_to_draw_call(cx);
+        if self.selectable {
+            // on top of the blocks, behind the text
+            self.draw_selection.append_to_draw_call(cx);
+            cx.begin_text_runs();
+        }
         self.clear_stacks();
     }
     
@@ -186,6 +204,7 @@ impl TextFlow{
         self.inline_code.clear();
         //self.font.clear();
         self.font_sizes.clear();
+        self.font_colors.clear();
         self.area_stack.clear();
         self.top_drop.clear();
         self.combine_spaces.clear();
@@ -205,10 +224,25 @@ impl TextFlow{
     }
     
     pub fn end(&mut self, cx: &mut Cx2d){
+        let runs = if self.selectable {Some(cx.end_text_runs())} else {None};
         // lets end the turtle with how far we walked
         cx.end_turtle_with_area(&mut self.area);
+        if let Some(runs) = runs {
+            self.selection.set_runs(runs, self.area.rect(cx));
+            for rect in self.selection.selection_rects() {
+                self.draw_selection.draw_abs(cx, rect);
+            }
+        }
         self.items.retain_visible();
     } 
+    
+    pub fn selected_text(&self) -> String {
+        self.selection.selected_text()
+    }
+    
+    pub fn copy_selection(&self, cx: &mut Cx) {
+        self.selection.copy_to_clipboard(cx);
+    }
 
     pub fn begin_code(&mut self, cx:&mut Cx2d){
         // alright we are going to push a block with a layout and a walk
@@ -265,6 +299,35 @@ impl TextFlow{
         self.draw_block.end(cx);
     }
     
+    pub fn begin_table(&mut self, cx:&mut Cx2d){
+        cx.begin_turtle(self.table_walk, Layout::flow_down());
+    }
+    
+    pub fn end_table(&mut self, cx:&mut Cx2d){
+        cx.end_turtle();
+    }
+    
+    pub fn begin_table_row(&mut self, cx:&mut Cx2d){
+        cx.begin_turtle(Walk::fill_fit(), Layout::flow_right());
+    }
+    
+    pub fn end_table_row(&mut self, cx:&mut Cx2d){
+        cx.end_turtle();
+    }
+    
+    // cells share the row width equally, align_x positions the content like a layout align
+    pub fn begin_table_cell(&mut self, cx:&mut Cx2d, columns:usize, align_x:f64){
+        let width = cx.turtle().padded_rect().size.x / columns.max(1) as f64;
+        self.draw_block.block_type = FlowBlockType::TableCell;
+        self.draw_block.begin(cx, Walk::size(Size::Fixed(width), Size::Fit), self.table_cell_layout.with_align_x(align_x));
+        self.area_stack.push(self.draw_block.draw_vars.area);
+    }
+    
+    pub fn end_table_cell(&mut self, cx:&mut Cx2d){
+        self.draw_block.draw_vars.area = self.area_stack.pop().unwrap();
+        self.draw_block.end(cx);
+    }
+    
     pub fn item(&mut self, cx: &mut Cx, entry_id: LiveId, template: LiveId) -> Option<WidgetRef> {
         if let Some(ptr) = self.templates.get(&template) {
             let entry = self.items.get_or_insert(cx, (entry_id, template), | cx | {
@@ -316,6 +379,11 @@ impl TextFlow{
             dt.text_style.font_size = *font_size;
             dt.ignore_newlines = *self.ignore_newlines.last().unwrap_or(&true);
             dt.combine_spaces = *self.combine_spaces.last().unwrap_or(&true);
+            // a pushed color overrides the one of the draw_text for this word
+            let old_color = dt.color;
+            if let Some(color) = self.font_colors.last(){
+                dt.color = *color;
+            }
             //if let Some(font) = self.font
             // the turtle is at pos X so we walk it.
             if self.inline_code.value() > 0{
@@ -354,6 +422,7 @@ impl TextFlow{
             else{
                 dt.draw_walk_word(cx, text);
             }
+            dt.color = old_color;
         }
     }
 }
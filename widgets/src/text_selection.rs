@@ -0,0 +1,426 @@
+use {
+    std::ops::Range,
+    crate::makepad_draw::*,
+};
+
+// Selection over text that a widget drew as TextRuns, possibly through several DrawTexts.
+// Offsets are byte offsets into the text of all runs joined up, with a newline between runs
+// on different lines and a space between runs that have a gap between them.
+#[derive(Default)]
+pub struct TextSelection {
+    runs: Vec<TextRun>,
+    // the offset of each run in `text`
+    starts: Vec<usize>,
+    // the line of each run, and the top and bottom of each line
+    run_lines: Vec<usize>,
+    lines: Vec<(f64, f64)>,
+    text: String,
+    // the position of the hit area when the runs were drawn, alignment can move it later
+    origin: DVec2,
+    head: usize,
+    tail: usize,
+    // the word or line a double or triple press selected, a drag extends it by whole ones
+    drag_start: Option<(usize, usize)>,
+}
+
+#[derive(PartialEq)]
+enum CharClass {
+    Space,
+    Word,
+    Other,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_whitespace() {
+        CharClass::Space
+    }
+    else if c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    }
+    else {
+        CharClass::Other
+    }
+}
+
+// Whether a cluster runs right to left, from the logical order of its neighbours.
+fn cluster_is_rtl(clusters: &[(Range<usize>, f64, f64)], index: usize) -> bool {
+    if let Some(next) = clusters.get(index + 1) {
+        next.0.start < clusters[index].0.start
+    }
+    else if index > 0 {
+        clusters[index - 1].0.start > clusters[index].0.start
+    }
+    else {
+        false
+    }
+}
+
+impl TextSelection {
+    // Takes the runs of the last draw, `rect` is the hit area of the widget as it was drawn.
+    pub fn set_runs(&mut self, runs: Vec<TextRun>, rect: Rect) {
+        self.text.clear();
+        self.starts.clear();
+        self.run_lines.clear();
+        self.lines.clear();
+        let mut last: Option<&TextRun> = None;
+        for run in &runs {
+            if let Some(last) = last {
+                if run.rect.pos.y >= last.rect.pos.y + last.rect.size.y * 0.5 {
+                    self.text.push('\n');
+                    self.lines.push((run.rect.pos.y, run.rect.pos.y + run.rect.size.y));
+                }
+                else if run.rect.pos.x > last.rect.pos.x + last.rect.size.x + 1.0
+                    && !last.text.ends_with(char::is_whitespace)
+                    && !run.text.starts_with(char::is_whitespace) {
+                    self.text.push(' ');
+                }
+            }
+            else {
+                self.lines.push((run.rect.pos.y, run.rect.pos.y + run.rect.size.y));
+            }
+            let line = self.lines.last_mut().unwrap();
+            line.0 = line.0.min(run.rect.pos.y);
+            line.1 = line.1.max(run.rect.pos.y + run.rect.size.y);
+            self.starts.push(self.text.len());
+            self.run_lines.push(self.lines.len() - 1);
+            self.text.push_str(&run.text);
+            last = Some(run);
+        }
+        self.runs = runs;
+        self.origin = rect.pos;
+        self.head = self.clamp(self.head);
+        self.tail = self.clamp(self.tail);
+    }
+
+    fn clamp(&self, offset: usize) -> usize {
+        let mut offset = offset.min(self.text.len());
+        while !self.text.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        offset
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn has_selection(&self) -> bool {
+        self.head != self.tail
+    }
+
+    pub fn selection(&self) -> Range<usize> {
+        self.head.min(self.tail)..self.head.max(self.tail)
+    }
+
+    pub fn selected_text(&self) -> String {
+        self.text[self.selection()].to_string()
+    }
+
+    pub fn set_selection(&mut self, range: Range<usize>) {
+        self.tail = self.clamp(range.start);
+        self.head = self.clamp(range.end);
+    }
+
+    pub fn select_all(&mut self) {
+        self.tail = 0;
+        self.head = self.text.len();
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.tail = self.head;
+    }
+
+    pub fn copy_to_clipboard(&self, cx: &mut Cx) {
+        if self.has_selection() {
+            cx.copy_to_clipboard(&self.selected_text());
+        }
+    }
+
+    // Selects the word, the run of spaces or the run of punctuation around an offset.
+    pub fn select_word(&mut self, offset: usize) {
+        let offset = self.clamp(offset);
+        let class = match self.text[offset..].chars().next().or_else( | | self.text[..offset].chars().next_back()) {
+            Some(c) => char_class(c),
+            None => return
+        };
+        let same = | c: char | char_class(c) == class;
+        let start = self.text[..offset].char_indices().rev()
+            .take_while( | (_, c) | same(*c))
+            .last()
+            .map_or(offset, | (i, _) | i);
+        let end = self.text[offset..].char_indices()
+            .find( | (_, c) | !same(*c))
+            .map_or(self.text.len(), | (i, _) | offset + i);
+        self.tail = start;
+        self.head = end;
+    }
+
+    // Selects the line around an offset, without the newline that ends it.
+    pub fn select_line(&mut self, offset: usize) {
+        let offset = self.clamp(offset);
+        self.tail = self.text[..offset].rfind('\n').map_or(0, | i | i + 1);
+        self.head = self.text[offset..].find('\n').map_or(self.text.len(), | i | offset + i);
+    }
+
+    // A press at an offset. A double press selects the word there and a triple press the line,
+    // `extend` moves only the head like shift clicking does.
+    pub fn press(&mut self, offset: usize, tap_count: u32, extend: bool) {
+        let offset = self.clamp(offset);
+        match tap_count {
+            1 => {
+                self.head = offset;
+                if !extend {
+                    self.tail = offset;
+                }
+            }
+            2 => self.select_word(offset),
+            _ => self.select_line(offset)
+        }
+        self.drag_start = (tap_count > 1).then_some((self.tail, self.head));
+    }
+
+    // Drags the head to an offset, by whole words or lines after a double or triple press.
+    pub fn drag(&mut self, offset: usize, tap_count: u32) {
+        let offset = self.clamp(offset);
+        match self.drag_start {
+            Some((start, end)) if tap_count > 1 => {
+                if tap_count == 2 {
+                    self.select_word(offset);
+                }
+                else {
+                    self.select_line(offset);
+                }
+                // the head goes to the far end, so the pressed word or line stays selected
+                if offset < start {
+                    self.head = self.tail;
+                    self.tail = end;
+                }
+                else {
+                    self.tail = start;
+                    self.head = self.head.max(end);
+                }
+            }
+            _ if tap_count == 1 => self.head = offset,
+            _ => ()
+        }
+    }
+
+    // The byte offset closest to a position in the coordinates the runs were drawn in.
+    pub fn offset_at(&self, pos: DVec2) -> usize {
+        if self.lines.is_empty() {
+            return 0
+        }
+        let line = self.lines.iter().position( | (_, bottom) | pos.y < *bottom).unwrap_or(self.lines.len() - 1);
+        // the run under the position, or the closest one on the line
+        let mut best: Option<(usize, f64)> = None;
+        for (index, run) in self.runs.iter().enumerate() {
+            if self.run_lines[index] != line {
+                continue;
+            }
+            let distance = if pos.x < run.rect.pos.x {
+                run.rect.pos.x - pos.x
+            }
+            else {
+                (pos.x - run.rect.pos.x - run.rect.size.x).max(0.0)
+            };
+            if best.is_none_or( | (_, best) | distance < best) {
+                best = Some((index, distance));
+            }
+        }
+        let index = if let Some((index, _)) = best {index} else {return 0};
+        let run = &self.runs[index];
+        let x = pos.x - run.rect.pos.x;
+        for (i, (range, cluster_x, width)) in run.clusters.iter().enumerate() {
+            if x < cluster_x + width || i == run.clusters.len() - 1 {
+                let before = (x < cluster_x + width * 0.5) != cluster_is_rtl(&run.clusters, i);
+                return self.starts[index] + if before {range.start} else {range.end}
+            }
+        }
+        self.starts[index]
+    }
+
+    // The rects covering a byte range, in the coordinates the runs were drawn in.
+    pub fn range_rects(&self, range: Range<usize>) -> Vec<Rect> {
+        let mut rects: Vec<(usize, Rect)> = Vec::new();
+        for (index, run) in self.runs.iter().enumerate() {
+            let start = self.starts[index];
+            let mut min_x = f64::INFINITY;
+            let mut max_x = f64::NEG_INFINITY;
+            for (cluster, x, width) in &run.clusters {
+                if start + cluster.start < range.end && start + cluster.end > range.start {
+                    min_x = min_x.min(*x);
+                    max_x = max_x.max(x + width);
+                }
+            }
+            if min_x > max_x {
+                continue;
+            }
+            let line = self.run_lines[index];
+            let (top, bottom) = self.lines[line];
+            let rect = Rect {
+                pos: dvec2(run.rect.pos.x + min_x, top),
+                size: dvec2(max_x - min_x, bottom - top)
+            };
+            if let Some((last_line, last)) = rects.last_mut() {
+                if *last_line == line && (rect.pos.x - last.pos.x - last.size.x).abs() < 1.0 {
+                    last.size.x = rect.pos.x + rect.size.x - last.pos.x;
+                    continue;
+                }
+            }
+            rects.push((line, rect));
+        }
+        rects.into_iter().map( | (_, rect) | rect).collect()
+    }
+
+    pub fn selection_rects(&self) -> Vec<Rect> {
+        if !self.has_selection() {
+            return Vec::new()
+        }
+        self.range_rects(self.selection())
+    }
+
+    // Drag, double click word and triple click line selection on `area`, and copying of the
+    // selection. Returns true when the selection changed and the widget should redraw.
+    pub fn handle_event(&mut self, cx: &mut Cx, event: &Event, area: Area) -> bool {
+        // positions as if the area was still where it was drawn
+        let shift = area.rect(cx).pos - self.origin;
+        match event.hits(cx, area) {
+            Hit::FingerHoverIn(_) => {
+                cx.set_cursor(MouseCursor::Text);
+            }
+            Hit::FingerDown(fe) => {
+                cx.set_cursor(MouseCursor::Text);
+                cx.set_key_focus(area);
+                self.press(self.offset_at(fe.abs - shift), fe.tap_count, fe.modifiers.shift);
+                return true
+            }
+            Hit::FingerMove(fe) => {
+                self.drag(self.offset_at(fe.abs - shift), fe.tap_count);
+                return true
+            }
+            Hit::FingerUp(fe) => {
+                self.drag_start = None;
+                if fe.was_long_press() && self.has_selection() {
+                    cx.show_clipboard_actions(self.selected_text());
+                }
+            }
+            Hit::TextCopy(ce) => {
+                *ce.response.borrow_mut() = Some(self.selected_text());
+            }
+            Hit::KeyDown(ke) => match ke.key_code {
+                KeyCode::KeyA if ke.modifiers.logo || ke.modifiers.control => {
+                    self.select_all();
+                    return true
+                }
+                _ => ()
+            }
+            Hit::KeyFocusLost(_) if self.has_selection() => {
+                self.clear_selection();
+                return true
+            }
+            _ => ()
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a run of 10 wide clusters, one per char
+    fn run(text: &str, x: f64, y: f64) -> TextRun {
+        let clusters: Vec<_> = text.char_indices().enumerate()
+            .map( | (i, (start, c)) | (start..start + c.len_utf8(), i as f64 * 10.0, 10.0))
+            .collect();
+        TextRun {
+            text: text.to_string(),
+            rect: Rect {pos: dvec2(x, y), size: dvec2(clusters.len() as f64 * 10.0, 20.0)},
+            clusters,
+        }
+    }
+
+    // "foo_bar baz, q\u{fc}x" on the first line, "second line" and "end" after a gap on the second
+    fn selection() -> TextSelection {
+        let mut selection = TextSelection::default();
+        let runs = vec![run("foo_bar baz, q\u{fc}x", 0.0, 0.0), run("second line", 0.0, 20.0), run("end", 200.0, 20.0)];
+        selection.set_runs(runs, Rect::default());
+        selection
+    }
+
+    #[test]
+    fn joins_runs_by_line() {
+        let selection = selection();
+        assert_eq!(selection.text(), "foo_bar baz, q\u{fc}x\nsecond line end");
+        assert_eq!(selection.offset_at(dvec2(12.0, 5.0)), 1);
+        assert_eq!(selection.offset_at(dvec2(-50.0, 25.0)), 18);
+        assert_eq!(selection.offset_at(dvec2(203.0, 25.0)), 30);
+        assert_eq!(selection.offset_at(dvec2(500.0, 500.0)), 33);
+    }
+
+    #[test]
+    fn selects_words() {
+        let mut selection = selection();
+        let mut word = | offset | {
+            selection.select_word(offset);
+            selection.selected_text()
+        };
+        // underscores are part of a word
+        assert_eq!(word(0), "foo_bar");
+        assert_eq!(word(5), "foo_bar");
+        // the char after the offset decides, so the end of a word is the space after it
+        assert_eq!(word(7), " ");
+        assert_eq!(word(11), ",");
+        // an offset inside a char moves to its start
+        assert_eq!(word(15), "q\u{fc}x");
+        assert_eq!(word(17), "\n");
+        assert_eq!(word(18), "second");
+        // at the end it is the word before
+        assert_eq!(word(33), "end");
+        let mut empty = TextSelection::default();
+        empty.select_word(0);
+        assert!(!empty.has_selection());
+    }
+
+    #[test]
+    fn triple_press_selects_lines() {
+        let mut selection = selection();
+        selection.press(3, 3, false);
+        assert_eq!(selection.selection(), 0..17);
+        selection.press(17, 3, false);
+        assert_eq!(selection.selected_text(), "foo_bar baz, q\u{fc}x");
+        selection.press(31, 3, false);
+        assert_eq!(selection.selected_text(), "second line end");
+        // a drag takes whole lines
+        selection.press(3, 3, false);
+        selection.drag(20, 3);
+        assert_eq!(selection.selection(), 0..33);
+    }
+
+    #[test]
+    fn drags_backwards() {
+        let mut selection = selection();
+        selection.press(10, 1, false);
+        selection.drag(2, 1);
+        assert_eq!(selection.selection(), 2..10);
+        assert_eq!(selection.selected_text(), "o_bar ba");
+        // shift extends from the same tail
+        selection.press(12, 1, true);
+        assert_eq!(selection.selected_text(), "z,");
+
+        // words, back over the pressed one and forward again
+        selection.press(9, 2, false);
+        assert_eq!(selection.selected_text(), "baz");
+        selection.drag(2, 2);
+        assert_eq!(selection.selected_text(), "foo_bar baz");
+        assert_eq!((selection.tail, selection.head), (11, 0));
+        selection.drag(15, 2);
+        assert_eq!(selection.selected_text(), "baz, q\u{fc}x");
+        assert_eq!((selection.tail, selection.head), (8, 17));
+
+        // lines backwards from the second one
+        selection.press(25, 3, false);
+        selection.drag(1, 3);
+        assert_eq!((selection.tail, selection.head), (33, 0));
+    }
+}
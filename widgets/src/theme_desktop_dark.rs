@@ -197,6 +197,7 @@ live_design! {
             text_style: <THEME_FONT_REGULAR> {},
             wrap: Word
         }
+        draw_selection: { color: (THEME_COLOR_BG_HIGHLIGHT_INLINE) }
     }
 
     H1 = <Label> {
@@ -500,6 +501,7 @@ live_design! {
 
         line_spacing: (THEME_FONT_LINE_SPACING),
         font_size: (THEME_FONT_SIZE_P),
+        draw_selection: { color: (THEME_COLOR_BG_HIGHLIGHT_INLINE) }
 
         draw_normal: {
             text_style: <THEME_FONT_REGULAR> {
@@ -556,6 +558,12 @@ live_design! {
             height: Fit, width: Fill,
         }
 
+        table_walk: { width: Fill, height: Fit }
+        table_cell_layout: {
+            flow: RightWrap,
+            padding: <THEME_MSPACE_1> { left: (THEME_SPACE_2), right: (THEME_SPACE_2) }
+        }
+
         inline_code_padding: <THEME_MSPACE_1> {},
         inline_code_margin: <THEME_MSPACE_1> {},
 
@@ -649,6 +657,16 @@ live_design! {
                         sdf.fill(self.line_color);
                         return sdf.result;
                     }
+                    FlowBlockType::TableCell => {
+                        sdf.rect(
+                            0.,
+                            0.,
+                            self.rect_size.x,
+                            self.rect_size.y
+                        );
+                        sdf.stroke(self.sep_color, 1.);
+                        return sdf.result;
+                    }
                 }
                 return #f00
             }
@@ -663,6 +681,7 @@ live_design! {
         line_spacing: (THEME_FONT_LINE_SPACING),
         font_size: (THEME_FONT_SIZE_P),
         paragraph_spacing: 16,
+        draw_selection: { color: (THEME_COLOR_BG_HIGHLIGHT_INLINE) }
 
         draw_normal: {
             text_style: <THEME_FONT_REGULAR> {
@@ -719,6 +738,12 @@ live_design! {
             height: Fit, width: Fill,
         }
 
+        table_walk: { width: Fill, height: Fit }
+        table_cell_layout: {
+            flow: RightWrap,
+            padding: <THEME_MSPACE_1> { left: (THEME_SPACE_2), right: (THEME_SPACE_2) }
+        }
+
         sep_walk: {
             width: Fill, height: 4.
             margin: <THEME_MSPACE_V_3> {}
@@ -808,6 +833,16 @@ live_design! {
                         sdf.fill(self.line_color);
                         return sdf.result;
                     }
+                    FlowBlockType::TableCell => {
+                        sdf.rect(
+                            0.,
+                            0.,
+                            self.rect_size.x,
+                            self.rect_size.y
+                        );
+                        sdf.stroke(self.sep_color, 1.);
+                        return sdf.result;
+                    }
                 }
                 return #f00
             }